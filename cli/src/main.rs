@@ -0,0 +1,5485 @@
+use s4pi_reforged::{Package, TgiFilter, ThumbnailKind, TGI, TypedResource};
+use s4pi_reforged::report::{hexdump, FailureContext, FailureReport};
+use s4pi_reforged::provenance::{Provenance, PROVENANCE_TYPE};
+use s4pi_reforged::merge::MergeOptions;
+use s4pi_reforged::observer::{LogObserver, OperationObserver};
+use s4pi_reforged::conflict::{Conflict, ConflictPolicy};
+use s4pi_reforged::stbl::{locale_of, with_locale, Locale};
+use s4pi_reforged::perf::{self, PerfTimer};
+use s4pi_reforged::package::stats::{MergeStats, MergeTypeStats};
+use rfd::FileDialog;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use anyhow::{Result, Context, anyhow};
+use log::{info, error, warn};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use rayon::prelude::*;
+
+#[cfg(windows)]
+fn prepare_console() {
+    extern "system" {
+        fn AllocConsole() -> i32;
+        fn GetConsoleWindow() -> isize;
+    }
+    unsafe {
+        if GetConsoleWindow() == 0 {
+            let _ = AllocConsole();
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn prepare_console() {
+    if !atty::is(atty::Stream::Stdout) && std::env::var("S4PI_TERMINAL_RELAUNCH").is_err() {
+        let exe = std::env::current_exe().expect("Failed to get current exe path");
+        let mut command = None;
+
+        // Try common terminal emulators
+        for term in &["x-terminal-emulator", "gnome-terminal", "konsole", "xfce4-terminal", "xterm"] {
+            if which::which(term).is_ok() {
+                let mut cmd = std::process::Command::new(term);
+                match *term {
+                    "gnome-terminal" => {
+                        cmd.arg("--").arg(&exe);
+                    }
+                    "konsole" => {
+                        cmd.arg("-e").arg(&exe);
+                    }
+                    "xfce4-terminal" => {
+                        cmd.arg("-e").arg(&exe);
+                    }
+                    _ => {
+                        cmd.arg("-e").arg(&exe);
+                    }
+                }
+                command = Some(cmd);
+                break;
+            }
+        }
+
+        if let Some(mut cmd) = command {
+            cmd.env("S4PI_TERMINAL_RELAUNCH", "1");
+            if cmd.spawn().is_ok() {
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+fn is_debug_mode() -> bool {
+    std::env::var("S4PI_DEBUG_MODE").map(|v| v == "1").unwrap_or(false)
+}
+
+struct GuiApp {
+    log_buffer: Arc<Mutex<String>>,
+    browser: Option<ResourceBrowser>,
+    /// Filled in by the background scan when a folder picked for merging has conflicts
+    pending_conflicts: Arc<Mutex<Option<ConflictPicker>>>,
+    conflicts: Option<ConflictPicker>,
+    /// Merge in background mode (fewer, low-priority threads and rate-limited reads)
+    background: bool,
+    /// The landing screen, shown on first run and from the "Guide" button
+    wizard: Option<Wizard>,
+    settings: GuiSettings,
+    progress: Arc<GuiProgress>,
+}
+
+impl GuiApp {
+    fn new(cc: &eframe::CreationContext<'_>, log_buffer: Arc<Mutex<String>>) -> Self {
+        let wizard = (!wizard_seen()).then(Wizard::new);
+        let settings = GuiSettings::load();
+        settings.apply(&cc.egui_ctx);
+        // The scale shortcuts step through the View menu's scales instead
+        cc.egui_ctx.options_mut(|options| options.zoom_with_keyboard = false);
+        Self {
+            log_buffer, browser: None, pending_conflicts: Arc::new(Mutex::new(None)), conflicts: None, background: false, wizard, settings,
+            progress: Arc::default(),
+        }
+    }
+
+    /// Lists the conflicts between the packages in `folder`, then offers to merge with the
+    /// user's picks
+    fn start_scan(&self, folder: PathBuf) {
+        let pending = Arc::clone(&self.pending_conflicts);
+        let options = MergeOptions { background: self.background, ..MergeOptions::default() };
+        std::thread::spawn(move || {
+            info!("Scanning {:?} for conflicts...", folder);
+            let conflicts = s4pi_reforged::merge::scan_conflicts(&folder);
+            if conflicts.is_empty() {
+                info!("No conflicts: no resource is in more than one package.");
+                return;
+            }
+            info!("{} resource(s) are in more than one package:", conflicts.len());
+            const MAX_LISTED: usize = 50;
+            for conflict in conflicts.iter().take(MAX_LISTED) {
+                info!("  {} in {}", conflict.tgi, conflict.packages.join(", "));
+            }
+            if conflicts.len() > MAX_LISTED {
+                info!("  ... {} more", conflicts.len() - MAX_LISTED);
+            }
+            *pending.lock().unwrap() = Some(ConflictPicker::new(folder, conflicts, options));
+        });
+    }
+
+    /// Merges `folder`, asking the user to settle any conflicts first
+    fn start_merge(&self, folder: PathBuf, options: MergeOptions) {
+        let log_buffer = Arc::clone(&self.log_buffer);
+        let progress = Arc::clone(&self.progress);
+        let pending = Arc::clone(&self.pending_conflicts);
+        let options = MergeOptions { background: self.background, ..options };
+        std::thread::spawn(move || {
+            let conflicts = s4pi_reforged::merge::scan_conflicts(&folder);
+            if conflicts.is_empty() {
+                spawn_merge(folder, options, &log_buffer, &progress);
+            } else {
+                *pending.lock().unwrap() = Some(ConflictPicker::new(folder, conflicts, options));
+            }
+        });
+    }
+
+    fn open_browser(&mut self, path: &Path) {
+        match ResourceBrowser::open(path) {
+            Ok(browser) => self.browser = Some(browser),
+            Err(e) => error!("Failed to open {:?}: {:#}", path, e),
+        }
+    }
+}
+
+/// The operation the GUI is running, shown as a progress bar above the console. Warnings and
+/// skipped items go to the console through the log, as on the command line.
+#[derive(Default)]
+struct GuiProgress {
+    /// The operation, how many items are done and how many there are
+    current: Mutex<Option<(String, usize, Option<usize>)>>,
+}
+
+impl OperationObserver for GuiProgress {
+    fn on_start(&self, operation: &str, total: Option<usize>) {
+        *self.current.lock().unwrap() = Some((operation.to_string(), 0, total));
+    }
+
+    fn on_progress(&self, done: usize, _total: Option<usize>, _item: &str) {
+        if let Some((_, current, _)) = self.current.lock().unwrap().as_mut() {
+            *current = done.max(*current);
+        }
+    }
+
+    fn on_item_skipped(&self, item: &str, reason: &str) {
+        LogObserver::default().on_item_skipped(item, reason);
+    }
+
+    fn on_warning(&self, message: &str) {
+        LogObserver::default().on_warning(message);
+    }
+
+    fn on_finish(&self, _operation: &str, _success: bool) {
+        *self.current.lock().unwrap() = None;
+    }
+}
+
+/// Runs a merge on a background thread, adding any error to the GUI log
+fn spawn_merge(folder: PathBuf, options: MergeOptions, log_buffer: &Arc<Mutex<String>>, progress: &Arc<GuiProgress>) {
+    let log_arc = Arc::clone(log_buffer);
+    let progress = Arc::clone(progress);
+    std::thread::spawn(move || {
+        if let Err(e) = run_merge(&folder, &options, false, progress.as_ref()) {
+            let mut log = log_arc.lock().unwrap();
+            log.push_str(&format!("Error during merge: {:?}\n", e));
+        }
+    });
+}
+
+/// Runs an un-merge on a background thread, adding any error to the GUI log
+fn spawn_unmerge(path: PathBuf, log_buffer: &Arc<Mutex<String>>, progress: &Arc<GuiProgress>) {
+    let log_arc = Arc::clone(log_buffer);
+    let progress = Arc::clone(progress);
+    std::thread::spawn(move || {
+        if let Err(e) = run_unmerge(&path, false, progress.as_ref()) {
+            let mut log = log_arc.lock().unwrap();
+            log.push_str(&format!("Error during un-merge: {:?}\n", e));
+        }
+    });
+}
+
+enum PickerOutcome {
+    Open,
+    Merge(MergeOptions),
+    Cancel,
+}
+
+/// Lets the user pick which package wins each resource that several packages in a merge contain
+struct ConflictPicker {
+    folder: PathBuf,
+    conflicts: Vec<Conflict>,
+    /// Index into each conflict's packages of the chosen one
+    choices: Vec<usize>,
+    /// The rest of the merge settings, used as they are
+    options: MergeOptions,
+    /// Whether the first choice should take keyboard focus, when the picker first opens
+    focus_first: bool,
+}
+
+impl ConflictPicker {
+    fn new(folder: PathBuf, conflicts: Vec<Conflict>, options: MergeOptions) -> Self {
+        // Start from what the game would load: the last package in sort order
+        let choices = conflicts.iter().map(|c| c.packages.len().saturating_sub(1)).collect();
+        Self { folder, conflicts, choices, options, focus_first: true }
+    }
+
+    fn show(&mut self, ctx: &egui::Context) -> PickerOutcome {
+        let mut outcome = PickerOutcome::Open;
+        // Taken here so it doesn't also close the resource browser underneath
+        if ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            return PickerOutcome::Cancel;
+        }
+        egui::Window::new("Resolve conflicts").collapsible(false).default_width(640.0).show(ctx, |ui| {
+            ui.label(format!("{} resource(s) are in more than one package. Pick the copy to keep for each:", self.conflicts.len()));
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                for (i, conflict) in self.conflicts.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.monospace(conflict.tgi.to_string());
+                        let combo = egui::ComboBox::from_id_source(i)
+                            .width(360.0)
+                            .selected_text(&conflict.packages[self.choices[i]])
+                            .show_ui(ui, |ui| {
+                                for (j, package) in conflict.packages.iter().enumerate() {
+                                    ui.selectable_value(&mut self.choices[i], j, package);
+                                }
+                            });
+                        if i == 0 && std::mem::take(&mut self.focus_first) {
+                            combo.response.request_focus();
+                        }
+                    });
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Merge").clicked() {
+                    let choices = self.conflicts.iter().zip(&self.choices)
+                        .map(|(c, &j)| (c.tgi, c.packages[j].clone()))
+                        .collect();
+                    outcome = PickerOutcome::Merge(MergeOptions { conflicts: ConflictPolicy::Choose(choices), ..self.options.clone() });
+                }
+                if ui.button("Cancel").on_hover_text("Esc").clicked() {
+                    outcome = PickerOutcome::Cancel;
+                }
+            });
+        });
+        outcome
+    }
+}
+
+/// Lists the resources of one package and shows the `info` view of the selected one
+struct ResourceBrowser {
+    path: PathBuf,
+    pkg: Package,
+    filter: String,
+    selected: Option<usize>,
+    detail: String,
+    /// The packages a merged package was made from, from its manifest
+    merged: Vec<s4pi_reforged::package::resource::ManifestEntry>,
+    /// The merged package whose resources are shown, and their keys
+    subpackage: Option<(usize, HashSet<TGI>)>,
+    /// Where keyboard focus was last frame: on the filter box or on a row of the list
+    filter_focused: bool,
+    list_focused: bool,
+    /// Moves keyboard focus to the filter box, or to the selected row (scrolling it into view)
+    /// once it's drawn
+    focus_filter: bool,
+    focus_selected: bool,
+    /// The rows of the filtered list drawn last frame
+    shown_rows: std::ops::Range<usize>,
+}
+
+impl ResourceBrowser {
+    fn open(path: &Path) -> Result<Self> {
+        let pkg = Package::open(path)?;
+        info!("Opened {:?} ({} resources)", path, pkg.entries.len());
+        let merged = match s4pi_reforged::manifest::find_manifest(&pkg) {
+            Some(_) => match s4pi_reforged::manifest::read_manifest(&pkg) {
+                Ok((_, manifest)) => manifest.entries,
+                Err(e) => {
+                    warn!("Failed to read the manifest of {:?}: {:#}", path, e);
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            pkg,
+            filter: String::new(),
+            selected: None,
+            detail: String::new(),
+            merged,
+            subpackage: None,
+            filter_focused: false,
+            list_focused: false,
+            focus_filter: true,
+            focus_selected: false,
+            shown_rows: 0..0,
+        })
+    }
+
+    /// Shows only the resources of the `index`th merged package, or all of them with `None`
+    fn show_subpackage(&mut self, index: Option<usize>) {
+        self.subpackage = index.map(|i| (i, self.merged[i].resources.iter().copied().collect()));
+    }
+
+    /// The list of merged packages, for a package with a manifest
+    fn show_merged(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        let current = self.subpackage.as_ref().map(|(i, _)| *i);
+        egui::CollapsingHeader::new(format!("Merged packages ({})", self.merged.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical().id_source("merged").max_height(200.0).show(ui, |ui| {
+                    if ui.selectable_label(current.is_none(), "All resources").clicked() {
+                        clicked = Some(None);
+                    }
+                    for (i, entry) in self.merged.iter().enumerate() {
+                        let text = format!("{}  ({})", entry.name, entry.resources.len());
+                        if ui.selectable_label(current == Some(i), text).clicked() {
+                            clicked = Some(Some(i));
+                        }
+                    }
+                });
+            });
+        if let Some(index) = clicked {
+            self.show_subpackage(index);
+        }
+    }
+
+    fn label(entry: &s4pi_reforged::IndexEntry) -> String {
+        format!("{}  {}", entry.tgi, kind_label(entry.tgi.res_type))
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = Some(index);
+        let entry = self.pkg.entries[index].clone();
+        self.detail = describe_resource(&self.pkg, &entry, false)
+            .unwrap_or_else(|e| format!("Failed to read {}: {:#}", entry.tgi, e));
+    }
+
+    /// Where the arrow, Page Up/Down, Home and End keys move the selection to in `visible`, when
+    /// the list has keyboard focus
+    fn navigate(&self, ui: &egui::Ui, visible: &[usize], page: usize) -> Option<usize> {
+        use egui::{Key, Modifiers};
+        let last = visible.len().checked_sub(1)?;
+        let current = self.selected.and_then(|selected| visible.iter().position(|&i| i == selected));
+        ui.ctx().input_mut(|input| {
+            let mut key = |key| input.consume_key(Modifiers::NONE, key);
+            Some(if key(Key::ArrowDown) {
+                current.map_or(0, |pos| (pos + 1).min(last))
+            } else if key(Key::ArrowUp) {
+                current.map_or(0, |pos| pos.saturating_sub(1))
+            } else if key(Key::PageDown) {
+                current.map_or(0, |pos| (pos + page).min(last))
+            } else if key(Key::PageUp) {
+                current.map_or(0, |pos| pos.saturating_sub(page))
+            } else if key(Key::Home) {
+                0
+            } else if key(Key::End) {
+                last
+            } else {
+                return None;
+            })
+        })
+    }
+
+    /// Draws the browser; returns false once the user closes it
+    fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        // Escape in the filter box only leaves it
+        if !self.filter_focused && ctx.input(|input| input.key_pressed(egui::Key::Escape)) {
+            open = false;
+        }
+        if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+            self.focus_filter = true;
+        }
+        egui::SidePanel::left("resources").resizable(true).default_width(420.0).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(self.path.file_name().unwrap_or_default().to_string_lossy());
+                if ui.button("Close").clicked() {
+                    open = false;
+                }
+            });
+            if !self.merged.is_empty() {
+                self.show_merged(ui);
+            }
+            let filter_response = ui.horizontal(|ui| {
+                ui.label("Filter:");
+                ui.text_edit_singleline(&mut self.filter).on_hover_text("Ctrl+F; Enter or Down moves to the list")
+            }).inner;
+            if std::mem::take(&mut self.focus_filter) {
+                filter_response.request_focus();
+            }
+            self.filter_focused = filter_response.has_focus();
+            let to_list = filter_response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+            let filter = self.filter.to_lowercase();
+            let in_subpackage = |tgi: &TGI| self.subpackage.as_ref().is_none_or(|(_, tgis)| tgis.contains(tgi));
+            let visible: Vec<usize> = (0..self.pkg.entries.len())
+                .filter(|&i| in_subpackage(&self.pkg.entries[i].tgi))
+                .filter(|&i| filter.is_empty() || Self::label(&self.pkg.entries[i]).to_lowercase().contains(&filter))
+                .collect();
+            match &self.subpackage {
+                Some((i, _)) => ui.label(format!("{} of {} resources in {}", visible.len(), self.pkg.entries.len(), self.merged[*i].name)),
+                None => ui.label(format!("{} of {} resources", visible.len(), self.pkg.entries.len())),
+            };
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            let page = (ui.available_height() / row_height) as usize;
+            let target = if to_list {
+                (!visible.is_empty()).then_some(0)
+            } else if self.list_focused {
+                self.navigate(ui, &visible, page.max(1))
+            } else {
+                None
+            };
+            let mut scroll = egui::ScrollArea::vertical().auto_shrink([false, false]);
+            if let Some(pos) = target {
+                self.select(visible[pos]);
+                self.focus_selected = true;
+                // A row that wasn't drawn can't scroll itself into view
+                if !self.shown_rows.contains(&pos) {
+                    scroll = scroll.vertical_scroll_offset(pos as f32 * (row_height + ui.spacing().item_spacing.y));
+                }
+            }
+
+            let mut clicked = None;
+            let mut list_focused = false;
+            scroll.show_rows(ui, row_height, visible.len(), |ui, rows| {
+                self.shown_rows = rows.clone();
+                for &i in &visible[rows] {
+                    let text = egui::RichText::new(Self::label(&self.pkg.entries[i])).monospace();
+                    let response = ui.selectable_label(self.selected == Some(i), text);
+                    if self.selected == Some(i) && std::mem::take(&mut self.focus_selected) {
+                        response.request_focus();
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() {
+                        clicked = Some(i);
+                        response.request_focus();
+                    }
+                    if response.has_focus() {
+                        // The arrow keys move the selection rather than focus
+                        list_focused = true;
+                        ui.memory_mut(|memory| memory.set_focus_lock_filter(response.id, egui::EventFilter {
+                            vertical_arrows: true,
+                            ..Default::default()
+                        }));
+                    }
+                }
+            });
+            self.list_focused = list_focused;
+            if let Some(i) = clicked {
+                self.select(i);
+            }
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+                if self.selected.is_none() {
+                    ui.label("Select a resource to inspect it.");
+                } else {
+                    ui.add(egui::Label::new(egui::RichText::new(&self.detail).monospace()).extend());
+                }
+            });
+        });
+        open
+    }
+}
+
+/// The common jobs the landing screen walks through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WizardTask {
+    Merge,
+    Unmerge,
+    Conflicts,
+}
+
+impl WizardTask {
+    fn title(self) -> &'static str {
+        match self {
+            WizardTask::Merge => "Merge my Mods folder",
+            WizardTask::Unmerge => "Un-merge a merged package",
+            WizardTask::Conflicts => "Scan for conflicts",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            WizardTask::Merge => "Combine every package in your Mods folder into one, so the game loads faster. The originals are left alone.",
+            WizardTask::Unmerge => "Split a package made by Merge back into the packages it was made from.",
+            WizardTask::Conflicts => "List the resources that more than one package replaces, and pick which copy to keep.",
+        }
+    }
+}
+
+enum WizardOutcome {
+    Open,
+    Merge(PathBuf, MergeOptions),
+    Unmerge(PathBuf),
+    Scan(PathBuf),
+    Close,
+}
+
+/// The landing screen: walks new users through the common jobs, with the Mods folder (and the
+/// package an earlier merge wrote into it) found automatically and the recommended settings
+/// picked
+struct Wizard {
+    task: Option<WizardTask>,
+    mods: Option<PathBuf>,
+    merged: Option<PathBuf>,
+    /// Merge setting, defaulting to the recommended one
+    provenance: bool,
+    /// Merge setting, defaulting to the recommended one
+    split: bool,
+    /// Whether the page's main button should take keyboard focus, when the page first shows
+    focus_first: bool,
+}
+
+impl Wizard {
+    fn new() -> Self {
+        let mods = s4pi_reforged::install::find_mods_folder();
+        let merged = mods.as_ref()
+            .map(|mods| mods.join("merged").join("merged.package"))
+            .filter(|path| path.is_file());
+        let defaults = MergeOptions::default();
+        Self { task: None, mods, merged, provenance: defaults.provenance, split: defaults.split, focus_first: true }
+    }
+
+    /// Shows a path with a button to pick another
+    fn path_row(ui: &mut egui::Ui, path: &mut Option<PathBuf>, found: &str, missing: &str, pick: impl FnOnce() -> Option<PathBuf>) {
+        match path {
+            Some(p) => ui.label(format!("{} {}", found, p.display())),
+            None => ui.label(missing),
+        };
+        if ui.button("Choose another...").clicked() {
+            if let Some(picked) = pick() {
+                *path = Some(picked);
+            }
+        }
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui) -> WizardOutcome {
+        let mut outcome = WizardOutcome::Open;
+        let Some(task) = self.task else {
+            ui.heading("Welcome to S4PI Tool");
+            ui.label("What would you like to do?");
+            ui.add_space(8.0);
+            for (i, task) in [WizardTask::Merge, WizardTask::Unmerge, WizardTask::Conflicts].into_iter().enumerate() {
+                let button = ui.button(egui::RichText::new(task.title()).heading());
+                if i == 0 && std::mem::take(&mut self.focus_first) {
+                    button.request_focus();
+                }
+                if button.clicked() {
+                    self.task = Some(task);
+                    self.focus_first = true;
+                }
+                ui.label(task.description());
+                ui.add_space(8.0);
+            }
+            ui.separator();
+            if ui.button("Skip to the tools").clicked() {
+                outcome = WizardOutcome::Close;
+            }
+            return outcome;
+        };
+
+        if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            self.task = None;
+            self.focus_first = true;
+            return outcome;
+        }
+        ui.heading(task.title());
+        ui.label(task.description());
+        ui.add_space(8.0);
+        let ready = match task {
+            WizardTask::Merge | WizardTask::Conflicts => {
+                Self::path_row(ui, &mut self.mods, "Mods folder:", "Couldn't find your Mods folder; choose it below.", || {
+                    FileDialog::new().set_title("Select your Mods folder").pick_folder()
+                });
+                if task == WizardTask::Merge {
+                    ui.add_space(8.0);
+                    ui.checkbox(&mut self.provenance, "Record where each resource came from (recommended)")
+                        .on_hover_text("Lets Un-merge check the pieces, and 'info' show what went into the package");
+                    ui.checkbox(&mut self.split, "Write one package per kind of content")
+                        .on_hover_text("CAS, build/buy, tuning and scripts in separate packages; off is best for most people");
+                    ui.label("The merged package is written to the \"merged\" folder inside the Mods folder. Move the originals out of Mods once you're happy with it.");
+                }
+                self.mods.clone()
+            }
+            WizardTask::Unmerge => {
+                Self::path_row(ui, &mut self.merged, "Merged package:", "Couldn't find a merged package in your Mods folder; choose it below.", || {
+                    FileDialog::new().set_title("Select .package file to un-merge").add_filter("Package Files", &["package"]).pick_file()
+                });
+                ui.label("The original packages are written to an \"unmerged\" folder next to it.");
+                self.merged.clone()
+            }
+        };
+
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            let back = ui.button("Back").on_hover_text("Esc");
+            if back.clicked() {
+                self.task = None;
+                self.focus_first = true;
+            }
+            let start = ui.add_enabled(ready.is_some(), egui::Button::new("Start"));
+            if std::mem::take(&mut self.focus_first) {
+                let first = if ready.is_some() { &start } else { &back };
+                first.request_focus();
+            }
+            if start.clicked() {
+                if let Some(path) = ready {
+                    outcome = match task {
+                        WizardTask::Merge => WizardOutcome::Merge(path, MergeOptions { provenance: self.provenance, split: self.split, ..MergeOptions::default() }),
+                        WizardTask::Unmerge => WizardOutcome::Unmerge(path),
+                        WizardTask::Conflicts => WizardOutcome::Scan(path),
+                    };
+                }
+            }
+        });
+        outcome
+    }
+}
+
+/// Marks the landing screen as seen, so later runs open straight to the tools
+fn wizard_marker() -> Option<PathBuf> {
+    s4pi_reforged::typemap::registry_dir().map(|dir| dir.join("wizard-seen"))
+}
+
+fn wizard_seen() -> bool {
+    wizard_marker().is_none_or(|path| path.exists())
+}
+
+fn set_wizard_seen() {
+    if let Some(path) = wizard_marker() {
+        let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&path, b""));
+        if let Err(e) = written {
+            warn!("Could not save {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Display settings, kept between runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GuiSettings {
+    /// How much bigger than normal everything is drawn; Ctrl+Plus, Ctrl+Minus and Ctrl+0 change it too
+    scale: f32,
+    high_contrast: bool,
+}
+
+impl Default for GuiSettings {
+    fn default() -> Self {
+        Self { scale: 1.0, high_contrast: false }
+    }
+}
+
+impl GuiSettings {
+    /// The scales offered in the View menu
+    const SCALES: [f32; 7] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0, 2.5];
+
+    fn path() -> Option<PathBuf> {
+        s4pi_reforged::typemap::registry_dir().map(|dir| dir.join("gui-settings"))
+    }
+
+    /// The saved settings, with the defaults for anything missing or unreadable
+    fn load() -> Self {
+        let mut settings = Self::default();
+        let text = Self::path().and_then(|path| std::fs::read_to_string(path).ok()).unwrap_or_default();
+        for (key, value) in text.lines().filter_map(|line| line.split_once('=')) {
+            match key.trim() {
+                "scale" => if let Ok(scale) = value.trim().parse::<f32>() {
+                    settings.scale = scale.clamp(Self::SCALES[0], Self::SCALES[Self::SCALES.len() - 1]);
+                },
+                "high_contrast" => settings.high_contrast = value.trim() == "true",
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    fn save(&self) {
+        if let Some(path) = Self::path() {
+            let text = format!("scale={}\nhigh_contrast={}\n", self.scale, self.high_contrast);
+            let written = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| s4pi_reforged::atomic::write(&path, text));
+            if let Err(e) = written {
+                warn!("Could not save {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// The scale after any Ctrl+Plus, Ctrl+Minus or Ctrl+0 this frame
+    fn scale_shortcuts(&self, ctx: &egui::Context) -> f32 {
+        use egui::gui_zoom::kb_shortcuts;
+        ctx.input_mut(|input| {
+            if input.consume_shortcut(&kb_shortcuts::ZOOM_RESET) {
+                1.0
+            } else if input.consume_shortcut(&kb_shortcuts::ZOOM_IN) || input.consume_shortcut(&kb_shortcuts::ZOOM_IN_SECONDARY) {
+                Self::SCALES.into_iter().find(|&scale| scale > self.scale + 0.01).unwrap_or(self.scale)
+            } else if input.consume_shortcut(&kb_shortcuts::ZOOM_OUT) {
+                Self::SCALES.into_iter().rev().find(|&scale| scale < self.scale - 0.01).unwrap_or(self.scale)
+            } else {
+                self.scale
+            }
+        })
+    }
+
+    fn apply(&self, ctx: &egui::Context) {
+        ctx.set_zoom_factor(self.scale);
+        ctx.set_visuals(if self.high_contrast { high_contrast_visuals() } else { egui::Visuals::dark() });
+    }
+}
+
+/// White on black with solid outlines, and a thick yellow ring around whatever has keyboard focus
+fn high_contrast_visuals() -> egui::Visuals {
+    use egui::{Color32, Stroke};
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(24);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::from_rgb(0x80, 0xC0, 0xFF);
+    visuals.selection.bg_fill = Color32::from_rgb(0x00, 0x40, 0xA0);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::YELLOW);
+
+    let widgets = &mut visuals.widgets;
+    widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+    for (state, fill, stroke) in [
+        (&mut widgets.inactive, Color32::BLACK, Stroke::new(1.0, Color32::WHITE)),
+        (&mut widgets.hovered, Color32::from_gray(48), Stroke::new(2.0, Color32::WHITE)),
+        // Focused widgets are drawn as active
+        (&mut widgets.active, Color32::from_gray(64), Stroke::new(3.0, Color32::YELLOW)),
+        (&mut widgets.open, Color32::from_gray(48), Stroke::new(2.0, Color32::YELLOW)),
+    ] {
+        state.bg_fill = fill;
+        state.weak_bg_fill = fill;
+        state.bg_stroke = stroke;
+        state.fg_stroke = Stroke::new(state.fg_stroke.width.max(1.5), Color32::WHITE);
+    }
+    visuals
+}
+
+impl eframe::App for GuiApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut settings = self.settings;
+        settings.scale = settings.scale_shortcuts(ctx);
+        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Guide").on_hover_text("Step-by-step help with merging, un-merging and conflicts").clicked() {
+                    self.wizard = Some(Wizard::new());
+                }
+
+                if ui.button("Merge").on_hover_text("Merge every package in a folder into one").clicked() {
+                    let folder = FileDialog::new()
+                        .set_title("Select Folder containing .package files")
+                        .pick_folder();
+                    if let Some(f) = folder {
+                        self.start_merge(f, MergeOptions::default());
+                    }
+                }
+
+                ui.checkbox(&mut self.background, "Background")
+                    .on_hover_text("Merge on fewer, low-priority threads with slower reads, to stay out of the way while you play or stream");
+
+                if ui.button("Un-merge").on_hover_text("Split a merged package back into the originals").clicked() {
+                    let file = FileDialog::new()
+                        .set_title("Select .package file to un-merge")
+                        .add_filter("Package Files", &["package"])
+                        .pick_file();
+                    if let Some(f) = file {
+                        spawn_unmerge(f, &self.log_buffer, &self.progress);
+                    }
+                }
+
+                if ui.button("Inspect").on_hover_text("Browse the resources in a package").clicked() {
+                    let file = FileDialog::new()
+                        .set_title("Select .package file to inspect")
+                        .add_filter("Package Files", &["package"])
+                        .pick_file();
+                    if let Some(f) = file {
+                        self.open_browser(&f);
+                    }
+                }
+
+                ui.menu_button("Extract", |ui| {
+                    if ui.button("Thumbnail").clicked() {
+                        let file = FileDialog::new()
+                            .set_title("Select .package file to extract thumbnails")
+                            .add_filter("Package Files", &["package"])
+                            .pick_file();
+                        if let Some(f) = file {
+                            let log_arc = Arc::clone(&self.log_buffer);
+                            std::thread::spawn(move || {
+                                if let Err(e) = run_extract_thumbnails(&f, false) {
+                                    let mut log = log_arc.lock().unwrap();
+                                    log.push_str(&format!("Error during extraction: {:?}\n", e));
+                                }
+                            });
+                        }
+                        ui.close_menu();
+                    }
+                });
+
+                if is_debug_mode() {
+                    ui.menu_button("Advanced", |ui| {
+                        if ui.button("Investigate").clicked() {
+                            let file = FileDialog::new()
+                                .set_title("Select .package file to investigate")
+                                .add_filter("Package Files", &["package"])
+                                .pick_file();
+                            if let Some(f) = file {
+                                let log_arc = Arc::clone(&self.log_buffer);
+                                std::thread::spawn(move || {
+                                    if let Err(e) = run_investigate(&f, None) {
+                                        let mut log = log_arc.lock().unwrap();
+                                        log.push_str(&format!("Error during investigation: {:?}\n", e));
+                                    }
+                                });
+                            }
+                            ui.close_menu();
+                        }
+                        if ui.button("Diagnostics").clicked() {
+                            let file = FileDialog::new()
+                                .set_title("Select .package file for diagnostics")
+                                .add_filter("Package Files", &["package"])
+                                .pick_file();
+                            if let Some(f) = file {
+                                let log_arc = Arc::clone(&self.log_buffer);
+                                std::thread::spawn(move || {
+                                    if let Err(e) = run_diagnostics(&f) {
+                                        let mut log = log_arc.lock().unwrap();
+                                        log.push_str(&format!("Error during diagnostics: {:?}\n", e));
+                                    }
+                                });
+                            }
+                            ui.close_menu();
+                        }
+                    });
+                }
+
+                ui.menu_button("View", |ui| {
+                    ui.label("Scale (Ctrl+Plus / Ctrl+Minus):");
+                    for scale in GuiSettings::SCALES {
+                        ui.radio_value(&mut settings.scale, scale, format!("{:.0}%", scale * 100.0));
+                    }
+                    ui.separator();
+                    ui.checkbox(&mut settings.high_contrast, "High contrast");
+                });
+                if ui.button("Exit").clicked() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+        });
+
+        if settings != self.settings {
+            settings.apply(ctx);
+            settings.save();
+            self.settings = settings;
+        }
+
+        if let Some(picker) = self.pending_conflicts.lock().unwrap().take() {
+            self.conflicts = Some(picker);
+        }
+        if let Some(picker) = &mut self.conflicts {
+            match picker.show(ctx) {
+                PickerOutcome::Merge(options) => {
+                    let options = MergeOptions { background: self.background, ..options };
+                    spawn_merge(picker.folder.clone(), options, &self.log_buffer, &self.progress);
+                    self.conflicts = None;
+                }
+                PickerOutcome::Open => {}
+                PickerOutcome::Cancel => {
+                    info!("Merge cancelled.");
+                    self.conflicts = None;
+                }
+            }
+        }
+
+        if let Some(browser) = &mut self.browser {
+            if !browser.show(ctx) {
+                self.browser = None;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+
+        if let Some(wizard) = &mut self.wizard {
+            let outcome = egui::CentralPanel::default().show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| wizard.show(ui)).inner
+            }).inner;
+            let done = !matches!(outcome, WizardOutcome::Open);
+            match outcome {
+                WizardOutcome::Open => {}
+                WizardOutcome::Merge(folder, options) => self.start_merge(folder, options),
+                WizardOutcome::Unmerge(path) => spawn_unmerge(path, &self.log_buffer, &self.progress),
+                WizardOutcome::Scan(folder) => self.start_scan(folder),
+                WizardOutcome::Close => {}
+            }
+            if done {
+                set_wizard_seen();
+                self.wizard = None;
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("S4PI Tool");
+
+            if let Some((operation, done, total)) = self.progress.current.lock().unwrap().clone() {
+                let bar = match total {
+                    Some(total) if total > 0 => egui::ProgressBar::new(done as f32 / total as f32)
+                        .text(format!("{} {} of {}", operation, done, total)),
+                    _ => egui::ProgressBar::new(0.0).animate(true).text(format!("{} {}", operation, done)),
+                };
+                ui.add(bar);
+            }
+
+            ui.label("Console Output:");
+            let log_text = self.log_buffer.lock().unwrap();
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    ui.add_sized(
+                        ui.available_size(),
+                        egui::TextEdit::multiline(&mut log_text.clone())
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            drop(log_text);
+        });
+        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+    }
+}
+
+struct LogWriter {
+    buffer: Arc<Mutex<String>>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(s) = std::str::from_utf8(buf) {
+            let mut log = self.buffer.lock().unwrap();
+            log.push_str(s);
+        }
+        io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: perf::TrackingAllocator = perf::TrackingAllocator;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let log_buffer = Arc::new(Mutex::new(String::new()));
+
+    if is_package_launch(&args) {
+        return run_gui(log_buffer, Some(PathBuf::from(&args[1])));
+    }
+
+    if args.len() > 1 {
+        // CLI Mode
+        let log_dir = s4pi_reforged::logging::default_log_dir();
+        s4pi_reforged::logging::init(env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")), &log_dir);
+        
+        return run_command(&args);
+    }
+
+    let is_terminal = atty::is(atty::Stream::Stdout);
+    let force_gui = std::env::var("S4PI_FORCE_GUI").is_ok();
+    let force_tui = std::env::var("S4PI_FORCE_TUI").is_ok();
+
+    // On Windows, if we are NOT forced into TUI and either forced into GUI or NOT in a terminal, use GUI.
+    // However, atty::is often returns true on Windows even when launched from Explorer if it's a console app.
+    // A better check for "launched from explorer" on Windows is sometimes checking if the console title matches the executable path or other tricks, 
+    // but here we will try to be more biased towards GUI for better UX when no args are provided.
+    
+    #[cfg(windows)]
+    let prefer_gui = !is_terminal || !force_tui; // On Windows, prefer GUI unless TUI is forced.
+    #[cfg(not(windows))]
+    let prefer_gui = !is_terminal || force_gui;
+
+    if (is_terminal && !prefer_gui) || force_tui {
+        // TUI Mode
+        prepare_console();
+        let mut console = env_logger::Builder::from_default_env();
+        console.filter_level(log::LevelFilter::Info);
+        s4pi_reforged::logging::init(console, &s4pi_reforged::logging::default_log_dir());
+        loop {
+            println!("\nChoose an action:");
+            println!("1. Merge .package files");
+            println!("2. Un-merge .package file (Using manifest)");
+            println!("3. Extract options");
+            if is_debug_mode() {
+                println!("4. Advanced options");
+            }
+            println!("q. Exit");
+
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)?;
+            let choice = choice.trim().to_lowercase();
+
+            match choice.as_str() {
+                "1" => {
+                    let folder = FileDialog::new()
+                        .set_title("Select Folder containing .package files")
+                        .pick_folder();
+
+                    if let Some(f) = folder {
+                        if let Err(e) = run_merge(&f, &MergeOptions::default(), false, &LogObserver::default()) {
+                            error!("Fatal error during merge: {:?}", e);
+                        }
+                    }
+                }
+                "2" => {
+                    let file = FileDialog::new()
+                        .set_title("Select .package file to un-merge")
+                        .add_filter("Package Files", &["package"])
+                        .pick_file();
+
+                    if let Some(f) = file {
+                        if let Err(e) = run_unmerge(&f, false, &LogObserver { progress_level: log::Level::Info }) {
+                            error!("Fatal error during un-merge: {:?}", e);
+                        }
+                    }
+                }
+                "3" => {
+                    println!("Extract options:");
+                    println!("1. Thumbnail");
+                    println!("0. Back");
+
+                    let mut ext_choice = String::new();
+                    io::stdin().read_line(&mut ext_choice)?;
+                    let ext_choice = ext_choice.trim();
+
+                    match ext_choice {
+                        "1" => {
+                            let file = FileDialog::new()
+                                .set_title("Select .package file to extract thumbnails")
+                                .add_filter("Package Files", &["package"])
+                                .pick_file();
+
+                            if let Some(f) = file {
+                                if let Err(e) = run_extract_thumbnails(&f, false) {
+                                    error!("Fatal error during extraction: {:?}", e);
+                                }
+                            }
+                        }
+                        "0" => continue,
+                        _ => println!("Invalid choice."),
+                    }
+                }
+                "4" if is_debug_mode() => {
+                    println!("Advanced options:");
+                    println!("1. Investigate .package file (Scan for unknown resources)");
+                    println!("2. Diagnostic .package file (Dump index and head)");
+                    println!("0. Back");
+
+                    let mut adv_choice = String::new();
+                    io::stdin().read_line(&mut adv_choice)?;
+                    let adv_choice = adv_choice.trim();
+
+                    match adv_choice {
+                        "1" => {
+                            let file = FileDialog::new()
+                                .set_title("Select .package file to investigate")
+                                .add_filter("Package Files", &["package"])
+                                .pick_file();
+
+                            if let Some(f) = file {
+                                if let Err(e) = run_investigate(&f, None) {
+                                    error!("Fatal error during investigation: {:?}", e);
+                                }
+                            }
+                        }
+                        "2" => {
+                            let file = FileDialog::new()
+                                .set_title("Select .package file for diagnostics")
+                                .add_filter("Package Files", &["package"])
+                                .pick_file();
+
+                            if let Some(f) = file {
+                                if let Err(e) = run_diagnostics(&f) {
+                                    error!("Fatal error during diagnostics: {:?}", e);
+                                }
+                            }
+                        }
+                        "0" => continue,
+                        _ => println!("Invalid choice."),
+                    }
+                }
+                "q" => break,
+                _ => println!("Invalid choice."),
+            }
+            if choice != "q" {
+                println!("\nPress Enter to return to the main menu...");
+                let mut _pause = String::new();
+                let _ = io::stdin().read_line(&mut _pause);
+            }
+        }
+    } else {
+        // GUI Mode
+
+        run_gui(log_buffer, None)?;
+    }
+
+    Ok(())
+}
+
+/// Every command [`run_command`] knows, apart from the debug ones
+const COMMANDS: &[&str] = &[
+    "merge", "unmerge", "manifest", "import", "overrides", "conflicts", "recompress", "packs", "import-typemap", "census-diff", "diff", "devwatch", "retarget", "selftest",
+    "install", "backup", "stbl", "info", "list", "palette", "check-objects", "validate", "repair", "cat", "set", "tray", "poses", "mesh", "texture", "extract", "convert", "install-shell-integration", "run", "schema", "modlist",
+];
+
+/// Runs one command-line command; `args` are the program's arguments, starting with its name
+fn run_command(args: &[String]) -> Result<()> {
+    let debug = is_debug_mode();
+    let cmd = args[1].as_str();
+
+    match cmd {
+        "merge" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged merge <folder> [--split] [--no-provenance] [--on-conflict <error|last>] [--background] [--level <0-9>] [--force]");
+                println!("\nMerges all .package files in the specified folder into a single package.");
+                println!("\nWith --split, writes one package per category instead (merged_CAS, merged_BuildBuy,");
+                println!("merged_Tuning and merged_Other). Each source package goes whole into the category");
+                println!("that fits it best, and every output carries the same manifest so any of them can");
+                println!("be given to 'unmerge'.");
+                println!("\nWhen several packages contain the same resource, the one whose path sorts last");
+                println!("wins, as when the game loads them unmerged. --on-conflict error stops the merge");
+                println!("instead. Every conflict is listed in merged/merge_report.txt.");
+                println!("\nThe merged package records the tool version, time and a SHA-256 hash of every");
+                println!("source file, which 'info' and 'diagnostics' show. --no-provenance leaves it out.");
+                println!("\n--background keeps the merge out of the way while you play or stream: it runs on");
+                println!("a quarter of the CPU cores at low priority, and reads packages at most {}/s.",
+                    perf::format_bytes(s4pi_reforged::background::DEFAULT_READ_RATE));
+                println!("\n--level sets the zlib compression level, from 0 (fastest) to 9 (smallest).");
+                println!("The default is {}.", s4pi_reforged::package::write::DEFAULT_LEVEL);
+                println!("\nAn earlier merged package isn't overwritten while another program (such as the");
+                println!("game) has it open, unless --force is given.");
+                println!("\nExamples:");
+                println!("  s4pi-reforged merge ./mods/to-merge");
+                println!("  s4pi-reforged merge ./mods/to-merge --split");
+                println!("  s4pi-reforged merge ./mods/to-merge --on-conflict error");
+                println!("  s4pi-reforged merge ./mods/to-merge --level 9");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged merge <folder> [--split] [--no-provenance] [--on-conflict <error|last>] [--background] [--level <0-9>] [--force]\nTry 's4pi-reforged merge --help' for more information."));
+            }
+            let conflicts = match args.iter().position(|a| a == "--on-conflict") {
+                Some(i) => {
+                    let name = args.get(i + 1).map(String::as_str).unwrap_or_default();
+                    ConflictPolicy::from_name(name)
+                        .ok_or_else(|| anyhow!("Unknown --on-conflict value {:?}; expected 'error' or 'last'.", name))?
+                }
+                None => ConflictPolicy::default(),
+            };
+            let level = match args.iter().position(|a| a == "--level") {
+                Some(i) => args.get(i + 1).and_then(|l| l.parse().ok()).filter(|l| *l <= 9)
+                    .ok_or_else(|| anyhow!("--level needs a compression level from 0 to 9."))?,
+                None => s4pi_reforged::package::write::DEFAULT_LEVEL,
+            };
+            let options = MergeOptions {
+                provenance: !args.iter().any(|a| a == "--no-provenance"),
+                split: args.iter().any(|a| a == "--split"),
+                conflicts,
+                background: args.iter().any(|a| a == "--background"),
+                level,
+            };
+            run_merge(Path::new(&args[2]), &options, args.iter().any(|a| a == "--force"), &LogObserver::default())?;
+        }
+        "unmerge" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged unmerge <file> [--force]");
+                println!("\nUn-merges a merged .package file into its original components using its manifest.");
+                println!("For a merge made with --split, pass any one of its outputs; the others are read");
+                println!("from the same folder. Packages already in the output folder aren't overwritten");
+                println!("while another program has them open, unless --force is given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged unmerge ./merged_mod.package");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged unmerge <file> [--force]\nTry 's4pi-reforged unmerge --help' for more information."));
+            }
+            run_unmerge(Path::new(&args[2]), args.iter().any(|a| a == "--force"), &LogObserver { progress_level: log::Level::Info })?;
+        }
+        "import" if args.get(2).is_some_and(|a| a == "stbl") => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged import stbl <package> <file-or-folder> [--force]");
+                println!("\nWrites string tables exported with 'extract stbl --json' or '--po' back into the");
+                println!("package, replacing the table with the same TGI or adding it if the package doesn't");
+                println!("have one. Strings can be edited, added or removed; the table's string length is");
+                println!("recomputed to match. Given a folder, every .json, .po and .pot file in it is imported.");
+                println!("\nA .po file goes into the table for its Language header, so a translation started");
+                println!("from an English .pot template becomes a new table in that language. Untranslated");
+                println!("and fuzzy strings keep their English text.");
+                println!("\nThe package is updated in place, unless another program has it open and --force");
+                println!("isn't given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged import stbl ./my_mod.package ./strings");
+                return Ok(());
+            }
+            let positional: Vec<&String> = args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+            let [package, json, ..] = positional.as_slice() else {
+                return Err(anyhow!("Usage: s4pi-reforged import stbl <package> <file-or-folder> [--force]\nTry 's4pi-reforged import stbl --help' for more information."));
+            };
+            run_import_stbl(Path::new(package), Path::new(json), args.iter().any(|a| a == "--force"))?;
+        }
+        "import" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged import <folder> [output.package] [--new-instances]");
+                println!("\nBuilds a package from a folder of exported resources.");
+                println!("\nRecognised filename layouts:");
+                println!("  s4pe/s3pe     S4_TTTTTTTT_GGGGGGGG_IIIIIIIIIIIIIIII[_Name]%%+TAG.ext");
+                println!("  Sims 4 Studio TTTTTTTT!GGGGGGGG!IIIIIIIIIIIIIIII[.Name.Type].ext");
+                println!("  Plain TGI     TTTTTTTT-GGGGGGGG-IIIIIIIIIIIIIIII.ext");
+                println!("\nSubfolders are searched too. Defaults to <folder>/imported/imported.package.");
+                println!("\n--new-instances gives every resource a new instance that nothing in your Mods");
+                println!("folder uses, as when cloning an item. Resources that shared an instance still");
+                println!("share one; a named resource gets the hash of its name (with the high bit set),");
+                println!("and the rest get random ones. The name map is rebuilt to match.");
+                println!("\nDXT1/3/5 DDS files for DST textures, as written by 'extract textures', are");
+                println!("shuffled back into DST.");
+                println!("\nExamples:");
+                println!("  s4pi-reforged import ./my-project-export ./my-project.package");
+                println!("  s4pi-reforged import ./cloned-export --new-instances");
+                println!("\nRun 's4pi-reforged import stbl --help' to import string tables from JSON.");
+                return Ok(());
+            }
+            let positional: Vec<&String> = args[2..].iter().filter(|a| !a.starts_with("--")).collect();
+            if positional.is_empty() {
+                return Err(anyhow!("Usage: s4pi-reforged import <folder> [output.package] [--new-instances]\nTry 's4pi-reforged import --help' for more information."));
+            }
+            run_import(Path::new(positional[0]), positional.get(1).map(Path::new), args.iter().any(|a| a == "--new-instances"))?;
+        }
+        "overrides" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged overrides <game-folder> <mods-folder>");
+                println!("\nIndexes the game's FullBuild/Delta packages (read-only) and reports which");
+                println!("resources in each Mods package override Maxis content and which are new.");
+                println!("Deltas are applied over the full builds as in game, so each override is shown");
+                println!("against the patched package it replaces.");
+                println!("\nExample:");
+                println!("  s4pi-reforged overrides \"C:\\Program Files\\EA Games\\The Sims 4\" ./Mods");
+                return Ok(());
+            }
+            if args.len() < 4 {
+                return Err(anyhow!("Usage: s4pi-reforged overrides <game-folder> <mods-folder>\nTry 's4pi-reforged overrides --help' for more information."));
+            }
+            run_overrides(Path::new(&args[2]), Path::new(&args[3]))?;
+        }
+        "conflicts" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged conflicts <mods-folder> [--all]");
+                println!("\nReads the index of every package in the folder and reports the resources more than");
+                println!("one of them contains. The game only uses the copy in the package that loads last");
+                println!("(sorted by path), so the others are overridden. Conflicts are grouped by how much");
+                println!("they matter: tuning, SimData, object and CAS definitions and string tables (high),");
+                println!("meshes, textures and other assets (medium), and thumbnails (low).");
+                println!("\nLow-severity conflicts are only counted unless --all is given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged conflicts ./Mods");
+                return Ok(());
+            }
+            let Some(folder) = args[2..].iter().find(|a| !a.starts_with("--")) else {
+                return Err(anyhow!("Usage: s4pi-reforged conflicts <mods-folder> [--all]\nTry 's4pi-reforged conflicts --help' for more information."));
+            };
+            run_conflicts(Path::new(folder), args.iter().any(|a| a == "--all"))?;
+        }
+        "census-diff" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged census-diff <folderA> <folderB>");
+                println!("\nCompares the resources in two Mods folder snapshots (e.g. before and after a");
+                println!("cleanup or a game patch) and reports which resources disappeared, which are new,");
+                println!("which changed size and which moved to a different package. Only package indexes");
+                println!("are read, so it is quick even on large folders.");
+                println!("\nExample:");
+                println!("  s4pi-reforged census-diff ./Mods-backup ./Mods");
+                return Ok(());
+            }
+            if args.len() < 4 {
+                return Err(anyhow!("Usage: s4pi-reforged census-diff <folderA> <folderB>\nTry 's4pi-reforged census-diff --help' for more information."));
+            }
+            run_census_diff(Path::new(&args[2]), Path::new(&args[3]))?;
+        }
+        "diff" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged diff <packageA> <packageB> [--json]");
+                println!("\nCompares two packages, such as the old and new version of some CC, and lists the");
+                println!("resources only in A, only in B, and in both but with different content. Content is");
+                println!("compared decompressed, so a resource that was only recompressed counts as the same.");
+                println!("\n--json prints the result as a JSON object instead, for scripts.");
+                println!("\nExample:");
+                println!("  s4pi-reforged diff ./old/my_cc.package ./new/my_cc.package");
+                return Ok(());
+            }
+            let positional: Vec<&String> = args[2..].iter().filter(|a| !a.starts_with("--")).collect();
+            let [a, b, ..] = positional[..] else {
+                return Err(anyhow!("Usage: s4pi-reforged diff <packageA> <packageB> [--json]\nTry 's4pi-reforged diff --help' for more information."));
+            };
+            run_diff(Path::new(a), Path::new(b), args.iter().any(|a| a == "--json"))?;
+        }
+        "devwatch" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged devwatch <project.package> --against <previous.package> [--once]");
+                println!("\nWatches a package you're working on and, every time it's saved, lists the resources");
+                println!("that were added, changed or removed and re-checks the ones that were added or changed:");
+                println!("that tuning instances are the hash of the tuning's name, that tuning and its SimData");
+                println!("still come in pairs and were changed together, and that string tables have no");
+                println!("duplicate or zero keys and haven't lost keys.");
+                println!("\nThe first report is against --against, e.g. the last released build; each one after");
+                println!("that is against the save before it. Press Ctrl+C to stop. --once reports once and");
+                println!("exits, with an error if problems were found.");
+                println!("\nExample:");
+                println!("  s4pi-reforged devwatch ./my_mod.package --against ./release/my_mod.package");
+                return Ok(());
+            }
+            let against = match args.iter().position(|a| a == "--against") {
+                Some(i) => args.get(i + 1).map(PathBuf::from)
+                    .ok_or_else(|| anyhow!("--against needs the package to compare with."))?,
+                None => return Err(anyhow!("Usage: s4pi-reforged devwatch <project.package> --against <previous.package> [--once]\nTry 's4pi-reforged devwatch --help' for more information.")),
+            };
+            let Some(project) = args[2..].iter().find(|a| !a.starts_with("--") && Path::new(a.as_str()) != against) else {
+                return Err(anyhow!("Usage: s4pi-reforged devwatch <project.package> --against <previous.package> [--once]\nTry 's4pi-reforged devwatch --help' for more information."));
+            };
+            run_devwatch(Path::new(project), &against, args.iter().any(|a| a == "--once"))?;
+        }
+        "retarget" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged retarget <file-or-folder> <from-instance> <to-instance> [--dry-run] [--force]");
+                println!("\nRewrites every reference to one tuning instance so it refers to another instead, in");
+                println!("the tuning XML and SimData of a package (or of every package in a folder), e.g. to point");
+                println!("an interaction at a different buff. Instances are decimal, as tuning writes them, or");
+                println!("0x-prefixed hex. Each reference changed is listed with its resource and its line in the");
+                println!("XML or its field in the SimData.");
+                println!("\n--dry-run only reports what would change. Packages another program has open are left");
+                println!("alone unless --force is given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged retarget ./adopted_mod.package 12345 67890 --dry-run");
+                return Ok(());
+            }
+            let positional: Vec<&String> = args[2..].iter().filter(|a| !a.starts_with("--")).collect();
+            let [target, from, to, ..] = positional.as_slice() else {
+                return Err(anyhow!("Usage: s4pi-reforged retarget <file-or-folder> <from-instance> <to-instance> [--dry-run] [--force]\nTry 's4pi-reforged retarget --help' for more information."));
+            };
+            let from = s4pi_reforged::edit::parse_number(from)?;
+            let to = s4pi_reforged::edit::parse_number(to)?;
+            run_retarget(Path::new(target), from, to, args.iter().any(|a| a == "--dry-run"), args.iter().any(|a| a == "--force"))?;
+        }
+        "selftest" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged selftest");
+                println!("\nChecks that this build reads and writes packages correctly on this computer before");
+                println!("you trust it with your Mods folder. Synthetic packages covering every index layout,");
+                println!("every way resource data is stored and every resource type this build can write are");
+                println!("written to a temporary folder, then opened, merged, un-merged and verified. A table");
+                println!("shows how each step went for each package, followed by the reason for any failure.");
+                println!("Nothing outside the temporary folder is touched, and it is removed afterwards.");
+                return Ok(());
+            }
+            run_selftest()?;
+        }
+        "install" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged install <archive-or-folder> [--mods <folder>] [--into <subfolder>] [--dry-run]");
+                println!("       s4pi-reforged install --undo [--mods <folder>]");
+                println!("\nCopies the packages and scripts (.ts4script) in a CC download into the Mods folder.");
+                println!("The download can be a .zip (or .7z, in builds with the 7z feature) or an extracted");
+                println!("folder. Each file is checked first: packages must have a valid header and scripts");
+                println!("must be script archives; anything that fails is reported and left out, as are");
+                println!("readmes and other files.");
+                println!("\nFiles go in a subfolder named after the download, or --into. Scripts go in the first");
+                println!("level of that subfolder, since the game ignores scripts nested deeper. A file whose");
+                println!("name is taken is numbered, unless the file there is identical.");
+                println!("\nThe Mods folder is found under Documents unless --mods is given. --dry-run shows");
+                println!("what would be installed without copying anything. Installs are recorded in");
+                println!("{} in the Mods folder; --undo removes the files of the last one.", s4pi_reforged::install::JOURNAL_NAME);
+                println!("\nExamples:");
+                println!("  s4pi-reforged install ./Downloads/new_hair.zip --dry-run");
+                println!("  s4pi-reforged install ./Downloads/new_hair.zip --into Hair/NewHair");
+                println!("  s4pi-reforged install --undo");
+                return Ok(());
+            }
+            let mut mods = None;
+            let mut into = None;
+            let mut positional = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--mods" => {
+                        mods = args.get(i + 1).map(PathBuf::from);
+                        i += 2;
+                    }
+                    "--into" => {
+                        into = args.get(i + 1).map(PathBuf::from);
+                        i += 2;
+                    }
+                    arg => {
+                        if !arg.starts_with("--") {
+                            positional.push(&args[i]);
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            let mods = match mods.or_else(s4pi_reforged::install::find_mods_folder) {
+                Some(mods) => mods,
+                None => return Err(anyhow!("Could not find the Mods folder under Documents; pass it with --mods <folder>.")),
+            };
+            if args.iter().any(|a| a == "--undo") {
+                run_install_undo(&mods)?;
+            } else if let Some(source) = positional.first() {
+                run_install(Path::new(source), &mods, into.as_deref(), args.iter().any(|a| a == "--dry-run"))?;
+            } else {
+                return Err(anyhow!("Usage: s4pi-reforged install <archive-or-folder> [--mods <folder>] [--into <subfolder>] [--dry-run]\nTry 's4pi-reforged install --help' for more information."));
+            }
+        }
+        "backup" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            match subcommand {
+                "list" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged backup list <backup-folder>");
+                        println!("\nLists the snapshots in a backup, oldest first, with the number and total size");
+                        println!("of the files in each.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged backup list ./ModsBackup");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged backup list <backup-folder>\nTry 's4pi-reforged backup list --help' for more information."));
+                    }
+                    run_backup_list(Path::new(&args[3]))?;
+                }
+                "restore" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged backup restore <backup-folder> [<snapshot>] [--to <folder>] [--force]");
+                        println!("\nPuts the Mods folder back the way it was in a snapshot, the latest one unless");
+                        println!("one is named (see 'backup list'): files that are missing or different are");
+                        println!("copied back, and files that weren't there are removed. The folder is backed up");
+                        println!("first, so a restore can itself be undone by restoring the snapshot it reports.");
+                        println!("\nThe backup is checked before anything changes. The snapshot goes back to the");
+                        println!("folder it was taken from, unless --to is given. Packages another program (such");
+                        println!("as the game) has open aren't touched, unless --force is given.");
+                        println!("\nExamples:");
+                        println!("  s4pi-reforged backup restore ./ModsBackup");
+                        println!("  s4pi-reforged backup restore ./ModsBackup 1760000000 --to ./Mods");
+                        return Ok(());
+                    }
+                    let mut to = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        match args[i].as_str() {
+                            "--to" => {
+                                to = args.get(i + 1).map(PathBuf::from);
+                                i += 2;
+                            }
+                            arg => {
+                                if !arg.starts_with("--") {
+                                    positional.push(args[i].as_str());
+                                }
+                                i += 1;
+                            }
+                        }
+                    }
+                    let Some(dest) = positional.first() else {
+                        return Err(anyhow!("Usage: s4pi-reforged backup restore <backup-folder> [<snapshot>] [--to <folder>] [--force]\nTry 's4pi-reforged backup restore --help' for more information."));
+                    };
+                    run_backup_restore(Path::new(dest), positional.get(1).copied(), to.as_deref(), args.iter().any(|a| a == "--force"))?;
+                }
+                _ => {
+                    if subcommand == "--help" || subcommand.is_empty() {
+                        println!("Usage: s4pi-reforged backup <mods-folder> <backup-folder>");
+                        println!("       s4pi-reforged backup list <backup-folder>");
+                        println!("       s4pi-reforged backup restore <backup-folder> [<snapshot>] [--to <folder>] [--force]");
+                        println!("\nTakes a snapshot of every file in the Mods folder, so merges and clean-ups can be");
+                        println!("rolled back with 'backup restore'. Files are stored by their SHA-256 hash: each");
+                        println!("version of a file is kept once however many snapshots include it, so a new");
+                        println!("snapshot only takes the space of what changed since the last.");
+                        println!("\nThe backup folder can be new or an earlier backup, and can be inside the Mods");
+                        println!("folder (it's left out of its own snapshots). Run 's4pi-reforged backup <subcommand>");
+                        println!("--help' for 'list' and 'restore'.");
+                        println!("\nExamples:");
+                        println!("  s4pi-reforged backup ./Mods ./ModsBackup");
+                        println!("  s4pi-reforged backup list ./ModsBackup");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged backup <mods-folder> <backup-folder>\nTry 's4pi-reforged backup --help' for more information."));
+                    }
+                    run_backup(Path::new(&args[2]), Path::new(&args[3]))?;
+                }
+            }
+        }
+        "poses" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged poses <file>");
+                println!("\nLists every animation clip in a pose pack with its name, duration and the tuning");
+                println!("that exposes it. Clips no tuning refers to are flagged, since a pose player won't");
+                println!("show them.");
+                println!("\nExample:");
+                println!("  s4pi-reforged poses ./my_pose_pack.package");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged poses <file>\nTry 's4pi-reforged poses --help' for more information."));
+            }
+            run_poses(Path::new(&args[2]))?;
+        }
+        "recompress" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged recompress <file-or-folder> [--force]");
+                println!("\nConverts RefPack-compressed resources in older packages to zlib, which the current");
+                println!("game prefers. Every other resource is copied unchanged. A folder is searched for");
+                println!("packages recursively. Prints the space saved per package and any resources that");
+                println!("could not be converted; those are left as they were. Nothing is changed while");
+                println!("another program has one of the packages open, unless --force is given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged recompress ./Mods/old-cc");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged recompress <file-or-folder> [--force]\nTry 's4pi-reforged recompress --help' for more information."));
+            }
+            run_recompress(Path::new(&args[2]), args.iter().any(|a| a == "--force"))?;
+        }
+        "packs" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged packs <file-or-folder> [--game <game-folder>]");
+                println!("\nReports which expansion, game, stuff and kit packs each package's content needs,");
+                println!("going by the pack ID in its catalog entries and CAS parts. Items from a pack the");
+                println!("player doesn't own don't show up in game. A folder is searched for packages");
+                println!("recursively.");
+                println!("\nWith --game, packs missing from that install are flagged, so you can see which");
+                println!("CC will be invisible there.");
+                println!("\nExample:");
+                println!("  s4pi-reforged packs ./Mods --game \"C:\\Program Files\\EA Games\\The Sims 4\"");
+                return Ok(());
+            }
+            let mut game = None;
+            let mut positional = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--game" => {
+                        game = args.get(i + 1).map(PathBuf::from);
+                        i += 2;
+                    }
+                    arg => {
+                        if !arg.starts_with("--") {
+                            positional.push(&args[i]);
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            let Some(path) = positional.first() else {
+                return Err(anyhow!("Usage: s4pi-reforged packs <file-or-folder> [--game <game-folder>]\nTry 's4pi-reforged packs --help' for more information."));
+            };
+            run_packs(Path::new(path), game.as_deref())?;
+        }
+        "palette" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged palette <file-or-folder> [--color <name>]");
+                println!("\nReports the colors of each CAS part: the swatch colors it shows in CAS and the main");
+                println!("colors of its thumbnail, listed by sort priority so you can check swatch order. Parts");
+                println!("whose swatch color doesn't show up in their thumbnail are marked. A folder is");
+                println!("searched for packages recursively.");
+                println!("\nWith --color, only parts with that color in their swatch or thumbnail are listed.");
+                println!("Colors: {}.", s4pi_reforged::palette::COLOR_NAMES.join(", "));
+                println!("\nExamples:");
+                println!("  s4pi-reforged palette ./my_hair.package");
+                println!("  s4pi-reforged palette ./Mods --color green");
+                return Ok(());
+            }
+            let mut color = None;
+            let mut positional = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--color" => {
+                        color = args.get(i + 1).map(|c| c.to_lowercase());
+                        i += 2;
+                    }
+                    arg => {
+                        if !arg.starts_with("--") {
+                            positional.push(&args[i]);
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            let Some(path) = positional.first() else {
+                return Err(anyhow!("Usage: s4pi-reforged palette <file-or-folder> [--color <name>]\nTry 's4pi-reforged palette --help' for more information."));
+            };
+            if let Some(color) = &color {
+                if !s4pi_reforged::palette::COLOR_NAMES.contains(&color.as_str()) {
+                    return Err(anyhow!("Unknown color {:?}; expected one of: {}.", color, s4pi_reforged::palette::COLOR_NAMES.join(", ")));
+                }
+            }
+            run_palette(Path::new(path), color.as_deref())?;
+        }
+        "check-objects" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged check-objects <file-or-folder> [--game <game-folder>]");
+                println!("\nChecks every object definition (OBJD) for the mistakes that make Build/Buy CC");
+                println!("missing or broken in game: a tuning ID with no object tuning, models, rigs, slots or");
+                println!("footprints that don't exist, and a catalog entry (COBJ) that is missing or whose name");
+                println!("and description aren't in a string table. A folder is searched for packages");
+                println!("recursively.");
+                println!("\nWithout --game, references only resolve within the package, so objects that reuse");
+                println!("the game's meshes or tuning are reported too. With --game, the install's resources");
+                println!("and English strings count as well.");
+                println!("\nExample:");
+                println!("  s4pi-reforged check-objects ./my_chair.package --game \"C:\\Program Files\\EA Games\\The Sims 4\"");
+                return Ok(());
+            }
+            let mut game = None;
+            let mut positional = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--game" => {
+                        game = args.get(i + 1).map(PathBuf::from);
+                        i += 2;
+                    }
+                    arg => {
+                        if !arg.starts_with("--") {
+                            positional.push(&args[i]);
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            let Some(path) = positional.first() else {
+                return Err(anyhow!("Usage: s4pi-reforged check-objects <file-or-folder> [--game <game-folder>]\nTry 's4pi-reforged check-objects --help' for more information."));
+            };
+            run_check_objects(Path::new(path), game.as_deref())?;
+        }
+        "validate" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged validate <file-or-folder>");
+                println!("\nChecks packages for the damage that makes CC fail to load or crash the game:");
+                println!("a bad header or index, entries whose data runs past the end of the file, data that");
+                println!("doesn't decompress or decompresses to the wrong size, and resources of known types");
+                println!("that don't parse. Every problem is listed with the resource it's in. A folder is");
+                println!("searched for packages recursively.");
+                println!("\nExits with an error if any package has a problem, so it can gate a release script.");
+                println!("\nExample:");
+                println!("  s4pi-reforged validate ./my_cc.package");
+                return Ok(());
+            }
+            let Some(path) = args[2..].iter().find(|a| !a.starts_with("--")) else {
+                return Err(anyhow!("Usage: s4pi-reforged validate <file-or-folder>\nTry 's4pi-reforged validate --help' for more information."));
+            };
+            run_validate(Path::new(path))?;
+        }
+        "repair" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged repair <file> [--out <repaired.package>] [--in-place]");
+                println!("\nFixes the damage 'validate' most often finds: a header entry count that doesn't match");
+                println!("the index, file size flags and compression codes that don't match the data, wrong");
+                println!("decompressed sizes, and entries whose data was cut off the end of the file (those are");
+                println!("dropped). Every fix is listed. Resource data is copied as it is, never recompressed.");
+                println!("\nThe repaired copy is written to <name>_repaired.package next to the original, or to");
+                println!("--out. --in-place replaces the original instead.");
+                println!("\nExample:");
+                println!("  s4pi-reforged repair ./broken.package");
+                return Ok(());
+            }
+            let output = match args.iter().position(|a| a == "--out") {
+                Some(i) => Some(args.get(i + 1).map(PathBuf::from)
+                    .ok_or_else(|| anyhow!("--out needs a file to write the repaired package to."))?),
+                None => None,
+            };
+            let positional: Vec<&String> = args[2..].iter()
+                .filter(|a| !a.starts_with("--") && output.as_deref() != Some(Path::new(a.as_str())))
+                .collect();
+            let Some(path) = positional.first() else {
+                return Err(anyhow!("Usage: s4pi-reforged repair <file> [--out <repaired.package>] [--in-place]\nTry 's4pi-reforged repair --help' for more information."));
+            };
+            let path = Path::new(path.as_str());
+            let output = match output {
+                Some(output) => output,
+                None if args.iter().any(|a| a == "--in-place") => path.to_path_buf(),
+                None => {
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    path.with_file_name(format!("{}_repaired.package", stem))
+                }
+            };
+            if output.exists() {
+                ensure_not_in_use(std::slice::from_ref(&output), args.iter().any(|a| a == "--force"))?;
+            }
+            run_repair(path, &output)?;
+        }
+        "stbl" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged stbl <subcommand> <path>");
+                println!("\nSubcommands for working with string tables (STBL).");
+                println!("\nAvailable subcommands:");
+                println!("  pseudo        Generates a pseudo-localized override of the English string tables");
+                println!("  collisions    Finds string keys given different text by several packages in a folder");
+                println!("  rekey         Changes string keys in string tables and tuning using a mapping file");
+                println!("  merge         Merges the string tables of several packages into one per language");
+                println!("  strip         Removes the string tables of languages you don't play in");
+                println!("\nRun 's4pi-reforged stbl <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "pseudo" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged stbl pseudo <package> [output.package]");
+                        println!("\nWrites every English string table in the package back out with accented");
+                        println!("characters, ~30% length padding and [bracket] markers, keeping the original");
+                        println!("TGIs so the output overrides the real strings in game.");
+                        println!("Defaults to <package>_pseudo.package next to the input.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged stbl pseudo ./my_mod.package");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged stbl pseudo <package> [output.package]\nTry 's4pi-reforged stbl pseudo --help' for more information."));
+                    }
+                    run_stbl_pseudo(Path::new(&args[3]), args.get(4).map(Path::new))?;
+                }
+                "collisions" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged stbl collisions <folder>");
+                        println!("\nReads the string tables of every package under the folder and lists each key");
+                        println!("that two or more packages define with different text in the same language.");
+                        println!("Only one of them can win in game, which is a common cause of wrong or garbled");
+                        println!("UI text. Different languages of the same key are not collisions.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged stbl collisions \"C:\\Users\\You\\Documents\\Electronic Arts\\The Sims 4\\Mods\"");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged stbl collisions <folder>\nTry 's4pi-reforged stbl collisions --help' for more information."));
+                    }
+                    run_stbl_collisions(Path::new(&args[3]))?;
+                }
+                "rekey" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged stbl rekey <file-or-folder> <mapping.txt> [--dry-run] [--force]");
+                        println!("\nGives strings new keys: every string table entry with a key listed in the mapping");
+                        println!("file is moved to its new key, and tuning references to the old key (0x1A2B3C4D) are");
+                        println!("updated to match. The mapping file has one 'OLD NEW' pair of hex keys per line;");
+                        println!("lines starting with # are ignored.");
+                        println!("\nA mapping that undoes the change is written next to the mapping file as");
+                        println!("<mapping>_reverse.txt. --dry-run only reports what would change. Packages");
+                        println!("another program has open are left alone unless --force is given.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged stbl rekey ./my_mod.package ./new_keys.txt --dry-run");
+                        return Ok(());
+                    }
+                    let positional: Vec<&String> = args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+                    let [target, mapping, ..] = positional.as_slice() else {
+                        return Err(anyhow!("Usage: s4pi-reforged stbl rekey <file-or-folder> <mapping.txt> [--dry-run] [--force]\nTry 's4pi-reforged stbl rekey --help' for more information."));
+                    };
+                    run_stbl_rekey(Path::new(target), Path::new(mapping), args.iter().any(|a| a == "--dry-run"), args.iter().any(|a| a == "--force"))?;
+                }
+                "strip" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged stbl strip <file-or-folder> --keep <locale,...> [--dry-run] [--force]");
+                        println!("\nRemoves every string table whose language isn't in the --keep list, which makes");
+                        println!("packages that ship many translations smaller. Languages are given by name, tag");
+                        println!("or language code (English, ENG_US, en), separated by commas. Tables with a locale");
+                        println!("code the game doesn't use are kept.");
+                        println!("\n--dry-run only reports what would be removed. Packages another program has open");
+                        println!("are left alone unless --force is given.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged stbl strip ./Mods --keep en,fr --dry-run");
+                        return Ok(());
+                    }
+                    let keep = match args.iter().position(|a| a == "--keep") {
+                        Some(i) => args.get(i + 1).ok_or_else(|| anyhow!("--keep needs a list of languages, e.g. en,fr."))?
+                            .split(',')
+                            .map(|name| name.parse::<Locale>().ok().or_else(|| Locale::from_language_code(name))
+                                .ok_or_else(|| anyhow!("Unknown language {:?}.", name)))
+                            .collect::<Result<Vec<_>>>()?,
+                        None => return Err(anyhow!("Usage: s4pi-reforged stbl strip <file-or-folder> --keep <locale,...> [--dry-run] [--force]\nTry 's4pi-reforged stbl strip --help' for more information.")),
+                    };
+                    let Some(target) = args.get(3).filter(|a| !a.starts_with("--")) else {
+                        return Err(anyhow!("Usage: s4pi-reforged stbl strip <file-or-folder> --keep <locale,...> [--dry-run] [--force]\nTry 's4pi-reforged stbl strip --help' for more information."));
+                    };
+                    run_stbl_strip(Path::new(target), &keep, args.iter().any(|a| a == "--dry-run"), args.iter().any(|a| a == "--force"))?;
+                }
+                "merge" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged stbl merge <file-or-folder>... [--out <package>]");
+                        println!("\nReads the string tables of the packages given (and of every package in the folders");
+                        println!("given) and writes one table per language to a new package, as the game would see");
+                        println!("them: when more than one table has a key, the one loaded last wins. Packages are");
+                        println!("taken in the order given, and a folder's in the order of their paths, as the game");
+                        println!("loads them. Keys given different text by different packages are listed, with the");
+                        println!("text that was kept.");
+                        println!("\nDefaults to <first input>_strings.package next to the first input.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged stbl merge ./translations --out ./all_translations.package");
+                        return Ok(());
+                    }
+                    let mut output = None;
+                    let mut inputs = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--out" {
+                            output = Some(args.get(i + 1).map(Path::new).ok_or_else(|| anyhow!("--out needs a package to write."))?);
+                            i += 2;
+                        } else {
+                            if !args[i].starts_with("--") {
+                                inputs.push(Path::new(&args[i]));
+                            }
+                            i += 1;
+                        }
+                    }
+                    if inputs.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged stbl merge <file-or-folder>... [--out <package>]\nTry 's4pi-reforged stbl merge --help' for more information."));
+                    }
+                    run_stbl_merge(&inputs, output)?;
+                }
+                _ => {
+                    println!("Unknown stbl subcommand: {}", subcommand);
+                    println!("Available subcommands: pseudo, collisions, rekey, merge");
+                }
+            }
+        }
+        "info" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged info <package> [TGI] [--full]");
+                println!("\nFinds one resource by TGI (TYPE:GROUP:INSTANCE in hex), parses it and prints the");
+                println!("result. Resources without a typed parser, or that fail to parse, are shown as a");
+                println!("hexdump followed by a guess at their structure (TGIs, strings, float runs, counted");
+                println!("arrays). Long output is cut short unless --full is given.");
+                println!("\nWithout a TGI, prints a summary of the package, including how it was merged if");
+                println!("it was produced by 'merge'.");
+                println!("\nThe package can also be a .zip archive (or .7z, in builds with the 7z feature), in");
+                println!("which case every package inside it is read without extracting the archive.");
+                println!("\nIn builds with the http feature, it can also be an http(s) URL. Only the header,");
+                println!("index and the resources shown are downloaded, using HTTP range requests, so a");
+                println!("large package can be checked before downloading it.");
+                println!("\nExamples:");
+                println!("  s4pi-reforged info ./my_mod.package 220557DA:80000000:00A1B2C3D4E5F601");
+                println!("  s4pi-reforged info ./merged.package");
+                println!("  s4pi-reforged info ./Downloads/new_cc.zip");
+                return Ok(());
+            }
+            let positional: Vec<&String> = args[2..].iter().filter(|a| !a.starts_with("--")).collect();
+            match positional.as_slice() {
+                [] => return Err(anyhow!("Usage: s4pi-reforged info <package> [TGI] [--full]\nTry 's4pi-reforged info --help' for more information.")),
+                [package] => run_package_info(Path::new(package))?,
+                [package, tgi, ..] => {
+                    let tgi: TGI = tgi.parse()?;
+                    run_info(Path::new(package), &tgi, args.iter().any(|a| a == "--full"))?;
+                }
+            }
+        }
+        "list" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged list <package> [--type <id>] [--group <id>] [--instance <id>] [--sort <size|type|instance>]");
+                println!("\nLists the resources in a package, one per line: TGI, size, size as stored,");
+                println!("compression and what kind of resource it is.");
+                println!("\n--type, --group and --instance only list resources with that ID (hex with 0x, or");
+                println!("decimal; repeat the option or separate IDs with commas for several). --sort size");
+                println!("puts the largest first; --sort type and --sort instance order by TGI. Without");
+                println!("--sort, resources are listed in index order.");
+                println!("\nExamples:");
+                println!("  s4pi-reforged list ./my_mod.package");
+                println!("  s4pi-reforged list ./merged.package --type 0x220557DA --sort size");
+                return Ok(());
+            }
+            let usage = "Usage: s4pi-reforged list <package> [--type <id>] [--group <id>] [--instance <id>] [--sort <size|type|instance>]\nTry 's4pi-reforged list --help' for more information.";
+            let (filter, rest) = TgiFilter::from_args(&args[2..])?;
+            let sort = rest.iter().position(|a| a == "--sort");
+            let order = match sort {
+                Some(i) => {
+                    let name = rest.get(i + 1).map(String::as_str).unwrap_or_default();
+                    ListOrder::from_name(name)
+                        .ok_or_else(|| anyhow!("Unknown --sort value {:?}; expected 'size', 'type' or 'instance'.", name))?
+                }
+                None => ListOrder::Index,
+            };
+            let positional: Vec<&String> = rest.iter().enumerate()
+                .filter(|(i, a)| !a.starts_with("--") && sort.is_none_or(|s| *i != s + 1))
+                .map(|(_, a)| a)
+                .collect();
+            let Some(path) = positional.first() else {
+                return Err(anyhow!(usage));
+            };
+            run_list(Path::new(path), &filter, order)?;
+        }
+        "cat" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged cat <package> <TGI>");
+                println!("\nWrites the decompressed bytes of one resource to standard output, for use in pipelines.");
+                println!("\nExample:");
+                println!("  s4pi-reforged cat ./my_mod.package 0333406C:00000000:00A1B2C3D4E5F601 | xmllint --format -");
+                return Ok(());
+            }
+            if args.len() < 4 {
+                return Err(anyhow!("Usage: s4pi-reforged cat <package> <TGI>\nTry 's4pi-reforged cat --help' for more information."));
+            }
+            let tgi: TGI = args[3].parse()?;
+            run_cat(Path::new(&args[2]), &tgi)?;
+        }
+        "set" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged set <package> <TGI> <field=value>... [--force]");
+                println!("\nChanges fields of one resource and rewrites the package in place. It refuses to");
+                println!("while another program has the package open, unless --force is given.");
+                println!("\nCatalog resources: {}", s4pi_reforged::edit::CATALOG_FIELDS.join(", "));
+                println!("CAS parts:         {} (tags as category:value,...)", s4pi_reforged::edit::CASP_FIELDS.join(", "));
+                println!("Object definitions: {}", s4pi_reforged::edit::OBJD_FIELDS.join(", "));
+                println!("String tables:     <key hash>=<text> (adds the string if the key is new)");
+                println!("\nNumbers can be decimal or hex with a 0x prefix.");
+                println!("\nExamples:");
+                println!("  s4pi-reforged set ./my_mod.package 319E4F1D:00000000:00A1B2C3D4E5F601 price=250");
+                println!("  s4pi-reforged set ./my_mod.package 220557DA:80000000:00A1B2C3D4E5F601 0x1A2B3C4D=\"New name\"");
+                return Ok(());
+            }
+            if args.len() < 5 {
+                return Err(anyhow!("Usage: s4pi-reforged set <package> <TGI> <field=value>... [--force]\nTry 's4pi-reforged set --help' for more information."));
+            }
+            let tgi: TGI = args[3].parse()?;
+            let edits = args[4..].iter()
+                .filter(|a| *a != "--force")
+                .map(|a| s4pi_reforged::edit::parse_assignment(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            run_set(Path::new(&args[2]), &tgi, &edits, args.iter().any(|a| a == "--force"))?;
+        }
+        "manifest" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged manifest <subcommand> <merged.package>");
+                println!("\nSubcommands for the manifest that lets a merged package be un-merged.");
+                println!("\nAvailable subcommands:");
+                println!("  upgrade       Rewrites an older manifest with checksums for verification");
+                println!("  verify        Checks every merged resource against its manifest checksum");
+                println!("  describe      Writes a content descriptor: a package's or folder's index and manifest, no data");
+                println!("\nRun 's4pi-reforged manifest <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "upgrade" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged manifest upgrade <merged.package> [--force]");
+                        println!("\nReads the manifest of a package merged by an older version, computes the size");
+                        println!("and hash of every resource it lists, and rewrites the manifest in place in the");
+                        println!("current format, so 'unmerge' and 'manifest verify' can check the package.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged manifest upgrade ./merged/merged.package");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged manifest upgrade <merged.package> [--force]\nTry 's4pi-reforged manifest upgrade --help' for more information."));
+                    }
+                    run_manifest_upgrade(Path::new(&args[3]), args.iter().any(|a| a == "--force"))?;
+                }
+                "verify" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged manifest verify <merged.package>");
+                        println!("\nChecks that every resource listed in the manifest is present and unchanged.");
+                        println!("The outputs of a merge made with --split are checked together.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged manifest verify ./merged/merged.package");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged manifest verify <merged.package>\nTry 's4pi-reforged manifest verify --help' for more information."));
+                    }
+                    run_manifest_verify(Path::new(&args[3]))?;
+                }
+                "describe" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged manifest describe <file-or-folder> [--out <descriptor.package>]");
+                        println!("\nWrites a content descriptor: a small package with a manifest of every package in");
+                        println!("the folder (name, resources and checksums) and an index of their resource keys,");
+                        println!("but none of the resource data. Share it to show what CC you have without sharing");
+                        println!("the CC itself. 'census-diff', 'merge' conflict checks and 'import --new-instances'");
+                        println!("read a descriptor as the packages it describes; the game loads nothing from it.");
+                        println!("\nThe descriptor is written next to the folder as <folder>_descriptor.package,");
+                        println!("or to --out.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged manifest describe ./Mods --out ./my_mod_list.package");
+                        return Ok(());
+                    }
+                    let output = match args.iter().position(|a| a == "--out") {
+                        Some(i) => Some(args.get(i + 1).map(PathBuf::from)
+                            .ok_or_else(|| anyhow!("--out needs a file to write the descriptor to."))?),
+                        None => None,
+                    };
+                    let positional: Vec<&String> = args[3..].iter()
+                        .filter(|a| !a.starts_with("--") && output.as_deref() != Some(Path::new(a.as_str())))
+                        .collect();
+                    let Some(path) = positional.first() else {
+                        return Err(anyhow!("Usage: s4pi-reforged manifest describe <file-or-folder> [--out <descriptor.package>]\nTry 's4pi-reforged manifest describe --help' for more information."));
+                    };
+                    run_manifest_describe(Path::new(path), output.as_deref())?;
+                }
+                _ => {
+                    println!("Unknown manifest subcommand: {}", subcommand);
+                    println!("Available subcommands: upgrade, verify, describe");
+                }
+            }
+        }
+        "install-shell-integration" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged install-shell-integration [--uninstall]");
+                println!("\nAdds right-click actions for the current user: 'Merge packages here' on folders,");
+                println!("and 'Un-merge' and 'Inspect package' on .package files. On Windows these go in the");
+                println!("Explorer context menu; on Linux they are .desktop actions offered by the file");
+                println!("manager's 'Open With' menu. Run it again after moving the executable.");
+                println!("\nExample:");
+                println!("  s4pi-reforged install-shell-integration");
+                return Ok(());
+            }
+            run_shell_integration(args.iter().any(|a| a == "--uninstall"))?;
+        }
+        "texture" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged texture <subcommand> <path>");
+                println!("\nSubcommands for inspecting textures.");
+                println!("\nAvailable subcommands:");
+                println!("  report        Lists each CAS part/object's textures, channels and sizes");
+                println!("\nRun 's4pi-reforged texture <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "report" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged texture report <package>");
+                        println!("\nLists the textures used by every CAS part and object in the package with their");
+                        println!("resolution, mip count and format, which channels (diffuse, shadow, specular,");
+                        println!("normal, emission) they fill, which expected channels are missing, and flags");
+                        println!("textures of 4096 pixels or more.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged texture report ./top.package");
+                        return Ok(());
+                    }
+                    if args.len() < 4 {
+                        return Err(anyhow!("Usage: s4pi-reforged texture report <package>\nTry 's4pi-reforged texture report --help' for more information."));
+                    }
+                    run_texture_report(Path::new(&args[3]))?;
+                }
+                _ => {
+                    println!("Unknown texture subcommand: {}", subcommand);
+                    println!("Available subcommands: report");
+                }
+            }
+        }
+        "mesh" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged mesh <subcommand> <path>");
+                println!("\nSubcommands for inspecting GEOM meshes.");
+                println!("\nAvailable subcommands:");
+                println!("  stats         Reports vertex/polygon counts, bounds and UV ranges per LOD");
+                println!("  lint          Checks bone weights, UVs and faces for common mesh problems");
+                println!("  export        Exports meshes as skinned glTF (.glb) files");
+                println!("  skeleton      Prints a rig's bone tree and IK chains, or exports it as glTF");
+                println!("\nRun 's4pi-reforged mesh <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "stats" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged mesh stats <package> [--max-polys <count>]");
+                        println!("\nLists every GEOM in the package, grouped by the CAS part that uses it, with its");
+                        println!("vertex and polygon counts, bounding box and UV range. With --max-polys, LOD 0 meshes");
+                        println!("over the given polygon budget are flagged.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged mesh stats ./hair.package --max-polys 10000");
+                        return Ok(());
+                    }
+                    let mut max_polys = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--max-polys" {
+                            let value = args.get(i + 1).ok_or_else(|| anyhow!("--max-polys needs a polygon count"))?;
+                            max_polys = Some(value.parse::<usize>().context("--max-polys must be a number")?);
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged mesh stats <package> [--max-polys <count>]\nTry 's4pi-reforged mesh stats --help' for more information."));
+                    }
+                    run_mesh_stats(Path::new(positional[0]), max_polys)?;
+                }
+                "lint" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged mesh lint <package> [--rig <rig file or package>]");
+                        println!("\nChecks every GEOM in the package for the usual causes of exploding or invisible");
+                        println!("CC: bone hashes missing from the rig, weights that don't add up to 1, bone indices");
+                        println!("without a bone, UVs outside 0-1 and degenerate faces. The rig is taken from the");
+                        println!("package itself unless --rig is given; without one, bone hashes aren't checked.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged mesh lint ./top.package --rig ./yfRig.rig");
+                        return Ok(());
+                    }
+                    let mut rig = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--rig" {
+                            rig = args.get(i + 1).map(Path::new);
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged mesh lint <package> [--rig <rig file or package>]\nTry 's4pi-reforged mesh lint --help' for more information."));
+                    }
+                    run_mesh_lint(Path::new(positional[0]), rig)?;
+                }
+                "export" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged mesh export <package> [--rig <rig file or package>] [--lod <n>]");
+                        println!("\nExports one LOD (default 0) of every mesh in the package as a binary glTF (.glb)");
+                        println!("into a 'meshes' directory. With a rig, the skeleton is included and the mesh is");
+                        println!("skinned to it, so it opens in Blender with its armature. Unless --rig is given,");
+                        println!("each mesh uses the rig in the package that its CAS part or GEOM refers to, or the");
+                        println!("package's only rig; with several rigs and no reference, give --rig. A mesh with");
+                        println!("fewer LODs than --lod asks for is exported at its lowest detail, with a warning.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged mesh export ./top.package --rig ./yfRig.rig");
+                        return Ok(());
+                    }
+                    let mut rig = None;
+                    let mut lod = 0;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--rig" {
+                            rig = args.get(i + 1).map(Path::new);
+                            i += 2;
+                        } else if args[i] == "--lod" {
+                            let value = args.get(i + 1).ok_or_else(|| anyhow!("--lod needs a LOD number"))?;
+                            lod = value.parse::<usize>().context("--lod must be a number")?;
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged mesh export <package> [--rig <rig file or package>] [--lod <n>]\nTry 's4pi-reforged mesh export --help' for more information."));
+                    }
+                    run_mesh_export(Path::new(positional[0]), rig, lod)?;
+                }
+                "skeleton" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged mesh skeleton <rig file or package> [--export]");
+                        println!("\nPrints the bone tree of a clear-format RIG (a .rig file, or the first one in a");
+                        println!("package) with each bone's hash, followed by its IK chains. With --export, the");
+                        println!("skeleton is also written as a binary glTF (.glb) holding just the armature, into a");
+                        println!("'meshes' directory next to it.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged mesh skeleton ./yfRig.rig --export");
+                        return Ok(());
+                    }
+                    let positional: Vec<&String> = args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged mesh skeleton <rig file or package> [--export]\nTry 's4pi-reforged mesh skeleton --help' for more information."));
+                    }
+                    run_mesh_skeleton(Path::new(positional[0]), args.iter().any(|a| a == "--export"))?;
+                }
+                _ => {
+                    println!("Unknown mesh subcommand: {}", subcommand);
+                    println!("Available subcommands: stats, lint, export, skeleton");
+                }
+            }
+        }
+        "tray" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged tray <subcommand> <path>");
+                println!("\nSubcommands for inspecting Sims, households and lots saved to the Tray.");
+                println!("\nAvailable subcommands:");
+                println!("  sim           Lists a saved Sim's names, sliders and the CC it needs");
+                println!("  household     Lists the CC needed by each Sim in a saved household");
+                println!("\nRun 's4pi-reforged tray <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "sim" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged tray sim <file> [--mods <folder>]");
+                        println!("\nReads a Sim saved to the Tray (.householdbinary) or a .sim export and lists its name,");
+                        println!("outfits, sculpts, sliders, skin tone and genetics. With --mods, the CAS parts, sculpts,");
+                        println!("sliders and skin tone it uses are looked up in the Mods folder to list the packages the");
+                        println!("Sim requires.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged tray sim ./0x00000001!0x0123456789abcdef.householdbinary --mods ./Mods");
+                        return Ok(());
+                    }
+                    let mut mods = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--mods" {
+                            mods = args.get(i + 1).map(Path::new);
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged tray sim <file> [--mods <folder>]\nTry 's4pi-reforged tray sim --help' for more information."));
+                    }
+                    run_tray_sim(Path::new(positional[0]), mods)?;
+                }
+                "household" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged tray household <file> --mods <folder>");
+                        println!("\nReads a saved household (.householdbinary) and reports, for each Sim in it, the");
+                        println!("packages in the Mods folder that Sim needs, then the total for the household.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged tray household ./0x00000001!0x0123456789abcdef.householdbinary --mods ./Mods");
+                        return Ok(());
+                    }
+                    let mut mods = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--mods" {
+                            mods = args.get(i + 1).map(Path::new);
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    let (Some(file), Some(mods)) = (positional.first(), mods) else {
+                        return Err(anyhow!("Usage: s4pi-reforged tray household <file> --mods <folder>\nTry 's4pi-reforged tray household --help' for more information."));
+                    };
+                    run_tray_household(Path::new(file), mods)?;
+                }
+                _ => {
+                    println!("Unknown tray subcommand: {}", subcommand);
+                    println!("Available subcommands: sim, household");
+                }
+            }
+        }
+        "extract" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged extract <subcommand> <path>");
+                println!("\nSubcommands used for extracting data from merged and unmerged packages.");
+                println!("\nAvailable subcommands:");
+                println!("  all           Extracts every resource (or those picked by --type, --group, --instance");
+                println!("                or --tgi), decompressed, with a file extension to match");
+                println!("  thumbnails    Extracts thumbnail resources (0x3C1AF1F2) as .jpg files");
+                println!("  tuning        Extracts tuning and other XML resources as .xml files");
+                println!("  stbl          Prints the string tables, or extracts them as .json or gettext .po files");
+                println!("  textures      Extracts DST/RLE2/DDS textures as .dds or .png files");
+                println!("  thumbcache    Extracts the game's cached renders of the CC in a Mods folder");
+                println!("\nRun 's4pi-reforged extract <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "all" | "--type" | "--group" | "--instance" | "--tgi" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract all <path> [--out <folder>] [--type <id>] [--group <id>] [--instance <id>] [--tgi <TGI>]");
+                        println!("\nWrites every resource in the package, decompressed, to its own file in a");
+                        println!("'resources' directory next to the package, or the folder given with --out.");
+                        println!("Files are named TTTTTTTT!GGGGGGGG!IIIIIIIIIIIIIIII.ext, which 'import' reads back,");
+                        println!("with an extension for what the resource holds: .xml for tuning, .stbl for string");
+                        println!("tables, .dds, .dst or .rle2 for textures, .jpg or .png for images, and .bin for");
+                        println!("anything else.");
+                        println!("\n--type, --group and --instance (hex with 0x, or decimal) and --tgi only extract the");
+                        println!("resources they match. Repeat an option, or separate IDs with commas, to match any of");
+                        println!("several. With a filter, 'all' can be left out.");
+                        println!("\nExamples:");
+                        println!("  s4pi-reforged extract all ./my_mod.package --out ./my_mod_export");
+                        println!("  s4pi-reforged extract --type 0x034AE111 --instance 0x1234ABCD5678EF00 ./my_cc.package");
+                        return Ok(());
+                    }
+                    let start = if subcommand == "all" { 3 } else { 2 };
+                    let (filter, rest) = TgiFilter::from_args(&args[start..])?;
+                    let output = match rest.iter().position(|a| a == "--out") {
+                        Some(i) => Some(rest.get(i + 1).map(Path::new)
+                            .ok_or_else(|| anyhow!("--out needs a folder to extract into."))?),
+                        None => None,
+                    };
+                    let positional: Vec<&String> = rest.iter()
+                        .filter(|a| !a.starts_with("--") && output != Some(Path::new(a.as_str())))
+                        .collect();
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged extract all <path> [--out <folder>] [--type <id>] [--group <id>] [--instance <id>] [--tgi <TGI>]\nTry 's4pi-reforged extract all --help' for more information."));
+                    }
+                    run_extract_all(Path::new(positional[0]), output, &filter)?;
+                }
+                "thumbnails" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract thumbnails <path> [--png]");
+                        println!("\nExtracts all thumbnail resources from the specified package into a 'thumbs' directory.");
+                        println!("Files are named <package>_<kind>_<instance>, where kind is cas, object, sim, tray,");
+                        println!("or the type and size of a multi-size thumbnail.");
+                        println!("\nThumbnails are written as stored, usually as JPEGs. Many keep a separate alpha");
+                        println!("channel that a JPEG viewer doesn't show; --png combines it with the colors and");
+                        println!("writes PNGs with the transparency instead.");
+                        println!("\nExamples:");
+                        println!("  s4pi-reforged extract thumbnails ./clothes.package");
+                        println!("  s4pi-reforged extract thumbnails ./clothes.package --png");
+                        return Ok(());
+                    }
+                    let Some(path) = args[3..].iter().find(|a| !a.starts_with("--")) else {
+                        return Err(anyhow!("Usage: s4pi-reforged extract thumbnails <path> [--png]\nTry 's4pi-reforged extract thumbnails --help' for more information."));
+                    };
+                    run_extract_thumbnails(Path::new(path), args.iter().any(|a| a == "--png"))?;
+                }
+                "stbl" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract stbl <path> [--json | --po]");
+                        println!("\nPrints every string in the package's string tables, table by table, with its key.");
+                        println!("\nWith --json, each table is written instead to a .json file in a 'strings' directory");
+                        println!("next to the package, named by its TGI. Each string has its key (hex), flags and");
+                        println!("text; edit the text and write the tables back with 'import stbl'.");
+                        println!("\nWith --po, the tables are written as gettext files for translation tools such as");
+                        println!("Poedit or Weblate: a .pot template for each English table and a .po file for each");
+                        println!("translated one. The English text is the msgid and the string's key the msgctxt.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged extract stbl ./my_mod.package --json");
+                        return Ok(());
+                    }
+                    let Some(path) = args[3..].iter().find(|a| !a.starts_with("--")) else {
+                        return Err(anyhow!("Usage: s4pi-reforged extract stbl <path> [--json | --po]\nTry 's4pi-reforged extract stbl --help' for more information."));
+                    };
+                    let format = match (args.iter().any(|a| a == "--json"), args.iter().any(|a| a == "--po")) {
+                        (true, true) => return Err(anyhow!("Give either --json or --po, not both.")),
+                        (true, false) => Some(StringFormat::Json),
+                        (false, true) => Some(StringFormat::Po),
+                        (false, false) => None,
+                    };
+                    run_extract_stbl(Path::new(path), format)?;
+                }
+                "tuning" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract tuning <path> [options]");
+                        println!("\nExtracts all tuning/XML resources from the specified package into a 'tuning' directory.");
+                        println!("\nOptions:");
+                        println!("  --pretty              Indent nested elements, one element per line");
+                        println!("  --sort-attributes     Sort attributes by name");
+                        println!("  --strip-whitespace    Drop whitespace-only text between elements");
+                        println!("  --canonical           All of the above, for output that diffs cleanly");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged extract tuning ./my_mod.package --canonical");
+                        return Ok(());
+                    }
+                    let positional: Vec<&String> = args[3..].iter().filter(|a| !a.starts_with("--")).collect();
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged extract tuning <path> [options]\nTry 's4pi-reforged extract tuning --help' for more information."));
+                    }
+                    let has_flag = |flag: &str| args.iter().any(|a| a == flag);
+                    let options = if has_flag("--canonical") {
+                        s4pi_reforged::tuning::XmlFormatOptions::canonical()
+                    } else {
+                        s4pi_reforged::tuning::XmlFormatOptions {
+                            pretty: has_flag("--pretty"),
+                            sort_attributes: has_flag("--sort-attributes"),
+                            strip_whitespace: has_flag("--strip-whitespace"),
+                        }
+                    };
+                    run_extract_tuning(Path::new(positional[0]), options)?;
+                }
+                "textures" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract textures <path> [--format dds|png] [--max-size <pixels>]");
+                        println!("\nExtracts all texture resources from the specified package into a 'textures' directory,");
+                        println!("one sub-folder per resource type. DST textures are unshuffled and RLE2 textures decoded");
+                        println!("to standard DDS. An index.csv lists each texture with the CASP/OBJD resources that use it.");
+                        println!("\nOptions:");
+                        println!("  --format dds|png      Output format (default: dds)");
+                        println!("  --max-size <pixels>   Only extract the largest mip level no bigger than this, without");
+                        println!("                        decoding the rest of the texture");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged extract textures ./clothes.package --format png --max-size 256");
+                        return Ok(());
+                    }
+                    let mut format = "dds";
+                    let mut max_size = None;
+                    let mut positional = Vec::new();
+                    let mut i = 3;
+                    while i < args.len() {
+                        if args[i] == "--format" {
+                            format = args.get(i + 1).map(|s| s.as_str()).unwrap_or("");
+                            i += 2;
+                        } else if args[i] == "--max-size" {
+                            let value = args.get(i + 1).map(|s| s.as_str()).unwrap_or("");
+                            max_size = Some(value.parse::<u32>()
+                                .map_err(|_| anyhow!("Invalid --max-size '{}'. Expected a size in pixels.", value))?);
+                            i += 2;
+                        } else {
+                            positional.push(&args[i]);
+                            i += 1;
+                        }
+                    }
+                    if positional.is_empty() {
+                        return Err(anyhow!("Usage: s4pi-reforged extract textures <path> [--format dds|png] [--max-size <pixels>]\nTry 's4pi-reforged extract textures --help' for more information."));
+                    }
+                    if format != "dds" && format != "png" {
+                        return Err(anyhow!("Unknown texture format '{}'. Expected 'dds' or 'png'.", format));
+                    }
+                    run_extract_textures(Path::new(positional[0]), format == "png", max_size)?;
+                }
+                "thumbcache" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged extract thumbcache <localthumbcache.package> <mods folder>");
+                        println!("\nMatches the in-game renders stored in the game's localthumbcache.package against");
+                        println!("the CAS parts and objects in the Mods folder, and extracts them into a 'thumbs'");
+                        println!("directory next to the cache. Useful for CC that ships without thumbnails.");
+                        println!("\nExample:");
+                        println!("  s4pi-reforged extract thumbcache \"./The Sims 4/localthumbcache.package\" \"./The Sims 4/Mods\"");
+                        return Ok(());
+                    }
+                    if args.len() < 5 {
+                        return Err(anyhow!("Usage: s4pi-reforged extract thumbcache <localthumbcache.package> <mods folder>\nTry 's4pi-reforged extract thumbcache --help' for more information."));
+                    }
+                    run_extract_thumbcache(Path::new(&args[3]), Path::new(&args[4]))?;
+                }
+                _ => {
+                    println!("Unknown extract subcommand: {}", subcommand);
+                    println!("Available subcommands: all, thumbnails, stbl, tuning, textures, thumbcache");
+                }
+            }
+        }
+        "convert" => {
+            let subcommand = args.get(2).map(|s| s.as_str()).unwrap_or("");
+            if subcommand == "--help" || subcommand.is_empty() {
+                println!("Usage: s4pi-reforged convert <subcommand> <in> <out>");
+                println!("\nSubcommands for converting extracted resources to and from editable formats.");
+                println!("\nAvailable subcommands:");
+                println!("  simdata       Converts SimData to the XML Sims 4 Studio uses, and back");
+                println!("\nRun 's4pi-reforged convert <subcommand> --help' for specific usage info.");
+                return Ok(());
+            }
+            match subcommand {
+                "simdata" => {
+                    if args.iter().any(|a| a == "--help") {
+                        println!("Usage: s4pi-reforged convert simdata <in> <out>");
+                        println!("\nConverts a SimData resource to the XML Sims 4 Studio exports, or such XML back to");
+                        println!("SimData; which way is decided by what <in> holds. The XML lists each instance");
+                        println!("field by field, then the schemas, so SimData can be diffed and edited as text.");
+                        println!("SimData read back from XML is laid out anew, so its bytes can differ from the");
+                        println!("original while its data is the same.");
+                        println!("\nExamples:");
+                        println!("  s4pi-reforged convert simdata ./buff.simdata ./buff.xml");
+                        println!("  s4pi-reforged convert simdata ./buff.xml ./buff.simdata");
+                        return Ok(());
+                    }
+                    if args.len() < 5 {
+                        return Err(anyhow!("Usage: s4pi-reforged convert simdata <in> <out>\nTry 's4pi-reforged convert simdata --help' for more information."));
+                    }
+                    run_convert_simdata(Path::new(&args[3]), Path::new(&args[4]))?;
+                }
+                _ => {
+                    println!("Unknown convert subcommand: {}", subcommand);
+                    println!("Available subcommands: simdata");
+                }
+            }
+        }
+        "investigate" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged investigate <file> [--record <file.jsonl>] [--resource <TGI>]");
+                println!("\nScans a package for resource types and reports known/unknown status, with a");
+                println!("guess at the structure of one resource of each unknown type.");
+                println!("\n--resource takes one resource apart instead: its index entry, how long it takes to");
+                println!("read, decompress and parse, the entropy of its data (overall and block by block),");
+                println!("the TGIs embedded in it and a guess at its structure. The resource is read once");
+                println!("and only a bounded summary of it is kept, so this works on very large resources.");
+                println!("\n--record appends a line per unknown type to a JSON lines file you can share to");
+                println!("help map new formats: the type ID, how many resources there are, their sizes");
+                println!("(rounded to powers of two) and the first 4 bytes of one of them. Nothing else about");
+                println!("the package is recorded.");
+                return Ok(());
+            }
+            let mut record = None;
+            let mut resource = None;
+            let mut positional = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--record" => {
+                        record = args.get(i + 1).map(PathBuf::from);
+                        i += 2;
+                    }
+                    "--resource" => {
+                        let value = args.get(i + 1).ok_or_else(|| anyhow!("--resource needs a TGI (TYPE:GROUP:INSTANCE in hex)."))?;
+                        resource = Some(value.parse::<TGI>()?);
+                        i += 2;
+                    }
+                    arg => {
+                        if !arg.starts_with("--") {
+                            positional.push(&args[i]);
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            let Some(path) = positional.first() else {
+                return Err(anyhow!("Usage: s4pi-reforged investigate <file> [--record <file.jsonl>] [--resource <TGI>]"));
+            };
+            match resource {
+                Some(tgi) => run_investigate_resource(Path::new(path), &tgi)?,
+                None => run_investigate(Path::new(path), record.as_deref())?,
+            }
+        }
+        "import-typemap" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged import-typemap <file.json>");
+                println!("\nAdds community names for resource types this tool has no wrapper for. The file is a");
+                println!("JSON object of hex type IDs to names, e.g. {{\"0x12345678\": \"Fancy Format\"}}. Names");
+                println!("are kept in {} under {} and shown by 'info', 'investigate'",
+                    s4pi_reforged::typemap::REGISTRY_NAME,
+                    if cfg!(windows) { "%APPDATA%\\s4pi-reforged" } else { "~/.config/s4pi-reforged" });
+                println!("and the resource browser. Importing a name for a type that already has one replaces it.");
+                println!("\nExample:");
+                println!("  s4pi-reforged import-typemap ./community-types.json");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged import-typemap <file.json>\nTry 's4pi-reforged import-typemap --help' for more information."));
+            }
+            run_import_typemap(Path::new(&args[2]))?;
+        }
+        "diagnostics" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged diagnostics <file>");
+                println!("\nDumps DBPF header and index entries for structural analysis.");
+                return Ok(());
+            }
+            if args.len() < 3 {
+                return Err(anyhow!("Usage: s4pi-reforged diagnostics <file>"));
+            }
+            run_diagnostics(Path::new(&args[2]))?;
+        }
+        "modlist" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged modlist <folder> [--out <file>]");
+                println!("\nLists every package and script mod in the folder with its size and, for packages,");
+                println!("its number of resources, one tab-separated line per file. Handy for sharing what");
+                println!("you have installed when reporting a problem. Prints the list unless --out is given.");
+                println!("\nExample:");
+                println!("  s4pi-reforged modlist ./Mods --out ./modlist.txt");
+                return Ok(());
+            }
+            let Some(folder) = args.get(2).filter(|a| !a.starts_with("--")) else {
+                return Err(anyhow!("Usage: s4pi-reforged modlist <folder> [--out <file>]\nTry 's4pi-reforged modlist --help' for more information."));
+            };
+            let output = match args.iter().position(|a| a == "--out") {
+                Some(i) => Some(args.get(i + 1).map(Path::new).ok_or_else(|| anyhow!("--out needs a file to write the list to."))?),
+                None => None,
+            };
+            run_modlist(Path::new(folder), output)?;
+        }
+        "run" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged run <recipe.toml> [--dry-run]");
+                println!("\nRuns the commands listed in a recipe file, in order, stopping at the first one");
+                println!("that fails unless it sets keep_going = true. Each [[step]] names a command and its");
+                println!("args, exactly as they would be typed after 's4pi-reforged'. [profiles] holds named");
+                println!("argument lists a step can add with profile = \"name\", and [vars] holds values the");
+                println!("args can use as {{name}}. {{mods}} is the Mods folder and {{recipe_dir}} the folder");
+                println!("the recipe is in, unless [vars] sets them. --dry-run prints the commands instead.");
+                println!("\nA typical routine checks packs, strips unused languages ('stbl strip'), merges,");
+                println!("verifies the result and exports a mod list ('modlist').");
+                println!("\nExample recipe:");
+                println!("  [profiles]");
+                println!("  strict = [\"--on-conflict\", \"error\"]");
+                println!();
+                println!("  [[step]]");
+                println!("  command = \"packs\"");
+                println!("  args = [\"{{mods}}\"]");
+                println!();
+                println!("  [[step]]");
+                println!("  command = \"merge\"");
+                println!("  args = [\"{{mods}}\"]");
+                println!("  profile = \"strict\"");
+                println!();
+                println!("  [[step]]");
+                println!("  command = \"manifest\"");
+                println!("  args = [\"verify\", \"{{mods}}/merged/merged.package\"]");
+                println!();
+                println!("  [[step]]");
+                println!("  command = \"modlist\"");
+                println!("  args = [\"{{mods}}\", \"--out\", \"{{recipe_dir}}/modlist.txt\"]");
+                println!("\nExample:");
+                println!("  s4pi-reforged run ./weekly.toml --dry-run");
+                return Ok(());
+            }
+            let Some(recipe) = args[2..].iter().find(|a| !a.starts_with("--")) else {
+                return Err(anyhow!("Usage: s4pi-reforged run <recipe.toml> [--dry-run]\nTry 's4pi-reforged run --help' for more information."));
+            };
+            run_recipe(Path::new(recipe), args.iter().any(|a| a == "--dry-run"))?;
+        }
+        "schema" => {
+            if args.iter().any(|a| a == "--help") {
+                println!("Usage: s4pi-reforged schema [--out <file.json>]");
+                println!("\nPrints, as JSON, every resource format this tool can read: the resource types");
+                println!("each wrapper handles, the fields it reads them into with their types, the versions");
+                println!("it reads differently and whether it can write them back. The field lists come from");
+                println!("the parser's own structs, so tools and documentation built on them stay in step.");
+                println!("\n--out writes the JSON to a file instead.");
+                println!("\nExample:");
+                println!("  s4pi-reforged schema --out formats.json");
+                return Ok(());
+            }
+            let json = s4pi_reforged::schema::to_json();
+            match args.iter().position(|a| a == "--out") {
+                Some(i) => {
+                    let output = args.get(i + 1).ok_or_else(|| anyhow!("--out needs a file to write the schema to."))?;
+                    s4pi_reforged::atomic::write(output, json).with_context(|| format!("Failed to write {:?}", output))?;
+                    info!("Wrote the schema of {} resource formats to {:?}", s4pi_reforged::schema::formats().len(), output);
+                }
+                None => println!("{}", json),
+            }
+        }
+        "--help" | "-h" | "help" => {
+            println!("S4PI Package Tool");
+            println!("\nUsage: s4pi-reforged <command> [args]");
+            println!("\nAvailable commands:");
+            println!("  merge       Merge multiple packages into one");
+            println!("  unmerge     Split a merged package into original files");
+            println!("  manifest    Upgrade or verify the manifest of a merged package");
+            println!("  import      Build a package from an s4pe/S4S export folder");
+            println!("  overrides   Report Mods resources that override game content");
+            println!("  conflicts   Report resources more than one Mods package contains");
+            println!("  recompress  Convert RefPack-compressed resources to zlib");
+            println!("  packs       Report the game packs each package's content needs");
+            println!("  import-typemap  Add community names for unknown resource types");
+            println!("  census-diff Compare the resources in two Mods folder snapshots");
+            println!("  validate    Check packages for damage before sharing them");
+            println!("  repair      Write a fixed copy of a damaged package");
+            println!("  devwatch    Re-check a package you're building every time it's saved");
+            println!("  retarget    Point tuning references at a different tuning instance");
+            println!("  selftest    Check this build reads and writes packages correctly");
+            println!("  install     Install the packages and scripts in a CC download into Mods");
+            println!("  backup      Back up the Mods folder, or restore an earlier backup");
+            println!("  stbl        String table tools (e.g., pseudo-localization)");
+            println!("  info        Parse and print a single resource");
+            println!("  cat         Write a single resource's bytes to stdout");
+            println!("  set         Edit fields of a single resource in place");
+            println!("  tray        Inspect Tray files (e.g., CC needed by a saved Sim)");
+            println!("  poses       List the animation clips in a pose pack");
+            println!("  mesh        Inspect GEOM meshes (e.g., polygon counts per LOD)");
+            println!("  texture     Inspect textures (e.g., channel usage per CAS part)");
+            println!("  extract     Extract specific resource types (e.g., thumbnails)");
+            println!("  convert     Convert extracted resources to and from text (e.g., SimData to XML)");
+            println!("  install-shell-integration  Add right-click menu actions for folders and packages");
+            println!("  run         Run the commands listed in a recipe file");
+            println!("  schema      Print the resource formats this tool reads, as JSON");
+            println!("  modlist     List the packages and script mods in a folder");
+            if debug {
+                println!("  investigate Scan for resource types (Debug)");
+                println!("  diagnostics Dump DBPF metadata (Debug)");
+            }
+            println!("\nRun 's4pi-reforged <command> --help' for more information on a specific command.");
+            return Ok(());
+        }
+        _ => {
+            println!("Unknown command: {}", cmd);
+            println!("Available commands: {}{}", COMMANDS.join(", "), if debug { ", investigate, diagnostics" } else { "" });
+            println!("Run 's4pi-reforged --help' for usage information.");
+        }
+    }
+    Ok(())
+}
+
+/// Stops a command that rewrites `paths` when another program has one of them open, unless
+/// `force` is set
+fn ensure_not_in_use(paths: &[PathBuf], force: bool) -> Result<()> {
+    let in_use = s4pi_reforged::inuse::find_in_use(paths);
+    if in_use.is_empty() {
+        return Ok(());
+    }
+    if force {
+        for file in &in_use {
+            warn!("{}; writing to it anyway because of --force.", file);
+        }
+        return Ok(());
+    }
+    let files: Vec<String> = in_use.iter().map(ToString::to_string).collect();
+    Err(anyhow!("{}.\nWriting to a package another program has open can corrupt it. Close that program (is The Sims 4 running?) and try again, or add --force to go ahead anyway.", files.join("\n")))
+}
+
+/// Runs the steps of a recipe through [`run_command`], or just prints them with `dry_run`
+fn run_recipe(path: &Path, dry_run: bool) -> Result<()> {
+    let mut recipe = s4pi_reforged::recipe::Recipe::load(path)?;
+    if !recipe.vars.contains_key("mods") {
+        if let Some(mods) = s4pi_reforged::install::find_mods_folder() {
+            recipe.vars.insert("mods".to_string(), mods.to_string_lossy().into_owned());
+        }
+    }
+    // Check every step before running any, so a typo late in the recipe doesn't stop it halfway
+    let mut commands = Vec::with_capacity(recipe.steps.len());
+    for (i, step) in recipe.steps.iter().enumerate() {
+        let known = COMMANDS.contains(&step.command.as_str()) || ["investigate", "diagnostics"].contains(&step.command.as_str());
+        if !known || step.command == "run" {
+            return Err(anyhow!("Step {} runs {:?}, which can't be used in a recipe", i + 1, step.command));
+        }
+        let mut args = vec!["s4pi-reforged".to_string(), step.command.clone()];
+        args.extend(recipe.args(step).with_context(|| format!("Step {}", i + 1))?);
+        commands.push(args);
+    }
+
+    let total = commands.len();
+    let mut failed = 0;
+    for (i, (step, args)) in recipe.steps.iter().zip(&commands).enumerate() {
+        let line = args.join(" ");
+        let label = step.name.as_deref().unwrap_or(&line);
+        if dry_run {
+            println!("[{}/{}] {}", i + 1, total, line);
+            continue;
+        }
+        info!("[{}/{}] {}", i + 1, total, label);
+        if let Err(e) = run_command(args) {
+            failed += 1;
+            if !step.keep_going {
+                return Err(e.context(format!("Step {} ({}) failed", i + 1, label)));
+            }
+            warn!("Step {} ({}) failed: {:#}. Carrying on.", i + 1, label, e);
+        }
+    }
+    if !dry_run {
+        info!("Recipe finished: {} step(s), {} failed.", total, failed);
+    }
+    Ok(())
+}
+
+/// Installs the GUI logger and runs the window, optionally opening `package` in the resource browser
+fn run_gui(log_buffer: Arc<Mutex<String>>, package: Option<PathBuf>) -> Result<()> {
+    let writer = LogWriter { buffer: Arc::clone(&log_buffer) };
+    let mut console = env_logger::Builder::new();
+    console
+        .filter_level(log::LevelFilter::Off) // Default to off
+        .filter_module("s4pi_merge", log::LevelFilter::Info)
+        .filter_module("s4pi_reforged", log::LevelFilter::Info)
+        .target(env_logger::Target::Pipe(Box::new(writer)));
+    s4pi_reforged::logging::init(console, &s4pi_reforged::logging::default_log_dir());
+
+    let native_options = eframe::NativeOptions::default();
+    eframe::run_native(
+        "S4PI Tool",
+        native_options,
+        Box::new(move |cc| {
+            let mut app = GuiApp::new(cc, log_buffer);
+            if let Some(path) = package {
+                app.open_browser(&path);
+            }
+            Ok(Box::new(app))
+        }),
+    ).map_err(|e| anyhow!("GUI Error: {:?}", e))
+}
+
+/// True when the arguments are just one existing .package file, as when a package is opened
+/// with the executable from a file manager or dropped onto it
+fn is_package_launch(args: &[String]) -> bool {
+    args.len() == 2
+        && Path::new(&args[1]).is_file()
+        && Path::new(&args[1]).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("package"))
+}
+
+fn run_diagnostics(path: &Path) -> Result<()> {
+    info!("Running Diagnostics: {:?}", path);
+    let pkg = Package::open(path)?;
+
+    println!("Package: {}", path.display());
+    println!("Header: {:?}", pkg.header);
+    println!("Index Count: {}", pkg.entries.len());
+    let integrity = pkg.check_obfuscation();
+    println!("Integrity: {}", integrity.summary());
+    for (entry, anomaly) in &integrity.anomalies {
+        println!("  {} at 0x{:08X}: {}", entry.tgi, entry.offset, anomaly);
+    }
+    match Provenance::read(&pkg) {
+        Ok(Some(provenance)) => print!("\n--- Provenance ---\n{}", provenance),
+        Ok(None) => {}
+        Err(e) => warn!("Could not read provenance record: {:#}", e),
+    }
+
+    for (i, entry) in pkg.entries.iter().enumerate() {
+        if i < 20 || i >= pkg.entries.len() - 5 || entry.tgi.res_type == 0x7FB6AD8A || entry.tgi.res_type == 0x73E93EEB {
+            println!("\nEntry {}:", i);
+            println!("  TGI: {:08X}:{:08X}:{:016X}", entry.tgi.res_type, entry.tgi.res_group, entry.tgi.instance);
+            println!("  Offset: 0x{:08X}", entry.offset);
+            println!("  Filesize: {} (0x{:08X})", entry.filesize, entry.filesize);
+            println!("  Memsize: {} (0x{:08X})", entry.memsize, entry.memsize);
+            println!("  Compression: 0x{:04X}", entry.compression);
+            println!("  Committed: 0x{:04X}", entry.committed);
+
+            let mut file = std::fs::File::open(path)?;
+            use std::io::{Seek, SeekFrom, Read};
+            file.seek(SeekFrom::Start(entry.offset as u64))?;
+            let mut head = [0u8; 8];
+            file.read_exact(&mut head)?;
+            println!("  Data Head: {:02X?}", head);
+        } else if i == 20 {
+            println!("\n... skipping intermediate entries ...");
+        }
+    }
+
+    let report = pkg.compression_report();
+    let percent = |count: usize| count as f32 / report.total.count.max(1) as f32 * 100.0;
+    let uncompressed = report.uncompressed();
+    println!("\n--- Compression Summary ---");
+    println!("Total Entries: {}", report.total.count);
+    println!("Compressed: {} ({:.2}%)", report.total.count - uncompressed.count, percent(report.total.count - uncompressed.count));
+    println!("Uncompressed: {} ({:.2}%)", uncompressed.count, percent(uncompressed.count));
+
+    println!("\nBy scheme:");
+    for (scheme, totals) in &report.by_scheme {
+        println!("  {:<14} {:>6} resources, {:>10} bytes stored, {:>10} bytes in memory ({:.0}%)",
+            scheme.to_string(), totals.count, totals.stored_bytes, totals.memory_bytes, totals.ratio() * 100.0);
+    }
+    println!("\nBy type:");
+    for (res_type, by_type) in &report.by_type {
+        let schemes: Vec<String> = by_type.by_scheme.iter().map(|(scheme, t)| format!("{} {}", t.count, scheme)).collect();
+        println!("  {:08X} {:>6} resources, {:>10} bytes stored, {:>10} bytes in memory ({})",
+            res_type, by_type.total.count, by_type.total.stored_bytes, by_type.total.memory_bytes, schemes.join(", "));
+    }
+
+    let uncompressed_entries: Vec<_> = pkg.entries.iter().enumerate()
+        .filter(|(_, entry)| !entry.is_compressed())
+        .collect();
+    if !uncompressed_entries.is_empty() {
+        println!("\nUncompressed Samples (up to 10):");
+        for (i, entry) in uncompressed_entries.iter().take(10) {
+            let tgi = entry.tgi;
+            println!("  Entry {}: TGI: {:08X}:{:08X}:{:016X}, Size: {}", i, tgi.res_type, tgi.res_group, tgi.instance, entry.memsize);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_investigate_resource(path: &Path, tgi: &TGI) -> Result<()> {
+    const MAX_TGIS: usize = 50;
+    const MAX_REGIONS: usize = 40;
+
+    let pkg = Package::open(path)?;
+    let report = pkg.investigate_resource(tgi)?;
+    let entry = &report.entry;
+
+    println!("Resource {} ({})", entry.tgi, kind_label(entry.tgi.res_type));
+    println!("\n--- Index Entry ---");
+    println!("  Offset: 0x{:08X}", entry.offset);
+    println!("  Stored size: {} ({} bytes)", perf::format_bytes(report.stored_len as u64), report.stored_len);
+    println!("  Size in index: {} ({} bytes)", perf::format_bytes(entry.memsize as u64), entry.memsize);
+    println!("  Compression: {} (0x{:04X}) | Committed: {}", entry.scheme(), entry.compression, entry.committed);
+    if let Some(magic) = &report.magic {
+        println!("  Magic: \"{}\"", magic);
+    }
+
+    println!("\n--- Timing ---");
+    println!("  Read: {:.2?}", report.read_time);
+    match &report.decompressed {
+        Ok(len) => {
+            let rate = *len as f64 / report.decompress_time.as_secs_f64().max(1e-9);
+            println!("  Decompress: {:.2?} to {} bytes ({}/s)", report.decompress_time, len, perf::format_bytes(rate as u64));
+            if *len != entry.memsize as usize {
+                println!("  Warning: the index says {} bytes", entry.memsize);
+            }
+        }
+        Err(e) => println!("  Decompress: failed after {:.2?}: {}", report.decompress_time, e),
+    }
+    if report.decompressed.is_ok() {
+        match (&report.parse_error, report.kind) {
+            (None, s4pi_reforged::package::resource::ResourceKind::Generic) => println!("  Parse: no wrapper for this type"),
+            (None, _) => println!("  Parse: {:.2?}, OK", report.parse_time),
+            (Some(e), _) => println!("  Parse: failed after {:.2?}: {}", report.parse_time, e),
+        }
+    }
+
+    println!("\n--- Entropy (bits per byte) ---");
+    println!("  Stored: {:.2} | Decompressed: {:.2}", report.stored_entropy, report.entropy);
+    if report.entropy_blocks.len() > 1 {
+        for block in &report.entropy_blocks {
+            let bar = "#".repeat((block.bits * 4.0).round() as usize);
+            println!("  {:08X}  {:>9}  {:>4.2}  {}", block.offset, perf::format_bytes(block.len as u64), block.bits, bar);
+        }
+    }
+
+    println!("\n--- Embedded TGIs ({}) ---", report.tgi_count);
+    for (offset, tgi, itg) in report.tgis.iter().take(MAX_TGIS) {
+        let order = if *itg { " (stored instance, type, group)" } else { "" };
+        let here = if pkg.find(tgi).is_some() { ", in this package" } else { "" };
+        println!("  {:08X}  {}  {}{}{}", offset, tgi, kind_label(tgi.res_type), order, here);
+    }
+    if report.tgi_count > MAX_TGIS {
+        println!("  ... {} more", report.tgi_count - MAX_TGIS);
+    }
+
+    if !report.head.is_empty() {
+        println!("\n--- First {} bytes ---", report.head.len());
+        print!("{}", hexdump(&report.head));
+    }
+    if !report.structure.is_empty() {
+        println!("\n--- Structure guess ---");
+        for region in report.structure.iter().take(MAX_REGIONS) {
+            println!("  {}", region);
+        }
+        if report.structure.len() > MAX_REGIONS {
+            println!("  ... {} more regions", report.structure.len() - MAX_REGIONS);
+        }
+    }
+    Ok(())
+}
+
+fn run_investigate(path: &Path, record: Option<&Path>) -> Result<()> {
+    use s4pi_reforged::package::investigate::TypeStatus;
+
+    info!("Investigating: {:?}", path);
+    let mut pkg = Package::open(path)?;
+    info!("Found {} resources.", pkg.entries.len());
+    let integrity = pkg.check_obfuscation();
+    if !integrity.is_clean() {
+        const MAX_ANOMALIES: usize = 20;
+        println!("Integrity: {}", integrity.summary());
+        for (entry, anomaly) in integrity.anomalies.iter().take(MAX_ANOMALIES) {
+            println!("  {} at 0x{:08X}: {}", entry.tgi, entry.offset, anomaly);
+        }
+        if integrity.anomalies.len() > MAX_ANOMALIES {
+            println!("  ... {} more", integrity.anomalies.len() - MAX_ANOMALIES);
+        }
+        println!("Investigating the {} recovered resources.", integrity.recovered.len());
+        pkg.recover(&integrity);
+    }
+    let report = pkg.investigate();
+
+    for (entry, manifest) in &report.manifests {
+        println!("\n--- Manifest Found (Type: 0x{:08X}) ---", entry.tgi.res_type);
+        println!("  Version: {}", manifest.version);
+        println!("  Entries: {}", manifest.entries.len());
+        for (i, entry) in manifest.entries.iter().enumerate() {
+            println!("    [{:>2}] Name: \"{}\"", i + 1, entry.name);
+            println!("         Resources: {}", entry.resources.len());
+        }
+        println!("----------------------------------------\n");
+    }
+
+    let failures = FailureReport::new();
+    for failure in &report.failures {
+        let error = s4pi_reforged::Error::Other(failure.error.clone());
+        failures.add(FailureContext::new(path, Some(&pkg.header), Some(&failure.entry), &error, Some(&failure.head)));
+    }
+    write_failure_report(&failures, "investigate");
+
+    println!("\nResource Type Summary:");
+    for (res_type, summary) in &report.types {
+        let status = match summary.status {
+            TypeStatus::Failed => format!("FAILED ({} errors)", summary.errors.len()),
+            TypeStatus::Unknown => "UNKNOWN".to_string(),
+            TypeStatus::Known => "KNOWN".to_string(),
+        };
+        match (&summary.tuning_class, type_names().name_of(*res_type)) {
+            (Some(class), _) => println!("  Type: 0x{:08X} | Count: {:>5} | Status: {} | Tuning: {}", res_type, summary.count, status, class),
+            (None, Some(name)) if summary.status != TypeStatus::Known => {
+                println!("  Type: 0x{:08X} | Count: {:>5} | Status: {} | Community name: {}", res_type, summary.count, status, name);
+            }
+            (None, _) => println!("  Type: 0x{:08X} | Count: {:>5} | Status: {}", res_type, summary.count, status),
+        }
+
+        if let Some(sample) = &summary.sample {
+            println!("    Size: {} bytes", sample.entry.memsize);
+            let hex: Vec<String> = sample.head.iter().map(|b| format!("{:02X}", b)).collect();
+            println!("    Sample Hex: {}", hex.join(" "));
+            let ascii: String = sample.head.iter().map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }
+            }).collect();
+            println!("    Sample ASCII: \"{}\"", ascii);
+            if !sample.structure.is_empty() {
+                const MAX_REGIONS: usize = 20;
+                println!("    Structure guess:");
+                for region in sample.structure.iter().take(MAX_REGIONS) {
+                    println!("      {}", region);
+                }
+                if sample.structure.len() > MAX_REGIONS {
+                    println!("      ... {} more regions", sample.structure.len() - MAX_REGIONS);
+                }
+            }
+        }
+    }
+
+    if let Some(record) = record {
+        let records = s4pi_reforged::typemap::unknown_type_records(&pkg, &report);
+        s4pi_reforged::typemap::append_records(record, &records)?;
+        info!("Recorded {} unknown type(s) in {:?}.", records.len(), record);
+    }
+
+    let failed: Vec<_> = report.types.iter().filter(|(_, s)| !s.errors.is_empty()).collect();
+    if !failed.is_empty() {
+        println!("\nParse Error Samples (one per type):");
+        for (res_type, summary) in failed {
+            println!("  0x{:08X}: {}", res_type, summary.errors[0].lines().next().unwrap_or("Unknown error"));
+        }
+    }
+
+    let unknown_types: Vec<u32> = report.unknown_types().collect();
+    if !unknown_types.is_empty() {
+        println!("\nCandidates for Manifest (Unknown/Failed Types):");
+        for res_type in unknown_types {
+            println!("  0x{:08X}", res_type);
+        }
+    } else {
+        println!("\nAll resource types are known and parsed successfully.");
+    }
+
+    Ok(())
+}
+
+fn run_import_typemap(file: &Path) -> Result<()> {
+    use s4pi_reforged::typemap::{registry_dir, TypeMap, REGISTRY_NAME};
+
+    let data = std::fs::read(file).with_context(|| format!("Failed to read {:?}", file))?;
+    let imported = TypeMap::from_json(&data).with_context(|| format!("Failed to read {:?}", file))?;
+    let registry = registry_dir().context("Could not find a folder to keep type names in")?.join(REGISTRY_NAME);
+    let mut names = TypeMap::load(&registry)?;
+    let report = names.import(&imported);
+    names.save(&registry)?;
+    info!("Imported {} type name(s): {} new, {} renamed, {} unchanged. {} name(s) are now in {:?}.",
+        imported.names.len(), report.added, report.renamed, report.unchanged, names.names.len(), registry);
+    Ok(())
+}
+
+fn run_extract_thumbnails(path: &Path, as_png: bool) -> Result<()> {
+    info!("Extracting thumbnails from: {:?}", path);
+    let timer = PerfTimer::start("extract thumbnails");
+    let pkg = Package::open(path)?;
+
+    let entries: Vec<_> = pkg.entries.iter()
+        .filter_map(|e| ThumbnailKind::of(e.tgi.res_type).map(|kind| (e.clone(), kind)))
+        .collect();
+
+    if entries.is_empty() {
+        info!("No thumbnail resources found in package.");
+        return Ok(());
+    }
+
+    info!("Found {} thumbnails.", entries.len());
+
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("thumbs");
+    std::fs::create_dir_all(&output_dir).context("Failed to create thumbs directory")?;
+
+    // Try to find manifest to get original package names
+    let manifest_entry = pkg.entries.iter().find(|e| e.tgi.res_type == 0x7FB6AD8A || e.tgi.res_type == 0x73E93EEB).cloned();
+    let mut tgi_to_name = HashMap::new();
+    if let Some(me) = manifest_entry {
+        if let Ok(TypedResource::Manifest(m)) = pkg.read_resource(&me) {
+            for entry in m.entries {
+                for tgi in entry.resources {
+                    tgi_to_name.insert(tgi, entry.name.clone());
+                }
+            }
+        }
+    }
+
+    let package_name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+    entries.par_iter().try_for_each(|(entry, kind)| perf::busy(|| -> Result<()> {
+        let mut data = pkg.read_raw_resource(entry)?;
+        let mut extension = s4pi_reforged::thumbcache::thumbnail_extension(&data);
+        if as_png && extension == "jpg" {
+            match s4pi_reforged::palette::decode_thumbnail(&data).and_then(|image| image.to_png()) {
+                Ok(png) => {
+                    data = png;
+                    extension = "png";
+                }
+                Err(e) => warn!("Failed to convert thumbnail {} to PNG: {}. Writing the JPEG.", entry.tgi, e),
+            }
+        }
+
+        let name_base = tgi_to_name.get(&entry.tgi).cloned().unwrap_or_else(|| package_name.clone());
+        let filename = format!("{}_{}_{:016X}.{}", name_base, kind.label(), entry.tgi.instance, extension);
+        let out_path = output_dir.join(filename);
+        
+        perf::write_file(out_path, data)?;
+        Ok(())
+    }))?;
+
+    info!("Thumbnail extraction complete! Files are in: {:?}", output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+fn run_extract_thumbcache(cache_path: &Path, mods_folder: &Path) -> Result<()> {
+    info!("Reading thumbnail cache: {:?}", cache_path);
+    let timer = PerfTimer::start("extract thumbcache");
+    let cache = s4pi_reforged::thumbcache::ThumbCache::open(cache_path)?;
+    info!("Found {} cached items.", cache.thumbnails.len());
+
+    let matches = s4pi_reforged::thumbcache::match_mods(&cache, mods_folder);
+    if matches.is_empty() {
+        info!("No CC in {:?} has a cached thumbnail.", mods_folder);
+        return Ok(());
+    }
+
+    let output_dir = cache_path.parent().unwrap_or(Path::new(".")).join("thumbs");
+    std::fs::create_dir_all(&output_dir).context("Failed to create thumbs directory")?;
+
+    let mut written = HashSet::new();
+    let mut without_own = 0;
+    for m in &matches {
+        if !m.has_own_thumbnail {
+            without_own += 1;
+        }
+        if !written.insert((m.package.clone(), m.item.instance)) {
+            continue;
+        }
+        let Some(data) = cache.read_best(m.item.instance)? else { continue };
+        let package_name = m.package.file_stem().unwrap_or_default().to_string_lossy();
+        let filename = format!("{}_{:016X}.{}", package_name, m.item.instance, s4pi_reforged::thumbcache::thumbnail_extension(&data));
+        perf::write_file(output_dir.join(filename), data)?;
+    }
+
+    info!("Extracted {} cached thumbnails ({} for items without their own thumbnail).", written.len(), without_own);
+    info!("Files are in: {:?}", output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+fn run_extract_all(path: &Path, output: Option<&Path>, filter: &TgiFilter) -> Result<()> {
+    if filter.is_empty() {
+        info!("Extracting every resource from: {:?}", path);
+    } else {
+        info!("Extracting matching resources from: {:?}", path);
+    }
+    let timer = PerfTimer::start("extract all");
+    let pkg = Package::open(path)?;
+
+    let entries = pkg.entries.iter().filter(|e| filter.matches(&e.tgi)).count();
+    if entries == 0 {
+        info!("No matching resources found in package.");
+        return Ok(());
+    }
+
+    let output_dir = match output {
+        Some(dir) => dir.to_path_buf(),
+        None => path.parent().unwrap_or(Path::new(".")).join("resources"),
+    };
+    let summary = s4pi_reforged::import::extract_resources(&pkg, filter, &output_dir, &LogObserver::default())?;
+    info!("Extracted {} of {} resources. Files are in: {:?}", summary.extracted, entries, output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+/// What `extract stbl` writes string tables as
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StringFormat {
+    Json,
+    /// A gettext template per English table and a catalogue per translation
+    Po,
+}
+
+fn run_extract_stbl(path: &Path, format: Option<StringFormat>) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let mut tables: Vec<(TGI, s4pi_reforged::StblResource)> = pkg.entries.iter()
+        .filter(|e| s4pi_reforged::stbl::is_stbl_type(e.tgi.res_type))
+        .filter_map(|e| match pkg.read_resource(e) {
+            Ok(TypedResource::Stbl(stbl)) => Some((e.tgi, stbl)),
+            Ok(_) => None,
+            Err(err) => {
+                warn!("Could not read string table {}: {:#}", e.tgi, err);
+                None
+            }
+        })
+        .collect();
+    if tables.is_empty() {
+        info!("No string tables found in package.");
+        return Ok(());
+    }
+    tables.sort_by_key(|(tgi, _)| (tgi.instance, tgi.res_group));
+
+    let Some(format) = format else {
+        for (tgi, stbl) in &tables {
+            let language = locale_of(tgi.instance).map_or("unknown language", Locale::name);
+            println!("\n{} ({}, {} strings)", tgi, language, stbl.entries.len());
+            for string in &stbl.entries {
+                println!("  0x{:08X}  {}", string.key_hash, string.string_value.escape_debug());
+            }
+        }
+        return Ok(());
+    };
+
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("strings");
+    std::fs::create_dir_all(&output_dir).with_context(|| format!("Failed to create {:?}", output_dir))?;
+    let english: HashMap<TGI, &s4pi_reforged::StblResource> = tables.iter()
+        .filter(|(tgi, _)| locale_of(tgi.instance) == Some(Locale::English))
+        .map(|(tgi, stbl)| (*tgi, stbl))
+        .collect();
+    for (tgi, stbl) in &tables {
+        let name = format!("{:08X}!{:08X}!{:016X}", tgi.res_type, tgi.res_group, tgi.instance);
+        let (filename, contents) = match format {
+            StringFormat::Json => (format!("{}.json", name), s4pi_reforged::stbl::table_to_json(tgi, stbl)),
+            StringFormat::Po if english.contains_key(tgi) => (format!("{}.pot", name), s4pi_reforged::stbl::table_to_po(tgi, stbl, None)),
+            StringFormat::Po => {
+                // Translated against the English table, when the package has it
+                let source = english.get(&TGI { instance: with_locale(tgi.instance, Locale::English), ..*tgi }).copied().unwrap_or(stbl);
+                (format!("{}.po", name), s4pi_reforged::stbl::table_to_po(tgi, source, Some(stbl)))
+            }
+        };
+        perf::write_file(output_dir.join(filename), contents)?;
+    }
+    info!("Extracted {} string tables ({} strings). Files are in: {:?}",
+        tables.len(), tables.iter().map(|(_, stbl)| stbl.entries.len()).sum::<usize>(), output_dir);
+    Ok(())
+}
+
+fn run_extract_tuning(path: &Path, options: s4pi_reforged::tuning::XmlFormatOptions) -> Result<()> {
+    info!("Extracting tuning from: {:?}", path);
+    let timer = PerfTimer::start("extract tuning");
+    let pkg = Package::open(path)?;
+
+    let entries: Vec<_> = pkg.entries.iter()
+        .filter(|e| matches!(
+            TypedResource::kind_of(e.tgi.res_type),
+            s4pi_reforged::package::resource::ResourceKind::Text | s4pi_reforged::package::resource::ResourceKind::Xml
+        ))
+        .cloned()
+        .collect();
+
+    if entries.is_empty() {
+        info!("No tuning resources found in package.");
+        return Ok(());
+    }
+
+    info!("Found {} tuning resources.", entries.len());
+
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("tuning");
+    std::fs::create_dir_all(&output_dir).context("Failed to create tuning directory")?;
+
+    entries.par_iter().try_for_each(|entry| perf::busy(|| -> Result<()> {
+        let data = pkg.read_raw_resource(entry)?;
+        let base = format!("{:08X}!{:08X}!{:016X}", entry.tgi.res_type, entry.tgi.res_group, entry.tgi.instance);
+
+        if !s4pi_reforged::tuning::looks_like_xml(&data) {
+            perf::write_file(output_dir.join(format!("{}.binary", base)), data)?;
+            return Ok(());
+        }
+
+        let text = String::from_utf8_lossy(&data);
+        let formatted = match s4pi_reforged::tuning::format_xml(&text, &options) {
+            Ok(f) => f,
+            Err(e) => {
+                warn!("Could not reformat {:?}, writing it unchanged: {}", entry.tgi, e);
+                text.to_string()
+            }
+        };
+
+        // Sims 4 Studio naming (TGI!...!.Name.Type.xml) so the folder can be re-imported as-is
+        let filename = match s4pi_reforged::tuning::tuning_name(&text) {
+            Some(name) => format!("{}.{}.Tuning.xml", base, sanitize_filename(&name)),
+            None => format!("{}.xml", base),
+        };
+        perf::write_file(output_dir.join(filename), formatted)?;
+        Ok(())
+    }))?;
+
+    info!("Tuning extraction complete! Files are in: {:?}", output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+fn run_extract_textures(path: &Path, as_png: bool, max_size: Option<u32>) -> Result<()> {
+    use s4pi_reforged::texture;
+
+    info!("Extracting textures from: {:?}", path);
+    let timer = PerfTimer::start("extract textures");
+    let pkg = Package::open(path)?;
+
+    let entries: Vec<_> = pkg.entries.iter()
+        .filter(|e| texture::is_texture_type(e.tgi.res_type))
+        .cloned()
+        .collect();
+
+    if entries.is_empty() {
+        info!("No texture resources found in package.");
+        return Ok(());
+    }
+
+    info!("Found {} texture resources.", entries.len());
+    let owners = texture::find_texture_owners(&pkg);
+
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("textures");
+    std::fs::create_dir_all(&output_dir).context("Failed to create textures directory")?;
+
+    let rows: Vec<String> = entries.par_iter().map(|entry| perf::busy(|| -> Result<String> {
+        let data = pkg.read_raw_resource(entry)?;
+        let type_dir = texture::texture_type_name(entry.tgi.res_type);
+        let base = format!("{:08X}!{:08X}!{:016X}", entry.tgi.res_type, entry.tgi.res_group, entry.tgi.instance);
+        std::fs::create_dir_all(output_dir.join(type_dir))?;
+
+        let dds = match max_size {
+            Some(max_size) => texture::extract_preview(&data, max_size),
+            None => texture::to_dds(&data),
+        };
+        let (file, format, width, height, mips) = match dds {
+            Ok(dds) => {
+                let dds_info = texture::parse_dds_header(&dds)?;
+                let png = if as_png {
+                    match texture::decode_dds(&dds).and_then(|img| img.to_png()) {
+                        Ok(png) => Some(png),
+                        Err(e) => {
+                            warn!("Could not convert {:?} to PNG, writing DDS instead: {}", entry.tgi, e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let file = match png {
+                    Some(png) => {
+                        let file = format!("{}/{}.png", type_dir, base);
+                        perf::write_file(output_dir.join(&file), png)?;
+                        file
+                    }
+                    None => {
+                        let file = format!("{}/{}.dds", type_dir, base);
+                        perf::write_file(output_dir.join(&file), &dds)?;
+                        file
+                    }
+                };
+                (file, dds_info.format_name(), dds_info.width, dds_info.height, dds_info.mip_count)
+            }
+            Err(e) => {
+                warn!("Could not decode {:?}, writing it unchanged: {}", entry.tgi, e);
+                let extension = type_dir.to_lowercase();
+                let file = format!("{}/{}.{}", type_dir, base, extension);
+                perf::write_file(output_dir.join(&file), &data)?;
+                (file, type_dir.to_string(), 0, 0, 0)
+            }
+        };
+
+        let owner_list = owners.get(&entry.tgi)
+            .map(|list| list.iter()
+                .map(|t| format!("{:08X}!{:08X}!{:016X}", t.res_type, t.res_group, t.instance))
+                .collect::<Vec<_>>()
+                .join(";"))
+            .unwrap_or_default();
+        Ok(format!("{},{},{},{},{},{},{},{}", base, type_dir, format, width, height, mips, file, owner_list))
+    })).collect::<Result<Vec<_>, _>>()?;
+
+    let mut index = String::from("tgi,type,format,width,height,mips,file,owners\n");
+    for row in rows {
+        index.push_str(&row);
+        index.push('\n');
+    }
+    perf::write_file(output_dir.join("index.csv"), index)?;
+
+    info!("Texture extraction complete! Files are in: {:?}", output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+/// Converts SimData to XML, or XML back to SimData, depending on what `input` holds
+fn run_convert_simdata(input: &Path, output: &Path) -> Result<()> {
+    use s4pi_reforged::package::resource::Resource;
+    use s4pi_reforged::simdata;
+
+    let data = std::fs::read(input).with_context(|| format!("Failed to read {:?}", input))?;
+    if simdata::is_simdata(&data) {
+        let resource = s4pi_reforged::SimDataResource::from_bytes(&data)
+            .with_context(|| format!("Failed to read the SimData in {:?}", input))?;
+        let xml = simdata::to_xml(&resource)?;
+        s4pi_reforged::atomic::write(output, xml).with_context(|| format!("Failed to write {:?}", output))?;
+        info!("Wrote SimData {:?} as XML to {:?}", input, output);
+    } else {
+        let xml = String::from_utf8(data).map_err(|_| anyhow!("{:?} is neither SimData nor XML", input))?;
+        let resource = simdata::from_xml(&xml).with_context(|| format!("Failed to read the SimData XML in {:?}", input))?;
+        s4pi_reforged::atomic::write(output, resource.to_bytes()?).with_context(|| format!("Failed to write {:?}", output))?;
+        info!("Wrote {} instance(s) from {:?} as SimData to {:?}", resource.instances()?.len(), input, output);
+    }
+    Ok(())
+}
+
+/// Replaces characters that are not allowed in filenames on Windows or Linux
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '!') { '_' } else { c })
+        .collect()
+}
+
+fn run_unmerge(path: &Path, force: bool, observer: &dyn OperationObserver) -> Result<()> {
+    info!("Un-merging: {:?}", path);
+    let timer = PerfTimer::start("unmerge");
+    let part_paths = s4pi_reforged::split::merge_parts(path);
+    if part_paths.len() > 1 {
+        info!("Reading the {} outputs of a split merge.", part_paths.len());
+    }
+    if let Ok(outputs) = s4pi_reforged::merge::unmerge_outputs(path) {
+        ensure_not_in_use(&outputs, force)?;
+    }
+
+    let summary = s4pi_reforged::merge::unmerge(path, observer)?;
+    if !summary.verified {
+        info!("The manifest has no checksums; run 'manifest upgrade' to add them.");
+    } else if summary.damaged > 0 {
+        warn!("{} resource(s) don't match the manifest; the merged package may be damaged.", summary.damaged);
+    }
+
+    info!("Un-merged {} packages. Files are in: {:?}", summary.packages.len(), summary.output_dir);
+    timer.finish().log();
+    Ok(())
+}
+
+fn run_manifest_upgrade(path: &Path, force: bool) -> Result<()> {
+    use s4pi_reforged::manifest::UpgradeOutcome;
+    ensure_not_in_use(&[path.to_path_buf()], force)?;
+    match s4pi_reforged::manifest::upgrade(path)? {
+        UpgradeOutcome::AlreadyCurrent { version } => {
+            info!("Manifest is already version {}; nothing to do.", version);
+        }
+        UpgradeOutcome::Upgraded { from, resources, missing } => {
+            for tgi in &missing {
+                warn!("Resource {} is listed in the manifest but not in the package", tgi);
+            }
+            info!("Upgraded manifest from version {} to {} ({} resources checksummed).",
+                from, s4pi_reforged::package::resource::ManifestResource::LATEST_VERSION, resources);
+        }
+    }
+    Ok(())
+}
+
+fn run_manifest_verify(path: &Path) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let (_, manifest) = s4pi_reforged::manifest::read_manifest(&pkg)?;
+    if !manifest.has_checksums() {
+        return Err(anyhow!("Manifest version {} has no checksums; run 'manifest upgrade' first.", manifest.version));
+    }
+    drop(pkg);
+    let parts = s4pi_reforged::split::merge_parts(path).iter().map(Package::open).collect::<Result<Vec<_>, _>>()?;
+    let issues = s4pi_reforged::manifest::verify_parts(&parts, &manifest);
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let total: usize = manifest.entries.iter().map(|e| e.resources.len()).sum();
+    if issues.is_empty() {
+        info!("All {} resources in {} packages match the manifest.", total, manifest.entries.len());
+        Ok(())
+    } else {
+        Err(anyhow!("{} of {} resources don't match the manifest", issues.len(), total))
+    }
+}
+
+fn run_manifest_describe(path: &Path, output: Option<&Path>) -> Result<()> {
+    let (inputs, base) = if path.is_dir() {
+        (s4pi_reforged::merge::find_inputs(path), path.to_path_buf())
+    } else {
+        (vec![path.to_path_buf()], path.parent().map(Path::to_path_buf).unwrap_or_default())
+    };
+    let output = match output {
+        Some(output) => output.to_path_buf(),
+        None => {
+            let stem = path.file_stem().or(path.file_name()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "mods".to_string());
+            path.with_file_name(format!("{}_descriptor.package", stem))
+        }
+    };
+    let inputs: Vec<PathBuf> = inputs.into_iter()
+        .filter(|p| std::fs::canonicalize(p).ok() != std::fs::canonicalize(&output).ok())
+        .collect();
+    if inputs.is_empty() {
+        return Err(anyhow!("No .package files found in {:?}", path));
+    }
+
+    let opened: Vec<(String, Package)> = inputs.par_iter()
+        .filter_map(|p| match Package::open(p) {
+            Ok(pkg) => Some((s4pi_reforged::merge::input_name(&base, p), pkg)),
+            Err(e) => {
+                error!("Failed to read {:?}: {:#}. Skipping.", p, e);
+                None
+            }
+        })
+        .collect();
+    let packages: Vec<(&str, &Package)> = opened.iter().map(|(name, pkg)| (name.as_str(), pkg)).collect();
+    let keys = s4pi_reforged::manifest::write_descriptor(&output, &packages)?;
+    info!("Wrote a descriptor of {} packages ({} resource keys) to {:?}.", packages.len(), keys, output);
+    Ok(())
+}
+
+fn run_shell_integration(uninstall: bool) -> Result<()> {
+    if uninstall {
+        let removed = s4pi_reforged::shell::uninstall()?;
+        for item in &removed {
+            info!("Removed {}", item);
+        }
+        info!("Shell integration removed ({} entries).", removed.len());
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe().context("Failed to find the path of this executable")?;
+    for item in s4pi_reforged::shell::install(&exe)? {
+        info!("Registered {}", item);
+    }
+    info!("Shell integration installed for {:?}. Run with --uninstall to remove it.", exe);
+    Ok(())
+}
+
+fn run_import_stbl(package: &Path, input: &Path, force: bool) -> Result<()> {
+    let mut files: Vec<PathBuf> = if input.is_dir() {
+        WalkDir::new(input)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ["json", "po", "pot"].iter().any(|e| ext.eq_ignore_ascii_case(e))))
+            .collect()
+    } else {
+        vec![input.to_path_buf()]
+    };
+    files.sort();
+    if files.is_empty() {
+        warn!("No .json, .po or .pot files found in {:?}.", input);
+        return Ok(());
+    }
+
+    use s4pi_reforged::package::resource::Resource;
+    let mut replacements: HashMap<TGI, Vec<u8>> = HashMap::new();
+    let mut strings = 0;
+    for file in &files {
+        let text = std::fs::read_to_string(file).with_context(|| format!("Failed to read {:?}", file))?;
+        let is_json = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let table = if is_json { s4pi_reforged::stbl::table_from_json(&text) } else { s4pi_reforged::stbl::table_from_po(&text) };
+        let (tgi, stbl) = table.with_context(|| format!("Failed to read {:?}", file))?;
+        strings += stbl.entries.len();
+        if replacements.insert(tgi, stbl.to_bytes()?).is_some() {
+            return Err(anyhow!("More than one file has string table {}", tgi));
+        }
+    }
+
+    ensure_not_in_use(&[package.to_path_buf()], force)?;
+    let existing: HashSet<TGI> = Package::open(package)?.entries.iter().map(|e| e.tgi).collect();
+    let added = replacements.keys().filter(|tgi| !existing.contains(tgi)).count();
+    Package::replace_resources(package, &replacements).with_context(|| format!("Failed to write {:?}", package))?;
+    info!("Imported {} string tables ({} strings) into {:?}: {} replaced, {} added.",
+        replacements.len(), strings, package, replacements.len() - added, added);
+    Ok(())
+}
+
+fn run_import(folder: &Path, output: Option<&Path>, new_instances: bool) -> Result<()> {
+    use s4pi_reforged::import::NAME_MAP_TYPE;
+    use s4pi_reforged::instance::InstanceAllocator;
+
+    info!("Importing resources from: {:?}", folder);
+    let (mut files, skipped) = s4pi_reforged::import::collect_import_files(folder)?;
+
+    for path in &skipped {
+        warn!("Skipping {:?}: filename does not contain a recognised TGI", path);
+    }
+
+    if files.is_empty() {
+        warn!("No importable resources found.");
+        return Ok(());
+    }
+
+    info!("Found {} resources to import.", files.len());
+
+    if new_instances {
+        let mut allocator = match s4pi_reforged::install::find_mods_folder() {
+            Some(mods) => {
+                info!("Checking the instances used in {:?}.", mods);
+                InstanceAllocator::avoiding_folder(&mods)
+            }
+            None => {
+                warn!("No Mods folder found; new instances are only checked against each other.");
+                InstanceAllocator::new()
+            }
+        };
+        // An exported name map lists the old instances; a new one is built from the file names
+        files.retain(|f| f.key.tgi.res_type != NAME_MAP_TYPE);
+        let mapping = s4pi_reforged::import::assign_new_instances(&mut files, &mut allocator);
+        info!("Gave {} instance(s) new IDs.", mapping.len());
+    }
+
+    let loaded: Vec<Result<(s4pi_reforged::import::ImportFile, Vec<u8>)>> = files
+        .into_par_iter()
+        .map(|file| {
+            let data = std::fs::read(&file.path)
+                .with_context(|| format!("Failed to read {:?}", file.path))?;
+            Ok((file, data))
+        })
+        .collect();
+
+    let mut package_data: HashMap<TGI, RawResource> = HashMap::new();
+    let mut names: Vec<(u64, String)> = Vec::new();
+
+    for res in loaded {
+        let (file, data) = res?;
+        if let Some(name) = &file.key.name {
+            names.push((file.key.tgi.instance, name.clone()));
+        }
+        // A DST texture exported as a plain DDS goes back in shuffled, as the game stores it
+        let data = match file.key.tgi.res_type {
+            s4pi_reforged::texture::DST_IMAGE => s4pi_reforged::texture::shuffle_dst(&data).unwrap_or(data),
+            _ => data,
+        };
+        let memsize = data.len() as u32;
+        if package_data.insert(file.key.tgi, (data, memsize, 0, 1)).is_some() {
+            warn!("Duplicate resource {:?} (from {:?}); keeping the last one.", file.key.tgi, file.path);
+        }
+    }
+
+    // s4pe stores resource names in a NameMap; rebuild one if the export didn't include it
+    let has_name_map = package_data.keys().any(|tgi| tgi.res_type == NAME_MAP_TYPE);
+    if !names.is_empty() && !has_name_map {
+        names.sort();
+        names.dedup_by_key(|(instance, _)| *instance);
+        let name_map = s4pi_reforged::NameMapResource {
+            version: 1,
+            entries: names.into_iter()
+                .map(|(instance, name)| s4pi_reforged::package::resource::NameMapEntry { instance, name })
+                .collect(),
+        };
+        use s4pi_reforged::package::resource::Resource;
+        let name_map_data = name_map.to_bytes().context("Failed to serialize name map")?;
+        let name_map_tgi = TGI { res_type: NAME_MAP_TYPE, res_group: 0, instance: 0 };
+        info!("Adding name map with {} entries.", name_map.entries.len());
+        package_data.insert(name_map_tgi, (name_map_data.clone(), name_map_data.len() as u32, 0, 1));
+    }
+
+    let output_file = match output {
+        Some(p) => p.to_path_buf(),
+        None => {
+            let output_dir = folder.join("imported");
+            std::fs::create_dir_all(&output_dir).context("Failed to create output directory")?;
+            output_dir.join("imported.package")
+        }
+    };
+
+    info!("Writing package to: {:?}", output_file);
+    Package::write_merged(&output_file, &package_data, true).context("Failed to write imported package")?;
+
+    info!("Import complete!");
+    info!("Resources imported: {}", package_data.len());
+    info!("Files skipped: {}", skipped.len());
+
+    Ok(())
+}
+
+fn run_poses(path: &Path) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let poses = s4pi_reforged::poses::list_poses(&pkg)?;
+    if poses.is_empty() {
+        println!("No animation clips found in package.");
+        return Ok(());
+    }
+
+    let mut unexposed = 0;
+    for pose in &poses {
+        let name = pose.name.as_deref().unwrap_or("(unnamed)");
+        match pose.duration {
+            Some(duration) => println!("\n{}  {:.2}s", name, duration),
+            None => println!("\n{}", name),
+        }
+        println!("  Resource: {}", pose.tgi);
+        if let Some(error) = &pose.error {
+            println!("  Could not read clip header: {}", error);
+        }
+        if pose.tuning.is_empty() {
+            unexposed += 1;
+            println!("  Tuning:   none (not referenced by any tuning in this package)");
+        } else {
+            println!("  Tuning:   {}", pose.tuning.join(", "));
+        }
+    }
+
+    println!("\n--- Pose Summary ---");
+    println!("Clips: {}", poses.len());
+    println!("Not referenced by tuning: {}", unexposed);
+    Ok(())
+}
+
+fn run_recompress(path: &Path, force: bool) -> Result<()> {
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    ensure_not_in_use(&packages, force)?;
+    info!("Recompressing {} packages.", packages.len());
+
+    let reports: Vec<(PathBuf, s4pi_reforged::Result<s4pi_reforged::recompress::RecompressReport>)> = packages
+        .into_par_iter()
+        .map(|path| {
+            let report = s4pi_reforged::recompress::refpack_to_zlib(&path);
+            (path, report)
+        })
+        .collect();
+
+    let mut converted = 0;
+    let mut saved = 0i64;
+    let mut failed = 0;
+    for (path, report) in reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to recompress {:?}: {:#}. Skipping.", path, e);
+                continue;
+            }
+        };
+        if report.converted == 0 && report.failed.is_empty() {
+            continue;
+        }
+        converted += report.converted;
+        saved += report.saved();
+        failed += report.failed.len();
+        println!("\n{}", path.display());
+        println!("  Converted: {} | {} -> {} bytes (saved {})", report.converted, report.bytes_before, report.bytes_after, report.saved());
+        for (tgi, reason) in &report.failed {
+            println!("    Failed {:08X}:{:08X}:{:016X}: {}", tgi.res_type, tgi.res_group, tgi.instance, reason);
+        }
+    }
+
+    println!("\n--- Recompress Summary ---");
+    println!("Resources converted: {}", converted);
+    println!("Bytes saved: {}", saved);
+    println!("Resources that failed: {}", failed);
+    Ok(())
+}
+
+fn run_packs(path: &Path, game_folder: Option<&Path>) -> Result<()> {
+    use s4pi_reforged::packs::{installed_packs, pack_code, pack_label, pack_requirements};
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    let installed = game_folder.map(installed_packs);
+    if let (Some(folder), Some(installed)) = (game_folder, &installed) {
+        info!("Found {} installed packs in {:?}.", installed.len(), folder);
+    }
+    info!("Checking {} packages.", packages.len());
+
+    let reports: Vec<(PathBuf, Result<_, _>)> = packages
+        .into_par_iter()
+        .map(|path| {
+            let report = Package::open(&path).map(|pkg| pack_requirements(&pkg));
+            (path, report)
+        })
+        .collect();
+
+    let mut needing_packs = 0;
+    let mut invisible = 0;
+    for (path, report) in reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to read {:?}: {:#}. Skipping.", path, e);
+                continue;
+            }
+        };
+        if report.is_empty() {
+            continue;
+        }
+        needing_packs += 1;
+        let mut missing = false;
+        println!("\n{}", path.display());
+        for (pack, items) in &report {
+            let flag = match &installed {
+                Some(installed) if !installed.contains(&pack_code(*pack)) => {
+                    missing = true;
+                    "  [NOT INSTALLED - invisible in game]"
+                }
+                _ => "",
+            };
+            println!("  {} (pack {}): {} item(s){}", pack_label(*pack), pack, items.len(), flag);
+        }
+        if missing {
+            invisible += 1;
+        }
+    }
+
+    println!("\n--- Pack Summary ---");
+    println!("Packages needing a pack: {}", needing_packs);
+    if installed.is_some() {
+        println!("Packages with items from packs not installed: {}", invisible);
+    }
+    Ok(())
+}
+
+fn run_palette(path: &Path, color: Option<&str>) -> Result<()> {
+    use s4pi_reforged::palette::{package_palette, swatch_rgb};
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    info!("Reading CAS parts in {} packages.", packages.len());
+
+    let reports: Vec<(PathBuf, Result<_, _>)> = packages
+        .into_par_iter()
+        .map(|path| {
+            let report = Package::open(&path).map(|pkg| package_palette(&pkg));
+            (path, report)
+        })
+        .collect();
+
+    let hex = |rgb: [u8; 3]| format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2]);
+    let mut parts = 0;
+    let mut mismatched = 0;
+    for (path, report) in reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to read {:?}: {:#}. Skipping.", path, e);
+                continue;
+            }
+        };
+        let report: Vec<_> = report.into_iter()
+            .filter(|part| color.is_none_or(|c| part.color_names().contains(&c)))
+            .collect();
+        if report.is_empty() {
+            continue;
+        }
+        println!("\n{}", path.display());
+        for part in &report {
+            parts += 1;
+            let flag = if part.matches_thumbnail() == Some(false) {
+                mismatched += 1;
+                "  [swatch color not in thumbnail]"
+            } else {
+                ""
+            };
+            println!("  {} {} (sort {} / {}){}", part.tgi, part.name, part.sort_priority, part.secondary_sort, flag);
+            let swatches: Vec<String> = part.swatch_colors.iter()
+                .map(|&c| format!("{} {}", hex(swatch_rgb(c)), s4pi_reforged::palette::color_name(swatch_rgb(c))))
+                .collect();
+            println!("    Swatch:    {}", if swatches.is_empty() { "none".to_string() } else { swatches.join(", ") });
+            let thumbnail = match part.thumbnail {
+                None => "none".to_string(),
+                Some(_) if part.dominant.is_empty() => "could not be decoded".to_string(),
+                Some(_) => part.dominant.iter()
+                    .map(|c| format!("{} {} {:.0}%", hex(c.rgb), c.name(), c.share * 100.0))
+                    .collect::<Vec<_>>().join(", "),
+            };
+            println!("    Thumbnail: {}", thumbnail);
+        }
+    }
+
+    println!("\n--- Palette Summary ---");
+    println!("CAS parts: {}", parts);
+    println!("Swatch colors not in their thumbnail: {}", mismatched);
+    Ok(())
+}
+
+fn run_check_objects(path: &Path, game_folder: Option<&Path>) -> Result<()> {
+    use s4pi_reforged::game::GameIndex;
+    use s4pi_reforged::objcheck::{check_objects, KnownResources};
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+
+    let game = match game_folder {
+        Some(folder) => {
+            info!("Indexing game packages in: {:?}", folder);
+            Some(GameIndex::build(folder)?)
+        }
+        None => None,
+    };
+    let string_keys = match &game {
+        Some(game) => {
+            info!("Reading the game's string tables.");
+            game.string_keys()
+        }
+        None => Default::default(),
+    };
+    let known = KnownResources { game: game.as_ref(), string_keys };
+    info!("Checking objects in {} packages.", packages.len());
+
+    let reports: Vec<(PathBuf, Result<_, _>)> = packages
+        .into_par_iter()
+        .map(|path| {
+            let report = Package::open(&path).map(|pkg| check_objects(&pkg, &known));
+            (path, report)
+        })
+        .collect();
+
+    let mut objects = 0;
+    let mut with_issues = 0;
+    for (path, report) in reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to read {:?}: {:#}. Skipping.", path, e);
+                continue;
+            }
+        };
+        if report.is_empty() {
+            continue;
+        }
+        println!("\n{}", path.display());
+        for check in &report {
+            objects += 1;
+            let name = check.name.as_deref().unwrap_or("(unnamed)");
+            if check.issues.is_empty() {
+                println!("  {} {}  OK", check.objd, name);
+                continue;
+            }
+            with_issues += 1;
+            println!("  {} {}  {} issue(s)", check.objd, name, check.issues.len());
+            for issue in &check.issues {
+                println!("    {}", issue);
+            }
+        }
+    }
+
+    println!("\nObjects checked: {} | With issues: {}", objects, with_issues);
+    Ok(())
+}
+
+fn run_validate(path: &Path) -> Result<()> {
+    use s4pi_reforged::package::validate::validate_file;
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    info!("Validating {} packages.", packages.len());
+
+    let reports: Vec<(PathBuf, Result<_, _>)> = packages
+        .into_par_iter()
+        .map(|path| {
+            let report = validate_file(&path);
+            (path, report)
+        })
+        .collect();
+
+    let mut failed = 0;
+    let mut issues = 0;
+    let mut entries = 0;
+    for (path, report) in &reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                println!("\n{}\n  can't be read: {:#}", path.display(), e);
+                failed += 1;
+                issues += 1;
+                continue;
+            }
+        };
+        entries += report.checked;
+        if report.is_valid() {
+            continue;
+        }
+        failed += 1;
+        issues += report.issue_count();
+        println!("\n{}", path.display());
+        for issue in &report.header {
+            println!("  header: {}", issue);
+        }
+        if let Some(error) = &report.index_error {
+            println!("  index: {}", error);
+        }
+        for (entry, issue) in &report.entries {
+            println!("  {} {}: {}", entry.tgi, kind_label(entry.tgi.res_type), issue);
+        }
+    }
+
+    println!("\n--- Validate Summary ---");
+    println!("Packages: {} | With problems: {} | Resources checked: {} | Problems: {}", reports.len(), failed, entries, issues);
+    if failed > 0 {
+        return Err(anyhow!("{} of {} packages have problems", failed, reports.len()));
+    }
+    Ok(())
+}
+
+fn run_repair(path: &Path, output: &Path) -> Result<()> {
+    let report = s4pi_reforged::package::repair::repair(path, output)?;
+    for fix in &report.fixes {
+        println!("Fixed {}", fix);
+    }
+    for (tgi, error) in &report.undecodable {
+        warn!("{} doesn't decompress ({}); kept as it was.", tgi, error);
+    }
+    if report.fixes.is_empty() {
+        info!("Nothing to fix; wrote an unchanged copy of {} resources to {:?}.", report.kept, output);
+    } else {
+        info!("Made {} fixes and wrote {} resources to {:?}.", report.fixes.len(), report.kept, output);
+    }
+    Ok(())
+}
+
+fn run_diff(a: &Path, b: &Path, json: bool) -> Result<()> {
+    let pkg_a = Package::open(a).with_context(|| format!("Failed to open {:?}", a))?;
+    let pkg_b = Package::open(b).with_context(|| format!("Failed to open {:?}", b))?;
+    let diff = pkg_a.diff(&pkg_b)?;
+    if json {
+        println!("{}", diff.to_json());
+        return Ok(());
+    }
+
+    let line = |tgi: &TGI| format!("  {}  {}", tgi, kind_label(tgi.res_type));
+    if !diff.removed.is_empty() {
+        println!("\nOnly in {}:", a.display());
+        for tgi in &diff.removed {
+            println!("{}", line(tgi));
+        }
+    }
+    if !diff.added.is_empty() {
+        println!("\nOnly in {}:", b.display());
+        for tgi in &diff.added {
+            println!("{}", line(tgi));
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("\nChanged:");
+        for change in &diff.changed {
+            println!("{}  {} -> {} bytes", line(&change.tgi), change.before.size, change.after.size);
+        }
+    }
+
+    println!("\n--- Diff Summary ---");
+    println!("Only in A: {} | Only in B: {} | Changed: {} | Unchanged: {}",
+        diff.removed.len(), diff.added.len(), diff.changed.len(), diff.unchanged);
+    if diff.is_empty() {
+        println!("No differences found.");
+    }
+    Ok(())
+}
+
+/// Prints what changed in one save of the package `devwatch` follows, returning the number of
+/// problems found
+fn print_dev_report(path: &Path, report: &s4pi_reforged::devwatch::DevReport) -> usize {
+    let diff = &report.diff;
+    println!("\n{}: {} added, {} changed, {} removed", path.display(), diff.added.len(), diff.changed.len(), diff.removed.len());
+    let touched = diff.added.iter().map(|tgi| ('+', *tgi))
+        .chain(diff.changed.iter().map(|c| ('~', c.tgi)))
+        .chain(diff.removed.iter().map(|tgi| ('-', *tgi)));
+    for (mark, tgi) in touched {
+        println!("  {} {}  {}", mark, tgi, kind_label(tgi.res_type));
+        for (_, issue) in report.issues.iter().filter(|(t, _)| *t == tgi) {
+            println!("      ! {}", issue);
+        }
+    }
+    // Issues on resources that didn't change themselves, e.g. tuning whose SimData was removed
+    for (tgi, issue) in report.issues.iter().filter(|(t, _)| !diff.added.contains(t) && !diff.changed.iter().any(|c| c.tgi == *t) && !diff.removed.contains(t)) {
+        println!("    {}  {}\n      ! {}", tgi, kind_label(tgi.res_type), issue);
+    }
+    if report.issues.is_empty() {
+        println!("No problems found.");
+    } else {
+        println!("{} problem(s) found.", report.issues.len());
+    }
+    report.issues.len()
+}
+
+fn run_devwatch(project: &Path, against: &Path, once: bool) -> Result<()> {
+    use s4pi_reforged::devwatch::{check_changes, snapshot};
+
+    let stamp = |path: &Path| std::fs::metadata(path).ok().map(|m| (m.modified().ok(), m.len()));
+    let mut previous = snapshot(against)?;
+    let mut seen = stamp(project);
+    let current = snapshot(project)?;
+    let problems = print_dev_report(project, &check_changes(&previous, &current)?);
+    if once {
+        if problems > 0 {
+            return Err(anyhow!("{} problem(s) found in {:?}", problems, project));
+        }
+        return Ok(());
+    }
+    previous = current;
+
+    info!("Watching {:?} for changes; press Ctrl+C to stop", project);
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let now = stamp(project);
+        if now.is_none() || now == seen {
+            continue;
+        }
+        // Wait for the save to finish: the size and time stop changing
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        if stamp(project) != now {
+            continue;
+        }
+        seen = now;
+        match snapshot(project) {
+            Ok(current) => {
+                match check_changes(&previous, &current) {
+                    Ok(report) => {
+                        print_dev_report(project, &report);
+                    }
+                    Err(e) => warn!("Failed to compare {:?}: {:#}", project, e),
+                }
+                previous = current;
+            }
+            // Reported once; the next save is checked against the last one that opened
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+fn run_retarget(path: &Path, from: u64, to: u64, dry_run: bool, force: bool) -> Result<()> {
+    use s4pi_reforged::retarget::retarget_package;
+    if from == to {
+        return Err(anyhow!("The instance to retarget to is the one being replaced."));
+    }
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    if !dry_run {
+        ensure_not_in_use(&packages, force)?;
+    }
+    info!("{} references to {} in {} packages.", if dry_run { "Checking" } else { "Retargeting" }, from, packages.len());
+
+    let mut sites = 0;
+    let mut resources = 0;
+    for package in &packages {
+        let report = match retarget_package(package, from, to, dry_run) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to retarget {:?}: {:#}. Skipping.", package, e);
+                continue;
+            }
+        };
+        if report.sites.is_empty() {
+            continue;
+        }
+        println!("\n{}", package.display());
+        for site in &report.sites {
+            println!("  {} {}", site.resource, site.location);
+        }
+        sites += report.sites.len();
+        resources += report.resources;
+    }
+
+    println!("\n--- Retarget Summary ---");
+    println!("References {}: {} in {} resource(s)", if dry_run { "to update" } else { "updated" }, sites, resources);
+    Ok(())
+}
+
+fn run_selftest() -> Result<()> {
+    use s4pi_reforged::selftest::{Outcome, Step};
+    let work_dir = std::env::temp_dir().join(format!("s4pi_selftest_{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir)?;
+    info!("Running the self-test in {:?}", work_dir);
+    let report = s4pi_reforged::selftest::run(&work_dir);
+    if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+        warn!("Failed to remove {:?}: {}", work_dir, e);
+    }
+    let report = report?;
+
+    let rows: Vec<String> = report.samples.iter().map(|s| format!("{} {}", s.group, s.name)).collect();
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let header: String = Step::ALL.iter().map(|step| format!("  {:8}", step.name())).collect();
+    println!("{}", format!("{:width$}{}", "", header, width = width).trim_end());
+    for (row, sample) in rows.iter().zip(&report.samples) {
+        let cells: String = sample.outcomes.iter().map(|outcome| format!("  {:8}", match outcome {
+            Outcome::Passed => "ok",
+            Outcome::Failed(_) => "FAILED",
+            Outcome::Skipped => "-",
+        })).collect();
+        println!("{}", format!("{:width$}{}", row, cells, width = width).trim_end());
+    }
+
+    let failures: Vec<String> = report.samples.iter().zip(&rows).flat_map(|(sample, row)| {
+        Step::ALL.iter().zip(&sample.outcomes).filter_map(move |(step, outcome)| match outcome {
+            Outcome::Failed(reason) => Some(format!("  {} ({}): {}", row, step.name(), reason)),
+            _ => None,
+        })
+    }).collect();
+    if !failures.is_empty() {
+        println!("\nFailures:");
+        for failure in &failures {
+            println!("{}", failure);
+        }
+    }
+
+    println!("\n--- Self-Test Summary ---");
+    println!("Packages: {}", report.samples.len());
+    println!("Steps failed: {}", report.failures());
+    if !report.passed() {
+        return Err(anyhow!("The self-test failed; this build should not be trusted with your Mods folder."));
+    }
+    Ok(())
+}
+
+fn run_census_diff(before_folder: &Path, after_folder: &Path) -> Result<()> {
+    use s4pi_reforged::census::{Census, CensusEntry};
+
+    fn packages_of(entries: &[CensusEntry]) -> String {
+        entries.iter().map(|e| e.package.display().to_string()).collect::<Vec<_>>().join(", ")
+    }
+
+    info!("Reading package indexes in: {:?}", before_folder);
+    let before = Census::scan(before_folder);
+    info!("Reading package indexes in: {:?}", after_folder);
+    let after = Census::scan(after_folder);
+    let diff = s4pi_reforged::census::diff(&before, &after);
+
+    if !diff.type_counts.is_empty() {
+        println!("\nResource counts by type:");
+        for (res_type, (old, new)) in &diff.type_counts {
+            println!("  {:08X} {:<24} {} -> {} ({:+})", res_type, kind_label(*res_type), old, new, *new as i64 - *old as i64);
+        }
+    }
+    if !diff.removed.is_empty() {
+        println!("\nRemoved:");
+        for (tgi, entries) in &diff.removed {
+            println!("  {:08X}:{:08X}:{:016X}  (was in {})", tgi.res_type, tgi.res_group, tgi.instance, packages_of(entries));
+        }
+    }
+    if !diff.added.is_empty() {
+        println!("\nAdded:");
+        for (tgi, entries) in &diff.added {
+            println!("  {:08X}:{:08X}:{:016X}  (in {})", tgi.res_type, tgi.res_group, tgi.instance, packages_of(entries));
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("\nChanged size:");
+        for change in &diff.changed {
+            let sizes = |entries: &[CensusEntry]| entries.iter().map(|e| e.size.to_string()).collect::<Vec<_>>().join("/");
+            println!("  {:08X}:{:08X}:{:016X}  {} -> {} bytes  (in {})", change.tgi.res_type, change.tgi.res_group, change.tgi.instance,
+                sizes(&change.before), sizes(&change.after), packages_of(&change.after));
+        }
+    }
+    if !diff.moved.is_empty() {
+        println!("\nMoved:");
+        for change in &diff.moved {
+            println!("  {:08X}:{:08X}:{:016X}  {} -> {}", change.tgi.res_type, change.tgi.res_group, change.tgi.instance,
+                packages_of(&change.before), packages_of(&change.after));
+        }
+    }
+
+    println!("\n--- Census Diff Summary ---");
+    println!("Packages: {} -> {}", before.packages.len(), after.packages.len());
+    println!("Resources: {} -> {}", before.resources.len(), after.resources.len());
+    println!("Removed: {} | Added: {} | Changed size: {} | Moved: {}", diff.removed.len(), diff.added.len(), diff.changed.len(), diff.moved.len());
+    if diff.is_empty() {
+        println!("No differences found.");
+    }
+    Ok(())
+}
+
+fn run_conflicts(folder: &Path, all: bool) -> Result<()> {
+    use s4pi_reforged::conflict::{folder_conflicts, Severity};
+
+    info!("Scanning packages in: {:?}", folder);
+    let conflicts = folder_conflicts(folder);
+
+    // By severity, most serious first, then by type
+    let mut groups: BTreeMap<(std::cmp::Reverse<Severity>, u32), Vec<&Conflict>> = BTreeMap::new();
+    for conflict in &conflicts {
+        let res_type = conflict.tgi.res_type;
+        groups.entry((std::cmp::Reverse(Severity::of(res_type)), res_type)).or_default().push(conflict);
+    }
+
+    let mut counts: BTreeMap<Severity, usize> = BTreeMap::new();
+    let mut current = None;
+    for ((std::cmp::Reverse(severity), res_type), group) in &groups {
+        *counts.entry(*severity).or_default() += group.len();
+        if current != Some(*severity) {
+            current = Some(*severity);
+            println!("\n{} severity: {}", severity.to_string().to_uppercase(), severity.hint());
+        }
+        println!("  {} ({} resources)", kind_label(*res_type), group.len());
+        if *severity == Severity::Low && !all {
+            continue;
+        }
+        for conflict in group {
+            let (winner, others) = conflict.packages.split_last().expect("a conflict has packages");
+            println!("    {}  {} overrides {}", conflict.tgi, winner, others.join(", "));
+        }
+    }
+
+    let packages: HashSet<&String> = conflicts.iter().flat_map(|c| &c.packages).collect();
+    println!("\n--- Conflicts Summary ---");
+    println!("Conflicting resources: {} (high: {}, medium: {}, low: {})", conflicts.len(),
+        counts.get(&Severity::High).unwrap_or(&0), counts.get(&Severity::Medium).unwrap_or(&0), counts.get(&Severity::Low).unwrap_or(&0));
+    println!("Packages involved: {}", packages.len());
+    if counts.contains_key(&Severity::Low) && !all {
+        println!("Run with --all to list the low-severity conflicts too.");
+    }
+    Ok(())
+}
+
+fn run_overrides(game_folder: &Path, mods_folder: &Path) -> Result<()> {
+    info!("Indexing game packages in: {:?}", game_folder);
+    let index = s4pi_reforged::game::GameIndex::build(game_folder)?;
+    info!("Indexed {} resources from {} game packages.", index.resources.len(), index.packages.len());
+
+    let mut mod_packages: Vec<_> = WalkDir::new(mods_folder)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_path_buf())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+        .collect();
+    mod_packages.sort();
+
+    info!("Checking {} mod packages.", mod_packages.len());
+
+    let reports: Vec<(std::path::PathBuf, s4pi_reforged::Result<s4pi_reforged::game::OverrideReport>)> = mod_packages
+        .into_par_iter()
+        .map(|path| {
+            let report = Package::open(&path).map(|pkg| {
+                let tgis = pkg.entries.iter()
+                    .map(|e| &e.tgi)
+                    .filter(|tgi| tgi.res_type != 0x7FB6AD8A && tgi.res_type != 0x73E93EEB);
+                s4pi_reforged::game::OverrideReport::classify(&index, tgis)
+            });
+            (path, report)
+        })
+        .collect();
+
+    let mut total_overrides = 0;
+    let mut total_additions = 0;
+    let mut overriding_packages = 0;
+
+    for (path, report) in reports {
+        let report = match report {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to read {:?}: {}. Skipping.", path, e);
+                continue;
+            }
+        };
+        total_overrides += report.overrides.len();
+        total_additions += report.additions.len();
+        if report.overrides.is_empty() {
+            continue;
+        }
+
+        overriding_packages += 1;
+        println!("\n{}", path.display());
+        println!("  Overrides: {} | New: {}", report.overrides.len(), report.additions.len());
+        for tgi in &report.overrides {
+            // Relative to the install, since every pack has its own ClientDeltaBuild0.package
+            let source = index.source_of(tgi)
+                .map(|p| p.strip_prefix(game_folder).unwrap_or(p).display().to_string())
+                .unwrap_or_default();
+            println!("    {:08X}:{:08X}:{:016X}  (from {})", tgi.res_type, tgi.res_group, tgi.instance, source);
+        }
+    }
+
+    println!("\n--- Override Summary ---");
+    println!("Packages overriding game content: {}", overriding_packages);
+    println!("Overriding resources: {}", total_overrides);
+    println!("New resources: {}", total_additions);
+
+    Ok(())
+}
+
+/// The URL `path` holds, if it is one rather than a file
+fn as_url(path: &Path) -> Option<&str> {
+    path.to_str().filter(|p| s4pi_reforged::remote::is_url(p))
+}
+
+fn print_download_summary(remote: &s4pi_reforged::remote::RemotePackage) {
+    println!("\nDownloaded {} of {}.", perf::format_bytes(remote.fetched()), perf::format_bytes(remote.size));
+}
+
+fn run_package_info(path: &Path) -> Result<()> {
+    if let Some(url) = as_url(path) {
+        let remote = s4pi_reforged::remote::open_package(url)?;
+        print_package_summary(url, &remote.package)?;
+        print_download_summary(&remote);
+        return Ok(());
+    }
+    if s4pi_reforged::archive::is_archive(path) {
+        let packages = s4pi_reforged::archive::open_packages(path)?;
+        if packages.is_empty() {
+            println!("No packages in {}", path.display());
+        }
+        for (i, (name, pkg)) in packages.into_iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            match pkg {
+                Ok(pkg) => {
+                    print_package_summary(&format!("{} in {}", name, path.display()), &pkg)?;
+                    print_integrity(&pkg);
+                }
+                Err(e) => println!("Package:   {} in {}
+Failed to open: {}", name, path.display(), e),
+            }
+        }
+        return Ok(());
+    }
+    let pkg = Package::open(path)?;
+    print_package_summary(&path.display().to_string(), &pkg)?;
+    print_integrity(&pkg);
+    Ok(())
+}
+
+/// Says whether the index matches the data, for packages that aren't clean
+fn print_integrity(pkg: &Package) {
+    let report = pkg.check_obfuscation();
+    if !report.is_clean() {
+        println!("\nIntegrity: {}", report.summary());
+    }
+}
+
+fn print_package_summary(label: &str, pkg: &Package) -> Result<()> {
+    let mut kinds: HashMap<String, usize> = HashMap::new();
+    for entry in &pkg.entries {
+        *kinds.entry(format!("{:?}", TypedResource::kind_of(entry.tgi.res_type))).or_default() += 1;
+    }
+
+    println!("Package:   {}", label);
+    println!("Version:   {}.{}", pkg.header.major, pkg.header.minor);
+    println!("Resources: {}", pkg.entries.len());
+    let mut kinds: Vec<_> = kinds.into_iter().collect();
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (kind, count) in kinds {
+        println!("  {:<18} {:>6}", kind, count);
+    }
+
+    match Provenance::read(pkg)? {
+        Some(provenance) => print!("\n{}", provenance),
+        None => println!("\nNo provenance record (not produced by 'merge', or merged with --no-provenance)."),
+    }
+    Ok(())
+}
+
+/// The order `list` prints resources in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListOrder {
+    Index,
+    /// Largest first
+    Size,
+    Type,
+    Instance,
+}
+
+impl ListOrder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "size" => Some(ListOrder::Size),
+            "type" => Some(ListOrder::Type),
+            "instance" => Some(ListOrder::Instance),
+            _ => None,
+        }
+    }
+}
+
+fn run_list(path: &Path, filter: &TgiFilter, order: ListOrder) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let mut entries: Vec<&s4pi_reforged::IndexEntry> = pkg.entries.iter()
+        .filter(|e| filter.matches(&e.tgi))
+        .collect();
+    match order {
+        ListOrder::Index => {}
+        ListOrder::Size => entries.sort_by_key(|e| (std::cmp::Reverse(e.memsize), e.tgi.res_type, e.tgi.res_group, e.tgi.instance)),
+        ListOrder::Type => entries.sort_by_key(|e| (e.tgi.res_type, e.tgi.res_group, e.tgi.instance)),
+        ListOrder::Instance => entries.sort_by_key(|e| (e.tgi.instance, e.tgi.res_type, e.tgi.res_group)),
+    }
+
+    println!("{:<35} {:>10} {:>10}  {:<12} Kind", "TGI", "Size", "Stored", "Compression");
+    for entry in &entries {
+        println!("{:<35} {:>10} {:>10}  {:<12} {}",
+            entry.tgi.to_string(), entry.memsize, entry.filesize & 0x7FFFFFFF,
+            entry.scheme().to_string(), kind_label(entry.tgi.res_type));
+    }
+    if entries.len() == pkg.entries.len() {
+        println!("\n{} resources", entries.len());
+    } else {
+        println!("\n{} of {} resources", entries.len(), pkg.entries.len());
+    }
+    Ok(())
+}
+
+fn run_info(path: &Path, tgi: &TGI, full: bool) -> Result<()> {
+    if let Some(url) = as_url(path) {
+        let remote = s4pi_reforged::remote::open_package(url)?;
+        let entry = remote.package.find(tgi).cloned()
+            .with_context(|| format!("Resource {} not found in {}", tgi, url))?;
+        print!("{}", describe_resource(&remote.package, &entry, full)?);
+        print_download_summary(&remote);
+        return Ok(());
+    }
+    if s4pi_reforged::archive::is_archive(path) {
+        for (name, pkg) in s4pi_reforged::archive::open_packages(path)? {
+            let Ok(pkg) = pkg else { continue };
+            if let Some(entry) = pkg.find(tgi).cloned() {
+                println!("Package:     {} in {}", name, path.display());
+                print!("{}", describe_resource(&pkg, &entry, full)?);
+                return Ok(());
+            }
+        }
+        return Err(anyhow!("Resource {} not found in any package in {:?}", tgi, path));
+    }
+    let mut pkg = Package::open(path)?;
+    let entry = pkg.find(tgi).cloned()
+        .with_context(|| format!("Resource {} not found in {:?}", tgi, path))?;
+    let error = match describe_resource(&pkg, &entry, full) {
+        Ok(text) => {
+            print!("{}", text);
+            return Ok(());
+        }
+        Err(e) => e,
+    };
+    // An index that lies about the data explains the failure better than the parse error does
+    let report = pkg.check_obfuscation();
+    if report.is_clean() {
+        return Err(error);
+    }
+    println!("Integrity:   {}", report.summary());
+    pkg.recover(&report);
+    let entry = pkg.find(tgi).cloned()
+        .with_context(|| format!("Resource {} in {:?} can't be recovered", tgi, path))?;
+    print!("{}", describe_resource(&pkg, &entry, full)?);
+    Ok(())
+}
+
+/// The `info` view of one resource: its index details followed by the parsed contents, or a
+/// hexdump and a guess at its structure when there's no typed parser. Long output is cut short
+/// unless `full` is set.
+/// The wrapper kind of a resource type, or its tuning instance type for tuning resources
+fn kind_label(res_type: u32) -> String {
+    match s4pi_reforged::tuning::tuning_class(res_type) {
+        Some(class) => format!("Tuning ({})", class),
+        None => match (TypedResource::kind_of(res_type), type_names().name_of(res_type)) {
+            (s4pi_reforged::package::resource::ResourceKind::Generic, Some(name)) => format!("Generic ({})", name),
+            (kind, _) => format!("{:?}", kind),
+        },
+    }
+}
+
+/// Type names imported with `import-typemap`, read once per run
+fn type_names() -> &'static s4pi_reforged::typemap::TypeMap {
+    static NAMES: std::sync::OnceLock<s4pi_reforged::typemap::TypeMap> = std::sync::OnceLock::new();
+    NAMES.get_or_init(s4pi_reforged::typemap::TypeMap::registry)
+}
+
+fn describe_resource(pkg: &Package, entry: &s4pi_reforged::IndexEntry, full: bool) -> Result<String> {
+    use std::fmt::Write as _;
+    const MAX_LINES: usize = 200;
+    const MAX_HEX_BYTES: usize = 512;
+
+    let data = pkg.read_raw_resource(entry)?;
+    let mut out = String::new();
+    writeln!(out, "Resource:    {}", entry.tgi)?;
+    writeln!(out, "Kind:        {}", kind_label(entry.tgi.res_type))?;
+    writeln!(out, "Size:        {} bytes ({} stored)", entry.memsize, entry.filesize & 0x7FFFFFFF)?;
+    writeln!(out, "Compression: 0x{:04X}", entry.compression)?;
+    writeln!(out)?;
+
+    if entry.tgi.res_type == PROVENANCE_TYPE {
+        write!(out, "{}", Provenance::from_bytes(&data)?)?;
+        return Ok(out);
+    }
+    if entry.tgi.res_type == s4pi_reforged::preview::OBJD_TYPE || entry.tgi.res_type == s4pi_reforged::preview::COBJ_TYPE {
+        match s4pi_reforged::preview::object_preview(pkg, entry) {
+            Ok(preview) => writeln!(out, "{}", preview)?,
+            Err(e) => warn!("Could not build object preview: {:#}", e),
+        }
+    }
+
+    let typed = match TypedResource::from_bytes(entry.tgi.res_type, &data) {
+        Ok(TypedResource::Generic(_)) => None,
+        Ok(typed) => Some(typed),
+        Err(e) => {
+            warn!("Could not parse resource: {}", e);
+            None
+        }
+    };
+
+    match typed {
+        Some(TypedResource::Text(text)) => writeln!(out, "{}", text.content)?,
+        Some(typed) => {
+            let dump = match &typed {
+                TypedResource::SimData(simdata) => describe_simdata(simdata),
+                typed => format!("{:#?}", typed),
+            };
+            let lines: Vec<&str> = dump.lines().collect();
+            let shown = if full { lines.len() } else { lines.len().min(MAX_LINES) };
+            for line in &lines[..shown] {
+                writeln!(out, "{}", line)?;
+            }
+            if shown < lines.len() {
+                writeln!(out, "... {} more lines (use --full to show everything)", lines.len() - shown)?;
+            }
+        }
+        None => {
+            let shown = if full { data.len() } else { data.len().min(MAX_HEX_BYTES) };
+            out.push_str(&hexdump(&data[..shown]));
+            if shown < data.len() {
+                writeln!(out, "... {} more bytes (use --full to show everything)", data.len() - shown)?;
+            }
+            let structure = s4pi_reforged::package::structure::guess_structure(&data);
+            let shown = if full { structure.len() } else { structure.len().min(MAX_LINES) };
+            writeln!(out, "\nStructure guess (heuristic):")?;
+            for region in &structure[..shown] {
+                writeln!(out, "{}", region)?;
+            }
+            if shown < structure.len() {
+                writeln!(out, "... {} more regions (use --full to show everything)", structure.len() - shown)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The instances in a SimData resource, one field per line, falling back to its tables where
+/// the objects can't be read
+fn describe_simdata(simdata: &s4pi_reforged::SimDataResource) -> String {
+    use std::fmt::Write as _;
+    let mut out = format!("SimData version 0x{:X}: {} table(s), {} schema(s)\n", simdata.version, simdata.tables.len(), simdata.schemas.len());
+    match simdata.instances() {
+        Ok(instances) => {
+            for (name, object) in instances {
+                let _ = writeln!(out, "\n{} ({})", name, object.schema.as_deref().unwrap_or("no schema name"));
+                for (field, value) in &object.fields {
+                    let _ = writeln!(out, "  {}: {}", field, value);
+                }
+            }
+        }
+        Err(e) => {
+            warn!("Could not read the SimData objects: {:#}", e);
+            let _ = write!(out, "{:#?}", simdata.tables);
+        }
+    }
+    out
+}
+
+fn run_cat(path: &Path, tgi: &TGI) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let entry = pkg.find(tgi).cloned()
+        .with_context(|| format!("Resource {} not found in {:?}", tgi, path))?;
+    let data = pkg.read_raw_resource(&entry)?;
+
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(&data).and_then(|_| stdout.flush()) {
+        // The reader closing the pipe early (e.g. `| head`) is not an error
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn run_set(path: &Path, tgi: &TGI, edits: &[(String, String)], force: bool) -> Result<()> {
+    ensure_not_in_use(&[path.to_path_buf()], force)?;
+    s4pi_reforged::edit::set_fields(path, tgi, edits)?;
+    for (field, value) in edits {
+        info!("Set {} = {}", field, value);
+    }
+    info!("Updated {} in {:?}", tgi, path);
+    Ok(())
+}
+
+/// Saves a bulk operation's failures next to the run logs and tells the user where to find them
+fn write_failure_report(failures: &FailureReport, operation: &str) {
+    if failures.is_empty() {
+        return;
+    }
+    match failures.write(&s4pi_reforged::logging::default_log_dir(), operation) {
+        Ok(path) => warn!("{} failure(s) recorded in {:?}; please attach this file when reporting the problem.", failures.len(), path),
+        Err(e) => error!("Could not write failure report: {:#}", e),
+    }
+}
+
+fn run_texture_report(path: &Path) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let reports = s4pi_reforged::texture::channel_report(&pkg);
+    if reports.is_empty() {
+        info!("No CAS parts or objects with textures found in package.");
+        return Ok(());
+    }
+
+    let mut oversized = 0;
+    let mut incomplete = 0;
+    for report in &reports {
+        let kind = if report.is_cas { "CASP" } else { "OBJD" };
+        println!("\n{} {:08X}:{:08X}:{:016X}", kind, report.owner.res_type, report.owner.res_group, report.owner.instance);
+        for texture in &report.textures {
+            let info = &texture.info;
+            println!(
+                "  {:<9} {:08X}:{:08X}:{:016X}  {}x{}  mips {}  {}{}",
+                format!("{:?}", texture.channel), texture.tgi.res_type, texture.tgi.res_group, texture.tgi.instance,
+                info.width, info.height, info.mip_count, info.pixel_format,
+                if info.is_oversized() { "  ! oversized" } else { "" }
+            );
+        }
+        if !report.missing.is_empty() {
+            incomplete += 1;
+            let missing: Vec<String> = report.missing.iter().map(|c| format!("{:?}", c)).collect();
+            println!("  Missing: {}", missing.join(", "));
+        }
+        oversized += report.oversized().count();
+    }
+
+    println!("\nItems: {} | Missing channels: {} | Oversized textures: {}", reports.len(), incomplete, oversized);
+    Ok(())
+}
+
+fn run_mesh_stats(path: &Path, max_polys: Option<usize>) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let groups = s4pi_reforged::mesh::find_mesh_groups(&pkg);
+    if groups.is_empty() {
+        info!("No GEOM resources found in package.");
+        return Ok(());
+    }
+
+    let entries: HashMap<TGI, s4pi_reforged::IndexEntry> = pkg.entries.iter().map(|e| (e.tgi, e.clone())).collect();
+    let mut over_budget = 0;
+    for group in &groups {
+        match group.owner {
+            Some(owner) => println!("\nCASP {:08X}:{:08X}:{:016X}", owner.res_type, owner.res_group, owner.instance),
+            None => println!("\nUnreferenced GEOM"),
+        }
+        for (lod, tgi) in group.lods.iter().enumerate() {
+            let label = format!("{:08X}:{:08X}:{:016X}", tgi.res_type, tgi.res_group, tgi.instance);
+            let geom = pkg.read_raw_resource(&entries[tgi])
+                .and_then(|data| s4pi_reforged::package::resource::GeomResource::from_package_data(&data));
+            let stats = match geom {
+                Ok(geom) => geom.stats(),
+                Err(e) => {
+                    println!("  LOD{} {}  (could not read: {})", lod, label, e);
+                    continue;
+                }
+            };
+            let size = stats.size();
+            println!(
+                "  LOD{} {}  verts {}  tris {}  size {:.3} x {:.3} x {:.3}  UV [{:.2}, {:.2}]-[{:.2}, {:.2}]  bones {}",
+                lod, label, stats.vertex_count, stats.face_count, size[0], size[1], size[2],
+                stats.uv_min[0], stats.uv_min[1], stats.uv_max[0], stats.uv_max[1], stats.bone_count
+            );
+            if let Some(max) = max_polys {
+                if lod == 0 && stats.face_count > max {
+                    over_budget += 1;
+                    println!("    ! {} polygons is over the budget of {}", stats.face_count, max);
+                }
+            }
+        }
+    }
+
+    if let Some(max) = max_polys {
+        println!("\nMeshes over the {} polygon budget: {}", max, over_budget);
+    }
+    Ok(())
+}
+
+/// Loads the rig given with --rig (a .rig file or a package containing one), or else the first rig in `pkg`
+fn load_rig(pkg: &Package, rig_path: Option<&Path>) -> Result<Option<Vec<s4pi_reforged::package::resource::RigBone>>> {
+    use s4pi_reforged::Resource;
+
+    match rig_path {
+        Some(rig_path) if rig_path.extension().is_some_and(|ext| ext == "package") => {
+            let rig_pkg = Package::open(rig_path)?;
+            Ok(Some(s4pi_reforged::mesh::find_rig(&rig_pkg).context("No clear-format RIG found in the rig package")?))
+        }
+        Some(rig_path) => {
+            let data = std::fs::read(rig_path).with_context(|| format!("Failed to read {:?}", rig_path))?;
+            Ok(Some(s4pi_reforged::RigResource::from_bytes(&data)?.bones()?))
+        }
+        None => Ok(s4pi_reforged::mesh::find_rig(pkg)),
+    }
+}
+
+fn run_mesh_export(path: &Path, rig_path: Option<&Path>, lod: usize) -> Result<()> {
+    let pkg = Package::open(path)?;
+    // Without --rig, each mesh gets the rig its CASP or GEOM refers to
+    let given_rig = match rig_path {
+        Some(_) => load_rig(&pkg, rig_path)?,
+        None => None,
+    };
+    let rig_count = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == s4pi_reforged::package::resource::ResourceKind::Rig)
+        .count();
+    if given_rig.is_none() && rig_count == 0 {
+        warn!("No rig available; meshes will be exported without a skeleton. Use --rig to provide one.");
+    }
+
+    let groups = s4pi_reforged::mesh::find_mesh_groups(&pkg);
+    if groups.is_empty() {
+        info!("No GEOM resources found in package.");
+        return Ok(());
+    }
+
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("meshes");
+    std::fs::create_dir_all(&output_dir).context("Failed to create meshes directory")?;
+
+    let entries: HashMap<TGI, s4pi_reforged::IndexEntry> = pkg.entries.iter().map(|e| (e.tgi, e.clone())).collect();
+    let mut exported = 0;
+    for group in &groups {
+        let Some(last) = group.lods.last() else { continue };
+        let tgi = match group.lods.get(lod) {
+            Some(tgi) => tgi,
+            None => {
+                warn!("{} has only {} LODs; exporting LOD {} instead of LOD {}",
+                    group.owner.unwrap_or(group.lods[0]), group.lods.len(), group.lods.len() - 1, lod);
+                last
+            }
+        };
+        let name = format!("{:08X}!{:08X}!{:016X}", tgi.res_type, tgi.res_group, tgi.instance);
+        let group_rig = match given_rig {
+            Some(_) => None,
+            None => s4pi_reforged::mesh::find_group_rig(&pkg, group),
+        };
+        let rig = given_rig.as_deref().or(group_rig.as_ref().map(|(_, bones)| bones.as_slice()));
+        if rig.is_none() && rig_count > 1 {
+            warn!("{} doesn't refer to any of the package's {} rigs; exporting it without a skeleton. Use --rig to choose one.", name, rig_count);
+        }
+        let result = pkg.read_raw_resource(&entries[tgi])
+            .and_then(|data| s4pi_reforged::package::resource::GeomResource::from_package_data(&data))
+            .and_then(|geom| s4pi_reforged::gltf::geom_to_glb(&geom, rig, &name));
+        match result {
+            Ok(glb) => {
+                perf::write_file(output_dir.join(format!("{}.glb", name)), glb)?;
+                exported += 1;
+            }
+            Err(e) => warn!("Could not export {}: {}", name, e),
+        }
+    }
+
+    info!("Exported {} meshes to: {:?}", exported, output_dir);
+    Ok(())
+}
+
+fn run_mesh_skeleton(path: &Path, export: bool) -> Result<()> {
+    use s4pi_reforged::Resource;
+
+    let skeleton = if path.extension().is_some_and(|ext| ext == "package") {
+        let pkg = Package::open(path)?;
+        let (tgi, skeleton) = s4pi_reforged::mesh::find_skeleton(&pkg).context("No clear-format RIG found in the package")?;
+        info!("Reading RIG {}", tgi);
+        skeleton
+    } else {
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        s4pi_reforged::RigResource::from_bytes(&data)?.skeleton()?
+    };
+    let name = skeleton.name.clone()
+        .unwrap_or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default());
+
+    println!("{} (version {}.{}, {} bones)", name, skeleton.major, skeleton.minor, skeleton.bones.len());
+    let walked = skeleton.walk();
+    for &(bone, depth) in &walked {
+        println!("{}{}  {:08X}", "  ".repeat(depth + 1), skeleton.bones[bone].name, skeleton.bones[bone].hash);
+    }
+    if walked.len() < skeleton.bones.len() {
+        warn!("{} bones are not reachable from a root bone (their parents form a loop).", skeleton.bones.len() - walked.len());
+    }
+
+    let bone_name = |index: i32| {
+        usize::try_from(index).ok().and_then(|i| skeleton.bones.get(i)).map_or("-", |b| b.name.as_str())
+    };
+    if !skeleton.ik_chains.is_empty() {
+        println!("\nIK chains:");
+        for (i, chain) in skeleton.ik_chains.iter().enumerate() {
+            let bones: Vec<&str> = chain.bones.iter().map(|&b| bone_name(b)).collect();
+            println!("  {}: {} (root {}, pole {})", i, bones.join(" -> "), bone_name(chain.root), bone_name(chain.pole));
+        }
+    }
+
+    if export {
+        let output_dir = path.parent().unwrap_or(Path::new(".")).join("meshes");
+        std::fs::create_dir_all(&output_dir).context("Failed to create meshes directory")?;
+        let output = output_dir.join(format!("{}.glb", name));
+        perf::write_file(&output, s4pi_reforged::gltf::skeleton_to_glb(&skeleton.bones, &name)?)?;
+        info!("Exported the skeleton to: {:?}", output);
+    }
+    Ok(())
+}
+
+fn run_mesh_lint(path: &Path, rig_path: Option<&Path>) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let rig = load_rig(&pkg, rig_path)?;
+    if rig.is_none() {
+        warn!("No rig available; bone hashes will not be checked. Use --rig to provide one.");
+    }
+
+    let geoms: Vec<_> = pkg.entries.iter()
+        .filter(|e| e.tgi.res_type == s4pi_reforged::mesh::GEOM_TYPE)
+        .cloned()
+        .collect();
+    if geoms.is_empty() {
+        info!("No GEOM resources found in package.");
+        return Ok(());
+    }
+
+    const MAX_LISTED: usize = 10;
+    let mut meshes_with_issues = 0;
+    for entry in &geoms {
+        let label = format!("{:08X}:{:08X}:{:016X}", entry.tgi.res_type, entry.tgi.res_group, entry.tgi.instance);
+        let geom = match pkg.read_raw_resource(entry)
+            .and_then(|data| s4pi_reforged::package::resource::GeomResource::from_package_data(&data)) {
+            Ok(geom) => geom,
+            Err(e) => {
+                println!("{}  could not read: {}", label, e);
+                meshes_with_issues += 1;
+                continue;
+            }
+        };
+        let issues = s4pi_reforged::mesh::lint_geom(&geom, rig.as_deref());
+        if issues.is_empty() {
+            println!("{}  OK", label);
+            continue;
+        }
+        meshes_with_issues += 1;
+        println!("{}  {} issue(s)", label, issues.len());
+        for issue in issues.iter().take(MAX_LISTED) {
+            println!("    {}", issue);
+        }
+        if issues.len() > MAX_LISTED {
+            println!("    ... and {} more", issues.len() - MAX_LISTED);
+        }
+    }
+
+    println!("\nMeshes checked: {} | With issues: {}", geoms.len(), meshes_with_issues);
+    Ok(())
+}
+
+fn run_tray_sim(path: &Path, mods_folder: Option<&Path>) -> Result<()> {
+    // A Sim saved to the tray is a household of one
+    let is_household = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("householdbinary"));
+    let sims = if is_household {
+        s4pi_reforged::tray::Household::open(path)?.sims
+    } else {
+        vec![s4pi_reforged::tray::SimFile::open(path)?]
+    };
+
+    println!("{}", path.display());
+    for sim in &sims {
+        println!("\n  {}", sim.name().as_deref().unwrap_or("(unnamed)"));
+        if let Some(age) = sim.age {
+            println!("    Age: {}", age);
+        }
+        if let Some(skin_tone) = sim.skin_tone {
+            println!("    Skin tone: {:016X}", skin_tone);
+        }
+        println!("    Outfits: {} ({} parts)", sim.outfits.len(), sim.outfits.iter().map(|o| o.parts.len()).sum::<usize>());
+        println!("    Sculpts: {}", sim.face.sculpts.len());
+        println!("    Sliders: {}", sim.sliders().len());
+        for slider in sim.sliders() {
+            println!("      {:016X}  {:.3}", slider.instance, slider.amount);
+        }
+        let genetics = &sim.genetics;
+        println!("    Genetics: {} parts, {} sculpts, {} sliders", genetics.parts.len(), genetics.face.sculpts.len(), genetics.face.sliders.len());
+        if let Some(physique) = &genetics.physique {
+            println!("      Physique: {}", physique);
+        }
+        println!("    Resource references: {}", sim.references.len());
+    }
+
+    let Some(mods_folder) = mods_folder else {
+        return Ok(());
+    };
+
+    info!("Indexing CC in: {:?}", mods_folder);
+    let index = s4pi_reforged::tray::CcIndex::build(mods_folder)?;
+    info!("Indexed {} packages.", index.packages.len());
+
+    let references = sims.iter().flat_map(|sim| sim.references.iter().copied()).collect();
+    let required = index.providing(&references);
+    let packages = index.packages_providing(&references);
+    println!("\n--- Required CC ---");
+    for package in &packages {
+        println!("{}", package.display());
+        for (tgi, _) in required.iter().filter(|(_, p)| p == package) {
+            println!("    {:08X}:{:08X}:{:016X}", tgi.res_type, tgi.res_group, tgi.instance);
+        }
+    }
+    println!("\nPackages required: {}", packages.len());
+    Ok(())
+}
+
+fn run_tray_household(path: &Path, mods_folder: &Path) -> Result<()> {
+    let household = s4pi_reforged::tray::Household::open(path)?;
+
+    info!("Indexing CC in: {:?}", mods_folder);
+    let index = s4pi_reforged::tray::CcIndex::build(mods_folder)?;
+    info!("Indexed {} packages.", index.packages.len());
+
+    println!("{}", household.name.as_deref().unwrap_or("(unnamed household)"));
+    if let Some(description) = &household.description {
+        println!("  {}", description);
+    }
+    for (i, sim) in household.sims.iter().enumerate() {
+        let packages = index.required_packages(sim);
+        let name = sim.name().unwrap_or_else(|| String::from("(unnamed)"));
+        println!("\nSim {} of {}: {} needs {} package(s)", i + 1, household.sims.len(), name, packages.len());
+        for package in packages {
+            println!("    {}", package.display());
+        }
+    }
+
+    println!("\nPackages required in total: {}", index.packages_providing(&household.references()).len());
+    Ok(())
+}
+
+fn run_stbl_merge(inputs: &[&Path], output: Option<&Path>) -> Result<()> {
+    let output = match output {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let first = inputs[0];
+            let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+            first.with_file_name(format!("{}_strings.package", stem))
+        }
+    };
+    let mut packages: Vec<PathBuf> = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut found: Vec<PathBuf> = WalkDir::new(input)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .map(|e| e.path().to_path_buf())
+                .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+                .collect();
+            found.sort();
+            packages.extend(found);
+        } else {
+            packages.push(input.to_path_buf());
+        }
+    }
+    // A merge from an earlier run is never an input
+    packages.retain(|p| p != &output);
+    info!("Merging string tables from {} packages.", packages.len());
+
+    let tables = s4pi_reforged::stbl::read_tables(&packages);
+    if tables.is_empty() {
+        warn!("No string tables found.");
+        return Ok(());
+    }
+    let merge = s4pi_reforged::stbl::merge_tables(&tables);
+
+    for collision in &merge.collisions {
+        let locale = Locale::from_code(collision.locale).map_or_else(|| format!("locale 0x{:02X}", collision.locale), |l| l.to_string());
+        println!("\nKey 0x{:08X} ({})", collision.key_hash, locale);
+        for (i, definition) in collision.definitions.iter().enumerate() {
+            let kept = if i + 1 == collision.definitions.len() { "  (kept)" } else { "" };
+            println!("  {}{}", definition.package.display(), kept);
+            println!("    {:?}", definition.text);
+        }
+    }
+
+    use s4pi_reforged::package::resource::Resource;
+    let mut resources: HashMap<TGI, RawResource> = HashMap::new();
+    for (tgi, stbl) in &merge.tables {
+        let data = stbl.to_bytes().context("Failed to serialize string table")?;
+        resources.insert(*tgi, (data.clone(), data.len() as u32, 0x5A42, 1));
+    }
+    Package::write_merged(&output, &resources, true).context("Failed to write merged string tables")?;
+
+    println!("\n--- String Table Merge Summary ---");
+    println!("Tables read: {} | Languages: {}", tables.len(), merge.tables.len());
+    for (tgi, stbl) in &merge.tables {
+        let language = locale_of(tgi.instance).map_or_else(|| format!("locale 0x{:02X}", tgi.instance >> 56), |l| l.to_string());
+        println!("  {}: {} strings", language, stbl.entries.len());
+    }
+    println!("Strings replaced by a later table: {} | Colliding keys: {}", merge.replaced, merge.collisions.len());
+    info!("Merged string tables written to: {:?}", output);
+    Ok(())
+}
+
+fn run_stbl_collisions(folder: &Path) -> Result<()> {
+    if !folder.is_dir() {
+        return Err(anyhow!("Folder not found: {:?}", folder));
+    }
+    info!("Scanning string tables in: {:?}", folder);
+    let collisions = s4pi_reforged::stbl::scan_key_collisions(folder);
+
+    for collision in &collisions {
+        let locale = match Locale::from_code(collision.locale) {
+            Some(locale) => locale.to_string(),
+            None => format!("locale 0x{:02X}", collision.locale),
+        };
+        println!("\nKey 0x{:08X} ({})", collision.key_hash, locale);
+        for definition in &collision.definitions {
+            let package = definition.package.strip_prefix(folder).unwrap_or(&definition.package);
+            println!("  {}  [{}]", package.display(), definition.table);
+            println!("    {:?}", definition.text);
+        }
+    }
+
+    let packages: HashSet<&Path> = collisions.iter()
+        .flat_map(|c| c.definitions.iter().map(|d| d.package.as_path()))
+        .collect();
+    println!("\n--- String Key Collisions ---");
+    println!("Colliding keys: {}", collisions.len());
+    println!("Packages involved: {}", packages.len());
+    Ok(())
+}
+
+fn run_install(source: &Path, mods: &Path, into: Option<&Path>, dry_run: bool) -> Result<()> {
+    use s4pi_reforged::install::{apply_install, plan_install, InstallKind};
+    info!("{} {:?} into {:?}", if dry_run { "Checking" } else { "Installing" }, source, mods);
+    let plan = plan_install(source, mods, into)?;
+
+    for file in &plan.files {
+        let kind = if file.kind == InstallKind::Script { "script " } else { "package" };
+        println!("  {} {} -> {}{}", kind, file.source, file.destination.display(),
+            if file.renamed { " (renamed, the name was taken)" } else { "" });
+    }
+    for path in &plan.already_installed {
+        println!("  Already installed: {}", path.display());
+    }
+    for (name, reason) in &plan.rejected {
+        println!("  Skipped {}: {}", name, reason);
+    }
+    if !plan.ignored.is_empty() {
+        println!("  Left out {} other file(s), e.g. {}", plan.ignored.len(), plan.ignored[0]);
+    }
+
+    if plan.files.is_empty() {
+        warn!("Nothing to install from {:?}.", source);
+    } else if dry_run {
+        info!("Dry run: {} file(s) would be installed.", plan.files.len());
+    } else {
+        apply_install(&plan, mods, source)?;
+        info!("Installed {} file(s). Run 's4pi-reforged install --undo' to remove them again.", plan.files.len());
+    }
+    Ok(())
+}
+
+fn run_install_undo(mods: &Path) -> Result<()> {
+    let report = s4pi_reforged::install::undo_last_install(mods)?;
+    info!("Undoing the install of {}", report.source);
+    for path in &report.removed {
+        println!("  Removed {}", path.display());
+    }
+    for path in &report.kept {
+        println!("  Kept {} (changed or removed since it was installed)", path.display());
+    }
+    info!("Removed {} file(s).", report.removed.len());
+    Ok(())
+}
+
+fn run_backup(mods: &Path, dest: &Path) -> Result<()> {
+    info!("Backing up {:?} to {:?}", mods, dest);
+    let report = s4pi_reforged::backup::backup(mods, dest)?;
+    let snapshot = &report.snapshot;
+    if report.created {
+        info!("Snapshot {}: {} file(s), {}; {} new file version(s) stored ({}).", snapshot.id, snapshot.files.len(),
+            perf::format_bytes(snapshot.total_size()), report.new_files, perf::format_bytes(report.new_bytes));
+    } else {
+        info!("Nothing changed since snapshot {}; no new snapshot taken.", snapshot.id);
+    }
+    Ok(())
+}
+
+fn run_backup_list(dest: &Path) -> Result<()> {
+    let snapshots = s4pi_reforged::backup::list_snapshots(dest)?;
+    if snapshots.is_empty() {
+        warn!("No snapshots in {:?}.", dest);
+        return Ok(());
+    }
+    for snapshot in &snapshots {
+        println!("  {}  {} file(s), {}  from {}", snapshot.id, snapshot.files.len(),
+            perf::format_bytes(snapshot.total_size()), snapshot.mods.display());
+    }
+    info!("{} snapshot(s).", snapshots.len());
+    Ok(())
+}
+
+fn run_backup_restore(dest: &Path, id: Option<&str>, to: Option<&Path>, force: bool) -> Result<()> {
+    let mods = match to {
+        Some(to) => to.to_path_buf(),
+        None => {
+            let snapshots = s4pi_reforged::backup::list_snapshots(dest)?;
+            let snapshot = match id {
+                Some(id) => snapshots.iter().find(|s| s.id == id),
+                None => snapshots.last(),
+            };
+            snapshot.map(|s| s.mods.clone())
+                .ok_or_else(|| anyhow!("No snapshot {}in {:?}; see 's4pi-reforged backup list'.", id.map(|id| format!("{} ", id)).unwrap_or_default(), dest))?
+        }
+    };
+    let packages: Vec<PathBuf> = WalkDir::new(&mods)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+        .collect();
+    ensure_not_in_use(&packages, force)?;
+
+    info!("Restoring {:?} from {:?}", mods, dest);
+    let report = s4pi_reforged::backup::restore(dest, id, &mods)?;
+    for path in &report.restored {
+        println!("  Restored {}", path);
+    }
+    for path in &report.removed {
+        println!("  Removed {}", path);
+    }
+    info!("Restored {} file(s), removed {}, {} unchanged.", report.restored.len(), report.removed.len(), report.unchanged);
+    if let Some(before) = &report.before {
+        info!("The folder as it was before is snapshot {}.", before);
+    }
+    Ok(())
+}
+
+fn run_stbl_strip(path: &Path, keep: &[Locale], dry_run: bool, force: bool) -> Result<()> {
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    if !dry_run {
+        ensure_not_in_use(&packages, force)?;
+    }
+    let names: Vec<&str> = keep.iter().map(|l| l.name()).collect();
+    info!("Keeping {} string tables in {} packages.", names.join(", "), packages.len());
+
+    let mut tables = 0;
+    let mut changed = 0;
+    for package in &packages {
+        let removed = match s4pi_reforged::stbl::strip_locales(package, keep, dry_run) {
+            Ok(removed) => removed,
+            Err(e) => {
+                error!("Failed to strip {:?}: {:#}. Skipping.", package, e);
+                continue;
+            }
+        };
+        if removed.is_empty() {
+            continue;
+        }
+        println!("{}: {} table(s)", package.display(), removed.len());
+        tables += removed.len();
+        changed += 1;
+    }
+    println!("\n{} {} string table(s) from {} package(s).", if dry_run { "Would remove" } else { "Removed" }, tables, changed);
+    Ok(())
+}
+
+fn run_modlist(folder: &Path, output: Option<&Path>) -> Result<()> {
+    let entries = s4pi_reforged::census::mod_list(folder);
+    let text = s4pi_reforged::census::format_mod_list(&entries);
+    match output {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("Failed to write {:?}", path))?;
+            info!("Wrote {} files to {:?}.", entries.len(), path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn run_stbl_rekey(path: &Path, mapping_file: &Path, dry_run: bool, force: bool) -> Result<()> {
+    use s4pi_reforged::stbl::{format_key_mapping, parse_key_mapping, rekey_package, reverse_key_mapping};
+    let text = std::fs::read_to_string(mapping_file).with_context(|| format!("Failed to read {:?}", mapping_file))?;
+    let mapping = parse_key_mapping(&text)?;
+    let reverse = reverse_key_mapping(&mapping)?;
+    if mapping.is_empty() {
+        warn!("The mapping file lists no keys.");
+        return Ok(());
+    }
+
+    let mut packages: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    packages.sort();
+    if !dry_run {
+        ensure_not_in_use(&packages, force)?;
+    }
+    info!("{} {} keys in {} packages.", if dry_run { "Checking" } else { "Re-keying" }, mapping.len(), packages.len());
+
+    let mut strings = 0;
+    let mut tuning_refs = 0;
+    let mut blocked = 0;
+    for package in &packages {
+        let report = match rekey_package(package, &mapping, dry_run) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to re-key {:?}: {:#}. Skipping.", package, e);
+                continue;
+            }
+        };
+        if report.strings == 0 && report.tuning_refs == 0 {
+            continue;
+        }
+        println!("\n{}", package.display());
+        println!("  Strings: {} in {} table(s) | Tuning references: {} in {} file(s)",
+            report.strings, report.tables, report.tuning_refs, report.tuning_files);
+        for (table, key) in &report.conflicts {
+            println!("    Conflict: 0x{:08X} is already used in {}", key, table);
+        }
+        if !report.conflicts.is_empty() {
+            blocked += 1;
+            println!("  Left unchanged because of the conflicts above.");
+            continue;
+        }
+        strings += report.strings;
+        tuning_refs += report.tuning_refs;
+    }
+
+    println!("\n--- Re-key Summary ---");
+    println!("Strings {}: {}", if dry_run { "to re-key" } else { "re-keyed" }, strings);
+    println!("Tuning references {}: {}", if dry_run { "to update" } else { "updated" }, tuning_refs);
+    println!("Packages left unchanged because of conflicts: {}", blocked);
+    if !dry_run && strings + tuning_refs > 0 {
+        let stem = mapping_file.file_stem().unwrap_or_default().to_string_lossy();
+        let reverse_file = mapping_file.with_file_name(format!("{}_reverse.txt", stem));
+        s4pi_reforged::atomic::write(&reverse_file, format_key_mapping(&reverse)).with_context(|| format!("Failed to write {:?}", reverse_file))?;
+        println!("Reverse mapping written to: {}", reverse_file.display());
+    }
+    Ok(())
+}
+
+fn run_stbl_pseudo(path: &Path, output: Option<&Path>) -> Result<()> {
+    info!("Pseudo-localizing string tables in: {:?}", path);
+    let pkg = Package::open(path)?;
+
+    let entries: Vec<_> = pkg.entries.iter()
+        .filter(|e| s4pi_reforged::stbl::is_stbl_type(e.tgi.res_type) && locale_of(e.tgi.instance) == Some(Locale::English))
+        .cloned()
+        .collect();
+
+    if entries.is_empty() {
+        warn!("No English string tables found in package.");
+        return Ok(());
+    }
+
+    let options = s4pi_reforged::stbl::PseudoOptions::default();
+    let mut package_data: HashMap<TGI, RawResource> = HashMap::new();
+    let mut string_count = 0;
+
+    use s4pi_reforged::package::resource::Resource;
+    for entry in &entries {
+        let stbl = match pkg.read_resource(entry)? {
+            TypedResource::Stbl(stbl) => stbl,
+            _ => return Err(anyhow!("Resource {:?} is not a string table", entry.tgi)),
+        };
+        let pseudo = s4pi_reforged::stbl::pseudo_localize_table(&stbl, &options);
+        string_count += pseudo.entries.len();
+        let data = pseudo.to_bytes().context("Failed to serialize string table")?;
+        package_data.insert(entry.tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+
+    let output_file = match output {
+        Some(p) => p.to_path_buf(),
+        None => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{}_pseudo.package", stem))
+        }
+    };
+
+    info!("Writing pseudo-localized package to: {:?}", output_file);
+    Package::write_merged(&output_file, &package_data, true).context("Failed to write pseudo-localized package")?;
+
+    info!("Pseudo-localization complete!");
+    info!("String tables: {}", package_data.len());
+    info!("Strings: {}", string_count);
+
+    Ok(())
+}
+
+/// Raw resource payload plus the index metadata needed to write it back out:
+/// (data, memsize, compression, committed).
+type RawResource = (Vec<u8>, u32, u16, u16);
+
+/// Logs the per-type breakdown of a merge, biggest types first. `sources` is the number of
+/// packages merged, for the total line.
+fn log_merge_stats(stats: &MergeStats, sources: usize) {
+    let mut types: Vec<(&u32, &MergeTypeStats)> = stats.by_type.iter().collect();
+    types.sort_by_key(|(res_type, s)| (std::cmp::Reverse(s.stored_bytes), **res_type));
+    info!("--- Merged Resources by Type ---");
+    info!("{:<36} {:>8} {:>8} {:>8} {:>10} {:>12}", "Type", "Merged", "Sources", "Deduped", "Overridden", "Size");
+    for (res_type, s) in types {
+        let label = format!("{} ({:08X})", kind_label(*res_type), res_type);
+        info!("{:<36} {:>8} {:>8} {:>8} {:>10} {:>12}",
+            label, s.merged, s.source_packages, s.deduplicated, s.overridden, perf::format_bytes(s.stored_bytes));
+    }
+    let total = stats.total();
+    info!("{:<36} {:>8} {:>8} {:>8} {:>10} {:>12}",
+        "Total", total.merged, sources, total.deduplicated, total.overridden, perf::format_bytes(total.stored_bytes));
+}
+
+fn run_merge(folder: &std::path::Path, options: &MergeOptions, force: bool, observer: &dyn OperationObserver) -> Result<()> {
+    if options.background {
+        let mode = s4pi_reforged::background::BackgroundMode::default();
+        info!("Merging in the background: {} low-priority thread(s), reads limited to {}/s.",
+            mode.threads, perf::format_bytes(mode.read_rate));
+    }
+    info!("Searching for .package files in: {:?}", folder);
+    let timer = PerfTimer::start("merge");
+    ensure_not_in_use(&s4pi_reforged::merge::output_paths(folder, options.split), force)?;
+    let summary = s4pi_reforged::merge::merge(folder, options, observer)?;
+    write_failure_report(&summary.failures, "merge");
+    if summary.outputs.is_empty() {
+        return Ok(());
+    }
+    if !summary.resolutions.is_empty() {
+        info!("{} conflicting resource(s) resolved; see {:?}", summary.resolutions.len(), folder.join("merged").join("merge_report.txt"));
+    }
+    for output in &summary.outputs {
+        info!("Wrote merged package to: {:?}", output);
+    }
+
+    info!("Merge complete!");
+    info!("Files processed: {}", summary.files_processed);
+    info!("Files skipped: {}", summary.files_skipped);
+    info!("Total resources merged: {}", summary.total_resources);
+    log_merge_stats(&summary.stats, summary.files_processed);
+    timer.finish().log();
+    Ok(())
+}
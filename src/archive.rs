@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use crate::package::Package;
+use log::warn;
+
+/// Archive formats packages can be opened from without extracting them first. RAR archives
+/// aren't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// Needs the `7z` feature
+    SevenZip,
+}
+
+impl ArchiveKind {
+    /// The archive kind for a file name, going by its extension
+    pub fn of<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "zip" => Some(Self::Zip),
+            "7z" => Some(Self::SevenZip),
+            _ => None,
+        }
+    }
+}
+
+/// True for archives this build can open: zip always, 7z with the `7z` feature
+pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+    match ArchiveKind::of(path) {
+        Some(ArchiveKind::Zip) => true,
+        Some(ArchiveKind::SevenZip) => cfg!(feature = "7z"),
+        None => false,
+    }
+}
+
+/// Whether an archive entry's name is a plain relative path that stays inside the folder it is
+/// extracted to: `/`-separated, with no `..`, root, drive prefix or backslash. Zip tools on
+/// Windows read a backslash as a separator, so `..\x.package` would climb out too.
+pub fn is_safe_entry_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(['\\', ':', '\0'])
+        && !name.starts_with('/')
+        && name.split('/').all(|part| part != "..")
+}
+
+fn is_package_name(name: &str) -> bool {
+    name.to_lowercase().ends_with(".package")
+}
+
+fn kind_of(archive: &Path) -> Result<ArchiveKind> {
+    ArchiveKind::of(archive).ok_or_else(|| Error::Other(format!("{:?} is not a zip or 7z archive", archive)))
+}
+
+/// Paths of the .package files inside an archive, in the order it stores them
+pub fn list_packages<P: AsRef<Path>>(archive: P) -> Result<Vec<String>> {
+    let archive = archive.as_ref();
+    match kind_of(archive)? {
+        ArchiveKind::Zip => {
+            let zip = open_zip(archive)?;
+            Ok(zip.file_names().filter(|n| is_package_name(n)).map(str::to_string).collect())
+        }
+        ArchiveKind::SevenZip => seven_zip::list(archive),
+    }
+}
+
+/// Opens the package stored at `name` inside an archive. The package is read into memory, since
+/// compressed archive entries can't be seeked.
+pub fn open_package<P: AsRef<Path>>(archive: P, name: &str) -> Result<Package> {
+    let archive = archive.as_ref();
+    let data = match kind_of(archive)? {
+        ArchiveKind::Zip => {
+            let mut zip = open_zip(archive)?;
+            let mut file = zip.by_name(name)
+                .with_context(|| format!("{} is not in {:?}", name, archive))?;
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            data
+        }
+        ArchiveKind::SevenZip => seven_zip::read_all(archive, |n| n == name)?
+            .pop()
+            .map(|(_, data)| data)
+            .ok_or_else(|| Error::Other(format!("{} is not in {:?}", name, archive)))?,
+    };
+    Package::from_reader(Cursor::new(data)).with_context(|| format!("Failed to open {} in {:?}", name, archive))
+}
+
+/// Opens every package inside an archive. A package that fails to open doesn't stop the others.
+pub fn open_packages<P: AsRef<Path>>(archive: P) -> Result<Vec<(String, Result<Package>)>> {
+    let archive = archive.as_ref();
+    match kind_of(archive)? {
+        ArchiveKind::Zip => list_packages(archive)?
+            .into_iter()
+            .map(|name| {
+                let pkg = open_package(archive, &name);
+                Ok((name, pkg))
+            })
+            .collect(),
+        // 7z archives are usually solid, so everything is read in one pass
+        ArchiveKind::SevenZip => Ok(seven_zip::read_all(archive, is_package_name)?
+            .into_iter()
+            .map(|(name, data)| {
+                let pkg = Package::from_reader(Cursor::new(data));
+                (name, pkg)
+            })
+            .collect()),
+    }
+}
+
+/// Names and contents of the files inside an archive that `wanted` accepts, e.g. to copy them
+/// out of it. Entries whose names could point outside the folder they are copied to (see
+/// [`is_safe_entry_name`]) are left out with a warning.
+pub fn read_files<P: AsRef<Path>>(archive: P, wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>> {
+    let archive = archive.as_ref();
+    match kind_of(archive)? {
+        ArchiveKind::Zip => {
+            let mut zip = open_zip(archive)?;
+            let mut files = Vec::new();
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+                if file.is_dir() || !wanted(file.name()) {
+                    continue;
+                }
+                if file.enclosed_name().is_none() || !is_safe_entry_name(file.name()) {
+                    warn!("Skipping {:?} in {:?}: its path leads outside the archive's folder", file.name(), archive);
+                    continue;
+                }
+                let mut data = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut data)
+                    .with_context(|| format!("Failed to read {} in {:?}", file.name(), archive))?;
+                files.push((file.name().to_string(), data));
+            }
+            Ok(files)
+        }
+        ArchiveKind::SevenZip => seven_zip::read_all(archive, wanted),
+    }
+}
+
+fn open_zip(archive: &Path) -> Result<zip::ZipArchive<BufReader<File>>> {
+    let file = File::open(archive).with_context(|| format!("Failed to open {:?}", archive))?;
+    zip::ZipArchive::new(BufReader::new(file)).with_context(|| format!("{:?} is not a valid zip archive", archive))
+}
+
+#[cfg(feature = "7z")]
+mod seven_zip {
+    use std::path::Path;
+    use crate::error::{Error, Result};
+    use sevenz_rust::{Password, SevenZReader};
+
+    pub fn list(archive: &Path) -> Result<Vec<String>> {
+        let reader = SevenZReader::open(archive, Password::empty())
+            .map_err(|e| Error::Other(format!("{:?} is not a valid 7z archive: {}", archive, e)))?;
+        Ok(reader.archive().files.iter()
+            .filter(|f| f.has_stream() && !f.is_directory() && super::is_package_name(f.name()))
+            .map(|f| f.name().to_string())
+            .collect())
+    }
+
+    /// Names and contents of the entries `wanted` accepts
+    pub fn read_all(archive: &Path, wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut reader = SevenZReader::open(archive, Password::empty())
+            .map_err(|e| Error::Other(format!("{:?} is not a valid 7z archive: {}", archive, e)))?;
+        let mut files = Vec::new();
+        reader.for_each_entries(|entry, data| {
+            let mut buf = Vec::new();
+            // Entries have to be read through even when skipped, to decode the ones after them
+            data.read_to_end(&mut buf)?;
+            if !entry.is_directory() && wanted(entry.name()) {
+                if super::is_safe_entry_name(entry.name()) {
+                    files.push((entry.name().to_string(), buf));
+                } else {
+                    log::warn!("Skipping {:?} in {:?}: its path leads outside the archive's folder", entry.name(), archive);
+                }
+            }
+            Ok(true)
+        }).map_err(|e| Error::Other(format!("Failed to read {:?}: {}", archive, e)))?;
+        Ok(files)
+    }
+}
+
+#[cfg(not(feature = "7z"))]
+mod seven_zip {
+    use std::path::Path;
+    use crate::error::{Error, Result};
+
+    pub fn list(archive: &Path) -> Result<Vec<String>> {
+        Err(Error::Unsupported(format!("Can't open {:?}: 7z support needs the `7z` feature", archive)))
+    }
+
+    pub fn read_all(archive: &Path, _wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>> {
+        list(archive).map(|_| Vec::new())
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Makes the temporary names of files written at the same time, even to the same path, unique
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// A file written under a temporary name in its destination folder and renamed into place by
+/// [`AtomicFile::commit`], so a crash or failed write never leaves a partial file where the game
+/// (or the next run) will load it. Dropped without committing, the temporary file is deleted and
+/// whatever was at the destination is left alone.
+///
+/// Temporary names end in `.tmp`, so they're never mistaken for packages.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: Option<File>,
+}
+
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(
+            ".{}.{}-{}.tmp", name, std::process::id(), NEXT_TEMP.fetch_add(1, Ordering::Relaxed),
+        ));
+        let file = File::create(&temp)?;
+        Ok(Self { path, temp, file: Some(file) })
+    }
+
+    /// Where the file ends up
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flushes the file to disk, closes it and renames it over the destination, so after a power
+    /// loss the destination holds either the old file or all of the new one
+    pub fn commit(mut self) -> std::io::Result<()> {
+        let file = self.file.take().expect("only taken when committing or dropping");
+        // Renaming an open file fails on Windows
+        let synced = file.sync_all();
+        drop(file);
+        synced.and_then(|_| std::fs::rename(&self.temp, &self.path)).inspect_err(|_| {
+            let _ = std::fs::remove_file(&self.temp);
+        })?;
+        sync_parent(&self.path);
+        Ok(())
+    }
+
+    fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("only taken when committing or dropping")
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file().seek(pos)
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Flushes the folder holding `path` to disk, so a rename into it survives a power loss. Only
+/// Unix needs (or allows) this; the file is already in place, so a filesystem that can't sync
+/// a folder isn't an error.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// `std::fs::write` through an [`AtomicFile`]: `path` ends up with all of `contents` or is left
+/// as it was
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> std::io::Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(contents.as_ref())?;
+    file.commit()
+}
@@ -0,0 +1,107 @@
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use crate::error::{Error, Result};
+use log::warn;
+
+/// Read rate background work is held to unless told otherwise: 32 MB/s
+pub const DEFAULT_READ_RATE: u64 = 32 * 1024 * 1024;
+
+/// How to hold back work that runs while the user is doing something else, like playing or
+/// streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackgroundMode {
+    /// Worker threads to use
+    pub threads: usize,
+    /// Bytes per second to read from packages, or 0 for no limit
+    pub read_rate: u64,
+}
+
+impl Default for BackgroundMode {
+    /// A quarter of the CPU cores (at least one) and [`DEFAULT_READ_RATE`]
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self { threads: (cores / 4).max(1), read_rate: DEFAULT_READ_RATE }
+    }
+}
+
+thread_local! {
+    /// Set on the threads of a background pool, whose reads are rate-limited
+    static IN_BACKGROUND: Cell<bool> = const { Cell::new(false) };
+}
+
+static READ_RATE: AtomicU64 = AtomicU64::new(0);
+/// When the next background read may start, so reads across all background threads share
+/// the rate
+static NEXT_READ: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// True on a thread running background work
+pub fn is_background_thread() -> bool {
+    IN_BACKGROUND.with(Cell::get)
+}
+
+/// Waits as long as reading `bytes` takes at the background read rate. Does nothing outside
+/// background work.
+pub fn throttle_read(bytes: u64) {
+    let rate = READ_RATE.load(Ordering::Relaxed);
+    if rate == 0 || !is_background_thread() {
+        return;
+    }
+    let cost = Duration::from_secs_f64(bytes as f64 / rate as f64);
+    let now = Instant::now();
+    let start = {
+        let mut next = NEXT_READ.lock().unwrap_or_else(|e| e.into_inner());
+        let start = next.filter(|t| *t > now).unwrap_or(now);
+        *next = Some(start + cost);
+        start
+    };
+    if start > now {
+        std::thread::sleep(start - now);
+    }
+}
+
+/// Lowers the calling thread's CPU (and on Windows, IO) priority
+pub fn lower_thread_priority() -> Result<()> {
+    #[cfg(windows)]
+    {
+        extern "system" {
+            fn GetCurrentThread() -> isize;
+            fn SetThreadPriority(thread: isize, priority: i32) -> i32;
+        }
+        const THREAD_MODE_BACKGROUND_BEGIN: i32 = 0x0001_0000;
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(Error::Other(format!("SetThreadPriority failed: {}", std::io::Error::last_os_error())));
+        }
+    }
+    #[cfg(unix)]
+    {
+        extern "C" {
+            fn setpriority(which: i32, who: u32, priority: i32) -> i32;
+        }
+        const PRIO_PROCESS: i32 = 0;
+        // On Linux this only affects the calling thread; elsewhere it lowers the whole process
+        if unsafe { setpriority(PRIO_PROCESS, 0, 10) } != 0 {
+            return Err(Error::Other(format!("setpriority failed: {}", std::io::Error::last_os_error())));
+        }
+    }
+    Ok(())
+}
+
+/// Runs `work` in background mode: on its own pool of low-priority threads, with package reads
+/// made from them held to the mode's read rate. Parallel work inside `work` uses that pool.
+pub fn run<T: Send>(mode: &BackgroundMode, work: impl FnOnce() -> T + Send) -> Result<T> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(mode.threads.max(1))
+        .thread_name(|i| format!("s4pi-background-{}", i))
+        .start_handler(|_| {
+            IN_BACKGROUND.with(|b| b.set(true));
+            if let Err(e) = lower_thread_priority() {
+                warn!("Could not lower the priority of a background thread: {:#}", e);
+            }
+        })
+        .build()
+        .map_err(|e| Error::Other(format!("Failed to start background threads: {}", e)))?;
+    READ_RATE.store(mode.read_rate, Ordering::Relaxed);
+    Ok(pool.install(work))
+}
@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+use crate::atomic::AtomicFile;
+use crate::error::{Context, Error, Result};
+
+/// Folder in a backup holding each file's contents once, named by its SHA-256
+const OBJECTS_DIR: &str = "objects";
+/// Folder in a backup holding one list of files per snapshot
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// One file of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFile {
+    /// Relative to the Mods folder, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// The state of a Mods folder at one point, as recorded by [`backup`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Unique within the backup and sorts in the order the snapshots were taken
+    pub id: String,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+    /// The folder that was backed up
+    pub mods: PathBuf,
+    /// Sorted by path
+    pub files: Vec<SnapshotFile>,
+}
+
+impl Snapshot {
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("snapshot\t{}\t{}\n", self.created, self.mods.display());
+        for file in &self.files {
+            text.push_str(&format!("{}\t{}\t{}\n", file.sha256, file.size, file.path));
+        }
+        text
+    }
+
+    fn from_text(id: &str, text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        let header = lines.next().and_then(|l| l.strip_prefix("snapshot\t"))
+            .ok_or_else(|| Error::InvalidInput(format!("Snapshot {} has no header", id)))?;
+        let (created, mods) = header.split_once('\t')
+            .ok_or_else(|| Error::InvalidInput(format!("Malformed header in snapshot {}: {}", id, header)))?;
+        let files = lines
+            .map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                match (fields.next(), fields.next().and_then(|s| s.parse().ok()), fields.next()) {
+                    (Some(sha256), Some(size), Some(path)) => Ok(SnapshotFile { path: path.to_string(), size, sha256: sha256.to_string() }),
+                    _ => Err(Error::InvalidInput(format!("Malformed line in snapshot {}: {}", id, line))),
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            id: id.to_string(),
+            created: created.parse().map_err(|_| Error::InvalidInput(format!("Malformed time in snapshot {}: {}", id, created)))?,
+            mods: PathBuf::from(mods),
+            files,
+        })
+    }
+}
+
+/// What [`backup`] did
+#[derive(Debug, Clone)]
+pub struct BackupReport {
+    pub snapshot: Snapshot,
+    /// False when nothing had changed since the latest snapshot, which is then reported instead
+    pub created: bool,
+    /// Files whose contents weren't in the backup yet, and their total size
+    pub new_files: usize,
+    pub new_bytes: u64,
+}
+
+/// What [`restore`] did
+#[derive(Debug, Clone, Default)]
+pub struct RestoreReport {
+    /// Snapshot of the folder as it was just before the restore, so the restore can be undone
+    pub before: Option<String>,
+    /// Files written from the backup, because they were missing or different
+    pub restored: Vec<String>,
+    /// Files that weren't in the restored snapshot
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+fn object_path(dest: &Path, sha256: &str) -> PathBuf {
+    dest.join(OBJECTS_DIR).join(&sha256[..2]).join(sha256)
+}
+
+fn hash_file(path: &Path) -> Result<(u64, String)> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok((size, hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()))
+}
+
+/// Copies `from` to `to` through an [`AtomicFile`], creating `to`'s folder
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    let mut source = File::open(from).with_context(|| format!("Failed to open {:?}", from))?;
+    let mut out = AtomicFile::create(to).with_context(|| format!("Failed to create {:?}", to))?;
+    std::io::copy(&mut source, &mut out).with_context(|| format!("Failed to copy {:?} to {:?}", from, to))?;
+    out.commit().with_context(|| format!("Failed to write {:?}", to))
+}
+
+/// Every file under `mods`, hashed, leaving out `dest` when the backup is kept inside the Mods
+/// folder and temporary files left by interrupted writes
+fn scan(mods: &Path, dest: &Path) -> Result<Vec<(PathBuf, SnapshotFile)>> {
+    let dest = dest.canonicalize().unwrap_or_else(|_| dest.to_path_buf());
+    let paths: Vec<PathBuf> = WalkDir::new(mods)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.path().canonicalize().map_or(true, |p| p != dest))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy();
+            !(name.starts_with('.') && name.ends_with(".tmp"))
+        })
+        .map(|e| e.into_path())
+        .collect();
+    paths.into_par_iter()
+        .map(|path| {
+            let (size, sha256) = hash_file(&path)?;
+            let relative = path.strip_prefix(mods).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            Ok((path, SnapshotFile { path: relative, size, sha256 }))
+        })
+        .collect()
+}
+
+/// The snapshots in the backup at `dest`, oldest first
+pub fn list_snapshots<P: AsRef<Path>>(dest: P) -> Result<Vec<Snapshot>> {
+    let dir = dest.as_ref().join(SNAPSHOTS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(id) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".txt")) else {
+            continue;
+        };
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        snapshots.push(Snapshot::from_text(id, &text)?);
+    }
+    snapshots.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+    Ok(snapshots)
+}
+
+/// Backs up every file in `mods` to `dest`, which can be an empty or missing folder or an
+/// earlier backup.
+///
+/// Each file's contents are stored once, named by their SHA-256, so a file that's in several
+/// snapshots (or in several places) takes up space only once, and a new snapshot only adds the
+/// files that changed. When nothing changed since the latest snapshot, no new one is made.
+pub fn backup<P: AsRef<Path>, Q: AsRef<Path>>(mods: P, dest: Q) -> Result<BackupReport> {
+    let (mods, dest) = (mods.as_ref(), dest.as_ref());
+    if !mods.is_dir() {
+        return Err(Error::InvalidInput(format!("Not a directory: {:?}", mods)));
+    }
+    let files = scan(mods, dest)?;
+
+    let mut new_files = 0;
+    let mut new_bytes = 0;
+    for (path, file) in &files {
+        let object = object_path(dest, &file.sha256);
+        if !object.is_file() {
+            copy_file(path, &object)?;
+            new_files += 1;
+            new_bytes += file.size;
+        }
+    }
+
+    let files: Vec<SnapshotFile> = files.into_iter().map(|(_, file)| file).collect();
+    let mods = mods.canonicalize().unwrap_or_else(|_| mods.to_path_buf());
+    if let Some(latest) = list_snapshots(dest)?.pop().filter(|s| s.files == files && s.mods == mods) {
+        return Ok(BackupReport { snapshot: latest, created: false, new_files, new_bytes });
+    }
+
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let dir = dest.join(SNAPSHOTS_DIR);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let id = (1..).map(|n| if n == 1 { created.to_string() } else { format!("{}-{}", created, n) })
+        .find(|id| !dir.join(format!("{}.txt", id)).exists())
+        .expect("some suffix is free");
+    let snapshot = Snapshot { id, created, mods, files };
+    let path = dir.join(format!("{}.txt", snapshot.id));
+    crate::atomic::write(&path, snapshot.to_text()).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(BackupReport { snapshot, created: true, new_files, new_bytes })
+}
+
+/// Puts `mods` back the way it was in snapshot `id` of the backup at `dest` (the latest one
+/// with `None`): files that are missing or different are written from the backup, and files
+/// the snapshot didn't have are removed, along with folders that leaves empty.
+///
+/// The folder is backed up first, so the restore itself can be undone by restoring
+/// [`RestoreReport::before`]. Nothing is changed unless every file of the snapshot is in the
+/// backup and undamaged.
+pub fn restore<P: AsRef<Path>, Q: AsRef<Path>>(dest: P, id: Option<&str>, mods: Q) -> Result<RestoreReport> {
+    let (dest, mods) = (dest.as_ref(), mods.as_ref());
+    let snapshots = list_snapshots(dest)?;
+    let snapshot = match id {
+        Some(id) => snapshots.iter().find(|s| s.id == id)
+            .ok_or_else(|| Error::InvalidInput(format!("No snapshot {} in {:?}", id, dest)))?,
+        None => snapshots.last().ok_or_else(|| Error::InvalidInput(format!("No snapshots in {:?}", dest)))?,
+    }.clone();
+
+    // Check the backup before touching the folder
+    let mut needed: Vec<&SnapshotFile> = snapshot.files.iter().collect();
+    needed.sort_by(|a, b| a.sha256.cmp(&b.sha256));
+    needed.dedup_by(|a, b| a.sha256 == b.sha256);
+    needed.par_iter()
+        .map(|file| {
+            let object = object_path(dest, &file.sha256);
+            match hash_file(&object) {
+                Ok((size, sha256)) if size == file.size && sha256 == file.sha256 => Ok(()),
+                Ok(_) => Err(Error::Other(format!("The backup of {} is damaged ({:?})", file.path, object))),
+                Err(e) => Err(Error::Other(format!("The backup of {} is missing: {}", file.path, e))),
+            }
+        })
+        .collect::<Result<Vec<()>>>()?;
+
+    let mut report = RestoreReport::default();
+    std::fs::create_dir_all(mods).with_context(|| format!("Failed to create {:?}", mods))?;
+    let before = backup(mods, dest)?;
+    report.before = Some(before.snapshot.id.clone());
+    let current: HashMap<&str, &SnapshotFile> = before.snapshot.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    for file in &snapshot.files {
+        if current.get(file.path.as_str()).is_some_and(|c| c.sha256 == file.sha256) {
+            report.unchanged += 1;
+            continue;
+        }
+        copy_file(&object_path(dest, &file.sha256), &mods.join(&file.path))?;
+        report.restored.push(file.path.clone());
+    }
+
+    let wanted: HashSet<&str> = snapshot.files.iter().map(|f| f.path.as_str()).collect();
+    for file in &before.snapshot.files {
+        if wanted.contains(file.path.as_str()) {
+            continue;
+        }
+        let path = mods.join(&file.path);
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        let mut folder = path.parent();
+        while let Some(dir) = folder.filter(|d| *d != mods && d.starts_with(mods)) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+            folder = dir.parent();
+        }
+        report.removed.push(file.path.clone());
+    }
+    Ok(report)
+}
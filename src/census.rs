@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::manifest::read_descriptor;
+use crate::observer::{LogObserver, OperationObserver, Progress};
+
+/// One copy of a resource found by a [`Census`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CensusEntry {
+    /// The package holding it, relative to the scanned folder
+    pub package: PathBuf,
+    /// Decompressed size from the package index
+    pub size: u32,
+}
+
+/// Every resource key in a folder of packages, read from the package indexes only
+#[derive(Debug, Default)]
+pub struct Census {
+    pub packages: Vec<PathBuf>,
+    /// Every copy of each resource; more than one when packages in the folder conflict
+    pub resources: HashMap<TGI, Vec<CensusEntry>>,
+}
+
+impl Census {
+    /// Reads the index of every .package under `folder`. Packages that fail to open are skipped.
+    ///
+    /// A content descriptor (see [`write_descriptor`](crate::manifest::write_descriptor)) counts
+    /// as the packages it describes, named `<descriptor>/<package>`. `folder` can also be a
+    /// single package, or a descriptor, whose packages then keep their own names.
+    pub fn scan<P: AsRef<Path>>(folder: P) -> Self {
+        Self::scan_with(folder, &LogObserver::default())
+    }
+
+    /// [`scan`](Self::scan), telling `observer` as each package is read and which were skipped
+    pub fn scan_with<P: AsRef<Path>>(folder: P, observer: &dyn OperationObserver) -> Self {
+        let folder = folder.as_ref();
+        let mut packages: Vec<PathBuf> = WalkDir::new(folder)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext == "package"))
+            .map(|e| e.path().to_path_buf())
+            .collect();
+        packages.sort();
+        let progress = Progress::start(observer, "scan", Some(packages.len()));
+
+        // Each package's contents, as (path within the package, resources); only a descriptor
+        // has more than one
+        type Contents = Vec<(Option<String>, Vec<(TGI, u32)>)>;
+        let indexes: Vec<Option<Contents>> = packages
+            .par_iter()
+            .map(|path| match Package::open(path) {
+                Ok(pkg) => {
+                    progress.advance(&path.to_string_lossy());
+                    match read_descriptor(&pkg) {
+                        Ok(Some(manifest)) => Some(manifest.entries.into_iter()
+                            .map(|entry| {
+                                let sizes = entry.checksums.iter().map(|c| c.size).chain(std::iter::repeat(0));
+                                (Some(entry.name), entry.resources.into_iter().zip(sizes).collect())
+                            })
+                            .collect()),
+                        _ => Some(vec![(None, pkg.entries.iter().map(|e| (e.tgi, e.memsize)).collect())]),
+                    }
+                }
+                Err(e) => {
+                    progress.skip(&path.to_string_lossy(), &e.to_string());
+                    None
+                }
+            })
+            .collect();
+
+        let mut listed = Vec::new();
+        let mut resources: HashMap<TGI, Vec<CensusEntry>> = HashMap::new();
+        for (path, contents) in packages.iter().zip(indexes) {
+            // Empty when `folder` is the package itself
+            let relative = path.strip_prefix(folder).unwrap_or(path);
+            // A package that failed to open is still listed, with no resources
+            for (name, index) in contents.unwrap_or_else(|| vec![(None, Vec::new())]) {
+                let package = match name {
+                    Some(name) => relative.join(name),
+                    None if relative.as_os_str().is_empty() => path.to_path_buf(),
+                    None => relative.to_path_buf(),
+                };
+                for (tgi, size) in index {
+                    resources.entry(tgi).or_default().push(CensusEntry { package: package.clone(), size });
+                }
+                listed.push(package);
+            }
+        }
+        let packages = listed;
+        progress.finish(true);
+        Self { packages, resources }
+    }
+
+    /// Number of resources of each type
+    pub fn type_counts(&self) -> BTreeMap<u32, usize> {
+        let mut counts = BTreeMap::new();
+        for tgi in self.resources.keys() {
+            *counts.entry(tgi.res_type).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// A resource present in both snapshots whose copies differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CensusChange {
+    pub tgi: TGI,
+    pub before: Vec<CensusEntry>,
+    pub after: Vec<CensusEntry>,
+}
+
+/// What changed between two [`Census`] snapshots
+#[derive(Debug, Default)]
+pub struct CensusDiff {
+    pub removed: Vec<(TGI, Vec<CensusEntry>)>,
+    pub added: Vec<(TGI, Vec<CensusEntry>)>,
+    /// Resources whose size changed
+    pub changed: Vec<CensusChange>,
+    /// Resources with the same size that now live in different packages
+    pub moved: Vec<CensusChange>,
+    /// Resource count per type before and after, for types whose count changed
+    pub type_counts: BTreeMap<u32, (usize, usize)>,
+}
+
+impl CensusDiff {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty() && self.moved.is_empty()
+    }
+}
+
+fn sort_key(tgi: &TGI) -> (u32, u32, u64) {
+    (tgi.res_type, tgi.res_group, tgi.instance)
+}
+
+/// Compares two snapshots of a folder, e.g. before and after a cleanup or a game patch.
+///
+/// Only the package indexes are compared, so a resource whose data changed but kept the same
+/// size is not reported.
+pub fn diff(before: &Census, after: &Census) -> CensusDiff {
+    let mut result = CensusDiff::default();
+
+    for (tgi, old) in &before.resources {
+        match after.resources.get(tgi) {
+            None => result.removed.push((*tgi, old.clone())),
+            Some(new) => {
+                let mut old_sizes: Vec<u32> = old.iter().map(|e| e.size).collect();
+                let mut new_sizes: Vec<u32> = new.iter().map(|e| e.size).collect();
+                old_sizes.sort();
+                new_sizes.sort();
+                let change = CensusChange { tgi: *tgi, before: old.clone(), after: new.clone() };
+                if old_sizes != new_sizes {
+                    result.changed.push(change);
+                } else if old.iter().map(|e| &e.package).ne(new.iter().map(|e| &e.package)) {
+                    result.moved.push(change);
+                }
+            }
+        }
+    }
+    for (tgi, new) in &after.resources {
+        if !before.resources.contains_key(tgi) {
+            result.added.push((*tgi, new.clone()));
+        }
+    }
+    result.removed.sort_by_key(|(tgi, _)| sort_key(tgi));
+    result.added.sort_by_key(|(tgi, _)| sort_key(tgi));
+    result.changed.sort_by_key(|c| sort_key(&c.tgi));
+    result.moved.sort_by_key(|c| sort_key(&c.tgi));
+
+    let old_counts = before.type_counts();
+    let new_counts = after.type_counts();
+    for res_type in old_counts.keys().chain(new_counts.keys()) {
+        let counts = (old_counts.get(res_type).copied().unwrap_or(0), new_counts.get(res_type).copied().unwrap_or(0));
+        if counts.0 != counts.1 {
+            result.type_counts.insert(*res_type, counts);
+        }
+    }
+    result
+}
+
+/// One file in a [`mod_list`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModListEntry {
+    /// Relative to the scanned folder
+    pub path: PathBuf,
+    pub size: u64,
+    /// Resources in a package's index; `None` for script mods and packages that fail to open
+    pub resources: Option<usize>,
+}
+
+/// Every package and script mod under `folder`, sorted by path: what is installed, for sharing
+/// with a creator or comparing with another setup
+pub fn mod_list<P: AsRef<Path>>(folder: P) -> Vec<ModListEntry> {
+    let folder = folder.as_ref();
+    let mut files: Vec<(PathBuf, u64)> = WalkDir::new(folder)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext == "package" || ext == "ts4script"))
+        .map(|e| (e.path().to_path_buf(), e.metadata().map_or(0, |m| m.len())))
+        .collect();
+    files.sort();
+    files.par_iter()
+        .map(|(path, size)| ModListEntry {
+            path: path.strip_prefix(folder).unwrap_or(path).to_path_buf(),
+            size: *size,
+            resources: match path.extension().is_some_and(|ext| ext == "package") {
+                true => Package::open(path).ok().map(|pkg| pkg.entries.len()),
+                false => None,
+            },
+        })
+        .collect()
+}
+
+/// A mod list as text: a header line, then one tab-separated line of path, size in bytes and
+/// resource count (`-` if unknown) per file
+pub fn format_mod_list(entries: &[ModListEntry]) -> String {
+    let mut out = String::from("# path\tsize\tresources\n");
+    for entry in entries {
+        let resources = entry.resources.map_or_else(|| "-".to_string(), |n| n.to_string());
+        out.push_str(&format!("{}\t{}\t{}\n", entry.path.to_string_lossy().replace('\\', "/"), entry.size, resources));
+    }
+    out
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::census::Census;
+use crate::error::{Error, Result};
+use crate::manifest::MANIFEST_TYPES;
+use crate::package::index::TGI;
+use crate::package::resource::{ResourceKind, TypedResource};
+use crate::provenance::PROVENANCE_TYPE;
+
+/// How a merge picks between input packages that contain the same resource
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Refuse to merge if any resource is in more than one package
+    Error,
+    /// The package whose name sorts last wins, as when the game loads the packages unmerged
+    #[default]
+    LastAlphabetical,
+    /// The named package wins for each listed resource; anything not listed falls back to
+    /// [`ConflictPolicy::LastAlphabetical`]
+    Choose(HashMap<TGI, String>),
+}
+
+impl ConflictPolicy {
+    /// Parses the value of `merge --on-conflict`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Self::Error),
+            "last" | "last-wins" => Some(Self::LastAlphabetical),
+            _ => None,
+        }
+    }
+}
+
+/// A resource found in more than one input package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub tgi: TGI,
+    /// The packages that contain it, in the order they sort (the last one wins by default)
+    pub packages: Vec<String>,
+}
+
+/// Which package's copy of a conflicting resource a merge kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub tgi: TGI,
+    pub winner: String,
+    pub losers: Vec<String>,
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} from {} (also in {})", self.tgi, self.winner, self.losers.join(", "))
+    }
+}
+
+/// Orders package names the way the game loads them: case-insensitively, by path
+fn load_order(a: &str, b: &str) -> std::cmp::Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b))
+}
+
+/// Finds every resource key listed by more than one of `packages` (name and resource keys)
+pub fn find_conflicts<'a, I>(packages: I) -> Vec<Conflict>
+where
+    I: IntoIterator<Item = (&'a str, &'a [TGI])>,
+{
+    let mut owners: HashMap<TGI, Vec<String>> = HashMap::new();
+    for (name, tgis) in packages {
+        for tgi in tgis {
+            let names = owners.entry(*tgi).or_default();
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    let mut conflicts: Vec<Conflict> = owners.into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(tgi, mut packages)| {
+            packages.sort_by(|a, b| load_order(a, b));
+            Conflict { tgi, packages }
+        })
+        .collect();
+    conflicts.sort_by_key(|c| (c.tgi.res_type, c.tgi.res_group, c.tgi.instance));
+    conflicts
+}
+
+/// Picks a winner for every conflict according to `policy`
+pub fn resolve(conflicts: &[Conflict], policy: &ConflictPolicy) -> Result<Vec<Resolution>> {
+    if *policy == ConflictPolicy::Error && !conflicts.is_empty() {
+        let examples: Vec<String> = conflicts.iter().take(5)
+            .map(|c| format!("  {} in {}", c.tgi, c.packages.join(", ")))
+            .collect();
+        return Err(Error::Other(format!(
+            "{} resource(s) are in more than one package:\n{}{}",
+            conflicts.len(),
+            examples.join("\n"),
+            if conflicts.len() > examples.len() { "\n  ..." } else { "" },
+        )));
+    }
+
+    conflicts.iter()
+        .map(|conflict| {
+            let chosen = match policy {
+                ConflictPolicy::Choose(choices) => choices.get(&conflict.tgi),
+                _ => None,
+            };
+            let winner = match chosen {
+                Some(name) if conflict.packages.contains(name) => name.clone(),
+                Some(name) => return Err(Error::Other(format!("{} is not in {}", conflict.tgi, name))),
+                None => conflict.packages.last().cloned().unwrap_or_default(),
+            };
+            let losers = conflict.packages.iter().filter(|p| **p != winner).cloned().collect();
+            Ok(Resolution { tgi: conflict.tgi, winner, losers })
+        })
+        .collect()
+}
+
+/// How much a conflict between packages in a Mods folder is likely to matter in game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Thumbnails and name maps: at worst a wrong picture in the catalog
+    Low,
+    /// Meshes, textures and other assets: the item looks like one package's version
+    Medium,
+    /// Tuning, SimData, object and CAS definitions, string tables and scripts: only one
+    /// package's version takes effect, which breaks mods that expect theirs
+    High,
+}
+
+impl Severity {
+    pub fn of(res_type: u32) -> Self {
+        if crate::tuning::tuning_class(res_type).is_some() {
+            return Severity::High;
+        }
+        match TypedResource::kind_of(res_type) {
+            ResourceKind::Thumbnail | ResourceKind::NameMap => Severity::Low,
+            ResourceKind::SimData | ResourceKind::ObjectDefinition | ResourceKind::Catalog | ResourceKind::Cwal
+            | ResourceKind::Cfnd | ResourceKind::Cstr | ResourceKind::CasPart | ResourceKind::Stbl
+            | ResourceKind::Script | ResourceKind::Xml => Severity::High,
+            _ => Severity::Medium,
+        }
+    }
+
+    /// What a conflict of this severity means for the player
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Severity::Low => "usually harmless; at worst a wrong catalog thumbnail",
+            Severity::Medium => "the item looks like the last package's version",
+            Severity::High => "only the last package's version takes effect; the other mods may break",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// Finds every resource that more than one package under `folder` contains, reading only the
+/// package indexes. Packages are named by their path relative to `folder`, and listed in the
+/// order the game loads them, so the last one is the copy the game uses.
+///
+/// Merge manifests and provenance records are left out; every merged package has them at the
+/// same key, and the game doesn't load them.
+pub fn folder_conflicts<P: AsRef<Path>>(folder: P) -> Vec<Conflict> {
+    let census = Census::scan(folder);
+    let mut contents: HashMap<String, Vec<TGI>> = HashMap::new();
+    for (tgi, copies) in census.resources.iter().filter(|(_, copies)| copies.len() > 1) {
+        if MANIFEST_TYPES.contains(&tgi.res_type) || tgi.res_type == PROVENANCE_TYPE {
+            continue;
+        }
+        for copy in copies {
+            contents.entry(copy.package.to_string_lossy().replace('\\', "/")).or_default().push(*tgi);
+        }
+    }
+    find_conflicts(contents.iter().map(|(name, tgis)| (name.as_str(), tgis.as_slice())))
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::Path;
+use crate::error::{Context, Result};
+use crate::instance::{fnv64, name_instance};
+use crate::package::Package;
+use crate::package::diff::PackageDiff;
+use crate::package::index::TGI;
+use crate::package::resource::TypedResource;
+use crate::stbl::is_stbl_type;
+use crate::tuning::{looks_like_xml, tuning_class, tuning_name};
+
+/// Resource type of SimData, the binary half of a tuning resource
+pub const SIMDATA_TYPE: u32 = 0x545AC67A;
+
+/// A problem [`check_changes`] found in a resource that was added or changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DevIssue {
+    /// The resource doesn't read or parse
+    Unreadable(String),
+    /// Tuning with no `n="..."` name on its root element
+    TuningUnnamed,
+    /// The tuning's instance isn't the hash of its name, so tuning that refers to it by name
+    /// won't find it
+    TuningHash { name: String, expected: u64 },
+    /// SimData with no tuning of the same instance
+    OrphanSimData,
+    /// The tuning's SimData was removed
+    MissingSimData { simdata: TGI },
+    /// The tuning changed but its SimData didn't, so the game still loads the old values
+    StaleSimData { simdata: TGI },
+    /// A string table lists the same key more than once
+    DuplicateStringKey(u32),
+    /// A string with key 0, which nothing can refer to
+    ZeroStringKey,
+    /// A key the previous version of the table had is gone
+    RemovedStringKey(u32),
+}
+
+impl std::fmt::Display for DevIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DevIssue::Unreadable(error) => write!(f, "can't be read: {}", error),
+            DevIssue::TuningUnnamed => write!(f, "the tuning has no n=\"...\" name"),
+            DevIssue::TuningHash { name, expected } => write!(
+                f, "the instance isn't the hash of the tuning name '{}'; expected {:016X}", name, expected,
+            ),
+            DevIssue::OrphanSimData => write!(f, "no tuning with the same instance uses this SimData"),
+            DevIssue::MissingSimData { simdata } => write!(f, "its SimData {} was removed", simdata),
+            DevIssue::StaleSimData { simdata } => write!(
+                f, "the tuning changed but its SimData {} didn't; regenerate it if tuned values changed", simdata,
+            ),
+            DevIssue::DuplicateStringKey(key) => write!(f, "string key 0x{:08X} is listed more than once", key),
+            DevIssue::ZeroStringKey => write!(f, "a string has key 0"),
+            DevIssue::RemovedStringKey(key) => write!(f, "string key 0x{:08X} was removed", key),
+        }
+    }
+}
+
+/// What changed between two builds of a package, and what's wrong with the changes
+#[derive(Debug, Clone, Default)]
+pub struct DevReport {
+    pub diff: PackageDiff,
+    /// Problems in added and changed resources, sorted by key
+    pub issues: Vec<(TGI, DevIssue)>,
+}
+
+/// Reads the package at `path` into memory, so it keeps reading correctly after the file is
+/// overwritten by the next build
+pub fn snapshot<P: AsRef<Path>>(path: P) -> Result<Package> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    Package::from_reader(Cursor::new(data)).with_context(|| format!("Failed to open {:?}", path))
+}
+
+fn is_tuning(tgi: &TGI) -> bool {
+    tuning_class(tgi.res_type).is_some()
+}
+
+/// Compares two builds of a package and re-checks every resource that was added or changed:
+/// that tuning is named after its instance, that tuning and SimData still come in pairs, and
+/// that string tables have no duplicate or zero keys and haven't lost keys.
+pub fn check_changes(previous: &Package, current: &Package) -> Result<DevReport> {
+    let diff = previous.diff(current)?;
+    let touched: Vec<TGI> = diff.added.iter().copied().chain(diff.changed.iter().map(|c| c.tgi)).collect();
+    let touched_set: HashSet<TGI> = touched.iter().copied().collect();
+    let simdata_of = |instance: u64| current.entries.iter()
+        .find(|e| e.tgi.res_type == SIMDATA_TYPE && e.tgi.instance == instance)
+        .map(|e| e.tgi);
+    let mut issues = Vec::new();
+
+    for tgi in &touched {
+        let Some(entry) = current.find(tgi) else { continue };
+        if is_tuning(tgi) {
+            let data = match current.read_raw_resource(entry) {
+                Ok(data) => data,
+                Err(e) => {
+                    issues.push((*tgi, DevIssue::Unreadable(e.to_string())));
+                    continue;
+                }
+            };
+            if looks_like_xml(&data) {
+                match tuning_name(&String::from_utf8_lossy(&data)) {
+                    None => issues.push((*tgi, DevIssue::TuningUnnamed)),
+                    Some(name) if tgi.instance != fnv64(&name) && tgi.instance != name_instance(&name) => {
+                        let expected = name_instance(&name);
+                        issues.push((*tgi, DevIssue::TuningHash { name, expected }));
+                    }
+                    Some(_) => {}
+                }
+            }
+            if let Some(simdata) = simdata_of(tgi.instance).filter(|s| !touched_set.contains(s)) {
+                if diff.changed.iter().any(|c| c.tgi == *tgi) {
+                    issues.push((*tgi, DevIssue::StaleSimData { simdata }));
+                }
+            }
+        } else if tgi.res_type == SIMDATA_TYPE {
+            if !current.entries.iter().any(|e| is_tuning(&e.tgi) && e.tgi.instance == tgi.instance) {
+                issues.push((*tgi, DevIssue::OrphanSimData));
+            }
+        } else if is_stbl_type(tgi.res_type) {
+            let stbl = match current.read_resource(entry) {
+                Ok(TypedResource::Stbl(stbl)) => stbl,
+                Ok(_) => continue,
+                Err(e) => {
+                    issues.push((*tgi, DevIssue::Unreadable(e.to_string())));
+                    continue;
+                }
+            };
+            let mut keys = HashSet::new();
+            let mut duplicates = HashSet::new();
+            for entry in &stbl.entries {
+                if entry.key_hash == 0 {
+                    issues.push((*tgi, DevIssue::ZeroStringKey));
+                } else if !keys.insert(entry.key_hash) && duplicates.insert(entry.key_hash) {
+                    issues.push((*tgi, DevIssue::DuplicateStringKey(entry.key_hash)));
+                }
+            }
+            if let Some(Ok(TypedResource::Stbl(before))) = previous.find(tgi).map(|e| previous.read_resource(e)) {
+                let mut removed: Vec<u32> = before.entries.iter().map(|e| e.key_hash)
+                    .filter(|key| !keys.contains(key)).collect();
+                removed.sort_unstable();
+                removed.dedup();
+                issues.extend(removed.into_iter().map(|key| (*tgi, DevIssue::RemovedStringKey(key))));
+            }
+        }
+    }
+
+    // Tuning that stayed while its SimData went
+    for simdata in diff.removed.iter().filter(|t| t.res_type == SIMDATA_TYPE) {
+        let tuning = current.entries.iter().find(|e| is_tuning(&e.tgi) && e.tgi.instance == simdata.instance);
+        if let Some(tuning) = tuning.filter(|_| simdata_of(simdata.instance).is_none()) {
+            issues.push((tuning.tgi, DevIssue::MissingSimData { simdata: *simdata }));
+        }
+    }
+
+    issues.sort_by_key(|(tgi, _)| (tgi.res_type, tgi.res_group, tgi.instance));
+    Ok(DevReport { diff, issues })
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use crate::package::Package;
+use crate::package::index::TGI;
+#[cfg(feature = "catalog")]
+use crate::package::resource::CatalogCommon;
+use crate::package::resource::{Resource, StblEntry, TypedResource};
+
+/// Fields `set` can change on catalog resources (COBJ, CWAL, CFND, CSTR, ...)
+pub const CATALOG_FIELDS: [&str; 5] = ["name_hash", "description_hash", "price", "thumbnail_hash", "dev_category_flags"];
+
+/// Fields `set` can change on CAS parts
+pub const CASP_FIELDS: [&str; 2] = ["name", "tags"];
+
+/// Fields `set` can change on object definitions
+pub const OBJD_FIELDS: [&str; 4] = ["name", "tuning", "tuning_id", "price"];
+
+/// Splits a `field=value` argument
+pub fn parse_assignment(arg: &str) -> Result<(String, String)> {
+    let (field, value) = arg.split_once('=')
+        .ok_or_else(|| Error::InvalidInput(format!("Expected field=value, got '{}'", arg)))?;
+    if field.is_empty() {
+        return Err(Error::InvalidInput(format!("Missing field name in '{}'", arg)));
+    }
+    Ok((field.to_string(), value.to_string()))
+}
+
+/// Parses a decimal number or a `0x` prefixed hex number
+pub fn parse_number(value: &str) -> Result<u64> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|e| Error::InvalidInput(format!("Invalid number '{}': {}", value, e)))
+}
+
+fn parse_u32(value: &str) -> Result<u32> {
+    u32::try_from(parse_number(value)?).map_err(|_| Error::InvalidInput(format!("'{}' does not fit in 32 bits", value)))
+}
+
+#[cfg(feature = "catalog")]
+fn set_catalog_field(common: &mut CatalogCommon, field: &str, value: &str) -> Result<()> {
+    match field {
+        "name_hash" => common.name_hash = parse_u32(value)?,
+        "description_hash" => common.description_hash = parse_u32(value)?,
+        "price" => common.price = parse_u32(value)?,
+        "thumbnail_hash" => common.thumbnail_hash = parse_number(value)?,
+        "dev_category_flags" => common.dev_category_flags = parse_u32(value)?,
+        _ => return Err(Error::InvalidInput(format!("Unknown catalog field '{}' (expected one of: {})", field, CATALOG_FIELDS.join(", ")))),
+    }
+    Ok(())
+}
+
+/// Parses a comma separated `category:value` tag list; an empty list clears the tags
+#[cfg(feature = "catalog")]
+fn parse_tags(value: &str) -> Result<Vec<(u16, u32)>> {
+    value.split(',').map(str::trim).filter(|t| !t.is_empty())
+        .map(|tag| {
+            let (category, value) = tag.split_once(':')
+                .ok_or_else(|| Error::InvalidInput(format!("Expected category:value, got '{}'", tag)))?;
+            let category = u16::try_from(parse_number(category)?)
+                .map_err(|_| Error::InvalidInput(format!("Tag category '{}' does not fit in 16 bits", category)))?;
+            Ok((category, parse_u32(value)?))
+        })
+        .collect()
+}
+
+/// Applies one `field=value` edit to a parsed resource.
+///
+/// Catalog resources take the [`CATALOG_FIELDS`], CAS parts the [`CASP_FIELDS`], with tags as a
+/// `category:value,...` list, and object definitions the [`OBJD_FIELDS`]. For string tables the field is the string's
+/// key hash and the value its new text; keys that aren't in the table yet are added.
+pub fn set_field(resource: &mut TypedResource, field: &str, value: &str) -> Result<()> {
+    match resource {
+        #[cfg(feature = "catalog")]
+        TypedResource::Catalog(r) => set_catalog_field(&mut r.common, field, value),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cwal(r) => set_catalog_field(&mut r.common, field, value),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cfnd(r) => set_catalog_field(&mut r.common, field, value),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cstr(r) => set_catalog_field(&mut r.common, field, value),
+        TypedResource::Stbl(stbl) => {
+            let key_hash = parse_u32(field).context("String table fields are key hashes")?;
+            match stbl.entries.iter_mut().find(|e| e.key_hash == key_hash) {
+                Some(entry) => entry.string_value = value.to_string(),
+                None => stbl.entries.push(StblEntry { key_hash, flags: 0, string_value: value.to_string() }),
+            }
+            stbl.recalculate_string_length();
+            Ok(())
+        }
+        #[cfg(feature = "catalog")]
+        TypedResource::CasPart(casp) => match field {
+            "name" => casp.set_name(value),
+            "tags" => casp.set_tags(&parse_tags(value)?),
+            _ => Err(Error::InvalidInput(format!("Unknown CAS part field '{}' (expected one of: {})", field, CASP_FIELDS.join(", ")))),
+        },
+        #[cfg(feature = "catalog")]
+        TypedResource::ObjectDefinition(objd) => {
+            match field {
+                "name" => objd.set_name(value),
+                "tuning" => objd.set_tuning(value),
+                "tuning_id" => objd.set_tuning_id(parse_number(value)?),
+                "price" => objd.set_simoleon_price(parse_u32(value)?),
+                _ => return Err(Error::InvalidInput(format!("Unknown object definition field '{}' (expected one of: {})", field, OBJD_FIELDS.join(", ")))),
+            }
+            Ok(())
+        }
+        _ => Err(Error::InvalidInput("Editing this resource type is not supported".to_string())),
+    }
+}
+
+fn to_bytes(resource: &TypedResource) -> Result<Vec<u8>> {
+    match resource {
+        #[cfg(feature = "catalog")]
+        TypedResource::Catalog(r) => r.to_bytes(),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cwal(r) => r.to_bytes(),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cfnd(r) => r.to_bytes(),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cstr(r) => r.to_bytes(),
+        TypedResource::Stbl(r) => r.to_bytes(),
+        #[cfg(feature = "catalog")]
+        TypedResource::CasPart(r) => r.to_bytes(),
+        #[cfg(feature = "catalog")]
+        TypedResource::ObjectDefinition(r) => r.to_bytes(),
+        _ => Err(Error::InvalidInput("Writing this resource type is not supported".to_string())),
+    }
+}
+
+/// Applies `edits` to one resource and rewrites the package in place with
+/// [`Package::replace_resources`]
+pub fn set_fields<P: AsRef<Path>>(path: P, tgi: &TGI, edits: &[(String, String)]) -> Result<()> {
+    let path = path.as_ref();
+    let pkg = Package::open(path)?;
+    let entry = pkg.find(tgi).cloned()
+        .ok_or(Error::ResourceNotFound(*tgi))
+        .with_context(|| format!("Failed to edit {:?}", path))?;
+
+    let data = pkg.read_raw_resource(&entry)?;
+    let mut resource = TypedResource::from_bytes(tgi.res_type, &data)?;
+    for (field, value) in edits {
+        set_field(&mut resource, field, value).with_context(|| format!("Failed to set {}", field))?;
+    }
+    drop(pkg);
+
+    Package::replace_resources(path, &HashMap::from([(*tgi, to_bytes(&resource)?)]))
+}
@@ -0,0 +1,100 @@
+use std::fmt::Display;
+use crate::package::index::TGI;
+
+/// Everything the library can fail with. Errors that had context added along the way (which
+/// file, which resource) are [`Error::Context`]; use [`Error::root_cause`] to get at the
+/// failure itself before matching on it.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file or stream failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The data isn't a DBPF package this library can read, or its index doesn't fit the file
+    #[error("Invalid package header: {0}")]
+    InvalidHeader(String),
+    /// A compressed resource couldn't be decompressed
+    #[error("Failed to decompress resource data: {0}")]
+    Decompression(String),
+    /// A resource that was asked for isn't in the package
+    #[error("Resource {0} not found")]
+    ResourceNotFound(TGI),
+    /// A resource's data doesn't match the format of its type
+    #[error("{0}")]
+    InvalidResource(String),
+    /// Reading or writing a binary structure failed
+    #[error(transparent)]
+    Binary(#[from] binrw::Error),
+    /// The operation isn't supported for this data, e.g. writing a resource type that can only
+    /// be read
+    #[error("{0}")]
+    Unsupported(String),
+    /// An argument, option or input file is not valid, e.g. a malformed TGI or an unknown recipe key
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(transparent)]
+    Xml(#[from] quick_xml::Error),
+    #[cfg(feature = "textures")]
+    #[error(transparent)]
+    Png(#[from] png::EncodingError),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "7z")]
+    #[error(transparent)]
+    SevenZ(#[from] sevenz_rust::Error),
+    #[cfg(feature = "http")]
+    #[error(transparent)]
+    Http(#[from] ureq::Error),
+    /// Any other failure, described by its message
+    #[error("{0}")]
+    Other(String),
+    /// `inner`, with a note on what was being done when it happened
+    #[error("{context}: {inner}")]
+    Context { context: String, inner: Box<Error> },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// The error with any [`Error::Context`] around it taken off
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::Context { inner, .. } => inner.root_cause(),
+            error => error,
+        }
+    }
+
+    fn with_note(self, context: String) -> Self {
+        Error::Context { context, inner: Box::new(self) }
+    }
+}
+
+/// Adds a note on what was being done to an error, or turns a missing value into an error
+pub(crate) trait Context<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|e| e.into().with_note(context.to_string()))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T> {
+        self.map_err(|e| e.into().with_note(context().to_string()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| Error::Other(context.to_string()))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T> {
+        self.ok_or_else(|| Error::Other(context().to_string()))
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::error::{Error, Result};
+use log::warn;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::package::Package;
+use crate::package::index::{CompressionScheme, TGI};
+
+/// Returns true for the Maxis packages that make up an installed game (full builds, deltas and string tables)
+pub fn is_game_package(path: &Path) -> bool {
+    if path.extension().is_none_or(|ext| ext != "package") {
+        return false;
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.contains("FullBuild") || name.contains("DeltaBuild") || name.starts_with("Strings_")
+}
+
+/// Returns true for a patch package: a `DeltaBuild`, or anything the game keeps under a `Delta`
+/// folder (such as a pack's patched string tables). What a delta has overrides the full builds.
+pub fn is_delta_package(path: &Path) -> bool {
+    path.file_name().unwrap_or_default().to_string_lossy().contains("DeltaBuild")
+        || path.components().any(|c| c.as_os_str().eq_ignore_ascii_case("Delta"))
+}
+
+/// Where a game package comes in the game's load order: every full build before every delta,
+/// then by folder, then by build number (so `FullBuild10` comes after `FullBuild2`)
+fn load_order(path: &Path) -> (bool, PathBuf, u32, String) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let build = name.find("Build")
+        .map(|i| name[i + 5..].chars().take_while(char::is_ascii_digit).collect::<String>())
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(0);
+    (is_delta_package(path), path.parent().map(Path::to_path_buf).unwrap_or_default(), build, name)
+}
+
+/// Lists the game's packages under an install folder in the order the game loads them, so a
+/// later package overrides an earlier one: full builds first, then the deltas that patch them
+pub fn find_game_packages<P: AsRef<Path>>(install_dir: P) -> Vec<PathBuf> {
+    let mut packages: Vec<PathBuf> = WalkDir::new(install_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && is_game_package(e.path()))
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    packages.sort_by_cached_key(|path| load_order(path));
+    packages
+}
+
+/// Read-only index of every resource key shipped with the game
+#[derive(Debug, Default)]
+pub struct GameIndex {
+    /// In load order, see [`find_game_packages`]
+    pub packages: Vec<PathBuf>,
+    /// Maps each TGI to the index (into `packages`) of the game package whose copy the game
+    /// uses: the last one in load order. Resources a delta deletes aren't in the map.
+    pub resources: HashMap<TGI, usize>,
+}
+
+impl GameIndex {
+    /// Reads the index of every game package under `install_dir`, in load order, so each
+    /// resource maps to the delta that last patched it. Packages that fail to open are skipped.
+    pub fn build<P: AsRef<Path>>(install_dir: P) -> Result<Self> {
+        let packages = find_game_packages(install_dir.as_ref());
+        if packages.is_empty() {
+            return Err(Error::Other(format!("No game packages found in {:?}", install_dir.as_ref())));
+        }
+
+        // Each key with whether the package deletes it rather than providing it
+        let indexes: Vec<Option<Vec<(TGI, bool)>>> = packages
+            .par_iter()
+            .map(|path| match Package::open(path) {
+                Ok(pkg) => Some(pkg.entries.iter().map(|e| (e.tgi, e.scheme() == CompressionScheme::Deleted)).collect()),
+                Err(e) => {
+                    warn!("Skipping game package {:?}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut resources = HashMap::new();
+        for (i, tgis) in indexes.into_iter().enumerate() {
+            for (tgi, deleted) in tgis.unwrap_or_default() {
+                if deleted {
+                    resources.remove(&tgi);
+                } else {
+                    resources.insert(tgi, i);
+                }
+            }
+        }
+
+        Ok(Self { packages, resources })
+    }
+
+    pub fn contains(&self, tgi: &TGI) -> bool {
+        self.resources.contains_key(tgi)
+    }
+
+    /// The game package that provides `tgi`, if any
+    pub fn source_of(&self, tgi: &TGI) -> Option<&Path> {
+        self.resources.get(tgi).map(|&i| self.packages[i].as_path())
+    }
+
+    /// Every string key in the game's English string tables; every shipped key has English
+    /// text, so this is enough to tell whether a key resolves. Reads the string tables
+    /// themselves, not just the index, so it takes a while on a full install.
+    pub fn string_keys(&self) -> HashSet<u32> {
+        let mut tables: HashMap<usize, Vec<TGI>> = HashMap::new();
+        for (tgi, &package) in &self.resources {
+            if crate::stbl::is_stbl_type(tgi.res_type) && crate::stbl::locale_of(tgi.instance) == Some(crate::stbl::Locale::English) {
+                tables.entry(package).or_default().push(*tgi);
+            }
+        }
+        tables.into_par_iter()
+            .flat_map_iter(|(package, tgis)| {
+                let pkg = match Package::open(&self.packages[package]) {
+                    Ok(pkg) => pkg,
+                    Err(e) => {
+                        warn!("Skipping game package {:?}: {}", self.packages[package], e);
+                        return Vec::new();
+                    }
+                };
+                tgis.iter()
+                    .filter_map(|tgi| pkg.find(tgi))
+                    .filter_map(|entry| match pkg.read_resource(entry) {
+                        Ok(crate::package::resource::TypedResource::Stbl(stbl)) => Some(stbl.entries),
+                        _ => None,
+                    })
+                    .flat_map(|entries| entries.into_iter().map(|e| e.key_hash))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Resources of a single mod package split by whether they replace game content
+#[derive(Debug, Default)]
+pub struct OverrideReport {
+    pub overrides: Vec<TGI>,
+    pub additions: Vec<TGI>,
+}
+
+impl OverrideReport {
+    pub fn classify<'a, I: IntoIterator<Item = &'a TGI>>(index: &GameIndex, tgis: I) -> Self {
+        let mut report = Self::default();
+        for tgi in tgis {
+            if index.contains(tgi) {
+                report.overrides.push(*tgi);
+            } else {
+                report.additions.push(*tgi);
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+use serde_json::{json, Value};
+use crate::package::resource::{GeomResource, RigBone};
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+type Mat4 = [f32; 16];
+
+/// Column-major transform from translation, rotation quaternion (x, y, z, w) and scale
+fn compose(t: [f32; 3], q: [f32; 4], s: [f32; 3]) -> Mat4 {
+    let [x, y, z, w] = q;
+    let (xx, yy, zz) = (x * x, y * y, z * z);
+    let (xy, xz, yz) = (x * y, x * z, y * z);
+    let (wx, wy, wz) = (w * x, w * y, w * z);
+    [
+        (1.0 - 2.0 * (yy + zz)) * s[0], 2.0 * (xy + wz) * s[0], 2.0 * (xz - wy) * s[0], 0.0,
+        2.0 * (xy - wz) * s[1], (1.0 - 2.0 * (xx + zz)) * s[1], 2.0 * (yz + wx) * s[1], 0.0,
+        2.0 * (xz + wy) * s[2], 2.0 * (yz - wx) * s[2], (1.0 - 2.0 * (xx + yy)) * s[2], 0.0,
+        t[0], t[1], t[2], 1.0,
+    ]
+}
+
+fn multiply(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut out = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    out
+}
+
+/// Inverse of an affine transform (bottom row 0, 0, 0, 1)
+fn invert_affine(m: &Mat4) -> Mat4 {
+    let (a, b, c) = (m[0], m[4], m[8]);
+    let (d, e, f) = (m[1], m[5], m[9]);
+    let (g, h, i) = (m[2], m[6], m[10]);
+    let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+    if det.abs() < f32::EPSILON {
+        return identity();
+    }
+    let inv = 1.0 / det;
+    let r = [
+        (e * i - f * h) * inv, (c * h - b * i) * inv, (b * f - c * e) * inv,
+        (f * g - d * i) * inv, (a * i - c * g) * inv, (c * d - a * f) * inv,
+        (d * h - e * g) * inv, (b * g - a * h) * inv, (a * e - b * d) * inv,
+    ];
+    let t = [m[12], m[13], m[14]];
+    let it = [
+        -(r[0] * t[0] + r[1] * t[1] + r[2] * t[2]),
+        -(r[3] * t[0] + r[4] * t[1] + r[5] * t[2]),
+        -(r[6] * t[0] + r[7] * t[1] + r[8] * t[2]),
+    ];
+    [
+        r[0], r[3], r[6], 0.0,
+        r[1], r[4], r[7], 0.0,
+        r[2], r[5], r[8], 0.0,
+        it[0], it[1], it[2], 1.0,
+    ]
+}
+
+fn identity() -> Mat4 {
+    compose([0.0; 3], [0.0, 0.0, 0.0, 1.0], [1.0; 3])
+}
+
+/// World (bind pose) transform of every bone, following parent links
+pub fn bind_pose(bones: &[RigBone]) -> Vec<Mat4> {
+    let mut world: Vec<Option<Mat4>> = vec![None; bones.len()];
+    fn resolve(i: usize, bones: &[RigBone], world: &mut Vec<Option<Mat4>>, depth: usize) -> Mat4 {
+        if let Some(m) = world[i] {
+            return m;
+        }
+        let bone = &bones[i];
+        let local = compose(bone.position, bone.orientation, bone.scale);
+        let parent = bone.parent_index;
+        let m = if parent >= 0 && (parent as usize) < bones.len() && parent as usize != i && depth < bones.len() {
+            multiply(&resolve(parent as usize, bones, world, depth + 1), &local)
+        } else {
+            local
+        };
+        world[i] = Some(m);
+        m
+    }
+    (0..bones.len()).map(|i| resolve(i, bones, &mut world, 0)).collect()
+}
+
+/// Adds a node per bone, parented as in the rig, and the roots to `scene_nodes`. Returns the
+/// index of the first bone's node.
+fn push_joints(nodes: &mut Vec<Value>, scene_nodes: &mut Vec<usize>, bones: &[RigBone]) -> usize {
+    let joint_base = nodes.len();
+    for bone in bones {
+        nodes.push(json!({
+            "name": bone.name,
+            "translation": bone.position,
+            "rotation": bone.orientation,
+            "scale": bone.scale,
+        }));
+    }
+    for (i, bone) in bones.iter().enumerate() {
+        let parent = bone.parent_index;
+        if parent >= 0 && (parent as usize) < bones.len() && parent as usize != i {
+            let children = nodes[joint_base + parent as usize]
+                .as_object_mut()
+                .unwrap()
+                .entry("children")
+                .or_insert_with(|| json!([]));
+            children.as_array_mut().unwrap().push(json!(joint_base + i));
+        } else {
+            scene_nodes.push(joint_base + i);
+        }
+    }
+    joint_base
+}
+
+/// Packs a glTF document and its binary buffer (which may be empty) into a .glb
+fn pack_glb(document: &Value, bin: &[u8]) -> Result<Vec<u8>> {
+    let mut json_bytes = serde_json::to_vec(document)?;
+    while !json_bytes.len().is_multiple_of(4) {
+        json_bytes.push(b' ');
+    }
+
+    let bin_chunk = if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let total = 12 + 8 + json_bytes.len() + bin_chunk;
+    let mut glb = Vec::with_capacity(total);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total as u32).to_le_bytes());
+    glb.extend_from_slice(&(json_bytes.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json_bytes);
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(bin);
+    }
+    Ok(glb)
+}
+
+struct BinaryBuilder {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BinaryBuilder {
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        let mut view = json!({ "buffer": 0, "byteOffset": self.data.len(), "byteLength": bytes.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.data.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn push_accessor(&mut self, bytes: &[u8], target: Option<u32>, component: u32, kind: &str, count: usize) -> usize {
+        let view = self.push_view(bytes, target);
+        self.accessors.push(json!({ "bufferView": view, "componentType": component, "count": count, "type": kind }));
+        self.accessors.len() - 1
+    }
+
+    fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+        self.push_accessor(&bytes, Some(ARRAY_BUFFER), FLOAT, kind, values.len())
+    }
+}
+
+/// Converts a GEOM to a binary glTF (.glb).
+///
+/// With a rig, the skeleton is added as a node hierarchy and the mesh is skinned to it with inverse
+/// bind matrices, so it opens in Blender with its armature. GEOM bone hashes the rig doesn't have
+/// are bound to the first joint.
+pub fn geom_to_glb(geom: &GeomResource, rig: Option<&[RigBone]>, name: &str) -> Result<Vec<u8>> {
+    let positions = geom.positions();
+    if positions.is_empty() {
+        return Err(Error::InvalidResource("GEOM has no vertex positions".to_string()));
+    }
+
+    let mut bin = BinaryBuilder { data: Vec::new(), views: Vec::new(), accessors: Vec::new() };
+    let mut attributes = serde_json::Map::new();
+
+    let position = bin.push_floats(positions, "VEC3");
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for p in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    bin.accessors[position]["min"] = json!(min);
+    bin.accessors[position]["max"] = json!(max);
+    attributes.insert("POSITION".into(), json!(position));
+
+    if geom.normals().len() == positions.len() {
+        attributes.insert("NORMAL".into(), json!(bin.push_floats(geom.normals(), "VEC3")));
+    }
+    for channel in 0..geom.uv_channel_count() {
+        if geom.uvs(channel).len() == positions.len() {
+            attributes.insert(format!("TEXCOORD_{}", channel), json!(bin.push_floats(geom.uvs(channel), "VEC2")));
+        }
+    }
+
+    let indices: Vec<u8> = geom.faces.faces.iter().flatten().flat_map(|i| i.to_le_bytes()).collect();
+    let index_accessor = bin.push_accessor(&indices, Some(ELEMENT_ARRAY_BUFFER), UNSIGNED_SHORT, "SCALAR", geom.faces.faces.len() * 3);
+
+    let mut nodes = vec![json!({ "name": name, "mesh": 0 })];
+    let mut scene_nodes = vec![0];
+    let mut skins = Vec::new();
+
+    let assignments = geom.bone_assignments();
+    if let Some(bones) = rig.filter(|b| !b.is_empty() && assignments.len() == positions.len()) {
+        let joint_base = push_joints(&mut nodes, &mut scene_nodes, bones);
+        let by_hash: HashMap<u32, usize> = bones.iter().enumerate().map(|(i, b)| (b.hash, i)).collect();
+
+        let inverse_binds: Vec<[f32; 16]> = bind_pose(bones).iter().map(invert_affine).collect();
+        let inverse_accessor = bin.push_floats(&inverse_binds, "MAT4");
+
+        let mut joints: Vec<[u16; 4]> = Vec::with_capacity(assignments.len());
+        let mut weights: Vec<[f32; 4]> = Vec::with_capacity(assignments.len());
+        for assignment in assignments {
+            let mut joint = [0u16; 4];
+            let mut weight = assignment.weights;
+            for k in 0..4 {
+                let rig_index = geom.bone_hashes.hashes.get(assignment.bones[k] as usize)
+                    .and_then(|hash| by_hash.get(hash));
+                match rig_index {
+                    Some(&i) => joint[k] = i as u16,
+                    None if weight[k] > 0.0 => joint[k] = 0,
+                    None => weight[k] = 0.0,
+                }
+            }
+            // glTF requires weights that sum to 1
+            let total: f32 = weight.iter().sum();
+            if total > 0.0 {
+                weight.iter_mut().for_each(|w| *w /= total);
+            } else {
+                weight = [1.0, 0.0, 0.0, 0.0];
+            }
+            joints.push(joint);
+            weights.push(weight);
+        }
+        let joint_bytes: Vec<u8> = joints.iter().flatten().flat_map(|j| j.to_le_bytes()).collect();
+        attributes.insert("JOINTS_0".into(), json!(bin.push_accessor(&joint_bytes, Some(ARRAY_BUFFER), UNSIGNED_SHORT, "VEC4", joints.len())));
+        attributes.insert("WEIGHTS_0".into(), json!(bin.push_floats(&weights, "VEC4")));
+
+        nodes[0]["skin"] = json!(0);
+        skins.push(json!({
+            "inverseBindMatrices": inverse_accessor,
+            "joints": (joint_base..joint_base + bones.len()).collect::<Vec<_>>(),
+            "skeleton": scene_nodes.get(1).copied().unwrap_or(joint_base),
+        }));
+    }
+
+    while !bin.data.len().is_multiple_of(4) {
+        bin.data.push(0);
+    }
+
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "s4pi-reforged" },
+        "scene": 0,
+        "scenes": [{ "nodes": scene_nodes }],
+        "nodes": nodes,
+        "meshes": [{ "name": name, "primitives": [{ "attributes": attributes, "indices": index_accessor, "mode": 4 }] }],
+        "buffers": [{ "byteLength": bin.data.len() }],
+        "bufferViews": bin.views,
+        "accessors": bin.accessors,
+    });
+    if !skins.is_empty() {
+        document["skins"] = json!(skins);
+    }
+
+    pack_glb(&document, &bin.data)
+}
+
+/// Converts a rig's bones to a binary glTF (.glb) holding only the skeleton, as a node
+/// hierarchy, for opening a rig on its own or posing meshes exported without one.
+pub fn skeleton_to_glb(bones: &[RigBone], name: &str) -> Result<Vec<u8>> {
+    if bones.is_empty() {
+        return Err(Error::InvalidResource("RIG has no bones".to_string()));
+    }
+    let mut nodes = Vec::with_capacity(bones.len());
+    let mut scene_nodes = Vec::new();
+    push_joints(&mut nodes, &mut scene_nodes, bones);
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "s4pi-reforged" },
+        "scene": 0,
+        "scenes": [{ "name": name, "nodes": scene_nodes }],
+        "nodes": nodes,
+    });
+    pack_glb(&document, &[])
+}
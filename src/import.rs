@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use crate::error::{Context, Error, Result};
+use walkdir::WalkDir;
+use crate::instance::InstanceAllocator;
+use crate::observer::{OperationObserver, Progress};
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TgiFilter, TGI};
+use crate::package::resource::TypedResource;
+use rayon::prelude::*;
+
+/// Filename layouts understood by the `import` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportLayout {
+    /// s4pe / s3pe: `S4_TTTTTTTT_GGGGGGGG_IIIIIIIIIIIIIIII[_Name]%%+TAG.ext`
+    S4pe,
+    /// Sims 4 Studio: `TTTTTTTT!GGGGGGGG!IIIIIIIIIIIIIIII[.Name.TypeName].ext`
+    S4s,
+    /// Bare TGI: `TTTTTTTT-GGGGGGGG-IIIIIIIIIIIIIIII.ext` (dashes or underscores, optional 0x)
+    Plain,
+}
+
+/// A resource key (and optional resource name) recovered from an exported filename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedName {
+    pub tgi: TGI,
+    pub name: Option<String>,
+    pub layout: ExportLayout,
+}
+
+/// A file on disk that will be imported as a resource
+#[derive(Debug, Clone)]
+pub struct ImportFile {
+    pub path: PathBuf,
+    pub key: ImportedName,
+}
+
+/// Parses the filename of a resource exported by s4pe, Sims 4 Studio or this tool.
+///
+/// Returns `None` for files that don't carry a TGI in their name (readme files, previews, etc.).
+pub fn parse_export_filename(file_name: &str) -> Option<ImportedName> {
+    parse_s4pe(file_name)
+        .or_else(|| parse_s4s(file_name))
+        .or_else(|| parse_plain(file_name))
+}
+
+fn parse_s4pe(file_name: &str) -> Option<ImportedName> {
+    let rest = file_name.strip_prefix("S4_")
+        .or_else(|| file_name.strip_prefix("S3_"))?;
+
+    // Everything from "%%+" on is the type tag and extension
+    let stem = match rest.find("%%+") {
+        Some(pos) => &rest[..pos],
+        None => rest.rsplit_once('.').map(|(s, _)| s).unwrap_or(rest),
+    };
+
+    let mut parts = stem.splitn(4, '_');
+    let res_type = parse_hex_u32(parts.next()?)?;
+    let res_group = parse_hex_u32(parts.next()?)?;
+    let instance = parse_hex_u64(parts.next()?)?;
+    let name = parts.next().filter(|n| !n.is_empty()).map(|n| n.to_string());
+
+    Some(ImportedName {
+        tgi: TGI { res_type, res_group, instance },
+        name,
+        layout: ExportLayout::S4pe,
+    })
+}
+
+fn parse_s4s(file_name: &str) -> Option<ImportedName> {
+    let mut parts = file_name.splitn(3, '!');
+    let res_type = parse_hex_u32(parts.next()?)?;
+    let res_group = parse_hex_u32(parts.next()?)?;
+    let tail = parts.next()?;
+
+    let (instance_str, suffix) = tail.split_once('.').unwrap_or((tail, ""));
+    let instance = parse_hex_u64(instance_str)?;
+
+    // Suffix is "[Name.]TypeName.ext" or just "ext"; the name is everything before the last two parts
+    let segments: Vec<&str> = suffix.split('.').collect();
+    let name = if segments.len() > 2 {
+        Some(segments[..segments.len() - 2].join("."))
+    } else {
+        None
+    };
+
+    Some(ImportedName {
+        tgi: TGI { res_type, res_group, instance },
+        name: name.filter(|n| !n.is_empty()),
+        layout: ExportLayout::S4s,
+    })
+}
+
+fn parse_plain(file_name: &str) -> Option<ImportedName> {
+    let stem = file_name.split('.').next()?;
+    let separator = if stem.contains('-') { '-' } else { '_' };
+    let parts: Vec<&str> = stem.split(separator).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    Some(ImportedName {
+        tgi: TGI {
+            res_type: parse_hex_u32(parts[0])?,
+            res_group: parse_hex_u32(parts[1])?,
+            instance: parse_hex_u64(parts[2])?,
+        },
+        name: None,
+        layout: ExportLayout::Plain,
+    })
+}
+
+fn strip_hex_prefix(s: &str) -> &str {
+    s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s)
+}
+
+fn parse_hex_u32(s: &str) -> Option<u32> {
+    let s = strip_hex_prefix(s);
+    if s.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(s, 16).ok()
+}
+
+fn parse_hex_u64(s: &str) -> Option<u64> {
+    let s = strip_hex_prefix(s);
+    if s.len() != 16 {
+        return None;
+    }
+    u64::from_str_radix(s, 16).ok()
+}
+
+/// Walks an export folder and returns every file whose name carries a TGI, sorted by path.
+///
+/// Subfolders are included so per-type folder layouts (as produced by S4S batch export) work as-is.
+pub fn collect_import_files<P: AsRef<Path>>(folder: P) -> Result<(Vec<ImportFile>, Vec<PathBuf>)> {
+    let folder = folder.as_ref();
+    if !folder.is_dir() {
+        return Err(Error::InvalidInput(format!("Not a directory: {:?}", folder)));
+    }
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for entry in WalkDir::new(folder).sort_by_file_name() {
+        let entry = entry.map_err(std::io::Error::from).with_context(|| format!("Failed to walk {:?}", folder))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy();
+        match parse_export_filename(&file_name) {
+            Some(key) => files.push(ImportFile { path: entry.path().to_path_buf(), key }),
+            None => skipped.push(entry.path().to_path_buf()),
+        }
+    }
+
+    Ok((files, skipped))
+}
+
+/// What [`extract_resources`] did
+#[derive(Debug, Default)]
+pub struct ExtractSummary {
+    pub extracted: usize,
+    /// Resources that couldn't be read or written, and why
+    pub failed: Vec<(TGI, Error)>,
+}
+
+/// Writes every resource of `pkg` that `filter` matches to `output_dir`, decompressed and named
+/// in the Sims 4 Studio layout so the folder can be imported again as-is. Resources that fail are
+/// skipped, reported to `observer` and listed in the summary.
+pub fn extract_resources<P: AsRef<Path>>(pkg: &Package, filter: &TgiFilter, output_dir: P, observer: &dyn OperationObserver) -> Result<ExtractSummary> {
+    let output_dir = output_dir.as_ref();
+    let entries: Vec<&IndexEntry> = pkg.entries.iter().filter(|e| filter.matches(&e.tgi)).collect();
+    let progress = Progress::start(observer, "extract", Some(entries.len()));
+    if let Err(e) = std::fs::create_dir_all(output_dir) {
+        progress.finish(false);
+        return Err(e).with_context(|| format!("Failed to create {:?}", output_dir));
+    }
+
+    let failed: Vec<(TGI, Error)> = entries.par_iter()
+        .filter_map(|entry| crate::perf::busy(|| -> Result<()> {
+            let data = pkg.read_raw_resource(entry)?;
+            let filename = format!("{:08X}!{:08X}!{:016X}.{}", entry.tgi.res_type, entry.tgi.res_group, entry.tgi.instance,
+                TypedResource::file_extension(entry.tgi.res_type, &data));
+            crate::perf::write_file(output_dir.join(filename), data)?;
+            Ok(())
+        }).map_or_else(
+            |e| {
+                progress.skip(&entry.tgi.to_string(), &format!("{:#}", e));
+                Some((entry.tgi, e))
+            },
+            |_| {
+                progress.advance(&entry.tgi.to_string());
+                None
+            },
+        ))
+        .collect();
+    progress.finish(true);
+    Ok(ExtractSummary { extracted: entries.len() - failed.len(), failed })
+}
+
+/// Name map resource type, which lists resource names by instance
+pub const NAME_MAP_TYPE: u32 = 0x0166038C;
+
+/// Gives every imported resource a new instance from `allocator`, returning the old instance
+/// of each mapped to its new one. Resources that shared an instance (such as an object and its
+/// catalog entry) still share one. Where one of them has a name the new instance is its
+/// [`crate::instance::name_instance`], unless that is taken; otherwise it is random. Name maps
+/// are left alone.
+pub fn assign_new_instances(files: &mut [ImportFile], allocator: &mut InstanceAllocator) -> HashMap<u64, u64> {
+    let mut names: BTreeMap<u64, Option<&str>> = BTreeMap::new();
+    for file in files.iter().filter(|f| f.key.tgi.res_type != NAME_MAP_TYPE) {
+        let name = names.entry(file.key.tgi.instance).or_default();
+        if name.is_none() {
+            *name = file.key.name.as_deref();
+        }
+    }
+    let mapping: HashMap<u64, u64> = names.into_iter()
+        .map(|(old, name)| (old, name.and_then(|n| allocator.named(n)).unwrap_or_else(|| allocator.random())))
+        .collect();
+    for file in files.iter_mut().filter(|f| f.key.tgi.res_type != NAME_MAP_TYPE) {
+        file.key.tgi.instance = mapping[&file.key.tgi.instance];
+    }
+    mapping
+}
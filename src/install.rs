@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::{Context, Error, Result};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+use crate::archive;
+use crate::package::Package;
+
+/// Name of the journal `install` keeps in the Mods folder so installs can be undone
+pub const JOURNAL_NAME: &str = "s4pi-reforged-installs.txt";
+
+/// The kinds of file an install copies into the Mods folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallKind {
+    Package,
+    /// Python script archive; the game only loads these at most one folder below Mods
+    Script,
+}
+
+impl InstallKind {
+    fn of(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".package") {
+            Some(Self::Package)
+        } else if name.ends_with(".ts4script") {
+            Some(Self::Script)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file an install will copy into the Mods folder
+#[derive(Debug, Clone)]
+pub struct InstallFile {
+    /// Where it is in the download (inside the archive, or relative to the folder)
+    pub source: String,
+    /// Where it goes, relative to the Mods folder
+    pub destination: PathBuf,
+    pub kind: InstallKind,
+    /// Set when the file had to be renamed because another file already had its name
+    pub renamed: bool,
+    pub data: Vec<u8>,
+}
+
+/// What installing a download would do, worked out without touching the Mods folder
+#[derive(Debug, Clone, Default)]
+pub struct InstallPlan {
+    pub files: Vec<InstallFile>,
+    /// Files already in the Mods folder with the same contents, by their path there
+    pub already_installed: Vec<PathBuf>,
+    /// Packages and scripts that failed validation, and why
+    pub rejected: Vec<(String, String)>,
+    /// Everything else in the download (readmes, previews, ...), which is left out
+    pub ignored: Vec<String>,
+}
+
+/// The Sims 4 Mods folder in the usual place under the user's Documents, if it exists
+pub fn find_mods_folder() -> Option<PathBuf> {
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })?;
+    let home = PathBuf::from(home);
+    [home.join("Documents"), home.join("OneDrive").join("Documents")]
+        .into_iter()
+        .map(|docs| docs.join("Electronic Arts").join("The Sims 4").join("Mods"))
+        .find(|mods| mods.is_dir())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Checks that a file is what its extension says, returning why not
+fn validate(kind: InstallKind, data: &[u8]) -> std::result::Result<(), String> {
+    match kind {
+        InstallKind::Package => Package::from_reader(Cursor::new(data.to_vec()))
+            .map(|_| ())
+            .map_err(|e| format!("not a valid package: {}", e)),
+        InstallKind::Script => {
+            let zip = zip::ZipArchive::new(Cursor::new(data))
+                .map_err(|e| format!("not a valid script archive: {}", e))?;
+            if zip.file_names().any(|n| n.ends_with(".py") || n.ends_with(".pyc")) {
+                Ok(())
+            } else {
+                Err("script archive contains no Python files".to_string())
+            }
+        }
+    }
+}
+
+/// The files in a download: every file inside an archive, or under a folder
+fn read_download(source: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    if source.is_dir() {
+        let mut paths: Vec<PathBuf> = WalkDir::new(source)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.path().to_path_buf())
+            .collect();
+        paths.sort();
+        paths.into_iter()
+            .map(|path| {
+                let name = path.strip_prefix(source).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+                let data = std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+                Ok((name, data))
+            })
+            .collect()
+    } else if archive::is_archive(source) {
+        archive::read_files(source, |_| true)
+    } else {
+        Err(Error::InvalidInput(format!("{:?} is not a folder or a supported archive", source)))
+    }
+}
+
+/// `name (2).ext`, `name (3).ext`, ... for the `n`th file wanting the same name
+fn numbered(name: &str, n: usize) -> String {
+    match name.rfind('.') {
+        Some(dot) => format!("{} ({}){}", &name[..dot], n, &name[dot..]),
+        None => format!("{} ({})", name, n),
+    }
+}
+
+/// Works out how to install a download (an archive or folder) into `mods`.
+///
+/// Packages and scripts are placed in `mods/<into>`, which defaults to the download's name.
+/// Scripts go in the first folder of `into`, since the game ignores scripts nested deeper.
+/// A file whose name is taken is numbered, unless the file there has the same contents.
+pub fn plan_install(source: &Path, mods: &Path, into: Option<&Path>) -> Result<InstallPlan> {
+    let into = match into {
+        Some(into) => into.to_path_buf(),
+        None => PathBuf::from(source.file_stem().ok_or_else(|| Error::Other(format!("{:?} has no name", source)))?),
+    };
+    if into.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(Error::InvalidInput(format!("{:?} must be a folder inside the Mods folder", into)));
+    }
+    let script_folder: PathBuf = into.components().take(1).collect();
+
+    let mut plan = InstallPlan::default();
+    let mut taken: HashSet<PathBuf> = HashSet::new();
+    for (name, data) in read_download(source)? {
+        // Metadata folders macOS adds to zips
+        if name.split('/').any(|part| part == "__MACOSX") {
+            continue;
+        }
+        if !archive::is_safe_entry_name(&name) {
+            plan.rejected.push((name, "its path leads outside the download's folder".to_string()));
+            continue;
+        }
+        let Some(kind) = InstallKind::of(&name) else {
+            plan.ignored.push(name);
+            continue;
+        };
+        if let Err(reason) = validate(kind, &data) {
+            plan.rejected.push((name, reason));
+            continue;
+        }
+
+        let file_name = name.rsplit('/').next().unwrap_or(&name).to_string();
+        let folder = if kind == InstallKind::Script { &script_folder } else { &into };
+        let mut n = 1;
+        // Ok with a free name, or Err with the name of an identical file already there
+        let destination = loop {
+            let candidate = folder.join(if n == 1 { file_name.clone() } else { numbered(&file_name, n) });
+            n += 1;
+            if taken.contains(&candidate) {
+                continue;
+            }
+            match std::fs::read(mods.join(&candidate)) {
+                Ok(existing) if existing == data => break Err(candidate),
+                Ok(_) => continue,
+                Err(_) if mods.join(&candidate).exists() => continue,
+                Err(_) => break Ok(candidate),
+            }
+        };
+        match destination {
+            Ok(destination) => {
+                taken.insert(destination.clone());
+                plan.files.push(InstallFile { source: name, destination, kind, renamed: n > 2, data });
+            }
+            Err(existing) => plan.already_installed.push(existing),
+        }
+    }
+    Ok(plan)
+}
+
+/// Copies the planned files into `mods` and records them in the install journal, so
+/// [`undo_last_install`] can take them out again. Each file is added to the journal as soon as
+/// it is copied, so if a later one fails, undoing removes the ones already in place.
+pub fn apply_install(plan: &InstallPlan, mods: &Path, source: &Path) -> Result<()> {
+    if plan.files.is_empty() {
+        return Ok(());
+    }
+    let journal = mods.join(JOURNAL_NAME);
+    let mut log = OpenOptions::new().create(true).append(true).open(&journal)
+        .with_context(|| format!("Failed to open {:?}", journal))?;
+    let mut record = |line: String| -> Result<()> {
+        log.write_all(line.as_bytes())
+            .and_then(|_| log.sync_data())
+            .with_context(|| format!("Failed to write {:?}", journal))
+    };
+    record(format!(
+        "install\t{}\t{}\n",
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        source.display(),
+    ))?;
+    for file in &plan.files {
+        let path = mods.join(&file.destination);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        crate::atomic::write(&path, &file.data).with_context(|| format!("Failed to write {:?}", path))?;
+        record(format!("{}\t{}\n", sha256_hex(&file.data), file.destination.to_string_lossy().replace('\\', "/")))?;
+    }
+    Ok(())
+}
+
+/// What undoing an install did
+#[derive(Debug, Clone, Default)]
+pub struct UndoReport {
+    /// The download the undone install came from
+    pub source: String,
+    pub removed: Vec<PathBuf>,
+    /// Files left in place because they were changed or removed after the install
+    pub kept: Vec<PathBuf>,
+}
+
+/// Removes the files of the most recent install recorded in the journal in `mods`, and drops it
+/// from the journal. Folders the install left empty are removed too.
+pub fn undo_last_install(mods: &Path) -> Result<UndoReport> {
+    let journal = mods.join(JOURNAL_NAME);
+    let text = std::fs::read_to_string(&journal)
+        .with_context(|| format!("No installs recorded in {:?}", mods))?;
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.iter().rposition(|l| l.starts_with("install\t"))
+        .ok_or_else(|| Error::Other(format!("No installs recorded in {:?}", mods)))?;
+
+    let mut report = UndoReport {
+        source: lines[start].splitn(3, '\t').nth(2).unwrap_or_default().to_string(),
+        ..Default::default()
+    };
+    for line in &lines[start + 1..] {
+        let (hash, relative) = line.split_once('\t')
+            .ok_or_else(|| Error::Other(format!("Malformed line in {:?}: {}", journal, line)))?;
+        let relative = PathBuf::from(relative);
+        let path = mods.join(&relative);
+        match std::fs::read(&path) {
+            Ok(data) if sha256_hex(&data) == hash => {
+                std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+                // Remove the folders the install created, if nothing else is in them now
+                let mut folder = path.parent();
+                while let Some(dir) = folder.filter(|d| *d != mods && d.starts_with(mods)) {
+                    if std::fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                    folder = dir.parent();
+                }
+                report.removed.push(relative);
+            }
+            _ => report.kept.push(relative),
+        }
+    }
+
+    let remaining: String = lines[..start].iter().map(|l| format!("{}\n", l)).collect();
+    if remaining.is_empty() {
+        std::fs::remove_file(&journal)?;
+    } else {
+        crate::atomic::write(&journal, remaining)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::census::Census;
+use crate::package::Package;
+use crate::package::index::TGI;
+
+/// Set on instances made for custom content, so they can't land on one of the game's own
+pub const CUSTOM_BIT: u64 = 0x8000_0000_0000_0000;
+
+/// FNV-1 64-bit hash of `text` lowercased, as the game hashes tuning and resource names
+pub fn fnv64(text: &str) -> u64 {
+    let mut hash: u64 = 0xCBF29CE484222325;
+    for b in text.to_lowercase().bytes() {
+        hash = hash.wrapping_mul(0x100000001B3);
+        hash ^= b as u64;
+    }
+    hash
+}
+
+/// FNV-1 32-bit hash of `text` lowercased, as the game hashes SimData table and column names
+pub fn fnv32(text: &str) -> u32 {
+    let mut hash: u32 = 0x811C9DC5;
+    for b in text.to_lowercase().bytes() {
+        hash = hash.wrapping_mul(0x01000193);
+        hash ^= b as u32;
+    }
+    hash
+}
+
+/// The instance creator tools give a resource named `name`: its [`fnv64`] hash with the high bit
+/// set. The same name always gives the same instance, so tuning can refer to it by name.
+pub fn name_instance(name: &str) -> u64 {
+    fnv64(name) | CUSTOM_BIT
+}
+
+/// A random instance with the high bit set; use [`InstanceAllocator`] to also avoid ones in use
+pub fn random_instance() -> u64 {
+    // RandomState is seeded from the OS once per thread and then stepped; the counter keeps two
+    // calls on one state apart
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish() | CUSTOM_BIT
+}
+
+/// Hands out instances that no resource in a set of packages uses, and that it hasn't handed
+/// out before.
+///
+/// Instances are compared whatever the type and group, since several resources of one item
+/// share an instance: a CAS part with its thumbnail, an object definition with its catalog
+/// entry. [`InstanceAllocator::paired`] gives such a set of keys at once.
+#[derive(Debug, Clone, Default)]
+pub struct InstanceAllocator {
+    taken: HashSet<u64>,
+}
+
+impl InstanceAllocator {
+    /// An allocator that only avoids what it hands out itself
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An allocator that avoids every instance in `packages`
+    pub fn avoiding<'a>(packages: impl IntoIterator<Item = &'a Package>) -> Self {
+        let mut allocator = Self::new();
+        for pkg in packages {
+            allocator.taken.extend(pkg.entries.iter().map(|e| e.tgi.instance));
+        }
+        allocator
+    }
+
+    /// An allocator that avoids every instance in the packages under `folder` (e.g. the Mods
+    /// folder). Only package indexes are read; packages that fail to open are skipped.
+    pub fn avoiding_folder<P: AsRef<Path>>(folder: P) -> Self {
+        let census = Census::scan(folder);
+        Self { taken: census.resources.keys().map(|tgi| tgi.instance).collect() }
+    }
+
+    /// Marks `instance` as used, returning false if it already was
+    pub fn reserve(&mut self, instance: u64) -> bool {
+        self.taken.insert(instance)
+    }
+
+    pub fn is_taken(&self, instance: u64) -> bool {
+        self.taken.contains(&instance)
+    }
+
+    /// A random instance that isn't in use, with the high bit set
+    pub fn random(&mut self) -> u64 {
+        loop {
+            let instance = random_instance();
+            if self.reserve(instance) {
+                return instance;
+            }
+        }
+    }
+
+    /// [`name_instance`] for `name`, or `None` if that instance is already in use (by another
+    /// resource with the same name, or a hash collision)
+    pub fn named(&mut self, name: &str) -> Option<u64> {
+        let instance = name_instance(name);
+        self.reserve(instance).then_some(instance)
+    }
+
+    /// Keys for resources that belong together and so share one new instance, one key per
+    /// `(type, group)`; e.g. an object definition and its catalog entry
+    pub fn paired(&mut self, kinds: &[(u32, u32)]) -> Vec<TGI> {
+        let instance = self.random();
+        kinds.iter().map(|&(res_type, res_group)| TGI { res_type, res_group, instance }).collect()
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+/// A program that has a file open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// A file that something else has open or locked, found by [`find_in_use`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InUse {
+    pub path: PathBuf,
+    /// The programs that have it open, where the platform can tell; empty when all that is
+    /// known is that it is locked
+    pub holders: Vec<Holder>,
+}
+
+impl std::fmt::Display for InUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.holders.is_empty() {
+            return write!(f, "{} is open in another program", self.path.display());
+        }
+        let holders: Vec<String> = self.holders.iter().map(|h| format!("{} (pid {})", h.name, h.pid)).collect();
+        write!(f, "{} is open in {}", self.path.display(), holders.join(", "))
+    }
+}
+
+/// The files among `paths` that another program has open or locked. Writing to them now could
+/// corrupt them, or what that program reads from them. Files that don't exist are skipped.
+///
+/// On Windows a file counts as in use when it can't be opened without sharing. Elsewhere it
+/// counts when it holds an advisory lock, and on Linux also when any other process has it open.
+pub fn find_in_use<P: AsRef<Path>>(paths: &[P]) -> Vec<InUse> {
+    let open = open_files();
+    paths.iter()
+        .map(AsRef::as_ref)
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let holders = path.canonicalize().ok().and_then(|p| open.get(&p).cloned()).unwrap_or_default();
+            (!holders.is_empty() || is_locked(path)).then(|| InUse { path: path.to_path_buf(), holders })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn is_locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    // Opening with no sharing fails while any other handle to the file is open
+    match std::fs::OpenOptions::new().read(true).write(true).share_mode(0).open(path) {
+        Ok(_) => false,
+        Err(e) => matches!(e.raw_os_error(), Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)),
+    }
+}
+
+#[cfg(not(windows))]
+fn is_locked(path: &Path) -> bool {
+    // Only advisory locks can be seen; a program that doesn't take one isn't caught here
+    std::fs::File::open(path)
+        .is_ok_and(|file| matches!(file.try_lock(), Err(std::fs::TryLockError::WouldBlock)))
+}
+
+/// Every file other processes have open, with the processes, read once from `/proc`. Processes
+/// of other users can't be seen without privileges.
+#[cfg(target_os = "linux")]
+fn open_files() -> std::collections::HashMap<PathBuf, Vec<Holder>> {
+    let mut files: std::collections::HashMap<PathBuf, Vec<Holder>> = std::collections::HashMap::new();
+    let own = std::process::id();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return files;
+    };
+    for proc in procs.filter_map(|e| e.ok()) {
+        let Some(pid) = proc.file_name().to_str().and_then(|p| p.parse::<u32>().ok()) else {
+            continue;
+        };
+        if pid == own {
+            continue;
+        }
+        let Ok(fds) = std::fs::read_dir(proc.path().join("fd")) else {
+            continue;
+        };
+        let name = std::fs::read_to_string(proc.path().join("comm"))
+            .map(|n| n.trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        for target in fds.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok()) {
+            let holders = files.entry(target).or_default();
+            if !holders.iter().any(|h| h.pid == pid) {
+                holders.push(Holder { pid, name: name.clone() });
+            }
+        }
+    }
+    files
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_files() -> std::collections::HashMap<PathBuf, Vec<Holder>> {
+    std::collections::HashMap::new()
+}
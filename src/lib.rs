@@ -1,6 +1,76 @@
+pub mod error;
 pub mod package;
+pub mod import;
+pub mod game;
+pub mod stbl;
+pub mod tuning;
+#[cfg(feature = "textures")]
+pub mod texture;
+#[cfg(feature = "tray")]
+pub mod tray;
+pub mod thumbcache;
+#[cfg(feature = "meshes")]
+pub mod mesh;
+#[cfg(feature = "meshes")]
+pub mod gltf;
+pub mod edit;
+pub mod translate;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod report;
+pub mod provenance;
+pub mod manifest;
+pub mod shell;
+pub mod split;
+pub mod relink;
+pub mod recompress;
+pub mod poses;
+pub mod census;
+pub mod conflict;
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+pub mod preview;
+pub mod archive;
+pub mod install;
+pub mod perf;
+pub mod background;
+#[cfg(feature = "catalog")]
+pub mod packs;
+pub mod typemap;
+pub mod remote;
+pub mod recipe;
+pub mod inuse;
+pub mod instance;
+#[cfg(feature = "textures")]
+pub mod palette;
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+pub mod objcheck;
+pub mod atomic;
+pub mod schema;
+pub mod backup;
+pub mod observer;
+pub mod merge;
+pub mod selftest;
+#[cfg(feature = "tuning")]
+pub mod devwatch;
+#[cfg(feature = "tuning")]
+pub mod simdata;
+#[cfg(feature = "tuning")]
+pub mod retarget;
 
+pub use error::{Error, Result};
 pub use package::Package;
 pub use package::header::PackageHeader;
-pub use package::index::{IndexEntry, TGI};
-pub use package::resource::{Resource, TypedResource, NameMapResource, StblResource, ObjectDefinitionResource, ObjectProperty, SimDataResource, TextResource, CatalogResource, RleResource, DstResource, ScriptResource, ClipResource, CasPartResource, JazzResource, RcolResource, RigResource, LiteResource, ThumbnailResource, ComplateResource, TxtcResource, ObjKeyResource, SimModifierResource, BoneResource, GenericResource};
+pub use package::index::{sort_entries_for_game, CompressionScheme, IndexEntry, TgiFilter, TGI};
+pub use package::diff::PackageDiff;
+pub use package::validate::ValidationReport;
+pub use package::stats::CompressionReport;
+pub use package::write::{CompressionOverride, WriteOptions};
+pub use package::resource::{Resource, TypedResource, NameMapResource, StblResource, TextResource, ScriptResource, ClipResource, JazzResource, LiteResource, ThumbnailResource, ThumbnailKind, ComplateResource, ObjKeyResource, GenericResource};
+#[cfg(feature = "catalog")]
+pub use package::resource::{ObjectDefinitionResource, ObjectProperty, ObjectPropertyId, CatalogResource, CasPartResource};
+#[cfg(feature = "tuning")]
+pub use package::resource::SimDataResource;
+#[cfg(feature = "textures")]
+pub use package::resource::{RleResource, DstResource, TxtcResource};
+#[cfg(feature = "meshes")]
+pub use package::resource::{RcolResource, RigResource, SimModifierResource, BlendGeometryResource, BoneResource};
@@ -0,0 +1,133 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::{Context, Result};
+use log::{Level, Log, Metadata, Record};
+use log::kv::{Key, Value, VisitSource};
+
+/// How many per-run log files to keep before the oldest are deleted
+pub const MAX_RUN_LOGS: usize = 20;
+
+/// Forwards records to the console logger and also appends them, with their key-value fields,
+/// to a JSON lines file for the current run.
+///
+/// Events about a specific package or resource carry `package` and `resource` fields, e.g.
+/// `warn!(package:? = path, resource:% = tgi; "Failed to read resource")`, so the file can be
+/// filtered after a failed merge.
+pub struct RunLogger {
+    console: env_logger::Logger,
+    file: Option<Mutex<File>>,
+}
+
+fn file_enabled(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Debug && metadata.target().starts_with("s4pi")
+}
+
+struct Fields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = match (value.to_u64(), value.to_i64(), value.to_bool()) {
+            (Some(n), _, _) => serde_json::Value::from(n),
+            (_, Some(n), _) => serde_json::Value::from(n),
+            (_, _, Some(b)) => serde_json::Value::from(b),
+            _ => serde_json::Value::from(value.to_string()),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Formats a record as one JSON object holding `time` (Unix milliseconds), `level`, `target`
+/// and `message` plus the record's key-value fields
+pub fn json_line(record: &Record) -> String {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let mut fields = Fields(serde_json::Map::new());
+    fields.0.insert("time".into(), time.into());
+    fields.0.insert("level".into(), record.level().as_str().into());
+    fields.0.insert("target".into(), record.target().into());
+    fields.0.insert("message".into(), record.args().to_string().into());
+    let _ = record.key_values().visit(&mut fields);
+    serde_json::Value::Object(fields.0).to_string()
+}
+
+impl Log for RunLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || (self.file.is_some() && file_enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+        if let Some(file) = &self.file {
+            if file_enabled(record.metadata()) {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", json_line(record));
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(Ok(mut file)) = self.file.as_ref().map(|f| f.lock()) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// The `logs` folder next to the executable, or in the temp folder if that can't be created
+pub fn default_log_dir() -> PathBuf {
+    let beside_exe = std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("logs")))
+        .filter(|dir| fs::create_dir_all(dir).is_ok());
+    beside_exe.unwrap_or_else(|| std::env::temp_dir().join("s4pi-reforged-logs"))
+}
+
+/// Creates a new log file for this run in `dir`, deleting the oldest ones past [`MAX_RUN_LOGS`]
+pub fn create_run_log(dir: &Path) -> Result<(PathBuf, File)> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create log folder {:?}", dir))?;
+
+    let mut existing: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    existing.sort();
+    let excess = (existing.len() + 1).saturating_sub(MAX_RUN_LOGS);
+    for old in existing.iter().take(excess) {
+        let _ = fs::remove_file(old);
+    }
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let path = dir.join(format!("s4pi-reforged-{}-{}.jsonl", started, std::process::id()));
+    let file = OpenOptions::new().create(true).append(true).open(&path)
+        .with_context(|| format!("Failed to create log file {:?}", path))?;
+    Ok((path, file))
+}
+
+/// Installs a [`RunLogger`] built from `console` as the global logger.
+///
+/// Returns the path of this run's log file, or `None` if it couldn't be created, in which case
+/// only the console is logged to.
+pub fn init(mut console: env_logger::Builder, log_dir: &Path) -> Option<PathBuf> {
+    let console = console.build();
+    let (path, file) = match create_run_log(log_dir) {
+        Ok((path, file)) => (Some(path), Some(Mutex::new(file))),
+        Err(e) => {
+            eprintln!("Logging to console only: {:#}", e);
+            (None, None)
+        }
+    };
+
+    let max_level = if file.is_some() { console.filter().max(log::LevelFilter::Debug) } else { console.filter() };
+    if log::set_boxed_logger(Box::new(RunLogger { console, file })).is_ok() {
+        log::set_max_level(max_level);
+    }
+    if let Some(path) = &path {
+        log::debug!("Logging this run to {:?}", path);
+    }
+    path
+}
@@ -0,0 +1,473 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use crate::observer::{OperationObserver, Progress};
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{ManifestResource, Resource, ResourceChecksum, TypedResource};
+use crate::provenance::{Provenance, SourceFile, PROVENANCE_TYPE};
+
+/// Merge manifest resource types (0x7FB6AD8A is what `merge` writes; 0x73E93EEB is also recognised)
+pub const MANIFEST_TYPES: [u32; 2] = [0x7FB6AD8A, 0x73E93EEB];
+
+/// The index entry of a package's merge manifest
+pub fn find_manifest(pkg: &Package) -> Option<IndexEntry> {
+    pkg.entries.iter().find(|e| MANIFEST_TYPES.contains(&e.tgi.res_type)).cloned()
+}
+
+/// Reads and parses a package's merge manifest
+pub fn read_manifest(pkg: &Package) -> Result<(IndexEntry, ManifestResource)> {
+    let entry = find_manifest(pkg).context("No manifest found in package")?;
+    match pkg.read_resource(&entry)? {
+        TypedResource::Manifest(manifest) => Ok((entry, manifest)),
+        _ => Err(Error::InvalidResource("Failed to parse manifest resource".to_string())),
+    }
+}
+
+/// Fills in the checksum of every resource each manifest entry lists and marks the manifest as
+/// the latest version. Resources missing from `checksums` get an empty checksum, which
+/// [`verify`] will report.
+pub fn add_checksums(manifest: &mut ManifestResource, checksums: &HashMap<TGI, ResourceChecksum>) {
+    for entry in &mut manifest.entries {
+        entry.checksums = entry.resources.iter()
+            .map(|tgi| checksums.get(tgi).copied().unwrap_or_default())
+            .collect();
+    }
+    manifest.version = ManifestResource::LATEST_VERSION;
+}
+
+/// What [`upgrade`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeOutcome {
+    /// The manifest already had checksums; the package was left alone
+    AlreadyCurrent { version: u32 },
+    Upgraded { from: u32, resources: usize, missing: Vec<TGI> },
+}
+
+/// Rewrites the manifest of a merged package in the latest format, computing the checksums
+/// it needs from the resources in the package
+pub fn upgrade<P: AsRef<Path>>(path: P) -> Result<UpgradeOutcome> {
+    let path = path.as_ref();
+    let pkg = Package::open(path)?;
+    let (manifest_entry, mut manifest) = read_manifest(&pkg)?;
+    if manifest.has_checksums() {
+        return Ok(UpgradeOutcome::AlreadyCurrent { version: manifest.version });
+    }
+
+    let from = manifest.version;
+    let mut checksums = HashMap::new();
+    let mut missing = Vec::new();
+    for tgi in manifest.entries.iter().flat_map(|e| e.resources.iter()) {
+        if checksums.contains_key(tgi) || missing.contains(tgi) {
+            continue;
+        }
+        match pkg.find(tgi).cloned() {
+            Some(entry) => {
+                let data = pkg.read_raw_resource(&entry).with_context(|| format!("Failed to read {}", tgi))?;
+                checksums.insert(*tgi, ResourceChecksum::of(&data));
+            }
+            None => missing.push(*tgi),
+        }
+    }
+    drop(pkg);
+
+    add_checksums(&mut manifest, &checksums);
+    let bytes = manifest.to_bytes()?;
+    Package::replace_resources(path, &HashMap::from([(manifest_entry.tgi, bytes)]))?;
+    Ok(UpgradeOutcome::Upgraded { from, resources: checksums.len(), missing })
+}
+
+/// A resource whose contents don't match its manifest checksum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyIssue {
+    Missing { package: String, tgi: TGI },
+    Mismatch { package: String, tgi: TGI, expected: ResourceChecksum, actual: ResourceChecksum },
+    Unreadable { package: String, tgi: TGI, error: String },
+}
+
+impl std::fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyIssue::Missing { package, tgi } => write!(f, "{}: {} is missing", package, tgi),
+            VerifyIssue::Mismatch { package, tgi, expected, actual } => write!(
+                f, "{}: {} has changed ({} bytes, hash {:016X}; expected {} bytes, hash {:016X})",
+                package, tgi, actual.size, actual.hash, expected.size, expected.hash,
+            ),
+            VerifyIssue::Unreadable { package, tgi, error } => write!(f, "{}: {} can't be read: {}", package, tgi, error),
+        }
+    }
+}
+
+/// Checks every resource listed in a version 2 manifest against its checksum.
+/// Older manifests have nothing to check against and always pass.
+pub fn verify(pkg: &Package, manifest: &ManifestResource) -> Vec<VerifyIssue> {
+    verify_parts(std::slice::from_ref(pkg), manifest)
+}
+
+/// [`verify`] for a merge split across several packages that share one manifest.
+/// Each resource is checked in the first package that contains it.
+pub fn verify_parts(parts: &[Package], manifest: &ManifestResource) -> Vec<VerifyIssue> {
+    verify_parts_with(parts, manifest, &())
+}
+
+/// [`verify_parts`], telling `observer` as each original package is checked, with every issue
+/// as a warning
+pub fn verify_parts_with(parts: &[Package], manifest: &ManifestResource, observer: &dyn OperationObserver) -> Vec<VerifyIssue> {
+    let mut issues = Vec::new();
+    if !manifest.has_checksums() {
+        return issues;
+    }
+    let progress = Progress::start(observer, "verify", Some(manifest.entries.len()));
+    for entry in &manifest.entries {
+        let found = issues.len();
+        for (tgi, expected) in entry.resources.iter().zip(&entry.checksums) {
+            let package = entry.name.clone();
+            let found = parts.iter().find_map(|pkg| pkg.find(tgi).cloned().map(|e| (pkg, e)));
+            let Some((pkg, index_entry)) = found else {
+                issues.push(VerifyIssue::Missing { package, tgi: *tgi });
+                continue;
+            };
+            match pkg.read_raw_resource(&index_entry) {
+                Ok(data) => {
+                    let actual = ResourceChecksum::of(&data);
+                    if actual != *expected {
+                        issues.push(VerifyIssue::Mismatch { package, tgi: *tgi, expected: *expected, actual });
+                    }
+                }
+                Err(e) => issues.push(VerifyIssue::Unreadable { package, tgi: *tgi, error: format!("{:#}", e) }),
+            }
+        }
+        for issue in &issues[found..] {
+            progress.warn(&issue.to_string());
+        }
+        progress.advance(&entry.name);
+    }
+    progress.finish(true);
+    issues
+}
+
+/// Decompressed data, memsize, compression and committed flag, as [`Package::write_merged`] takes them
+pub type RawResource = (Vec<u8>, u32, u16, u16);
+
+/// One merged package (or every output of a split merge) seen as the original packages its
+/// manifest lists, so a single sub-package can be read without un-merging the whole file
+pub struct MergedPackageView {
+    parts: Vec<Package>,
+    manifest: ManifestResource,
+}
+
+impl MergedPackageView {
+    /// Opens a merged package. For an output of a split merge, the other outputs next to it are
+    /// opened too.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let parts = crate::split::merge_parts(path.as_ref()).iter()
+            .map(Package::open)
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_parts(parts)
+    }
+
+    /// Builds a view over packages that share one manifest, read from the first one that has it
+    pub fn from_parts(parts: Vec<Package>) -> Result<Self> {
+        let i = parts.iter().position(|pkg| find_manifest(pkg).is_some())
+            .context("No manifest found in package")?;
+        let (_, manifest) = read_manifest(&parts[i])?;
+        Ok(Self { parts, manifest })
+    }
+
+    pub fn manifest(&self) -> &ManifestResource {
+        &self.manifest
+    }
+
+    /// Names of the original packages, in the order they were merged
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.manifest.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// Position of the original package `name` in the manifest
+    fn position_of(&self, name: &str) -> Result<usize> {
+        let base = |n: &str| n.strip_suffix(".package").unwrap_or(n).to_string();
+        self.manifest.entries.iter()
+            .position(|e| base(&e.name) == base(name))
+            .ok_or_else(|| Error::Other(format!("No package named {:?} in the manifest", name)))
+    }
+
+    /// The resources that came from the original package `name`. The `.package` extension may be
+    /// left off.
+    pub fn resources_of(&self, name: &str) -> Result<&[TGI]> {
+        self.position_of(name).map(|i| self.manifest.entries[i].resources.as_slice())
+    }
+
+    /// The index entry of `tgi` in whichever part holds it, with the part's position
+    pub fn find(&self, tgi: &TGI) -> Option<(usize, &IndexEntry)> {
+        self.parts.iter().enumerate().find_map(|(i, pkg)| pkg.find(tgi).map(|e| (i, e)))
+    }
+
+    /// Reads the decompressed data of one resource
+    pub fn read_raw_resource(&self, tgi: &TGI) -> Result<Vec<u8>> {
+        let (i, entry) = self.find(tgi).map(|(i, e)| (i, e.clone()))
+            .ok_or(Error::ResourceNotFound(*tgi))?;
+        self.parts[i].read_raw_resource(&entry)
+    }
+
+    /// Reads every resource of the original package `name`, decompressed and with the index
+    /// details needed to write it back out with [`Package::write_merged`]
+    pub fn read_package(&self, name: &str) -> Result<HashMap<TGI, RawResource>> {
+        let tgis = self.resources_of(name)?.to_vec();
+        let mut resources = HashMap::with_capacity(tgis.len());
+        for tgi in tgis {
+            let (i, entry) = self.find(&tgi).map(|(i, e)| (i, e.clone()))
+                .ok_or_else(|| Error::Other(format!("{} is listed in the manifest but not in the merged package", tgi)))?;
+            let data = self.parts[i].read_raw_resource(&entry)
+                .with_context(|| format!("Failed to read {}", tgi))?;
+            resources.insert(tgi, (data, entry.memsize, entry.compression, entry.committed));
+        }
+        Ok(resources)
+    }
+
+    /// Writes the original package `name` back out on its own
+    pub fn extract<P: AsRef<Path>>(&self, name: &str, output: P) -> Result<()> {
+        let resources = self.read_package(name)?;
+        Package::write_merged(output, &resources, false)
+    }
+}
+
+/// What [`replace_subpackage`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubpackageUpdate {
+    /// Resources the new version adds
+    pub added: Vec<TGI>,
+    /// Resources whose data changed
+    pub updated: Vec<TGI>,
+    /// Resources the new version no longer has
+    pub removed: Vec<TGI>,
+    /// Resources that were already identical
+    pub unchanged: usize,
+    /// Resources of the new version left out because a package merged later also has them, and
+    /// its copy wins as it did in the original merge
+    pub shadowed: Vec<TGI>,
+}
+
+/// Stores `data` zlib-compressed, as merges store their manifest and provenance records
+fn zlib_entry(tgi: TGI, data: &[u8]) -> Result<(IndexEntry, Vec<u8>)> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    let compressed = encoder.finish()?;
+    let entry = IndexEntry { tgi, offset: 0, filesize: compressed.len() as u32, memsize: data.len() as u32, compression: 0x5A42, committed: 1 };
+    Ok((entry, compressed))
+}
+
+/// Swaps the resources that came from the original package `name` in a merged package for those
+/// of `new_package`, a newer version of it, and updates the manifest (and provenance record, if
+/// there is one) to match.
+///
+/// Resources the new version no longer has are removed unless another merged package also lists
+/// them. If one of those is listed by a package merged earlier, the merged package only holds
+/// this package's copy, which overrode it, so the earlier copy can't be restored; the update is
+/// refused, leaving the merged package as it was, and the packages must be merged again. Every
+/// other resource is copied across as stored, without being decompressed or
+/// recompressed. For a split merge, new resources go to the output that held the old version,
+/// and every output gets the updated manifest.
+pub fn replace_subpackage<P: AsRef<Path>, Q: AsRef<Path>>(merged: P, name: &str, new_package: Q) -> Result<SubpackageUpdate> {
+    let part_paths = crate::split::merge_parts(merged.as_ref());
+    let parts = part_paths.iter().map(Package::open).collect::<Result<Vec<_>>>()?;
+    let view = MergedPackageView::from_parts(parts)?;
+    let position = view.position_of(name)?;
+
+    let new_pkg = Package::open(new_package.as_ref())?;
+    let skipped = |res_type: u32| MANIFEST_TYPES.contains(&res_type) || res_type == PROVENANCE_TYPE;
+    let new_entries: Vec<IndexEntry> = new_pkg.entries.iter().filter(|e| !skipped(e.tgi.res_type)).cloned().collect();
+
+    let later: HashSet<TGI> = view.manifest.entries[position + 1..].iter().flat_map(|e| e.resources.iter().copied()).collect();
+    let others: HashSet<TGI> = view.manifest.entries.iter().enumerate()
+        .filter(|(i, _)| *i != position)
+        .flat_map(|(_, e)| e.resources.iter().copied())
+        .collect();
+    let home = view.manifest.entries[position].resources.iter()
+        .find_map(|tgi| view.find(tgi).map(|(i, _)| i))
+        .unwrap_or(0);
+
+    let mut update = SubpackageUpdate::default();
+    let mut checksums: HashMap<TGI, ResourceChecksum> = view.manifest.entries.iter()
+        .flat_map(|e| e.resources.iter().copied().zip(e.checksums.iter().copied()))
+        .collect();
+    // Resources whose stored data is replaced, and the part each one goes to
+    let mut replacements: HashMap<TGI, (usize, IndexEntry, Vec<u8>)> = HashMap::new();
+    for entry in &new_entries {
+        let tgi = entry.tgi;
+        if later.contains(&tgi) {
+            update.shadowed.push(tgi);
+            continue;
+        }
+        let data = new_pkg.read_raw_resource(entry).with_context(|| format!("Failed to read {} from the new package", tgi))?;
+        let checksum = ResourceChecksum::of(&data);
+        let part = match view.find(&tgi).map(|(i, e)| (i, e.clone())) {
+            Some((i, existing)) => {
+                let current = view.parts[i].read_raw_resource(&existing).with_context(|| format!("Failed to read {}", tgi))?;
+                if ResourceChecksum::of(&current) == checksum {
+                    update.unchanged += 1;
+                    checksums.insert(tgi, checksum);
+                    continue;
+                }
+                update.updated.push(tgi);
+                i
+            }
+            None => {
+                update.added.push(tgi);
+                home
+            }
+        };
+        let stored = new_pkg.read_stored_resource(entry)?;
+        replacements.insert(tgi, (part, entry.clone(), stored));
+        checksums.insert(tgi, checksum);
+    }
+    drop(new_pkg);
+
+    let new_tgis: HashSet<TGI> = new_entries.iter().map(|e| e.tgi).collect();
+    let earlier: HashSet<TGI> = view.manifest.entries[..position].iter().flat_map(|e| e.resources.iter().copied()).collect();
+    let unrecoverable: Vec<String> = view.manifest.entries[position].resources.iter()
+        .filter(|tgi| !new_tgis.contains(tgi) && earlier.contains(tgi) && !later.contains(tgi))
+        .map(|tgi| tgi.to_string())
+        .collect();
+    if !unrecoverable.is_empty() {
+        return Err(Error::InvalidInput(format!(
+            "The new version of {} drops {} resource(s) it overrode in a package merged before it, and the merged package doesn't have that package's copies to restore: {}. Merge the original packages again instead.",
+            name, unrecoverable.len(), unrecoverable.join(", "))));
+    }
+    update.removed = view.manifest.entries[position].resources.iter()
+        .filter(|tgi| !new_tgis.contains(tgi) && !others.contains(tgi))
+        .copied()
+        .collect();
+
+    let MergedPackageView { mut parts, mut manifest } = view;
+    manifest.entries[position].resources = new_entries.iter().map(|e| e.tgi).collect();
+    if manifest.has_checksums() {
+        add_checksums(&mut manifest, &checksums);
+    } else {
+        manifest.entries[position].checksums.clear();
+    }
+    let manifest_data = manifest.to_bytes()?;
+
+    let provenance = parts.iter_mut().find_map(|pkg| Provenance::read(pkg).ok().flatten());
+    let provenance = match provenance {
+        Some(mut record) => {
+            let base = |n: &str| {
+                let file = n.rsplit(['/', '\\']).next().unwrap_or(n);
+                file.strip_suffix(".package").unwrap_or(file).to_string()
+            };
+            for source in record.sources.iter_mut().filter(|s| base(&s.name) == base(name)) {
+                *source = SourceFile::hash(new_package.as_ref(), &source.name)?;
+            }
+            Some(record.to_bytes())
+        }
+        None => None,
+    };
+
+    let removed: HashSet<TGI> = update.removed.iter().copied().collect();
+    for (i, (pkg, path)) in parts.into_iter().zip(&part_paths).enumerate() {
+        let mut resources = Vec::with_capacity(pkg.entries.len());
+        for entry in pkg.entries.clone() {
+            if removed.contains(&entry.tgi) {
+                continue;
+            }
+            if MANIFEST_TYPES.contains(&entry.tgi.res_type) {
+                resources.push(zlib_entry(entry.tgi, &manifest_data)?);
+            } else if let Some(record) = provenance.as_ref().filter(|_| entry.tgi.res_type == PROVENANCE_TYPE) {
+                resources.push(zlib_entry(entry.tgi, record)?);
+            } else if let Some((_, new_entry, stored)) = replacements.remove(&entry.tgi) {
+                resources.push((new_entry, stored));
+            } else {
+                let stored = pkg.read_stored_resource(&entry).with_context(|| format!("Failed to read {}", entry.tgi))?;
+                resources.push((entry, stored));
+            }
+        }
+        let mut added: Vec<TGI> = replacements.iter().filter(|(_, (part, ..))| *part == i).map(|(tgi, _)| *tgi).collect();
+        added.sort_by_key(|tgi| (tgi.res_type, tgi.res_group, tgi.instance));
+        for tgi in added {
+            let (_, new_entry, stored) = replacements.remove(&tgi).expect("listed above");
+            resources.push((new_entry, stored));
+        }
+        // The original has to be closed before it can be replaced on Windows
+        drop(pkg);
+
+        Package::write_stored(path, &resources).context("Failed to write updated package")?;
+    }
+    Ok(update)
+}
+
+/// Compression code of the index entries of a descriptor. The game skips entries marked deleted,
+/// so a descriptor left in the Mods folder loads nothing.
+const DESCRIPTOR_COMPRESSION: u16 = 0xFFE0;
+
+/// True if `pkg` is a content descriptor written by [`write_descriptor`]: a manifest, and index
+/// entries with no data behind them
+pub fn is_descriptor(pkg: &Package) -> bool {
+    find_manifest(pkg).is_some()
+        && pkg.entries.iter().all(|e| {
+            MANIFEST_TYPES.contains(&e.tgi.res_type) || (e.compression == DESCRIPTOR_COMPRESSION && e.filesize == 0)
+        })
+}
+
+/// The manifest of a content descriptor: the packages it describes, each with its resources and
+/// their checksums. `None` for any other package.
+pub fn read_descriptor(pkg: &Package) -> Result<Option<ManifestResource>> {
+    if !is_descriptor(pkg) {
+        return Ok(None);
+    }
+    read_manifest(pkg).map(|(_, manifest)| Some(manifest))
+}
+
+/// Writes a content descriptor for `packages` (name and package): a package holding only a
+/// manifest of what each of them contains, with checksums, and an index of their resource keys
+/// and sizes but none of their data. Descriptors are small enough to share a mod list without
+/// sharing the CC, and [`Census::scan`](crate::census::Census::scan) and conflict checks read
+/// them as the packages they describe.
+///
+/// A package that is itself a descriptor is copied in as the packages it lists. Resources that
+/// can't be read get an empty checksum. Returns the number of resource keys described.
+pub fn write_descriptor<P: AsRef<Path>>(output: P, packages: &[(&str, &Package)]) -> Result<usize> {
+    use rayon::prelude::*;
+
+    let mut manifest = ManifestResource { version: ManifestResource::LATEST_VERSION, padding: 0, entries: Vec::new() };
+    for (name, pkg) in packages {
+        if let Some(described) = read_descriptor(pkg)? {
+            manifest.entries.extend(described.entries);
+            continue;
+        }
+        let entries: Vec<&IndexEntry> = pkg.entries.iter()
+            .filter(|e| !MANIFEST_TYPES.contains(&e.tgi.res_type) && e.tgi.res_type != PROVENANCE_TYPE)
+            .collect();
+        let checksums = entries.par_iter()
+            .map(|entry| match pkg.read_raw_resource(entry) {
+                Ok(data) => ResourceChecksum::of(&data),
+                Err(e) => {
+                    log::warn!("Could not read {} from {}: {}", entry.tgi, name, e);
+                    ResourceChecksum::default()
+                }
+            })
+            .collect();
+        manifest.entries.push(crate::package::resource::ManifestEntry {
+            name: name.to_string(),
+            resources: entries.iter().map(|e| e.tgi).collect(),
+            checksums,
+        });
+    }
+
+    let mut sizes: HashMap<TGI, u32> = HashMap::new();
+    for entry in &manifest.entries {
+        for (tgi, checksum) in entry.resources.iter().zip(&entry.checksums) {
+            sizes.entry(*tgi).or_insert(checksum.size);
+        }
+    }
+    let mut keys: Vec<(TGI, u32)> = sizes.into_iter().collect();
+    keys.sort_by_key(|(tgi, _)| (tgi.res_type, tgi.res_group, tgi.instance));
+
+    let manifest_tgi = TGI { res_type: MANIFEST_TYPES[0], res_group: 0, instance: 0 };
+    let mut resources = vec![zlib_entry(manifest_tgi, &manifest.to_bytes()?)?];
+    resources.extend(keys.iter().map(|&(tgi, memsize)| {
+        let entry = IndexEntry { tgi, offset: 0, filesize: 0, memsize, compression: DESCRIPTOR_COMPRESSION, committed: 1 };
+        (entry, Vec::new())
+    }));
+    Package::write_stored(output, &resources)?;
+    Ok(keys.len())
+}
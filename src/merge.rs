@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use log::{debug, error};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::conflict::{find_conflicts, resolve, Conflict, ConflictPolicy, Resolution};
+use crate::error::{Context, Error, Result};
+use crate::manifest::{add_checksums, is_descriptor, read_descriptor, read_manifest, RawResource, MANIFEST_TYPES};
+use crate::observer::{OperationObserver, Progress};
+use crate::package::Package;
+use crate::package::write::WriteOptions;
+use crate::package::index::TGI;
+use crate::package::obfuscation::Verdict;
+use crate::package::resource::{ManifestEntry, ManifestResource, Resource, ResourceChecksum};
+use crate::package::stats::MergeStats;
+use crate::perf;
+use crate::provenance::{Provenance, SourceFile, PROVENANCE_TYPE};
+use crate::report::{catch_panic, FailureContext, FailureReport};
+use crate::split::{is_split_output, merge_parts, MergeCategory};
+
+/// How [`merge`] combines a folder of packages
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// Record a provenance resource in the output
+    pub provenance: bool,
+    /// Write one package per [`MergeCategory`] instead of a single merged package
+    pub split: bool,
+    /// Which package wins when several contain the same resource
+    pub conflicts: ConflictPolicy,
+    /// Run on fewer, low-priority threads with rate-limited reads
+    pub background: bool,
+    /// zlib level the merged package is written with
+    pub level: u32,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self { provenance: true, split: false, conflicts: ConflictPolicy::default(), background: false, level: crate::package::write::DEFAULT_LEVEL }
+    }
+}
+
+/// What [`merge`] did
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    pub files_processed: usize,
+    pub files_skipped: usize,
+    /// Resources in the outputs, not counting the manifest and provenance record
+    pub total_resources: usize,
+    pub stats: MergeStats,
+    /// How each resource more than one package contains was settled
+    pub resolutions: Vec<Resolution>,
+    /// The packages written; empty when there was nothing to merge
+    pub outputs: Vec<PathBuf>,
+    /// Packages and resources that couldn't be read, for a report to attach to an issue
+    pub failures: FailureReport,
+}
+
+/// What [`unmerge`] did
+#[derive(Debug, Clone, Default)]
+pub struct UnmergeSummary {
+    pub output_dir: PathBuf,
+    /// The packages written back out, one per manifest entry
+    pub packages: Vec<PathBuf>,
+    /// Whether the manifest had checksums to check the merged package against
+    pub verified: bool,
+    /// Resources that don't match the manifest's checksums
+    pub damaged: usize,
+}
+
+/// A package read for merging: its name, resource order and payloads.
+type ScannedPackage = (String, Vec<TGI>, Vec<(TGI, RawResource)>);
+
+/// The packages under `folder` that a merge would read, sorted by path
+pub fn find_inputs(folder: &Path) -> Vec<PathBuf> {
+    let mut inputs = Vec::new();
+    for entry in WalkDir::new(folder).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "package") {
+            // Avoid processing the output of an earlier merge if it already exists in a "merged" subfolder
+            let in_merged_dir = path.parent().and_then(|p| p.file_name()).is_some_and(|n| n == "merged");
+            let earlier_output = path.to_string_lossy().contains("merged/merged.package") || (in_merged_dir && is_split_output(path));
+            if !earlier_output {
+                inputs.push(path.to_path_buf());
+            }
+        }
+    }
+    inputs.sort();
+    inputs
+}
+
+/// How a merge input is named in conflict reports (and provenance): its path relative to the
+/// merged folder, with forward slashes
+pub fn input_name(folder: &Path, path: &Path) -> String {
+    path.strip_prefix(folder).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Whether `res_type` is one of the records a merge adds rather than a merged resource
+pub fn is_merge_record(res_type: u32) -> bool {
+    MANIFEST_TYPES.contains(&res_type) || res_type == PROVENANCE_TYPE
+}
+
+/// The packages a merge of `folder` writes, or replaces, so they can be checked before it runs
+pub fn output_paths(folder: &Path, split: bool) -> Vec<PathBuf> {
+    let output_dir = folder.join("merged");
+    if split {
+        MergeCategory::ALL.iter().map(|category| output_dir.join(category.file_name())).collect()
+    } else {
+        vec![output_dir.join("merged.package")]
+    }
+}
+
+/// Finds the resources more than one merge input contains, reading only the package indexes.
+/// A content descriptor counts as the packages it describes.
+pub fn scan_conflicts(folder: &Path) -> Vec<Conflict> {
+    let scanned: Vec<(String, Vec<TGI>)> = find_inputs(folder)
+        .par_iter()
+        .filter_map(|path| {
+            let pkg = Package::open(path).inspect_err(|e| log::warn!(package:? = path; "Failed to open package: {:#}", e)).ok()?;
+            let name = input_name(folder, path);
+            if let Ok(Some(manifest)) = read_descriptor(&pkg) {
+                return Some(manifest.entries.into_iter()
+                    .map(|entry| (format!("{}/{}", name, entry.name), entry.resources))
+                    .collect::<Vec<_>>());
+            }
+            let tgis = pkg.entries.iter().map(|e| e.tgi).filter(|tgi| !is_merge_record(tgi.res_type)).collect();
+            Some(vec![(name, tgis)])
+        })
+        .flatten()
+        .collect();
+    find_conflicts(scanned.iter().map(|(name, tgis)| (name.as_str(), tgis.as_slice())))
+}
+
+/// Writes the conflict resolutions of a merge to `merge_report.txt` in the output folder,
+/// removing a report left by an earlier merge when there were none
+fn write_merge_report(output_dir: &Path, resolutions: &[Resolution]) -> Result<()> {
+    let path = output_dir.join("merge_report.txt");
+    if resolutions.is_empty() {
+        if path.is_file() {
+            std::fs::remove_file(&path).with_context(|| format!("Failed to remove old {:?}", path))?;
+        }
+        return Ok(());
+    }
+    let mut report = format!("{} resource(s) were in more than one package:\n", resolutions.len());
+    for resolution in resolutions {
+        report.push_str(&format!("{}\n", resolution));
+    }
+    crate::atomic::write(&path, report).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+/// Reads every resource of the package at `path` for merging, recording what fails in `failures`
+fn scan_package(path: &Path, failures: &FailureReport) -> Result<ScannedPackage> {
+    let filename = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let mut pkg_resources = Vec::new();
+    let mut pkg_data = Vec::new();
+
+    let pkg = match catch_panic(|| Package::open(path)) {
+        Ok(pkg) => pkg,
+        Err(e) => {
+            error!(package:? = path; "Failed to open package: {:#}", e);
+            failures.add(FailureContext::new(path, None, None, &e, None));
+            return Err(e).with_context(|| format!("Failed to open {:?}", path));
+        }
+    };
+    if is_descriptor(&pkg) {
+        return Err(Error::InvalidInput(format!("{:?} is a content descriptor, which has no resource data to merge", path)));
+    }
+
+    for entry in &pkg.entries {
+        if is_merge_record(entry.tgi.res_type) {
+            continue;
+        }
+        let data = match catch_panic(|| pkg.read_raw_resource(entry)) {
+            Ok(data) => data,
+            Err(e) => {
+                error!(package:? = path, resource:% = entry.tgi, offset = entry.offset, compression = entry.compression; "Failed to read resource: {:#}", e);
+                failures.add(FailureContext::from_stored(path, Some(&pkg.header), entry, &e));
+                let integrity = pkg.check_obfuscation();
+                if integrity.verdict == Verdict::Obfuscated {
+                    return Err(Error::InvalidInput(format!("{:?} is {}", path, integrity.summary())));
+                }
+                return Err(e).with_context(|| format!("Failed to read {} from {:?}", entry.tgi, path));
+            }
+        };
+        pkg_data.push((entry.tgi, (data, entry.memsize, entry.compression, entry.committed)));
+        pkg_resources.push(entry.tgi);
+    }
+    debug!(package:? = path, resources = pkg_resources.len(); "Scanned package");
+    Ok((filename, pkg_resources, pkg_data))
+}
+
+/// Merges every .package under `folder` into `folder/merged/merged.package`, or one package per
+/// category with [`MergeOptions::split`], telling `observer` as each package is read.
+///
+/// When several packages contain the same resource, [`MergeOptions::conflicts`] decides which
+/// copy is kept, and every conflict is listed in `merge_report.txt` next to the output. Every
+/// output carries a manifest of the packages merged, so [`unmerge`] can take any of them apart
+/// again. Packages that can't be read are skipped and recorded in [`MergeSummary::failures`].
+pub fn merge(folder: &Path, options: &MergeOptions, observer: &dyn OperationObserver) -> Result<MergeSummary> {
+    if options.background {
+        let mode = crate::background::BackgroundMode::default();
+        let options = MergeOptions { background: false, ..options.clone() };
+        return crate::background::run(&mode, || merge(folder, &options, observer))?;
+    }
+    let inputs = find_inputs(folder);
+    let progress = Progress::start(observer, "merge", Some(inputs.len()));
+    let result = merge_inputs(folder, &inputs, options, &progress);
+    progress.finish(result.is_ok());
+    result
+}
+
+fn merge_inputs(folder: &Path, files_to_process: &[PathBuf], options: &MergeOptions, progress: &Progress) -> Result<MergeSummary> {
+    let mut summary = MergeSummary::default();
+    if files_to_process.is_empty() {
+        progress.warn("No .package files found to merge.");
+        return Ok(summary);
+    }
+
+    let results: Vec<Result<ScannedPackage>> = files_to_process
+        .par_iter()
+        .map(|path| perf::busy(|| {
+            let result = scan_package(path, &summary.failures);
+            match &result {
+                Ok(_) => progress.advance(&input_name(folder, path)),
+                Err(e) => progress.skip(&input_name(folder, path), &format!("{:#}", e)),
+            }
+            result
+        }))
+        .collect();
+
+    let sources: Vec<SourceFile> = if options.provenance {
+        files_to_process.par_iter()
+            .zip(results.par_iter())
+            .filter(|(_, result)| result.is_ok())
+            .filter_map(|(path, _)| {
+                SourceFile::hash(path, &input_name(folder, path))
+                    .inspect_err(|e| progress.warn(&format!("Could not hash source file {:?}: {:#}", path, e)))
+                    .ok()
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Settle every resource more than one package contains before anything is written
+    let names: Vec<String> = files_to_process.iter().map(|path| input_name(folder, path)).collect();
+    let conflicts = find_conflicts(
+        names.iter().zip(&results)
+            .filter_map(|(name, result)| result.as_ref().ok().map(|(_, tgis, _)| (name.as_str(), tgis.as_slice())))
+    );
+    let resolutions = resolve(&conflicts, &options.conflicts)?;
+    let winners: HashMap<TGI, &str> = resolutions.iter().map(|r| (r.tgi, r.winner.as_str())).collect();
+    let wins = |name: &str, tgi: &TGI| winners.get(tgi).is_none_or(|winner| *winner == name);
+
+    let mut merged_data: HashMap<TGI, RawResource> = HashMap::new();
+    // Which output each resource goes to when splitting; the package its data comes from decides
+    let mut categories: HashMap<TGI, MergeCategory> = HashMap::new();
+    let mut manifest_entries = Vec::new();
+    // Copies another package's copy won over, told apart once the winners are checksummed
+    let mut dropped: Vec<(TGI, RawResource)> = Vec::new();
+
+    for (name, res) in names.iter().zip(results) {
+        match res {
+            Ok((filename, pkg_resources, pkg_data)) => {
+                summary.files_processed += 1;
+                summary.stats.add_source(&pkg_resources);
+                if options.split {
+                    let category = MergeCategory::classify(&pkg_resources);
+                    debug!(package = filename.as_str(), category = category.name(); "Classified package");
+                    categories.extend(pkg_resources.iter().filter(|tgi| wins(name, tgi)).map(|tgi| (*tgi, category)));
+                }
+                manifest_entries.push(ManifestEntry {
+                    name: filename,
+                    resources: pkg_resources,
+                    checksums: Vec::new(),
+                });
+                for (tgi, data) in pkg_data {
+                    if wins(name, &tgi) {
+                        merged_data.insert(tgi, data);
+                    } else {
+                        dropped.push((tgi, data));
+                    }
+                }
+            }
+            Err(_) => summary.files_skipped += 1,
+        }
+    }
+
+    if merged_data.is_empty() {
+        progress.warn("No resources found to merge.");
+        return Ok(summary);
+    }
+
+    let mut manifest = ManifestResource {
+        version: 1,
+        padding: 0,
+        entries: manifest_entries,
+    };
+    let checksums: HashMap<TGI, ResourceChecksum> = merged_data.par_iter()
+        .map(|(tgi, (data, ..))| (*tgi, ResourceChecksum::of(data)))
+        .collect();
+    add_checksums(&mut manifest, &checksums);
+    let dropped: Vec<(TGI, bool)> = std::mem::take(&mut dropped).into_par_iter()
+        .map(|(tgi, (data, ..))| (tgi, checksums.get(&tgi) == Some(&ResourceChecksum::of(&data))))
+        .collect();
+    for (tgi, identical) in dropped {
+        summary.stats.add_dropped(tgi.res_type, identical);
+    }
+
+    let manifest_data = manifest.to_bytes().context("Failed to serialize manifest")?;
+    let manifest_tgi = TGI { res_type: MANIFEST_TYPES[0], res_group: 0, instance: 0 };
+    summary.total_resources = merged_data.len();
+
+    // Every output gets the manifest (and provenance), so any one of them can be un-merged.
+    // The compression flag makes write_merged compress them whatever the options say.
+    let mut shared: HashMap<TGI, RawResource> = HashMap::new();
+    shared.insert(manifest_tgi, (manifest_data.clone(), manifest_data.len() as u32, 0x5A42, 1));
+
+    if options.provenance {
+        let mut record_options = std::collections::BTreeMap::new();
+        record_options.insert("compress".to_string(), "true".to_string());
+        if options.split {
+            record_options.insert("split".to_string(), "true".to_string());
+        }
+        let record = Provenance::new(record_options, sources).to_bytes();
+        shared.insert(Provenance::tgi(), (record.clone(), record.len() as u32, 0x5A42, 1));
+    }
+
+    let output_dir = folder.join("merged");
+    std::fs::create_dir_all(&output_dir).context("Failed to create output directory")?;
+    write_merge_report(&output_dir, &resolutions)?;
+    summary.resolutions = resolutions;
+
+    let mut outputs: Vec<(PathBuf, HashMap<TGI, RawResource>)> = Vec::new();
+    if options.split {
+        for category in MergeCategory::ALL {
+            let part: HashMap<TGI, RawResource> = merged_data.iter()
+                .filter(|(tgi, _)| categories.get(tgi) == Some(&category))
+                .map(|(tgi, data)| (*tgi, data.clone()))
+                .collect();
+            let path = output_dir.join(category.file_name());
+            if part.is_empty() {
+                // Don't leave a stale output from an earlier split merge behind
+                if path.is_file() {
+                    std::fs::remove_file(&path).with_context(|| format!("Failed to remove old {:?}", path))?;
+                }
+                continue;
+            }
+            outputs.push((path, part));
+        }
+    } else {
+        outputs.push((output_dir.join("merged.package"), std::mem::take(&mut merged_data)));
+    }
+    drop(merged_data);
+
+    for (output_file, mut data) in outputs {
+        debug!("Writing merged package to: {:?} ({} resources)", output_file, data.len());
+        data.extend(shared.iter().map(|(tgi, d)| (*tgi, d.clone())));
+        let write = WriteOptions { compress: true, level: options.level, ..Default::default() };
+        Package::write_merged(&output_file, &data, write).context("Failed to write merged package")?;
+        // Sizes come from the written index, so they're what the merge really takes up on disk
+        match Package::open(&output_file) {
+            Ok(pkg) => summary.stats.add_output(&pkg.entries.iter().filter(|e| !is_merge_record(e.tgi.res_type)).cloned().collect::<Vec<_>>()),
+            Err(e) => progress.warn(&format!("Could not read back {:?} for the summary: {:#}", output_file, e)),
+        }
+        summary.outputs.push(output_file);
+    }
+    Ok(summary)
+}
+
+/// The file name an original package is un-merged to
+fn unmerged_name(name: &str) -> String {
+    if name.to_lowercase().ends_with(".package") {
+        name.to_string()
+    } else {
+        format!("{}.package", name)
+    }
+}
+
+/// The packages [`unmerge`] of the merged package at `path` writes, so they can be checked
+/// before it runs
+pub fn unmerge_outputs(path: &Path) -> Result<Vec<PathBuf>> {
+    let (_, manifest) = read_manifest(&Package::open(path)?)?;
+    let output_dir = path.parent().unwrap_or(Path::new(".")).join("unmerged");
+    Ok(manifest.entries.iter().map(|entry| output_dir.join(unmerged_name(&entry.name))).collect())
+}
+
+/// Writes each original package listed in the manifest of the merged package at `path` back out
+/// to an `unmerged` folder next to it, telling `observer` as each is written. For a split merge,
+/// any one output can be given; the others are read from the same folder.
+///
+/// A version 2 manifest's checksums are checked first, and resources that don't match are
+/// reported as warnings; the packages are still written.
+pub fn unmerge(path: &Path, observer: &dyn OperationObserver) -> Result<UnmergeSummary> {
+    let (_, manifest) = read_manifest(&Package::open(path)?)
+        .context("This package cannot be un-merged automatically.")?;
+    // Shared by every worker; reads from each part are serialized, decompression isn't
+    let parts = merge_parts(path).iter().map(Package::open).collect::<Result<Vec<_>>>()?;
+
+    let mut summary = UnmergeSummary {
+        output_dir: path.parent().unwrap_or(Path::new(".")).join("unmerged"),
+        verified: manifest.has_checksums(),
+        ..Default::default()
+    };
+    if summary.verified {
+        summary.damaged = crate::manifest::verify_parts_with(&parts, &manifest, observer).len();
+    }
+
+    let progress = Progress::start(observer, "unmerge", Some(manifest.entries.len()));
+    let result = std::fs::create_dir_all(&summary.output_dir)
+        .with_context(|| format!("Failed to create {:?}", summary.output_dir))
+        .and_then(|_| manifest.entries.par_iter().map(|entry| perf::busy(|| -> Result<PathBuf> {
+            let filename = unmerged_name(&entry.name);
+            let mut sub_package_data: HashMap<TGI, RawResource> = HashMap::new();
+            for tgi in &entry.resources {
+                // Find the resource in the merged package (or whichever part of a split merge has it)
+                let found = parts.iter().find_map(|pkg| pkg.find(tgi).cloned().map(|e| (pkg, e)));
+                if let Some((part, entry)) = found {
+                    // Read RAW resource to preserve compression/metadata if possible
+                    let data = part.read_raw_resource(&entry)?;
+                    sub_package_data.insert(*tgi, (data, entry.memsize, entry.compression, entry.committed));
+                } else {
+                    progress.warn(&format!("Resource {} listed in manifest but not found in package!", tgi));
+                }
+            }
+            let output_path = summary.output_dir.join(&filename);
+            Package::write_merged(&output_path, &sub_package_data, false)?;
+            progress.advance(&filename);
+            Ok(output_path)
+        })).collect::<Result<Vec<_>>>());
+    progress.finish(result.is_ok());
+    summary.packages = result?;
+    Ok(summary)
+}
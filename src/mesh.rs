@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{GeomResource, ResourceKind, RigBone, RigSkeleton, TypedResource};
+
+/// GEOM (body/CAS mesh) resource type
+pub const GEOM_TYPE: u32 = 0x015A1849;
+
+/// The GEOMs that make up one item's levels of detail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshGroup {
+    /// The CASP the meshes belong to; `None` for GEOMs no CASP in the package references
+    pub owner: Option<TGI>,
+    /// GEOMs in LOD order (LOD 0, the highest detail, first)
+    pub lods: Vec<TGI>,
+}
+
+/// Groups the GEOMs in a package by the CASP that uses them.
+///
+/// A CASP lists its LOD meshes in its TGI list from highest to lowest detail, so that order is
+/// used as the LOD order.
+#[cfg(feature = "catalog")]
+pub fn find_mesh_groups(pkg: &Package) -> Vec<MeshGroup> {
+    let geoms: Vec<TGI> = pkg.entries.iter()
+        .filter(|e| e.tgi.res_type == GEOM_TYPE)
+        .map(|e| e.tgi)
+        .collect();
+    let present: HashSet<TGI> = geoms.iter().copied().collect();
+
+    let casps: Vec<_> = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::CasPart)
+        .cloned()
+        .collect();
+
+    let mut groups = Vec::new();
+    let mut grouped = HashSet::new();
+    for casp in casps {
+        let Ok(TypedResource::CasPart(resource)) = pkg.read_resource(&casp) else { continue };
+        let mut lods = Vec::new();
+        for tgi in resource.tgi_list().unwrap_or_default() {
+            if present.contains(&tgi) && !lods.contains(&tgi) {
+                lods.push(tgi);
+            }
+        }
+        if !lods.is_empty() {
+            grouped.extend(lods.iter().copied());
+            groups.push(MeshGroup { owner: Some(casp.tgi), lods });
+        }
+    }
+
+    for tgi in geoms {
+        if !grouped.contains(&tgi) {
+            groups.push(MeshGroup { owner: None, lods: vec![tgi] });
+        }
+    }
+    groups
+}
+
+/// The rig a group's meshes are skinned to, with its TGI: the first RIG in the package that the
+/// CASP or one of the GEOMs lists among its TGIs, or else the package's only RIG. `None` when the
+/// package has no rig the group can be matched to, e.g. several rigs and none referenced.
+#[cfg(feature = "catalog")]
+pub fn find_group_rig(pkg: &Package, group: &MeshGroup) -> Option<(TGI, Vec<RigBone>)> {
+    let rigs: Vec<_> = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::Rig)
+        .cloned()
+        .collect();
+    let read = |entry: &IndexEntry| match pkg.read_resource(entry) {
+        Ok(TypedResource::Rig(rig)) => rig.bones().ok().map(|bones| (entry.tgi, bones)),
+        _ => None,
+    };
+
+    let mut referenced = Vec::new();
+    if let Some(Ok(TypedResource::CasPart(casp))) = group.owner.and_then(|tgi| pkg.find(&tgi)).map(|e| pkg.read_resource(e)) {
+        referenced.extend(casp.tgi_list().unwrap_or_default());
+    }
+    for entry in group.lods.iter().filter_map(|tgi| pkg.find(tgi)) {
+        if let Ok(geom) = pkg.read_raw_resource(entry).and_then(|data| GeomResource::from_package_data(&data)) {
+            referenced.extend(geom.tgi_blocks);
+        }
+    }
+    if let Some(rig) = referenced.iter().filter_map(|tgi| rigs.iter().find(|e| e.tgi == *tgi)).find_map(read) {
+        return Some(rig);
+    }
+    match rigs.as_slice() {
+        [only] => read(only),
+        _ => None,
+    }
+}
+
+/// Reads the bones of the first clear-format RIG in a package
+pub fn find_rig(pkg: &Package) -> Option<Vec<RigBone>> {
+    let rigs: Vec<_> = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::Rig)
+        .cloned()
+        .collect();
+    rigs.iter().find_map(|entry| match pkg.read_resource(entry) {
+        Ok(TypedResource::Rig(rig)) => rig.bones().ok(),
+        _ => None,
+    })
+}
+
+/// Reads the whole of the first clear-format RIG in a package that parses, with its TGI
+pub fn find_skeleton(pkg: &Package) -> Option<(TGI, RigSkeleton)> {
+    let rigs: Vec<_> = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::Rig)
+        .cloned()
+        .collect();
+    rigs.iter().find_map(|entry| match pkg.read_resource(entry) {
+        Ok(TypedResource::Rig(rig)) => rig.skeleton().ok().map(|skeleton| (entry.tgi, skeleton)),
+        _ => None,
+    })
+}
+
+/// A problem found by [`lint_geom`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintIssue {
+    /// A bone hash in the GEOM that the rig doesn't have (the mesh will "explode" in game)
+    MissingBone { hash: u32 },
+    /// A vertex weighted to a bone index past the end of the GEOM's bone hash list
+    BoneIndexOutOfRange { vertex: usize, index: u8 },
+    /// A skinned vertex whose weights don't add up to 1
+    UnnormalizedWeights { vertex: usize, total: f32 },
+    /// A UV outside the 0-1 range of the texture
+    UvOutOfRange { vertex: usize, channel: usize, uv: [f32; 2] },
+    /// A position that is NaN or infinite
+    InvalidPosition { vertex: usize },
+    /// A face that references a vertex that doesn't exist
+    FaceIndexOutOfRange { face: usize },
+    /// A face with repeated vertices or zero area
+    DegenerateFace { face: usize },
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintIssue::MissingBone { hash } => write!(f, "bone hash {:08X} is not in the rig", hash),
+            LintIssue::BoneIndexOutOfRange { vertex, index } => write!(f, "vertex {} uses bone index {} which has no bone hash", vertex, index),
+            LintIssue::UnnormalizedWeights { vertex, total } => write!(f, "vertex {} weights add up to {:.3}", vertex, total),
+            LintIssue::UvOutOfRange { vertex, channel, uv } => write!(f, "vertex {} UV{} ({:.3}, {:.3}) is outside 0-1", vertex, channel, uv[0], uv[1]),
+            LintIssue::InvalidPosition { vertex } => write!(f, "vertex {} has a NaN or infinite position", vertex),
+            LintIssue::FaceIndexOutOfRange { face } => write!(f, "face {} references a missing vertex", face),
+            LintIssue::DegenerateFace { face } => write!(f, "face {} is degenerate", face),
+        }
+    }
+}
+
+const WEIGHT_TOLERANCE: f32 = 0.02;
+const UV_TOLERANCE: f32 = 0.001;
+
+/// Checks a GEOM for the usual causes of exploding or invisible CC: bones missing from the rig,
+/// bad weights, out of range UVs and degenerate faces. Bone hashes are only checked when a rig is given.
+pub fn lint_geom(geom: &GeomResource, rig: Option<&[RigBone]>) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let hashes = &geom.bone_hashes.hashes;
+
+    if let Some(rig) = rig {
+        let rig_hashes: HashSet<u32> = rig.iter().map(|b| b.hash).collect();
+        for hash in hashes {
+            if !rig_hashes.contains(hash) {
+                issues.push(LintIssue::MissingBone { hash: *hash });
+            }
+        }
+    }
+
+    for (vertex, assignment) in geom.bone_assignments().iter().enumerate() {
+        let mut total = 0.0;
+        for (index, weight) in assignment.bones.iter().zip(assignment.weights) {
+            if weight > 0.0 && *index as usize >= hashes.len() {
+                issues.push(LintIssue::BoneIndexOutOfRange { vertex, index: *index });
+            }
+            total += weight;
+        }
+        if (total - 1.0).abs() > WEIGHT_TOLERANCE {
+            issues.push(LintIssue::UnnormalizedWeights { vertex, total });
+        }
+    }
+
+    for channel in 0..geom.uv_channel_count() {
+        for (vertex, uv) in geom.uvs(channel).iter().enumerate() {
+            let in_range = |v: f32| (-UV_TOLERANCE..=1.0 + UV_TOLERANCE).contains(&v);
+            if !in_range(uv[0]) || !in_range(uv[1]) {
+                issues.push(LintIssue::UvOutOfRange { vertex, channel, uv: *uv });
+            }
+        }
+    }
+
+    let positions = geom.positions();
+    for (vertex, p) in positions.iter().enumerate() {
+        if p.iter().any(|v| !v.is_finite()) {
+            issues.push(LintIssue::InvalidPosition { vertex });
+        }
+    }
+
+    for (face, indices) in geom.faces.faces.iter().enumerate() {
+        let Some(corners) = indices.iter().map(|&i| positions.get(i as usize)).collect::<Option<Vec<_>>>() else {
+            issues.push(LintIssue::FaceIndexOutOfRange { face });
+            continue;
+        };
+        let repeated = indices[0] == indices[1] || indices[1] == indices[2] || indices[0] == indices[2];
+        if repeated || triangle_area(corners[0], corners[1], corners[2]) <= f32::EPSILON {
+            issues.push(LintIssue::DegenerateFace { face });
+        }
+    }
+
+    issues
+}
+
+fn triangle_area(a: &[f32; 3], b: &[f32; 3], c: &[f32; 3]) -> f32 {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let cross = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt() / 2.0
+}
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use crate::game::GameIndex;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::package::resource::TypedResource;
+use crate::preview::{COBJ_TYPE, OBJD_TYPE};
+
+/// Resource type of object tuning, which an OBJD's tuning ID points at
+pub const OBJECT_TUNING_TYPE: u32 = 0xB61DE6B4;
+
+/// A problem found by [`check_objects`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectIssue {
+    /// The OBJD or its catalog entry can't be parsed
+    Unreadable { tgi: TGI, error: String },
+    /// The OBJD has no tuning ID, so the game can't create the object
+    NoTuningId,
+    /// No object tuning with the OBJD's tuning ID, in the package or the game
+    MissingTuning { tuning_id: u64 },
+    /// A model, rig, slot or footprint the OBJD lists that isn't in the package or the game
+    MissingReference { property: &'static str, tgi: TGI },
+    /// The OBJD lists no models, so there's nothing to draw
+    NoModels,
+    /// No catalog entry with the OBJD's instance, so the object doesn't show up in Build/Buy
+    NoCatalogEntry,
+    /// The catalog entry's name hash is 0
+    NoName,
+    /// A catalog name or description hash that no string table defines
+    UnresolvedString { field: &'static str, hash: u32 },
+}
+
+impl std::fmt::Display for ObjectIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectIssue::Unreadable { tgi, error } => write!(f, "{} can't be read: {}", tgi, error),
+            ObjectIssue::NoTuningId => write!(f, "the OBJD has no tuning ID; set it to the instance of the object's tuning"),
+            ObjectIssue::MissingTuning { tuning_id } => write!(
+                f, "tuning ID {:016X} has no object tuning ({:08X}:00000000:{:016X}) in the package or the game",
+                tuning_id, OBJECT_TUNING_TYPE, tuning_id,
+            ),
+            ObjectIssue::MissingReference { property, tgi } => write!(
+                f, "{} {} is not in the package or the game; add it or fix the OBJD's {} list", property, tgi, property,
+            ),
+            ObjectIssue::NoModels => write!(f, "the OBJD lists no models"),
+            ObjectIssue::NoCatalogEntry => write!(
+                f, "no catalog entry ({:08X}) with the same instance, so it won't show in Build/Buy", COBJ_TYPE,
+            ),
+            ObjectIssue::NoName => write!(f, "the catalog entry has no name hash"),
+            ObjectIssue::UnresolvedString { field, hash } => write!(
+                f, "catalog {} hash 0x{:08X} is not in any string table; add it to the package's STBL", field, hash,
+            ),
+        }
+    }
+}
+
+/// The result of checking one object definition
+#[derive(Debug, Clone)]
+pub struct ObjectCheck {
+    pub objd: TGI,
+    pub name: Option<String>,
+    pub issues: Vec<ObjectIssue>,
+}
+
+/// What's known to exist outside the package being checked
+#[derive(Debug, Default)]
+pub struct KnownResources<'a> {
+    /// The game's resources; without it, references only resolve within the package
+    pub game: Option<&'a GameIndex>,
+    /// String keys defined elsewhere, e.g. from [`GameIndex::string_keys`]
+    pub string_keys: HashSet<u32>,
+}
+
+/// Checks every object definition in `pkg`: that its tuning, models, rig, slots and footprints
+/// exist in the package or the game, and that its catalog entry's name and description
+/// resolve in a string table. Objects come back in index order, including ones with no issues.
+pub fn check_objects(pkg: &Package, known: &KnownResources) -> Vec<ObjectCheck> {
+    let tgis: HashSet<TGI> = pkg.entries.iter().map(|e| e.tgi).collect();
+    let exists = |tgi: &TGI| tgis.contains(tgi) || known.game.is_some_and(|game| game.contains(tgi));
+    let mut string_keys: Option<HashSet<u32>> = None;
+
+    let mut checks = Vec::new();
+    for entry in pkg.entries.iter().filter(|e| e.tgi.res_type == OBJD_TYPE) {
+        let mut check = ObjectCheck { objd: entry.tgi, name: None, issues: Vec::new() };
+        let objd = match pkg.read_resource(entry) {
+            Ok(TypedResource::ObjectDefinition(objd)) => objd,
+            Ok(_) => continue,
+            Err(e) => {
+                check.issues.push(ObjectIssue::Unreadable { tgi: entry.tgi, error: e.to_string() });
+                checks.push(check);
+                continue;
+            }
+        };
+        check.name = objd.name().map(str::to_string);
+
+        match objd.tuning_id() {
+            None | Some(0) => check.issues.push(ObjectIssue::NoTuningId),
+            Some(tuning_id) => {
+                if !exists(&TGI { res_type: OBJECT_TUNING_TYPE, res_group: 0, instance: tuning_id }) {
+                    check.issues.push(ObjectIssue::MissingTuning { tuning_id });
+                }
+            }
+        }
+
+        if objd.models().is_none_or(|models| models.is_empty()) {
+            check.issues.push(ObjectIssue::NoModels);
+        }
+        let references = [
+            ("model", objd.models()),
+            ("rig", objd.rig()),
+            ("slot", objd.slots()),
+            ("footprint", objd.footprints()),
+        ];
+        for (property, list) in references {
+            for tgi in list.unwrap_or_default() {
+                // Unused slots are left zeroed
+                if tgi.instance != 0 && !exists(tgi) {
+                    check.issues.push(ObjectIssue::MissingReference { property, tgi: *tgi });
+                }
+            }
+        }
+
+        let cobj = pkg.entries.iter().find(|e| e.tgi.res_type == COBJ_TYPE && e.tgi.instance == entry.tgi.instance);
+        match cobj.map(|e| (e.tgi, pkg.read_resource(e))) {
+            None => check.issues.push(ObjectIssue::NoCatalogEntry),
+            Some((tgi, Err(e))) => check.issues.push(ObjectIssue::Unreadable { tgi, error: e.to_string() }),
+            Some((_, Ok(TypedResource::Catalog(catalog)))) => {
+                let keys = string_keys.get_or_insert_with(|| crate::stbl::string_keys(pkg));
+                let resolves = |hash: u32| keys.contains(&hash) || known.string_keys.contains(&hash);
+                if catalog.common.name_hash == 0 {
+                    check.issues.push(ObjectIssue::NoName);
+                } else if !resolves(catalog.common.name_hash) {
+                    check.issues.push(ObjectIssue::UnresolvedString { field: "name", hash: catalog.common.name_hash });
+                }
+                let description = catalog.common.description_hash;
+                if description != 0 && !resolves(description) {
+                    check.issues.push(ObjectIssue::UnresolvedString { field: "description", hash: description });
+                }
+            }
+            Some((_, Ok(_))) => {}
+        }
+
+        checks.push(check);
+    }
+    checks
+}
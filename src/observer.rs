@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use log::{log, warn, Level};
+
+/// Follows a long-running operation (a merge, un-merge, extraction, scan or verification) as it
+/// goes, so a frontend can show progress and problems without parsing log output.
+///
+/// Every method has an empty default, so an observer only implements what it shows. Operations
+/// call the observer from their worker threads, in no particular order between items. `()`
+/// observes nothing, and [`LogObserver`] writes everything to the log.
+pub trait OperationObserver: Sync {
+    /// `operation` is starting on `total` items, where it knows how many there will be
+    fn on_start(&self, _operation: &str, _total: Option<usize>) {}
+    /// `done` items are finished, the latest being `item`
+    fn on_progress(&self, _done: usize, _total: Option<usize>, _item: &str) {}
+    /// `item` was left out, e.g. a package that wouldn't open; it still counts as done
+    fn on_item_skipped(&self, _item: &str, _reason: &str) {}
+    /// Something the user should know about that doesn't stop the operation
+    fn on_warning(&self, _message: &str) {}
+    /// `operation` is over, whether it succeeded or returned an error
+    fn on_finish(&self, _operation: &str, _success: bool) {}
+}
+
+impl OperationObserver for () {}
+
+/// Writes what an operation reports to the log: skipped items and warnings as warnings, and
+/// progress at `progress_level`
+#[derive(Debug, Clone, Copy)]
+pub struct LogObserver {
+    pub progress_level: Level,
+}
+
+impl Default for LogObserver {
+    fn default() -> Self {
+        Self { progress_level: Level::Debug }
+    }
+}
+
+impl OperationObserver for LogObserver {
+    fn on_start(&self, operation: &str, total: Option<usize>) {
+        match total {
+            Some(total) => log!(self.progress_level, "Starting {} of {} item(s)", operation, total),
+            None => log!(self.progress_level, "Starting {}", operation),
+        }
+    }
+
+    fn on_progress(&self, done: usize, total: Option<usize>, item: &str) {
+        match total {
+            Some(total) => log!(self.progress_level, "[{}/{}] {}", done, total, item),
+            None => log!(self.progress_level, "[{}] {}", done, item),
+        }
+    }
+
+    fn on_item_skipped(&self, item: &str, reason: &str) {
+        warn!("Skipped {}: {}", item, reason);
+    }
+
+    fn on_warning(&self, message: &str) {
+        warn!("{}", message);
+    }
+
+    fn on_finish(&self, operation: &str, success: bool) {
+        log!(self.progress_level, "Finished {}{}", operation, if success { "" } else { " with an error" });
+    }
+}
+
+/// Counts the items of one operation for its observer, from any number of threads
+pub struct Progress<'a> {
+    observer: &'a dyn OperationObserver,
+    operation: &'static str,
+    total: Option<usize>,
+    done: AtomicUsize,
+}
+
+impl<'a> Progress<'a> {
+    /// Tells `observer` that `operation` is starting
+    pub fn start(observer: &'a dyn OperationObserver, operation: &'static str, total: Option<usize>) -> Self {
+        observer.on_start(operation, total);
+        Self { observer, operation, total, done: AtomicUsize::new(0) }
+    }
+
+    /// `item` is finished
+    pub fn advance(&self, item: &str) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.observer.on_progress(done, self.total, item);
+    }
+
+    /// `item` was left out because of `reason`
+    pub fn skip(&self, item: &str, reason: &str) {
+        self.observer.on_item_skipped(item, reason);
+        self.advance(item);
+    }
+
+    pub fn warn(&self, message: &str) {
+        self.observer.on_warning(message);
+    }
+
+    /// Tells the observer the operation is over
+    pub fn finish(self, success: bool) {
+        self.observer.on_finish(self.operation, success);
+    }
+}
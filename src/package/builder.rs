@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use crate::instance::InstanceAllocator;
+use rayon::prelude::*;
+use crate::package::Package;
+use crate::package::header::PackageHeader;
+use crate::package::index::{CompressionScheme, IndexEntry, TGI};
+use crate::package::resource::{GenericResource, TypedResource};
+use crate::package::write::WriteOptions;
+
+/// Builds a new package from nothing, one resource at a time.
+///
+/// Resources are written in the order they were added; adding a TGI a second time replaces its
+/// data in place. Each resource is stored uncompressed or zlib-compressed, per
+/// [`PackageBuilder::compression`] unless it was added with its own scheme. Zlib is only used
+/// where it makes the resource smaller.
+///
+/// ```no_run
+/// use s4pi_reforged::{CompressionScheme, TGI};
+/// use s4pi_reforged::package::builder::PackageBuilder;
+///
+/// let tuning = TGI { res_type: 0x0333406C, res_group: 0, instance: 0x1234 };
+/// PackageBuilder::new()
+///     .add(tuning, b"<I n=\"my_tuning\" />".to_vec())
+///     .add_with(TGI { instance: 0x5678, ..tuning }, Vec::new(), CompressionScheme::Uncompressed)
+///     .write("my_mod.package")?;
+/// # Ok::<(), s4pi_reforged::Error>(())
+/// ```
+pub struct PackageBuilder {
+    header: PackageHeader,
+    compression: CompressionScheme,
+    /// Decompressed data, the scheme to store it with and its committed flag
+    resources: Vec<(TGI, Vec<u8>, CompressionScheme, u16)>,
+    /// Where each TGI is in `resources`, so adding one doesn't search the whole list
+    positions: HashMap<TGI, usize>,
+}
+
+impl Default for PackageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackageBuilder {
+    /// An empty version 2.1 package whose resources are zlib-compressed
+    pub fn new() -> Self {
+        Self { header: PackageHeader::new(), compression: CompressionScheme::Zlib, resources: Vec::new(), positions: HashMap::new() }
+    }
+
+    /// Sets the scheme for resources added with [`PackageBuilder::add`] and
+    /// [`PackageBuilder::add_typed`]: [`CompressionScheme::Zlib`] or
+    /// [`CompressionScheme::Uncompressed`]
+    pub fn compression(&mut self, scheme: CompressionScheme) -> &mut Self {
+        self.compression = scheme;
+        self
+    }
+
+    /// Sets the header's creation time, in seconds since the Unix epoch
+    pub fn created(&mut self, timestamp: u32) -> &mut Self {
+        self.header.created = timestamp;
+        self
+    }
+
+    /// Sets the header's modification time, in seconds since the Unix epoch
+    pub fn modified(&mut self, timestamp: u32) -> &mut Self {
+        self.header.modified = timestamp;
+        self
+    }
+
+    /// Sets the user version in the header, which the game ignores but some tools use to tag
+    /// their packages
+    pub fn user_version(&mut self, major: u32, minor: u32) -> &mut Self {
+        self.header.unused1 = major;
+        self.header.unused2 = minor;
+        self
+    }
+
+    /// Adds a resource from its decompressed bytes
+    pub fn add(&mut self, tgi: TGI, data: Vec<u8>) -> &mut Self {
+        let scheme = self.compression;
+        self.add_with(tgi, data, scheme)
+    }
+
+    /// Adds a resource from its decompressed bytes, to be stored with `scheme`
+    pub fn add_with(&mut self, tgi: TGI, data: Vec<u8>, scheme: CompressionScheme) -> &mut Self {
+        self.put(tgi, data, scheme, 1)
+    }
+
+    /// Adds a parsed resource, written back out with its wrapper. A generic resource read from a
+    /// package keeps its source entry's committed flag, and is only compressed if it was before.
+    pub fn add_typed(&mut self, tgi: TGI, resource: &TypedResource) -> Result<&mut Self> {
+        let data = resource.to_bytes().with_context(|| format!("Failed to write resource {}", tgi))?;
+        Ok(match resource {
+            TypedResource::Generic(GenericResource { source: Some(entry), .. }) => {
+                let scheme = match entry.scheme() {
+                    CompressionScheme::Uncompressed => CompressionScheme::Uncompressed,
+                    _ => CompressionScheme::Zlib,
+                };
+                self.put(tgi, data, scheme, entry.committed)
+            }
+            _ => self.add(tgi, data),
+        })
+    }
+
+    fn put(&mut self, tgi: TGI, data: Vec<u8>, scheme: CompressionScheme, committed: u16) -> &mut Self {
+        match self.positions.get(&tgi) {
+            Some(&position) => self.resources[position] = (tgi, data, scheme, committed),
+            None => {
+                self.positions.insert(tgi, self.resources.len());
+                self.resources.push((tgi, data, scheme, committed));
+            }
+        }
+        self
+    }
+
+    /// Takes a resource back out, returning its data if it was added
+    pub fn remove(&mut self, tgi: &TGI) -> Option<Vec<u8>> {
+        let position = self.positions.remove(tgi)?;
+        let (_, data, ..) = self.resources.remove(position);
+        for (tgi, ..) in &self.resources[position..] {
+            *self.positions.get_mut(tgi).expect("every added TGI has a position") -= 1;
+        }
+        Some(data)
+    }
+
+    /// An [`InstanceAllocator`] that avoids the instances added so far, for keys of new resources
+    pub fn allocator(&self) -> InstanceAllocator {
+        let mut allocator = InstanceAllocator::new();
+        for (tgi, ..) in &self.resources {
+            allocator.reserve(tgi.instance);
+        }
+        allocator
+    }
+
+    /// Number of resources added so far
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// The resources in their stored form, compressed in parallel
+    fn stored(&self) -> Result<Vec<(IndexEntry, Vec<u8>)>> {
+        self.resources
+            .par_iter()
+            .map(|(tgi, data, scheme, committed)| crate::perf::busy(|| {
+                let compression = match scheme {
+                    CompressionScheme::Uncompressed => 0,
+                    CompressionScheme::Zlib => 0x5A42,
+                    other => return Err(Error::InvalidInput(format!("Can't store resource {} as {}", tgi, other))),
+                };
+                let entry = IndexEntry { tgi: *tgi, offset: 0, filesize: 0, memsize: 0, compression: 0, committed: *committed };
+                super::stored_form(entry, data, compression, &WriteOptions::default())
+            }))
+            .collect()
+    }
+
+    /// The package file's bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Cursor::new(Vec::new());
+        super::write_package(&mut out, self.header.clone(), &self.stored()?)?;
+        Ok(out.into_inner())
+    }
+
+    /// Writes the package to `path`, replacing any file there
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let stored = self.stored()?;
+        let mut file = crate::atomic::AtomicFile::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+        super::write_package(&mut file, self.header.clone(), &stored)?;
+        file.commit().with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(())
+    }
+
+    /// The package, held in memory, ready to read back or to [`Package::save_as`]
+    pub fn build(&self) -> Result<Package> {
+        Package::from_reader(Cursor::new(self.to_bytes()?))
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::error::{Context, Result};
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::ResourceChecksum;
+
+/// A resource in both packages whose decompressed data differs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceChange {
+    pub tgi: TGI,
+    pub before: ResourceChecksum,
+    pub after: ResourceChecksum,
+}
+
+/// What differs between two packages, from [`Package::diff`]. Keys are sorted by type, group
+/// and instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDiff {
+    /// Resources only in the first package
+    pub removed: Vec<TGI>,
+    /// Resources only in the second package
+    pub added: Vec<TGI>,
+    pub changed: Vec<ResourceChange>,
+    /// Resources in both packages with the same data
+    pub unchanged: usize,
+}
+
+impl PackageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty()
+    }
+
+    /// The diff as a JSON object: `only_in_a` and `only_in_b` list keys as `T:G:I`, `changed`
+    /// lists each key with its size and hash in both packages, and `unchanged` is a count
+    pub fn to_json(&self) -> String {
+        let checksum = |c: &ResourceChecksum| serde_json::json!({ "size": c.size, "hash": format!("{:016X}", c.hash) });
+        serde_json::json!({
+            "only_in_a": self.removed.iter().map(TGI::to_string).collect::<Vec<_>>(),
+            "only_in_b": self.added.iter().map(TGI::to_string).collect::<Vec<_>>(),
+            "changed": self.changed.iter().map(|c| serde_json::json!({
+                "tgi": c.tgi.to_string(),
+                "a": checksum(&c.before),
+                "b": checksum(&c.after),
+            })).collect::<Vec<_>>(),
+            "unchanged": self.unchanged,
+        }).to_string()
+    }
+}
+
+fn sort_key(tgi: &TGI) -> (u32, u32, u64) {
+    (tgi.res_type, tgi.res_group, tgi.instance)
+}
+
+impl Package {
+    /// Compares this package with `other`, e.g. an old and a new version of some CC.
+    ///
+    /// Resources in both are compared by their decompressed data, so one that was only
+    /// recompressed counts as unchanged. Resources stored byte-for-byte the same aren't
+    /// decompressed at all.
+    pub fn diff(&self, other: &Package) -> Result<PackageDiff> {
+        let theirs: HashMap<TGI, &IndexEntry> = other.entries.iter().map(|e| (e.tgi, e)).collect();
+        let ours: HashMap<TGI, &IndexEntry> = self.entries.iter().map(|e| (e.tgi, e)).collect();
+
+        let mut diff = PackageDiff {
+            removed: ours.keys().filter(|tgi| !theirs.contains_key(tgi)).copied().collect(),
+            added: theirs.keys().filter(|tgi| !ours.contains_key(tgi)).copied().collect(),
+            ..Default::default()
+        };
+
+        let common: Vec<(&IndexEntry, &IndexEntry)> = ours.iter()
+            .filter_map(|(tgi, ours)| theirs.get(tgi).map(|theirs| (*ours, *theirs)))
+            .collect();
+        let compared: Vec<Option<ResourceChange>> = common.par_iter()
+            .map(|(ours, theirs)| -> Result<Option<ResourceChange>> {
+                if ours.filesize == theirs.filesize && ours.compression == theirs.compression
+                    && self.read_stored_resource(ours)? == other.read_stored_resource(theirs)? {
+                    return Ok(None);
+                }
+                let before = ResourceChecksum::of(&self.read_raw_resource(ours).with_context(|| format!("Failed to read {}", ours.tgi))?);
+                let after = ResourceChecksum::of(&other.read_raw_resource(theirs).with_context(|| format!("Failed to read {}", theirs.tgi))?);
+                Ok((before != after).then_some(ResourceChange { tgi: ours.tgi, before, after }))
+            })
+            .collect::<Result<_>>()?;
+        diff.unchanged = compared.iter().filter(|c| c.is_none()).count();
+        diff.changed = compared.into_iter().flatten().collect();
+
+        diff.removed.sort_by_key(sort_key);
+        diff.added.sort_by_key(sort_key);
+        diff.changed.sort_by_key(|c| sort_key(&c.tgi));
+        Ok(diff)
+    }
+}
@@ -1,7 +1,8 @@
 use binrw::binrw;
+use crate::package::index::IndexEntry;
 
 #[binrw]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 #[br(little)]
 #[bw(little)]
 pub struct PackageHeader {
@@ -16,21 +17,134 @@ pub struct PackageHeader {
     pub modified: u32,
     pub index_version: u32,  // Usually 3
     pub index_count: u32,
-    pub index_size_total_deprecated: u32, // Not used in TS4 but placeholder for alignment
-    pub unused4: u32,
+    pub index_position_low: u32, // 32-bit index offset from older writers; TS4 reads `index_position`
     pub index_size: u32,
-    pub unused5: [u32; 3],
+    pub unused4: u32,
+    pub unused5: [u32; 3],   // The last is the index minor version (3)
     pub index_position: u64, // TS4 uses 64-bit offsets
     pub unused6: [u32; 6],
 }
 
+/// Smallest possible index record: instance low, offset, sizes, compression and committed,
+/// with type, group and instance high shared in the index header
+const MIN_INDEX_RECORD: u64 = 20;
+/// An index record with no shared fields, as [`crate::Package::write_merged`] writes them
+const FULL_INDEX_RECORD: u32 = 32;
+
+/// Where the index is and how big it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexLocation {
+    pub position: u64,
+    pub size: u32,
+}
+
+/// Something wrong with a header, found by [`PackageHeader::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderIssue {
+    BadMagic([u8; 4]),
+    UnsupportedVersion { major: u32, minor: u32 },
+    /// The index starts or ends past the end of the file
+    IndexOutOfBounds { position: u64, size: u32, file_len: u64 },
+    /// More entries than could fit in the index
+    EntryCountTooLarge { count: u32, index_size: u32 },
+    /// The 32-bit and 64-bit index positions disagree
+    PositionMismatch { position: u64, legacy: u32 },
+}
+
+impl std::fmt::Display for HeaderIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderIssue::BadMagic(magic) => write!(f, "bad magic {:?} (expected \"DBPF\")", String::from_utf8_lossy(magic)),
+            HeaderIssue::UnsupportedVersion { major, minor } => write!(f, "unsupported version {}.{}", major, minor),
+            HeaderIssue::IndexOutOfBounds { position, size, file_len } => write!(
+                f, "index at 0x{:X} ({} bytes) runs past the end of the file ({} bytes)", position, size, file_len,
+            ),
+            HeaderIssue::EntryCountTooLarge { count, index_size } => write!(
+                f, "{} entries can't fit in a {} byte index", count, index_size,
+            ),
+            HeaderIssue::PositionMismatch { position, legacy } => write!(
+                f, "index position 0x{:X} doesn't match the 32-bit position 0x{:X}", position, legacy,
+            ),
+        }
+    }
+}
+
 impl PackageHeader {
     pub const SIZE: u64 = 96;
 
+    /// A version 2.1 header with an empty index, as written for new packages
+    pub fn new() -> Self {
+        Self {
+            magic: *b"DBPF",
+            major: 2,
+            minor: 1,
+            unused5: [0, 0, 3],
+            ..Default::default()
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         &self.magic == b"DBPF" && self.major == 2
     }
 
+    /// Number of entries in the index
+    pub fn entry_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn set_entry_count(&mut self, count: u32) {
+        self.index_count = count;
+    }
+
+    /// Where the index is. Falls back to the 32-bit position for packages that only set that.
+    pub fn index_location(&self) -> IndexLocation {
+        let position = if self.index_position != 0 { self.index_position } else { self.index_position_low as u64 };
+        IndexLocation { position, size: self.index_size }
+    }
+
+    /// Sets the index position and size, keeping the 32-bit position in step
+    /// (it's left at 0 when the index is beyond 4 GiB)
+    pub fn set_index_location(&mut self, location: IndexLocation) {
+        self.index_position = location.position;
+        self.index_position_low = u32::try_from(location.position).unwrap_or(0);
+        self.index_size = location.size;
+    }
+
+    /// Checks the header against itself and the length of the file it came from
+    pub fn validate(&self, file_len: u64) -> Vec<HeaderIssue> {
+        let mut issues = Vec::new();
+        if &self.magic != b"DBPF" {
+            issues.push(HeaderIssue::BadMagic(self.magic));
+        }
+        if self.major != 2 {
+            issues.push(HeaderIssue::UnsupportedVersion { major: self.major, minor: self.minor });
+        }
+
+        let location = self.index_location();
+        // The index always has at least its 4-byte flags field
+        let end = location.position + (location.size as u64).max(4);
+        if location.position < Self::SIZE || end > file_len {
+            issues.push(HeaderIssue::IndexOutOfBounds { position: location.position, size: location.size, file_len });
+        }
+        if location.size != 0 && 4 + self.index_count as u64 * MIN_INDEX_RECORD > location.size as u64 {
+            issues.push(HeaderIssue::EntryCountTooLarge { count: self.index_count, index_size: location.size });
+        }
+        if self.index_position != 0 && self.index_position_low != 0 && self.index_position != self.index_position_low as u64 {
+            issues.push(HeaderIssue::PositionMismatch { position: self.index_position, legacy: self.index_position_low });
+        }
+        issues
+    }
+
+    /// Rebuilds the values derived from the index (entry count, index size and the 32-bit
+    /// position) from the entries actually read from it.
+    ///
+    /// The size is that of an index with no shared fields, which is how this crate writes them.
+    pub fn repair_from(&mut self, entries: &[IndexEntry]) {
+        self.set_entry_count(entries.len() as u32);
+        let position = self.index_location().position;
+        self.set_index_location(IndexLocation { position, size: 4 + entries.len() as u32 * FULL_INDEX_RECORD });
+    }
+
     pub fn read<R: std::io::Read + std::io::Seek>(reader: &mut R) -> Result<Self, binrw::Error> {
         use binrw::BinReaderExt;
         reader.read_le()
@@ -1,4 +1,5 @@
 use binrw::binrw;
+use crate::error::Error;
 
 #[binrw]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -10,6 +11,30 @@ pub struct TGI {
     pub instance: u64,
 }
 
+impl std::fmt::Display for TGI {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08X}:{:08X}:{:016X}", self.res_type, self.res_group, self.instance)
+    }
+}
+
+impl std::str::FromStr for TGI {
+    type Err = Error;
+
+    /// Parses `T:G:I`, also accepting `!`, `-` or `_` as separators and an optional `0x` on each part
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split([':', '!', '-', '_']).collect();
+        let [res_type, res_group, instance] = parts[..] else {
+            return Err(Error::InvalidInput(format!("Invalid TGI '{}': expected TYPE:GROUP:INSTANCE in hex", s)));
+        };
+        let hex = |part: &str| part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")).unwrap_or(part).to_string();
+        Ok(TGI {
+            res_type: u32::from_str_radix(&hex(res_type), 16).map_err(|e| Error::InvalidInput(format!("Invalid type in TGI '{}': {}", s, e)))?,
+            res_group: u32::from_str_radix(&hex(res_group), 16).map_err(|e| Error::InvalidInput(format!("Invalid group in TGI '{}': {}", s, e)))?,
+            instance: u64::from_str_radix(&hex(instance), 16).map_err(|e| Error::InvalidInput(format!("Invalid instance in TGI '{}': {}", s, e)))?,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexEntry {
     pub tgi: TGI,
@@ -24,4 +49,140 @@ impl IndexEntry {
     pub fn is_compressed(&self) -> bool {
         self.compression != 0
     }
+
+    pub fn scheme(&self) -> CompressionScheme {
+        CompressionScheme::from_code(self.compression)
+    }
+}
+
+/// Where a resource goes in the index of a package the game or Sims 4 Studio writes: by type,
+/// then group, then instance, each compared as an unsigned number
+pub fn game_order(tgi: &TGI) -> (u32, u32, u64) {
+    (tgi.res_type, tgi.res_group, tgi.instance)
+}
+
+/// Puts `entries` in the order the game's own packages list their resources in, and Sims 4
+/// Studio writes them in (see [`game_order`]), so packages written from them can be compared
+/// byte for byte with other tools' output. No resource type is moved ahead of the others, the
+/// merge manifest included. Entries with the same TGI keep the order they were in.
+pub fn sort_entries_for_game(entries: &mut [IndexEntry]) {
+    entries.sort_by_key(|entry| game_order(&entry.tgi));
+}
+
+/// The compression field of an index entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CompressionScheme {
+    Uncompressed,
+    Zlib,
+    RefPack,
+    Streamable,
+    Deleted,
+    Unknown(u16),
+}
+
+impl CompressionScheme {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            0x0000 => CompressionScheme::Uncompressed,
+            0x5A42 => CompressionScheme::Zlib,
+            0xFFFF => CompressionScheme::RefPack,
+            0xFFFE => CompressionScheme::Streamable,
+            0xFFE0 => CompressionScheme::Deleted,
+            other => CompressionScheme::Unknown(other),
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionScheme::Uncompressed => write!(f, "uncompressed"),
+            CompressionScheme::Zlib => write!(f, "zlib"),
+            CompressionScheme::RefPack => write!(f, "refpack"),
+            CompressionScheme::Streamable => write!(f, "streamable"),
+            CompressionScheme::Deleted => write!(f, "deleted"),
+            CompressionScheme::Unknown(code) => write!(f, "unknown (0x{:04X})", code),
+        }
+    }
+}
+
+/// Picks resources by type, group, instance or full key, as given on the command line with
+/// `--type`, `--group`, `--instance` and `--tgi`.
+///
+/// Each field matches any of its values; a resource has to match every field that has values,
+/// so `--type A --type B --group C` means "type A or B, in group C". An empty filter matches
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TgiFilter {
+    pub types: Vec<u32>,
+    pub groups: Vec<u32>,
+    pub instances: Vec<u64>,
+    pub keys: Vec<TGI>,
+}
+
+impl TgiFilter {
+    /// The options [`TgiFilter::from_args`] reads, each followed by a value
+    pub const FLAGS: [&'static str; 4] = ["--type", "--group", "--instance", "--tgi"];
+
+    /// Reads the filter options out of `args`, returning the filter and the arguments that
+    /// aren't part of it. Values can be hex (`0x034AE111`) or decimal, and several can be given
+    /// at once separated by commas; `--tgi` takes a key in [`TGI`]'s text form.
+    pub fn from_args<S: AsRef<str>>(args: &[S]) -> Result<(Self, Vec<String>), Error> {
+        let mut filter = TgiFilter::default();
+        let mut rest = Vec::new();
+        let mut args = args.iter().map(AsRef::as_ref);
+        while let Some(arg) = args.next() {
+            if !Self::FLAGS.contains(&arg) {
+                rest.push(arg.to_string());
+                continue;
+            }
+            let value = args.next()
+                .ok_or_else(|| Error::InvalidInput(format!("{} needs a value, such as 0x034AE111", arg)))?;
+            for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                match arg {
+                    "--tgi" => filter.keys.push(part.parse()?),
+                    "--instance" => filter.instances.push(parse_id(part)?),
+                    flag => {
+                        let id = u32::try_from(parse_id(part)?)
+                            .map_err(|_| Error::InvalidInput(format!("{} value '{}' doesn't fit in 32 bits", flag, part)))?;
+                        if flag == "--type" { filter.types.push(id) } else { filter.groups.push(id) }
+                    }
+                }
+            }
+        }
+        Ok((filter, rest))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.groups.is_empty() && self.instances.is_empty() && self.keys.is_empty()
+    }
+
+    pub fn matches(&self, tgi: &TGI) -> bool {
+        (self.types.is_empty() || self.types.contains(&tgi.res_type))
+            && (self.groups.is_empty() || self.groups.contains(&tgi.res_group))
+            && (self.instances.is_empty() || self.instances.contains(&tgi.instance))
+            && (self.keys.is_empty() || self.keys.contains(tgi))
+    }
+}
+
+/// Parses a resource ID: hex with a `0x` prefix, otherwise decimal. Hex without the prefix is
+/// still read as hex when it has a letter in it, e.g. `034AE111` copied from a key, or when it
+/// is zero-padded to a type or instance's 8 or 16 digits, e.g. `01661233`. Other zero-padded
+/// numbers could be either, so they need the prefix.
+pub fn parse_id(value: &str) -> Result<u64, Error> {
+    let value = value.trim();
+    let all_digits = value.bytes().all(|b| b.is_ascii_digit());
+    let padded = value.len() > 1 && value.starts_with('0');
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None if all_digits && padded && matches!(value.len(), 8 | 16) => u64::from_str_radix(value, 16),
+        None if all_digits && padded => {
+            return Err(Error::InvalidInput(format!(
+                "Ambiguous ID '{}': write 0x{} for hex, or drop the leading zeros for decimal", value, value
+            )));
+        }
+        None if all_digits => value.parse(),
+        None => u64::from_str_radix(value, 16),
+    };
+    parsed.map_err(|_| Error::InvalidInput(format!("Invalid ID '{}': expected hex with 0x, or decimal", value)))
 }
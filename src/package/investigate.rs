@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use crate::error::{Error, Result};
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{ManifestResource, ResourceKind, TypedResource};
+use crate::package::structure::{find_tgis, guess_structure, Region};
+use crate::package::validate::decompress;
+use crate::report::{catch_panic, REPORT_HEAD_BYTES};
+use crate::tuning::{looks_like_xml, tuning_class, tuning_instance_type};
+
+/// Bytes of data kept from one resource of each unknown or failing type
+pub const SAMPLE_BYTES: usize = 64;
+
+/// How well the resources of one type parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeStatus {
+    /// Parsed with a dedicated wrapper
+    Known,
+    /// No wrapper for the type; kept as a generic resource
+    Unknown,
+    /// At least one resource of the type failed to parse
+    Failed,
+}
+
+/// The first bytes of one resource, for eyeballing unknown formats
+#[derive(Debug, Clone)]
+pub struct ResourceSample {
+    pub entry: IndexEntry,
+    pub head: Vec<u8>,
+    /// Guessed layout of the whole resource, for types with no wrapper or that fail to parse
+    pub structure: Vec<Region>,
+}
+
+/// One resource that failed to read or parse
+#[derive(Debug, Clone)]
+pub struct ParseFailure {
+    pub entry: IndexEntry,
+    pub error: String,
+    /// The first [`REPORT_HEAD_BYTES`] of the resource, decompressed if that worked and as stored
+    /// otherwise
+    pub head: Vec<u8>,
+}
+
+/// Everything known about the resources of one type
+#[derive(Debug, Clone)]
+pub struct TypeSummary {
+    pub count: usize,
+    pub status: TypeStatus,
+    /// Parse errors, one per failing resource
+    pub errors: Vec<String>,
+    /// Sample of the first resource, for unknown and failing types and merge manifests
+    pub sample: Option<ResourceSample>,
+    /// Tuning instance type (e.g. "buff") for tuning resources, from
+    /// [`crate::tuning::TUNING_TYPES`] or else the root element of the first resource
+    pub tuning_class: Option<String>,
+}
+
+/// What [`Package::investigate`] found
+#[derive(Debug, Default)]
+pub struct InvestigationReport {
+    pub resource_count: usize,
+    pub types: BTreeMap<u32, TypeSummary>,
+    /// Merge manifests found in the package
+    pub manifests: Vec<(IndexEntry, ManifestResource)>,
+    pub failures: Vec<ParseFailure>,
+}
+
+impl InvestigationReport {
+    /// Types with no wrapper or with resources that failed to parse
+    pub fn unknown_types(&self) -> impl Iterator<Item = u32> + '_ {
+        self.types.iter()
+            .filter(|(_, summary)| summary.status != TypeStatus::Known)
+            .map(|(res_type, _)| *res_type)
+    }
+}
+
+fn head(data: &[u8], len: usize) -> Vec<u8> {
+    data[..data.len().min(len)].to_vec()
+}
+
+impl Package {
+    /// Parses every resource and reports per type how many there are and whether they parse.
+    ///
+    /// Parse errors (and panics in a wrapper) are collected rather than returned, so one bad
+    /// resource doesn't stop the rest from being checked.
+    pub fn investigate(&self) -> InvestigationReport {
+        let mut report = InvestigationReport { resource_count: self.entries.len(), ..Default::default() };
+
+        for entry in self.entries.clone() {
+            let summary = report.types.entry(entry.tgi.res_type).or_insert_with(|| TypeSummary {
+                count: 0,
+                status: TypeStatus::Known,
+                errors: Vec::new(),
+                sample: None,
+                tuning_class: tuning_class(entry.tgi.res_type).map(str::to_string),
+            });
+            summary.count += 1;
+
+            if summary.tuning_class.is_none() && summary.count == 1
+                && matches!(TypedResource::kind_of(entry.tgi.res_type), ResourceKind::Xml | ResourceKind::Text)
+            {
+                if let Ok(data) = self.read_raw_resource(&entry) {
+                    if looks_like_xml(&data) {
+                        summary.tuning_class = tuning_instance_type(&String::from_utf8_lossy(&data));
+                    }
+                }
+            }
+
+            match catch_panic(|| self.read_resource(&entry)) {
+                Ok(TypedResource::Generic(_)) => {
+                    if summary.status == TypeStatus::Known {
+                        summary.status = TypeStatus::Unknown;
+                    }
+                }
+                Ok(TypedResource::Manifest(manifest)) => report.manifests.push((entry.clone(), manifest)),
+                Ok(_) => {}
+                Err(e) => {
+                    summary.status = TypeStatus::Failed;
+                    let error = format!("{:#}", e);
+                    summary.errors.push(error.clone());
+                    let data = self.read_raw_resource(&entry)
+                        .or_else(|_| self.read_stored_resource(&entry))
+                        .unwrap_or_default();
+                    report.failures.push(ParseFailure { entry: entry.clone(), error, head: head(&data, REPORT_HEAD_BYTES) });
+                }
+            }
+        }
+
+        for (res_type, summary) in report.types.iter_mut() {
+            if summary.status == TypeStatus::Known && !crate::manifest::MANIFEST_TYPES.contains(res_type) {
+                continue;
+            }
+            if let Some(entry) = self.entries.iter().find(|e| e.tgi.res_type == *res_type).cloned() {
+                if let Ok(data) = self.read_raw_resource(&entry) {
+                    let structure = match summary.status {
+                        TypeStatus::Known => Vec::new(),
+                        _ => guess_structure(&data),
+                    };
+                    summary.sample = Some(ResourceSample { entry, head: head(&data, SAMPLE_BYTES), structure });
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Most embedded TGIs [`Package::investigate_resource`] keeps; the rest are only counted
+pub const MAX_EMBEDDED_TGIS: usize = 1000;
+/// Most blocks a resource's entropy is broken into, however big it is
+pub const MAX_ENTROPY_BLOCKS: usize = 64;
+/// Smallest block the entropy is measured over
+const MIN_ENTROPY_BLOCK: usize = 4096;
+
+/// Shannon entropy of `data` in bits per byte: near 8 for compressed or encrypted data, lower
+/// for structured data, 0 for a single repeated byte
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// The entropy of one run of a resource's data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntropyBlock {
+    pub offset: usize,
+    pub len: usize,
+    /// Bits per byte
+    pub bits: f64,
+}
+
+/// Everything [`Package::investigate_resource`] found out about one resource
+#[derive(Debug, Clone)]
+pub struct ResourceInvestigation {
+    pub entry: IndexEntry,
+    pub kind: ResourceKind,
+    /// The first four bytes, when they're printable; most formats start with a tag like `DST1`
+    pub magic: Option<String>,
+    /// Size of the data as stored; `entry.memsize` is what the index says it decompresses to
+    pub stored_len: usize,
+    /// Size the data really decompressed to, or why it didn't
+    pub decompressed: std::result::Result<usize, String>,
+    pub read_time: Duration,
+    pub decompress_time: Duration,
+    /// Time taken by the wrapper; zero for types without one or data that didn't decompress
+    pub parse_time: Duration,
+    /// Why the resource's wrapper failed to parse it
+    pub parse_error: Option<String>,
+    /// Entropy of the data as stored, in bits per byte
+    pub stored_entropy: f64,
+    /// Entropy of the decompressed data, overall and in up to [`MAX_ENTROPY_BLOCKS`] blocks, so
+    /// embedded images or compressed streams stand out from the structure around them
+    pub entropy: f64,
+    pub entropy_blocks: Vec<EntropyBlock>,
+    /// Offset, key and whether it's stored instance-type-group, for up to [`MAX_EMBEDDED_TGIS`]
+    /// resource keys found in the data
+    pub tgis: Vec<(usize, TGI, bool)>,
+    /// All the resource keys found, including those past [`MAX_EMBEDDED_TGIS`]
+    pub tgi_count: usize,
+    /// The first [`SAMPLE_BYTES`] of the decompressed data
+    pub head: Vec<u8>,
+    /// Guessed layout of the first [`MAX_GUESS_BYTES`](crate::package::structure::MAX_GUESS_BYTES)
+    pub structure: Vec<Region>,
+}
+
+impl Package {
+    /// Takes one resource apart: its index entry, how long it takes to read, decompress and
+    /// parse, the entropy of its data, the resource keys in it and a guess at its layout. Meant
+    /// for the one resource in a huge package that misbehaves.
+    ///
+    /// The data is read and decompressed once, and what's kept of it is bounded (the TGI list,
+    /// the entropy blocks and the structure guess), so even a resource of hundreds of megabytes
+    /// costs no more memory than its stored and decompressed data.
+    pub fn investigate_resource(&self, tgi: &TGI) -> Result<ResourceInvestigation> {
+        let entry = self.find(tgi).cloned().ok_or(Error::ResourceNotFound(*tgi))?;
+
+        let start = Instant::now();
+        let stored = self.read_stored_resource(&entry)?;
+        let read_time = start.elapsed();
+        let stored_len = stored.len();
+        let stored_entropy = entropy(&stored);
+
+        let start = Instant::now();
+        let decompressed = decompress(&entry, stored);
+        let decompress_time = start.elapsed();
+        let (data, decompressed) = match decompressed {
+            Ok((data, len)) => (data, Ok(len)),
+            Err(e) => (Vec::new(), Err(format!("{:#}", e))),
+        };
+
+        let kind = TypedResource::kind_of(tgi.res_type);
+        let start = Instant::now();
+        let parse_error = match (&decompressed, kind) {
+            (Ok(_), ResourceKind::Generic) | (Err(_), _) => None,
+            (Ok(_), _) => catch_panic(|| TypedResource::from_bytes(tgi.res_type, &data)).err().map(|e| format!("{:#}", e)),
+        };
+        let parse_time = if decompressed.is_ok() && kind != ResourceKind::Generic { start.elapsed() } else { Duration::ZERO };
+
+        let block_len = data.len().div_ceil(MAX_ENTROPY_BLOCKS).max(MIN_ENTROPY_BLOCK);
+        let entropy_blocks = data.chunks(block_len).enumerate()
+            .map(|(i, block)| EntropyBlock { offset: i * block_len, len: block.len(), bits: entropy(block) })
+            .collect();
+        let (tgis, tgi_count) = find_tgis(&data, MAX_EMBEDDED_TGIS);
+        let magic = data.get(..4)
+            .filter(|tag| tag.iter().all(|b| b.is_ascii_alphanumeric() || *b == b' ' || *b == b'_'))
+            .map(|tag| String::from_utf8_lossy(tag).into_owned());
+
+        Ok(ResourceInvestigation {
+            entry,
+            kind,
+            magic,
+            stored_len,
+            decompressed,
+            read_time,
+            decompress_time,
+            parse_time,
+            parse_error,
+            stored_entropy,
+            entropy: entropy(&data),
+            entropy_blocks,
+            tgis,
+            tgi_count,
+            head: head(&data, SAMPLE_BYTES),
+            structure: guess_structure(&data),
+        })
+    }
+}
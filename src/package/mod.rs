@@ -1,34 +1,75 @@
 pub mod header;
 pub mod index;
+pub mod builder;
+pub mod diff;
+pub mod investigate;
+pub mod obfuscation;
+pub mod repair;
 pub mod resource;
-
-use header::PackageHeader;
+#[cfg(feature = "tuning")]
+pub mod simdata;
+pub mod stats;
+pub mod structure;
+pub mod validate;
+pub mod write;
+
+use header::{IndexLocation, PackageHeader};
 use index::{IndexEntry, TGI};
-use resource::TypedResource;
+use resource::{GenericResource, TypedResource};
+use stats::CompressionReport;
+use write::WriteOptions;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::collections::HashMap;
 use std::fs::File;
-use std::path::Path;
-use anyhow::{Result, Context, anyhow};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::atomic::AtomicFile;
+use crate::error::{Context, Error, Result};
 use log::warn;
 use rayon::prelude::*;
 
+/// Anything a package can be read from: a file on disk, or an in-memory copy of one (e.g. a
+/// package inside an archive)
+pub trait PackageSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> PackageSource for T {}
+
+/// A DBPF package: its header and index, with resources read from the source as needed.
+///
+/// Reading takes `&self`, so one package can be shared by parallel workers instead of each
+/// opening the file again.
 pub struct Package {
     pub header: PackageHeader,
     pub entries: Vec<IndexEntry>,
-    file: Option<File>,
+    /// Locked only for the seek and read of one resource, so a package can be shared between
+    /// threads that decompress and parse in parallel
+    file: Mutex<Option<Box<dyn PackageSource>>>,
+    /// The file the package was opened from or last saved to
+    path: Option<PathBuf>,
+    /// Resources inserted since the package was opened or saved: their decompressed data, and
+    /// the compression to save them with
+    pending: HashMap<TGI, (Vec<u8>, u16)>,
+    modified: bool,
 }
 
 impl Package {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut file = File::open(path)?;
+        let mut pkg = Self::from_reader(File::open(path.as_ref())?)?;
+        pkg.path = Some(path.as_ref().to_path_buf());
+        Ok(pkg)
+    }
+
+    /// Reads a package's header and index from any seekable source. Resources are read from it
+    /// as they are needed, so it is kept for the life of the package.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(mut file: R) -> Result<Self> {
         let header = PackageHeader::read(&mut file)
-            .context("Failed to read package header")?;
+            .map_err(|e| Error::InvalidHeader(e.to_string()))?;
 
         if !header.is_valid() {
-            return Err(anyhow!("Invalid DBPF header or unsupported version"));
+            return Err(Error::InvalidHeader("not a DBPF package, or an unsupported version".to_string()));
         }
 
-        file.seek(SeekFrom::Start(header.index_position))?;
+        file.seek(SeekFrom::Start(header.index_location().position))?;
         
         // Reading index
         // The index starts with a 4-byte index type
@@ -37,9 +78,10 @@ impl Package {
         let index_type = u32::from_le_bytes(type_buf);
 
         // Sanity check for index_count to prevent excessive pre-allocation
-        let file_len = file.metadata()?.len();
+        let file_len = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(header.index_location().position + 4))?;
         if header.index_count as u64 * 20 > file_len {
-            return Err(anyhow!("Invalid package header: index_count too large for file size"));
+            return Err(Error::InvalidHeader("index_count too large for file size".to_string()));
         }
 
         let mut entries = Vec::with_capacity(header.index_count as usize);
@@ -113,18 +155,187 @@ impl Package {
         Ok(Self {
             header,
             entries,
-            file: Some(file),
+            file: Mutex::new(Some(Box::new(file))),
+            path: None,
+            pending: HashMap::new(),
+            modified: false,
         })
     }
 
-    pub fn read_raw_resource(&mut self, entry: &IndexEntry) -> Result<Vec<u8>> {
-        let file = self.file.as_mut().ok_or_else(|| anyhow!("Package file not open"))?;
-        file.seek(SeekFrom::Start(entry.offset as u64))?;
+    /// The file the package was opened from or last saved to, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// True if resources were inserted or removed since the package was opened or saved
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Adds a resource, or replaces the data of the one already there, from its decompressed
+    /// bytes. The change is kept in memory until [`Package::save`] or [`Package::save_as`].
+    ///
+    /// A replaced resource keeps its committed flag and is saved compressed if it was before;
+    /// a new one is saved zlib-compressed.
+    pub fn insert_resource(&mut self, tgi: TGI, data: Vec<u8>) {
+        self.insert_stored_as(tgi, data, None);
+    }
+
+    /// Adds or replaces a parsed resource, written back out with its wrapper. A generic resource
+    /// read from a package keeps its source entry's compression and committed flag.
+    pub fn insert_typed(&mut self, tgi: TGI, resource: &TypedResource) -> Result<()> {
+        let data = resource.to_bytes().with_context(|| format!("Failed to write resource {}", tgi))?;
+        let stored_as = match resource {
+            TypedResource::Generic(generic) => generic.source.as_ref().map(|e| (e.compression, e.committed)),
+            _ => None,
+        };
+        self.insert_stored_as(tgi, data, stored_as);
+        Ok(())
+    }
+
+    /// Inserts `data`, to be saved with the given compression and committed flag, or else those
+    /// of the entry it replaces
+    fn insert_stored_as(&mut self, tgi: TGI, data: Vec<u8>, stored_as: Option<(u16, u16)>) {
+        let size = data.len() as u32;
+        let compression = match self.entries.iter_mut().find(|e| e.tgi == tgi) {
+            Some(entry) => {
+                let pending = &self.pending;
+                let (compression, committed) = stored_as
+                    .unwrap_or_else(|| (pending.get(&tgi).map_or(entry.compression, |(_, c)| *c), entry.committed));
+                // Until it is saved, the resource is read back from memory, uncompressed
+                *entry = IndexEntry { offset: 0, filesize: size, memsize: size, compression: 0, committed, ..entry.clone() };
+                compression
+            }
+            None => {
+                let (compression, committed) = stored_as.unwrap_or((0x5A42, 1));
+                self.entries.push(IndexEntry { tgi, offset: 0, filesize: size, memsize: size, compression: 0, committed });
+                compression
+            }
+        };
+        self.pending.insert(tgi, (data, compression));
+        self.modified = true;
+    }
+
+    /// Removes a resource, returning its index entry if the package had it. The change is kept
+    /// in memory until the package is saved.
+    pub fn remove_resource(&mut self, tgi: &TGI) -> Option<IndexEntry> {
+        let position = self.entries.iter().position(|e| e.tgi == *tgi)?;
+        self.pending.remove(tgi);
+        self.modified = true;
+        Some(self.entries.remove(position))
+    }
+
+    /// Writes the package back to the file it was opened from. See [`Package::save_as`].
+    pub fn save(&mut self) -> Result<()> {
+        self.save_with(&WriteOptions::default())
+    }
+
+    /// Like [`Package::save`], compressing inserted resources with `options`
+    pub fn save_with(&mut self, options: &WriteOptions) -> Result<()> {
+        let path = self.path.clone()
+            .ok_or_else(|| Error::Other("Package wasn't opened from a file; use save_as".to_string()))?;
+        self.save_as_with(path, options)
+    }
+
+    /// Writes the package, with any inserted and removed resources, to `path` and carries on
+    /// from that file. Resources that weren't changed are copied as stored, without being
+    /// decompressed.
+    ///
+    /// The package is written next to `path` and renamed over it, so saving over the file the
+    /// package was opened from is safe, and a failed write leaves the original intact.
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.save_as_with(path, &WriteOptions::default())
+    }
+
+    /// Like [`Package::save_as`], compressing inserted resources with `options`. Resources
+    /// that weren't changed are still copied as stored.
+    pub fn save_as_with<P: AsRef<Path>>(&mut self, path: P, options: &WriteOptions) -> Result<()> {
+        options.validate()?;
+        let path = path.as_ref();
+        // Inserted resources are compressed in parallel; the rest are read in index order
+        let compressed: HashMap<TGI, (IndexEntry, Vec<u8>)> = options.install(|| {
+            self.entries.par_iter()
+                .filter_map(|entry| self.pending.get(&entry.tgi).map(|pending| (entry, pending)))
+                .map(|(entry, (data, compression))| {
+                    Ok((entry.tgi, stored_form(entry.clone(), data, *compression, options)?))
+                })
+                .collect::<Result<_>>()
+        })??;
+        let mut resources = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.clone() {
+            let resource = match compressed.get(&entry.tgi) {
+                Some(resource) => resource.clone(),
+                None => {
+                    let data = self.read_stored_resource(&entry)?;
+                    (entry, data)
+                }
+            };
+            resources.push(resource);
+        }
+
+        let mut file = AtomicFile::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+        write_package(&mut file, PackageHeader::new(), &resources).context("Failed to write package")?;
+        // The old file has to be closed before it can be replaced on Windows. Only close it when
+        // it is the one being replaced, and open it again if replacing fails, so a failed save
+        // leaves the package reading from where it was.
+        let replaces_source = self.path.as_deref().is_some_and(|source| is_same_file(source, path));
+        if replaces_source {
+            self.file = Mutex::new(None);
+        }
+        if let Err(e) = file.commit() {
+            if replaces_source {
+                self.file = Mutex::new(Some(Box::new(File::open(path)?)));
+            }
+            return Err(e).with_context(|| format!("Failed to replace {:?}", path));
+        }
+        *self = Package::open(path)?;
+        Ok(())
+    }
+
+    /// The index entry for `tgi`, if the package contains it
+    pub fn find(&self, tgi: &TGI) -> Option<&IndexEntry> {
+        self.entries.iter().find(|e| e.tgi == *tgi)
+    }
+
+    /// Counts and byte totals per compression scheme and per resource type
+    pub fn compression_report(&self) -> CompressionReport {
+        CompressionReport::from_entries(&self.entries)
+    }
+
+    /// The bytes of a resource exactly as stored in the file, without decompressing them
+    pub fn read_stored_resource(&self, entry: &IndexEntry) -> Result<Vec<u8>> {
+        if let Some((data, _)) = self.pending.get(&entry.tgi) {
+            return Ok(data.clone());
+        }
+        crate::background::throttle_read(entry.filesize as u64);
         let mut buf = vec![0u8; entry.filesize as usize];
-        file.read_exact(&mut buf)?;
+        {
+            // A panic elsewhere while reading can't leave the source in a state a seek won't fix
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            let file = file.as_mut().ok_or_else(|| Error::Other("Package file not open".to_string()))?;
+            file.seek(SeekFrom::Start(entry.offset as u64))?;
+            file.read_exact(&mut buf)?;
+        }
+        crate::perf::record_read(buf.len() as u64);
+        Ok(buf)
+    }
+
+    /// Length of the file the package is read from
+    pub(crate) fn source_len(&self) -> Result<u64> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let file = file.as_mut().ok_or_else(|| Error::Other("Package file not open".to_string()))?;
+        Ok(file.seek(SeekFrom::End(0))?)
+    }
+
+    pub fn read_raw_resource(&self, entry: &IndexEntry) -> Result<Vec<u8>> {
+        // Inserted resources are held decompressed, whatever an older copy of the entry says
+        if let Some((data, _)) = self.pending.get(&entry.tgi) {
+            return Ok(data.clone());
+        }
+        let buf = self.read_stored_resource(entry)?;
 
         if entry.is_compressed() {
-            if buf.len() >= 2 && buf[1] == 0xFB {
+            if is_refpack(&buf) {
                 // RefPack/LZ77
                 return decompress_refpack(&buf, entry.memsize as usize);
             }
@@ -134,7 +345,7 @@ impl Package {
             let mut decoder = ZlibDecoder::new(&buf[..]);
             let mut decompressed = Vec::with_capacity(entry.memsize as usize);
             decoder.read_to_end(&mut decompressed)
-                .context("Failed to decompress resource data (Zlib)")?;
+                .map_err(|e| Error::Decompression(format!("Zlib: {}", e)))?;
             
             if decompressed.len() != entry.memsize as usize {
                 warn!("Decompressed size mismatch for resource: expected {}, got {}", entry.memsize, decompressed.len());
@@ -145,145 +356,208 @@ impl Package {
         Ok(buf)
     }
 
-    pub fn read_resource(&mut self, entry: &IndexEntry) -> Result<TypedResource> {
+    pub fn read_resource(&self, entry: &IndexEntry) -> Result<TypedResource> {
         let data = self.read_raw_resource(entry)?;
         // Handle decompression here if needed before passing to TypedResource
-        TypedResource::from_bytes(entry.tgi.res_type, &data)
+        match TypedResource::from_bytes(entry.tgi.res_type, &data)? {
+            TypedResource::Generic(_) => Ok(TypedResource::Generic(GenericResource::from_entry(entry, &data))),
+            resource => Ok(resource),
+        }
     }
 
+    /// Writes resources (decompressed data, memsize, compression and committed flags) to a new
+    /// package, in [`game_order`](index::game_order). `options` decides how they are compressed; `true` and `false`
+    /// work as shorthand for compressing everything or only what was compressed before.
     pub fn write_merged<P: AsRef<Path>>(
         output_path: P,
         merged_entries: &std::collections::HashMap<TGI, (Vec<u8>, u32, u16, u16)>,
-        compress: bool,
+        options: impl Into<WriteOptions>,
     ) -> Result<()> {
-        let mut file = File::create(output_path)?;
-        
-    let mut header = PackageHeader::default();
-    header.magic = *b"DBPF";
-    header.major = 2;
-    header.minor = 1;
-    header.index_version = 0; 
-    header.index_count = merged_entries.len() as u32;
-    header.unused4 = 0; 
-    header.index_size = 0; 
-    header.unused5[2] = 3; 
-    header.write(&mut file)?;
-
-        file.seek(SeekFrom::Start(PackageHeader::SIZE))?;
-
-        // Sort entries, but try to put Manifest (0x7FB6AD8A) first if it exists
+        let options = options.into();
+        options.validate()?;
         let mut sorted_keys: Vec<_> = merged_entries.keys().collect();
-        sorted_keys.sort_by(|a, b| {
-            let a_is_manifest = a.res_type == 0x7FB6AD8A;
-            let b_is_manifest = b.res_type == 0x7FB6AD8A;
-            if a_is_manifest && !b_is_manifest {
-                std::cmp::Ordering::Less
-            } else if !a_is_manifest && b_is_manifest {
-                std::cmp::Ordering::Greater
-            } else {
-                (a.res_type, a.res_group, a.instance).cmp(&(b.res_type, b.res_group, b.instance))
-            }
-        });
+        sorted_keys.sort_by_key(|tgi| index::game_order(tgi));
 
         // Parallel compression
-        let processed_entries: Vec<(TGI, Vec<u8>, u32, u16, u16)> = sorted_keys
+        let processed_entries: Vec<(TGI, Vec<u8>, u32, u16, u16)> = options.install(|| sorted_keys
             .par_iter()
-            .map(|&tgi| {
+            .map(|&tgi| crate::perf::busy(|| {
                 let (raw_data, memsize, compression_flag, committed) = &merged_entries[tgi];
-                
-                let (final_data, final_compression) = if compress || *compression_flag != 0 {
-                    // Check if it's already compressed by looking at the data head (0x78 or 0xFB)
-                    let is_already_compressed = raw_data.len() >= 2 && (raw_data[0] == 0x78 || raw_data[1] == 0xFB);
-                    
-                    if is_already_compressed {
-                        (raw_data.clone(), 0x5A42)
-                    } else {
-                        use flate2::Compression;
-                        use flate2::write::ZlibEncoder;
-                        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-                        if let Err(e) = encoder.write_all(raw_data) {
-                            warn!("Compression error for {:?}: {}", tgi, e);
-                            return (*tgi, raw_data.clone(), *memsize, 0, *committed);
-                        }
-                        let compressed = match encoder.finish() {
-                            Ok(c) => c,
-                            Err(e) => {
-                                warn!("Compression finish error for {:?}: {}", tgi, e);
-                                return (*tgi, raw_data.clone(), *memsize, 0, *committed);
-                            }
-                        };
-                        
-                        if compressed.len() < raw_data.len() {
-                            (compressed, 0x5A42)
-                        } else {
-                            (raw_data.clone(), 0x5A42)
-                        }
+                let Some(level) = options.level_for(tgi.res_type, *compression_flag != 0) else {
+                    return (*tgi, raw_data.clone(), *memsize, 0, *committed);
+                };
+                if is_compressed_payload(raw_data, *memsize) {
+                    // Keep the code it came with, e.g. 0xFFFF for RefPack
+                    let code = if *compression_flag != 0 { *compression_flag } else { 0x5A42 };
+                    return (*tgi, raw_data.clone(), *memsize, code, *committed);
+                }
+                match options.compress(raw_data, level) {
+                    Ok(Some(compressed)) => (*tgi, compressed, *memsize, 0x5A42, *committed),
+                    Ok(None) => (*tgi, raw_data.clone(), *memsize, 0, *committed),
+                    Err(e) => {
+                        warn!(resource:% = tgi; "Compression error for {:?}: {}", tgi, e);
+                        (*tgi, raw_data.clone(), *memsize, 0, *committed)
                     }
-                } else {
-                    (raw_data.clone(), 0x0000)
+                }
+            }))
+            .collect())?;
+
+        let stored: Vec<(IndexEntry, Vec<u8>)> = processed_entries.into_iter()
+            .map(|(tgi, final_data, memsize, final_compression, committed)| {
+                let entry = IndexEntry {
+                    tgi,
+                    offset: 0,
+                    filesize: final_data.len() as u32,
+                    memsize,
+                    compression: final_compression,
+                    committed,
                 };
-                
-                (*tgi, final_data, *memsize, final_compression, *committed)
+                (entry, final_data)
             })
             .collect();
+        Package::write_stored(output_path, &stored)
+    }
 
-        let mut entries = Vec::with_capacity(processed_entries.len());
-        for (tgi, final_data, memsize, final_compression, committed) in processed_entries {
-            let offset = file.stream_position()? as u32;
-            file.write_all(&final_data)?;
-            
-            entries.push(IndexEntry {
-                tgi,
-                offset,
-                filesize: final_data.len() as u32,
-                memsize,
-                compression: final_compression,
-                committed,
-            });
-        }
+    /// Writes a package holding just one resource, such as an override of a single string table
+    /// or tuning file. `data` is the decompressed resource; `options` decides whether it's stored
+    /// compressed, and `false` stores it as it is.
+    pub fn write_single<P: AsRef<Path>>(output_path: P, tgi: TGI, data: impl Into<Vec<u8>>, options: impl Into<WriteOptions>) -> Result<()> {
+        let data = data.into();
+        let memsize = data.len() as u32;
+        let resources = HashMap::from([(tgi, (data, memsize, 0, 1))]);
+        Self::write_merged(output_path, &resources, options)
+    }
 
-        let index_position = file.stream_position()?;
-        
-            file.write_all(&0u32.to_le_bytes())?;
-
-        for entry in &entries {
-            file.write_all(&entry.tgi.res_type.to_le_bytes())?;
-            file.write_all(&entry.tgi.res_group.to_le_bytes())?;
-            let instance_hi = (entry.tgi.instance >> 32) as u32;
-            file.write_all(&instance_hi.to_le_bytes())?;
-            let instance_lo = entry.tgi.instance as u32;
-            file.write_all(&instance_lo.to_le_bytes())?;
-            file.write_all(&entry.offset.to_le_bytes())?;
-            let fs_val = if entry.compression != 0 { entry.filesize | 0x80000000 } else { entry.filesize };
-            file.write_all(&fs_val.to_le_bytes())?;
-            file.write_all(&entry.memsize.to_le_bytes())?;
-            // Use 0x5A42 for Zlib as observed in original Gorilla file
-            let compression_to_write: u16 = if entry.compression != 0 { 0x5A42 } else { 0x0000 };
-            file.write_all(&compression_to_write.to_le_bytes())?;
-            file.write_all(&entry.committed.to_le_bytes())?;
+    /// Writes a package from resource data that is already in its stored form, in the order
+    /// given. Each entry's size, compression and committed fields are written as they are; only
+    /// the offsets are filled in.
+    ///
+    /// Like every package writer here, it writes through an [`AtomicFile`], so a failed write
+    /// leaves whatever was at `output_path` intact.
+    pub fn write_stored<P: AsRef<Path>>(output_path: P, resources: &[(IndexEntry, Vec<u8>)]) -> Result<()> {
+        let output_path = output_path.as_ref();
+        let mut file = AtomicFile::create(output_path).with_context(|| format!("Failed to create {:?}", output_path))?;
+        write_package(&mut file, PackageHeader::new(), resources)?;
+        file.commit().with_context(|| format!("Failed to write {:?}", output_path))?;
+        Ok(())
+    }
+
+    /// Rewrites the package at `path` in place with some resources' data replaced (or added).
+    ///
+    /// Every other resource is copied over unchanged, keeping its compression and committed flags.
+    /// The new package is written next to the original and renamed over it, so a failed write
+    /// leaves the original intact.
+    pub fn replace_resources<P: AsRef<Path>>(path: P, replacements: &HashMap<TGI, Vec<u8>>) -> Result<()> {
+        let mut pkg = Package::open(path)?;
+        for (tgi, data) in replacements {
+            pkg.insert_resource(*tgi, data.clone());
         }
+        pkg.save()
+    }
+}
 
-        let index_size = (file.stream_position()? - index_position) as u32;
+/// Writes a package made of `header` and resources already in their stored form, filling in
+/// the header's index fields and each entry's offset
+fn write_package<W: Write + Seek>(out: &mut W, mut header: PackageHeader, resources: &[(IndexEntry, Vec<u8>)]) -> Result<()> {
+    header.index_count = resources.len() as u32;
+    header.write(out)?;
 
-        // Go back and update header
-        header.index_position = index_position;
-        header.index_size = 0; // Use 0 for index_size field in header if index_version is 0, matching original
-        header.unused4 = index_size; 
-        
-        file.seek(SeekFrom::Start(0))?;
-        header.write(&mut file)?;
+    out.seek(SeekFrom::Start(PackageHeader::SIZE))?;
 
-        Ok(())
+    let mut entries = Vec::with_capacity(resources.len());
+    for (entry, data) in resources {
+        let offset = out.stream_position()? as u32;
+        out.write_all(data)?;
+        crate::perf::record_compressed(data.len() as u64, entry.memsize as u64);
+        entries.push(IndexEntry { offset, filesize: data.len() as u32, ..entry.clone() });
+    }
+
+    let index_position = out.stream_position()?;
+
+    out.write_all(&0u32.to_le_bytes())?;
+
+    for entry in &entries {
+        out.write_all(&entry.tgi.res_type.to_le_bytes())?;
+        out.write_all(&entry.tgi.res_group.to_le_bytes())?;
+        let instance_hi = (entry.tgi.instance >> 32) as u32;
+        out.write_all(&instance_hi.to_le_bytes())?;
+        let instance_lo = entry.tgi.instance as u32;
+        out.write_all(&instance_lo.to_le_bytes())?;
+        out.write_all(&entry.offset.to_le_bytes())?;
+        let fs_val = if entry.compression != 0 { entry.filesize | 0x80000000 } else { entry.filesize };
+        out.write_all(&fs_val.to_le_bytes())?;
+        out.write_all(&entry.memsize.to_le_bytes())?;
+        out.write_all(&entry.compression.to_le_bytes())?;
+        out.write_all(&entry.committed.to_le_bytes())?;
+    }
+
+    let index_size = (out.stream_position()? - index_position) as u32;
+
+    // Go back and update header
+    header.set_index_location(IndexLocation { position: index_position, size: index_size });
+
+    out.seek(SeekFrom::Start(0))?;
+    header.write(out)?;
+    crate::perf::record_written(index_position + index_size as u64);
+
+    Ok(())
+}
+
+/// Whether two paths name the same file; a path that doesn't exist yet matches only itself
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// The index entry and bytes to store decompressed `data` with: zlib-compressed when
+/// `options` call for it (`compression` says whether the resource was compressed) and that
+/// saves enough, otherwise as it is
+fn stored_form(entry: IndexEntry, data: &[u8], compression: u16, options: &WriteOptions) -> Result<(IndexEntry, Vec<u8>)> {
+    let memsize = data.len() as u32;
+    if let Some(level) = options.level_for(entry.tgi.res_type, compression != 0) {
+        if let Some(compressed) = options.compress(data, level)? {
+            return Ok((IndexEntry { filesize: compressed.len() as u32, memsize, compression: 0x5A42, ..entry }, compressed));
+        }
+    }
+    Ok((IndexEntry { filesize: memsize, memsize, compression: 0, ..entry }, data.to_vec()))
+}
+
+/// True if `data` is a zlib or RefPack stream that inflates to `memsize` bytes.
+///
+/// Resources read through `read_raw_resource` are already decompressed, and plenty of them start
+/// with 0x78 ('x') or have 0xFB as their second byte, so the signature alone isn't enough.
+fn is_compressed_payload(data: &[u8], memsize: u32) -> bool {
+    if data.len() < 2 || data.len() == memsize as usize {
+        return false;
     }
+    if data[0] == 0x78 && ((data[0] as u16) << 8 | data[1] as u16).is_multiple_of(31) {
+        use flate2::read::ZlibDecoder;
+        let mut inflated = Vec::with_capacity(memsize as usize);
+        return ZlibDecoder::new(data).read_to_end(&mut inflated).is_ok() && inflated.len() == memsize as usize;
+    }
+    data[1] == 0xFB && refpack_inflate(data, memsize as usize).is_ok_and(|(_, written)| written == memsize as usize)
+}
+
+/// True if stored resource data is a RefPack stream rather than zlib.
+///
+/// A zlib header can never have 0xFB as its second byte (the header check would fail), so this is
+/// safe for any data stored compressed.
+pub(crate) fn is_refpack(stored: &[u8]) -> bool {
+    stored.len() >= 2 && stored[1] == 0xFB
+}
+
+pub(crate) fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
+    refpack_inflate(data, memsize).map(|(decompressed, _)| decompressed)
 }
 
-fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
+/// Decompresses a RefPack stream into a `memsize` buffer, also returning how many bytes the
+/// stream actually produced (a truncated stream leaves the rest zeroed)
+pub(crate) fn refpack_inflate(data: &[u8], memsize: usize) -> Result<(Vec<u8>, usize)> {
     let mut decompressed = vec![0u8; memsize];
     let mut r_pos = 0;
     let mut w_pos = 0;
 
     if data.len() < 2 {
-        return Err(anyhow!("RefPack data too short"));
+        return Err(Error::Decompression("RefPack data too short".to_string()));
     }
 
     let compression_type = data[r_pos];
@@ -292,12 +566,13 @@ fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
     r_pos += 1;
 
     if signature != 0xFB {
-        return Err(anyhow!("Invalid RefPack signature: expected 0xFB, got 0x{:02X}", signature));
+        return Err(Error::Decompression(format!("Invalid RefPack signature: expected 0xFB, got 0x{:02X}", signature)));
     }
 
-    let size_bytes = if compression_type & 0x80 != 0 { 3 } else { 4 };
+    // The 0x80 flag marks a 4-byte size; normally it is 3 bytes
+    let size_bytes = if compression_type & 0x80 != 0 { 4 } else { 3 };
     if r_pos + size_bytes > data.len() {
-        return Err(anyhow!("RefPack data too short for size header"));
+        return Err(Error::Decompression("RefPack data too short for size header".to_string()));
     }
     
     // We already know memsize from the index, but RefPack also stores it.
@@ -317,7 +592,7 @@ fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
             let num_copy = (((byte0 & 0x1C) >> 2) + 3) as usize;
             let copy_offset = ((byte0 as usize & 0x60) << 3) + byte1 as usize + 1;
 
-            copy_plain(&data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
+            copy_plain(data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
             copy_ref(&mut decompressed, &mut w_pos, num_copy, copy_offset)?;
         } else if byte0 <= 0xBF {
             if r_pos + 1 >= data.len() { break; }
@@ -330,7 +605,7 @@ fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
             let num_copy = ((byte0 & 0x3F) + 4) as usize;
             let copy_offset = ((byte1 as usize & 0x3F) << 8) + byte2 as usize + 1;
 
-            copy_plain(&data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
+            copy_plain(data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
             copy_ref(&mut decompressed, &mut w_pos, num_copy, copy_offset)?;
         } else if byte0 <= 0xDF {
             if r_pos + 2 >= data.len() { break; }
@@ -345,23 +620,23 @@ fn decompress_refpack(data: &[u8], memsize: usize) -> Result<Vec<u8>> {
             let num_copy = ((byte0 as usize & 0x0C) << 6) + byte3 as usize + 5;
             let copy_offset = ((byte0 as usize & 0x10) << 12) + ((byte1 as usize) << 8) + byte2 as usize + 1;
 
-            copy_plain(&data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
+            copy_plain(data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
             copy_ref(&mut decompressed, &mut w_pos, num_copy, copy_offset)?;
         } else if byte0 <= 0xFB {
             let num_plain = (((byte0 & 0x1F) << 2) + 4) as usize;
-            copy_plain(&data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
+            copy_plain(data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
         } else {
             let num_plain = (byte0 & 0x03) as usize;
-            copy_plain(&data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
+            copy_plain(data, &mut r_pos, &mut decompressed, &mut w_pos, num_plain)?;
         }
     }
 
-    Ok(decompressed)
+    Ok((decompressed, w_pos))
 }
 
 fn copy_plain(src: &[u8], src_pos: &mut usize, dest: &mut [u8], dest_pos: &mut usize, count: usize) -> Result<()> {
     if *src_pos + count > src.len() || *dest_pos + count > dest.len() {
-        return Err(anyhow!("RefPack: plain copy out of bounds"));
+        return Err(Error::Decompression("RefPack: plain copy out of bounds".to_string()));
     }
     dest[*dest_pos..*dest_pos + count].copy_from_slice(&src[*src_pos..*src_pos + count]);
     *src_pos += count;
@@ -371,7 +646,7 @@ fn copy_plain(src: &[u8], src_pos: &mut usize, dest: &mut [u8], dest_pos: &mut u
 
 fn copy_ref(dest: &mut [u8], dest_pos: &mut usize, count: usize, offset: usize) -> Result<()> {
     if offset > *dest_pos || *dest_pos + count > dest.len() {
-        return Err(anyhow!("RefPack: reference copy out of bounds (offset={}, pos={}, count={}, len={})", offset, *dest_pos, count, dest.len()));
+        return Err(Error::Decompression(format!("RefPack: reference copy out of bounds (offset={}, pos={}, count={}, len={})", offset, *dest_pos, count, dest.len())));
     }
     for _ in 0..count {
         dest[*dest_pos] = dest[*dest_pos - offset];
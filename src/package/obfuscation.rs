@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::io::Read;
+use crate::package::Package;
+use crate::package::header::PackageHeader;
+use crate::package::index::{CompressionScheme, IndexEntry, TGI};
+use super::{is_compressed_payload, is_refpack};
+
+/// How many resources need anomalies that damage doesn't explain before a package counts as
+/// intentionally obfuscated
+pub const MIN_DELIBERATE: usize = 2;
+
+/// Something wrong with one index entry, found by [`Package::check_obfuscation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anomaly {
+    /// The data decodes, but not with the compression the index claims
+    WrongCompression { claimed: u16, actual: u16 },
+    /// A compression code no game writes, on data that decodes with a real one
+    UnknownCompression { claimed: u16, actual: u16 },
+    /// The data decodes with the claimed compression, but not to the claimed size
+    WrongSize { claimed: u32, actual: u32 },
+    /// The data overlaps another resource's without being the same bytes
+    Overlapping,
+    /// An earlier entry has the same TGI
+    DuplicateKey,
+    /// The data lies past the end of the file, or over the header or the index
+    OutOfBounds,
+    /// The data doesn't decode with any compression
+    Undecodable,
+}
+
+impl Anomaly {
+    /// True for anomalies a truncated download or a crashed writer doesn't leave behind: the
+    /// data is intact, but the index lies about it
+    pub fn is_deliberate(&self) -> bool {
+        !matches!(self, Anomaly::OutOfBounds | Anomaly::Undecodable)
+    }
+
+    /// Short name of the kind of anomaly, for summaries
+    pub fn label(&self) -> &'static str {
+        match self {
+            Anomaly::WrongCompression { .. } => "wrong compression flag",
+            Anomaly::UnknownCompression { .. } => "unknown compression code",
+            Anomaly::WrongSize { .. } => "wrong size",
+            Anomaly::Overlapping => "overlapping data",
+            Anomaly::DuplicateKey => "duplicate key",
+            Anomaly::OutOfBounds => "out of bounds",
+            Anomaly::Undecodable => "undecodable",
+        }
+    }
+}
+
+impl std::fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Anomaly::WrongCompression { claimed, actual } | Anomaly::UnknownCompression { claimed, actual } => write!(
+                f, "{}: stored as {}, indexed as {}", self.label(),
+                CompressionScheme::from_code(*actual), CompressionScheme::from_code(*claimed),
+            ),
+            Anomaly::WrongSize { claimed, actual } => write!(f, "wrong size: {} bytes, indexed as {}", actual, claimed),
+            _ => write!(f, "{}", self.label()),
+        }
+    }
+}
+
+/// What the anomalies in a package add up to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// Anomalies that damage explains, such as a truncated file
+    Damaged,
+    /// At least [`MIN_DELIBERATE`] resources whose index entries lie about intact data
+    Obfuscated,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Clean => write!(f, "clean"),
+            Verdict::Damaged => write!(f, "damaged"),
+            Verdict::Obfuscated => write!(f, "intentionally obfuscated"),
+        }
+    }
+}
+
+/// What [`Package::check_obfuscation`] found
+#[derive(Debug, Clone)]
+pub struct ObfuscationReport {
+    pub verdict: Verdict,
+    /// Every anomaly, with the entry as the index has it. An entry can have more than one.
+    pub anomalies: Vec<(IndexEntry, Anomaly)>,
+    /// The index with every resource that could be recovered, corrected to match its data and
+    /// with duplicate keys dropped. See [`Package::recover`].
+    pub recovered: Vec<IndexEntry>,
+    /// Resources whose data couldn't be recovered
+    pub lost: Vec<TGI>,
+}
+
+impl ObfuscationReport {
+    pub fn is_clean(&self) -> bool {
+        self.verdict == Verdict::Clean
+    }
+
+    /// One line for reports, e.g. "intentionally obfuscated: 12 wrong compression flag,
+    /// 2 overlapping data; 2 resources lost"
+    pub fn summary(&self) -> String {
+        if self.is_clean() {
+            return self.verdict.to_string();
+        }
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for (_, anomaly) in &self.anomalies {
+            match counts.iter_mut().find(|(label, _)| *label == anomaly.label()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((anomaly.label(), 1)),
+            }
+        }
+        let counts: Vec<String> = counts.iter().map(|(label, count)| format!("{} {}", count, label)).collect();
+        let lost = match self.lost.len() {
+            0 => "everything is recoverable".to_string(),
+            1 => "1 resource lost".to_string(),
+            n => format!("{} resources lost", n),
+        };
+        format!("{}: {}; {}", self.verdict, counts.join(", "), lost)
+    }
+}
+
+/// The compression code and decompressed size that fit stored data, trying what the entry
+/// claims first
+fn fit(entry: &IndexEntry, stored: &[u8]) -> Option<(u16, u32)> {
+    if !entry.is_compressed() && stored.len() == entry.memsize as usize {
+        return Some((0, entry.memsize));
+    }
+    if is_compressed_payload(stored, entry.memsize) {
+        return Some((if is_refpack(stored) { 0xFFFF } else { 0x5A42 }, entry.memsize));
+    }
+    if stored.len() == entry.memsize as usize {
+        return Some((0, entry.memsize));
+    }
+    // A complete zlib stream that inflates to some other size
+    if stored.first() == Some(&0x78) {
+        let mut inflated = Vec::new();
+        if flate2::read::ZlibDecoder::new(stored).read_to_end(&mut inflated).is_ok() && !inflated.is_empty() {
+            return Some((0x5A42, inflated.len() as u32));
+        }
+    }
+    None
+}
+
+/// Indices of entries whose data partly overlaps another's. Entries sharing exactly the same
+/// bytes are left alone, as some tools store identical resources once.
+fn overlapping(entries: &[IndexEntry], skip: impl Fn(&IndexEntry) -> bool) -> HashSet<usize> {
+    let mut order: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].filesize > 0 && !skip(&entries[i]))
+        .collect();
+    order.sort_by_key(|&i| entries[i].offset);
+    let mut found = HashSet::new();
+    // The entry whose data reaches furthest so far, and where it ends
+    let mut furthest: Option<(usize, u64)> = None;
+    for i in order {
+        let entry = &entries[i];
+        let end = entry.offset as u64 + entry.filesize as u64;
+        if let Some((j, furthest_end)) = furthest {
+            let other = &entries[j];
+            let shared = other.offset == entry.offset && other.filesize == entry.filesize;
+            if (entry.offset as u64) < furthest_end && !shared {
+                found.insert(i);
+                found.insert(j);
+            }
+            if end <= furthest_end {
+                continue;
+            }
+        }
+        furthest = Some((i, end));
+    }
+    found
+}
+
+impl Package {
+    /// Checks every index entry against the data it points to, looking for the tricks used to
+    /// stop packages being opened by tools: shuffled offsets, bogus compression flags and sizes,
+    /// and decoy entries. Reads and decompresses every resource.
+    pub fn check_obfuscation(&self) -> ObfuscationReport {
+        let file_len = self.source_len().unwrap_or(u64::MAX);
+        let index = self.header.index_location();
+        let index_end = index.position + index.size as u64;
+        let skip = |entry: &IndexEntry| {
+            self.pending.contains_key(&entry.tgi) || entry.scheme() == CompressionScheme::Deleted
+        };
+        let overlapping = overlapping(&self.entries, skip);
+
+        let mut anomalies = Vec::new();
+        let mut recovered = Vec::with_capacity(self.entries.len());
+        let mut lost = Vec::new();
+        let mut seen = HashSet::new();
+        let mut kept = HashSet::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            if skip(entry) {
+                recovered.push(entry.clone());
+                kept.insert(entry.tgi);
+                continue;
+            }
+            if !seen.insert(entry.tgi) {
+                anomalies.push((entry.clone(), Anomaly::DuplicateKey));
+            }
+            if overlapping.contains(&i) {
+                anomalies.push((entry.clone(), Anomaly::Overlapping));
+            }
+
+            let start = entry.offset as u64;
+            let end = start + entry.filesize as u64;
+            let in_header = start < PackageHeader::SIZE && entry.filesize > 0;
+            let in_index = start < index_end && end > index.position;
+            if in_header || in_index || end > file_len {
+                anomalies.push((entry.clone(), Anomaly::OutOfBounds));
+                lost.push(entry.tgi);
+                continue;
+            }
+            let Some((compression, memsize)) = self.read_stored_resource(entry).ok().and_then(|stored| fit(entry, &stored)) else {
+                anomalies.push((entry.clone(), Anomaly::Undecodable));
+                lost.push(entry.tgi);
+                continue;
+            };
+
+            let claimed = entry.scheme();
+            let anomaly = if matches!(claimed, CompressionScheme::Unknown(_)) {
+                Some(Anomaly::UnknownCompression { claimed: entry.compression, actual: compression })
+            } else if entry.is_compressed() != (compression != 0) {
+                Some(Anomaly::WrongCompression { claimed: entry.compression, actual: compression })
+            } else if memsize != entry.memsize {
+                Some(Anomaly::WrongSize { claimed: entry.memsize, actual: memsize })
+            } else {
+                None
+            };
+            if let Some(anomaly) = anomaly {
+                anomalies.push((entry.clone(), anomaly));
+            }
+            // The first copy of a key that decodes wins
+            if kept.insert(entry.tgi) {
+                // Keep the claimed code when it is a real one that fits, e.g. zlib data marked RefPack
+                let compression = if anomaly.is_some_and(|a| !matches!(a, Anomaly::WrongSize { .. })) { compression } else { entry.compression };
+                recovered.push(IndexEntry { compression, memsize, ..entry.clone() });
+            }
+        }
+        lost.retain(|tgi| !kept.contains(tgi));
+
+        let deliberate: HashSet<(TGI, u32)> = anomalies.iter()
+            .filter(|(_, anomaly)| anomaly.is_deliberate())
+            .map(|(entry, _)| (entry.tgi, entry.offset))
+            .collect();
+        let verdict = if deliberate.len() >= MIN_DELIBERATE {
+            Verdict::Obfuscated
+        } else if anomalies.is_empty() {
+            Verdict::Clean
+        } else {
+            Verdict::Damaged
+        };
+        ObfuscationReport { verdict, anomalies, recovered, lost }
+    }
+
+    /// Replaces the index with the entries recovered in `report`, so the resources an obfuscated
+    /// index lies about can be read, and saving writes a package any tool can open
+    pub fn recover(&mut self, report: &ObfuscationReport) {
+        self.entries = report.recovered.clone();
+        self.modified = true;
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::atomic::AtomicFile;
+use crate::error::{Context, Error, Result};
+use crate::package::{is_refpack, refpack_inflate, write_package};
+use crate::package::header::PackageHeader;
+use crate::package::index::{CompressionScheme, IndexEntry, TGI};
+
+/// Something [`repair`] fixed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// The header's entry count didn't match the records in the index
+    EntryCount { header: u32, index: u32 },
+    /// The entry is compressed but the high bit of its file size, which says so, wasn't set
+    SizeFlag { tgi: TGI },
+    /// The entry's data is compressed but its compression code said it wasn't
+    CompressionCode { tgi: TGI, code: u16 },
+    /// The index gave the wrong decompressed size
+    Memsize { tgi: TGI, from: u32, to: u32 },
+    /// The entry's data was past the end of the file, so the entry was left out
+    DroppedEntry { tgi: TGI, offset: u32, filesize: u32 },
+}
+
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fix::EntryCount { header, index } => write!(f, "entry count: header said {}, the index has {}", header, index),
+            Fix::SizeFlag { tgi } => write!(f, "{}: set the compressed flag on its file size", tgi),
+            Fix::CompressionCode { tgi, code } => write!(f, "{}: set compression to 0x{:04X} to match its data", tgi, code),
+            Fix::Memsize { tgi, from, to } => write!(f, "{}: size was {} bytes, the data is {}", tgi, from, to),
+            Fix::DroppedEntry { tgi, offset, filesize } => write!(
+                f, "{}: dropped, its data at 0x{:X} ({} bytes) is past the end of the file", tgi, offset, filesize,
+            ),
+        }
+    }
+}
+
+/// What [`repair`] did
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub fixes: Vec<Fix>,
+    /// Entries written to the repaired package
+    pub kept: usize,
+    /// Entries kept as they were because their data doesn't decompress, with the error
+    pub undecodable: Vec<(TGI, String)>,
+}
+
+/// One index record as stored, before any of the reader's corrections
+struct RawEntry {
+    entry: IndexEntry,
+    /// The file size field with its high bit
+    filesize_raw: u32,
+}
+
+/// Reads as many whole records as the index has, trusting the header's count only as far as
+/// the index (and the file) goes
+fn read_raw_index<R: Read + Seek>(file: &mut R, header: &PackageHeader, file_len: u64) -> Result<Vec<RawEntry>> {
+    let location = header.index_location();
+    if location.position < PackageHeader::SIZE || location.position + 4 > file_len {
+        return Err(Error::InvalidHeader(format!("index position 0x{:X} is outside the file", location.position)));
+    }
+    // An index size that's missing or runs off the end is ignored in favour of the file's end
+    let declared_end = location.position + location.size as u64;
+    let fits = location.size >= 4 && declared_end <= file_len;
+    let end = if fits { declared_end } else { file_len };
+    let mut index = vec![0u8; (end - location.position) as usize];
+    file.seek(SeekFrom::Start(location.position))?;
+    file.read_exact(&mut index)?;
+
+    let word = |pos: usize| u32::from_le_bytes(index[pos..pos + 4].try_into().unwrap());
+    let flags = word(0);
+    let mut pos = 4;
+    let mut constant = |bit: u32| -> Option<u32> {
+        if flags & bit == 0 || pos + 4 > index.len() {
+            return None;
+        }
+        pos += 4;
+        Some(word(pos - 4))
+    };
+    let constant_type = constant(0x01);
+    let constant_group = constant(0x02);
+    let constant_instance_hi = constant(0x04);
+    let record_len = 32 - 4 * [constant_type, constant_group, constant_instance_hi].iter().flatten().count();
+
+    let available = (index.len().saturating_sub(pos) / record_len) as u32;
+    // With an index size that fits the file exactly, the records are all there is to go on;
+    // otherwise the count can only be too high
+    let exact = fits && (index.len() - pos).is_multiple_of(record_len);
+    let count = if exact { available } else { header.index_count.min(available) };
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let mut field = |fixed: Option<u32>| match fixed {
+            Some(value) => value,
+            None => {
+                pos += 4;
+                word(pos - 4)
+            }
+        };
+        let res_type = field(constant_type);
+        let res_group = field(constant_group);
+        let instance_hi = field(constant_instance_hi);
+        let instance_lo = field(None);
+        let offset = field(None);
+        let filesize_raw = field(None);
+        let memsize = field(None);
+        let flags = field(None);
+        entries.push(RawEntry {
+            entry: IndexEntry {
+                tgi: TGI { res_type, res_group, instance: ((instance_hi as u64) << 32) | instance_lo as u64 },
+                offset,
+                filesize: filesize_raw & 0x7FFFFFFF,
+                memsize,
+                compression: flags as u16,
+                committed: (flags >> 16) as u16,
+            },
+            filesize_raw,
+        });
+    }
+    Ok(entries)
+}
+
+/// How big `stored` really is once decompressed, if it decompresses. The index's size can't be
+/// trusted here, so RefPack data is inflated to the size in its own header.
+fn decompressed_len(stored: &[u8]) -> Result<usize> {
+    if is_refpack(stored) {
+        let size_bytes = if stored[0] & 0x80 != 0 { 4 } else { 3 };
+        let size = stored.get(2..2 + size_bytes)
+            .ok_or_else(|| Error::Decompression("RefPack data too short for size header".to_string()))?
+            .iter()
+            .fold(0usize, |size, &b| (size << 8) | b as usize);
+        return refpack_inflate(stored, size).map(|(_, written)| written);
+    }
+    let mut decompressed = Vec::new();
+    flate2::read::ZlibDecoder::new(stored).read_to_end(&mut decompressed)
+        .map_err(|e| Error::Decompression(format!("Zlib: {}", e)))?;
+    Ok(decompressed.len())
+}
+
+/// True if `stored` starts like a zlib or RefPack stream
+fn looks_compressed(stored: &[u8]) -> bool {
+    is_refpack(stored) || (stored.len() >= 2 && stored[0] == 0x78 && u16::from_be_bytes([stored[0], stored[1]]).is_multiple_of(31))
+}
+
+/// Writes a repaired copy of the package at `input` to `output`, fixing what commonly goes
+/// wrong in damaged packages: a header entry count that doesn't match the index, file size
+/// flags and compression codes that don't match the data, decompressed sizes the index has
+/// wrong, and entries whose data was cut off the end of the file.
+///
+/// Resource data is copied as it is stored, never recompressed. The copy is written through an
+/// [`AtomicFile`], so `output` can be `input` itself.
+pub fn repair<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<RepairReport> {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    let mut file = File::open(input).with_context(|| format!("Failed to open {:?}", input))?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+    let header = PackageHeader::read(&mut file).map_err(|e| Error::InvalidHeader(e.to_string()))?;
+    if !header.is_valid() {
+        return Err(Error::InvalidHeader("not a DBPF package, or an unsupported version".to_string()));
+    }
+
+    let mut report = RepairReport::default();
+    let raw = read_raw_index(&mut file, &header, file_len)?;
+    if raw.len() as u32 != header.index_count {
+        report.fixes.push(Fix::EntryCount { header: header.index_count, index: raw.len() as u32 });
+    }
+
+    let mut resources = Vec::with_capacity(raw.len());
+    for RawEntry { mut entry, filesize_raw } in raw {
+        let tgi = entry.tgi;
+        if entry.scheme() == CompressionScheme::Deleted {
+            resources.push((entry, Vec::new()));
+            continue;
+        }
+        if entry.offset as u64 + entry.filesize as u64 > file_len {
+            report.fixes.push(Fix::DroppedEntry { tgi, offset: entry.offset, filesize: entry.filesize });
+            continue;
+        }
+        let mut stored = vec![0u8; entry.filesize as usize];
+        file.seek(SeekFrom::Start(entry.offset as u64))?;
+        file.read_exact(&mut stored)?;
+
+        if entry.compression == 0 && looks_compressed(&stored) && decompressed_len(&stored).is_ok() {
+            let code = if is_refpack(&stored) { 0xFFFF } else { 0x5A42 };
+            report.fixes.push(Fix::CompressionCode { tgi, code });
+            entry.compression = code;
+        } else if entry.is_compressed() && filesize_raw & 0x80000000 == 0 {
+            // Uncompressed entries can have the flag too (the game sets it on everything), but a
+            // compressed one without it is read as stored
+            report.fixes.push(Fix::SizeFlag { tgi });
+        }
+
+        let actual = if entry.is_compressed() {
+            match decompressed_len(&stored) {
+                Ok(len) => Some(len as u32),
+                Err(e) => {
+                    report.undecodable.push((tgi, format!("{:#}", e)));
+                    None
+                }
+            }
+        } else {
+            Some(entry.filesize)
+        };
+        if let Some(actual) = actual.filter(|&actual| actual != entry.memsize) {
+            report.fixes.push(Fix::Memsize { tgi, from: entry.memsize, to: actual });
+            entry.memsize = actual;
+        }
+        resources.push((entry, stored));
+    }
+    drop(file);
+
+    report.kept = resources.len();
+    let mut out = AtomicFile::create(output).with_context(|| format!("Failed to create {:?}", output))?;
+    write_package(&mut out, header, &resources)?;
+    out.commit().with_context(|| format!("Failed to write {:?}", output))?;
+    Ok(report)
+}
@@ -1,8 +1,14 @@
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
-use anyhow::{Result, Context};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+#[cfg(any(feature = "catalog", feature = "meshes"))]
+use std::io::Write;
+use crate::error::{Context, Error, Result};
 use binrw::{BinRead, BinWrite, binrw, BinReaderExt, BinWriterExt};
-use crate::package::index::TGI;
+use crate::package::index::{IndexEntry, TGI};
 use std::collections::HashMap;
+#[cfg(feature = "tuning")]
+use std::collections::BTreeMap;
+#[cfg(feature = "tuning")]
+use crate::package::simdata::{SimDataObject, SimDataReader, SimDataSchema, SimDataTable, SimDataType, SimDataValue, SimDataWriter};
 
 pub trait Resource: std::fmt::Debug {
     fn from_bytes(data: &[u8]) -> Result<Self> where Self: Sized;
@@ -13,11 +19,14 @@ pub trait Resource: std::fmt::Debug {
 #[derive(Debug)]
 pub struct GenericResource {
     pub data: Vec<u8>,
+    /// Index entry the resource was read from, so it can be written back with the same
+    /// compression and committed flags. `None` for resources built from bytes alone.
+    pub source: Option<IndexEntry>,
 }
 
 impl Resource for GenericResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
-        Ok(Self { data: data.to_vec() })
+        Ok(Self { data: data.to_vec(), source: None })
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>> {
@@ -25,6 +34,23 @@ impl Resource for GenericResource {
     }
 }
 
+impl GenericResource {
+    pub fn from_entry(entry: &IndexEntry, data: &[u8]) -> Self {
+        Self { data: data.to_vec(), source: Some(entry.clone()) }
+    }
+
+    /// The data, memsize, compression and committed flags in the form
+    /// [`crate::Package::write_merged`] takes. Resources with no source entry are written
+    /// compressed and committed, like other new resources.
+    pub fn merge_entry(&self) -> (Vec<u8>, u32, u16, u16) {
+        let (compression, committed) = match &self.source {
+            Some(entry) => (entry.compression, entry.committed),
+            None => (0x5A42, 1),
+        };
+        (self.data.clone(), self.data.len() as u32, compression, committed)
+    }
+}
+
 /// NameMap resource (0x0166038C)
 #[binrw]
 #[derive(Debug)]
@@ -74,6 +100,55 @@ pub struct ClipResource {
     pub raw_data: Vec<u8>,
 }
 
+/// The fixed fields at the start of a clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipHeader {
+    pub version: u32,
+    pub flags: u32,
+    /// Length of the animation in seconds
+    pub duration: f32,
+    /// The clip's own name; only stored from version 7
+    pub clip_name: Option<String>,
+    pub rig_namespace: String,
+}
+
+impl ClipResource {
+    /// Reads the clip header: flags, duration, initial offset, namespace hashes and names
+    pub fn header(&self) -> Result<ClipHeader> {
+        fn read_string(cursor: &mut Cursor<&Vec<u8>>) -> Result<String> {
+            let len = cursor.read_le::<u32>()? as usize;
+            let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+            if len > remaining {
+                return Err(Error::InvalidResource(format!("Clip string length {} runs past the end of the data", len)));
+            }
+            let mut buf = vec![0u8; len];
+            cursor.read_exact(&mut buf)?;
+            Ok(String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string())
+        }
+
+        let mut cursor = Cursor::new(&self.raw_data);
+        let version = cursor.read_le::<u32>()?;
+        let flags = cursor.read_le::<u32>()?;
+        let duration = cursor.read_le::<f32>()?;
+        // Initial offset: rotation quaternion and translation
+        cursor.seek(SeekFrom::Current(7 * 4))?;
+        let mut hashes = 0;
+        if version >= 5 {
+            hashes += 1; // reference namespace
+        }
+        if version >= 10 {
+            hashes += 2; // surface namespace and joint name
+        }
+        if version >= 11 {
+            hashes += 1; // surface child namespace
+        }
+        cursor.seek(SeekFrom::Current(hashes * 4))?;
+        let clip_name = if version >= 7 { Some(read_string(&mut cursor)?) } else { None };
+        let rig_namespace = read_string(&mut cursor)?;
+        Ok(ClipHeader { version, flags, duration, clip_name, rig_namespace })
+    }
+}
+
 impl Resource for ClipResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -86,13 +161,264 @@ impl Resource for ClipResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// CAS Part resource (0x034AE111)
+///
+/// The data is kept as read, and the editing methods ([`set_name`](Self::set_name),
+/// [`set_tags`](Self::set_tags), [`set_tgi_list`](Self::set_tgi_list)) rewrite only the bytes of
+/// their field and the TGI list offset, so a CASP written back out is identical to the one read
+/// apart from the edits, whatever its version.
 #[derive(Debug)]
 pub struct CasPartResource {
     pub version: u32,
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "catalog")]
+impl CasPartResource {
+    /// Reads the TGI list at the end of the CASP (textures, meshes, regions, etc.).
+    ///
+    /// The list position is stored right after the version as an offset from the end of that field,
+    /// and entries are stored instance, group, type.
+    pub fn tgi_list(&self) -> Result<Vec<TGI>> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        cursor.seek(SeekFrom::Start(4))?;
+        let offset = cursor.read_le::<u32>()? as u64;
+        cursor.seek(SeekFrom::Start(8 + offset))?;
+        let count = cursor.read_le::<u8>()?;
+        let mut tgis = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let instance = cursor.read_le::<u64>()?;
+            let res_group = cursor.read_le::<u32>()?;
+            let res_type = cursor.read_le::<u32>()?;
+            tgis.push(TGI { res_type, res_group, instance });
+        }
+        Ok(tgis)
+    }
+
+    /// Reads the part's name, which follows the preset count as a 7-bit length prefixed
+    /// big-endian UTF-16 string
+    fn read_name(cursor: &mut Cursor<&Vec<u8>>) -> Result<String> {
+        cursor.seek(SeekFrom::Start(12))?;
+        let mut len = 0usize;
+        for shift in (0..35).step_by(7) {
+            let byte = cursor.read_le::<u8>()?;
+            len |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+        if len > remaining {
+            return Err(Error::InvalidResource(format!("CASP name length {} runs past the end of the data", len)));
+        }
+        let mut buf = vec![0u8; len];
+        cursor.read_exact(&mut buf)?;
+        let units: Vec<u16> = buf.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    /// The part's name, e.g. `yfHair_Curly`
+    pub fn name(&self) -> Result<String> {
+        Self::read_name(&mut Cursor::new(&self.raw_data))
+    }
+
+    /// The part's sort priority and secondary sort index, which order its swatches in CAS
+    pub fn sort_order(&self) -> Result<(f32, u16)> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        Self::read_name(&mut cursor)?;
+        Ok((cursor.read_le::<f32>()?, cursor.read_le::<u16>()?))
+    }
+
+    /// Moves `cursor` from the start of the data to the tag count
+    fn seek_to_tags(&self, cursor: &mut Cursor<&Vec<u8>>) -> Result<()> {
+        Self::read_name(cursor)?;
+        // Sort priority, secondary sort index, property ID, aural material hash, parameter flags
+        cursor.seek(SeekFrom::Current(4 + 2 + 4 + 4 + 1))?;
+        if self.version >= 39 {
+            cursor.seek(SeekFrom::Current(1))?;
+        }
+        // Excluded part flags, and excluded modifier region flags (64-bit from version 36)
+        cursor.seek(SeekFrom::Current(8))?;
+        if self.version >= 41 {
+            cursor.seek(SeekFrom::Current(8))?;
+        }
+        cursor.seek(SeekFrom::Current(if self.version >= 36 { 8 } else { 4 }))?;
+        Ok(())
+    }
+
+    /// Bytes per tag: a 16-bit category and a value, 32-bit from version 37
+    fn tag_size(&self) -> usize {
+        if self.version >= 37 { 6 } else { 4 }
+    }
+
+    /// Moves `cursor` from the start of the data to the field after the reserved word that
+    /// follows age/gender, where versions 34 and up store the pack ID
+    fn seek_past_flags(&self, cursor: &mut Cursor<&Vec<u8>>) -> Result<()> {
+        self.seek_to_tags(cursor)?;
+        let tags = cursor.read_le::<u32>()? as i64;
+        cursor.seek(SeekFrom::Current(tags * self.tag_size() as i64))?;
+        // Price, title and description keys
+        cursor.seek(SeekFrom::Current(12))?;
+        if self.version >= 43 {
+            cursor.seek(SeekFrom::Current(4))?;
+        }
+        // Unique texture space, body type and subtype, age/gender, reserved
+        cursor.seek(SeekFrom::Current(1 + 4 + 4 + 4 + 4))?;
+        Ok(())
+    }
+
+    /// The game pack the part belongs to (0 for the base game), if the CASP is new enough to
+    /// record one (version 34 and up). The game hides parts from packs the player doesn't own.
+    pub fn pack_id(&self) -> Result<Option<i16>> {
+        if self.version < 34 {
+            return Ok(None);
+        }
+        let mut cursor = Cursor::new(&self.raw_data);
+        self.seek_past_flags(&mut cursor)?;
+        Ok(Some(cursor.read_le::<i16>()?))
+    }
+
+    /// The swatch colors CAS shows for the part, as ARGB
+    pub fn swatch_colors(&self) -> Result<Vec<u32>> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        self.seek_past_flags(&mut cursor)?;
+        if self.version >= 34 {
+            // Pack ID, pack flags, reserved
+            cursor.seek(SeekFrom::Current(2 + 1 + 9))?;
+        } else if cursor.read_le::<u8>()? > 0 {
+            cursor.seek(SeekFrom::Current(1))?;
+        }
+        let count = cursor.read_le::<u8>()?;
+        (0..count).map(|_| Ok(cursor.read_le::<u32>()?)).collect()
+    }
+
+    /// The part's tags, as category and value
+    pub fn tags(&self) -> Result<Vec<(u16, u32)>> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        self.seek_to_tags(&mut cursor)?;
+        let count = cursor.read_le::<u32>()?;
+        (0..count)
+            .map(|_| {
+                let category = cursor.read_le::<u16>()?;
+                let value = if self.version >= 37 { cursor.read_le::<u32>()? } else { cursor.read_le::<u16>()? as u32 };
+                Ok((category, value))
+            })
+            .collect()
+    }
+
+    /// Where the TGI list starts: the offset after the version counts from the end of that field
+    fn tgi_list_start(&self) -> Result<usize> {
+        let offset = self.raw_data.get(4..8)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| Error::InvalidResource("CASP is too short to have a TGI list".to_string()))?;
+        if 8 + offset >= self.raw_data.len() {
+            return Err(Error::InvalidResource(format!("CASP TGI list offset {} is past the end of the data", offset)));
+        }
+        Ok(8 + offset)
+    }
+
+    /// Replaces `range` of the data, which has to come before the TGI list, with `bytes`, and
+    /// moves the TGI list offset to match. Everything else is kept byte for byte.
+    fn splice(&mut self, range: std::ops::Range<usize>, bytes: &[u8]) -> Result<()> {
+        let start = self.tgi_list_start()?;
+        if range.end > start {
+            return Err(Error::InvalidResource("CASP field overlaps the TGI list".to_string()));
+        }
+        let offset = u32::try_from(start - 8 - range.len() + bytes.len())
+            .map_err(|_| Error::InvalidInput("CASP is too large".to_string()))?;
+        self.raw_data.splice(range, bytes.iter().copied());
+        self.raw_data[4..8].copy_from_slice(&offset.to_le_bytes());
+        Ok(())
+    }
+
+    /// Renames the part
+    pub fn set_name(&mut self, name: &str) -> Result<()> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        Self::read_name(&mut cursor)?;
+        let end = cursor.position() as usize;
+
+        let units: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        let mut bytes = Vec::with_capacity(units.len() + 5);
+        let mut len = units.len();
+        loop {
+            let byte = (len & 0x7F) as u8;
+            len >>= 7;
+            if len == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+        bytes.extend_from_slice(&units);
+        self.splice(12..end, &bytes)
+    }
+
+    /// Replaces the part's tags. Before version 37 values are 16-bit, so larger ones are refused.
+    pub fn set_tags(&mut self, tags: &[(u16, u32)]) -> Result<()> {
+        let mut cursor = Cursor::new(&self.raw_data);
+        self.seek_to_tags(&mut cursor)?;
+        let start = cursor.position() as usize;
+        let end = start + 4 + cursor.read_le::<u32>()? as usize * self.tag_size();
+
+        let mut bytes = Vec::with_capacity(4 + tags.len() * self.tag_size());
+        bytes.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+        for &(category, value) in tags {
+            bytes.extend_from_slice(&category.to_le_bytes());
+            if self.version >= 37 {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            } else {
+                let value = u16::try_from(value).map_err(|_| Error::InvalidInput(format!(
+                    "Tag value {} doesn't fit in the 16 bits version {} CASPs have", value, self.version,
+                )))?;
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        self.splice(start..end, &bytes)
+    }
+
+    /// Replaces the TGI list. The rest of the CASP refers to entries by their position, so the
+    /// new list has to start with the entries that are there; new ones go at the end. Use
+    /// [`set_tgi`](Self::set_tgi) to change an entry.
+    pub fn set_tgi_list(&mut self, tgis: &[TGI]) -> Result<()> {
+        let count = u8::try_from(tgis.len())
+            .map_err(|_| Error::InvalidInput(format!("A CASP can list at most 255 TGIs, not {}", tgis.len())))?;
+        let current = self.tgi_list()?;
+        if !tgis.starts_with(&current) {
+            return Err(Error::InvalidInput(format!(
+                "The new TGI list has to start with the {} entries already there, which the rest of the part refers to by position",
+                current.len(),
+            )));
+        }
+        let start = self.tgi_list_start()?;
+        let end = (start + 1 + self.raw_data[start] as usize * 16).min(self.raw_data.len());
+        let mut bytes = Vec::with_capacity(1 + tgis.len() * 16);
+        bytes.push(count);
+        for tgi in tgis {
+            bytes.extend_from_slice(&tgi.instance.to_le_bytes());
+            bytes.extend_from_slice(&tgi.res_group.to_le_bytes());
+            bytes.extend_from_slice(&tgi.res_type.to_le_bytes());
+        }
+        self.raw_data.splice(start..end, bytes);
+        Ok(())
+    }
+
+    /// Overwrites entry `index` of the TGI list in place
+    pub fn set_tgi(&mut self, index: usize, tgi: TGI) -> Result<()> {
+        let count = self.tgi_list()?.len();
+        if index >= count {
+            return Err(Error::InvalidResource(format!("TGI index {} out of range (list has {})", index, count)));
+        }
+        let offset = u32::from_le_bytes([self.raw_data[4], self.raw_data[5], self.raw_data[6], self.raw_data[7]]) as usize;
+        let start = 8 + offset + 1 + index * 16;
+        self.raw_data[start..start + 8].copy_from_slice(&tgi.instance.to_le_bytes());
+        self.raw_data[start + 8..start + 12].copy_from_slice(&tgi.res_group.to_le_bytes());
+        self.raw_data[start + 12..start + 16].copy_from_slice(&tgi.res_type.to_le_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "catalog")]
 impl Resource for CasPartResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -121,36 +447,58 @@ impl Resource for JazzResource {
     }
 }
 
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum TypedResource {
     NameMap(NameMapResource),
     Stbl(StblResource),
+    #[cfg(feature = "catalog")]
     ObjectDefinition(ObjectDefinitionResource),
+    #[cfg(feature = "tuning")]
     SimData(SimDataResource),
     Text(TextResource),
+    #[cfg(feature = "catalog")]
     Catalog(CatalogResource),
+    #[cfg(feature = "textures")]
     Rle(RleResource),
+    #[cfg(feature = "textures")]
     Dst(DstResource),
     Script(ScriptResource),
     Clip(ClipResource),
+    #[cfg(feature = "catalog")]
     CasPart(CasPartResource),
     Jazz(JazzResource),
+    #[cfg(feature = "meshes")]
     Rcol(RcolResource),
+    #[cfg(feature = "meshes")]
     Rig(RigResource),
     Lite(LiteResource),
     Thumbnail(ThumbnailResource),
     Complate(ComplateResource),
+    #[cfg(feature = "textures")]
     Txtc(TxtcResource),
     ObjKey(ObjKeyResource),
+    #[cfg(feature = "meshes")]
     SimModifier(SimModifierResource),
+    #[cfg(feature = "meshes")]
     Bone(BoneResource),
+    #[cfg(feature = "catalog")]
     Cwal(CwalResource),
+    #[cfg(feature = "catalog")]
     Cfnd(CfndResource),
+    #[cfg(feature = "catalog")]
     Cstr(CstrResource),
+    #[cfg(feature = "catalog")]
     Mtbl(MtblResource),
+    #[cfg(feature = "catalog")]
     Trim(TrimResource),
+    #[cfg(feature = "meshes")]
+    BlendGeometry(BlendGeometryResource),
+    #[cfg(feature = "meshes")]
     Geom(GeomResource),
     Manifest(ManifestResource),
     Xml(GenericStubResource),
+    #[cfg(feature = "audio")]
     Audio(GenericStubResource),
     Image(GenericStubResource),
     Binary(GenericStubResource),
@@ -180,105 +528,298 @@ impl GenericStubResource {
     }
 }
 
+/// The wrapper a resource type is parsed with, decided from the type ID alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    NameMap,
+    Stbl,
+    ObjectDefinition,
+    SimData,
+    Text,
+    Catalog,
+    Cwal,
+    Cfnd,
+    Cstr,
+    Rle,
+    Dst,
+    Script,
+    Clip,
+    CasPart,
+    Jazz,
+    Rcol,
+    Rig,
+    Lite,
+    Thumbnail,
+    Complate,
+    Txtc,
+    ObjKey,
+    SimModifier,
+    Bone,
+    Mtbl,
+    Trim,
+    BlendGeometry,
+    Manifest,
+    Xml,
+    Audio,
+    Image,
+    World,
+    Binary,
+    Generic,
+}
+
+/// The resource types each wrapper handles, the source of [`TypedResource::kind_of`]; anything
+/// else is [`ResourceKind::Generic`]
+pub const RESOURCE_KINDS: &[(ResourceKind, &[u32])] = &[
+    (ResourceKind::NameMap, &[0x0166038C, 0xF3A38370]),
+    (ResourceKind::Stbl, &[0x220557AA, 0x220557DA]),
+    (ResourceKind::ObjectDefinition, &[0xC0DB5AE7]),
+    (ResourceKind::SimData, &[0x545AC67A]),
+    (ResourceKind::Text, &[0x034AEECB, 0xE882D22F, 0x738E14F4, 0x6017E351]),
+    // Catalog resources
+    (ResourceKind::Catalog, &[
+        0x319E4F1D, 0x9F5CFF10, 0xB4F762C9, 0x07936CE0, 0x1D6DF1CF, 0xA057811C,
+        0xEBCBB16C, 0x1C1CF1F7, 0xE7ADA79D, 0xA5DFFCF3, 0x0418FE2A, 0xF1EDBD86,
+        0x3F0C529A, 0xB0311D0F, 0x84C23219, 0x74050B1F, 0x91EDBD3E, 0x48C28979,
+        0xA8F7B517,
+    ]),
+    (ResourceKind::Cwal, &[0xD5F0F921]),
+    (ResourceKind::Cfnd, &[0x2FAE983E]),
+    (ResourceKind::Cstr, &[0x9A20CD1C]),
+    (ResourceKind::Rle, &[0x3453CF95]),
+    (ResourceKind::Dst, &[0x00B2D882, 0xB6C8B6A0]),
+    (ResourceKind::Script, &[0x073FAA07]),
+    (ResourceKind::Clip, &[0x6B20C4F3]),
+    (ResourceKind::CasPart, &[0x034AE111]),
+    (ResourceKind::Jazz, &[0x02D5DF13]),
+    (ResourceKind::Rcol, &[0x015A1849, 0x01D0E75D, 0x01D10F34, 0x01661233]),
+    (ResourceKind::Rig, &[0x8EAF13DE]),
+    (ResourceKind::Lite, &[0x03B4C61D]),
+    (ResourceKind::Thumbnail, &[
+        0x0D338A3A, 0x16CCF748, 0x3BD45407, 0x3C1AF1F2, 0x3C2A8647, 0x5B282D45,
+        0xCD9DE247, 0xE18CAEE2, 0xE254AE6E, 0x0580A2B4, 0x0580A2B5, 0x0580A2B6,
+        0x0589DC44, 0x0589DC45, 0x0589DC46, 0x0589DC47, 0x05B17698, 0x05B17699,
+        0x05B1769A, 0x05B1B524, 0x05B1B525, 0x05B1B526, 0x2653E3C8, 0x2653E3C9,
+        0x2653E3CA, 0x2D4284F0, 0x2D4284F1, 0x2D4284F2, 0x5DE9DBA0, 0x5DE9DBA1,
+        0x5DE9DBA2, 0x626F60CC, 0x626F60CD, 0x626F60CE, 0x9C925813, 0xA1FF2FC4,
+        0xAD366F95, 0xAD366F96, 0xFCEAB65B,
+    ]),
+    (ResourceKind::Complate, &[0x044AE110]),
+    (ResourceKind::Txtc, &[0x033A1435, 0x0341ACC9]),
+    (ResourceKind::ObjKey, &[0x02DC343F]),
+    (ResourceKind::SimModifier, &[0xC5F6763E]),
+    (ResourceKind::Bone, &[0x00AE6C67]),
+    (ResourceKind::Mtbl, &[0x81CA1A10]),
+    (ResourceKind::Trim, &[0x76BCF80C]),
+    (ResourceKind::BlendGeometry, &[0x067CAA11]),
+    // Manifest stub
+    (ResourceKind::Manifest, &[0x73E93EEB, 0x7FB6AD8A]),
+    // Legacy stubs (XML/Text)
+    (ResourceKind::Xml, &[
+        0x0069453E, 0x0333406C, 0x03B33DDF, 0x03E9D964, 0x04D2B465, 0x074DFB83,
+        0x0C772E27, 0x0CA4C78B, 0x0E4D15FB, 0x0EEB823A, 0x11E72A63, 0x122FC66A,
+        0x12496650, 0x1A8506C5, 0x1B25A024, 0x1C12D458, 0x2451C101, 0x2553F435,
+        0x2673076D, 0x28B64675, 0x2C01BC15, 0x2C70ADF8, 0x2E47A104, 0x2F59B437,
+        0x31397645, 0x339BC5BD, 0x37B999F1, 0x37EF2EE7, 0x3F163505, 0x3FD6243E,
+        0x4115F9D5, 0x457FC032, 0x48C2D5ED, 0x48C75CE3, 0x49395302, 0x4DB8251E,
+        0x4F739CEE, 0x51077643, 0x51E7A18D, 0x54BD4618, 0x598F28E7, 0x5B02819E,
+        0x6017E896, 0x6224C9D6, 0x69A5DAA4, 0x6E0DDA9F, 0x6FA49828, 0x7147A350,
+        0x738E6C56, 0x73996BEB, 0x78559E9E, 0x7DF2169C, 0x800A3690, 0x86136AA5,
+        0x893E429C, 0x8FB3E0B1, 0x99CBC754, 0x99D98089, 0x9C07855F, 0x9CC21262,
+        0x9DB989FD, 0x9DDB5FDA, 0x9DF2F1F2, 0xA576C2E7, 0xAD6FDF1F, 0xAFADAC48,
+        0xB61DE6B4, 0xB7FF8F95, 0xB9881120, 0xBA7B60B8, 0xBE04173A, 0xC020FCAD,
+        0xC202C770, 0xC2CAA646, 0xC582D2FB, 0xCB5FDDC7, 0xD2DC5BAD, 0xD70DD79E,
+        0xD83892B7, 0xD8800D66, 0xDD057DCC, 0xDE6AD3CF, 0xDEBAFB73, 0xE04A24A3,
+        0xE06AE65E, 0xE0D75679, 0xE1477E18, 0xE231B3D8, 0xE24B5287, 0xE350DBD8,
+        0xE5105066, 0xE5105068, 0xE55EEACB, 0xE6BBD7DE, 0xEB97F823, 0xEC3DA10E,
+        0xEC6A8FC6, 0xEE17C6AD, 0xF3ABFF3C, 0xF93B40CF, 0xF958A092, 0xFA0FFA34,
+        0xFBC3AEEB,
+    ]),
+    // Legacy stubs (Audio)
+    (ResourceKind::Audio, &[0x01A527DB, 0x01EEF63A, 0xBDD82221, 0x01131757]),
+    // Legacy stubs (Image)
+    (ResourceKind::Image, &[
+        0x2E75C764, 0x2E75C765, 0x2E75C766, 0x2E75C767, 0x2F7D0004, 0x3F8662EA,
+        0xD84E7FC5, 0xD84E7FC6, 0xD84E7FC7,
+    ]),
+    // Legacy stubs (World)
+    (ResourceKind::World, &[
+        0x19301120, 0x1CC04273, 0x370EFD6E, 0x3924DE26, 0x9063660D, 0x9151E6BC,
+        0xDB43E069, 0xAC16FBEC, 0x025ED6F4, 0x0354796A, 0x71BDB8A2, 0xCF9A4ACE,
+    ]),
+    // Legacy stubs (Binary)
+    (ResourceKind::Binary, &[
+        0x00DE5AC5, 0x010FAF71, 0x02019972, 0x033260E3, 0x033B2B66, 0x0A227BCF,
+        0x105205BA, 0x12952634, 0x153D2219, 0x16CA6BC4, 0x17C0C281, 0x18F3C673,
+        0x1C99B344, 0x20D81496, 0x25796DCA, 0x26978421, 0x276CA4B9, 0x2A8A5E22,
+        0x2AD195F2, 0x3BF8FD86, 0x4F726BBE, 0x56278554, 0x5BE29703, 0x62E94D38,
+        0x62ECC59A, 0x6F40796A, 0x71A449C9, 0x729F6C4F, 0x78C8BCE4, 0x892C4B8A,
+        0x8B18FF6E, 0x91568FD8, 0x9917EACD, 0xA0451CBD, 0xAC03A936, 0xB0118C15,
+        0xB3C438F0, 0xBA856C78, 0xBC4A5044, 0xBC80ED59, 0xC71CA490, 0xD3044521,
+        0xD33C281E, 0xD382BF57, 0xD65DAFF9, 0xD99F5E5C, 0xD9BD0909, 0xEA5118B0,
+        0xEAA32ADD, 0xF0633989, 0xFD04E3BE,
+    ]),
+];
+
 impl TypedResource {
-    pub fn from_bytes(res_type: u32, data: &[u8]) -> Result<Self> {
-        match res_type {
-            // Already handled specific types (NameMap, Stbl, etc.)
-            0x0166038C | 0xF3A38370 => Ok(TypedResource::NameMap(NameMapResource::from_bytes(data)?)),
-            0x220557AA | 0x220557DA => Ok(TypedResource::Stbl(StblResource::from_bytes(data)?)),
-            0xC0DB5AE7 => Ok(TypedResource::ObjectDefinition(ObjectDefinitionResource::from_bytes(data)?)),
-            0x545AC67A => Ok(TypedResource::SimData(SimDataResource::from_bytes(data)?)),
-            0x034AEECB | 0xE882D22F | 0x738E14F4 | 0x6017E351 => Ok(TypedResource::Text(TextResource::from_bytes(data)?)),
-            
-            // Catalog resources
-            0x319E4F1D | 0x9F5CFF10 | 0xB4F762C9 | 0x07936CE0 | 0x1D6DF1CF | 0x2FAE983E |
-            0xA057811C | 0xEBCBB16C | 0x9A20CD1C | 0xD5F0F921 | 0x1C1CF1F7 | 0xE7ADA79D |
-            0xA5DFFCF3 | 0x0418FE2A | 0xF1EDBD86 | 0x3F0C529A | 0xB0311D0F | 0x84C23219 |
-            0x74050B1F | 0x91EDBD3E | 0x48C28979 | 0xA8F7B517 => {
-                match res_type {
-                    0xD5F0F921 => Ok(TypedResource::Cwal(CwalResource::from_bytes(data)?)),
-                    0x2FAE983E => Ok(TypedResource::Cfnd(CfndResource::from_bytes(data)?)),
-                    0x9A20CD1C => Ok(TypedResource::Cstr(CstrResource::from_bytes(data)?)),
-                    _ => Ok(TypedResource::Catalog(CatalogResource::from_bytes(data)?)),
-                }
+    /// Looks up which wrapper handles `res_type` without reading any data
+    pub fn kind_of(res_type: u32) -> ResourceKind {
+        static KINDS: std::sync::OnceLock<HashMap<u32, ResourceKind>> = std::sync::OnceLock::new();
+        let kinds = KINDS.get_or_init(|| RESOURCE_KINDS.iter()
+            .flat_map(|&(kind, types)| types.iter().map(move |&res_type| (res_type, kind)))
+            .collect());
+        kinds.get(&res_type).copied().unwrap_or(ResourceKind::Generic)
+    }
+
+    /// The file extension to save a resource of `res_type` with, from its wrapper and, for
+    /// images and untyped resources, the signature of its decompressed `data`
+    pub fn file_extension(res_type: u32, data: &[u8]) -> &'static str {
+        #[cfg(feature = "textures")]
+        {
+            use crate::texture::{detect_format, TextureFormat};
+            // DST textures have a DDS header too, with a FourCC that says they are shuffled
+            match detect_format(data) {
+                TextureFormat::Dds => return "dds",
+                TextureFormat::Dst => return "dst",
+                TextureFormat::Rle2 => return "rle2",
+                TextureFormat::Rles => return "rles",
+                TextureFormat::Lrle => return "lrle",
+                TextureFormat::Unknown => {}
             }
-            0x3453CF95 => Ok(TypedResource::Rle(RleResource::from_bytes(data)?)),
-            0x00B2D882 | 0xB6C8B6A0 => Ok(TypedResource::Dst(DstResource::from_bytes(data)?)),
-            0x073FAA07 => Ok(TypedResource::Script(ScriptResource::from_bytes(data)?)),
-            0x6B20C4F3 => Ok(TypedResource::Clip(ClipResource::from_bytes(data)?)),
-            0x034AE111 => Ok(TypedResource::CasPart(CasPartResource::from_bytes(data)?)),
-            0x02D5DF13 => Ok(TypedResource::Jazz(JazzResource::from_bytes(data)?)),
-            0x015A1849 | 0x01D0E75D | 0x01D10F34 | 0x01661233 => Ok(TypedResource::Rcol(RcolResource::from_bytes(data)?)),
-            0x8EAF13DE => Ok(TypedResource::Rig(RigResource::from_bytes(data)?)),
-            0x03B4C61D => Ok(TypedResource::Lite(LiteResource::from_bytes(data)?)),
-            0x0D338A3A | 0x16CCF748 | 0x3BD45407 | 0x3C1AF1F2 | 0x3C2A8647 | 0x5B282D45 | 
-            0xCD9DE247 | 0xE18CAEE2 | 0xE254AE6E | 0x0580A2B4 | 0x0580A2B5 | 0x0580A2B6 |
-            0x0589DC44 | 0x0589DC45 | 0x0589DC46 | 0x0589DC47 | 0x05B17698 | 0x05B17699 |
-            0x05B1769A | 0x05B1B524 | 0x05B1B525 | 0x05B1B526 | 0x2653E3C8 | 0x2653E3C9 |
-            0x2653E3CA | 0x2D4284F0 | 0x2D4284F1 | 0x2D4284F2 | 0x5DE9DBA0 | 0x5DE9DBA1 |
-            0x5DE9DBA2 | 0x626F60CC | 0x626F60CD | 0x626F60CE | 0x9C925813 | 0xA1FF2FC4 |
-            0xAD366F95 | 0xAD366F96 | 0xFCEAB65B => Ok(TypedResource::Thumbnail(ThumbnailResource::from_bytes(data)?)),
-            0x044AE110 => Ok(TypedResource::Complate(ComplateResource::from_bytes(data)?)),
-            0x033A1435 | 0x0341ACC9 => Ok(TypedResource::Txtc(TxtcResource::from_bytes(data)?)),
-            0x02DC343F => Ok(TypedResource::ObjKey(ObjKeyResource::from_bytes(data)?)),
-            0xC5F6763E => Ok(TypedResource::SimModifier(SimModifierResource::from_bytes(data)?)),
-            0x00AE6C67 => Ok(TypedResource::Bone(BoneResource::from_bytes(data)?)),
-            0x81CA1A10 => Ok(TypedResource::Mtbl(MtblResource::from_bytes(data)?)),
-            0x76BCF80C => Ok(TypedResource::Trim(TrimResource::from_bytes(data)?)),
-
-            // Manifest stub
-            0x73E93EEB | 0x7FB6AD8A => Ok(TypedResource::Manifest(ManifestResource::from_bytes(data)?)),
-
-            // Legacy stubs (XML/Text)
-            0x0069453E | 0x0333406C | 0x03B33DDF | 0x03E9D964 | 0x04D2B465 | 0x074DFB83 |
-            0x0C772E27 | 0x0CA4C78B | 0x0E4D15FB | 0x0EEB823A | 0x11E72A63 | 0x122FC66A |
-            0x12496650 | 0x1A8506C5 | 0x1B25A024 | 0x1C12D458 | 0x2451C101 | 0x2553F435 |
-            0x2673076D | 0x28B64675 | 0x2C01BC15 | 0x2C70ADF8 | 0x2E47A104 | 0x2F59B437 |
-            0x31397645 | 0x339BC5BD | 0x37B999F1 | 0x37EF2EE7 | 0x3F163505 | 0x3FD6243E |
-            0x4115F9D5 | 0x457FC032 | 0x48C2D5ED | 0x48C75CE3 | 0x49395302 | 0x4DB8251E |
-            0x4F739CEE | 0x51077643 | 0x51E7A18D | 0x54BD4618 | 0x598F28E7 | 0x5B02819E |
-            0x6017E896 | 0x6224C9D6 | 0x69A5DAA4 | 0x6E0DDA9F | 0x6FA49828 | 0x7147A350 |
-            0x738E6C56 | 0x73996BEB | 0x78559E9E | 0x7DF2169C | 0x800A3690 | 0x86136AA5 |
-            0x893E429C | 0x8FB3E0B1 | 0x99CBC754 | 0x99D98089 | 0x9C07855F | 0x9CC21262 |
-            0x9DB989FD | 0x9DDB5FDA | 0x9DF2F1F2 | 0xA576C2E7 | 0xAD6FDF1F | 0xAFADAC48 |
-            0xB61DE6B4 | 0xB7FF8F95 | 0xB9881120 | 0xBA7B60B8 | 0xBE04173A | 0xC020FCAD |
-            0xC202C770 | 0xC2CAA646 | 0xC582D2FB | 0xCB5FDDC7 | 0xD2DC5BAD | 0xD70DD79E |
-            0xD83892B7 | 0xD8800D66 | 0xDD057DCC | 0xDE6AD3CF | 0xDEBAFB73 |
-            0xE04A24A3 | 0xE06AE65E | 0xE0D75679 | 0xE1477E18 | 0xE231B3D8 | 0xE24B5287 |
-            0xE350DBD8 | 0xE5105066 | 0xE5105068 | 0xE55EEACB | 0xE6BBD7DE | 0xEB97F823 |
-            0xEC3DA10E | 0xEC6A8FC6 | 0xEE17C6AD | 0xF3ABFF3C | 0xF93B40CF | 0xF958A092 |
-            0xFA0FFA34 | 0xFBC3AEEB => Ok(TypedResource::Xml(GenericStubResource::from_bytes_with_type(res_type, data)?)),
-
-            // Legacy stubs (Audio)
-            0x01A527DB | 0x01EEF63A | 0xBDD82221 | 0x01131757 => Ok(TypedResource::Audio(GenericStubResource::from_bytes_with_type(res_type, data)?)),
-
-            // Legacy stubs (Image)
-            0x2E75C764 | 0x2E75C765 | 0x2E75C766 | 0x2E75C767 | 0x2F7D0004 | 0x3F8662EA |
-            0xD84E7FC5 | 0xD84E7FC6 | 0xD84E7FC7 => Ok(TypedResource::Image(GenericStubResource::from_bytes_with_type(res_type, data)?)),
-
-            // Legacy stubs (World)
-            0x19301120 | 0x1CC04273 | 0x370EFD6E | 0x3924DE26 | 0x9063660D | 0x9151E6BC |
-            0xDB43E069 | 0xAC16FBEC | 0x025ED6F4 | 0x0354796A | 0x71BDB8A2 | 0xCF9A4ACE => Ok(TypedResource::World(GenericStubResource::from_bytes_with_type(res_type, data)?)),
-
-            // Legacy stubs (Binary)
-            0x00DE5AC5 | 0x010FAF71 | 0x02019972 | 0x033260E3 | 0x033B2B66 | 0x067CAA11 |
-            0x0A227BCF | 0x105205BA | 0x12952634 | 0x153D2219 | 0x16CA6BC4 |
-            0x17C0C281 | 0x18F3C673 | 0x1C99B344 | 0x20D81496 | 0x25796DCA |
-            0x26978421 | 0x276CA4B9 | 0x2A8A5E22 | 0x2AD195F2 | 0x3BF8FD86 |
-            0x4F726BBE | 0x56278554 | 0x5BE29703 | 0x62E94D38 | 0x62ECC59A |
-            0x6F40796A | 0x71A449C9 | 0x729F6C4F | 0x78C8BCE4 | 
-            0x892C4B8A | 0x8B18FF6E | 0x91568FD8 | 0x9917EACD | 0xA0451CBD |
-            0xAC03A936 | 0xB0118C15 | 
-            0xB3C438F0 | 0xBA856C78 | 0xBC4A5044 | 0xBC80ED59 | 
-            0xC71CA490 | 0xD3044521 | 0xD33C281E | 0xD382BF57 | 0xD65DAFF9 | 0xD99F5E5C |
-            0xD9BD0909 | 0xEA5118B0 | 0xEAA32ADD | 0xF0633989 | 
-            0xFD04E3BE => Ok(TypedResource::Binary(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+        }
+        match Self::kind_of(res_type) {
+            ResourceKind::Xml | ResourceKind::Text => "xml",
+            ResourceKind::Stbl => "stbl",
+            ResourceKind::SimData => "simdata",
+            ResourceKind::Thumbnail | ResourceKind::Image => crate::thumbcache::thumbnail_extension(data),
+            _ if crate::tuning::tuning_class(res_type).is_some() => "xml",
+            ResourceKind::Generic => match crate::thumbcache::thumbnail_extension(data) {
+                "bin" if crate::tuning::looks_like_xml(data) => "xml",
+                extension => extension,
+            },
+            _ => "bin",
+        }
+    }
 
+    pub fn from_bytes(res_type: u32, data: &[u8]) -> Result<Self> {
+        match Self::kind_of(res_type) {
+            ResourceKind::NameMap => Ok(TypedResource::NameMap(NameMapResource::from_bytes(data)?)),
+            ResourceKind::Stbl => Ok(TypedResource::Stbl(StblResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::ObjectDefinition => Ok(TypedResource::ObjectDefinition(ObjectDefinitionResource::from_bytes(data)?)),
+            #[cfg(feature = "tuning")]
+            ResourceKind::SimData => Ok(TypedResource::SimData(SimDataResource::from_bytes(data)?)),
+            ResourceKind::Text => Ok(TypedResource::Text(TextResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Catalog => Ok(TypedResource::Catalog(CatalogResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Cwal => Ok(TypedResource::Cwal(CwalResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Cfnd => Ok(TypedResource::Cfnd(CfndResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Cstr => Ok(TypedResource::Cstr(CstrResource::from_bytes(data)?)),
+            #[cfg(feature = "textures")]
+            ResourceKind::Rle => Ok(TypedResource::Rle(RleResource::from_bytes(data)?)),
+            #[cfg(feature = "textures")]
+            ResourceKind::Dst => Ok(TypedResource::Dst(DstResource::from_bytes(data)?)),
+            ResourceKind::Script => Ok(TypedResource::Script(ScriptResource::from_bytes(data)?)),
+            ResourceKind::Clip => Ok(TypedResource::Clip(ClipResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::CasPart => Ok(TypedResource::CasPart(CasPartResource::from_bytes(data)?)),
+            ResourceKind::Jazz => Ok(TypedResource::Jazz(JazzResource::from_bytes(data)?)),
+            #[cfg(feature = "meshes")]
+            ResourceKind::Rcol => Ok(TypedResource::Rcol(RcolResource::from_bytes(data)?)),
+            #[cfg(feature = "meshes")]
+            ResourceKind::Rig => Ok(TypedResource::Rig(RigResource::from_bytes(data)?)),
+            ResourceKind::Lite => Ok(TypedResource::Lite(LiteResource::from_bytes(data)?)),
+            ResourceKind::Thumbnail => Ok(TypedResource::Thumbnail(ThumbnailResource::from_bytes_with_type(res_type, data)?)),
+            ResourceKind::Complate => Ok(TypedResource::Complate(ComplateResource::from_bytes(data)?)),
+            #[cfg(feature = "textures")]
+            ResourceKind::Txtc => Ok(TypedResource::Txtc(TxtcResource::from_bytes(data)?)),
+            ResourceKind::ObjKey => Ok(TypedResource::ObjKey(ObjKeyResource::from_bytes(data)?)),
+            #[cfg(feature = "meshes")]
+            ResourceKind::SimModifier => Ok(TypedResource::SimModifier(SimModifierResource::from_bytes(data)?)),
+            #[cfg(feature = "meshes")]
+            ResourceKind::Bone => Ok(TypedResource::Bone(BoneResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Mtbl => Ok(TypedResource::Mtbl(MtblResource::from_bytes(data)?)),
+            #[cfg(feature = "catalog")]
+            ResourceKind::Trim => Ok(TypedResource::Trim(TrimResource::from_bytes(data)?)),
+            #[cfg(feature = "meshes")]
+            ResourceKind::BlendGeometry => Ok(TypedResource::BlendGeometry(BlendGeometryResource::from_bytes(data)?)),
+            ResourceKind::Manifest => Ok(TypedResource::Manifest(ManifestResource::from_bytes(data)?)),
+            ResourceKind::Xml => Ok(TypedResource::Xml(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+            #[cfg(feature = "audio")]
+            ResourceKind::Audio => Ok(TypedResource::Audio(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+            ResourceKind::Image => Ok(TypedResource::Image(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+            ResourceKind::World => Ok(TypedResource::World(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+            ResourceKind::Binary => Ok(TypedResource::Binary(GenericStubResource::from_bytes_with_type(res_type, data)?)),
+            // Generic, and kinds whose parser is left out of this build
             _ => Ok(TypedResource::Generic(GenericResource::from_bytes(data)?)),
         }
     }
+
+    /// The resource's bytes, as it would be stored decompressed in a package
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            TypedResource::NameMap(r) => r.to_bytes(),
+            TypedResource::Stbl(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::ObjectDefinition(r) => r.to_bytes(),
+            #[cfg(feature = "tuning")]
+            TypedResource::SimData(r) => r.to_bytes(),
+            TypedResource::Text(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Catalog(r) => r.to_bytes(),
+            #[cfg(feature = "textures")]
+            TypedResource::Rle(r) => r.to_bytes(),
+            #[cfg(feature = "textures")]
+            TypedResource::Dst(r) => r.to_bytes(),
+            TypedResource::Script(r) => r.to_bytes(),
+            TypedResource::Clip(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::CasPart(r) => r.to_bytes(),
+            TypedResource::Jazz(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::Rcol(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::Rig(r) => r.to_bytes(),
+            TypedResource::Lite(r) => r.to_bytes(),
+            TypedResource::Thumbnail(r) => r.to_bytes(),
+            TypedResource::Complate(r) => r.to_bytes(),
+            #[cfg(feature = "textures")]
+            TypedResource::Txtc(r) => r.to_bytes(),
+            TypedResource::ObjKey(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::SimModifier(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::Bone(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Cwal(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Cfnd(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Cstr(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Mtbl(r) => r.to_bytes(),
+            #[cfg(feature = "catalog")]
+            TypedResource::Trim(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::BlendGeometry(r) => r.to_bytes(),
+            #[cfg(feature = "meshes")]
+            TypedResource::Geom(r) => r.to_bytes(),
+            TypedResource::Manifest(r) => r.to_bytes(),
+            TypedResource::Xml(r) => r.to_bytes(),
+            #[cfg(feature = "audio")]
+            TypedResource::Audio(r) => r.to_bytes(),
+            TypedResource::Image(r) => r.to_bytes(),
+            TypedResource::Binary(r) => r.to_bytes(),
+            TypedResource::World(r) => r.to_bytes(),
+            TypedResource::Generic(r) => r.to_bytes(),
+        }
+    }
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -291,6 +832,7 @@ pub struct ColorList {
     pub colors: Vec<u32>,
 }
 
+#[cfg(feature = "catalog")]
 /// Wall resource (0xD5F0F921)
 #[binrw]
 #[derive(Debug)]
@@ -306,6 +848,7 @@ pub struct CwalResource {
     pub unk_iid01: u64,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -318,6 +861,7 @@ pub struct WallMATDEntryList {
     pub entries: Vec<WallMATDEntry>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -327,6 +871,7 @@ pub struct WallMATDEntry {
     pub matd_ref: TGI,   // Order is ITG in C# but TGIBlock default is usually ITG for catalog
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -339,6 +884,7 @@ pub struct WallImgGroupList {
     pub entries: Vec<WallImgGroup>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -348,6 +894,7 @@ pub struct WallImgGroup {
     pub img_ref: TGI,
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for CwalResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -362,6 +909,7 @@ impl Resource for CwalResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// Foundation resource (0x2FAE983E)
 #[binrw]
 #[derive(Debug)]
@@ -382,6 +930,7 @@ pub struct CfndResource {
     pub colors: ColorList,
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for CfndResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -396,6 +945,7 @@ impl Resource for CfndResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// Stairs resource (0x9A20CD1C)
 #[binrw]
 #[derive(Debug)]
@@ -418,6 +968,7 @@ pub struct CstrResource {
     pub unk05: u8,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -431,6 +982,7 @@ pub struct CstrReferences {
     pub obj_ref: TGI,
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for CstrResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -445,6 +997,7 @@ impl Resource for CstrResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// Material Table resource (0x81CA1A10)
 #[binrw]
 #[derive(Debug)]
@@ -455,6 +1008,7 @@ pub struct MtblResource {
     pub entries: MtblEntryList,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -467,6 +1021,7 @@ pub struct MtblEntryList {
     pub entries: Vec<MtblEntry>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -488,6 +1043,7 @@ pub struct MtblEntry {
     pub vfx_hash: u64,
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for MtblResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -502,6 +1058,7 @@ impl Resource for MtblResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// Trim resource (0x76BCF80C)
 #[binrw]
 #[derive(Debug)]
@@ -515,12 +1072,14 @@ pub struct TrimResource {
     pub has_footprint: u8,
 }
 
+#[cfg(feature = "catalog")]
 #[derive(Debug)]
 pub enum TrimEntryList {
     V3(Vec<TrimPt3Entry>),
     V4(Vec<TrimPt4Entry>),
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -531,6 +1090,7 @@ pub struct TrimPt3Entry {
     pub v: f32,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -542,6 +1102,7 @@ pub struct TrimPt4Entry {
     pub mapping_mode: f32,
 }
 
+#[cfg(feature = "catalog")]
 impl BinRead for TrimEntryList {
     type Args<'a> = (u32,);
 
@@ -568,6 +1129,7 @@ impl BinRead for TrimEntryList {
     }
 }
 
+#[cfg(feature = "catalog")]
 impl BinWrite for TrimEntryList {
     type Args<'a> = (u32,);
 
@@ -595,6 +1157,7 @@ impl BinWrite for TrimEntryList {
     }
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for TrimResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -609,6 +1172,7 @@ impl Resource for TrimResource {
     }
 }
 
+#[cfg(feature = "meshes")]
 /// RCOL (Resource Collection) base wrapper
 #[derive(Debug)]
 pub struct RcolResource {
@@ -619,6 +1183,7 @@ pub struct RcolResource {
     pub chunks: Vec<RcolChunk>,
 }
 
+#[cfg(feature = "meshes")]
 #[derive(Debug)]
 pub struct RcolChunk {
     pub tgi: TGI,
@@ -626,6 +1191,7 @@ pub struct RcolChunk {
     pub data: Vec<u8>,
 }
 
+#[cfg(feature = "meshes")]
 impl Resource for RcolResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -636,13 +1202,13 @@ impl Resource for RcolResource {
         let count_chunks = cursor.read_le::<i32>()?;
 
         if count_resources < 0 || count_chunks < 0 {
-            return Err(anyhow::anyhow!("Invalid RCOL header: negative count"));
+            return Err(Error::InvalidResource("Invalid RCOL header: negative count".to_string()));
         }
 
         // Basic sanity check: each resource/chunk entry takes at least some bytes
         let data_len = data.len();
         if (count_resources as usize * 16) > data_len || (count_chunks as usize * 28) > data_len {
-             return Err(anyhow::anyhow!("Invalid RCOL header: count too large for data size"));
+             return Err(Error::InvalidResource("Invalid RCOL header: count too large for data size".to_string()));
         }
 
         let mut chunk_tgis = Vec::with_capacity(count_chunks as usize);
@@ -684,10 +1250,10 @@ impl Resource for RcolResource {
             let tgi = chunk_tgis[i];
 
             if len < 0 {
-                return Err(anyhow::anyhow!("Invalid RCOL chunk length: {}", len));
+                return Err(Error::InvalidResource(format!("Invalid RCOL chunk length: {}", len)));
             }
             if pos as u64 + len as u64 > data_len as u64 {
-                return Err(anyhow::anyhow!("RCOL chunk extends beyond data bounds: pos={}, len={}", pos, len));
+                return Err(Error::InvalidResource(format!("RCOL chunk extends beyond data bounds: pos={}, len={}", pos, len)));
             }
 
             cursor.seek(SeekFrom::Start(pos as u64))?;
@@ -715,13 +1281,43 @@ impl Resource for RcolResource {
         })
     }
 
+    /// Writes the header and tables, then each chunk padded to 4 bytes, with the chunk index
+    /// recomputed from where the chunks land
     fn to_bytes(&self) -> Result<Vec<u8>> {
-        // Implementation of RCOL serialization would be complex due to offset management.
-        // For investigation purposes, we might not need to write it back yet.
-        Err(anyhow::anyhow!("RcolResource writing not yet implemented"))
+        let count = |n: usize, what: &str| i32::try_from(n).map_err(|_| Error::InvalidInput(format!("Too many RCOL {}: {}", what, n)));
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_le(&self.version)?;
+        cursor.write_le(&self.public_chunks)?;
+        cursor.write_le(&self.unused)?;
+        cursor.write_le(&count(self.external_resources.len(), "external resources")?)?;
+        cursor.write_le(&count(self.chunks.len(), "chunks")?)?;
+        for tgi in self.chunks.iter().map(|c| &c.tgi).chain(&self.external_resources) {
+            cursor.write_le(&tgi.res_type)?;
+            cursor.write_le(&tgi.res_group)?;
+            cursor.write_le(&tgi.instance)?;
+        }
+
+        let index_start = cursor.position() as usize;
+        let mut position = index_start + self.chunks.len() * 8;
+        for chunk in &self.chunks {
+            let length = i32::try_from(chunk.data.len())
+                .map_err(|_| Error::InvalidInput(format!("RCOL chunk {} is too large", chunk.tag)))?;
+            let start = u32::try_from(position)
+                .map_err(|_| Error::InvalidInput("RCOL chunks don't fit in 4 GB".to_string()))?;
+            cursor.write_le(&start)?;
+            cursor.write_le(&length)?;
+            position = (position + chunk.data.len()).next_multiple_of(4);
+        }
+        for chunk in &self.chunks {
+            cursor.write_all(&chunk.data)?;
+            let padding = (cursor.position() as usize).next_multiple_of(4) - cursor.position() as usize;
+            cursor.write_all(&[0u8; 3][..padding])?;
+        }
+        Ok(cursor.into_inner())
     }
 }
 
+#[cfg(feature = "meshes")]
 /// Rig resource (0x8EAF13DE)
 #[derive(Debug)]
 pub struct RigResource {
@@ -729,6 +1325,7 @@ pub struct RigResource {
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "meshes")]
 impl Resource for RigResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -750,6 +1347,175 @@ impl Resource for RigResource {
     }
 }
 
+#[cfg(feature = "meshes")]
+/// A single joint of a skeleton as stored in a clear-format RIG
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigBone {
+    pub position: [f32; 3],
+    /// Rotation quaternion (x, y, z, w)
+    pub orientation: [f32; 4],
+    pub scale: [f32; 3],
+    pub name: String,
+    /// Index of the mirrored bone (e.g. left hand for right hand)
+    pub opposite_index: i32,
+    /// Index of the parent bone, -1 for the root
+    pub parent_index: i32,
+    /// FNV-32 hash of the name; what GEOM bone hash lists refer to
+    pub hash: u32,
+    pub flags: u32,
+}
+
+#[cfg(feature = "meshes")]
+impl RigResource {
+    pub fn is_clear(&self) -> bool {
+        self.format == "Clear"
+    }
+
+    /// Reads the bone list of a clear-format rig. Granny-format rigs are not supported.
+    pub fn bones(&self) -> Result<Vec<RigBone>> {
+        self.read_bones().map(|(_, bones)| bones)
+    }
+
+    /// Reads the whole of a clear-format rig: its bones, the skeleton name (major version 4) and
+    /// its IK chains. Granny-format rigs are not supported.
+    pub fn skeleton(&self) -> Result<RigSkeleton> {
+        let (mut cursor, bones) = self.read_bones()?;
+        let bones_end = cursor.position();
+        cursor.set_position(0);
+        let major = cursor.read_le::<u32>()?;
+        let minor = cursor.read_le::<u32>()?;
+        cursor.set_position(bones_end);
+
+        let name = if major >= 4 {
+            Some(self.read_string(&mut cursor)?)
+        } else {
+            None
+        };
+        let count = cursor.read_le::<u32>()?;
+        if count as usize > self.raw_data.len() / 64 {
+            return Err(Error::InvalidResource(format!("Invalid RIG IK chain count {}", count)));
+        }
+        let mut ik_chains = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let bone_count = cursor.read_le::<u32>()?;
+            if bone_count as usize > self.raw_data.len() / 4 {
+                return Err(Error::InvalidResource(format!("Invalid RIG IK chain bone count {}", bone_count)));
+            }
+            let chain_bones = (0..bone_count).map(|_| cursor.read_le::<i32>()).collect::<binrw::BinResult<Vec<_>>>()?;
+            ik_chains.push(RigIkChain {
+                bones: chain_bones,
+                info_nodes: cursor.read_le::<[i32; 11]>()?,
+                pole: cursor.read_le::<i32>()?,
+                slot_info: cursor.read_le::<i32>()?,
+                slot_offset: cursor.read_le::<i32>()?,
+                root: cursor.read_le::<i32>()?,
+            });
+        }
+        Ok(RigSkeleton { major, minor, name, bones, ik_chains })
+    }
+
+    fn read_string(&self, cursor: &mut Cursor<&Vec<u8>>) -> Result<String> {
+        let len = cursor.read_le::<u32>()? as usize;
+        if len > self.raw_data.len() {
+            return Err(Error::InvalidResource(format!("Invalid RIG name length {}", len)));
+        }
+        let mut name = vec![0u8; len];
+        cursor.read_exact(&mut name)?;
+        Ok(String::from_utf8_lossy(&name).into_owned())
+    }
+
+    /// Reads the bones, returning them with the cursor left just after the bone list
+    fn read_bones(&self) -> Result<(Cursor<&Vec<u8>>, Vec<RigBone>)> {
+        if !self.is_clear() {
+            return Err(Error::Unsupported(format!("{} rigs are not supported", self.format)));
+        }
+        let mut cursor = Cursor::new(&self.raw_data);
+        cursor.seek(SeekFrom::Start(8))?;
+        let count = cursor.read_le::<u32>()?;
+        if count as usize > self.raw_data.len() / 48 {
+            return Err(Error::InvalidResource(format!("Invalid RIG bone count {}", count)));
+        }
+
+        let mut bones = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let position = cursor.read_le::<[f32; 3]>()?;
+            let orientation = cursor.read_le::<[f32; 4]>()?;
+            let scale = cursor.read_le::<[f32; 3]>()?;
+            let name = self.read_string(&mut cursor)?;
+            bones.push(RigBone {
+                position,
+                orientation,
+                scale,
+                name,
+                opposite_index: cursor.read_le::<i32>()?,
+                parent_index: cursor.read_le::<i32>()?,
+                hash: cursor.read_le::<u32>()?,
+                flags: cursor.read_le::<u32>()?,
+            });
+        }
+        Ok((cursor, bones))
+    }
+}
+
+#[cfg(feature = "meshes")]
+/// An inverse kinematics chain of a clear-format RIG. Every field is a bone index, -1 for none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigIkChain {
+    /// The bones the chain moves, from the end effector up
+    pub bones: Vec<i32>,
+    pub info_nodes: [i32; 11],
+    pub pole: i32,
+    pub slot_info: i32,
+    pub slot_offset: i32,
+    pub root: i32,
+}
+
+#[cfg(feature = "meshes")]
+/// Everything a clear-format RIG holds, as read by [`RigResource::skeleton`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigSkeleton {
+    pub major: u32,
+    pub minor: u32,
+    /// The skeleton's name (e.g. `yfRig`); only major version 4 rigs have one
+    pub name: Option<String>,
+    pub bones: Vec<RigBone>,
+    pub ik_chains: Vec<RigIkChain>,
+}
+
+#[cfg(feature = "meshes")]
+impl RigSkeleton {
+    /// Bones with no parent (or a parent index that points nowhere or at the bone itself)
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.bones.len()).filter(|&i| self.parent(i).is_none()).collect()
+    }
+
+    pub fn parent(&self, bone: usize) -> Option<usize> {
+        let parent = self.bones.get(bone)?.parent_index;
+        (parent >= 0 && (parent as usize) < self.bones.len() && parent as usize != bone).then_some(parent as usize)
+    }
+
+    /// Bones whose parent is `bone`, in rig order
+    pub fn children(&self, bone: usize) -> Vec<usize> {
+        (0..self.bones.len()).filter(|&i| self.parent(i) == Some(bone)).collect()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|b| b.name == name)
+    }
+
+    /// Bones in depth-first order from each root, with their depth in the tree. Bones caught in a
+    /// parent loop are never reached from a root and are left out.
+    pub fn walk(&self) -> Vec<(usize, usize)> {
+        let mut order = Vec::with_capacity(self.bones.len());
+        let mut stack: Vec<(usize, usize)> = self.roots().into_iter().rev().map(|i| (i, 0)).collect();
+        while let Some((bone, depth)) = stack.pop() {
+            order.push((bone, depth));
+            stack.extend(self.children(bone).into_iter().rev().map(|i| (i, depth + 1)));
+        }
+        order
+    }
+}
+
 /// Lite resource (0x03B4C61D)
 #[derive(Debug)]
 pub struct LiteResource {
@@ -770,22 +1536,137 @@ impl Resource for LiteResource {
     }
 }
 
-/// SimData resource (0x545AC67A)
+#[cfg(feature = "tuning")]
+/// SimData resource (0x545AC67A): the binary half of a tuning resource, which the game reads
+/// instead of the XML for many classes
+///
+/// The tables and schemas are read on load; [`instances`](Self::instances) decodes the objects
+/// in them. The data is written back as it was read.
 #[derive(Debug)]
 pub struct SimDataResource {
     pub version: u32,
+    pub tables: Vec<SimDataTable>,
+    pub schemas: Vec<SimDataSchema>,
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "tuning")]
+impl SimDataResource {
+    /// Lays out new SimData holding `instances`, each with the name of its table. Objects are
+    /// matched to `schemas` by name, and each schema's columns keep the offsets they're given.
+    pub fn build(version: u32, header_word: u32, schemas: &[SimDataSchema], instances: &[(String, SimDataObject)]) -> Result<Self> {
+        Self::from_bytes(&SimDataWriter::write(version, header_word, schemas, instances)?)
+    }
+
+    /// The extra header word of version 0x101 and later
+    pub fn header_word(&self) -> Option<u32> {
+        self.raw_data.get(24..28).filter(|_| self.version >= 0x101).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// The objects in the named tables, which are the instances the tuning defines, each with
+    /// its table's name
+    pub fn instances(&self) -> Result<Vec<(String, SimDataObject)>> {
+        let reader = self.reader();
+        let mut instances = Vec::new();
+        for table in &self.tables {
+            let (Some(name), Some(schema)) = (&table.name, table.schema) else { continue };
+            for row in 0..table.row_count as usize {
+                let object = reader.object(schema, table.data_offset + row * table.row_size as usize, 0)?;
+                instances.push((name.clone(), object));
+            }
+        }
+        Ok(instances)
+    }
+
+    /// Every row of table `index`: objects for tables with a schema, plain values for the rest
+    pub fn rows(&self, index: usize) -> Result<Vec<SimDataValue>> {
+        let table = self.tables.get(index)
+            .ok_or_else(|| Error::InvalidInput(format!("SimData has no table {}", index)))?;
+        let reader = self.reader();
+        (0..table.row_count as usize)
+            .map(|row| {
+                let position = table.data_offset + row * table.row_size as usize;
+                match table.schema {
+                    Some(schema) => Ok(SimDataValue::Object(Some(reader.object(schema, position, 0)?))),
+                    None => reader.value(table.data_type, None, position, 0),
+                }
+            })
+            .collect()
+    }
+
+    /// The fields whose values differ between the instances of this and `other`, as
+    /// `instance.field` paths, e.g. to see what two mods' versions of the same SimData disagree on
+    pub fn differences(&self, other: &SimDataResource) -> Result<Vec<String>> {
+        let flatten = |instances: Vec<(String, SimDataObject)>| -> BTreeMap<String, String> {
+            instances.iter()
+                .flat_map(|(name, object)| object.flatten().into_iter().map(move |(path, value)| (format!("{}.{}", name, path), value)))
+                .collect()
+        };
+        let ours = flatten(self.instances()?);
+        let theirs = flatten(other.instances()?);
+        let mut paths: Vec<String> = ours.iter()
+            .filter(|(path, value)| theirs.get(*path) != Some(value))
+            .map(|(path, _)| path.clone())
+            .chain(theirs.keys().filter(|path| !ours.contains_key(*path)).cloned())
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Changes every reference to tuning instance `from` to `to` in place: the 64-bit integer and
+    /// table set reference fields holding it, and the instance of resource keys. Returns where
+    /// each was, as `table[row].column` for object fields and `type[row]` for plain values.
+    pub fn retarget(&mut self, from: u64, to: u64) -> Vec<String> {
+        let holds_instance = |data_type: SimDataType| matches!(data_type,
+            SimDataType::Int64 | SimDataType::UInt64 | SimDataType::TableSetReference | SimDataType::ResourceKey);
+        let mut fields = Vec::new();
+        for table in &self.tables {
+            let schema = table.schema.and_then(|s| self.schemas.get(s));
+            let label = table.name.clone()
+                .or_else(|| schema.and_then(|s| s.name.clone()))
+                .unwrap_or_else(|| table.data_type.to_string());
+            let offsets: Vec<(usize, Option<String>)> = match schema {
+                Some(schema) => schema.columns.iter()
+                    .filter(|c| holds_instance(c.data_type))
+                    .map(|c| (c.offset as usize, Some(c.name.clone().unwrap_or_else(|| format!("0x{:08X}", c.name_hash)))))
+                    .collect(),
+                None if holds_instance(table.data_type) => vec![(0, None)],
+                None => continue,
+            };
+            for row in 0..table.row_count as usize {
+                let start = table.data_offset + row * table.row_size as usize;
+                for (offset, column) in &offsets {
+                    let Some(bytes) = self.raw_data.get_mut(start + offset..start + offset + 8) else { continue };
+                    if bytes != from.to_le_bytes() {
+                        continue;
+                    }
+                    bytes.copy_from_slice(&to.to_le_bytes());
+                    fields.push(match column {
+                        Some(column) => format!("{}[{}].{}", label, row, column),
+                        None => format!("{}[{}]", label, row),
+                    });
+                }
+            }
+        }
+        fields
+    }
+
+    fn reader(&self) -> SimDataReader<'_> {
+        SimDataReader { data: &self.raw_data, tables: &self.tables, schemas: &self.schemas }
+    }
+}
+
+#[cfg(feature = "tuning")]
 impl Resource for SimDataResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
         let magic = cursor.read_le::<u32>()?;
         if magic != 0x41544144 { // "DATA"
-            return Err(anyhow::anyhow!("Invalid SimData magic"));
+            return Err(Error::InvalidResource("Invalid SimData magic".to_string()));
         }
         let version = cursor.read_le::<u32>()?;
-        Ok(Self { version, raw_data: data.to_vec() })
+        let (tables, schemas) = SimDataReader::layout(data, version)?;
+        Ok(Self { version, tables, schemas, raw_data: data.to_vec() })
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>> {
@@ -809,13 +1690,131 @@ impl Resource for TextResource {
     }
 }
 
+#[cfg(feature = "catalog")]
 /// Object Definition resource (0xC0DB5AE7)
 #[derive(Debug)]
 pub struct ObjectDefinitionResource {
     pub version: u16,
-    pub properties: HashMap<u32, ObjectProperty>,
+    pub properties: HashMap<ObjectPropertyId, ObjectProperty>,
+    /// The order of the property table as read; properties are written in this order, with any
+    /// added since after them, so an unedited definition is written back as it was
+    pub order: Vec<ObjectPropertyId>,
+}
+
+#[cfg(feature = "catalog")]
+/// The properties an object definition can carry, keyed by their hashed IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectPropertyId {
+    Name,
+    Tuning,
+    MaterialVariant,
+    TuningId,
+    Icons,
+    Rig,
+    Slots,
+    Models,
+    Footprints,
+    Components,
+    SimoleonPrice,
+    ThumbnailGeometryState,
+    PositiveEnvironmentScore,
+    NegativeEnvironmentScore,
+    EnvironmentScoreEmotionTags,
+    EnvironmentScores,
+    IsBaby,
+    Unknown1,
+    Unknown2,
+    Unknown3,
+    Unknown4,
+    /// A property ID with no known meaning; its data isn't parsed
+    Other(u32),
+}
+
+#[cfg(feature = "catalog")]
+impl ObjectPropertyId {
+    const KNOWN: [ObjectPropertyId; 21] = [
+        ObjectPropertyId::Name,
+        ObjectPropertyId::Tuning,
+        ObjectPropertyId::MaterialVariant,
+        ObjectPropertyId::TuningId,
+        ObjectPropertyId::Icons,
+        ObjectPropertyId::Rig,
+        ObjectPropertyId::Slots,
+        ObjectPropertyId::Models,
+        ObjectPropertyId::Footprints,
+        ObjectPropertyId::Components,
+        ObjectPropertyId::SimoleonPrice,
+        ObjectPropertyId::ThumbnailGeometryState,
+        ObjectPropertyId::PositiveEnvironmentScore,
+        ObjectPropertyId::NegativeEnvironmentScore,
+        ObjectPropertyId::EnvironmentScoreEmotionTags,
+        ObjectPropertyId::EnvironmentScores,
+        ObjectPropertyId::IsBaby,
+        ObjectPropertyId::Unknown1,
+        ObjectPropertyId::Unknown2,
+        ObjectPropertyId::Unknown3,
+        ObjectPropertyId::Unknown4,
+    ];
+
+    pub fn from_id(id: u32) -> Self {
+        Self::KNOWN.iter()
+            .find(|known| known.id() == id)
+            .copied()
+            .unwrap_or(ObjectPropertyId::Other(id))
+    }
+
+    /// The hashed ID stored in the property table
+    pub fn id(self) -> u32 {
+        match self {
+            ObjectPropertyId::Name => 0xE7F07786,
+            ObjectPropertyId::Tuning => 0x790FA4BC,
+            ObjectPropertyId::MaterialVariant => 0xECD5A95F,
+            ObjectPropertyId::TuningId => 0xB994039B,
+            ObjectPropertyId::Icons => 0xCADED888,
+            ObjectPropertyId::Rig => 0xE206AE4F,
+            ObjectPropertyId::Slots => 0x8A85AFF3,
+            ObjectPropertyId::Models => 0x8D20ACC6,
+            ObjectPropertyId::Footprints => 0x6C737AD8,
+            ObjectPropertyId::Components => 0xE6E421FB,
+            ObjectPropertyId::SimoleonPrice => 0xE4F4FAA4,
+            ObjectPropertyId::ThumbnailGeometryState => 0x4233F8A0,
+            ObjectPropertyId::PositiveEnvironmentScore => 0x7236BEEA,
+            ObjectPropertyId::NegativeEnvironmentScore => 0x44FC7512,
+            ObjectPropertyId::EnvironmentScoreEmotionTags => 0x2172AEBE,
+            ObjectPropertyId::EnvironmentScores => 0xDCD08394,
+            ObjectPropertyId::IsBaby => 0xAEE67A1C,
+            ObjectPropertyId::Unknown1 => 0xAC8E1BC0,
+            ObjectPropertyId::Unknown2 => 0xEC3712E6,
+            ObjectPropertyId::Unknown3 => 0x52F7F4BC,
+            ObjectPropertyId::Unknown4 => 0xF3936A90,
+            ObjectPropertyId::Other(id) => id,
+        }
+    }
+
+    /// Whether `property` is the kind of value this property is stored as. An [`Other`] ID takes
+    /// only raw bytes, and only when it isn't one of the known IDs.
+    ///
+    /// [`Other`]: ObjectPropertyId::Other
+    pub fn accepts(self, property: &ObjectProperty) -> bool {
+        use ObjectPropertyId as Id;
+        match self {
+            Id::Name | Id::Tuning | Id::MaterialVariant => matches!(property, ObjectProperty::String(_)),
+            Id::TuningId | Id::Unknown3 => matches!(property, ObjectProperty::UInt64(_)),
+            Id::Icons | Id::Rig | Id::Slots | Id::Models | Id::Footprints => matches!(property, ObjectProperty::TGIBlockList(_)),
+            Id::Components => matches!(property, ObjectProperty::UInt32List(_)),
+            Id::Unknown1 => matches!(property, ObjectProperty::Byte(_)),
+            Id::SimoleonPrice | Id::ThumbnailGeometryState => matches!(property, ObjectProperty::UInt32(_)),
+            Id::PositiveEnvironmentScore | Id::NegativeEnvironmentScore => matches!(property, ObjectProperty::Float(_)),
+            Id::Unknown2 | Id::IsBaby => matches!(property, ObjectProperty::Bool(_)),
+            Id::EnvironmentScoreEmotionTags => matches!(property, ObjectProperty::UInt16List(_)),
+            Id::EnvironmentScores => matches!(property, ObjectProperty::FloatList(_)),
+            Id::Unknown4 => matches!(property, ObjectProperty::ByteList(_)),
+            Id::Other(id) => matches!(property, ObjectProperty::Unknown(_)) && Id::from_id(id) == self,
+        }
+    }
 }
 
+#[cfg(feature = "catalog")]
 #[derive(Debug)]
 pub enum ObjectProperty {
     String(String),
@@ -832,6 +1831,148 @@ pub enum ObjectProperty {
     Unknown(Vec<u8>),
 }
 
+#[cfg(feature = "catalog")]
+impl ObjectDefinitionResource {
+    pub fn get(&self, id: ObjectPropertyId) -> Option<&ObjectProperty> {
+        self.properties.get(&id)
+    }
+
+    /// Sets a property, which has to be the kind of value `id` is stored as (see
+    /// [`ObjectPropertyId::accepts`]) so the definition still reads back the same
+    pub fn set(&mut self, id: ObjectPropertyId, property: ObjectProperty) -> Result<()> {
+        if !id.accepts(&property) {
+            return Err(Error::InvalidInput(format!("Object definition property 0x{:08X} can't hold {:?}", id.id(), property)));
+        }
+        self.put(id, property);
+        Ok(())
+    }
+
+    /// Sets a property the typed setters already know is the right kind
+    fn put(&mut self, id: ObjectPropertyId, property: ObjectProperty) {
+        self.properties.insert(id, property);
+    }
+
+    fn string(&self, id: ObjectPropertyId) -> Option<&str> {
+        match self.get(id) {
+            Some(ObjectProperty::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn tgis(&self, id: ObjectPropertyId) -> Option<&[TGI]> {
+        match self.get(id) {
+            Some(ObjectProperty::TGIBlockList(tgis)) => Some(tgis),
+            _ => None,
+        }
+    }
+
+    fn float(&self, id: ObjectPropertyId) -> Option<f32> {
+        match self.get(id) {
+            Some(ObjectProperty::Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.string(ObjectPropertyId::Name)
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.put(ObjectPropertyId::Name, ObjectProperty::String(name.into()));
+    }
+
+    /// Name of the object's tuning file
+    pub fn tuning(&self) -> Option<&str> {
+        self.string(ObjectPropertyId::Tuning)
+    }
+
+    pub fn set_tuning(&mut self, tuning: impl Into<String>) {
+        self.put(ObjectPropertyId::Tuning, ObjectProperty::String(tuning.into()));
+    }
+
+    pub fn material_variant(&self) -> Option<&str> {
+        self.string(ObjectPropertyId::MaterialVariant)
+    }
+
+    /// Instance of the object's tuning resource
+    pub fn tuning_id(&self) -> Option<u64> {
+        match self.get(ObjectPropertyId::TuningId) {
+            Some(ObjectProperty::UInt64(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn set_tuning_id(&mut self, id: u64) {
+        self.put(ObjectPropertyId::TuningId, ObjectProperty::UInt64(id));
+    }
+
+    pub fn icons(&self) -> Option<&[TGI]> {
+        self.tgis(ObjectPropertyId::Icons)
+    }
+
+    pub fn rig(&self) -> Option<&[TGI]> {
+        self.tgis(ObjectPropertyId::Rig)
+    }
+
+    pub fn slots(&self) -> Option<&[TGI]> {
+        self.tgis(ObjectPropertyId::Slots)
+    }
+
+    pub fn models(&self) -> Option<&[TGI]> {
+        self.tgis(ObjectPropertyId::Models)
+    }
+
+    pub fn set_models(&mut self, models: Vec<TGI>) {
+        self.put(ObjectPropertyId::Models, ObjectProperty::TGIBlockList(models));
+    }
+
+    pub fn footprints(&self) -> Option<&[TGI]> {
+        self.tgis(ObjectPropertyId::Footprints)
+    }
+
+    /// Component name hashes
+    pub fn components(&self) -> Option<&[u32]> {
+        match self.get(ObjectPropertyId::Components) {
+            Some(ObjectProperty::UInt32List(components)) => Some(components),
+            _ => None,
+        }
+    }
+
+    pub fn simoleon_price(&self) -> Option<u32> {
+        match self.get(ObjectPropertyId::SimoleonPrice) {
+            Some(ObjectProperty::UInt32(price)) => Some(*price),
+            _ => None,
+        }
+    }
+
+    pub fn set_simoleon_price(&mut self, price: u32) {
+        self.put(ObjectPropertyId::SimoleonPrice, ObjectProperty::UInt32(price));
+    }
+
+    pub fn positive_environment_score(&self) -> Option<f32> {
+        self.float(ObjectPropertyId::PositiveEnvironmentScore)
+    }
+
+    pub fn negative_environment_score(&self) -> Option<f32> {
+        self.float(ObjectPropertyId::NegativeEnvironmentScore)
+    }
+
+    pub fn environment_scores(&self) -> Option<&[f32]> {
+        match self.get(ObjectPropertyId::EnvironmentScores) {
+            Some(ObjectProperty::FloatList(scores)) => Some(scores),
+            _ => None,
+        }
+    }
+
+    pub fn is_baby(&self) -> Option<bool> {
+        match self.get(ObjectPropertyId::IsBaby) {
+            Some(ObjectProperty::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "catalog")]
 impl Resource for ObjectDefinitionResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -848,34 +1989,43 @@ impl Resource for ObjectDefinitionResource {
             entries.push((prop_id, offset));
         }
 
+        // Properties we can't parse run up to whatever comes next: another property or the table
+        let mut boundaries: Vec<u64> = entries.iter().map(|(_, offset)| *offset as u64)
+            .chain([table_offset as u64, data.len() as u64])
+            .collect();
+        boundaries.sort_unstable();
+
         let mut properties = HashMap::new();
+        let mut order = Vec::with_capacity(entries.len());
         for (prop_id, offset) in entries {
+            use ObjectPropertyId as Id;
             cursor.seek(SeekFrom::Start(offset as u64))?;
-            let property = match prop_id {
-                0xE7F07786 | 0x790FA4BC | 0xECD5A95F => { // Name, Tuning, MaterialVariant
+            let id = Id::from_id(prop_id);
+            let property = match id {
+                Id::Name | Id::Tuning | Id::MaterialVariant => {
                     let len = cursor.read_le::<u32>()?;
                     let mut buf = vec![0u8; len as usize];
                     cursor.read_exact(&mut buf)?;
                     ObjectProperty::String(String::from_utf8_lossy(&buf).into_owned())
                 }
-                0xB994039B | 0x52F7F4BC => { // TuningID, Unknown3
+                Id::TuningId | Id::Unknown3 => {
                     ObjectProperty::UInt64(cursor.read_le::<u64>()?)
                 }
-                0xCADED888 | 0xE206AE4F | 0x8A85AFF3 | 0x8D20ACC6 | 0x6C737AD8 => { // Icon, Rig, Slot, Model, Footprint
+                Id::Icons | Id::Rig | Id::Slots | Id::Models | Id::Footprints => {
                     let byte_count = cursor.read_le::<u32>()?;
                     let count = byte_count / 16; // 16 bytes per TGI (8 + 4 + 4)
                     let mut tgis = Vec::with_capacity(count as usize);
                     for _ in 0..count {
                         // Swapped ITG order in legacy code: instance(8), type(4), group(4)
                         let mut instance = cursor.read_le::<u64>()?;
-                        instance = (instance << 32) | (instance >> 32); // swap hi/lo
+                        instance = instance.rotate_right(32); // swap hi/lo
                         let res_type = cursor.read_le::<u32>()?;
                         let res_group = cursor.read_le::<u32>()?;
                         tgis.push(TGI { res_type, res_group, instance });
                     }
                     ObjectProperty::TGIBlockList(tgis)
                 }
-                0xE6E421FB => { // Components
+                Id::Components => {
                     let count = cursor.read_le::<u32>()?;
                     let mut components = Vec::with_capacity(count as usize);
                     for _ in 0..count {
@@ -883,19 +2033,19 @@ impl Resource for ObjectDefinitionResource {
                     }
                     ObjectProperty::UInt32List(components)
                 }
-                0xAC8E1BC0 => { // Unknown1
+                Id::Unknown1 => {
                     ObjectProperty::Byte(cursor.read_le::<u8>()?)
                 }
-                0xE4F4FAA4 | 0x4233F8A0 => { // SimoleonPrice, ThumbnailGeometryState
+                Id::SimoleonPrice | Id::ThumbnailGeometryState => {
                     ObjectProperty::UInt32(cursor.read_le::<u32>()?)
                 }
-                0x7236BEEA | 0x44FC7512 => { // PositiveEnvironmentScore, NegativeEnvironmentScore
+                Id::PositiveEnvironmentScore | Id::NegativeEnvironmentScore => {
                     ObjectProperty::Float(cursor.read_le::<f32>()?)
                 }
-                0xEC3712E6 | 0xAEE67A1C => { // Unknown2, IsBaby
+                Id::Unknown2 | Id::IsBaby => {
                     ObjectProperty::Bool(cursor.read_le::<u8>()? != 0)
                 }
-                0x2172AEBE => { // EnvironmentScoreEmotionTags
+                Id::EnvironmentScoreEmotionTags => {
                     let count = cursor.read_le::<u32>()?;
                     let mut tags = Vec::with_capacity(count as usize);
                     for _ in 0..count {
@@ -903,7 +2053,7 @@ impl Resource for ObjectDefinitionResource {
                     }
                     ObjectProperty::UInt16List(tags)
                 }
-                0xDCD08394 => { // EnvironmentScores
+                Id::EnvironmentScores => {
                     let count = cursor.read_le::<u32>()?;
                     let mut scores = Vec::with_capacity(count as usize);
                     for _ in 0..count {
@@ -911,31 +2061,90 @@ impl Resource for ObjectDefinitionResource {
                     }
                     ObjectProperty::FloatList(scores)
                 }
-                0xF3936A90 => { // Unknown4
+                Id::Unknown4 => {
                     let len = cursor.read_le::<u32>()?;
                     let mut buf = vec![0u8; len as usize];
                     cursor.read_exact(&mut buf)?;
                     ObjectProperty::ByteList(buf)
                 }
-                _ => {
-                    // We don't know the size for unknown properties easily without reading the whole file
-                    // But we can try to infer it if it's the last property or by looking at next offset
-                    // For now, let's just mark it as Unknown
-                    ObjectProperty::Unknown(vec![])
+                Id::Other(_) => {
+                    let end = boundaries.iter().copied().find(|b| *b > offset as u64).unwrap_or(offset as u64);
+                    let mut buf = vec![0u8; (end - offset as u64) as usize];
+                    cursor.read_exact(&mut buf)?;
+                    ObjectProperty::Unknown(buf)
                 }
             };
-            properties.insert(prop_id, property);
+            if properties.insert(id, property).is_none() {
+                order.push(id);
+            }
         }
 
-        Ok(Self { version, properties })
+        Ok(Self { version, properties, order })
     }
 
+    /// Writes the header, the properties and then the property table, as the game's own
+    /// definitions are laid out
     fn to_bytes(&self) -> Result<Vec<u8>> {
-        // Implementation of writing would be complex due to table and offsets.
-        // For now, let's just return an error or a placeholder.
-        // Since we are primarily interested in "Investigate" mode (reading),
-        // we can leave this for later if merging requires re-writing modified resources.
-        Err(anyhow::anyhow!("Writing ObjectDefinitionResource not yet implemented"))
+        let mut added: Vec<ObjectPropertyId> = self.properties.keys().filter(|id| !self.order.contains(id)).copied().collect();
+        added.sort();
+        let ids: Vec<ObjectPropertyId> = self.order.iter().filter(|id| self.properties.contains_key(id)).copied().chain(added).collect();
+        let count = u16::try_from(ids.len())
+            .map_err(|_| Error::InvalidInput(format!("Too many object definition properties: {}", ids.len())))?;
+
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_le(&self.version)?;
+        cursor.write_le(&0u32)?; // table offset, filled in below
+        let mut table = Vec::with_capacity(ids.len());
+        for id in ids {
+            table.push((id.id(), cursor.position() as u32));
+            let len_prefixed = |len: usize| u32::try_from(len).map_err(|_| Error::InvalidInput(format!("Object definition property 0x{:08X} is too long", id.id())));
+            match &self.properties[&id] {
+                ObjectProperty::String(value) => {
+                    cursor.write_le(&len_prefixed(value.len())?)?;
+                    cursor.write_all(value.as_bytes())?;
+                }
+                ObjectProperty::UInt64(value) => cursor.write_le(value)?,
+                ObjectProperty::TGIBlockList(tgis) => {
+                    cursor.write_le(&len_prefixed(tgis.len() * 16)?)?;
+                    for tgi in tgis {
+                        cursor.write_le(&tgi.instance.rotate_right(32))?;
+                        cursor.write_le(&tgi.res_type)?;
+                        cursor.write_le(&tgi.res_group)?;
+                    }
+                }
+                ObjectProperty::UInt32List(values) => {
+                    cursor.write_le(&len_prefixed(values.len())?)?;
+                    cursor.write_le(values)?;
+                }
+                ObjectProperty::Byte(value) => cursor.write_le(value)?,
+                ObjectProperty::UInt32(value) => cursor.write_le(value)?,
+                ObjectProperty::Float(value) => cursor.write_le(value)?,
+                ObjectProperty::Bool(value) => cursor.write_le(&(*value as u8))?,
+                ObjectProperty::UInt16List(values) => {
+                    cursor.write_le(&len_prefixed(values.len())?)?;
+                    cursor.write_le(values)?;
+                }
+                ObjectProperty::FloatList(values) => {
+                    cursor.write_le(&len_prefixed(values.len())?)?;
+                    cursor.write_le(values)?;
+                }
+                ObjectProperty::ByteList(bytes) => {
+                    cursor.write_le(&len_prefixed(bytes.len())?)?;
+                    cursor.write_all(bytes)?;
+                }
+                ObjectProperty::Unknown(bytes) => cursor.write_all(bytes)?,
+            }
+        }
+
+        let table_offset = cursor.position() as u32;
+        cursor.write_le(&count)?;
+        for (id, offset) in table {
+            cursor.write_le(&id)?;
+            cursor.write_le(&offset)?;
+        }
+        let mut data = cursor.into_inner();
+        data[2..6].copy_from_slice(&table_offset.to_le_bytes());
+        Ok(data)
     }
 }
 
@@ -971,6 +2180,15 @@ pub struct StblEntry {
     pub string_value: String,
 }
 
+impl StblResource {
+    /// Recomputes `string_length` (total string bytes plus one terminator per entry) after entries change
+    pub fn recalculate_string_length(&mut self) {
+        self.string_length = self.entries.iter()
+            .map(|e| e.string_value.len() as u32 + 1)
+            .sum();
+    }
+}
+
 impl Resource for StblResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -986,6 +2204,7 @@ impl Resource for StblResource {
 }
 
 
+#[cfg(feature = "catalog")]
 /// Catalog resource (COBJ 0x319E4F1D, CSTL 0x9F5CFF10, etc.)
 #[binrw]
 #[derive(Debug)]
@@ -1030,6 +2249,7 @@ pub struct CatalogResource {
     pub slot_type_set: u64,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 #[br(little)]
@@ -1067,6 +2287,7 @@ pub struct CatalogCommon {
     pub varient_thumb_image_hash: u64,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 pub struct CatalogTagList {
@@ -1077,12 +2298,10 @@ pub struct CatalogTagList {
     pub tags: Vec<u16>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 pub struct LegacyTagList {
-    #[br(calc = 0)]
-    #[bw(calc = 0)]
-    pub _version: u32,
     #[br(temp)]
     #[bw(calc = tags.len() as u32)]
     count: u32,
@@ -1090,6 +2309,7 @@ pub struct LegacyTagList {
     pub tags: Vec<u16>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 pub struct SellingPointList {
@@ -1100,6 +2320,7 @@ pub struct SellingPointList {
     pub points: Vec<SellingPoint>,
 }
 
+#[cfg(feature = "catalog")]
 #[binrw]
 #[derive(Debug)]
 pub struct SellingPoint {
@@ -1107,6 +2328,7 @@ pub struct SellingPoint {
     pub value: f32,
 }
 
+#[cfg(feature = "catalog")]
 impl Resource for CatalogResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1122,6 +2344,7 @@ impl Resource for CatalogResource {
 }
 
 
+#[cfg(feature = "textures")]
 /// RLE Image resource (0x3453CF95)
 #[derive(Debug)]
 pub struct RleResource {
@@ -1132,6 +2355,7 @@ pub struct RleResource {
     pub mip_count: u16,
 }
 
+#[cfg(feature = "textures")]
 impl Resource for RleResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1146,10 +2370,11 @@ impl Resource for RleResource {
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>> {
-        Err(anyhow::anyhow!("Writing RleResource not yet implemented"))
+        Err(Error::Unsupported("Writing RleResource not yet implemented".to_string()))
     }
 }
 
+#[cfg(feature = "textures")]
 /// DST Texture resource (0x00B2D882)
 #[derive(Debug)]
 pub struct DstResource {
@@ -1157,6 +2382,7 @@ pub struct DstResource {
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "textures")]
 impl Resource for DstResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1169,6 +2395,19 @@ impl Resource for DstResource {
     }
 }
 
+#[cfg(feature = "textures")]
+impl DstResource {
+    /// A DST resource from a DXT1/DXT3/DXT5 DDS file, shuffled the way the game stores it
+    pub fn from_dds(dds: &[u8]) -> Result<Self> {
+        Self::from_bytes(&crate::texture::shuffle_dst(dds)?)
+    }
+
+    /// The texture as a plain DDS file. Resources of this type that hold a plain DDS already
+    /// are returned as they are.
+    pub fn to_dds(&self) -> Result<Vec<u8>> {
+        crate::texture::to_dds(&self.raw_data)
+    }
+}
 
 /// Script resource (Encrypted Signed Assembly 0x073FAA07)
 #[derive(Debug)]
@@ -1198,7 +2437,55 @@ impl Resource for ScriptResource {
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>> {
-        Err(anyhow::anyhow!("Writing ScriptResource not yet implemented"))
+        Err(Error::Unsupported("Writing ScriptResource not yet implemented".to_string()))
+    }
+}
+
+/// What a thumbnail type shows, from its type ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThumbnailKind {
+    /// CAS part and body part swatches
+    CasPart,
+    /// Build/Buy catalog objects
+    Object,
+    /// Sims, Sim presets and featured outfits
+    Sim,
+    /// Lots, rooms and households saved to the tray
+    Tray,
+    /// One size of a family of types that store the same image at several sizes.
+    /// `family` is the first type of the family and `size` counts up from the smallest (0).
+    SizeVariant { family: u32, size: u8 },
+}
+
+/// Families of consecutive type IDs that each hold one image at increasing sizes
+const THUMBNAIL_SIZE_FAMILIES: [(u32, u8); 8] = [
+    (0x0580A2B4, 3), (0x0589DC44, 4), (0x05B17698, 3), (0x05B1B524, 3),
+    (0x2653E3C8, 3), (0x2D4284F0, 3), (0x5DE9DBA0, 3), (0x626F60CC, 3),
+];
+
+impl ThumbnailKind {
+    /// Classifies a thumbnail type; `None` for types that aren't thumbnails
+    pub fn of(res_type: u32) -> Option<ThumbnailKind> {
+        match res_type {
+            0x3C1AF1F2 | 0x5B282D45 => Some(ThumbnailKind::CasPart),
+            0x3C2A8647 => Some(ThumbnailKind::Object),
+            0x16CCF748 | 0x9C925813 | 0xCD9DE247 | 0xE254AE6E => Some(ThumbnailKind::Sim),
+            0x0D338A3A | 0x3BD45407 | 0xA1FF2FC4 | 0xAD366F95 | 0xAD366F96 | 0xE18CAEE2 | 0xFCEAB65B => Some(ThumbnailKind::Tray),
+            _ => THUMBNAIL_SIZE_FAMILIES.iter()
+                .find(|(family, sizes)| res_type >= *family && res_type < family + *sizes as u32)
+                .map(|(family, _)| ThumbnailKind::SizeVariant { family: *family, size: (res_type - family) as u8 }),
+        }
+    }
+
+    /// Short name for grouping and naming extracted files, e.g. `cas` or `0580A2B4_size1`
+    pub fn label(&self) -> String {
+        match self {
+            ThumbnailKind::CasPart => "cas".to_string(),
+            ThumbnailKind::Object => "object".to_string(),
+            ThumbnailKind::Sim => "sim".to_string(),
+            ThumbnailKind::Tray => "tray".to_string(),
+            ThumbnailKind::SizeVariant { family, size } => format!("{:08X}_size{}", family, size),
+        }
     }
 }
 
@@ -1206,9 +2493,35 @@ impl Resource for ScriptResource {
 #[derive(Debug)]
 pub struct ThumbnailResource {
     pub has_alpha: bool,
+    /// Set when the resource was read knowing its type
+    pub kind: Option<ThumbnailKind>,
     pub raw_data: Vec<u8>,
 }
 
+impl ThumbnailResource {
+    pub fn from_bytes_with_type(res_type: u32, data: &[u8]) -> Result<Self> {
+        Ok(Self { kind: ThumbnailKind::of(res_type), ..Self::from_bytes(data)? })
+    }
+
+    #[cfg(feature = "textures")]
+    /// The embedded PNG holding the alpha channel, when [`has_alpha`](Self::has_alpha) is set
+    pub fn alpha_png(&self) -> Option<&[u8]> {
+        crate::palette::thumbnail_alpha(&self.raw_data)
+    }
+
+    #[cfg(feature = "textures")]
+    /// The thumbnail as RGBA, with the alpha channel combined into the JPEG color data
+    pub fn to_rgba(&self) -> Result<crate::texture::RgbaImage> {
+        crate::palette::decode_thumbnail(&self.raw_data)
+    }
+
+    #[cfg(feature = "textures")]
+    /// The thumbnail as a PNG, keeping the transparency a JPEG can't
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        self.to_rgba()?.to_png()
+    }
+}
+
 impl Resource for ThumbnailResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut has_alpha = false;
@@ -1222,7 +2535,7 @@ impl Resource for ThumbnailResource {
                 }
             }
         }
-        Ok(Self { has_alpha, raw_data: data.to_vec() })
+        Ok(Self { has_alpha, kind: None, raw_data: data.to_vec() })
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>> {
@@ -1267,6 +2580,7 @@ impl Resource for ComplateResource {
     }
 }
 
+#[cfg(feature = "textures")]
 /// Txtc resource (0x033A1435, 0x0341ACC9)
 #[derive(Debug)]
 pub struct TxtcResource {
@@ -1274,6 +2588,7 @@ pub struct TxtcResource {
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "textures")]
 impl Resource for TxtcResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1305,6 +2620,118 @@ impl Resource for ObjKeyResource {
     }
 }
 
+#[cfg(feature = "meshes")]
+/// Blend geometry resource (0x067CAA11): the per-vertex position and normal deltas a CAS slider
+/// or preset applies to a mesh
+#[binrw]
+#[derive(Debug)]
+#[br(little, magic = b"BGEO")]
+#[bw(little, magic = b"BGEO")]
+pub struct BlendGeometryResource {
+    pub version: u32,
+    #[br(temp)]
+    #[bw(calc = lods.len() as u32)]
+    lod_count: u32,
+    #[br(temp)]
+    #[bw(calc = blend_map.len() as u32)]
+    vertex_count: u32,
+    #[br(temp)]
+    #[bw(calc = vectors.len() as u32)]
+    vector_count: u32,
+    #[br(count = lod_count)]
+    pub lods: Vec<BlendLod>,
+    /// One entry per vertex across all LODs
+    #[br(count = vertex_count)]
+    pub blend_map: Vec<BlendMapEntry>,
+    #[br(count = vector_count)]
+    pub vectors: Vec<BlendVector>,
+}
+
+#[cfg(feature = "meshes")]
+#[binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[br(little)]
+#[bw(little)]
+pub struct BlendLod {
+    /// First vertex of this LOD in the blend map
+    pub index_base: u32,
+    pub vertex_count: u32,
+    pub vector_count: u32,
+}
+
+#[cfg(feature = "meshes")]
+/// Which deltas a vertex has, and where its vectors are
+#[binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[br(little)]
+#[bw(little)]
+pub struct BlendMapEntry(pub u16);
+
+#[cfg(feature = "meshes")]
+impl BlendMapEntry {
+    pub fn has_position(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    pub fn has_normal(self) -> bool {
+        self.0 & 2 != 0
+    }
+
+    /// Offset into the vector list
+    pub fn offset(self) -> i16 {
+        (self.0 as i16) >> 2
+    }
+}
+
+#[cfg(feature = "meshes")]
+/// A packed delta vector. Each component keeps its sign in the top bit and its magnitude in
+/// 1/8000ths in the rest.
+#[binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[br(little)]
+#[bw(little)]
+pub struct BlendVector {
+    pub x: u16,
+    pub y: u16,
+    pub z: u16,
+}
+
+#[cfg(feature = "meshes")]
+impl BlendVector {
+    pub fn to_f32(self) -> [f32; 3] {
+        let unpack = |v: u16| {
+            let magnitude = (v & 0x7FFF) as f32 / 8000.0;
+            if v & 0x8000 != 0 { -magnitude } else { magnitude }
+        };
+        [unpack(self.x), unpack(self.y), unpack(self.z)]
+    }
+}
+
+#[cfg(feature = "meshes")]
+impl BlendGeometryResource {
+    /// The blend map entries of one LOD's vertices
+    pub fn lod_vertices(&self, lod: usize) -> Option<&[BlendMapEntry]> {
+        let lod = self.lods.get(lod)?;
+        let start = lod.index_base as usize;
+        self.blend_map.get(start..start + lod.vertex_count as usize)
+    }
+}
+
+#[cfg(feature = "meshes")]
+impl Resource for BlendGeometryResource {
+    fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor::new(data);
+        Self::read(&mut cursor).context("Failed to read BlendGeometryResource")
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut cursor = Cursor::new(Vec::new());
+        self.write(&mut cursor).context("Failed to write BlendGeometryResource")?;
+        Ok(cursor.into_inner())
+    }
+}
+
+#[cfg(feature = "meshes")]
 /// SimModifier resource (0xC5F6763E)
 #[derive(Debug)]
 pub struct SimModifierResource {
@@ -1312,6 +2739,7 @@ pub struct SimModifierResource {
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "meshes")]
 impl Resource for SimModifierResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         // contexData comes first in legacy, skip it for basic identification
@@ -1330,6 +2758,7 @@ impl Resource for SimModifierResource {
     }
 }
 
+#[cfg(feature = "meshes")]
 /// Bone resource (0x00AE6C67)
 #[derive(Debug)]
 pub struct BoneResource {
@@ -1337,6 +2766,7 @@ pub struct BoneResource {
     pub raw_data: Vec<u8>,
 }
 
+#[cfg(feature = "meshes")]
 impl Resource for BoneResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1353,6 +2783,7 @@ impl Resource for BoneResource {
     }
 }
 
+#[cfg(feature = "meshes")]
 /// Geometry resource (0x015A1849)
 #[binrw]
 #[derive(Debug)]
@@ -1370,11 +2801,15 @@ pub struct GeomResource {
     #[br(temp)]
     #[bw(calc = vertex_data.vertices.len() as u32)]
     pub num_verts: u32,
-    pub vertex_formats: GeomVertexFormatList,
+    vertex_formats: GeomVertexFormatList,
     #[br(args(num_verts, &vertex_formats))]
-    pub vertex_data: GeomVertexDataList,
+    vertex_data: GeomVertexDataList,
+    /// Kept in step with the two fields above, which only [`GeomResource::set_vertices`] changes
+    #[br(calc = GeomVertices::decode(&vertex_formats, &vertex_data))]
+    #[bw(ignore)]
+    decoded: GeomVertices,
     #[br(temp)]
-    #[bw(calc = 1)]
+    #[bw(calc = bytes_per_face_point.len() as u32)]
     pub item_count: u32,
     #[br(count = item_count)]
     pub bytes_per_face_point: Vec<u8>,
@@ -1382,6 +2817,7 @@ pub struct GeomResource {
     #[bw(calc = faces.faces.len() as u32 * 3)]
     pub num_face_points: u32,
     #[br(args(num_face_points, &bytes_per_face_point))]
+    #[bw(args_raw = bytes_per_face_point.first().copied().unwrap_or(2))]
     pub faces: GeomFaceList,
     #[br(if(version == 0x05))]
     pub skin_index: Option<i32>,
@@ -1390,10 +2826,14 @@ pub struct GeomResource {
     #[br(if(version == 0x0C))]
     pub unknown_things2: Option<GeomUnknownThing2List>,
     pub bone_hashes: GeomBoneHashList,
+    /// Written after the bone hashes by [`Resource::to_bytes`], which also recomputes
+    /// `tgi_offset` and `tgi_size`
     #[br(seek_before = SeekFrom::Start(tgi_offset as u64 + 16), count = tgi_size / 16)]
+    #[bw(ignore)]
     pub tgi_blocks: Vec<TGI>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomMtnf {
@@ -1402,8 +2842,9 @@ pub struct GeomMtnf {
     pub data: Vec<u8>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeomVertexFormatList {
     #[br(temp)]
     #[bw(calc = formats.len() as u32)]
@@ -1412,19 +2853,127 @@ pub struct GeomVertexFormatList {
     pub formats: Vec<GeomVertexFormat>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeomVertexFormat {
     pub usage: u32,
     pub data_type: u32,
     pub element_size: u8,
 }
 
-#[derive(Debug)]
+#[cfg(feature = "meshes")]
+impl GeomVertexFormat {
+    pub const USAGE_POSITION: u32 = 1;
+    pub const USAGE_NORMAL: u32 = 2;
+    pub const USAGE_UV: u32 = 3;
+    pub const USAGE_BONE_ASSIGNMENT: u32 = 4;
+    pub const USAGE_WEIGHTS: u32 = 5;
+    pub const USAGE_TANGENT: u32 = 6;
+    pub const USAGE_COLOR: u32 = 7;
+    pub const USAGE_VERTEX_ID: u32 = 10;
+
+    pub const TYPE_FLOAT: u32 = 1;
+    pub const TYPE_BYTE: u32 = 2;
+    pub const TYPE_ARGB: u32 = 3;
+    pub const TYPE_UINT32: u32 = 4;
+}
+
+#[cfg(feature = "meshes")]
+/// Bone influences of one vertex: indices into the GEOM's bone hash list and their weights
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoneAssignment {
+    pub bones: [u8; 4],
+    pub weights: [f32; 4],
+}
+
+#[cfg(feature = "meshes")]
+/// Vertex data decoded from the raw per-vertex bytes using the vertex format table
+#[derive(Debug, Default)]
+pub struct GeomVertices {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 3]>,
+    /// One list per UV channel, in the order the channels appear in the format table
+    pub uvs: Vec<Vec<[f32; 2]>>,
+    pub bone_assignments: Vec<BoneAssignment>,
+    pub colors: Vec<u32>,
+    pub vertex_ids: Vec<u32>,
+}
+
+#[cfg(feature = "meshes")]
+fn read_f32s<const N: usize>(bytes: &[u8]) -> [f32; N] {
+    let mut out = [0.0; N];
+    for (value, chunk) in out.iter_mut().zip(bytes.chunks_exact(4)) {
+        *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    out
+}
+
+#[cfg(feature = "meshes")]
+impl GeomVertices {
+    pub fn decode(formats: &GeomVertexFormatList, data: &GeomVertexDataList) -> Self {
+        let mut decoded = Self::default();
+        let uv_channels = formats.formats.iter().filter(|f| f.usage == GeomVertexFormat::USAGE_UV).count();
+        decoded.uvs = vec![Vec::with_capacity(data.vertices.len()); uv_channels];
+        let has_bones = formats.formats.iter().any(|f| f.usage == GeomVertexFormat::USAGE_BONE_ASSIGNMENT);
+        if has_bones {
+            decoded.bone_assignments = vec![BoneAssignment::default(); data.vertices.len()];
+        }
+
+        for (v, vertex) in data.vertices.iter().enumerate() {
+            let mut pos = 0;
+            let mut uv_channel = 0;
+            for format in &formats.formats {
+                let size = format.element_size as usize;
+                let Some(bytes) = vertex.get(pos..pos + size) else { break };
+                pos += size;
+                match format.usage {
+                    GeomVertexFormat::USAGE_POSITION => decoded.positions.push(read_f32s(bytes)),
+                    GeomVertexFormat::USAGE_NORMAL => decoded.normals.push(read_f32s(bytes)),
+                    GeomVertexFormat::USAGE_TANGENT => decoded.tangents.push(read_f32s(bytes)),
+                    GeomVertexFormat::USAGE_UV => {
+                        decoded.uvs[uv_channel].push(read_f32s(bytes));
+                        uv_channel += 1;
+                    }
+                    GeomVertexFormat::USAGE_BONE_ASSIGNMENT if has_bones => {
+                        for (bone, byte) in decoded.bone_assignments[v].bones.iter_mut().zip(bytes) {
+                            *bone = *byte;
+                        }
+                    }
+                    GeomVertexFormat::USAGE_WEIGHTS if has_bones => {
+                        // TS4 stores weights as bytes (0-255); older meshes use floats
+                        decoded.bone_assignments[v].weights = if format.data_type == GeomVertexFormat::TYPE_FLOAT {
+                            read_f32s(bytes)
+                        } else {
+                            let mut weights = [0.0; 4];
+                            for (weight, byte) in weights.iter_mut().zip(bytes) {
+                                *weight = *byte as f32 / 255.0;
+                            }
+                            weights
+                        };
+                    }
+                    GeomVertexFormat::USAGE_COLOR if size == 4 => {
+                        decoded.colors.push(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+                    }
+                    GeomVertexFormat::USAGE_VERTEX_ID if size == 4 => {
+                        decoded.vertex_ids.push(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        decoded
+    }
+}
+
+#[cfg(feature = "meshes")]
+#[derive(Debug, Clone)]
 pub struct GeomVertexDataList {
     pub vertices: Vec<Vec<u8>>,
 }
 
+#[cfg(feature = "meshes")]
 impl BinRead for GeomVertexDataList {
     type Args<'a> = (u32, &'a GeomVertexFormatList);
 
@@ -1445,6 +2994,7 @@ impl BinRead for GeomVertexDataList {
     }
 }
 
+#[cfg(feature = "meshes")]
 impl BinWrite for GeomVertexDataList {
     type Args<'a> = ();
     fn write_options<W: Write + Seek>(
@@ -1460,11 +3010,13 @@ impl BinWrite for GeomVertexDataList {
     }
 }
 
+#[cfg(feature = "meshes")]
 #[derive(Debug)]
 pub struct GeomFaceList {
     pub faces: Vec<[u16; 3]>,
 }
 
+#[cfg(feature = "meshes")]
 impl BinRead for GeomFaceList {
     type Args<'a> = (u32, &'a Vec<u8>);
 
@@ -1479,11 +3031,11 @@ impl BinRead for GeomFaceList {
         let mut faces = Vec::with_capacity((num_face_points / 3) as usize);
         for _ in 0..(num_face_points / 3) {
             let mut face = [0u16; 3];
-            for j in 0..3 {
+            for point in face.iter_mut() {
                 if bpf == 1 {
-                    face[j] = reader.read_le::<u8>()? as u16;
+                    *point = reader.read_le::<u8>()? as u16;
                 } else {
-                    face[j] = reader.read_le::<u16>()?;
+                    *point = reader.read_le::<u16>()?;
                 }
             }
             faces.push(face);
@@ -1492,19 +3044,32 @@ impl BinRead for GeomFaceList {
     }
 }
 
+#[cfg(feature = "meshes")]
 impl BinWrite for GeomFaceList {
-    type Args<'a> = ();
+    /// Bytes per face point
+    type Args<'a> = u8;
     fn write_options<W: Write + Seek>(
         &self,
-        _writer: &mut W,
+        writer: &mut W,
         _options: binrw::Endian,
-        _args: Self::Args<'_>,
+        bytes_per_face_point: Self::Args<'_>,
     ) -> binrw::BinResult<()> {
-        // Implement writing if needed
+        for point in self.faces.iter().flatten() {
+            if bytes_per_face_point == 1 {
+                let point = u8::try_from(*point).map_err(|_| binrw::Error::AssertFail {
+                    pos: writer.stream_position().unwrap_or(0),
+                    message: format!("Face point {} does not fit in one byte", point),
+                })?;
+                writer.write_all(&[point])?;
+            } else {
+                writer.write_all(&point.to_le_bytes())?;
+            }
+        }
         Ok(())
     }
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomUnknownThingList {
@@ -1515,6 +3080,7 @@ pub struct GeomUnknownThingList {
     pub things: Vec<GeomUnknownThing>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomUnknownThing {
@@ -1526,6 +3092,7 @@ pub struct GeomUnknownThing {
     pub unknown2: Vec<[f32; 2]>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomUnknownThing2List {
@@ -1536,6 +3103,7 @@ pub struct GeomUnknownThing2List {
     pub things: Vec<GeomUnknownThing2>,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomUnknownThing2 {
@@ -1547,6 +3115,7 @@ pub struct GeomUnknownThing2 {
     pub unknown18: u8,
 }
 
+#[cfg(feature = "meshes")]
 #[binrw]
 #[derive(Debug)]
 pub struct GeomBoneHashList {
@@ -1557,6 +3126,141 @@ pub struct GeomBoneHashList {
     pub hashes: Vec<u32>,
 }
 
+#[cfg(feature = "meshes")]
+impl GeomResource {
+    /// What each vertex holds, and how it is stored
+    pub fn vertex_formats(&self) -> &GeomVertexFormatList {
+        &self.vertex_formats
+    }
+
+    /// Each vertex's bytes, laid out per [`vertex_formats`](Self::vertex_formats)
+    pub fn vertex_data(&self) -> &GeomVertexDataList {
+        &self.vertex_data
+    }
+
+    /// Replaces the vertices. Every vertex must be as long as the formats add up to.
+    pub fn set_vertices(&mut self, formats: GeomVertexFormatList, data: GeomVertexDataList) -> Result<()> {
+        check_vertex_sizes(&formats, &data)?;
+        self.decoded = GeomVertices::decode(&formats, &data);
+        self.vertex_formats = formats;
+        self.vertex_data = data;
+        Ok(())
+    }
+
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.decoded.positions
+    }
+
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.decoded.normals
+    }
+
+    /// Texture coordinates for a UV channel; empty if the mesh has no such channel
+    pub fn uvs(&self, channel: usize) -> &[[f32; 2]] {
+        self.decoded.uvs.get(channel).map(|uv| uv.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn uv_channel_count(&self) -> usize {
+        self.decoded.uvs.len()
+    }
+
+    /// Per-vertex bone influences; `bones` index into `bone_hashes`
+    pub fn bone_assignments(&self) -> &[BoneAssignment] {
+        &self.decoded.bone_assignments
+    }
+
+    /// Parses a GEOM resource as stored in a package, either bare or wrapped in an RCOL
+    pub fn from_package_data(data: &[u8]) -> Result<Self> {
+        if data.starts_with(b"GEOM") {
+            return Self::from_bytes(data);
+        }
+        let rcol = RcolResource::from_bytes(data)?;
+        let chunk = rcol.chunks.iter()
+            .find(|c| c.data.starts_with(b"GEOM"))
+            .ok_or_else(|| Error::InvalidResource("RCOL contains no GEOM chunk".to_string()))?;
+        Self::from_bytes(&chunk.data)
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        let positions = self.positions();
+        let mut bounds_min = [f32::MAX; 3];
+        let mut bounds_max = [f32::MIN; 3];
+        for p in positions {
+            for axis in 0..3 {
+                bounds_min[axis] = bounds_min[axis].min(p[axis]);
+                bounds_max[axis] = bounds_max[axis].max(p[axis]);
+            }
+        }
+        if positions.is_empty() {
+            bounds_min = [0.0; 3];
+            bounds_max = [0.0; 3];
+        }
+
+        let mut uv_min = [0.0; 2];
+        let mut uv_max = [0.0; 2];
+        if let Some(first) = self.uvs(0).first() {
+            uv_min = *first;
+            uv_max = *first;
+            for uv in self.uvs(0) {
+                for axis in 0..2 {
+                    uv_min[axis] = uv_min[axis].min(uv[axis]);
+                    uv_max[axis] = uv_max[axis].max(uv[axis]);
+                }
+            }
+        }
+
+        MeshStats {
+            vertex_count: self.vertex_data.vertices.len(),
+            face_count: self.faces.faces.len(),
+            bounds_min,
+            bounds_max,
+            uv_channels: self.uv_channel_count(),
+            uv_min,
+            uv_max,
+            bone_count: self.bone_hashes.hashes.len(),
+        }
+    }
+}
+
+#[cfg(feature = "meshes")]
+/// Size and extent summary of a single GEOM
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshStats {
+    pub vertex_count: usize,
+    /// Triangle count
+    pub face_count: usize,
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    pub uv_channels: usize,
+    /// Range of the first UV channel
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub bone_count: usize,
+}
+
+#[cfg(feature = "meshes")]
+impl MeshStats {
+    pub fn size(&self) -> [f32; 3] {
+        [
+            self.bounds_max[0] - self.bounds_min[0],
+            self.bounds_max[1] - self.bounds_min[1],
+            self.bounds_max[2] - self.bounds_min[2],
+        ]
+    }
+}
+
+#[cfg(feature = "meshes")]
+fn check_vertex_sizes(formats: &GeomVertexFormatList, data: &GeomVertexDataList) -> Result<()> {
+    let stride: usize = formats.formats.iter().map(|f| f.element_size as usize).sum();
+    match data.vertices.iter().position(|v| v.len() != stride) {
+        Some(v) => Err(Error::InvalidResource(format!(
+            "GEOM vertex {} is {} bytes; its vertex formats need {}", v, data.vertices[v].len(), stride
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "meshes")]
 impl Resource for GeomResource {
     fn from_bytes(data: &[u8]) -> Result<Self> {
         let mut cursor = Cursor::new(data);
@@ -1566,7 +3270,20 @@ impl Resource for GeomResource {
     fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
         let mut cursor = Cursor::new(&mut data);
+        check_vertex_sizes(&self.vertex_formats, &self.vertex_data)?;
         self.write(&mut cursor).context("Failed to write GeomResource")?;
+
+        // The TGI list is a count and the TGIs; its offset is from the end of the offset field
+        let tgi_offset = data.len() as u32 - 12;
+        let tgi_size = 4 + self.tgi_blocks.len() as u32 * 16;
+        data.extend_from_slice(&(self.tgi_blocks.len() as u32).to_le_bytes());
+        for tgi in &self.tgi_blocks {
+            data.extend_from_slice(&tgi.res_type.to_le_bytes());
+            data.extend_from_slice(&tgi.res_group.to_le_bytes());
+            data.extend_from_slice(&tgi.instance.to_le_bytes());
+        }
+        data[8..12].copy_from_slice(&tgi_offset.to_le_bytes());
+        data[12..16].copy_from_slice(&tgi_size.to_le_bytes());
         Ok(data)
     }
 }
@@ -1612,14 +3329,24 @@ pub struct ManifestResource {
     #[br(temp)]
     #[bw(calc = entries.len() as u32)]
     pub entry_count: u32,
-    #[br(count = entry_count)]
+    #[br(count = entry_count, args { inner: (version,) })]
+    #[bw(args(*version))]
     pub entries: Vec<ManifestEntry>,
 }
 
+impl ManifestResource {
+    /// Version 2 adds a [`ResourceChecksum`] for every listed resource
+    pub const LATEST_VERSION: u32 = 2;
+
+    pub fn has_checksums(&self) -> bool {
+        self.version >= 2
+    }
+}
+
 #[binrw]
 #[derive(Debug)]
-#[br(little)]
-#[bw(little)]
+#[br(little, import(version: u32))]
+#[bw(little, import(version: u32))]
 pub struct ManifestEntry {
     #[br(temp)]
     #[bw(calc = name.len() as u32)]
@@ -1633,6 +3360,32 @@ pub struct ManifestEntry {
     #[br(count = resource_count, map = |v: Vec<ManifestTGI>| v.into_iter().map(TGI::from).collect())]
     #[bw(map = |v: &Vec<TGI>| v.iter().map(|&t| ManifestTGI::from(t)).collect::<Vec<_>>())]
     pub resources: Vec<TGI>,
+    /// One per resource, in the same order (version 2 and later; empty before that)
+    #[br(if(version >= 2), count = resource_count)]
+    #[bw(if(version >= 2))]
+    pub checksums: Vec<ResourceChecksum>,
+}
+
+/// Size and FNV-1a 64 hash of a resource's decompressed data, so an un-merge can tell whether
+/// the merged package was damaged after it was written
+#[binrw]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[br(little)]
+#[bw(little)]
+pub struct ResourceChecksum {
+    pub size: u32,
+    pub hash: u64,
+}
+
+impl ResourceChecksum {
+    pub fn of(data: &[u8]) -> Self {
+        let mut hash: u64 = 0xCBF29CE484222325;
+        for &b in data {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001B3);
+        }
+        Self { size: data.len() as u32, hash }
+    }
 }
 
 impl Resource for ManifestResource {
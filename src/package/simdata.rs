@@ -0,0 +1,878 @@
+use std::collections::BTreeMap;
+use byteorder::{ByteOrder, LittleEndian};
+use crate::error::{Context, Error, Result};
+use crate::package::index::TGI;
+
+/// An offset field that points nowhere
+const NULL_OFFSET: i32 = i32::MIN;
+/// How deep objects can nest before the data is taken to be circular
+const MAX_DEPTH: usize = 64;
+
+/// The type of a SimData column or table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimDataType {
+    Boolean,
+    Character,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    String,
+    HashedString,
+    Object,
+    Vector,
+    Float2,
+    Float3,
+    Float4,
+    TableSetReference,
+    ResourceKey,
+    LocalizationKey,
+    Variant,
+    Unknown(u32),
+}
+
+impl SimDataType {
+    pub fn from_code(code: u32) -> Self {
+        use SimDataType::*;
+        const TYPES: [SimDataType; 22] = [
+            Boolean, Character, Int8, UInt8, Int16, UInt16, Int32, UInt32, Int64, UInt64, Float, String, HashedString,
+            Object, Vector, Float2, Float3, Float4, TableSetReference, ResourceKey, LocalizationKey, Variant,
+        ];
+        TYPES.get(code as usize).copied().unwrap_or(Unknown(code))
+    }
+
+    /// The number the type is stored as, the inverse of [`from_code`](Self::from_code)
+    pub fn code(self) -> u32 {
+        use SimDataType::*;
+        match self {
+            Boolean => 0,
+            Character => 1,
+            Int8 => 2,
+            UInt8 => 3,
+            Int16 => 4,
+            UInt16 => 5,
+            Int32 => 6,
+            UInt32 => 7,
+            Int64 => 8,
+            UInt64 => 9,
+            Float => 10,
+            String => 11,
+            HashedString => 12,
+            Object => 13,
+            Vector => 14,
+            Float2 => 15,
+            Float3 => 16,
+            Float4 => 17,
+            TableSetReference => 18,
+            ResourceKey => 19,
+            LocalizationKey => 20,
+            Variant => 21,
+            Unknown(code) => code,
+        }
+    }
+
+    /// Bytes a value of this type takes in a row
+    pub fn size(self) -> usize {
+        use SimDataType::*;
+        match self {
+            Boolean | Character | Int8 | UInt8 => 1,
+            Int16 | UInt16 => 2,
+            Int32 | UInt32 | Float | String | Object | LocalizationKey => 4,
+            Int64 | UInt64 | HashedString | Vector | Float2 | TableSetReference | Variant => 8,
+            Float3 => 12,
+            Float4 | ResourceKey => 16,
+            Unknown(_) => 0,
+        }
+    }
+
+    /// The boundary a value of this type is kept on within a row
+    pub fn alignment(self) -> usize {
+        use SimDataType::*;
+        match self {
+            Boolean | Character | Int8 | UInt8 | Unknown(_) => 1,
+            Int16 | UInt16 => 2,
+            Int64 | UInt64 | TableSetReference | ResourceKey => 8,
+            _ => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for SimDataType {
+    /// The type's name, e.g. `Float`, or its code for types this parser doesn't know
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimDataType::Unknown(code) => write!(f, "{}", code),
+            known => write!(f, "{:?}", known),
+        }
+    }
+}
+
+impl std::str::FromStr for SimDataType {
+    type Err = Error;
+
+    /// Parses a type name as [`Display`](std::fmt::Display) writes it, or a type code
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(code) = s.parse::<u32>() {
+            return Ok(Self::from_code(code));
+        }
+        (0..22).map(Self::from_code)
+            .find(|t| format!("{:?}", t).eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::InvalidInput(format!("Unknown SimData type '{}'", s)))
+    }
+}
+
+/// A column of a [`SimDataSchema`]: a named field at a fixed offset in each row
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimDataColumn {
+    pub name: Option<String>,
+    pub name_hash: u32,
+    pub data_type: SimDataType,
+    pub flags: u16,
+    /// Where the field is in the row
+    pub offset: u32,
+    /// For object columns, the index of the object's schema in [`SimDataResource::schemas`]
+    ///
+    /// [`SimDataResource::schemas`]: crate::package::resource::SimDataResource::schemas
+    pub schema: Option<usize>,
+}
+
+/// The layout of the rows of a table, like a tuning class
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimDataSchema {
+    pub name: Option<String>,
+    pub name_hash: u32,
+    pub schema_hash: u32,
+    /// Bytes per row
+    pub size: u32,
+    pub columns: Vec<SimDataColumn>,
+}
+
+/// A run of rows in the data. Tables with a schema hold objects; the rest hold plain values of
+/// `data_type`, such as the characters of strings or the elements of vectors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimDataTable {
+    /// Only the tables the tuning's instances live in are named
+    pub name: Option<String>,
+    pub name_hash: u32,
+    /// Index in [`SimDataResource::schemas`]
+    ///
+    /// [`SimDataResource::schemas`]: crate::package::resource::SimDataResource::schemas
+    pub schema: Option<usize>,
+    pub data_type: SimDataType,
+    pub row_size: u32,
+    pub row_count: u32,
+    /// Where the first row starts in the resource
+    pub data_offset: usize,
+}
+
+impl SimDataTable {
+    fn contains(&self, position: usize) -> bool {
+        position >= self.data_offset && position < self.data_offset + self.row_size as usize * self.row_count as usize
+    }
+}
+
+/// One value read from SimData
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimDataValue {
+    Boolean(bool),
+    Character(u8),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    String(Option<String>),
+    HashedString(Option<String>, u32),
+    Object(Option<SimDataObject>),
+    Vector(Vec<SimDataValue>),
+    Float2([f32; 2]),
+    Float3([f32; 3]),
+    Float4([f32; 4]),
+    TableSetReference(u64),
+    ResourceKey(TGI),
+    LocalizationKey(u32),
+    Variant { type_hash: u32, value: Option<Box<SimDataValue>> },
+    /// A type this parser doesn't know, with its bytes
+    Unknown(u32, Vec<u8>),
+}
+
+/// A row of a table with a schema: its fields in column order, named by column (or by the
+/// column's name hash in hex, where the name was left out)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimDataObject {
+    pub schema: Option<String>,
+    pub fields: Vec<(String, SimDataValue)>,
+}
+
+impl SimDataValue {
+    /// The type the value is stored as
+    pub fn data_type(&self) -> SimDataType {
+        match self {
+            SimDataValue::Boolean(_) => SimDataType::Boolean,
+            SimDataValue::Character(_) => SimDataType::Character,
+            SimDataValue::Int8(_) => SimDataType::Int8,
+            SimDataValue::UInt8(_) => SimDataType::UInt8,
+            SimDataValue::Int16(_) => SimDataType::Int16,
+            SimDataValue::UInt16(_) => SimDataType::UInt16,
+            SimDataValue::Int32(_) => SimDataType::Int32,
+            SimDataValue::UInt32(_) => SimDataType::UInt32,
+            SimDataValue::Int64(_) => SimDataType::Int64,
+            SimDataValue::UInt64(_) => SimDataType::UInt64,
+            SimDataValue::Float(_) => SimDataType::Float,
+            SimDataValue::String(_) => SimDataType::String,
+            SimDataValue::HashedString(..) => SimDataType::HashedString,
+            SimDataValue::Object(_) => SimDataType::Object,
+            SimDataValue::Vector(_) => SimDataType::Vector,
+            SimDataValue::Float2(_) => SimDataType::Float2,
+            SimDataValue::Float3(_) => SimDataType::Float3,
+            SimDataValue::Float4(_) => SimDataType::Float4,
+            SimDataValue::TableSetReference(_) => SimDataType::TableSetReference,
+            SimDataValue::ResourceKey(_) => SimDataType::ResourceKey,
+            SimDataValue::LocalizationKey(_) => SimDataType::LocalizationKey,
+            SimDataValue::Variant { .. } => SimDataType::Variant,
+            SimDataValue::Unknown(code, _) => SimDataType::Unknown(*code),
+        }
+    }
+}
+
+impl SimDataObject {
+    pub fn get(&self, field: &str) -> Option<&SimDataValue> {
+        self.fields.iter().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+
+    /// Every plain value in the object by its dotted path, with vector elements as `name[i]`,
+    /// so two objects can be compared field by field
+    pub fn flatten(&self) -> BTreeMap<String, String> {
+        let mut fields = BTreeMap::new();
+        for (name, value) in &self.fields {
+            flatten_value(name, value, &mut fields);
+        }
+        fields
+    }
+}
+
+fn flatten_value(path: &str, value: &SimDataValue, fields: &mut BTreeMap<String, String>) {
+    match value {
+        SimDataValue::Object(Some(object)) => {
+            for (name, value) in &object.fields {
+                flatten_value(&format!("{}.{}", path, name), value, fields);
+            }
+        }
+        SimDataValue::Vector(elements) => {
+            fields.insert(format!("{}.len", path), elements.len().to_string());
+            for (i, element) in elements.iter().enumerate() {
+                flatten_value(&format!("{}[{}]", path, i), element, fields);
+            }
+        }
+        SimDataValue::Variant { type_hash, value: Some(value) } => {
+            fields.insert(format!("{}.type", path), format!("0x{:08X}", type_hash));
+            flatten_value(path, value, fields);
+        }
+        value => {
+            fields.insert(path.to_string(), value.to_string());
+        }
+    }
+}
+
+impl std::fmt::Display for SimDataValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimDataValue::Boolean(v) => write!(f, "{}", v),
+            SimDataValue::Character(v) => write!(f, "{:?}", *v as char),
+            SimDataValue::Int8(v) => write!(f, "{}", v),
+            SimDataValue::UInt8(v) => write!(f, "{}", v),
+            SimDataValue::Int16(v) => write!(f, "{}", v),
+            SimDataValue::UInt16(v) => write!(f, "{}", v),
+            SimDataValue::Int32(v) => write!(f, "{}", v),
+            SimDataValue::UInt32(v) => write!(f, "{}", v),
+            SimDataValue::Int64(v) => write!(f, "{}", v),
+            SimDataValue::UInt64(v) => write!(f, "{}", v),
+            SimDataValue::Float(v) => write!(f, "{}", v),
+            SimDataValue::String(v) | SimDataValue::HashedString(v, _) => match v {
+                Some(text) => write!(f, "{:?}", text),
+                None => write!(f, "null"),
+            },
+            SimDataValue::Object(None) => write!(f, "null"),
+            SimDataValue::Object(Some(object)) => {
+                write!(f, "{} {{ ", object.schema.as_deref().unwrap_or("object"))?;
+                for (i, (name, value)) in object.fields.iter().enumerate() {
+                    write!(f, "{}{}: {}", if i > 0 { ", " } else { "" }, name, value)?;
+                }
+                write!(f, " }}")
+            }
+            SimDataValue::Vector(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { ", " } else { "" }, element)?;
+                }
+                write!(f, "]")
+            }
+            SimDataValue::Float2(v) => write!(f, "({}, {})", v[0], v[1]),
+            SimDataValue::Float3(v) => write!(f, "({}, {}, {})", v[0], v[1], v[2]),
+            SimDataValue::Float4(v) => write!(f, "({}, {}, {}, {})", v[0], v[1], v[2], v[3]),
+            SimDataValue::TableSetReference(v) => write!(f, "0x{:016X}", v),
+            SimDataValue::ResourceKey(tgi) => write!(f, "{}", tgi),
+            SimDataValue::LocalizationKey(v) => write!(f, "0x{:08X}", v),
+            SimDataValue::Variant { type_hash, value: None } => write!(f, "variant 0x{:08X} null", type_hash),
+            SimDataValue::Variant { type_hash, value: Some(value) } => write!(f, "variant 0x{:08X} {}", type_hash, value),
+            SimDataValue::Unknown(code, bytes) => write!(f, "type {} {:02X?}", code, bytes),
+        }
+    }
+}
+
+/// Reads the layout of a SimData resource: its tables and schemas, with every name and offset
+/// resolved
+pub(crate) struct SimDataReader<'a> {
+    pub data: &'a [u8],
+    pub tables: &'a [SimDataTable],
+    pub schemas: &'a [SimDataSchema],
+}
+
+fn too_short(what: &str) -> Error {
+    Error::InvalidResource(format!("SimData is too short for its {}", what))
+}
+
+impl<'a> SimDataReader<'a> {
+    fn slice(&self, position: usize, len: usize, what: &str) -> Result<&'a [u8]> {
+        self.data.get(position..position + len).ok_or_else(|| too_short(what))
+    }
+
+    fn u32_at(&self, position: usize) -> Result<u32> {
+        Ok(LittleEndian::read_u32(self.slice(position, 4, "header")?))
+    }
+
+    /// Follows the offset field at `position`, which counts from the field itself
+    fn offset_at(&self, position: usize) -> Result<Option<usize>> {
+        let offset = self.u32_at(position)? as i32;
+        if offset == NULL_OFFSET {
+            return Ok(None);
+        }
+        let target = position as i64 + offset as i64;
+        if target < 0 || target as usize > self.data.len() {
+            return Err(Error::InvalidResource(format!("SimData offset at 0x{:X} points outside the data", position)));
+        }
+        Ok(Some(target as usize))
+    }
+
+    fn string_at(&self, position: usize) -> Result<Option<String>> {
+        let Some(start) = self.offset_at(position)? else { return Ok(None) };
+        let len = self.data[start..].iter().position(|&b| b == 0).ok_or_else(|| too_short("strings"))?;
+        Ok(Some(String::from_utf8_lossy(&self.data[start..start + len]).into_owned()))
+    }
+
+    /// The tables and schemas of `data`
+    pub fn layout(data: &'a [u8], version: u32) -> Result<(Vec<SimDataTable>, Vec<SimDataSchema>)> {
+        let reader = Self { data, tables: &[], schemas: &[] };
+        let mut tables = Vec::new();
+        let mut schemas = Vec::new();
+        let table_count = reader.u32_at(12)? as usize;
+        let schema_count = reader.u32_at(20)? as usize;
+        // Every table and schema takes up room in the data, so counts beyond that are corrupt
+        if table_count > data.len() / 28 {
+            return Err(Error::InvalidResource(format!("Invalid SimData table count {}", table_count)));
+        }
+        if schema_count > data.len() / 24 {
+            return Err(Error::InvalidResource(format!("Invalid SimData schema count {}", schema_count)));
+        }
+        if version >= 0x101 {
+            reader.slice(24, 4, "header")?;
+        }
+
+        // Schemas first, so tables and columns can refer to them by index
+        let mut schema_positions = Vec::with_capacity(schema_count);
+        if let Some(start) = reader.offset_at(16)?.filter(|_| schema_count > 0) {
+            for i in 0..schema_count {
+                let at = start + i * 24;
+                reader.slice(at, 24, "schemas")?;
+                schema_positions.push(at);
+            }
+        }
+        let schema_index = |position: Option<usize>| -> Result<Option<usize>> {
+            match position {
+                None => Ok(None),
+                Some(position) => schema_positions.iter().position(|&p| p == position).map(Some)
+                    .ok_or_else(|| Error::InvalidResource(format!("SimData refers to a schema at 0x{:X} it doesn't have", position))),
+            }
+        };
+        for &at in &schema_positions {
+            let column_count = reader.u32_at(at + 20)? as usize;
+            if column_count > data.len() / 20 {
+                return Err(Error::InvalidResource(format!("Invalid SimData column count {}", column_count)));
+            }
+            let mut columns = Vec::with_capacity(column_count);
+            if let Some(start) = reader.offset_at(at + 16)?.filter(|_| column_count > 0) {
+                for c in 0..column_count {
+                    let column = start + c * 20;
+                    let bytes = reader.slice(column, 20, "columns")?;
+                    columns.push(SimDataColumn {
+                        name: reader.string_at(column)?,
+                        name_hash: LittleEndian::read_u32(&bytes[4..]),
+                        data_type: SimDataType::from_code(LittleEndian::read_u16(&bytes[8..]) as u32),
+                        flags: LittleEndian::read_u16(&bytes[10..]),
+                        offset: LittleEndian::read_u32(&bytes[12..]),
+                        schema: schema_index(reader.offset_at(column + 16)?)?,
+                    });
+                }
+            }
+            schemas.push(SimDataSchema {
+                name: reader.string_at(at)?,
+                name_hash: reader.u32_at(at + 4)?,
+                schema_hash: reader.u32_at(at + 8)?,
+                size: reader.u32_at(at + 12)?,
+                columns,
+            });
+        }
+
+        if let Some(start) = reader.offset_at(8)?.filter(|_| table_count > 0) {
+            for i in 0..table_count {
+                let at = start + i * 28;
+                reader.slice(at, 28, "tables")?;
+                let table = SimDataTable {
+                    name: reader.string_at(at)?,
+                    name_hash: reader.u32_at(at + 4)?,
+                    schema: schema_index(reader.offset_at(at + 8)?)?,
+                    data_type: SimDataType::from_code(reader.u32_at(at + 12)?),
+                    row_size: reader.u32_at(at + 16)?,
+                    data_offset: reader.offset_at(at + 20)?.unwrap_or(0),
+                    row_count: reader.u32_at(at + 24)?,
+                };
+                let size = (table.row_size as usize).checked_mul(table.row_count as usize).ok_or_else(|| too_short("table rows"))?;
+                reader.slice(table.data_offset, size, "table rows")?;
+                tables.push(table);
+            }
+        }
+        Ok((tables, schemas))
+    }
+
+    fn table_containing(&self, position: usize) -> Option<&SimDataTable> {
+        self.tables.iter().find(|t| t.contains(position))
+    }
+
+    /// The object with `schema` at `position`
+    pub fn object(&self, schema: usize, position: usize, depth: usize) -> Result<SimDataObject> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidResource("SimData objects nest too deeply; the data may be circular".to_string()));
+        }
+        let schema = &self.schemas[schema];
+        let fields = schema.columns.iter()
+            .map(|column| {
+                let name = column.name.clone().unwrap_or_else(|| format!("{:08X}", column.name_hash));
+                let value = self.value(column.data_type, column.schema, position + column.offset as usize, depth + 1)?;
+                Ok((name, value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(SimDataObject { schema: schema.name.clone(), fields })
+    }
+
+    /// What `position` points into: an object of the table's schema, or a value of its type
+    fn pointed_value(&self, position: usize, depth: usize) -> Result<SimDataValue> {
+        let table = self.table_containing(position)
+            .ok_or_else(|| Error::InvalidResource(format!("SimData points at 0x{:X}, outside every table", position)))?;
+        match table.schema {
+            Some(schema) => Ok(SimDataValue::Object(Some(self.object(schema, position, depth)?))),
+            None => self.value(table.data_type, None, position, depth),
+        }
+    }
+
+    pub fn value(&self, data_type: SimDataType, schema: Option<usize>, position: usize, depth: usize) -> Result<SimDataValue> {
+        let bytes = self.slice(position, data_type.size(), "values")?;
+        let f32_at = |i: usize| LittleEndian::read_f32(&bytes[i * 4..]);
+        Ok(match data_type {
+            SimDataType::Boolean => SimDataValue::Boolean(bytes[0] != 0),
+            SimDataType::Character => SimDataValue::Character(bytes[0]),
+            SimDataType::Int8 => SimDataValue::Int8(bytes[0] as i8),
+            SimDataType::UInt8 => SimDataValue::UInt8(bytes[0]),
+            SimDataType::Int16 => SimDataValue::Int16(LittleEndian::read_i16(bytes)),
+            SimDataType::UInt16 => SimDataValue::UInt16(LittleEndian::read_u16(bytes)),
+            SimDataType::Int32 => SimDataValue::Int32(LittleEndian::read_i32(bytes)),
+            SimDataType::UInt32 => SimDataValue::UInt32(LittleEndian::read_u32(bytes)),
+            SimDataType::Int64 => SimDataValue::Int64(LittleEndian::read_i64(bytes)),
+            SimDataType::UInt64 => SimDataValue::UInt64(LittleEndian::read_u64(bytes)),
+            SimDataType::Float => SimDataValue::Float(f32_at(0)),
+            SimDataType::String => SimDataValue::String(self.string_at(position)?),
+            SimDataType::HashedString => SimDataValue::HashedString(self.string_at(position)?, LittleEndian::read_u32(&bytes[4..])),
+            SimDataType::Object => match self.offset_at(position)? {
+                None => SimDataValue::Object(None),
+                Some(target) => match schema {
+                    Some(schema) => SimDataValue::Object(Some(self.object(schema, target, depth)?)),
+                    None => self.pointed_value(target, depth)?,
+                },
+            },
+            SimDataType::Vector => {
+                let count = LittleEndian::read_u32(&bytes[4..]) as usize;
+                match self.offset_at(position)?.filter(|_| count > 0) {
+                    None => SimDataValue::Vector(Vec::new()),
+                    Some(start) => {
+                        let table = self.table_containing(start)
+                            .ok_or_else(|| Error::InvalidResource(format!("SimData vector at 0x{:X} is outside every table", start)))?;
+                        let stride = table.row_size as usize;
+                        if stride == 0 {
+                            return Err(Error::InvalidResource(format!("SimData vector at 0x{:X} is in a table with no row size", start)));
+                        }
+                        SimDataValue::Vector((0..count)
+                            .map(|i| self.pointed_value(start + i * stride, depth + 1))
+                            .collect::<Result<_>>()?)
+                    }
+                }
+            }
+            SimDataType::Float2 => SimDataValue::Float2([f32_at(0), f32_at(1)]),
+            SimDataType::Float3 => SimDataValue::Float3([f32_at(0), f32_at(1), f32_at(2)]),
+            SimDataType::Float4 => SimDataValue::Float4([f32_at(0), f32_at(1), f32_at(2), f32_at(3)]),
+            SimDataType::TableSetReference => SimDataValue::TableSetReference(LittleEndian::read_u64(bytes)),
+            SimDataType::ResourceKey => SimDataValue::ResourceKey(TGI {
+                instance: LittleEndian::read_u64(bytes),
+                res_type: LittleEndian::read_u32(&bytes[8..]),
+                res_group: LittleEndian::read_u32(&bytes[12..]),
+            }),
+            SimDataType::LocalizationKey => SimDataValue::LocalizationKey(LittleEndian::read_u32(bytes)),
+            SimDataType::Variant => {
+                let type_hash = LittleEndian::read_u32(&bytes[4..]);
+                let value = match self.offset_at(position)? {
+                    None => None,
+                    Some(target) => Some(Box::new(self.pointed_value(target, depth + 1)?)),
+                };
+                SimDataValue::Variant { type_hash, value }
+            }
+            SimDataType::Unknown(code) => SimDataValue::Unknown(code, bytes.to_vec()),
+        })
+    }
+}
+
+/// Where an offset field points, resolved once every table has its place in the output
+#[derive(Debug, Clone, Copy)]
+struct Target {
+    table: usize,
+    byte: usize,
+}
+
+/// A table being filled in, with the offset fields in its rows still to resolve
+#[derive(Debug)]
+struct PlannedTable {
+    name: Option<String>,
+    schema: Option<usize>,
+    data_type: SimDataType,
+    row_size: usize,
+    alignment: usize,
+    data: Vec<u8>,
+    fixups: Vec<(usize, Target)>,
+}
+
+/// Lays out SimData from its schemas and instances: a named table per instance, the objects
+/// and values they point to in one unnamed table per schema or type, and the text of strings
+/// in a character table
+pub(crate) struct SimDataWriter<'a> {
+    schemas: &'a [SimDataSchema],
+    tables: Vec<PlannedTable>,
+}
+
+fn align(position: usize, alignment: usize) -> usize {
+    position.div_ceil(alignment.max(1)) * alignment.max(1)
+}
+
+fn put_u32(data: &mut [u8], position: usize, value: u32) {
+    LittleEndian::write_u32(&mut data[position..], value);
+}
+
+/// Writes the offset field at `position` so it points at `target`, or nowhere
+fn put_offset(data: &mut [u8], position: usize, target: Option<usize>) {
+    let offset = target.map_or(NULL_OFFSET, |target| (target as i64 - position as i64) as i32);
+    LittleEndian::write_i32(&mut data[position..], offset);
+}
+
+impl<'a> SimDataWriter<'a> {
+    /// The bytes of a SimData resource holding `instances`, each with the name of its table.
+    /// `header_word` fills the extra header field of version 0x101 and later.
+    pub fn write(version: u32, header_word: u32, schemas: &'a [SimDataSchema], instances: &[(String, SimDataObject)]) -> Result<Vec<u8>> {
+        let mut writer = Self { schemas, tables: Vec::new() };
+        for (name, object) in instances {
+            let schema = writer.schema_of(object)?;
+            // Rows of the same instance table are kept together
+            let table = match writer.tables.last() {
+                Some(last) if last.name.as_deref() == Some(name.as_str()) && last.schema == Some(schema) => writer.tables.len() - 1,
+                _ => writer.add_table(Some(name.clone()), Some(schema), SimDataType::Object),
+            };
+            let at = writer.alloc(table, 1);
+            writer.write_object(table, at, schema, object, 0)?;
+        }
+        Ok(writer.finish(version, header_word))
+    }
+
+    fn schema_named(&self, name: &str) -> Result<usize> {
+        self.schemas.iter().position(|s| s.name.as_deref() == Some(name))
+            .ok_or_else(|| Error::InvalidInput(format!("SimData has no schema named '{}'", name)))
+    }
+
+    fn schema_of(&self, object: &SimDataObject) -> Result<usize> {
+        let name = object.schema.as_deref()
+            .ok_or_else(|| Error::InvalidInput("A SimData object has no schema".to_string()))?;
+        self.schema_named(name)
+    }
+
+    fn add_table(&mut self, name: Option<String>, schema: Option<usize>, data_type: SimDataType) -> usize {
+        let (row_size, alignment) = match schema {
+            Some(schema) => {
+                let schema = &self.schemas[schema];
+                (schema.size as usize, schema.columns.iter().map(|c| c.data_type.alignment()).max().unwrap_or(1))
+            }
+            None => (data_type.size(), data_type.alignment()),
+        };
+        self.tables.push(PlannedTable { name, schema, data_type, row_size, alignment, data: Vec::new(), fixups: Vec::new() });
+        self.tables.len() - 1
+    }
+
+    /// The unnamed table objects of `schema`, or values of `data_type`, go in
+    fn table_for(&mut self, schema: Option<usize>, data_type: SimDataType) -> usize {
+        let found = self.tables.iter().position(|t| t.name.is_none() && t.schema == schema && (schema.is_some() || t.data_type == data_type));
+        found.unwrap_or_else(|| self.add_table(None, schema, data_type))
+    }
+
+    /// Adds `count` empty rows to `table`, returning where the first starts
+    fn alloc(&mut self, table: usize, count: usize) -> usize {
+        let table = &mut self.tables[table];
+        let at = table.data.len();
+        table.data.resize(at + table.row_size * count, 0);
+        at
+    }
+
+    fn put(&mut self, table: usize, at: usize, bytes: &[u8]) {
+        self.tables[table].data[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn point(&mut self, table: usize, at: usize, target: Option<Target>) {
+        match target {
+            Some(target) => self.tables[table].fixups.push((at, target)),
+            None => self.put(table, at, &NULL_OFFSET.to_le_bytes()),
+        }
+    }
+
+    fn write_object(&mut self, table: usize, at: usize, schema: usize, object: &SimDataObject, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidInput("SimData objects nest too deeply".to_string()));
+        }
+        let schemas = self.schemas;
+        for column in &schemas[schema].columns {
+            let name = column.name.clone().unwrap_or_else(|| format!("{:08X}", column.name_hash));
+            let value = object.get(&name)
+                .ok_or_else(|| Error::InvalidInput(format!("SimData object of schema {} has no field '{}'", schemas[schema].name.as_deref().unwrap_or("?"), name)))?;
+            self.write_value(table, at + column.offset as usize, column.data_type, column.schema, value, depth + 1)
+                .with_context(|| format!("In field '{}'", name))?;
+        }
+        Ok(())
+    }
+
+    /// Puts `value` in a row of the table for its kind and returns where, for offset fields that
+    /// don't say what they point to
+    fn write_pointed(&mut self, value: &SimDataValue, depth: usize) -> Result<Option<Target>> {
+        match value {
+            SimDataValue::Object(None) => Ok(None),
+            SimDataValue::Object(Some(object)) => {
+                let schema = self.schema_of(object)?;
+                let table = self.table_for(Some(schema), SimDataType::Object);
+                let at = self.alloc(table, 1);
+                self.write_object(table, at, schema, object, depth)?;
+                Ok(Some(Target { table, byte: at }))
+            }
+            value => {
+                let table = self.table_for(None, value.data_type());
+                let at = self.alloc(table, 1);
+                self.write_value(table, at, value.data_type(), None, value, depth)?;
+                Ok(Some(Target { table, byte: at }))
+            }
+        }
+    }
+
+    fn write_string(&mut self, table: usize, at: usize, text: Option<&str>) {
+        let target = text.map(|text| {
+            let strings = self.table_for(None, SimDataType::Character);
+            let start = self.alloc(strings, text.len() + 1);
+            self.put(strings, start, text.as_bytes());
+            Target { table: strings, byte: start }
+        });
+        self.point(table, at, target);
+    }
+
+    fn write_vector(&mut self, table: usize, at: usize, elements: &[SimDataValue], depth: usize) -> Result<()> {
+        self.put(table, at + 4, &(elements.len() as u32).to_le_bytes());
+        let Some(first) = elements.first() else {
+            self.point(table, at, None);
+            return Ok(());
+        };
+        let schema = match first {
+            SimDataValue::Object(Some(object)) => Some(self.schema_of(object)?),
+            SimDataValue::Object(None) => return Err(Error::InvalidInput("SimData vectors can't hold null objects".to_string())),
+            _ => None,
+        };
+        let data_type = first.data_type();
+        let elements_table = self.table_for(schema, data_type);
+        let stride = self.tables[elements_table].row_size;
+        let start = self.alloc(elements_table, elements.len());
+        for (i, element) in elements.iter().enumerate() {
+            let position = start + i * stride;
+            match (element, schema) {
+                (SimDataValue::Object(Some(object)), Some(schema)) if self.schema_of(object)? == schema => {
+                    self.write_object(elements_table, position, schema, object, depth + 1)?;
+                }
+                (element, None) if element.data_type() == data_type => {
+                    self.write_value(elements_table, position, data_type, None, element, depth + 1)?;
+                }
+                _ => return Err(Error::InvalidInput("SimData vector elements must all be of one type".to_string())),
+            }
+        }
+        self.point(table, at, Some(Target { table: elements_table, byte: start }));
+        Ok(())
+    }
+
+    fn write_value(&mut self, table: usize, at: usize, data_type: SimDataType, schema: Option<usize>, value: &SimDataValue, depth: usize) -> Result<()> {
+        if data_type == SimDataType::Object && schema.is_none() && value.data_type() != SimDataType::Object {
+            // An object column without a schema can point at a plain value
+            let target = self.write_pointed(value, depth)?;
+            self.point(table, at, target);
+            return Ok(());
+        }
+        if value.data_type() != data_type {
+            return Err(Error::InvalidInput(format!("Expected a {} value, got a {}", data_type, value.data_type())));
+        }
+        match value {
+            SimDataValue::Boolean(v) => self.put(table, at, &[*v as u8]),
+            SimDataValue::Character(v) | SimDataValue::UInt8(v) => self.put(table, at, &[*v]),
+            SimDataValue::Int8(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::Int16(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::UInt16(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::Int32(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::UInt32(v) | SimDataValue::LocalizationKey(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::Int64(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::UInt64(v) | SimDataValue::TableSetReference(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::Float(v) => self.put(table, at, &v.to_le_bytes()),
+            SimDataValue::Float2(v) => self.put(table, at, &v.iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<_>>()),
+            SimDataValue::Float3(v) => self.put(table, at, &v.iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<_>>()),
+            SimDataValue::Float4(v) => self.put(table, at, &v.iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<_>>()),
+            SimDataValue::String(text) => self.write_string(table, at, text.as_deref()),
+            SimDataValue::HashedString(text, hash) => {
+                self.write_string(table, at, text.as_deref());
+                self.put(table, at + 4, &hash.to_le_bytes());
+            }
+            SimDataValue::ResourceKey(tgi) => {
+                self.put(table, at, &tgi.instance.to_le_bytes());
+                self.put(table, at + 8, &tgi.res_type.to_le_bytes());
+                self.put(table, at + 12, &tgi.res_group.to_le_bytes());
+            }
+            SimDataValue::Object(Some(object)) => {
+                let schema = match schema {
+                    Some(schema) => schema,
+                    None => self.schema_of(object)?,
+                };
+                let objects = self.table_for(Some(schema), SimDataType::Object);
+                let start = self.alloc(objects, 1);
+                self.write_object(objects, start, schema, object, depth)?;
+                self.point(table, at, Some(Target { table: objects, byte: start }));
+            }
+            SimDataValue::Object(None) => self.point(table, at, None),
+            SimDataValue::Vector(elements) => self.write_vector(table, at, elements, depth)?,
+            SimDataValue::Variant { type_hash, value } => {
+                let target = match value {
+                    Some(value) => self.write_pointed(value, depth + 1)?,
+                    None => None,
+                };
+                self.point(table, at, target);
+                self.put(table, at + 4, &type_hash.to_le_bytes());
+            }
+            // Types this parser doesn't know take no space in a row
+            SimDataValue::Unknown(..) => {}
+        }
+        Ok(())
+    }
+
+    /// Places the header, table and schema lists, the tables' rows and then every name, and
+    /// resolves the offsets between them
+    fn finish(self, version: u32, header_word: u32) -> Vec<u8> {
+        let header_size = if version >= 0x101 { 28 } else { 24 };
+        let tables_at = header_size;
+        let schemas_at = tables_at + self.tables.len() * 28;
+        let mut columns_at = Vec::with_capacity(self.schemas.len());
+        let mut position = schemas_at + self.schemas.len() * 24;
+        for schema in self.schemas {
+            columns_at.push(position);
+            position += schema.columns.len() * 20;
+        }
+        position = align(position, 16);
+        let mut data_at = Vec::with_capacity(self.tables.len());
+        for table in &self.tables {
+            position = align(position, table.alignment);
+            data_at.push(position);
+            position += table.data.len();
+        }
+
+        // Names are written once each, after the data
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut text = Vec::new();
+        let every_name = self.tables.iter().filter_map(|t| t.name.as_deref())
+            .chain(self.schemas.iter().filter_map(|s| s.name.as_deref()))
+            .chain(self.schemas.iter().flat_map(|s| s.columns.iter().filter_map(|c| c.name.as_deref())));
+        for name in every_name {
+            names.entry(name).or_insert_with(|| {
+                let at = position + text.len();
+                text.extend_from_slice(name.as_bytes());
+                text.push(0);
+                at
+            });
+        }
+        let mut out = vec![0u8; position + text.len()];
+        out[position..].copy_from_slice(&text);
+        let name_at = |name: &Option<String>| name.as_deref().map(|name| names[name]);
+
+        out[0..4].copy_from_slice(b"DATA");
+        put_u32(&mut out, 4, version);
+        put_offset(&mut out, 8, Some(tables_at).filter(|_| !self.tables.is_empty()));
+        put_u32(&mut out, 12, self.tables.len() as u32);
+        put_offset(&mut out, 16, Some(schemas_at).filter(|_| !self.schemas.is_empty()));
+        put_u32(&mut out, 20, self.schemas.len() as u32);
+        if version >= 0x101 {
+            put_u32(&mut out, 24, header_word);
+        }
+
+        for (i, schema) in self.schemas.iter().enumerate() {
+            let at = schemas_at + i * 24;
+            put_offset(&mut out, at, name_at(&schema.name));
+            put_u32(&mut out, at + 4, schema.name_hash);
+            put_u32(&mut out, at + 8, schema.schema_hash);
+            put_u32(&mut out, at + 12, schema.size);
+            put_offset(&mut out, at + 16, Some(columns_at[i]).filter(|_| !schema.columns.is_empty()));
+            put_u32(&mut out, at + 20, schema.columns.len() as u32);
+            for (c, column) in schema.columns.iter().enumerate() {
+                let at = columns_at[i] + c * 20;
+                put_offset(&mut out, at, name_at(&column.name));
+                put_u32(&mut out, at + 4, column.name_hash);
+                LittleEndian::write_u16(&mut out[at + 8..], column.data_type.code() as u16);
+                LittleEndian::write_u16(&mut out[at + 10..], column.flags);
+                put_u32(&mut out, at + 12, column.offset);
+                put_offset(&mut out, at + 16, column.schema.map(|s| schemas_at + s * 24));
+            }
+        }
+
+        for (i, table) in self.tables.iter().enumerate() {
+            let at = tables_at + i * 28;
+            put_offset(&mut out, at, name_at(&table.name));
+            put_u32(&mut out, at + 4, table.name.as_deref().map_or(0, crate::instance::fnv32));
+            put_offset(&mut out, at + 8, table.schema.map(|s| schemas_at + s * 24));
+            put_u32(&mut out, at + 12, table.data_type.code());
+            put_u32(&mut out, at + 16, table.row_size as u32);
+            put_offset(&mut out, at + 20, Some(data_at[i]));
+            put_u32(&mut out, at + 24, table.data.len().checked_div(table.row_size).unwrap_or(0) as u32);
+
+            out[data_at[i]..data_at[i] + table.data.len()].copy_from_slice(&table.data);
+            for &(byte, target) in &table.fixups {
+                put_offset(&mut out, data_at[i] + byte, Some(data_at[target.table] + target.byte));
+            }
+        }
+        out
+    }
+}
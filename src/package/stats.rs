@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::package::index::{CompressionScheme, IndexEntry, TGI};
+
+/// Resource count and sizes for one slice of a [`CompressionReport`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionTotals {
+    pub count: usize,
+    /// Bytes taken up in the file
+    pub stored_bytes: u64,
+    /// Bytes once decompressed
+    pub memory_bytes: u64,
+}
+
+impl CompressionTotals {
+    fn add(&mut self, entry: &IndexEntry) {
+        self.count += 1;
+        self.stored_bytes += entry.filesize as u64;
+        self.memory_bytes += entry.memsize as u64;
+    }
+
+    /// Stored size as a fraction of the decompressed size (1.0 when nothing is saved)
+    pub fn ratio(&self) -> f64 {
+        if self.memory_bytes == 0 {
+            1.0
+        } else {
+            self.stored_bytes as f64 / self.memory_bytes as f64
+        }
+    }
+}
+
+/// Totals for one resource type, overall and split by compression scheme
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeCompression {
+    pub total: CompressionTotals,
+    pub by_scheme: BTreeMap<CompressionScheme, CompressionTotals>,
+}
+
+/// How a package's resources are compressed, from its index alone
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub total: CompressionTotals,
+    pub by_scheme: BTreeMap<CompressionScheme, CompressionTotals>,
+    pub by_type: BTreeMap<u32, TypeCompression>,
+}
+
+impl CompressionReport {
+    pub fn from_entries(entries: &[IndexEntry]) -> Self {
+        let mut report = Self::default();
+        for entry in entries {
+            let scheme = entry.scheme();
+            report.total.add(entry);
+            report.by_scheme.entry(scheme).or_default().add(entry);
+            let by_type = report.by_type.entry(entry.tgi.res_type).or_default();
+            by_type.total.add(entry);
+            by_type.by_scheme.entry(scheme).or_default().add(entry);
+        }
+        report
+    }
+
+    /// Totals for the resources stored without compression
+    pub fn uncompressed(&self) -> CompressionTotals {
+        self.by_scheme.get(&CompressionScheme::Uncompressed).copied().unwrap_or_default()
+    }
+}
+
+/// What one resource type contributed to a merge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeTypeStats {
+    /// Resources of the type in the merged output
+    pub merged: usize,
+    /// Input packages with at least one resource of the type
+    pub source_packages: usize,
+    /// Copies left out because the copy kept has the same data
+    pub deduplicated: usize,
+    /// Copies left out because another package's different copy won
+    pub overridden: usize,
+    /// Bytes the type takes up in the output
+    pub stored_bytes: u64,
+    /// Bytes once decompressed
+    pub memory_bytes: u64,
+}
+
+impl MergeTypeStats {
+    fn add(&mut self, other: &MergeTypeStats) {
+        self.merged += other.merged;
+        self.source_packages += other.source_packages;
+        self.deduplicated += other.deduplicated;
+        self.overridden += other.overridden;
+        self.stored_bytes += other.stored_bytes;
+        self.memory_bytes += other.memory_bytes;
+    }
+}
+
+/// Per-type breakdown of a merge: where its resources came from, what was left out and how
+/// much of the output each type takes up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub by_type: BTreeMap<u32, MergeTypeStats>,
+}
+
+impl MergeStats {
+    /// Counts one input package, given its resource keys
+    pub fn add_source(&mut self, tgis: &[TGI]) {
+        let types: BTreeSet<u32> = tgis.iter().map(|tgi| tgi.res_type).collect();
+        for res_type in types {
+            self.by_type.entry(res_type).or_default().source_packages += 1;
+        }
+    }
+
+    /// Counts a copy of a resource the merge left out; `identical` when the copy kept has the
+    /// same data
+    pub fn add_dropped(&mut self, res_type: u32, identical: bool) {
+        let stats = self.by_type.entry(res_type).or_default();
+        if identical {
+            stats.deduplicated += 1;
+        } else {
+            stats.overridden += 1;
+        }
+    }
+
+    /// Counts the resources of a written output from its index
+    pub fn add_output(&mut self, entries: &[IndexEntry]) {
+        for entry in entries {
+            let stats = self.by_type.entry(entry.tgi.res_type).or_default();
+            stats.merged += 1;
+            stats.stored_bytes += entry.filesize as u64;
+            stats.memory_bytes += entry.memsize as u64;
+        }
+    }
+
+    /// Every type added together. Its `source_packages` counts a package once per type it has.
+    pub fn total(&self) -> MergeTypeStats {
+        let mut total = MergeTypeStats::default();
+        for stats in self.by_type.values() {
+            total.add(stats);
+        }
+        total
+    }
+}
@@ -0,0 +1,248 @@
+use crate::package::index::TGI;
+use crate::package::resource::{ResourceKind, TypedResource};
+
+/// Bytes of a resource [`guess_structure`] looks at; the rest is left as one unknown region
+pub const MAX_GUESS_BYTES: usize = 64 * 1024;
+
+/// Longest length-prefixed string [`guess_structure`] recognises
+const MAX_TEXT_LEN: usize = 1024;
+/// Largest count [`guess_structure`] takes as the length of an array
+const MAX_ARRAY_COUNT: usize = 4096;
+/// Record sizes tried for arrays of unknown records
+const RECORD_SIZES: [usize; 12] = [4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 48, 64];
+
+/// How a string's length is given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// u32 byte count, then ASCII
+    LengthPrefixed,
+    /// 7-bit encoded byte count, then big-endian UTF-16, as in CAS part names
+    SevenBitUtf16Be,
+    /// ASCII ending in a NUL byte
+    NullTerminated,
+}
+
+/// What a run of bytes looks like
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldGuess {
+    /// A resource key with a known resource type, stored type-group-instance, or
+    /// instance-type-group when `itg` is set
+    Tgi { tgi: TGI, itg: bool },
+    Text { text: String, encoding: TextEncoding },
+    /// Consecutive 32-bit floats in a range real data uses
+    Floats(Vec<f32>),
+    /// A count (`count_size` bytes) followed by that many records of `record_size` bytes
+    Array { count: u32, count_size: usize, record_size: usize, tgis: bool },
+    /// Nothing recognisable
+    Unknown,
+}
+
+/// One run of bytes and what it looks like
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub offset: usize,
+    pub len: usize,
+    pub guess: FieldGuess,
+}
+
+/// Guesses at the layout of a resource in an unknown format: TGIs, strings, float runs and
+/// counts followed by arrays, with whatever is left over as unknown regions.
+///
+/// These are heuristics for reverse-engineering new formats, not a parse; a guess can be
+/// wrong, and a field can be missed.
+pub fn guess_structure(data: &[u8]) -> Vec<Region> {
+    let scanned = &data[..data.len().min(MAX_GUESS_BYTES)];
+    let mut regions: Vec<Region> = Vec::new();
+    let mut pos = 0;
+    while pos < scanned.len() {
+        if let Some((len, guess)) = guess_at(scanned, pos) {
+            regions.push(Region { offset: pos, len, guess });
+            pos += len;
+            continue;
+        }
+        match regions.last_mut() {
+            Some(region) if region.guess == FieldGuess::Unknown => region.len += 1,
+            _ => regions.push(Region { offset: pos, len: 1, guess: FieldGuess::Unknown }),
+        }
+        pos += 1;
+    }
+    if data.len() > scanned.len() {
+        regions.push(Region { offset: scanned.len(), len: data.len() - scanned.len(), guess: FieldGuess::Unknown });
+    }
+    regions
+}
+
+/// Finds resource keys anywhere in `data`, not only in the part [`guess_structure`] looks at:
+/// the offset of each, the key and whether it's stored instance-type-group. Keeps the first
+/// `limit`, and returns how many there are in all.
+pub fn find_tgis(data: &[u8], limit: usize) -> (Vec<(usize, TGI, bool)>, usize) {
+    let mut found = Vec::new();
+    let mut total = 0;
+    let mut pos = 0;
+    while pos + 16 <= data.len() {
+        match [false, true].into_iter().find_map(|itg| tgi_at(data, pos, itg).map(|tgi| (tgi, itg))) {
+            Some((tgi, itg)) => {
+                if found.len() < limit {
+                    found.push((pos, tgi, itg));
+                }
+                total += 1;
+                pos += 16;
+            }
+            None => pos += 1,
+        }
+    }
+    (found, total)
+}
+
+/// The field starting at `pos` and its length, trying the least ambiguous guesses first
+fn guess_at(data: &[u8], pos: usize) -> Option<(usize, FieldGuess)> {
+    for itg in [false, true] {
+        if let Some(tgi) = tgi_at(data, pos, itg) {
+            return Some((16, FieldGuess::Tgi { tgi, itg }));
+        }
+    }
+    text_at(data, pos)
+        .or_else(|| array_at(data, pos))
+        .or_else(|| floats_at(data, pos))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(pos..pos + 8)?.try_into().ok()?))
+}
+
+/// A TGI at `pos` whose type is one the wrappers know and whose instance isn't 0
+fn tgi_at(data: &[u8], pos: usize, itg: bool) -> Option<TGI> {
+    let tgi = if itg {
+        TGI { res_type: u32_at(data, pos + 8)?, res_group: u32_at(data, pos + 12)?, instance: u64_at(data, pos)? }
+    } else {
+        TGI { res_type: u32_at(data, pos)?, res_group: u32_at(data, pos + 4)?, instance: u64_at(data, pos + 8)? }
+    };
+    let known = TypedResource::kind_of(tgi.res_type) != ResourceKind::Generic;
+    (known && tgi.instance != 0).then_some(tgi)
+}
+
+fn printable(b: u8) -> bool {
+    b.is_ascii_graphic() || b == b' '
+}
+
+fn text_at(data: &[u8], pos: usize) -> Option<(usize, FieldGuess)> {
+    // u32 length, then ASCII
+    if let Some(len) = u32_at(data, pos).map(|l| l as usize).filter(|l| (2..=MAX_TEXT_LEN).contains(l)) {
+        if let Some(bytes) = data.get(pos + 4..pos + 4 + len).filter(|b| b.iter().all(|&b| printable(b))) {
+            let text = String::from_utf8_lossy(bytes).to_string();
+            return Some((4 + len, FieldGuess::Text { text, encoding: TextEncoding::LengthPrefixed }));
+        }
+    }
+    // One byte 7-bit length, then big-endian UTF-16 that is all ASCII
+    let len = *data.get(pos)? as usize;
+    if (4..0x80).contains(&len) && len.is_multiple_of(2) {
+        if let Some(bytes) = data.get(pos + 1..pos + 1 + len) {
+            if bytes.chunks_exact(2).all(|c| c[0] == 0 && printable(c[1])) {
+                let text = bytes.chunks_exact(2).map(|c| c[1] as char).collect();
+                return Some((1 + len, FieldGuess::Text { text, encoding: TextEncoding::SevenBitUtf16Be }));
+            }
+        }
+    }
+    // At least 4 ASCII characters and a NUL
+    let run = data[pos..].iter().take_while(|&&b| printable(b)).count();
+    if run >= 4 && data.get(pos + run) == Some(&0) {
+        let text = String::from_utf8_lossy(&data[pos..pos + run]).to_string();
+        return Some((run + 1, FieldGuess::Text { text, encoding: TextEncoding::NullTerminated }));
+    }
+    None
+}
+
+fn array_at(data: &[u8], pos: usize) -> Option<(usize, FieldGuess)> {
+    // A u32 or u8 count of TGIs, in either order
+    for count_size in [4, 1] {
+        let count = match count_size {
+            4 => u32_at(data, pos)? as usize,
+            _ => *data.get(pos)? as usize,
+        };
+        if count == 0 || count > MAX_ARRAY_COUNT {
+            continue;
+        }
+        let start = pos + count_size;
+        for itg in [false, true] {
+            if (0..count).all(|i| tgi_at(data, start + i * 16, itg).is_some()) {
+                let guess = FieldGuess::Array { count: count as u32, count_size, record_size: 16, tgis: true };
+                return Some((count_size + count * 16, guess));
+            }
+        }
+    }
+    // A u32 count of fixed-size records running exactly to the end of the data
+    let count = u32_at(data, pos)? as usize;
+    if !(2..=MAX_ARRAY_COUNT).contains(&count) {
+        return None;
+    }
+    let remaining = data.len() - pos - 4;
+    let record_size = RECORD_SIZES.into_iter().find(|size| count * size == remaining)?;
+    Some((4 + remaining, FieldGuess::Array { count: count as u32, count_size: 4, record_size, tgis: false }))
+}
+
+/// 0, or a finite float far enough from 0 and small enough to be a real value rather than an
+/// integer or packed bytes read as a float
+fn plausible_float(bits: u32) -> bool {
+    let value = f32::from_bits(bits);
+    bits == 0 || (value.is_finite() && (1e-4..=1e5).contains(&value.abs()))
+}
+
+fn floats_at(data: &[u8], pos: usize) -> Option<(usize, FieldGuess)> {
+    let mut values = Vec::new();
+    while let Some(bits) = u32_at(data, pos + values.len() * 4).filter(|&b| plausible_float(b)) {
+        if bits == 0 && values.is_empty() {
+            return None;
+        }
+        values.push(f32::from_bits(bits));
+    }
+    while values.last() == Some(&0.0) {
+        values.pop();
+    }
+    let non_zero = values.iter().filter(|v| **v != 0.0).count();
+    (values.len() >= 3 && non_zero >= 2).then(|| (values.len() * 4, FieldGuess::Floats(values)))
+}
+
+impl std::fmt::Display for FieldGuess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const SHOWN_FLOATS: usize = 8;
+        match self {
+            FieldGuess::Tgi { tgi, itg: false } => write!(f, "TGI {}", tgi),
+            FieldGuess::Tgi { tgi, itg: true } => write!(f, "TGI {} (stored instance, type, group)", tgi),
+            FieldGuess::Text { text, encoding } => {
+                let encoding = match encoding {
+                    TextEncoding::LengthPrefixed => "u32 length, ASCII",
+                    TextEncoding::SevenBitUtf16Be => "7-bit length, UTF-16BE",
+                    TextEncoding::NullTerminated => "NUL-terminated ASCII",
+                };
+                write!(f, "text {:?} ({})", text, encoding)
+            }
+            FieldGuess::Floats(values) => {
+                let shown: Vec<String> = values.iter().take(SHOWN_FLOATS).map(|v| v.to_string()).collect();
+                write!(f, "{} floats: {}", values.len(), shown.join(", "))?;
+                if values.len() > SHOWN_FLOATS {
+                    write!(f, ", ...")?;
+                }
+                Ok(())
+            }
+            FieldGuess::Array { count, count_size, record_size, tgis } => {
+                let width = if *count_size == 1 { "u8" } else { "u32" };
+                write!(f, "{} count {}, then {} x {}-byte records", width, count, count, record_size)?;
+                if *tgis {
+                    write!(f, " (TGIs)")?;
+                }
+                Ok(())
+            }
+            FieldGuess::Unknown => write!(f, "not recognised"),
+        }
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08X}  {:>6} bytes  {}", self.offset, self.len, self.guess)
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use rayon::prelude::*;
+use crate::error::{Error, Result};
+use crate::package::{is_refpack, refpack_inflate, Package};
+use crate::package::header::{HeaderIssue, PackageHeader};
+use crate::package::index::{CompressionScheme, IndexEntry};
+use crate::package::resource::TypedResource;
+use crate::report::catch_panic;
+
+/// Something wrong with one index entry, found by [`Package::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryIssue {
+    /// The entry's data runs past the end of the file
+    OutOfBounds { offset: u32, filesize: u32, file_len: u64 },
+    /// The entry's data starts inside the package header
+    InsideHeader { offset: u32 },
+    /// A compression code the game doesn't know
+    UnknownCompression(u16),
+    DecompressionFailed(String),
+    /// The data decompresses (or is stored) to a different size than the index says
+    SizeMismatch { memsize: u32, actual: usize },
+    /// The resource has a wrapper but doesn't parse with it
+    ParseFailed(String),
+}
+
+impl std::fmt::Display for EntryIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryIssue::OutOfBounds { offset, filesize, file_len } => write!(
+                f, "data at 0x{:X} ({} bytes) runs past the end of the file ({} bytes)", offset, filesize, file_len,
+            ),
+            EntryIssue::InsideHeader { offset } => write!(f, "data at 0x{:X} starts inside the header", offset),
+            EntryIssue::UnknownCompression(code) => write!(f, "unknown compression 0x{:04X}", code),
+            EntryIssue::DecompressionFailed(error) => write!(f, "doesn't decompress: {}", error),
+            EntryIssue::SizeMismatch { memsize, actual } => write!(
+                f, "index says {} bytes but the data is {} bytes", memsize, actual,
+            ),
+            EntryIssue::ParseFailed(error) => write!(f, "doesn't parse: {}", error),
+        }
+    }
+}
+
+/// What [`Package::validate`] and [`validate_file`] found
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub header: Vec<HeaderIssue>,
+    /// Why the index couldn't be read, in which case no entries were checked
+    pub index_error: Option<String>,
+    /// Problem entries, in index order; an entry can have more than one
+    pub entries: Vec<(IndexEntry, EntryIssue)>,
+    /// Entries checked, not counting deleted ones
+    pub checked: usize,
+    /// Entries that parsed with a dedicated wrapper
+    pub parsed: usize,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.header.is_empty() && self.index_error.is_none() && self.entries.is_empty()
+    }
+
+    /// Number of problems found, counting an unreadable index as one
+    pub fn issue_count(&self) -> usize {
+        self.header.len() + self.index_error.iter().count() + self.entries.len()
+    }
+}
+
+/// Decompresses stored data the way [`Package::read_raw_resource`] does, but returns the size
+/// the data actually came to instead of trusting the index
+pub(crate) fn decompress(entry: &IndexEntry, stored: Vec<u8>) -> Result<(Vec<u8>, usize)> {
+    if !entry.is_compressed() {
+        let len = stored.len();
+        return Ok((stored, len));
+    }
+    if is_refpack(&stored) {
+        return refpack_inflate(&stored, entry.memsize as usize);
+    }
+    let mut decompressed = Vec::with_capacity(entry.memsize as usize);
+    flate2::read::ZlibDecoder::new(&stored[..]).read_to_end(&mut decompressed)
+        .map_err(|e| Error::Decompression(format!("Zlib: {}", e)))?;
+    let len = decompressed.len();
+    Ok((decompressed, len))
+}
+
+impl Package {
+    /// Checks the package the way the game will read it: the header, that every entry's data
+    /// is inside the file, that it decompresses to the size the index gives, and that resources
+    /// with a wrapper parse. Deleted entries are skipped.
+    ///
+    /// Problems are collected rather than returned, so one bad entry doesn't hide the rest.
+    pub fn validate(&self) -> Result<ValidationReport> {
+        let file_len = self.source_len()?;
+        let mut report = ValidationReport { header: self.header.validate(file_len), ..Default::default() };
+
+        let live: Vec<&IndexEntry> = self.entries.iter().filter(|e| e.scheme() != CompressionScheme::Deleted).collect();
+        let checked: Vec<(Vec<EntryIssue>, bool)> = live.par_iter()
+            .map(|entry| {
+                let mut issues = Vec::new();
+                let end = entry.offset as u64 + entry.filesize as u64;
+                if end > file_len {
+                    issues.push(EntryIssue::OutOfBounds { offset: entry.offset, filesize: entry.filesize, file_len });
+                    return (issues, false);
+                }
+                if entry.filesize > 0 && (entry.offset as u64) < PackageHeader::SIZE {
+                    issues.push(EntryIssue::InsideHeader { offset: entry.offset });
+                }
+                if let CompressionScheme::Unknown(code) = entry.scheme() {
+                    issues.push(EntryIssue::UnknownCompression(code));
+                    return (issues, false);
+                }
+                let decompressed = self.read_stored_resource(entry).and_then(|stored| decompress(entry, stored));
+                let data = match decompressed {
+                    Ok((data, actual)) => {
+                        if actual != entry.memsize as usize {
+                            issues.push(EntryIssue::SizeMismatch { memsize: entry.memsize, actual });
+                        }
+                        data
+                    }
+                    Err(e) => {
+                        issues.push(EntryIssue::DecompressionFailed(format!("{:#}", e)));
+                        return (issues, false);
+                    }
+                };
+                match catch_panic(|| TypedResource::from_bytes(entry.tgi.res_type, &data)) {
+                    Ok(TypedResource::Generic(_)) => (issues, false),
+                    Ok(_) => (issues, true),
+                    Err(e) => {
+                        issues.push(EntryIssue::ParseFailed(format!("{:#}", e)));
+                        (issues, false)
+                    }
+                }
+            })
+            .collect();
+
+        report.checked = live.len();
+        for (entry, (issues, parsed)) in live.into_iter().zip(checked) {
+            report.parsed += parsed as usize;
+            report.entries.extend(issues.into_iter().map(|issue| (entry.clone(), issue)));
+        }
+        Ok(report)
+    }
+}
+
+/// [`Package::validate`] for a file that might not open: when the index can't be read, the
+/// report has the header's problems and why the index failed. Errors only if the file can't be
+/// read or is too short to have a header.
+pub fn validate_file<P: AsRef<Path>>(path: P) -> Result<ValidationReport> {
+    match Package::open(path.as_ref()) {
+        Ok(pkg) => pkg.validate(),
+        Err(e) => {
+            let mut file = File::open(path.as_ref())?;
+            let file_len = file.seek(SeekFrom::End(0))?;
+            file.seek(SeekFrom::Start(0))?;
+            let header = PackageHeader::read(&mut file)
+                .map_err(|e| Error::InvalidHeader(e.to_string()))?;
+            Ok(ValidationReport { header: header.validate(file_len), index_error: Some(format!("{:#}", e)), ..Default::default() })
+        }
+    }
+}
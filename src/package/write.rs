@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::io::Write;
+use crate::error::{Error, Result};
+
+/// zlib's own default level, a good balance of speed and size
+pub const DEFAULT_LEVEL: u32 = 6;
+
+/// How resources of one type are stored, whatever [`WriteOptions::compress`] says
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionOverride {
+    /// Never compressed, e.g. for data that is already compressed and won't shrink
+    Store,
+    /// Compressed at this zlib level
+    Level(u32),
+}
+
+/// How [`crate::Package::write_merged`] and [`crate::Package::save_with`] compress resources:
+/// lower levels write faster, higher ones write smaller packages
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteOptions {
+    /// Compress every resource, rather than only those that were compressed already
+    pub compress: bool,
+    /// zlib level, from 0 (fastest) to 9 (smallest)
+    pub level: u32,
+    /// Fraction of its size compression has to save for a resource to be stored compressed;
+    /// at 0.0 any saving will do
+    pub min_saving: f64,
+    /// Compression by resource type, overriding `compress` and `level`
+    pub overrides: HashMap<u32, CompressionOverride>,
+    /// Threads to compress on; `None` uses rayon's global pool
+    pub threads: Option<usize>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self { compress: false, level: DEFAULT_LEVEL, min_saving: 0.0, overrides: HashMap::new(), threads: None }
+    }
+}
+
+/// `true` compresses everything, `false` only what was compressed already, both at the default level
+impl From<bool> for WriteOptions {
+    fn from(compress: bool) -> Self {
+        Self { compress, ..Self::default() }
+    }
+}
+
+impl WriteOptions {
+    /// Checks the levels and the saving threshold
+    pub fn validate(&self) -> Result<()> {
+        let levels = std::iter::once(self.level).chain(self.overrides.values().filter_map(|o| match o {
+            CompressionOverride::Level(level) => Some(*level),
+            CompressionOverride::Store => None,
+        }));
+        for level in levels {
+            if level > 9 {
+                return Err(Error::InvalidInput(format!("Compression level {} is out of range; expected 0 to 9", level)));
+            }
+        }
+        if !(0.0..1.0).contains(&self.min_saving) {
+            return Err(Error::InvalidInput(format!("Minimum saving {} is out of range; expected at least 0 and below 1", self.min_saving)));
+        }
+        Ok(())
+    }
+
+    /// The zlib level to store a resource of `res_type` with, or `None` to store it as it is.
+    /// `was_compressed` says whether its source had it compressed.
+    pub fn level_for(&self, res_type: u32, was_compressed: bool) -> Option<u32> {
+        match self.overrides.get(&res_type) {
+            Some(CompressionOverride::Store) => None,
+            Some(CompressionOverride::Level(level)) => Some(*level),
+            None => (self.compress || was_compressed).then_some(self.level),
+        }
+    }
+
+    /// Compresses `data` at `level`, returning it only if that saves at least `min_saving`
+    pub(crate) fn compress(&self, data: &[u8], level: u32) -> Result<Option<Vec<u8>>> {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(level.min(9)));
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let limit = data.len() as f64 * (1.0 - self.min_saving);
+        // Stored data flagged as zlib would fail to inflate on read, so it has to shrink
+        Ok((compressed.len() < data.len() && compressed.len() as f64 <= limit).then_some(compressed))
+    }
+
+    /// Runs `work` on the configured number of threads
+    pub(crate) fn install<T: Send>(&self, work: impl FnOnce() -> T + Send) -> Result<T> {
+        match self.threads {
+            None => Ok(work()),
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads.max(1))
+                    .build()
+                    .map_err(|e| Error::Other(format!("Failed to start compression threads: {}", e)))?;
+                Ok(pool.install(work))
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::package::resource::{CatalogCommon, TypedResource};
+
+/// Game packs by the ID catalog entries and CAS parts record, as numbered by the game's Pack
+/// enum: the product code (as in the install folder, e.g. `EP01`) and name
+pub const PACKS: &[(i16, &str, &str)] = &[
+    (0, "BASE", "Base Game"),
+    (1, "FP01", "Holiday Celebration Pack"),
+    (2, "EP01", "Get to Work"),
+    (3, "EP02", "Get Together"),
+    (4, "GP01", "Outdoor Retreat"),
+    (5, "GP02", "Spa Day"),
+    (6, "GP03", "Dine Out"),
+    (7, "SP01", "Luxury Party Stuff"),
+    (8, "SP02", "Perfect Patio Stuff"),
+    (9, "SP03", "Cool Kitchen Stuff"),
+    (10, "SP04", "Spooky Stuff"),
+    (11, "SP05", "Movie Hangout Stuff"),
+    (12, "SP06", "Romantic Garden Stuff"),
+    (13, "SP07", "Kids Room Stuff"),
+    (14, "SP08", "Backyard Stuff"),
+    (15, "GP04", "Vampires"),
+    (16, "SP09", "Vintage Glamour Stuff"),
+    (17, "SP10", "Bowling Night Stuff"),
+    (18, "EP03", "City Living"),
+    (19, "SP11", "Fitness Stuff"),
+    (20, "SP12", "Toddler Stuff"),
+    (21, "GP05", "Parenthood"),
+    (22, "SP13", "Laundry Day Stuff"),
+    (23, "EP04", "Cats & Dogs"),
+];
+
+/// The product code for a pack ID, e.g. `EP01`, or `pack 57` for IDs not in [`PACKS`]
+pub fn pack_code(id: i16) -> String {
+    PACKS.iter().find(|(pack, ..)| *pack == id)
+        .map(|(_, code, _)| code.to_string())
+        .unwrap_or_else(|| format!("pack {}", id))
+}
+
+/// The pack's code and name, e.g. `EP01 Get to Work`
+pub fn pack_label(id: i16) -> String {
+    match PACKS.iter().find(|(pack, ..)| *pack == id) {
+        Some((_, code, name)) => format!("{} {}", code, name),
+        None => format!("pack {}", id),
+    }
+}
+
+/// The catalog fields shared by Build/Buy catalog resources, for those that have them
+fn catalog_common(resource: &TypedResource) -> Option<&CatalogCommon> {
+    match resource {
+        TypedResource::Catalog(r) => Some(&r.common),
+        TypedResource::Cwal(r) => Some(&r.common),
+        TypedResource::Cfnd(r) => Some(&r.common),
+        TypedResource::Cstr(r) => Some(&r.common),
+        _ => None,
+    }
+}
+
+/// The items in a package that need a pack other than the base game, by pack ID
+pub fn pack_requirements(pkg: &Package) -> BTreeMap<i16, Vec<TGI>> {
+    let mut packs: BTreeMap<i16, Vec<TGI>> = BTreeMap::new();
+    for entry in pkg.entries.clone() {
+        let pack = match pkg.read_resource(&entry) {
+            Ok(TypedResource::CasPart(casp)) => casp.pack_id().ok().flatten(),
+            Ok(resource) => catalog_common(&resource).and_then(|common| common.pack_id),
+            Err(_) => None,
+        };
+        if let Some(pack) = pack.filter(|p| *p != 0) {
+            packs.entry(pack).or_default().push(entry.tgi);
+        }
+    }
+    packs
+}
+
+/// Codes of the packs installed in a game folder, going by its pack folders (`EP01`, `GP05`, ...)
+pub fn installed_packs<P: AsRef<Path>>(install_dir: P) -> BTreeSet<String> {
+    let Ok(entries) = std::fs::read_dir(install_dir) else { return BTreeSet::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(str::to_uppercase))
+        .filter(|name| {
+            name.len() == 4
+                && ["EP", "GP", "SP", "FP"].contains(&&name[..2])
+                && name[2..].chars().all(|c| c.is_ascii_digit())
+        })
+        .collect()
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use crate::error::{Error, Result};
+use crate::package::index::TGI;
+#[cfg(feature = "catalog")]
+use crate::package::Package;
+#[cfg(feature = "catalog")]
+use crate::package::resource::{ThumbnailKind, TypedResource};
+use crate::texture::RgbaImage;
+
+/// Names [`color_name`] can give, in the order reports list them
+pub const COLOR_NAMES: [&str; 11] = [
+    "red", "orange", "yellow", "green", "blue", "purple", "pink", "brown", "black", "grey", "white",
+];
+
+/// Marks the alpha channel of a JPEG thumbnail, at offset 24: an application segment right after
+/// the JFIF header holding "ALFA", the big-endian length of a PNG, and the PNG, whose gray level
+/// is the alpha of each pixel
+const ALPHA_MAGIC: &[u8; 4] = b"ALFA";
+
+/// The PNG holding the alpha channel of a JPEG thumbnail, if it has one
+pub fn thumbnail_alpha(data: &[u8]) -> Option<&[u8]> {
+    if !data.starts_with(&[0xFF, 0xD8]) || data.get(24..28)? != ALPHA_MAGIC {
+        return None;
+    }
+    let length = u32::from_be_bytes(data.get(28..32)?.try_into().ok()?) as usize;
+    data.get(32..32usize.checked_add(length)?)
+}
+
+/// Decodes a JPEG or PNG thumbnail to RGBA. A JPEG's alpha channel (see [`thumbnail_alpha`]) is
+/// applied; without one, every pixel is opaque.
+pub fn decode_thumbnail(data: &[u8]) -> Result<RgbaImage> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        use zune_jpeg::zune_core::{bytestream::ZCursor, colorspace::ColorSpace, options::DecoderOptions};
+        let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+        let mut decoder = zune_jpeg::JpegDecoder::new_with_options(ZCursor::new(data), options);
+        let mut pixels = decoder.decode().map_err(|e| Error::InvalidResource(format!("Invalid JPEG thumbnail: {:?}", e)))?;
+        let (width, height) = decoder.dimensions().unwrap_or_default();
+        if let Some(alpha) = thumbnail_alpha(data) {
+            let mask = decode_thumbnail(alpha)?;
+            if (mask.width, mask.height) != (width as u32, height as u32) {
+                return Err(Error::InvalidResource(format!(
+                    "Thumbnail alpha is {}x{} but the image is {}x{}", mask.width, mask.height, width, height,
+                )));
+            }
+            for (pixel, mask) in pixels.chunks_exact_mut(4).zip(mask.pixels.chunks_exact(4)) {
+                pixel[3] = mask[0];
+            }
+        }
+        Ok(RgbaImage { width: width as u32, height: height as u32, pixels })
+    } else if data.starts_with(b"\x89PNG") {
+        let invalid = |e: png::DecodingError| Error::InvalidResource(format!("Invalid PNG thumbnail: {}", e));
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16 | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader.next_frame(&mut buf).map_err(invalid)?;
+        buf.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            _ => buf,
+        };
+        Ok(RgbaImage { width: info.width, height: info.height, pixels })
+    } else {
+        Err(Error::Unsupported("Thumbnail is neither JPEG nor PNG".to_string()))
+    }
+}
+
+/// One of the main colors of an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DominantColor {
+    pub rgb: [u8; 3],
+    /// Fraction of the counted pixels that are this color
+    pub share: f32,
+}
+
+impl DominantColor {
+    pub fn name(&self) -> &'static str {
+        color_name(self.rgb)
+    }
+}
+
+/// Histogram bucket of a pixel: the top 4 bits of each channel
+fn bucket(pixel: &[u8]) -> u16 {
+    ((pixel[0] as u16 >> 4) << 8) | ((pixel[1] as u16 >> 4) << 4) | (pixel[2] as u16 >> 4)
+}
+
+/// The `count` most common colors of `image`, most common first.
+///
+/// Transparent pixels are skipped, and so is the backdrop: thumbnails show the item on a flat
+/// background, so a color that covers most of the border is left out.
+pub fn dominant_colors(image: &RgbaImage, count: usize) -> Vec<DominantColor> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width == 0 || height == 0 || image.pixels.len() < width * height * 4 {
+        return Vec::new();
+    }
+    let pixel = |x: usize, y: usize| &image.pixels[(y * width + x) * 4..(y * width + x) * 4 + 4];
+
+    let mut border: HashMap<u16, usize> = HashMap::new();
+    let edge = (0..width).flat_map(|x| [(x, 0), (x, height - 1)]).chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]));
+    let mut border_len = 0;
+    for (x, y) in edge {
+        *border.entry(bucket(pixel(x, y))).or_default() += 1;
+        border_len += 1;
+    }
+    let background = border.into_iter().max_by_key(|&(b, n)| (n, b)).filter(|&(_, n)| n * 2 > border_len).map(|(b, _)| b);
+
+    // Per bucket: pixel count and channel sums, so each color is reported as its bucket's average
+    let mut buckets: HashMap<u16, (u64, [u64; 3])> = HashMap::new();
+    let mut total = 0u64;
+    for p in image.pixels.chunks_exact(4).take(width * height) {
+        let b = bucket(p);
+        if p[3] < 128 || Some(b) == background {
+            continue;
+        }
+        let entry = buckets.entry(b).or_default();
+        entry.0 += 1;
+        for (sum, &channel) in entry.1.iter_mut().zip(p) {
+            *sum += channel as u64;
+        }
+        total += 1;
+    }
+
+    let mut sorted: Vec<(u16, (u64, [u64; 3]))> = buckets.into_iter().collect();
+    sorted.sort_by_key(|&(b, (n, _))| (std::cmp::Reverse(n), b));
+    sorted.into_iter().take(count).map(|(_, (n, sums))| DominantColor {
+        rgb: sums.map(|s| (s / n) as u8),
+        share: n as f32 / total as f32,
+    }).collect()
+}
+
+/// A plain color name for `rgb`, one of [`COLOR_NAMES`]
+pub fn color_name(rgb: [u8; 3]) -> &'static str {
+    let [r, g, b] = rgb.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let chroma = max - min;
+    if lightness < 0.12 {
+        return "black";
+    }
+    if chroma < 0.12 {
+        return if lightness > 0.85 { "white" } else if lightness < 0.2 { "black" } else { "grey" };
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    match hue {
+        h if !(15.0..340.0).contains(&h) => if lightness > 0.7 { "pink" } else { "red" },
+        h if h < 45.0 => if lightness < 0.4 || chroma < 0.3 { "brown" } else { "orange" },
+        h if h < 70.0 => if lightness < 0.3 { "brown" } else { "yellow" },
+        h if h < 170.0 => "green",
+        h if h < 260.0 => "blue",
+        h if h < 290.0 => "purple",
+        _ => "pink",
+    }
+}
+
+/// RGB of an ARGB swatch color
+pub fn swatch_rgb(argb: u32) -> [u8; 3] {
+    [(argb >> 16) as u8, (argb >> 8) as u8, argb as u8]
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> f32 {
+    a.iter().zip(b).map(|(&x, y)| (x as f32 - y as f32).powi(2)).sum::<f32>().sqrt()
+}
+
+/// The swatch colors of one CAS part next to the main colors of its thumbnail
+#[derive(Debug, Clone)]
+pub struct SwatchPalette {
+    /// The CASP
+    pub tgi: TGI,
+    pub name: String,
+    pub sort_priority: f32,
+    pub secondary_sort: u16,
+    /// ARGB, as CAS shows them on the swatch button
+    pub swatch_colors: Vec<u32>,
+    /// The CAS thumbnail sharing the part's instance, if the package has one
+    pub thumbnail: Option<TGI>,
+    /// Main colors of the thumbnail; empty without one
+    pub dominant: Vec<DominantColor>,
+}
+
+impl SwatchPalette {
+    /// Whether the first swatch color shows up among the thumbnail's main colors, by name or
+    /// by being close to one. A part whose swatch doesn't match its thumbnail usually has its
+    /// swatches out of order or a thumbnail left over from another swatch. `None` when there's
+    /// nothing to compare.
+    pub fn matches_thumbnail(&self) -> Option<bool> {
+        let swatch = swatch_rgb(*self.swatch_colors.first()?);
+        if self.dominant.is_empty() {
+            return None;
+        }
+        let name = color_name(swatch);
+        Some(self.dominant.iter().any(|c| c.name() == name || distance(c.rgb, swatch) < 60.0))
+    }
+
+    /// Names of the swatch and thumbnail colors, for searching by color
+    pub fn color_names(&self) -> Vec<&'static str> {
+        let names: Vec<&'static str> = self.swatch_colors.iter().map(|&c| color_name(swatch_rgb(c)))
+            .chain(self.dominant.iter().filter(|c| c.share >= 0.1).map(DominantColor::name))
+            .collect();
+        COLOR_NAMES.into_iter().filter(|n| names.contains(n)).collect()
+    }
+}
+
+/// How many thumbnail colors [`package_palette`] reports per part
+pub const DOMINANT_COLORS: usize = 4;
+
+/// The swatch palette of every CAS part in `pkg`, by sort priority and then secondary sort
+/// index, the fields that set swatch order. Parts that fail to parse are skipped; a thumbnail
+/// that fails to decode leaves that part's thumbnail colors empty.
+#[cfg(feature = "catalog")]
+pub fn package_palette(pkg: &Package) -> Vec<SwatchPalette> {
+    let thumbnails: HashMap<u64, TGI> = pkg.entries.iter()
+        .filter(|e| ThumbnailKind::of(e.tgi.res_type) == Some(ThumbnailKind::CasPart))
+        .map(|e| (e.tgi.instance, e.tgi))
+        .collect();
+
+    let mut palettes = Vec::new();
+    for entry in &pkg.entries {
+        let Ok(TypedResource::CasPart(casp)) = pkg.read_resource(entry) else { continue };
+        let (Ok(name), Ok((sort_priority, secondary_sort)), Ok(swatch_colors)) = (casp.name(), casp.sort_order(), casp.swatch_colors()) else {
+            continue;
+        };
+        let thumbnail = thumbnails.get(&entry.tgi.instance).copied();
+        let dominant = thumbnail
+            .and_then(|tgi| pkg.find(&tgi))
+            .and_then(|e| pkg.read_raw_resource(e).ok())
+            .and_then(|data| decode_thumbnail(&data).ok())
+            .map(|image| dominant_colors(&image, DOMINANT_COLORS))
+            .unwrap_or_default();
+        palettes.push(SwatchPalette { tgi: entry.tgi, name, sort_priority, secondary_sort, swatch_colors, thumbnail, dominant });
+    }
+    palettes.sort_by(|a, b| {
+        a.sort_priority.total_cmp(&b.sort_priority)
+            .then(a.secondary_sort.cmp(&b.secondary_sort))
+            .then(a.name.cmp(&b.name))
+    });
+    palettes
+}
@@ -0,0 +1,232 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use log::info;
+
+// Process-wide counters; nothing here leaves the machine. Operations running at the same time
+// (e.g. two GUI jobs) share them, so their summaries overlap.
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+static BYTES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+static STORED: AtomicU64 = AtomicU64::new(0);
+static UNCOMPRESSED: AtomicU64 = AtomicU64::new(0);
+static BUSY_NANOS: AtomicU64 = AtomicU64::new(0);
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// The system allocator, keeping count of the bytes allocated so summaries can report peak
+/// memory. Install it in a binary with `#[global_allocator]`; without it, peak memory is unknown.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            allocated(new_size);
+        }
+        new_ptr
+    }
+}
+
+fn allocated(size: usize) {
+    let now = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_ALLOCATED.fetch_max(now, Ordering::Relaxed);
+    if !TRACKING.load(Ordering::Relaxed) {
+        TRACKING.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Counts bytes read from package files
+pub fn record_read(bytes: u64) {
+    BYTES_READ.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Counts bytes written to output files
+pub fn record_written(bytes: u64) {
+    BYTES_WRITTEN.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Counts a resource written to a package: its stored size and its size uncompressed
+pub fn record_compressed(stored: u64, uncompressed: u64) {
+    STORED.fetch_add(stored, Ordering::Relaxed);
+    UNCOMPRESSED.fetch_add(uncompressed, Ordering::Relaxed);
+}
+
+/// [`crate::atomic::write`], counting the bytes written
+pub fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> std::io::Result<()> {
+    crate::atomic::write(path, &contents)?;
+    record_written(contents.as_ref().len() as u64);
+    Ok(())
+}
+
+thread_local! {
+    static BUSY_DEPTH: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Runs one unit of parallel work, counting the time it takes towards thread utilization.
+/// Work nested inside other work on the same thread is only counted once.
+pub fn busy<T>(work: impl FnOnce() -> T) -> T {
+    let depth = BUSY_DEPTH.with(|d| d.replace(d.get() + 1));
+    let started = Instant::now();
+    let result = work();
+    if depth == 0 {
+        BUSY_NANOS.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+    BUSY_DEPTH.with(|d| d.set(depth));
+    result
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    read: u64,
+    written: u64,
+    stored: u64,
+    uncompressed: u64,
+    busy: u64,
+}
+
+impl Counters {
+    fn now() -> Self {
+        Self {
+            read: BYTES_READ.load(Ordering::Relaxed),
+            written: BYTES_WRITTEN.load(Ordering::Relaxed),
+            stored: STORED.load(Ordering::Relaxed),
+            uncompressed: UNCOMPRESSED.load(Ordering::Relaxed),
+            busy: BUSY_NANOS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Measures one operation, from [`PerfTimer::start`] to [`PerfTimer::finish`]
+pub struct PerfTimer {
+    operation: String,
+    started: Instant,
+    start: Counters,
+}
+
+impl PerfTimer {
+    pub fn start(operation: &str) -> Self {
+        PEAK_ALLOCATED.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+        Self { operation: operation.to_string(), started: Instant::now(), start: Counters::now() }
+    }
+
+    pub fn finish(self) -> PerfSummary {
+        let end = Counters::now();
+        PerfSummary {
+            operation: self.operation,
+            wall: self.started.elapsed(),
+            bytes_read: end.read - self.start.read,
+            bytes_written: end.written - self.start.written,
+            stored: end.stored - self.start.stored,
+            uncompressed: end.uncompressed - self.start.uncompressed,
+            threads: rayon::current_num_threads(),
+            busy: Duration::from_nanos(end.busy - self.start.busy),
+            peak_memory: TRACKING.load(Ordering::Relaxed).then(|| PEAK_ALLOCATED.load(Ordering::Relaxed) as u64),
+        }
+    }
+}
+
+/// How long an operation took and what it moved, for tuning options like thread count
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerfSummary {
+    pub operation: String,
+    pub wall: Duration,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Stored and uncompressed sizes of the resources written to packages
+    pub stored: u64,
+    pub uncompressed: u64,
+    /// Size of the thread pool parallel work ran on
+    pub threads: usize,
+    /// Time spent in parallel work, summed over threads
+    pub busy: Duration,
+    /// Most memory allocated at once, if the binary uses [`TrackingAllocator`]
+    pub peak_memory: Option<u64>,
+}
+
+impl PerfSummary {
+    /// Stored size as a fraction of the uncompressed size, if any resources were written
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.uncompressed > 0).then(|| self.stored as f64 / self.uncompressed as f64)
+    }
+
+    /// Fraction of the thread pool's time spent in parallel work
+    pub fn utilization(&self) -> Option<f64> {
+        let available = self.wall.as_secs_f64() * self.threads as f64;
+        (available > 0.0 && !self.busy.is_zero()).then(|| (self.busy.as_secs_f64() / available).min(1.0))
+    }
+
+    /// Logs the summary, with each figure as a field in the JSON run log. Percentages are
+    /// whole numbers, and figures that weren't measured are 0.
+    pub fn log(&self) {
+        let percent = |fraction: Option<f64>| fraction.map_or(0, |f| (f * 100.0).round() as u64);
+        info!(
+            operation = self.operation.as_str(),
+            wall_ms = self.wall.as_millis() as u64,
+            bytes_read = self.bytes_read,
+            bytes_written = self.bytes_written,
+            compression_percent = percent(self.compression_ratio()),
+            threads = self.threads as u64,
+            utilization_percent = percent(self.utilization()),
+            peak_memory = self.peak_memory.unwrap_or(0);
+            "{}", self
+        );
+    }
+}
+
+/// A byte count in the largest unit that keeps it at least 1, e.g. `12.3 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+impl std::fmt::Display for PerfSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} took {:.2}s | read {} | wrote {}", self.operation, self.wall.as_secs_f64(),
+            format_bytes(self.bytes_read), format_bytes(self.bytes_written))?;
+        if let Some(ratio) = self.compression_ratio() {
+            write!(f, " | compressed to {:.0}%", ratio * 100.0)?;
+        }
+        match self.utilization() {
+            Some(utilization) => write!(f, " | {} thread(s), {:.0}% busy", self.threads, utilization * 100.0)?,
+            None => write!(f, " | {} thread(s)", self.threads)?,
+        }
+        if let Some(peak) = self.peak_memory {
+            write!(f, " | peak memory {}", format_bytes(peak))?;
+        }
+        Ok(())
+    }
+}
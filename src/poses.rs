@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use crate::error::Result;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::package::resource::{ResourceKind, TypedResource};
+use crate::tuning::{looks_like_xml, tuning_name};
+
+/// One animation clip in a pose pack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub tgi: TGI,
+    /// The clip's name from its header, or from the package's name map for older clips
+    pub name: Option<String>,
+    /// Length in seconds, if the clip header could be read
+    pub duration: Option<f32>,
+    /// Tuning files that mention the clip by name, usually the pose pack's snippet
+    pub tuning: Vec<String>,
+    /// Why the clip header couldn't be read
+    pub error: Option<String>,
+}
+
+/// Lists every animation clip in a package with its name, duration and the tuning that exposes it.
+///
+/// A clip counts as exposed by a tuning file when its name appears there as the whole value of an
+/// element (e.g. `<T n="pose_name">a_pose_clip</T>`), which is how pose players reference them.
+pub fn list_poses(pkg: &Package) -> Result<Vec<Pose>> {
+    let mut names: HashMap<u64, String> = HashMap::new();
+    let mut tuning: Vec<(String, String)> = Vec::new();
+    for entry in pkg.entries.clone() {
+        match TypedResource::kind_of(entry.tgi.res_type) {
+            ResourceKind::NameMap => {
+                if let Ok(TypedResource::NameMap(map)) = pkg.read_resource(&entry) {
+                    names.extend(map.entries.into_iter().map(|e| (e.instance, e.name)));
+                }
+            }
+            ResourceKind::Xml | ResourceKind::Text => {
+                let data = pkg.read_raw_resource(&entry)?;
+                if looks_like_xml(&data) {
+                    let xml = String::from_utf8_lossy(&data).into_owned();
+                    let label = tuning_name(&xml).unwrap_or_else(|| entry.tgi.to_string());
+                    tuning.push((label, xml));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let clips: Vec<_> = pkg.entries.iter()
+        .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::Clip)
+        .cloned()
+        .collect();
+    let mut poses = Vec::with_capacity(clips.len());
+    for entry in clips {
+        let header = match pkg.read_resource(&entry) {
+            Ok(TypedResource::Clip(clip)) => clip.header(),
+            Ok(_) => unreachable!("clip types always parse as clips"),
+            Err(e) => Err(e),
+        };
+        let (name, duration, error) = match header {
+            Ok(header) => (header.clip_name.filter(|n| !n.is_empty()), Some(header.duration), None),
+            Err(e) => (None, None, Some(format!("{:#}", e))),
+        };
+        let name = name.or_else(|| names.get(&entry.tgi.instance).cloned());
+        let exposed_by = match &name {
+            Some(name) => {
+                let needle = format!(">{}<", name);
+                tuning.iter().filter(|(_, xml)| xml.contains(&needle)).map(|(label, _)| label.clone()).collect()
+            }
+            None => Vec::new(),
+        };
+        poses.push(Pose { tgi: entry.tgi, name, duration, tuning: exposed_by, error });
+    }
+    Ok(poses)
+}
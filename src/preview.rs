@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::error::{Error, Result};
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{ResourceKind, ThumbnailKind, TypedResource};
+use crate::stbl::{is_stbl_type, locale_of, Locale};
+
+/// Object definition (OBJD)
+pub const OBJD_TYPE: u32 = 0xC0DB5AE7;
+/// Object catalog entry (COBJ); shares its instance with the object's OBJD
+pub const COBJ_TYPE: u32 = 0x319E4F1D;
+
+/// A model an object refers to, and what its RCOL chain holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelSummary {
+    pub tgi: TGI,
+    /// False when the model isn't in the package (e.g. it comes from the game)
+    pub present: bool,
+    /// Mesh levels of detail (MLOD), whether stored as chunks or separate resources
+    pub lods: usize,
+    /// Number of chunks of each kind across the model and the RCOLs it references, e.g. VBUF
+    pub chunks: BTreeMap<String, usize>,
+    /// Decompressed size of the model and the RCOLs it references
+    pub bytes: u64,
+}
+
+/// Everything needed to show one Build/Buy object at a glance
+#[derive(Debug, Clone, Default)]
+pub struct ObjectPreview {
+    pub objd: Option<TGI>,
+    pub catalog: Option<TGI>,
+    /// The OBJD's internal name
+    pub name: Option<String>,
+    /// The catalog name, from a string table in the package (English if there is one)
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// Catalog price, or the OBJD's when there is no catalog entry
+    pub price: Option<u32>,
+    /// Catalog tags, which place the object in Build/Buy categories
+    pub tags: Vec<u16>,
+    /// The object's catalog thumbnail and its image data (JPEG or PNG)
+    pub thumbnail: Option<(TGI, Vec<u8>)>,
+    pub models: Vec<ModelSummary>,
+    /// Footprints the OBJD refers to, and whether each is in the package
+    pub footprints: Vec<(TGI, bool)>,
+}
+
+/// Model LOD (MLOD) RCOL type
+const MLOD_TYPE: u32 = 0x01D10F34;
+
+/// Finds the string with `key` in the package's string tables, preferring English
+fn lookup_string(tables: &[(Option<Locale>, HashMap<u32, String>)], key: u32) -> Option<String> {
+    if key == 0 {
+        return None;
+    }
+    tables.iter()
+        .filter(|(locale, _)| *locale == Some(Locale::English))
+        .chain(tables.iter())
+        .find_map(|(_, strings)| strings.get(&key).cloned())
+}
+
+fn summarize_model(pkg: &Package, entries: &HashMap<TGI, IndexEntry>, tgi: TGI) -> ModelSummary {
+    let mut summary = ModelSummary { tgi, present: entries.contains_key(&tgi), lods: 0, chunks: BTreeMap::new(), bytes: 0 };
+    // The model, then the LODs and other RCOLs it references (one level down is enough)
+    let mut queue = vec![(tgi, 0)];
+    let mut visited = Vec::new();
+    while let Some((tgi, depth)) = queue.pop() {
+        if visited.contains(&tgi) {
+            continue;
+        }
+        visited.push(tgi);
+        if tgi.res_type == MLOD_TYPE {
+            summary.lods += 1;
+        }
+        let Some(entry) = entries.get(&tgi) else { continue };
+        summary.bytes += entry.memsize as u64;
+        let Ok(TypedResource::Rcol(rcol)) = pkg.read_resource(entry) else { continue };
+        for chunk in &rcol.chunks {
+            *summary.chunks.entry(chunk.tag.clone()).or_insert(0) += 1;
+            if chunk.tgi.res_type == MLOD_TYPE && chunk.tgi != tgi {
+                summary.lods += 1;
+            }
+        }
+        if depth == 0 {
+            queue.extend(rcol.external_resources.iter().filter(|t| entries.contains_key(t)).map(|t| (*t, 1)));
+        }
+    }
+    summary
+}
+
+/// Builds the preview of the object whose OBJD or COBJ is `entry`, looking up the other half
+/// by instance, plus its thumbnail, models and footprints
+pub fn object_preview(pkg: &Package, entry: &IndexEntry) -> Result<ObjectPreview> {
+    if entry.tgi.res_type != OBJD_TYPE && entry.tgi.res_type != COBJ_TYPE {
+        return Err(Error::InvalidInput(format!("{} is not an object definition or catalog entry", entry.tgi)));
+    }
+    let instance = entry.tgi.instance;
+    let entries: HashMap<TGI, IndexEntry> = pkg.entries.iter().map(|e| (e.tgi, e.clone())).collect();
+    let find = |res_type: u32| pkg.entries.iter().find(|e| e.tgi.res_type == res_type && e.tgi.instance == instance).cloned();
+    let objd_entry = find(OBJD_TYPE);
+    let cobj_entry = find(COBJ_TYPE);
+    let thumbnail_entry = pkg.entries.iter()
+        .find(|e| e.tgi.instance == instance && ThumbnailKind::of(e.tgi.res_type) == Some(ThumbnailKind::Object))
+        .cloned();
+
+    let mut preview = ObjectPreview {
+        objd: objd_entry.as_ref().map(|e| e.tgi),
+        catalog: cobj_entry.as_ref().map(|e| e.tgi),
+        ..Default::default()
+    };
+
+    let mut model_tgis = Vec::new();
+    if let Some(objd_entry) = objd_entry {
+        if let TypedResource::ObjectDefinition(objd) = pkg.read_resource(&objd_entry)? {
+            preview.name = objd.name().map(str::to_string);
+            preview.price = objd.simoleon_price();
+            model_tgis = objd.models().unwrap_or_default().to_vec();
+            preview.footprints = objd.footprints().unwrap_or_default().iter()
+                .map(|tgi| (*tgi, entries.contains_key(tgi)))
+                .collect();
+        }
+    }
+
+    if let Some(cobj_entry) = cobj_entry {
+        if let TypedResource::Catalog(cobj) = pkg.read_resource(&cobj_entry)? {
+            let common = &cobj.common;
+            let tables: Vec<(Option<Locale>, HashMap<u32, String>)> = pkg.entries.clone().iter()
+                .filter(|e| is_stbl_type(e.tgi.res_type))
+                .filter_map(|e| match pkg.read_resource(e) {
+                    Ok(TypedResource::Stbl(stbl)) => Some((
+                        locale_of(e.tgi.instance),
+                        stbl.entries.into_iter().map(|s| (s.key_hash, s.string_value)).collect(),
+                    )),
+                    _ => None,
+                })
+                .collect();
+            preview.display_name = lookup_string(&tables, common.name_hash);
+            preview.description = lookup_string(&tables, common.description_hash);
+            preview.price = Some(common.price);
+            preview.tags = common.tags.as_ref().map(|t| t.tags.clone())
+                .or_else(|| common.legacy_tags.as_ref().map(|t| t.tags.clone()))
+                .unwrap_or_default();
+        }
+    }
+
+    if let Some(thumbnail_entry) = thumbnail_entry {
+        let data = pkg.read_raw_resource(&thumbnail_entry)?;
+        preview.thumbnail = Some((thumbnail_entry.tgi, data));
+    }
+
+    preview.models = model_tgis.into_iter()
+        .filter(|tgi| TypedResource::kind_of(tgi.res_type) == ResourceKind::Rcol)
+        .map(|tgi| summarize_model(pkg, &entries, tgi))
+        .collect();
+    Ok(preview)
+}
+
+/// Previews every object in a package: one per OBJD, plus catalog entries without an OBJD
+pub fn object_previews(pkg: &Package) -> Result<Vec<ObjectPreview>> {
+    let mut seen = Vec::new();
+    let mut previews = Vec::new();
+    let objects: Vec<IndexEntry> = pkg.entries.iter()
+        .filter(|e| e.tgi.res_type == OBJD_TYPE)
+        .chain(pkg.entries.iter().filter(|e| e.tgi.res_type == COBJ_TYPE))
+        .cloned()
+        .collect();
+    for entry in objects {
+        if seen.contains(&entry.tgi.instance) {
+            continue;
+        }
+        seen.push(entry.tgi.instance);
+        previews.push(object_preview(pkg, &entry)?);
+    }
+    Ok(previews)
+}
+
+impl std::fmt::Display for ObjectPreview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.display_name.as_deref().or(self.name.as_deref()).unwrap_or("(unnamed)");
+        writeln!(f, "Object:      {}", name)?;
+        if let (Some(internal), Some(_)) = (&self.name, &self.display_name) {
+            writeln!(f, "Internal:    {}", internal)?;
+        }
+        if let Some(description) = &self.description {
+            writeln!(f, "Description: {}", description)?;
+        }
+        if let Some(price) = self.price {
+            writeln!(f, "Price:       §{}", price)?;
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|t| t.to_string()).collect();
+            writeln!(f, "Tags:        {}", tags.join(", "))?;
+        }
+        match &self.thumbnail {
+            Some((tgi, data)) => writeln!(f, "Thumbnail:   {} ({} bytes, {})", tgi, data.len(), crate::thumbcache::thumbnail_extension(data))?,
+            None => writeln!(f, "Thumbnail:   none in package")?,
+        }
+        for model in &self.models {
+            if !model.present {
+                writeln!(f, "Model:       {} (not in package)", model.tgi)?;
+                continue;
+            }
+            let chunks: Vec<String> = model.chunks.iter().map(|(tag, n)| format!("{} x{}", tag, n)).collect();
+            writeln!(f, "Model:       {} | LODs: {} | {} bytes | {}", model.tgi, model.lods, model.bytes, chunks.join(", "))?;
+        }
+        for (tgi, present) in &self.footprints {
+            writeln!(f, "Footprint:   {}{}", tgi, if *present { "" } else { " (not in package)" })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::{Context, Error, Result};
+use sha2::{Digest, Sha256};
+use crate::package::Package;
+use crate::package::index::TGI;
+
+/// Resource type of the provenance record merges write. Like the merge manifest (0x7FB6AD8A)
+/// this is not a game type; the game ignores it.
+pub const PROVENANCE_TYPE: u32 = 0x7FB6AD8C;
+
+/// One input package of a merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Path relative to the merged folder
+    pub name: String,
+    pub size: u64,
+    /// SHA-256 of the file, lowercase hex
+    pub sha256: String,
+}
+
+impl SourceFile {
+    /// Hashes `path`, recording it under `name`
+    pub fn hash(path: &Path, name: &str) -> Result<Self> {
+        let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1 << 16];
+        let mut size = 0u64;
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+        let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(Self { name: name.to_string(), size, sha256 })
+    }
+}
+
+/// How and when a merged package was produced, stored in the package as JSON
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub tool_version: String,
+    /// Unix time in seconds
+    pub created: u64,
+    /// The merge options in effect, e.g. `compress = true`
+    pub options: BTreeMap<String, String>,
+    pub sources: Vec<SourceFile>,
+}
+
+impl Provenance {
+    /// A record for a merge happening now with this build of the tool
+    pub fn new(options: BTreeMap<String, String>, sources: Vec<SourceFile>) -> Self {
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Self { tool_version: env!("CARGO_PKG_VERSION").to_string(), created, options, sources }
+    }
+
+    /// The key the record is stored under; there is only ever one per package
+    pub fn tgi() -> TGI {
+        TGI { res_type: PROVENANCE_TYPE, res_group: 0, instance: 0 }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let sources: Vec<serde_json::Value> = self.sources.iter()
+            .map(|s| serde_json::json!({ "name": s.name, "size": s.size, "sha256": s.sha256 }))
+            .collect();
+        let value = serde_json::json!({
+            "tool": "s4pi-reforged",
+            "tool_version": self.tool_version,
+            "created": self.created,
+            "options": self.options,
+            "sources": sources,
+        });
+        serde_json::to_vec_pretty(&value).unwrap_or_default()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(data).context("Provenance record is not valid JSON")?;
+        let text = |v: &serde_json::Value| v.as_str().map(str::to_string);
+        let tool_version = text(&value["tool_version"]).ok_or_else(|| Error::Other("Provenance record has no tool version".to_string()))?;
+        let options = value["options"].as_object()
+            .map(|o| o.iter().map(|(k, v)| (k.clone(), text(v).unwrap_or_else(|| v.to_string()))).collect())
+            .unwrap_or_default();
+        let sources = value["sources"].as_array().map(|a| a.as_slice()).unwrap_or_default().iter()
+            .map(|s| SourceFile {
+                name: text(&s["name"]).unwrap_or_default(),
+                size: s["size"].as_u64().unwrap_or(0),
+                sha256: text(&s["sha256"]).unwrap_or_default(),
+            })
+            .collect();
+        Ok(Self { tool_version, created: value["created"].as_u64().unwrap_or(0), options, sources })
+    }
+
+    /// Reads the provenance record of a package, if it has one
+    pub fn read(pkg: &Package) -> Result<Option<Self>> {
+        let Some(entry) = pkg.find(&Self::tgi()).cloned() else { return Ok(None) };
+        Self::from_bytes(&pkg.read_raw_resource(&entry)?).map(Some)
+    }
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Merged by s4pi-reforged {} at {} (Unix time)", self.tool_version, self.created)?;
+        if !self.options.is_empty() {
+            let options: Vec<String> = self.options.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            writeln!(f, "Options: {}", options.join(", "))?;
+        }
+        writeln!(f, "Sources ({}):", self.sources.len())?;
+        for source in &self.sources {
+            writeln!(f, "  {}  {:>10} bytes  sha256 {}", source.name, source.size, source.sha256)?;
+        }
+        Ok(())
+    }
+}
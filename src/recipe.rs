@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::error::{Context, Error, Result};
+
+/// One command in a [`Recipe`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Shown while the step runs; defaults to the command line
+    pub name: Option<String>,
+    pub command: String,
+    pub args: Vec<String>,
+    /// A profile from the recipe whose arguments are added after `args`
+    pub profile: Option<String>,
+    /// Carry on with the next step if this one fails
+    pub keep_going: bool,
+}
+
+/// A list of commands to run in order, read from a TOML file. `[vars]` holds values the
+/// arguments can use as `{name}`, `[profiles]` holds named argument lists (e.g. a set of merge
+/// options) and each `[[step]]` is one command:
+///
+/// ```toml
+/// [profiles]
+/// strict = ["--on-conflict", "error"]
+///
+/// [[step]]
+/// command = "merge"
+/// args = ["{mods}"]
+/// profile = "strict"
+/// ```
+///
+/// `{recipe_dir}` is the folder the recipe is in, unless `[vars]` says otherwise. Other defaults,
+/// such as `{mods}` for the Mods folder, are up to the caller to add to `vars`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recipe {
+    pub vars: BTreeMap<String, String>,
+    pub profiles: BTreeMap<String, Vec<String>>,
+    pub steps: Vec<Step>,
+}
+
+fn strings(value: &toml::Value, what: &str) -> Result<Vec<String>> {
+    let array = value.as_array().ok_or_else(|| Error::InvalidInput(format!("{} must be a list of strings", what)))?;
+    array.iter()
+        .map(|v| v.as_str().map(str::to_string).ok_or_else(|| Error::InvalidInput(format!("{} must be a list of strings", what))))
+        .collect()
+}
+
+fn string(value: &toml::Value, what: &str) -> Result<String> {
+    value.as_str().map(str::to_string).ok_or_else(|| Error::InvalidInput(format!("{} must be a string", what)))
+}
+
+impl Recipe {
+    /// Parses a recipe. `dir` is the folder it came from, for `{recipe_dir}`.
+    pub fn from_toml(text: &str, dir: &Path) -> Result<Self> {
+        let table: toml::Table = text.parse().context("Recipe is not valid TOML")?;
+        let mut recipe = Recipe::default();
+        recipe.vars.insert("recipe_dir".to_string(), dir.to_string_lossy().into_owned());
+
+        for (key, value) in &table {
+            match key.as_str() {
+                "vars" => {
+                    let vars = value.as_table().ok_or_else(|| Error::InvalidInput("[vars] must be a table".to_string()))?;
+                    for (name, value) in vars {
+                        recipe.vars.insert(name.clone(), string(value, &format!("vars.{}", name))?);
+                    }
+                }
+                "profiles" => {
+                    let profiles = value.as_table().ok_or_else(|| Error::InvalidInput("[profiles] must be a table".to_string()))?;
+                    for (name, value) in profiles {
+                        recipe.profiles.insert(name.clone(), strings(value, &format!("profiles.{}", name))?);
+                    }
+                }
+                "step" => {
+                    let steps = value.as_array().ok_or_else(|| Error::InvalidInput("Steps must be written as [[step]] tables".to_string()))?;
+                    for (i, step) in steps.iter().enumerate() {
+                        recipe.steps.push(Self::parse_step(step, i + 1)?);
+                    }
+                }
+                other => return Err(Error::InvalidInput(format!("Unknown recipe section {:?}; expected vars, profiles or step", other))),
+            }
+        }
+
+        for (i, step) in recipe.steps.iter().enumerate() {
+            if let Some(profile) = &step.profile {
+                if !recipe.profiles.contains_key(profile) {
+                    return Err(Error::InvalidInput(format!("Step {} uses profile {:?}, which isn't under [profiles]", i + 1, profile)));
+                }
+            }
+        }
+        Ok(recipe)
+    }
+
+    fn parse_step(value: &toml::Value, number: usize) -> Result<Step> {
+        let table = value.as_table().ok_or_else(|| Error::InvalidInput(format!("Step {} must be a table", number)))?;
+        let mut step = Step { name: None, command: String::new(), args: Vec::new(), profile: None, keep_going: false };
+        for (key, value) in table {
+            let what = format!("Step {} {}", number, key);
+            match key.as_str() {
+                "name" => step.name = Some(string(value, &what)?),
+                "command" => step.command = string(value, &what)?,
+                "args" => step.args = strings(value, &what)?,
+                "profile" => step.profile = Some(string(value, &what)?),
+                "keep_going" => step.keep_going = value.as_bool().ok_or_else(|| Error::InvalidInput(format!("{} must be true or false", what)))?,
+                other => return Err(Error::InvalidInput(format!("Step {} has unknown key {:?}; expected name, command, args, profile or keep_going", number, other))),
+            }
+        }
+        if step.command.is_empty() {
+            return Err(Error::InvalidInput(format!("Step {} has no command", number)));
+        }
+        Ok(step)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        Self::from_toml(&text, &dir).with_context(|| format!("Failed to read recipe {:?}", path))
+    }
+
+    /// The arguments `step` runs with: its own, then its profile's, with `{name}` replaced by
+    /// the value of each var
+    pub fn args(&self, step: &Step) -> Result<Vec<String>> {
+        let profile = step.profile.as_ref().and_then(|p| self.profiles.get(p)).map_or(&[][..], Vec::as_slice);
+        step.args.iter().chain(profile).map(|arg| self.expand(arg)).collect()
+    }
+
+    fn expand(&self, arg: &str) -> Result<String> {
+        let mut out = String::new();
+        let mut rest = arg;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or_else(|| Error::InvalidInput(format!("Unclosed {{ in {:?}", arg)))? + start;
+            let name = &rest[start + 1..end];
+            let value = self.vars.get(name).ok_or_else(|| match name {
+                "mods" => Error::InvalidInput("No Mods folder found for {{mods}}; set mods under [vars]".to_string()),
+                _ => Error::InvalidInput(format!("{{{}}} is not set under [vars]", name)),
+            })?;
+            out.push_str(&rest[..start]);
+            out.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
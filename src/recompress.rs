@@ -0,0 +1,89 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::package::{decompress_refpack, is_refpack, Package};
+use crate::package::index::{CompressionScheme, IndexEntry, TGI};
+
+/// What [`refpack_to_zlib`] did to one package
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecompressReport {
+    /// RefPack resources that were re-stored as zlib
+    pub converted: usize,
+    /// Stored size of the converted resources before and after
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// RefPack resources that couldn't be converted and were kept as they were
+    pub failed: Vec<(TGI, String)>,
+}
+
+impl RecompressReport {
+    /// Bytes saved by the conversion (negative if zlib came out bigger)
+    pub fn saved(&self) -> i64 {
+        self.bytes_before as i64 - self.bytes_after as i64
+    }
+}
+
+const ZLIB: u16 = 0x5A42;
+
+fn is_refpack_entry(entry: &IndexEntry, stored: &[u8]) -> bool {
+    entry.scheme() == CompressionScheme::RefPack || (entry.is_compressed() && is_refpack(stored))
+}
+
+/// Re-encodes one RefPack resource as zlib, checking the result inflates back to the same bytes
+fn convert(entry: &IndexEntry, stored: &[u8]) -> Result<Vec<u8>> {
+    let data = decompress_refpack(stored, entry.memsize as usize)?;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data)?;
+    let compressed = encoder.finish()?;
+
+    let mut check = Vec::with_capacity(data.len());
+    ZlibDecoder::new(&compressed[..]).read_to_end(&mut check)?;
+    if check != data {
+        return Err(Error::Other("zlib round trip did not match the original data".to_string()));
+    }
+    Ok(compressed)
+}
+
+/// Converts every RefPack-compressed resource in the package at `path` to zlib, which the current
+/// game prefers. Everything else is copied over byte for byte, with its index entry unchanged.
+///
+/// Resources that fail to convert are kept as RefPack and listed in the report. The package is
+/// only rewritten when something was converted, and like [`Package::replace_resources`] the new
+/// file is written next to the original and renamed over it.
+pub fn refpack_to_zlib<P: AsRef<Path>>(path: P) -> Result<RecompressReport> {
+    let path = path.as_ref();
+    let pkg = Package::open(path)?;
+    let mut report = RecompressReport::default();
+    let mut resources = Vec::with_capacity(pkg.entries.len());
+
+    for entry in pkg.entries.clone() {
+        let stored = pkg.read_stored_resource(&entry)
+            .with_context(|| format!("Failed to read {}", entry.tgi))?;
+        if !is_refpack_entry(&entry, &stored) {
+            resources.push((entry, stored));
+            continue;
+        }
+        match convert(&entry, &stored) {
+            Ok(compressed) => {
+                report.converted += 1;
+                report.bytes_before += stored.len() as u64;
+                report.bytes_after += compressed.len() as u64;
+                resources.push((IndexEntry { compression: ZLIB, ..entry }, compressed));
+            }
+            Err(e) => {
+                report.failed.push((entry.tgi, format!("{:#}", e)));
+                resources.push((entry, stored));
+            }
+        }
+    }
+    drop(pkg);
+
+    if report.converted == 0 {
+        return Ok(report);
+    }
+    Package::write_stored(path, &resources).context("Failed to write recompressed package")?;
+    Ok(report)
+}
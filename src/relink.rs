@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use crate::package::index::TGI;
+use crate::package::resource::{ManifestResource, TypedResource};
+#[cfg(feature = "catalog")]
+use crate::package::resource::{
+    CasPartResource, CatalogCommon, CatalogResource, CfndResource, CstrResource, CwalResource,
+    ObjectDefinitionResource, ObjectProperty,
+};
+#[cfg(feature = "meshes")]
+use crate::package::resource::RcolResource;
+
+/// Patches the references a resource holds to other resources after they've been renumbered.
+///
+/// `map` takes each old TGI to its new one; references not in it are left alone.
+pub trait Relink {
+    /// Returns how many references were changed
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize;
+}
+
+fn relink_tgi(tgi: &mut TGI, map: &HashMap<TGI, TGI>) -> usize {
+    match map.get(tgi) {
+        Some(new) if new != tgi => {
+            *tgi = *new;
+            1
+        }
+        _ => 0,
+    }
+}
+
+fn relink_all<'a>(tgis: impl IntoIterator<Item = &'a mut TGI>, map: &HashMap<TGI, TGI>) -> usize {
+    tgis.into_iter().map(|tgi| relink_tgi(tgi, map)).sum()
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CatalogCommon {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        relink_all(&mut self.product_styles, map)
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CatalogResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        self.common.relink(map)
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CwalResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        self.common.relink(map)
+            + relink_all(self.matd_list.entries.iter_mut().map(|e| &mut e.matd_ref), map)
+            + relink_all(self.img_group_list.entries.iter_mut().map(|e| &mut e.img_ref), map)
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CfndResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        self.common.relink(map) + relink_all([&mut self.modl_ref1, &mut self.trim_ref, &mut self.modl_ref2], map)
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CstrResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        let refs = &mut self.ref_list;
+        self.common.relink(map)
+            + relink_all(
+                [&mut refs.modl_ref01, &mut refs.modl_ref02, &mut refs.modl_ref03, &mut refs.unk_ref01, &mut refs.wall_ref, &mut refs.obj_ref],
+                map,
+            )
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for ObjectDefinitionResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        self.properties.values_mut()
+            .map(|property| match property {
+                ObjectProperty::TGIBlockList(tgis) => relink_all(tgis, map),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+#[cfg(feature = "meshes")]
+impl Relink for RcolResource {
+    /// Chunk TGIs are included: a chunk carries the TGI of the resource it belongs to
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        relink_all(&mut self.external_resources, map) + relink_all(self.chunks.iter_mut().map(|c| &mut c.tgi), map)
+    }
+}
+
+#[cfg(feature = "catalog")]
+impl Relink for CasPartResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        // A CASP whose TGI list can't be read has no references we know how to patch
+        let Ok(tgis) = self.tgi_list() else { return 0 };
+        tgis.into_iter()
+            .enumerate()
+            .filter_map(|(i, tgi)| map.get(&tgi).filter(|new| **new != tgi).map(|new| (i, *new)))
+            .filter(|(i, new)| self.set_tgi(*i, *new).is_ok())
+            .count()
+    }
+}
+
+impl Relink for ManifestResource {
+    fn relink(&mut self, map: &HashMap<TGI, TGI>) -> usize {
+        relink_all(self.entries.iter_mut().flat_map(|e| e.resources.iter_mut()), map)
+    }
+}
+
+/// Applies [`Relink`] to any resource that supports it. Returns how many references were
+/// changed; resources without known references are left alone and return 0.
+pub fn relink(resource: &mut TypedResource, map: &HashMap<TGI, TGI>) -> usize {
+    match resource {
+        #[cfg(feature = "catalog")]
+        TypedResource::ObjectDefinition(r) => r.relink(map),
+        #[cfg(feature = "catalog")]
+        TypedResource::Catalog(r) => r.relink(map),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cwal(r) => r.relink(map),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cfnd(r) => r.relink(map),
+        #[cfg(feature = "catalog")]
+        TypedResource::Cstr(r) => r.relink(map),
+        #[cfg(feature = "meshes")]
+        TypedResource::Rcol(r) => r.relink(map),
+        #[cfg(feature = "catalog")]
+        TypedResource::CasPart(r) => r.relink(map),
+        TypedResource::Manifest(r) => r.relink(map),
+        _ => 0,
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::error::Result;
+use crate::package::Package;
+
+/// Least number of bytes requested at once. The header and the index of most packages fit in
+/// one request.
+pub const MIN_FETCH: u64 = 64 * 1024;
+
+/// True for `http://` and `https://` URLs
+pub fn is_url(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// A package opened from a URL. Only the header and index are downloaded up front; resources
+/// are fetched as they are read.
+pub struct RemotePackage {
+    pub package: Package,
+    /// Size of the whole package file
+    pub size: u64,
+    fetched: Arc<AtomicU64>,
+}
+
+impl RemotePackage {
+    /// Bytes downloaded so far
+    pub fn fetched(&self) -> u64 {
+        self.fetched.load(Ordering::Relaxed)
+    }
+}
+
+/// Opens a package from a URL using HTTP range requests. The server has to support them;
+/// most file hosts and CDNs do. Needs the `http` feature.
+pub fn open_package(url: &str) -> Result<RemotePackage> {
+    http::open_package(url)
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use crate::error::{Context, Error, Result};
+    use crate::package::Package;
+    use super::{RemotePackage, MIN_FETCH};
+
+    /// Reads a file over HTTP, one range request per read that misses the last range fetched
+    struct RangeReader {
+        agent: ureq::Agent,
+        url: String,
+        len: u64,
+        pos: u64,
+        buffer_start: u64,
+        buffer: Vec<u8>,
+        fetched: Arc<AtomicU64>,
+    }
+
+    impl RangeReader {
+        /// Fetches `len` bytes from `start`, returning them and the size of the whole file
+        fn fetch(&self, start: u64, len: u64) -> Result<(Vec<u8>, u64)> {
+            let mut response = self.agent.get(&self.url)
+                .header("Range", format!("bytes={}-{}", start, start + len - 1))
+                .call()
+                .with_context(|| format!("Failed to fetch {}", self.url))?;
+            match response.status().as_u16() {
+                206 => {}
+                200 => return Err(Error::Unsupported(format!("{} doesn't support range requests; download the package instead", self.url))),
+                status => return Err(Error::Other(format!("{} returned HTTP {}", self.url, status))),
+            }
+            // Content-Range: bytes <start>-<end>/<size>
+            let size = response.headers().get("content-range")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit('/').next())
+                .and_then(|size| size.trim().parse().ok())
+                .ok_or_else(|| Error::Other(format!("{} didn't say how big the file is", self.url)))?;
+            // ureq refuses a body that reaches the limit, so leave a byte spare
+            let data = response.body_mut().with_config().limit(len + 1).read_to_vec()
+                .with_context(|| format!("Failed to download from {}", self.url))?;
+            self.fetched.fetch_add(data.len() as u64, Ordering::Relaxed);
+            Ok((data, size))
+        }
+    }
+
+    impl Read for RangeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if buf.is_empty() || self.pos >= self.len {
+                return Ok(0);
+            }
+            let buffer_end = self.buffer_start + self.buffer.len() as u64;
+            if self.pos < self.buffer_start || self.pos >= buffer_end {
+                let len = (buf.len() as u64).max(MIN_FETCH).min(self.len - self.pos);
+                let (data, _) = self.fetch(self.pos, len).map_err(io::Error::other)?;
+                if data.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the server sent no data"));
+                }
+                self.buffer_start = self.pos;
+                self.buffer = data;
+            }
+            let offset = (self.pos - self.buffer_start) as usize;
+            let n = buf.len().min(self.buffer.len() - offset);
+            buf[..n].copy_from_slice(&self.buffer[offset..offset + n]);
+            self.pos += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl Seek for RangeReader {
+        fn seek(&mut self, from: SeekFrom) -> io::Result<u64> {
+            let pos = match from {
+                SeekFrom::Start(pos) => Some(pos),
+                SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+                SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            };
+            self.pos = pos.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the file"))?;
+            Ok(self.pos)
+        }
+    }
+
+    pub fn open_package(url: &str) -> Result<RemotePackage> {
+        let agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+        let fetched = Arc::new(AtomicU64::new(0));
+        let mut reader = RangeReader {
+            agent, url: url.to_string(), len: 0, pos: 0, buffer_start: 0, buffer: Vec::new(), fetched: Arc::clone(&fetched),
+        };
+        // The first range holds the header and tells us the file size
+        let (data, size) = reader.fetch(0, MIN_FETCH)?;
+        reader.len = size;
+        reader.buffer = data;
+        let package = Package::from_reader(reader).with_context(|| format!("{} is not a valid package", url))?;
+        Ok(RemotePackage { package, size, fetched })
+    }
+}
+
+#[cfg(not(feature = "http"))]
+mod http {
+    use crate::error::{Error, Result};
+    use super::RemotePackage;
+
+    pub fn open_package(url: &str) -> Result<RemotePackage> {
+        Err(Error::Unsupported(format!("Can't open {}: opening packages from a URL needs the `http` feature", url)))
+    }
+}
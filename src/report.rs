@@ -0,0 +1,147 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::{Context, Error, Result};
+use crate::package::header::PackageHeader;
+use crate::package::index::IndexEntry;
+
+/// How many bytes of the offending resource a failure report includes
+pub const REPORT_HEAD_BYTES: usize = 256;
+
+/// Classic 16-bytes-per-line hexdump with an ASCII column
+pub fn hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+        let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        let _ = writeln!(out, "{:08X}  {:<47}  {}", i * 16, hex.join(" "), ascii);
+    }
+    out
+}
+
+/// Runs `f`, turning a panic into an error so one bad resource can't take down a bulk operation
+pub fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(Error::Other(format!("panicked: {}", message)))
+        }
+    }
+}
+
+/// Everything known about one failure: where it happened, the error, and the first bytes of the data
+#[derive(Debug, Clone)]
+pub struct FailureContext {
+    pub package: PathBuf,
+    /// The package's header fields, if the header could be read
+    pub header: Option<String>,
+    /// The index entry of the offending resource; `None` for failures affecting the whole package
+    pub entry: Option<IndexEntry>,
+    pub error: String,
+    /// The first [`REPORT_HEAD_BYTES`] of the resource
+    pub head: Vec<u8>,
+}
+
+impl FailureContext {
+    /// `data` is the resource's (decompressed) bytes, when they were read successfully
+    pub fn new(package: &Path, header: Option<&PackageHeader>, entry: Option<&IndexEntry>, error: &crate::error::Error, data: Option<&[u8]>) -> Self {
+        let header = header.map(|h| format!(
+            "magic {:?}, version {}.{}, index version {}, {} entries, index at 0x{:X} ({} bytes)",
+            String::from_utf8_lossy(&h.magic), h.major, h.minor, h.index_version, h.entry_count(),
+            h.index_location().position, h.index_location().size,
+        ));
+        let head = data.map(|d| d[..d.len().min(REPORT_HEAD_BYTES)].to_vec()).unwrap_or_default();
+        Self { package: package.to_path_buf(), header, entry: entry.cloned(), error: format!("{:#}", error), head }
+    }
+
+    /// Like [`FailureContext::new`], but reads the head of the resource as stored in the file.
+    /// Used when reading or decompressing the resource is what failed.
+    pub fn from_stored(package: &Path, header: Option<&PackageHeader>, entry: &IndexEntry, error: &crate::error::Error) -> Self {
+        let stored = read_stored_head(package, entry).ok();
+        Self::new(package, header, Some(entry), error, stored.as_deref())
+    }
+}
+
+fn read_stored_head(package: &Path, entry: &IndexEntry) -> Result<Vec<u8>> {
+    let mut file = File::open(package)?;
+    file.seek(SeekFrom::Start(entry.offset as u64))?;
+    let len = (entry.filesize & 0x7FFFFFFF).min(REPORT_HEAD_BYTES as u32) as usize;
+    let mut buf = vec![0u8; len];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Collects failures from a bulk operation (safe to share between rayon workers) and writes
+/// them to one text file that can be attached to an issue
+#[derive(Debug, Default)]
+pub struct FailureReport {
+    failures: Mutex<Vec<FailureContext>>,
+}
+
+impl FailureReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, failure: FailureContext) {
+        self.failures.lock().unwrap().push(failure);
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Renders the report; `operation` names what was running, e.g. "merge"
+    pub fn to_text(&self, operation: &str) -> String {
+        let failures = self.failures.lock().unwrap();
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut out = String::new();
+        let _ = writeln!(out, "s4pi-reforged failure report");
+        let _ = writeln!(out, "Tool version: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(out, "Platform:     {} {}", std::env::consts::OS, std::env::consts::ARCH);
+        let _ = writeln!(out, "Operation:    {}", operation);
+        let _ = writeln!(out, "Time:         {} (Unix)", time);
+        let _ = writeln!(out, "Failures:     {}", failures.len());
+
+        for (i, failure) in failures.iter().enumerate() {
+            let _ = writeln!(out, "\n--- Failure {} ---", i + 1);
+            let _ = writeln!(out, "Package:     {}", failure.package.display());
+            if let Some(header) = &failure.header {
+                let _ = writeln!(out, "Header:      {}", header);
+            }
+            if let Some(entry) = &failure.entry {
+                let _ = writeln!(out, "Resource:    {}", entry.tgi);
+                let _ = writeln!(out, "Offset:      0x{:08X}", entry.offset);
+                let _ = writeln!(out, "Size:        {} bytes ({} stored)", entry.memsize, entry.filesize & 0x7FFFFFFF);
+                let _ = writeln!(out, "Compression: 0x{:04X}", entry.compression);
+            }
+            let _ = writeln!(out, "Error:       {}", failure.error);
+            if !failure.head.is_empty() {
+                let _ = writeln!(out, "First {} bytes:", failure.head.len());
+                out.push_str(&hexdump(&failure.head));
+            }
+        }
+        out
+    }
+
+    /// Writes the report into `dir` and returns its path
+    pub fn write(&self, dir: &Path, operation: &str) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = dir.join(format!("failure-report-{}-{}.txt", operation, time));
+        crate::atomic::write(&path, self.to_text(operation))
+            .with_context(|| format!("Failed to write failure report {:?}", path))?;
+        Ok(path)
+    }
+}
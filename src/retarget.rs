@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::error::Result;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::package::resource::{Resource, ResourceKind, TypedResource};
+use crate::tuning::looks_like_xml;
+
+/// One reference [`retarget_package`] changed (or would change, for a dry run)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetargetSite {
+    pub resource: TGI,
+    /// `line N` in tuning XML; the field's path in SimData, as given by
+    /// [`SimDataResource::retarget`](crate::package::resource::SimDataResource::retarget)
+    pub location: String,
+}
+
+/// What [`retarget_package`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetargetReport {
+    pub sites: Vec<RetargetSite>,
+    /// Resources with at least one site
+    pub resources: usize,
+}
+
+fn is_token_boundary(byte: Option<&u8>) -> bool {
+    byte.is_none_or(|b| !(b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_' | b':')))
+}
+
+/// Replaces every reference to tuning instance `from` in `xml` with `to`. Tuning refers to other
+/// tuning by its decimal instance (e.g. `<T n="buff_type">12345</T>`), so whole decimal numbers in
+/// element text are matched; attributes and comments are left alone, which keeps the `s="..."`
+/// of the tuning's own root element as it is. Returns the new text and the line of each
+/// reference changed.
+pub fn retarget_xml(xml: &str, from: u64, to: u64) -> (String, Vec<usize>) {
+    let bytes = xml.as_bytes();
+    let to = to.to_string();
+    let mut out = String::with_capacity(xml.len());
+    let mut lines = Vec::new();
+    let mut line = 1;
+    let mut last = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'<' => {
+                let end = if xml[i..].starts_with("<!--") { "-->" } else { ">" };
+                let close = xml[i..].find(end).map_or(bytes.len(), |p| i + p + end.len());
+                line += bytes[i..close].iter().filter(|&&b| b == b'\n').count();
+                i = close;
+            }
+            b'\n' => {
+                line += 1;
+                i += 1;
+            }
+            b'0'..=b'9' if is_token_boundary(i.checked_sub(1).map(|p| &bytes[p])) => {
+                let end = bytes[i..].iter().position(|b| !b.is_ascii_digit()).map_or(bytes.len(), |p| i + p);
+                if is_token_boundary(bytes.get(end)) && xml[i..end].parse::<u64>() == Ok(from) {
+                    out.push_str(&xml[last..i]);
+                    out.push_str(&to);
+                    last = end;
+                    lines.push(line);
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    out.push_str(&xml[last..]);
+    (out, lines)
+}
+
+/// Points every reference to tuning instance `from` in the package at `path` to `to`, in its
+/// tuning XML and its SimData, e.g. to have an interaction give a different buff. With `dry_run`
+/// the package is left alone and the report says what would change.
+pub fn retarget_package<P: AsRef<Path>>(path: P, from: u64, to: u64, dry_run: bool) -> Result<RetargetReport> {
+    let path = path.as_ref();
+    let pkg = Package::open(path)?;
+    let mut report = RetargetReport::default();
+    let mut replacements: HashMap<TGI, Vec<u8>> = HashMap::new();
+
+    for entry in pkg.entries.clone() {
+        let (data, locations) = match TypedResource::kind_of(entry.tgi.res_type) {
+            ResourceKind::Xml | ResourceKind::Text => {
+                let data = pkg.read_raw_resource(&entry)?;
+                if !looks_like_xml(&data) {
+                    continue;
+                }
+                let Ok(xml) = std::str::from_utf8(&data) else { continue };
+                let (retargeted, lines) = retarget_xml(xml, from, to);
+                (retargeted.into_bytes(), lines.iter().map(|line| format!("line {}", line)).collect())
+            }
+            ResourceKind::SimData => {
+                let TypedResource::SimData(mut simdata) = pkg.read_resource(&entry)? else { continue };
+                let fields = simdata.retarget(from, to);
+                (simdata.to_bytes()?, fields)
+            }
+            _ => continue,
+        };
+        if locations.is_empty() {
+            continue;
+        }
+        report.resources += 1;
+        report.sites.extend(locations.into_iter().map(|location| RetargetSite { resource: entry.tgi, location }));
+        replacements.insert(entry.tgi, data);
+    }
+    drop(pkg);
+
+    if !dry_run && !replacements.is_empty() {
+        Package::replace_resources(path, &replacements)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,224 @@
+use crate::package::index::TGI;
+use crate::package::resource::*;
+
+/// A field of a wrapper struct and its Rust type, e.g. `Vec<StblEntry>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub type_name: String,
+}
+
+/// The fields of one struct, in declaration order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructSchema {
+    pub name: &'static str,
+    pub fields: Vec<FieldSchema>,
+}
+
+/// What the parser knows about one kind of resource, from [`formats`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatSchema {
+    pub kind: ResourceKind,
+    /// The resource types read with this wrapper, from [`RESOURCE_KINDS`]
+    pub resource_types: &'static [u32],
+    /// The struct the resource is read into
+    pub wrapper: StructSchema,
+    /// The versions the wrapper reads differently, or "any" where it doesn't look at the version
+    pub versions: &'static str,
+    /// Whether the wrapper can write the resource back out
+    pub writable: bool,
+}
+
+/// The [`StructSchema`] of struct `$ty`, with each field's type taken from the struct itself. The
+/// field list has to name every field, so this stops compiling when the struct changes.
+macro_rules! describe {
+    ($ty:ident { $($field:ident),* $(,)? }) => {{
+        #[allow(dead_code)]
+        fn every_field($ty { $($field: _),* }: &$ty) {}
+        StructSchema {
+            name: stringify!($ty),
+            fields: vec![$(FieldSchema { name: stringify!($field), type_name: field_type(|r: &$ty| &r.$field) }),*],
+        }
+    }};
+}
+
+fn field_type<S, F>(_: fn(&S) -> &F) -> String {
+    short_type_name(std::any::type_name::<F>())
+}
+
+/// `alloc::vec::Vec<s4pi_reforged::package::index::TGI>` as `Vec<TGI>`
+fn short_type_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut segment = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            out.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            out.push(c);
+        }
+    }
+    out.push_str(segment.rsplit("::").next().unwrap_or_default());
+    out
+}
+
+/// Every [`ResourceKind`] the parser has a wrapper for, with the resource types it reads, the
+/// fields it reads them into and which versions it handles. [`ResourceKind::Generic`], for
+/// everything else, comes last.
+pub fn formats() -> Vec<FormatSchema> {
+    let types = |kind: ResourceKind| -> &'static [u32] {
+        RESOURCE_KINDS.iter().find(|(k, _)| *k == kind).map_or(&[], |(_, types)| types)
+    };
+    let format = |kind, wrapper, versions, writable| FormatSchema { kind, resource_types: types(kind), wrapper, versions, writable };
+    let stub = || describe!(GenericStubResource { res_type, data });
+    #[cfg(feature = "catalog")]
+    const CATALOG_COMMON: &str = "common block before 10, from 10 (pack ID) and from 11 (tag list)";
+
+    vec![
+        format(ResourceKind::NameMap, describe!(NameMapResource { version, entries }), "any", true),
+        format(ResourceKind::Stbl, describe!(StblResource { version, is_compressed, reserved, string_length, entries }), "any", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::ObjectDefinition, describe!(ObjectDefinitionResource { version, properties, order }), "any", true),
+        #[cfg(feature = "tuning")]
+        format(ResourceKind::SimData, describe!(SimDataResource { version, tables, schemas, raw_data }), "0x100, and from 0x101 (extra header word)", true),
+        format(ResourceKind::Text, describe!(TextResource { content }), "any", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Catalog, describe!(CatalogResource {
+            version, common, aural_materials_version, aural_properties_version, aural_ambient_object,
+            ambience_file_instance_id, is_override_ambience, unknown01, placement_flags_high, placement_flags_low,
+            slot_type_set,
+        }), "common block before 10, from 10 (pack ID) and from 11 (tag list); aural properties 1 to 4", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Cwal, describe!(CwalResource { version, common, matd_list, img_group_list, unk01, colors, unk_iid01 }), CATALOG_COMMON, true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Cfnd, describe!(CfndResource {
+            version, common, unk01, unk02, modl_ref1, material_variant, swatch_grouping, float1, float2, trim_ref,
+            modl_ref2, colors,
+        }), CATALOG_COMMON, true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Cstr, describe!(CstrResource {
+            version, common, hash_indicator, hash01, hash02, hash03, ref_list, unk01, unk02, unk03, material_variant,
+            swatch_grouping, colors, unk05,
+        }), CATALOG_COMMON, true),
+        #[cfg(feature = "textures")]
+        format(ResourceKind::Rle, describe!(RleResource { magic, version, width, height, mip_count }), "any", false),
+        #[cfg(feature = "textures")]
+        format(ResourceKind::Dst, describe!(DstResource { version, raw_data }), "DST1, DST3 and DST5, and plain DDS", true),
+        format(ResourceKind::Script, describe!(ScriptResource { version, game_version }), "1, and from 2 (game version)", false),
+        format(ResourceKind::Clip, describe!(ClipResource { version, raw_data }), "header from 5, 7, 10 and 11", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::CasPart, describe!(CasPartResource { version, raw_data }), "pack, tag and TGI list layouts from 34, 36, 37, 39, 41 and 43", true),
+        format(ResourceKind::Jazz, describe!(JazzResource { raw_data }), "any", true),
+        #[cfg(feature = "meshes")]
+        format(ResourceKind::Rcol, describe!(RcolResource { version, public_chunks, unused, external_resources, chunks }), "any", true),
+        #[cfg(feature = "meshes")]
+        format(ResourceKind::Rig, describe!(RigResource { format, raw_data }), "Clear rigs; Granny rigs are kept as they are", true),
+        format(ResourceKind::Lite, describe!(LiteResource { version, raw_data }), "any", true),
+        format(ResourceKind::Thumbnail, describe!(ThumbnailResource { has_alpha, kind, raw_data }), "any", true),
+        format(ResourceKind::Complate, describe!(ComplateResource { unknown1, content, unknown2 }), "any", true),
+        #[cfg(feature = "textures")]
+        format(ResourceKind::Txtc, describe!(TxtcResource { version, raw_data }), "any", true),
+        format(ResourceKind::ObjKey, describe!(ObjKeyResource { format, raw_data }), "any", true),
+        #[cfg(feature = "meshes")]
+        format(ResourceKind::SimModifier, describe!(SimModifierResource { version, raw_data }), "any", true),
+        #[cfg(feature = "meshes")]
+        format(ResourceKind::Bone, describe!(BoneResource { version, raw_data }), "any", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Mtbl, describe!(MtblResource { version, entries }), "any", true),
+        #[cfg(feature = "catalog")]
+        format(ResourceKind::Trim, describe!(TrimResource { version, entries, material_set_key, has_footprint }), "3, and from 4 (mapping mode)", true),
+        #[cfg(feature = "meshes")]
+        format(ResourceKind::BlendGeometry, describe!(BlendGeometryResource { version, lods, blend_map, vectors }), "any", true),
+        format(ResourceKind::Manifest, describe!(ManifestResource { version, padding, entries }), "1, and from 2 (checksums)", true),
+        format(ResourceKind::Xml, stub(), "any", true),
+        #[cfg(feature = "audio")]
+        format(ResourceKind::Audio, stub(), "any", true),
+        format(ResourceKind::Image, stub(), "any", true),
+        format(ResourceKind::World, stub(), "any", true),
+        format(ResourceKind::Binary, stub(), "any", true),
+        format(ResourceKind::Generic, describe!(GenericResource { data, source }), "any", true),
+    ]
+}
+
+/// The structs the wrappers' fields are made of, for looking up the types [`formats`] names
+pub fn definitions() -> Vec<StructSchema> {
+    vec![
+        describe!(TGI { res_type, res_group, instance }),
+        describe!(NameMapEntry { instance, name }),
+        describe!(StblEntry { key_hash, flags, string_value }),
+        #[cfg(feature = "catalog")]
+        describe!(CatalogCommon {
+            version, name_hash, description_hash, price, thumbnail_hash, dev_category_flags, product_styles, pack_id,
+            pack_flags, reserved_bytes, unused2, unused3, tags, legacy_tags, selling_points, unlock_by_hash,
+            unlocked_by_hash, swatch_colors_sort_priority, varient_thumb_image_hash,
+        }),
+        #[cfg(feature = "catalog")]
+        describe!(CatalogTagList { tags }),
+        #[cfg(feature = "catalog")]
+        describe!(LegacyTagList { tags }),
+        #[cfg(feature = "catalog")]
+        describe!(SellingPointList { points }),
+        #[cfg(feature = "catalog")]
+        describe!(SellingPoint { hash, value }),
+        #[cfg(feature = "catalog")]
+        describe!(ColorList { colors }),
+        #[cfg(feature = "catalog")]
+        describe!(WallMATDEntryList { entries }),
+        #[cfg(feature = "catalog")]
+        describe!(WallMATDEntry { matd_label, matd_ref }),
+        #[cfg(feature = "catalog")]
+        describe!(WallImgGroupList { entries }),
+        #[cfg(feature = "catalog")]
+        describe!(WallImgGroup { unk01, img_ref }),
+        #[cfg(feature = "catalog")]
+        describe!(CstrReferences { modl_ref01, modl_ref02, modl_ref03, unk_ref01, wall_ref, obj_ref }),
+        #[cfg(feature = "catalog")]
+        describe!(MtblEntryList { entries }),
+        #[cfg(feature = "catalog")]
+        describe!(MtblEntry {
+            model_iid, base_file_name_hash, width_and_mapping_flags, minimum_wall_height, number_of_levels, unused,
+            thumbnail_bounds_min_x, thumbnail_bounds_min_z, thumbnail_bounds_min_y, thumbnail_bounds_max_x,
+            thumbnail_bounds_max_z, thumbnail_bounds_max_y, model_flags, vfx_hash,
+        }),
+        #[cfg(feature = "catalog")]
+        describe!(TrimPt3Entry { x, y, v }),
+        #[cfg(feature = "catalog")]
+        describe!(TrimPt4Entry { x, y, v, mapping_mode }),
+        #[cfg(feature = "meshes")]
+        describe!(RcolChunk { tgi, tag, data }),
+        #[cfg(feature = "meshes")]
+        describe!(BlendLod { index_base, vertex_count, vector_count }),
+        #[cfg(feature = "meshes")]
+        describe!(BlendVector { x, y, z }),
+        describe!(ManifestEntry { name, resources, checksums }),
+        describe!(ResourceChecksum { size, hash }),
+    ]
+}
+
+fn struct_json(schema: &StructSchema) -> serde_json::Value {
+    serde_json::json!({
+        "name": schema.name,
+        "fields": schema.fields.iter()
+            .map(|f| serde_json::json!({ "name": f.name, "type": f.type_name }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// [`formats`] and [`definitions`] as one JSON document, for tools and documentation that
+/// need to know what this parser supports. Resource types are written as `0x%08X`.
+pub fn to_json() -> String {
+    let formats: Vec<serde_json::Value> = formats().iter().map(|format| serde_json::json!({
+        "kind": format!("{:?}", format.kind),
+        "resource_types": format.resource_types.iter().map(|t| format!("0x{:08X}", t)).collect::<Vec<_>>(),
+        "wrapper": struct_json(&format.wrapper),
+        "versions": format.versions,
+        "writable": format.writable,
+    })).collect();
+    let definitions: Vec<serde_json::Value> = definitions().iter().map(struct_json).collect();
+    serde_json::to_string_pretty(&serde_json::json!({
+        "generator": concat!("s4pi_reforged ", env!("CARGO_PKG_VERSION")),
+        "formats": formats,
+        "definitions": definitions,
+    })).expect("JSON values always serialize")
+}
@@ -0,0 +1,448 @@
+use std::io::{Cursor, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::error::{Context, Error, Result};
+use crate::manifest::{read_manifest, verify};
+use crate::merge::{merge, unmerge, MergeOptions};
+use crate::package::Package;
+use crate::package::header::{IndexLocation, PackageHeader};
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{Resource, StblEntry, StblResource, TypedResource};
+use crate::package::validate::validate_file;
+use crate::report::catch_panic;
+
+/// A resource type no wrapper claims, for samples whose data only has to come back unchanged
+const SAMPLE_TYPE: u32 = 0x5E1F7E57;
+
+/// The round trip every sample package goes through, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// The package reads back with the data it was written with, through its wrapper for typed
+    /// resources
+    Open,
+    /// Merging a folder holding it keeps every resource as it was
+    Merge,
+    /// Un-merging the merged package gives it back
+    Unmerge,
+    /// The merged package's manifest checksums match, and the un-merged package validates
+    Verify,
+}
+
+impl Step {
+    pub const ALL: [Step; 4] = [Step::Open, Step::Merge, Step::Unmerge, Step::Verify];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Step::Open => "open",
+            Step::Merge => "merge",
+            Step::Unmerge => "unmerge",
+            Step::Verify => "verify",
+        }
+    }
+}
+
+/// How one step went for one sample
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    /// Not run because an earlier step failed
+    Skipped,
+}
+
+/// One row of the self-test: a sample package and how each [`Step`] went for it
+#[derive(Debug, Clone)]
+pub struct SampleResult {
+    /// What the sample covers: `index`, `compression` or `resource`
+    pub group: &'static str,
+    pub name: String,
+    /// One per [`Step::ALL`]
+    pub outcomes: [Outcome; 4],
+}
+
+/// What [`run`] found
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub samples: Vec<SampleResult>,
+}
+
+impl SelfTestReport {
+    pub fn failures(&self) -> usize {
+        self.samples.iter().flat_map(|s| &s.outcomes).filter(|o| matches!(o, Outcome::Failed(_))).count()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+}
+
+/// A resource as a sample stores it: its index entry, its stored bytes and what they should
+/// read back as
+struct SampleResource {
+    entry: IndexEntry,
+    stored: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl SampleResource {
+    fn uncompressed(tgi: TGI, data: Vec<u8>) -> Self {
+        let entry = IndexEntry { tgi, offset: 0, filesize: data.len() as u32, memsize: data.len() as u32, compression: 0, committed: 1 };
+        Self { entry, stored: data.clone(), data }
+    }
+}
+
+struct Sample {
+    group: &'static str,
+    name: String,
+    /// Flags for the fields every index entry shares: 0x1 type, 0x2 group, 0x4 instance high
+    index_type: u32,
+    resources: Vec<SampleResource>,
+    /// Whether the resources should parse with their wrapper and write back unchanged
+    typed: bool,
+    /// Why the sample couldn't be made, which fails it at the first step
+    problem: Option<String>,
+}
+
+/// Writes `sample` as a package, with its index in the layout its index type calls for
+fn write_sample(path: &Path, sample: &Sample) -> Result<()> {
+    let first = &sample.resources[0].entry.tgi;
+    let mut out = Cursor::new(Vec::new());
+    out.seek(SeekFrom::Start(PackageHeader::SIZE))?;
+    let mut offsets = Vec::with_capacity(sample.resources.len());
+    for resource in &sample.resources {
+        offsets.push(out.position() as u32);
+        out.write_all(&resource.stored)?;
+    }
+
+    let index_position = out.position();
+    out.write_all(&sample.index_type.to_le_bytes())?;
+    let constants = [first.res_type, first.res_group, (first.instance >> 32) as u32];
+    for (bit, value) in constants.iter().enumerate() {
+        if sample.index_type & (1 << bit) != 0 {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    for (resource, offset) in sample.resources.iter().zip(offsets) {
+        let entry = &resource.entry;
+        let fields = [entry.tgi.res_type, entry.tgi.res_group, (entry.tgi.instance >> 32) as u32];
+        for (bit, value) in fields.iter().enumerate() {
+            if sample.index_type & (1 << bit) == 0 {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        out.write_all(&(entry.tgi.instance as u32).to_le_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        let filesize = resource.stored.len() as u32 | if entry.compression != 0 { 0x80000000 } else { 0 };
+        out.write_all(&filesize.to_le_bytes())?;
+        out.write_all(&entry.memsize.to_le_bytes())?;
+        out.write_all(&entry.compression.to_le_bytes())?;
+        out.write_all(&entry.committed.to_le_bytes())?;
+    }
+
+    let mut header = PackageHeader::new();
+    header.set_entry_count(sample.resources.len() as u32);
+    header.set_index_location(IndexLocation { position: index_position, size: (out.position() - index_position) as u32 });
+    out.seek(SeekFrom::Start(0))?;
+    header.write(&mut out)?;
+    crate::atomic::write(path, out.into_inner()).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Checks that the package at `path` holds exactly the sample's resources, with their data
+fn check_package(path: &Path, sample: &Sample, typed: bool) -> Result<()> {
+    let pkg = Package::open(path)?;
+    let count = pkg.entries.iter().filter(|e| !crate::merge::is_merge_record(e.tgi.res_type)).count();
+    if count != sample.resources.len() {
+        return Err(Error::InvalidResource(format!("Expected {} resources, found {}", sample.resources.len(), count)));
+    }
+    for resource in &sample.resources {
+        let tgi = resource.entry.tgi;
+        let entry = pkg.find(&tgi).cloned().ok_or(Error::ResourceNotFound(tgi))?;
+        if pkg.read_raw_resource(&entry)? != resource.data {
+            return Err(Error::InvalidResource(format!("Resource {} reads back different data", tgi)));
+        }
+        if typed {
+            let parsed = pkg.read_resource(&entry)?;
+            if matches!(parsed, TypedResource::Generic(_)) {
+                return Err(Error::InvalidResource(format!("Resource {} wasn't read with its wrapper", tgi)));
+            }
+            if parsed.to_bytes().with_context(|| format!("Failed to write resource {}", tgi))? != resource.data {
+                return Err(Error::InvalidResource(format!("Resource {} is written back differently", tgi)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs every step for one sample in its own folder under `dir`
+fn run_sample(sample: &Sample, dir: &Path) -> [Outcome; 4] {
+    let mut outcomes = [Outcome::Skipped, Outcome::Skipped, Outcome::Skipped, Outcome::Skipped];
+    let mods = dir.join("mods");
+    let input = mods.join("sample.package");
+    let steps: [&dyn Fn() -> Result<()>; 4] = [
+        &|| {
+            if let Some(problem) = &sample.problem {
+                return Err(Error::InvalidResource(problem.clone()));
+            }
+            std::fs::create_dir_all(&mods)?;
+            write_sample(&input, sample)?;
+            check_package(&input, sample, sample.typed)
+        },
+        &|| {
+            let options = MergeOptions { provenance: false, ..MergeOptions::default() };
+            let summary = merge(&mods, &options, &())?;
+            let output = summary.outputs.first()
+                .ok_or_else(|| Error::Other("The merge wrote no package".to_string()))?;
+            check_package(output, sample, false)
+        },
+        &|| {
+            let summary = unmerge(&crate::merge::output_paths(&mods, false)[0], &())?;
+            let output = summary.packages.first()
+                .ok_or_else(|| Error::Other("The un-merge wrote no package".to_string()))?;
+            check_package(output, sample, sample.typed)
+        },
+        &|| {
+            let merged = Package::open(&crate::merge::output_paths(&mods, false)[0])?;
+            let (_, manifest) = read_manifest(&merged)?;
+            if !manifest.has_checksums() {
+                return Err(Error::InvalidResource("The merged package's manifest has no checksums".to_string()));
+            }
+            if let Some(issue) = verify(&merged, &manifest).first() {
+                return Err(Error::InvalidResource(issue.to_string()));
+            }
+            let unmerged = mods.join("merged").join("unmerged").join("sample.package");
+            let report = validate_file(&unmerged)?;
+            if !report.is_valid() {
+                return Err(Error::InvalidResource(format!("The un-merged package has {} validation issue(s)", report.issue_count())));
+            }
+            Ok(())
+        },
+    ];
+    for (outcome, step) in outcomes.iter_mut().zip(steps) {
+        match catch_panic(step) {
+            Ok(()) => *outcome = Outcome::Passed,
+            Err(e) => {
+                *outcome = Outcome::Failed(format!("{:#}", e));
+                break;
+            }
+        }
+    }
+    outcomes
+}
+
+/// Bytes that compress well but aren't all the same
+fn filler(len: usize) -> Vec<u8> {
+    (0..len).map(|i| b"s4pi-reforged selftest "[i % 23]).collect()
+}
+
+/// A RefPack stream of `data` made of literal runs only, which every reader has to handle
+fn refpack_literals(data: &[u8]) -> Vec<u8> {
+    let len = data.len() as u32;
+    let mut out = vec![0x10, 0xFB, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    let mut chunks = data.chunks(112).peekable();
+    while let Some(chunk) = chunks.next() {
+        let runs = chunk.len() / 4 * 4;
+        if runs > 0 {
+            out.push(0xE0 + (runs / 4 - 1) as u8);
+            out.extend_from_slice(&chunk[..runs]);
+        }
+        if chunks.peek().is_none() {
+            out.push(0xFC + (chunk.len() - runs) as u8);
+            out.extend_from_slice(&chunk[runs..]);
+        }
+    }
+    if data.is_empty() {
+        out.push(0xFC);
+    }
+    out
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// One sample per index layout: three resources sharing their type, group and instance high
+/// bits, so each combination of shared fields can be used
+fn index_samples() -> Vec<Sample> {
+    (0..8)
+        .map(|index_type| Sample {
+            group: "index",
+            name: format!("type 0x{:02X}", index_type),
+            index_type,
+            resources: (1..=3)
+                .map(|i| SampleResource::uncompressed(TGI { res_type: SAMPLE_TYPE, res_group: 0x80000000, instance: 0x0A0B0C0D_00000000 | i }, filler(40 + i as usize)))
+                .collect(),
+            typed: false,
+            problem: None,
+        })
+        .collect()
+}
+
+/// One sample per way resource data can be stored
+fn compression_samples() -> Result<Vec<Sample>> {
+    let data = filler(300);
+    let tgi = TGI { res_type: SAMPLE_TYPE, res_group: 0, instance: 1 };
+    let stored = |compression: u16, stored: Vec<u8>| SampleResource {
+        entry: IndexEntry { tgi, offset: 0, filesize: stored.len() as u32, memsize: data.len() as u32, compression, committed: 1 },
+        stored,
+        data: data.clone(),
+    };
+    Ok([
+        ("uncompressed", stored(0, data.clone())),
+        ("zlib", stored(0x5A42, zlib(&data)?)),
+        ("refpack", stored(0xFFFF, refpack_literals(&data))),
+    ]
+    .into_iter()
+    .map(|(name, resource)| Sample { group: "compression", name: name.to_string(), index_type: 0, resources: vec![resource], typed: false, problem: None })
+    .collect())
+}
+
+/// The fields every catalog resource starts with, in the version 9 layout, all empty
+#[cfg(feature = "catalog")]
+fn catalog_common() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&9u32.to_le_bytes()); // common version
+    data.extend_from_slice(&[0; 20]); // name and description hashes, price, thumbnail hash
+    data.extend_from_slice(&[0; 6]); // dev category flags, product styles, unused
+    data.extend_from_slice(&[0; 8]); // legacy tags, selling points
+    data.extend_from_slice(&[0; 18]); // unlock hashes, swatch sort priority, variant thumbnail
+    data
+}
+
+/// Bytes for a resource of each type a wrapper reads and writes. Where the wrapper writes a
+/// canonical form, the sample is that form, so it has to come back byte for byte.
+fn typed_seeds() -> Result<Vec<(&'static str, u32, Vec<u8>)>> {
+    let mut seeds = Vec::new();
+
+    let mut name_map = Vec::new();
+    name_map.extend_from_slice(&1u32.to_le_bytes());
+    name_map.extend_from_slice(&1u32.to_le_bytes());
+    name_map.extend_from_slice(&0x1234u64.to_le_bytes());
+    name_map.extend_from_slice(&6u32.to_le_bytes());
+    name_map.extend_from_slice(b"sample");
+    seeds.push(("name map", 0x0166038C, name_map));
+
+    let mut stbl = StblResource { version: 5, is_compressed: 0, reserved: [0; 2], string_length: 0, entries: vec![
+        StblEntry { key_hash: 0x1A2B3C4D, flags: 0, string_value: "Self-test".to_string() },
+    ] };
+    stbl.recalculate_string_length();
+    seeds.push(("string table", 0x220557DA, stbl.to_bytes()?));
+    seeds.push(("tuning", 0x034AEECB, b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<I c=\"Buff\" i=\"buff\" m=\"buffs.buff\" n=\"selftest\" s=\"1\">\n  <T n=\"visible\">False</T>\n</I>".to_vec()));
+    #[cfg(feature = "tuning")]
+    seeds.push(("SimData", 0x545AC67A, crate::simdata::from_xml(concat!(
+        "<SimData version=\"0x00000101\" u=\"0x00000000\"><Instances><I name=\"selftest\" schema=\"Buff\">",
+        "<T name=\"visible\">0</T></I></Instances><Schemas><Schema name=\"Buff\" schema_hash=\"0x00000000\"><Columns>",
+        "<Column name=\"visible\" type=\"Boolean\" flags=\"0x0000\" /></Columns></Schema></Schemas></SimData>",
+    ))?.to_bytes()?));
+
+    #[cfg(feature = "catalog")]
+    {
+        let mut objd = Vec::new();
+        objd.extend_from_slice(&2u16.to_le_bytes());
+        objd.extend_from_slice(&19u32.to_le_bytes()); // table offset
+        objd.extend_from_slice(&9u32.to_le_bytes());
+        objd.extend_from_slice(b"selftest");
+        objd.push(1); // an unknown property's byte
+        objd.extend_from_slice(&2u16.to_le_bytes());
+        objd.extend_from_slice(&0xE7F07786u32.to_le_bytes()); // name
+        objd.extend_from_slice(&6u32.to_le_bytes());
+        objd.extend_from_slice(&0x0BADF00Du32.to_le_bytes());
+        objd.extend_from_slice(&18u32.to_le_bytes());
+        seeds.push(("object definition", 0xC0DB5AE7, objd));
+
+        let mut cobj = 0x19u32.to_le_bytes().to_vec();
+        cobj.extend(catalog_common());
+        cobj.extend_from_slice(&[0; 16]); // aural materials version and materials
+        cobj.extend_from_slice(&1u32.to_le_bytes()); // aural properties version
+        cobj.extend_from_slice(&[0; 32]); // aural quality, unused, placement flags, slot type set
+        seeds.push(("catalog object", 0x319E4F1D, cobj));
+        for (name, res_type) in [("wall", 0xD5F0F921), ("foundation", 0x2FAE983E), ("stairs", 0x9A20CD1C)] {
+            let mut seed = 1u32.to_le_bytes().to_vec();
+            seed.extend(catalog_common());
+            seed.extend_from_slice(&[0; 160]);
+            seeds.push((name, res_type, seed));
+        }
+        seeds.push(("material table", 0x81CA1A10, [&b"MTBL"[..], &1u32.to_le_bytes(), &0u32.to_le_bytes()].concat()));
+        seeds.push(("trim", 0x76BCF80C, [&b"TRIM"[..], &3u32.to_le_bytes(), &[0; 21]].concat()));
+
+        let mut casp = 43u32.to_le_bytes().to_vec();
+        casp.extend_from_slice(&[0; 8]); // TGI list offset, preset count
+        casp.push(0); // empty name
+        casp.extend_from_slice(&[0; 40]);
+        casp.extend_from_slice(&1u32.to_le_bytes()); // one tag
+        casp.extend_from_slice(&[0; 49]);
+        seeds.push(("CAS part", 0x034AE111, casp));
+    }
+
+    #[cfg(feature = "meshes")]
+    {
+        let model = crate::package::resource::RcolResource {
+            version: 3,
+            public_chunks: 1,
+            unused: 0,
+            external_resources: vec![TGI { res_type: 0x01D10F34, res_group: 0, instance: 2 }],
+            chunks: vec![crate::package::resource::RcolChunk {
+                tgi: TGI { res_type: 0x01661233, res_group: 0, instance: 1 },
+                tag: "MODL".to_string(),
+                data: [&b"MODL"[..], &0x100u32.to_le_bytes(), &[0; 9]].concat(),
+            }],
+        };
+        seeds.push(("RCOL model", 0x01661233, model.to_bytes()?));
+        seeds.push(("rig", 0x8EAF13DE, [&3u32.to_le_bytes()[..], &1u32.to_le_bytes(), &[0; 16]].concat()));
+        seeds.push(("sim modifier", 0xC5F6763E, filler(32)));
+        seeds.push(("bone", 0x00AE6C67, [&1u32.to_le_bytes()[..], &filler(16)].concat()));
+        seeds.push(("blend geometry", 0x067CAA11, [&b"BGEO"[..], &0x600u32.to_le_bytes(), &[0; 12]].concat()));
+    }
+
+    #[cfg(feature = "textures")]
+    {
+        seeds.push(("DST texture", 0x00B2D882, [&b"DST1"[..], &filler(60)].concat()));
+        seeds.push(("texture compositor", 0x033A1435, [&1u32.to_le_bytes()[..], &filler(24)].concat()));
+    }
+
+    seeds.push(("clip", 0x6B20C4F3, [&11u32.to_le_bytes()[..], &filler(60)].concat()));
+    seeds.push(("jazz", 0x02D5DF13, filler(48)));
+    seeds.push(("light", 0x03B4C61D, [&b"LITE"[..], &4u32.to_le_bytes(), &filler(24)].concat()));
+    seeds.push(("thumbnail", 0x3C1AF1F2, [&[0xFF, 0xD8, 0xFF, 0xE0][..], &filler(60)].concat()));
+    let mut complate = 2u32.to_le_bytes().to_vec();
+    complate.extend_from_slice(&8i32.to_le_bytes());
+    complate.extend("selftest".encode_utf16().flat_map(u16::to_le_bytes));
+    complate.extend_from_slice(&0u32.to_le_bytes());
+    seeds.push(("complate", 0x044AE110, complate));
+    seeds.push(("object key", 0x02DC343F, [&7u32.to_le_bytes()[..], &filler(20)].concat()));
+    Ok(seeds)
+}
+
+/// One sample per wrapper that can write, holding a resource of its type
+fn typed_samples() -> Result<Vec<Sample>> {
+    Ok(typed_seeds()?
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, res_type, seed))| {
+            let tgi = TGI { res_type, res_group: 0, instance: 0x5E1F_0000 + i as u64 };
+            // The wrapper's own output, so the sample is in the form it writes
+            let (data, problem) = match TypedResource::from_bytes(res_type, &seed).and_then(|r| r.to_bytes()) {
+                Ok(data) => (data, None),
+                Err(e) => (seed, Some(format!("The wrapper can't read and write the sample: {:#}", e))),
+            };
+            Sample { group: "resource", name: name.to_string(), index_type: 0, resources: vec![SampleResource::uncompressed(tgi, data)], typed: true, problem }
+        })
+        .collect())
+}
+
+/// Writes synthetic packages covering every index layout, every way resource data is stored
+/// and every resource type this build can write, and takes each through open, merge, un-merge
+/// and verify in its own folder under `work_dir`. A sample that fails a step skips the rest.
+pub fn run(work_dir: &Path) -> Result<SelfTestReport> {
+    let mut samples = index_samples();
+    samples.extend(compression_samples()?);
+    samples.extend(typed_samples()?);
+
+    let mut report = SelfTestReport::default();
+    for (i, sample) in samples.iter().enumerate() {
+        let dir = work_dir.join(format!("{:02}", i));
+        let outcomes = run_sample(sample, &dir);
+        report.samples.push(SampleResult { group: sample.group, name: sample.name.clone(), outcomes });
+    }
+    Ok(report)
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::error::{Context, Error, Result};
+
+/// What a context-menu action is offered on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellTarget {
+    Folder,
+    PackageFile,
+}
+
+/// A right-click action that runs a CLI command on the clicked folder or file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShellAction {
+    /// Stable identifier, used for registry keys and .desktop file names
+    pub id: &'static str,
+    pub label: &'static str,
+    pub target: ShellTarget,
+    /// CLI command run with the clicked path as its argument. Empty opens the path in the GUI
+    /// resource browser instead of a console.
+    pub command: &'static str,
+}
+
+pub const SHELL_ACTIONS: [ShellAction; 3] = [
+    ShellAction { id: "merge", label: "Merge packages here", target: ShellTarget::Folder, command: "merge" },
+    ShellAction { id: "unmerge", label: "Un-merge", target: ShellTarget::PackageFile, command: "unmerge" },
+    ShellAction { id: "inspect", label: "Inspect package", target: ShellTarget::PackageFile, command: "" },
+];
+
+/// MIME type registered for .package files on Linux
+pub const PACKAGE_MIME_TYPE: &str = "application/x-sims4-package";
+
+const KEY_PREFIX: &str = "s4pi-reforged";
+
+/// The registry key (under HKEY_CURRENT_USER) and command line for each action on Windows.
+///
+/// CLI commands run in a console that stays open (`cmd /k`) so the output can be read.
+pub fn windows_registry_entries(exe: &Path) -> Vec<(ShellAction, String, String)> {
+    SHELL_ACTIONS.iter()
+        .map(|action| {
+            let parent = match action.target {
+                ShellTarget::Folder => r"Software\Classes\Directory\shell",
+                ShellTarget::PackageFile => r"Software\Classes\SystemFileAssociations\.package\shell",
+            };
+            let key = format!(r"{}\{}.{}", parent, KEY_PREFIX, action.id);
+            let command = if action.command.is_empty() {
+                format!(r#""{}" "%1""#, exe.display())
+            } else {
+                format!(r#"cmd.exe /k ""{}" {} "%1"""#, exe.display(), action.command)
+            };
+            (*action, key, command)
+        })
+        .collect()
+}
+
+/// The file name and contents of a .desktop entry for each action on Linux
+pub fn desktop_entries(exe: &Path) -> Vec<(String, String)> {
+    SHELL_ACTIONS.iter()
+        .map(|action| {
+            let mime = match action.target {
+                ShellTarget::Folder => "inode/directory",
+                ShellTarget::PackageFile => PACKAGE_MIME_TYPE,
+            };
+            let exec = match action.command {
+                "" => format!("\"{}\" %f", exe.display()),
+                command => format!("\"{}\" {} %f", exe.display(), command),
+            };
+            let contents = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nComment=s4pi-reforged {}\nExec={}\nTerminal={}\nNoDisplay=true\nMimeType={};\n",
+                action.label, action.id, exec, !action.command.is_empty(), mime,
+            );
+            (format!("{}-{}.desktop", KEY_PREFIX, action.id), contents)
+        })
+        .collect()
+}
+
+/// shared-mime-info definition that maps `*.package` to [`PACKAGE_MIME_TYPE`]
+pub fn mime_definition() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n  <mime-type type=\"{}\">\n    <comment>The Sims 4 package</comment>\n    <glob pattern=\"*.package\"/>\n  </mime-type>\n</mime-info>\n",
+        PACKAGE_MIME_TYPE,
+    )
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program).args(args).status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        return Err(Error::Other(format!("{} {} exited with {}", program, args.join(" "), status)));
+    }
+    Ok(())
+}
+
+fn xdg_data_home() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = std::env::var_os("HOME").context("HOME is not set")?;
+    Ok(PathBuf::from(home).join(".local/share"))
+}
+
+/// Registers the context-menu actions for the current user, pointing at `exe`.
+/// Returns a line describing each thing that was registered.
+pub fn install(exe: &Path) -> Result<Vec<String>> {
+    let mut installed = Vec::new();
+    if cfg!(windows) {
+        for (action, key, command) in windows_registry_entries(exe) {
+            let full_key = format!(r"HKCU\{}", key);
+            run("reg", &["add", &full_key, "/ve", "/d", action.label, "/f"])?;
+            run("reg", &["add", &full_key, "/v", "Icon", "/d", &exe.display().to_string(), "/f"])?;
+            run("reg", &["add", &format!(r"{}\command", full_key), "/ve", "/d", &command, "/f"])?;
+            installed.push(full_key);
+        }
+    } else if cfg!(target_os = "linux") {
+        let data = xdg_data_home()?;
+        let applications = data.join("applications");
+        let mime_packages = data.join("mime/packages");
+        std::fs::create_dir_all(&applications)?;
+        std::fs::create_dir_all(&mime_packages)?;
+
+        let mime_path = mime_packages.join(format!("{}.xml", KEY_PREFIX));
+        crate::atomic::write(&mime_path, mime_definition())?;
+        installed.push(mime_path.display().to_string());
+        for (name, contents) in desktop_entries(exe) {
+            let path = applications.join(name);
+            crate::atomic::write(&path, contents)?;
+            installed.push(path.display().to_string());
+        }
+
+        // Refreshing the caches is best-effort; desktops pick the files up on next login anyway
+        let _ = run("update-mime-database", &[&data.join("mime").display().to_string()]);
+        let _ = run("update-desktop-database", &[&applications.display().to_string()]);
+    } else {
+        return Err(Error::Unsupported("Shell integration is only available on Windows and Linux".to_string()));
+    }
+    Ok(installed)
+}
+
+/// Removes everything [`install`] registered. Returns a line for each thing removed.
+pub fn uninstall() -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    if cfg!(windows) {
+        for (_, key, _) in windows_registry_entries(Path::new("")) {
+            let full_key = format!(r"HKCU\{}", key);
+            if run("reg", &["delete", &full_key, "/f"]).is_ok() {
+                removed.push(full_key);
+            }
+        }
+    } else if cfg!(target_os = "linux") {
+        let data = xdg_data_home()?;
+        let mut paths = vec![data.join("mime/packages").join(format!("{}.xml", KEY_PREFIX))];
+        paths.extend(desktop_entries(Path::new("")).into_iter().map(|(name, _)| data.join("applications").join(name)));
+        for path in paths {
+            if std::fs::remove_file(&path).is_ok() {
+                removed.push(path.display().to_string());
+            }
+        }
+        let _ = run("update-mime-database", &[&data.join("mime").display().to_string()]);
+        let _ = run("update-desktop-database", &[&data.join("applications").display().to_string()]);
+    } else {
+        return Err(Error::Unsupported("Shell integration is only available on Windows and Linux".to_string()));
+    }
+    Ok(removed)
+}
@@ -0,0 +1,408 @@
+use std::fmt::Write;
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use crate::error::{Context, Error, Result};
+use crate::instance::fnv32;
+use crate::package::index::TGI;
+use crate::package::resource::SimDataResource;
+use crate::package::simdata::{SimDataColumn, SimDataObject, SimDataSchema, SimDataType, SimDataValue};
+
+/// What version 0x101 SimData keeps in its extra header word when the XML doesn't say
+const DEFAULT_HEADER_WORD: u32 = 0xFFFFFFFF;
+
+/// True for binary SimData, as opposed to its XML
+pub fn is_simdata(data: &[u8]) -> bool {
+    data.starts_with(b"DATA")
+}
+
+/// The name XML uses for schema `index`: its name, or its name hash in hex for unnamed ones
+fn schema_ref(schemas: &[SimDataSchema], index: usize) -> String {
+    let schema = &schemas[index];
+    schema.name.clone().unwrap_or_else(|| format!("0x{:08X}", schema.name_hash))
+}
+
+fn attribute(out: &mut String, key: &str, value: &str) {
+    let _ = write!(out, " {}=\"{}\"", key, escape(value));
+}
+
+fn indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+fn value_text(value: &SimDataValue) -> String {
+    let join = |floats: &[f32]| floats.iter().map(f32::to_string).collect::<Vec<_>>().join(",");
+    match value {
+        SimDataValue::Character(v) => v.to_string(),
+        SimDataValue::String(Some(text)) | SimDataValue::HashedString(Some(text), _) => text.clone(),
+        SimDataValue::Float2(v) => join(v),
+        SimDataValue::Float3(v) => join(v),
+        SimDataValue::Float4(v) => join(v),
+        SimDataValue::Unknown(_, bytes) => bytes.iter().map(|b| format!("{:02X}", b)).collect(),
+        value => value.to_string(),
+    }
+}
+
+/// Writes one value as S4S does: `T` for plain values, `U` for objects, `L` for vectors and `V`
+/// for variants, or `tag` where given. `typed` adds the type of plain values, which only their
+/// column gives otherwise.
+fn write_value(out: &mut String, depth: usize, tag: Option<&str>, name: Option<&str>, value: &SimDataValue, typed: bool) {
+    indent(out, depth);
+    let tag = tag.unwrap_or(match value {
+        SimDataValue::Object(_) => "U",
+        SimDataValue::Vector(_) => "L",
+        SimDataValue::Variant { .. } => "V",
+        _ => "T",
+    });
+    out.push('<');
+    out.push_str(tag);
+    if let Some(name) = name {
+        attribute(out, "name", name);
+    }
+    if typed && tag == "T" {
+        attribute(out, "type", &value.data_type().to_string());
+    }
+    match value {
+        SimDataValue::Object(None) | SimDataValue::String(None) => attribute(out, "null", "true"),
+        SimDataValue::HashedString(text, hash) => {
+            attribute(out, "hash", &format!("0x{:08X}", hash));
+            if text.is_none() {
+                attribute(out, "null", "true");
+            }
+        }
+        SimDataValue::Object(Some(object)) => {
+            if let Some(schema) = &object.schema {
+                attribute(out, "schema", schema);
+            }
+        }
+        SimDataValue::Variant { type_hash, .. } => attribute(out, "variant", &format!("0x{:08X}", type_hash)),
+        _ => {}
+    }
+
+    let children: Vec<(Option<&str>, &SimDataValue, bool)> = match value {
+        SimDataValue::Object(Some(object)) => object.fields.iter().map(|(name, value)| (Some(name.as_str()), value, false)).collect(),
+        SimDataValue::Vector(elements) => elements.iter().map(|element| (None, element, true)).collect(),
+        SimDataValue::Variant { value: Some(value), .. } => vec![(None, value.as_ref(), true)],
+        _ => Vec::new(),
+    };
+    if tag == "T" && !matches!(value, SimDataValue::String(None) | SimDataValue::HashedString(None, _)) {
+        let _ = writeln!(out, ">{}</T>", escape(value_text(value)));
+    } else if children.is_empty() {
+        out.push_str(" />\n");
+    } else {
+        out.push_str(">\n");
+        for (name, value, typed) in children {
+            write_value(out, depth + 1, None, name, value, typed);
+        }
+        indent(out, depth);
+        let _ = writeln!(out, "</{}>", tag);
+    }
+}
+
+/// SimData as the XML Sims 4 Studio exports: its instances, field by field, then its schemas.
+/// [`from_xml`] reads it back.
+pub fn to_xml(simdata: &SimDataResource) -> Result<String> {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<SimData");
+    attribute(&mut out, "version", &format!("0x{:08X}", simdata.version));
+    if let Some(word) = simdata.header_word() {
+        attribute(&mut out, "u", &format!("0x{:08X}", word));
+    }
+    out.push_str(">\n  <Instances>\n");
+    for (name, object) in simdata.instances()? {
+        write_value(&mut out, 2, Some("I"), Some(&name), &SimDataValue::Object(Some(object)), false);
+    }
+
+    out.push_str("  </Instances>\n  <Schemas>\n");
+    for schema in &simdata.schemas {
+        out.push_str("    <Schema");
+        if let Some(name) = &schema.name {
+            attribute(&mut out, "name", name);
+        }
+        if schema.name.as_deref().map(fnv32) != Some(schema.name_hash) {
+            attribute(&mut out, "name_hash", &format!("0x{:08X}", schema.name_hash));
+        }
+        attribute(&mut out, "schema_hash", &format!("0x{:08X}", schema.schema_hash));
+        out.push_str(">\n      <Columns>\n");
+        for column in &schema.columns {
+            out.push_str("        <Column");
+            if let Some(name) = &column.name {
+                attribute(&mut out, "name", name);
+            }
+            if column.name.as_deref().map(fnv32) != Some(column.name_hash) {
+                attribute(&mut out, "name_hash", &format!("0x{:08X}", column.name_hash));
+            }
+            attribute(&mut out, "type", &column.data_type.to_string());
+            attribute(&mut out, "flags", &format!("0x{:04X}", column.flags));
+            if let Some(index) = column.schema {
+                attribute(&mut out, "schema", &schema_ref(&simdata.schemas, index));
+            }
+            out.push_str(" />\n");
+        }
+        out.push_str("      </Columns>\n    </Schema>\n");
+    }
+    out.push_str("  </Schemas>\n</SimData>\n");
+    Ok(out)
+}
+
+/// An element of the XML, with its attributes and text unescaped
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+impl XmlElement {
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    fn required(&self, key: &str) -> Result<&str> {
+        self.attr(key).ok_or_else(|| Error::InvalidInput(format!("<{}> has no '{}' attribute", self.name, key)))
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn is_null(&self) -> bool {
+        self.attr("null").is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+}
+
+fn unescaped(raw: &str) -> Result<String> {
+    unescape(raw).map(|text| text.into_owned())
+        .map_err(|e| Error::InvalidInput(format!("Invalid XML escape in '{}': {}", raw, e)))
+}
+
+fn start_element(start: &quick_xml::events::BytesStart) -> Result<XmlElement> {
+    let mut attributes = Vec::new();
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| Error::InvalidInput(format!("Invalid XML attribute: {}", e)))?;
+        attributes.push((attr.key.0.to_string(), unescaped(&attr.value)?));
+    }
+    Ok(XmlElement { name: start.name().0.to_string(), attributes, ..Default::default() })
+}
+
+/// The document's root element
+fn parse_xml(xml: &str) -> Result<XmlElement> {
+    let mut reader = Reader::from_str(xml.trim_start_matches('\u{FEFF}'));
+    let mut stack: Vec<(XmlElement, String)> = Vec::new();
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| Error::InvalidInput(format!("XML parse error at byte {}: {}", reader.error_position(), e)))?;
+        match event {
+            Event::Start(start) => stack.push((start_element(&start)?, String::new())),
+            Event::Empty(start) => {
+                let element = start_element(&start)?;
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let (mut element, raw) = stack.pop().ok_or_else(|| Error::InvalidInput("Unexpected closing tag".to_string()))?;
+                element.text = unescaped(&raw)?;
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(text) => if let Some((_, raw)) = stack.last_mut() { raw.push_str(&text) },
+            Event::GeneralRef(r) => if let Some((_, raw)) = stack.last_mut() { let _ = write!(raw, "&{};", &*r); },
+            Event::CData(data) => if let Some((_, raw)) = stack.last_mut() { raw.push_str(&escape(&*data)) },
+            Event::Eof => return Err(Error::InvalidInput("The XML has no root element".to_string())),
+            _ => {}
+        }
+    }
+}
+
+/// A whole number in decimal or `0x` hex, with an optional minus sign
+fn parse_int<T: TryFrom<i128>>(text: &str) -> Result<T> {
+    let invalid = || Error::InvalidInput(format!("Invalid number '{}'", text));
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse(),
+    }.map_err(|_| invalid())?;
+    T::try_from(if negative { -magnitude } else { magnitude }).map_err(|_| invalid())
+}
+
+fn parse_floats<const N: usize>(text: &str) -> Result<[f32; N]> {
+    let floats = text.split(',')
+        .map(|f| f.trim().parse::<f32>().map_err(|_| Error::InvalidInput(format!("Invalid number '{}'", f))))
+        .collect::<Result<Vec<f32>>>()?;
+    floats.try_into().map_err(|_| Error::InvalidInput(format!("Expected {} comma separated numbers, got '{}'", N, text)))
+}
+
+fn plain_value(data_type: SimDataType, text: &str) -> Result<SimDataValue> {
+    let text = text.trim();
+    Ok(match data_type {
+        SimDataType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "true" | "1" => SimDataValue::Boolean(true),
+            "false" | "0" => SimDataValue::Boolean(false),
+            _ => return Err(Error::InvalidInput(format!("Invalid boolean '{}'", text))),
+        },
+        SimDataType::Character => SimDataValue::Character(parse_int(text)?),
+        SimDataType::Int8 => SimDataValue::Int8(parse_int(text)?),
+        SimDataType::UInt8 => SimDataValue::UInt8(parse_int(text)?),
+        SimDataType::Int16 => SimDataValue::Int16(parse_int(text)?),
+        SimDataType::UInt16 => SimDataValue::UInt16(parse_int(text)?),
+        SimDataType::Int32 => SimDataValue::Int32(parse_int(text)?),
+        SimDataType::UInt32 => SimDataValue::UInt32(parse_int(text)?),
+        SimDataType::Int64 => SimDataValue::Int64(parse_int(text)?),
+        SimDataType::UInt64 => SimDataValue::UInt64(parse_int(text)?),
+        SimDataType::Float => SimDataValue::Float(text.parse().map_err(|_| Error::InvalidInput(format!("Invalid number '{}'", text)))?),
+        SimDataType::Float2 => SimDataValue::Float2(parse_floats(text)?),
+        SimDataType::Float3 => SimDataValue::Float3(parse_floats(text)?),
+        SimDataType::Float4 => SimDataValue::Float4(parse_floats(text)?),
+        SimDataType::TableSetReference => SimDataValue::TableSetReference(parse_int(text)?),
+        SimDataType::ResourceKey => SimDataValue::ResourceKey(text.parse::<TGI>()?),
+        SimDataType::LocalizationKey => SimDataValue::LocalizationKey(parse_int(text)?),
+        SimDataType::Unknown(code) => SimDataValue::Unknown(code, Vec::new()),
+        other => return Err(Error::InvalidInput(format!("{} values can't be written as text", other))),
+    })
+}
+
+/// Reads the schemas, then the instances against them
+struct XmlReader {
+    schemas: Vec<SimDataSchema>,
+}
+
+impl XmlReader {
+    fn schema(&self, reference: &str) -> Result<usize> {
+        let hash = reference.strip_prefix("0x").and_then(|hex| u32::from_str_radix(hex, 16).ok());
+        self.schemas.iter()
+            .position(|s| s.name.as_deref() == Some(reference) || (s.name.is_none() && Some(s.name_hash) == hash))
+            .ok_or_else(|| Error::InvalidInput(format!("The XML has no schema '{}'", reference)))
+    }
+
+    fn object(&self, element: &XmlElement, schema: usize) -> Result<SimDataObject> {
+        let schema = &self.schemas[schema];
+        let field_name = |column: &SimDataColumn| column.name.clone().unwrap_or_else(|| format!("{:08X}", column.name_hash));
+        if let Some(unknown) = element.children.iter().map(|c| c.attr("name").unwrap_or_default())
+            .find(|name| !schema.columns.iter().any(|column| field_name(column) == *name))
+        {
+            return Err(Error::InvalidInput(format!("Schema {} has no field '{}'", schema.name.as_deref().unwrap_or("?"), unknown)));
+        }
+        let fields = schema.columns.iter()
+            .map(|column| {
+                let name = field_name(column);
+                let child = element.children.iter().find(|c| c.attr("name") == Some(name.as_str()))
+                    .ok_or_else(|| Error::InvalidInput(format!("Missing field '{}'", name)))?;
+                let value = self.value(child, column.data_type, column.schema).with_context(|| format!("In field '{}'", name))?;
+                Ok((name, value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(SimDataObject { schema: schema.name.clone(), fields })
+    }
+
+    /// A value that says what it is itself: a vector element or a variant's value
+    fn pointed(&self, element: &XmlElement) -> Result<SimDataValue> {
+        match element.name.as_str() {
+            "U" => self.value(element, SimDataType::Object, None),
+            "L" => self.value(element, SimDataType::Vector, None),
+            "V" => self.value(element, SimDataType::Variant, None),
+            _ => self.value(element, element.required("type")?.parse()?, None),
+        }
+    }
+
+    fn value(&self, element: &XmlElement, data_type: SimDataType, schema: Option<usize>) -> Result<SimDataValue> {
+        let null = element.is_null();
+        Ok(match data_type {
+            SimDataType::Object if element.name != "U" && schema.is_none() => self.pointed(element)?,
+            SimDataType::Object if null => SimDataValue::Object(None),
+            SimDataType::Object => {
+                let schema = match element.attr("schema") {
+                    Some(reference) => self.schema(reference)?,
+                    None => schema.ok_or_else(|| Error::InvalidInput(format!("<{}> has no 'schema' attribute", element.name)))?,
+                };
+                SimDataValue::Object(Some(self.object(element, schema)?))
+            }
+            SimDataType::Vector => SimDataValue::Vector(element.children.iter().map(|c| self.pointed(c)).collect::<Result<_>>()?),
+            SimDataType::Variant => SimDataValue::Variant {
+                type_hash: parse_int(element.required("variant")?)?,
+                value: match element.children.first() {
+                    Some(child) => Some(Box::new(self.pointed(child)?)),
+                    None => None,
+                },
+            },
+            SimDataType::String => SimDataValue::String((!null).then(|| element.text.clone())),
+            SimDataType::HashedString => SimDataValue::HashedString((!null).then(|| element.text.clone()), parse_int(element.required("hash")?)?),
+            plain => plain_value(plain, &element.text)?,
+        })
+    }
+}
+
+/// The name hash an XML schema or column has: the one it gives, or the hash of its name
+fn name_hash(element: &XmlElement) -> Result<(Option<String>, u32)> {
+    let name = element.attr("name").map(str::to_string);
+    let hash = match (element.attr("name_hash"), &name) {
+        (Some(hash), _) => parse_int(hash)?,
+        (None, Some(name)) => fnv32(name),
+        (None, None) => return Err(Error::InvalidInput(format!("<{}> has neither a name nor a name_hash", element.name))),
+    };
+    Ok((name, hash))
+}
+
+/// Reads SimData back from the XML [`to_xml`] writes, and Sims 4 Studio exports, and lays it out
+/// anew. Rows are laid out from the column types, each value on its natural boundary, so the
+/// bytes can differ from the original resource while the data is the same.
+pub fn from_xml(xml: &str) -> Result<SimDataResource> {
+    let root = parse_xml(xml)?;
+    if root.name != "SimData" {
+        return Err(Error::InvalidInput(format!("Expected a <SimData> document, got <{}>", root.name)));
+    }
+    let version = parse_int(root.attr("version").unwrap_or("0x101"))?;
+    let header_word = root.attr("u").map(parse_int).transpose()?.unwrap_or(DEFAULT_HEADER_WORD);
+
+    // Schemas are listed first so columns can refer to schemas that come after them
+    let schema_elements: Vec<&XmlElement> = root.child("Schemas").map(|s| s.children.iter().collect()).unwrap_or_default();
+    let mut reader = XmlReader { schemas: Vec::with_capacity(schema_elements.len()) };
+    for element in &schema_elements {
+        let (name, name_hash) = name_hash(element)?;
+        reader.schemas.push(SimDataSchema {
+            name,
+            name_hash,
+            schema_hash: parse_int(element.attr("schema_hash").unwrap_or("0"))?,
+            size: 0,
+            columns: Vec::new(),
+        });
+    }
+    for (index, element) in schema_elements.iter().enumerate() {
+        let mut columns = Vec::new();
+        let (mut offset, mut alignment) = (0usize, 1);
+        for column in element.child("Columns").map(|c| c.children.as_slice()).unwrap_or_default() {
+            let (name, name_hash) = name_hash(column)?;
+            let data_type: SimDataType = column.required("type")?.parse()?;
+            offset = offset.next_multiple_of(data_type.alignment());
+            alignment = alignment.max(data_type.alignment());
+            columns.push(SimDataColumn {
+                name,
+                name_hash,
+                data_type,
+                flags: parse_int(column.attr("flags").unwrap_or("0"))?,
+                offset: offset as u32,
+                schema: column.attr("schema").map(|reference| reader.schema(reference)).transpose()?,
+            });
+            offset += data_type.size();
+        }
+        reader.schemas[index].size = offset.next_multiple_of(alignment) as u32;
+        reader.schemas[index].columns = columns;
+    }
+
+    let mut instances = Vec::new();
+    for element in root.child("Instances").map(|i| i.children.as_slice()).unwrap_or_default() {
+        let name = element.required("name")?;
+        let schema = reader.schema(element.required("schema")?)?;
+        let object = reader.object(element, schema).with_context(|| format!("In instance '{}'", name))?;
+        instances.push((name.to_string(), object));
+    }
+    SimDataResource::build(version, header_word, &reader.schemas, &instances)
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+use crate::package::index::TGI;
+use crate::package::resource::{ResourceKind, TypedResource};
+
+/// The output a source package goes to when a merge is split by category.
+///
+/// Each source package is kept whole in one output, so everything a mod needs loads together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MergeCategory {
+    Cas,
+    BuildBuy,
+    Tuning,
+    Other,
+}
+
+impl MergeCategory {
+    pub const ALL: [MergeCategory; 4] = [MergeCategory::Cas, MergeCategory::BuildBuy, MergeCategory::Tuning, MergeCategory::Other];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MergeCategory::Cas => "CAS",
+            MergeCategory::BuildBuy => "BuildBuy",
+            MergeCategory::Tuning => "Tuning",
+            MergeCategory::Other => "Other",
+        }
+    }
+
+    /// File name of this category's output, e.g. `merged_CAS.package`
+    pub fn file_name(self) -> String {
+        format!("merged_{}.package", self.name())
+    }
+
+    /// Picks the category for a source package from the resources it contains.
+    ///
+    /// Packages with a CAS part are CAS; otherwise packages with an object definition or catalog
+    /// entry are Build/Buy. Packages made up only of tuning, SimData and strings are Tuning, and
+    /// everything else is Other.
+    pub fn classify<'a>(tgis: impl IntoIterator<Item = &'a TGI>) -> MergeCategory {
+        let kinds: Vec<ResourceKind> = tgis.into_iter().map(|tgi| TypedResource::kind_of(tgi.res_type)).collect();
+        if kinds.contains(&ResourceKind::CasPart) {
+            return MergeCategory::Cas;
+        }
+        let is_build_buy = |kind: &ResourceKind| matches!(
+            kind,
+            ResourceKind::ObjectDefinition | ResourceKind::Catalog | ResourceKind::Cwal | ResourceKind::Cfnd | ResourceKind::Cstr
+        );
+        if kinds.iter().any(is_build_buy) {
+            return MergeCategory::BuildBuy;
+        }
+        let is_tuning = |kind: &ResourceKind| matches!(
+            kind,
+            ResourceKind::Xml | ResourceKind::SimData | ResourceKind::Stbl | ResourceKind::Text | ResourceKind::NameMap | ResourceKind::Script
+        );
+        if !kinds.is_empty() && kinds.iter().all(is_tuning) {
+            return MergeCategory::Tuning;
+        }
+        MergeCategory::Other
+    }
+}
+
+/// True if `path` is named like one of the outputs of a split merge
+pub fn is_split_output(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    MergeCategory::ALL.iter().any(|c| name.eq_ignore_ascii_case(&c.file_name()))
+}
+
+/// Every package that makes up the same merge as `path`: the existing split outputs next to it
+/// if it is one of them, or just `path` for an ordinary merged package
+pub fn merge_parts(path: &Path) -> Vec<PathBuf> {
+    if !is_split_output(path) {
+        return vec![path.to_path_buf()];
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut parts: Vec<PathBuf> = MergeCategory::ALL.iter()
+        .map(|c| dir.join(c.file_name()))
+        .filter(|p| p.is_file())
+        .collect();
+    if !parts.iter().any(|p| p == path) {
+        parts.insert(0, path.to_path_buf());
+    }
+    parts
+}
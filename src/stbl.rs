@@ -0,0 +1,893 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::edit::parse_number;
+use crate::error::{Context, Error, Result};
+use log::warn;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::package::Package;
+use crate::package::index::TGI;
+use crate::package::resource::{Resource, ResourceKind, StblEntry, StblResource, TypedResource};
+use crate::tuning::looks_like_xml;
+
+/// STBL resource types (0x220557DA is what the game loads; 0x220557AA is seen in older tools)
+pub const STBL_TYPES: [u32; 2] = [0x220557DA, 0x220557AA];
+
+pub fn is_stbl_type(res_type: u32) -> bool {
+    STBL_TYPES.contains(&res_type)
+}
+
+/// Every string key defined by a string table in `pkg`, in any language. Tables that can't be
+/// read are skipped.
+pub fn string_keys(pkg: &Package) -> HashSet<u32> {
+    pkg.entries.iter()
+        .filter(|e| is_stbl_type(e.tgi.res_type))
+        .filter_map(|e| match pkg.read_resource(e) {
+            Ok(TypedResource::Stbl(stbl)) => Some(stbl.entries.into_iter().map(|entry| entry.key_hash)),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/// Options for pseudo-localization
+#[derive(Debug, Clone)]
+pub struct PseudoOptions {
+    /// Replace ASCII letters with accented look-alikes
+    pub accents: bool,
+    /// Extra length to add, as a percentage of the original string's length
+    pub padding_percent: usize,
+    /// Wrap each string in `[` `]` so truncation is obvious
+    pub brackets: bool,
+}
+
+impl Default for PseudoOptions {
+    fn default() -> Self {
+        Self { accents: true, padding_percent: 30, brackets: true }
+    }
+}
+
+fn accent(c: char) -> char {
+    match c {
+        'a' => 'á', 'b' => 'ƀ', 'c' => 'ç', 'd' => 'ð', 'e' => 'é', 'f' => 'ƒ', 'g' => 'ĝ',
+        'h' => 'ĥ', 'i' => 'í', 'j' => 'ĵ', 'k' => 'ķ', 'l' => 'ļ', 'm' => 'ɱ', 'n' => 'ñ',
+        'o' => 'ö', 'p' => 'þ', 'q' => 'ǫ', 'r' => 'ŕ', 's' => 'š', 't' => 'ţ', 'u' => 'û',
+        'v' => 'ṽ', 'w' => 'ŵ', 'x' => 'ẋ', 'y' => 'ý', 'z' => 'ž',
+        'A' => 'Å', 'B' => 'Ɓ', 'C' => 'Ç', 'D' => 'Ð', 'E' => 'É', 'F' => 'Ƒ', 'G' => 'Ĝ',
+        'H' => 'Ĥ', 'I' => 'Î', 'J' => 'Ĵ', 'K' => 'Ķ', 'L' => 'Ļ', 'M' => 'Ṁ', 'N' => 'Ñ',
+        'O' => 'Ö', 'P' => 'Þ', 'Q' => 'Ǫ', 'R' => 'Ŕ', 'S' => 'Š', 'T' => 'Ţ', 'U' => 'Û',
+        'V' => 'Ṽ', 'W' => 'Ŵ', 'X' => 'Ẋ', 'Y' => 'Ý', 'Z' => 'Ž',
+        _ => c,
+    }
+}
+
+/// Pseudo-localizes a single string.
+///
+/// Text inside `{...}` tokens (e.g. `{0.SimFirstName}`, `{M0.he}`) and `<...>` markup is left
+/// untouched so the game can still substitute it.
+pub fn pseudo_localize(s: &str, options: &PseudoOptions) -> String {
+    let mut out = String::with_capacity(s.len() * 2);
+    let mut depth_brace = 0usize;
+    let mut in_tag = false;
+    let mut visible = 0usize;
+
+    if options.brackets {
+        out.push('[');
+    }
+
+    for c in s.chars() {
+        match c {
+            '{' => depth_brace += 1,
+            '}' => depth_brace = depth_brace.saturating_sub(1),
+            '<' if depth_brace == 0 => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(c);
+                continue;
+            }
+            _ => {}
+        }
+
+        if depth_brace == 0 && !in_tag && c != '}' {
+            visible += 1;
+            out.push(if options.accents { accent(c) } else { c });
+        } else {
+            out.push(c);
+        }
+    }
+
+    let padding = (visible * options.padding_percent).div_ceil(100);
+    for i in 0..padding {
+        out.push(if i % 2 == 0 { '~' } else { '·' });
+    }
+
+    if options.brackets {
+        out.push(']');
+    }
+    out
+}
+
+/// Builds a pseudo-localized copy of a string table, keeping every key
+pub fn pseudo_localize_table(stbl: &StblResource, options: &PseudoOptions) -> StblResource {
+    let entries = stbl.entries.iter()
+        .map(|e| StblEntry {
+            key_hash: e.key_hash,
+            flags: e.flags,
+            string_value: pseudo_localize(&e.string_value, options),
+        })
+        .collect();
+
+    let mut table = StblResource {
+        version: stbl.version,
+        is_compressed: stbl.is_compressed,
+        reserved: stbl.reserved,
+        string_length: 0,
+        entries,
+    };
+    table.recalculate_string_length();
+    table
+}
+
+/// The languages the game ships string tables for, by the locale code each one's tables
+/// carry in the top byte of their instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Locale {
+    English = 0x00,
+    ChineseSimplified = 0x01,
+    ChineseTraditional = 0x02,
+    Czech = 0x03,
+    Danish = 0x04,
+    Dutch = 0x05,
+    Finnish = 0x06,
+    French = 0x07,
+    German = 0x08,
+    Italian = 0x0B,
+    Japanese = 0x0C,
+    Korean = 0x0D,
+    Norwegian = 0x0E,
+    Polish = 0x0F,
+    PortugueseBrazil = 0x11,
+    Russian = 0x12,
+    Spanish = 0x13,
+    SpanishLatinAmerica = 0x15,
+    Swedish = 0x16,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 19] = [
+        Locale::English, Locale::ChineseSimplified, Locale::ChineseTraditional, Locale::Czech,
+        Locale::Danish, Locale::Dutch, Locale::Finnish, Locale::French, Locale::German,
+        Locale::Italian, Locale::Japanese, Locale::Korean, Locale::Norwegian, Locale::Polish,
+        Locale::PortugueseBrazil, Locale::Russian, Locale::Spanish, Locale::SpanishLatinAmerica,
+        Locale::Swedish,
+    ];
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Locale> {
+        Self::ALL.into_iter().find(|l| l.code() == code)
+    }
+
+    /// The game's tag for the language, as in `Strings_ENG_US.package`
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::English => "ENG_US",
+            Locale::ChineseSimplified => "CHS_CN",
+            Locale::ChineseTraditional => "CHT_CN",
+            Locale::Czech => "CZE_CZ",
+            Locale::Danish => "DAN_DK",
+            Locale::Dutch => "DUT_NL",
+            Locale::Finnish => "FIN_FI",
+            Locale::French => "FRE_FR",
+            Locale::German => "GER_DE",
+            Locale::Italian => "ITA_IT",
+            Locale::Japanese => "JPN_JP",
+            Locale::Korean => "KOR_KR",
+            Locale::Norwegian => "NOR_NO",
+            Locale::Polish => "POL_PL",
+            Locale::PortugueseBrazil => "POR_BR",
+            Locale::Russian => "RUS_RU",
+            Locale::Spanish => "SPA_ES",
+            Locale::SpanishLatinAmerica => "SPA_EA",
+            Locale::Swedish => "SWE_SE",
+        }
+    }
+
+    /// The language code translation tools use for the language, as in a .po file's
+    /// `Language` header
+    pub fn language_code(self) -> &'static str {
+        match self {
+            Locale::English => "en_US",
+            Locale::ChineseSimplified => "zh_CN",
+            Locale::ChineseTraditional => "zh_TW",
+            Locale::Czech => "cs_CZ",
+            Locale::Danish => "da_DK",
+            Locale::Dutch => "nl_NL",
+            Locale::Finnish => "fi_FI",
+            Locale::French => "fr_FR",
+            Locale::German => "de_DE",
+            Locale::Italian => "it_IT",
+            Locale::Japanese => "ja_JP",
+            Locale::Korean => "ko_KR",
+            Locale::Norwegian => "nb_NO",
+            Locale::Polish => "pl_PL",
+            Locale::PortugueseBrazil => "pt_BR",
+            Locale::Russian => "ru_RU",
+            Locale::Spanish => "es_ES",
+            Locale::SpanishLatinAmerica => "es_MX",
+            Locale::Swedish => "sv_SE",
+        }
+    }
+
+    /// The locale for a translation tool's language code: an exact match (`pt_BR`, `fr-FR`), or
+    /// else the first locale in the same language (`fr`, `es_AR`)
+    pub fn from_language_code(code: &str) -> Option<Locale> {
+        let code = code.trim().replace('-', "_");
+        let language = match code.split('_').next().unwrap_or_default().to_ascii_lowercase().as_str() {
+            "no" | "nn" => "nb".to_string(),
+            language => language.to_string(),
+        };
+        Self::ALL.into_iter()
+            .find(|l| l.language_code().eq_ignore_ascii_case(&code))
+            .or_else(|| Self::ALL.into_iter().find(|l| l.language_code().split('_').next() == Some(language.as_str())))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::ChineseSimplified => "Chinese (Simplified)",
+            Locale::ChineseTraditional => "Chinese (Traditional)",
+            Locale::Czech => "Czech",
+            Locale::Danish => "Danish",
+            Locale::Dutch => "Dutch",
+            Locale::Finnish => "Finnish",
+            Locale::French => "French",
+            Locale::German => "German",
+            Locale::Italian => "Italian",
+            Locale::Japanese => "Japanese",
+            Locale::Korean => "Korean",
+            Locale::Norwegian => "Norwegian",
+            Locale::Polish => "Polish",
+            Locale::PortugueseBrazil => "Portuguese (Brazil)",
+            Locale::Russian => "Russian",
+            Locale::Spanish => "Spanish",
+            Locale::SpanishLatinAmerica => "Spanish (Latin America)",
+            Locale::Swedish => "Swedish",
+        }
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = crate::error::Error;
+
+    /// Accepts a tag (`GER_DE`), an English name (`german`) or a hex code (`0x08`)
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        let code = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        Self::ALL.into_iter()
+            .find(|l| Some(l.code()) == code || l.tag().eq_ignore_ascii_case(s) || l.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::InvalidInput(format!("Unknown locale '{}'", s)))
+    }
+}
+
+/// The raw locale code of a string table: the top byte of its instance
+pub fn locale_code(instance: u64) -> u8 {
+    (instance >> 56) as u8
+}
+
+/// The language of a string table from its instance, if the code is one the game uses
+pub fn locale_of(instance: u64) -> Option<Locale> {
+    Locale::from_code(locale_code(instance))
+}
+
+/// The instance of the same string table in another language.
+///
+/// Translations of a table share its type, group and the low 56 bits of its instance, so only
+/// the top byte changes.
+pub fn with_locale(instance: u64, locale: Locale) -> u64 {
+    (instance & 0x00FF_FFFF_FFFF_FFFF) | ((locale.code() as u64) << 56)
+}
+
+/// Removes the string tables of every language not in `keep` from the package at `path`,
+/// returning the tables removed. Tables whose locale code isn't one the game uses are kept. With
+/// `dry_run` the package is left alone and the tables that would go are returned.
+pub fn strip_locales<P: AsRef<Path>>(path: P, keep: &[Locale], dry_run: bool) -> Result<Vec<TGI>> {
+    let mut pkg = Package::open(path.as_ref())?;
+    let removed: Vec<TGI> = pkg.entries.iter()
+        .filter(|e| is_stbl_type(e.tgi.res_type) && locale_of(e.tgi.instance).is_some_and(|l| !keep.contains(&l)))
+        .map(|e| e.tgi)
+        .collect();
+    if !dry_run && !removed.is_empty() {
+        for tgi in &removed {
+            pkg.remove_resource(tgi);
+        }
+        pkg.save()?;
+    }
+    Ok(removed)
+}
+
+/// A string table as JSON, for translating or editing it outside the game: its key, version and
+/// language, and every string as its key (hex), flags and text. [`table_from_json`] reads it back.
+pub fn table_to_json(tgi: &TGI, stbl: &StblResource) -> String {
+    table_to_json_marked(tgi, stbl, &HashSet::new())
+}
+
+/// [`table_to_json`] with the strings whose keys are in `machine_translated` marked
+/// `"machine_translated": true`, so a reviewer can find them. [`table_from_json`] ignores the mark.
+pub fn table_to_json_marked(tgi: &TGI, stbl: &StblResource, machine_translated: &HashSet<u32>) -> String {
+    let strings: Vec<serde_json::Value> = stbl.entries.iter()
+        .map(|e| {
+            let mut string = serde_json::json!({ "key": format!("0x{:08X}", e.key_hash), "flags": e.flags, "text": e.string_value });
+            if machine_translated.contains(&e.key_hash) {
+                string["machine_translated"] = serde_json::Value::Bool(true);
+            }
+            string
+        })
+        .collect();
+    let value = serde_json::json!({
+        "tgi": tgi.to_string(),
+        "version": stbl.version,
+        "language": locale_of(tgi.instance).map(Locale::tag),
+        "strings": strings,
+    });
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+/// Reads a string table written by [`table_to_json`], with `string_length` recomputed for the
+/// edited text. `language` is only informational; the table's language is the one in its key.
+pub fn table_from_json(text: &str) -> Result<(TGI, StblResource)> {
+    let value: serde_json::Value = serde_json::from_str(text).context("String table is not valid JSON")?;
+    let tgi: TGI = value["tgi"].as_str()
+        .ok_or_else(|| Error::InvalidInput("String table has no 'tgi'".to_string()))?
+        .parse()?;
+    if !is_stbl_type(tgi.res_type) {
+        return Err(Error::InvalidInput(format!("{} is not a string table key", tgi)));
+    }
+    let version = match &value["version"] {
+        serde_json::Value::Null => 5,
+        version => version.as_u64().and_then(|v| u16::try_from(v).ok())
+            .ok_or_else(|| Error::InvalidInput(format!("Invalid string table version {}", version)))?,
+    };
+    let strings = value["strings"].as_array()
+        .ok_or_else(|| Error::InvalidInput("String table has no 'strings' list".to_string()))?;
+
+    let mut seen = HashSet::new();
+    let mut entries = Vec::with_capacity(strings.len());
+    for (i, string) in strings.iter().enumerate() {
+        let key = string["key"].as_str()
+            .ok_or_else(|| Error::InvalidInput(format!("String {} has no 'key'", i + 1)))?;
+        let key_hash = u32::try_from(parse_number(key)?)
+            .map_err(|_| Error::InvalidInput(format!("String key '{}' does not fit in 32 bits", key)))?;
+        if !seen.insert(key_hash) {
+            return Err(Error::InvalidInput(format!("String key {} is in the table twice", key)));
+        }
+        let flags = match &string["flags"] {
+            serde_json::Value::Null => 0,
+            flags => flags.as_u64().and_then(|f| u8::try_from(f).ok())
+                .ok_or_else(|| Error::InvalidInput(format!("String {} has invalid flags {}", key, flags)))?,
+        };
+        let text = string["text"].as_str()
+            .ok_or_else(|| Error::InvalidInput(format!("String {} has no 'text'", key)))?;
+        entries.push(StblEntry { key_hash, flags, string_value: text.to_string() });
+    }
+
+    let mut stbl = StblResource { version, is_compressed: 0, reserved: [0, 0], string_length: 0, entries };
+    stbl.recalculate_string_length();
+    Ok((tgi, stbl))
+}
+
+/// Escapes `text` for a quoted .po string
+fn po_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A `keyword "text"` line, or for text with line breaks, `keyword ""` followed by one quoted
+/// line per line of text as gettext tools write them
+fn po_field(keyword: &str, text: &str) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    if lines.len() < 2 {
+        return format!("{} \"{}\"\n", keyword, po_escape(text));
+    }
+    let mut out = format!("{} \"\"\n", keyword);
+    for line in lines {
+        out.push_str(&format!("\"{}\"\n", po_escape(line)));
+    }
+    out
+}
+
+/// A string table as a gettext catalogue, for translation tools like Poedit or Weblate. Each
+/// string's key is its `msgctxt` (`0x1A2B3C4D`), its text in `source` (normally the English
+/// table) is the `msgid`, and its text in `translation` the `msgstr`. Without a translation the
+/// result is a template (.pot) with every `msgstr` empty. Strings only the translation has
+/// come last, with their own text as the `msgid`.
+///
+/// The header records the table's TGI (`X-Stbl-TGI`) and, for a translation, its `Language`,
+/// so [`table_from_po`] knows which table to rebuild. Non-zero flags are kept in a `#. flags:`
+/// comment.
+pub fn table_to_po(tgi: &TGI, source: &StblResource, translation: Option<&StblResource>) -> String {
+    table_to_po_marked(tgi, source, translation, &HashSet::new())
+}
+
+/// [`table_to_po`] with the translations whose keys are in `fuzzy` flagged `#, fuzzy`, as
+/// translation tools do for text that still needs a person to check it
+pub fn table_to_po_marked(tgi: &TGI, source: &StblResource, translation: Option<&StblResource>, fuzzy: &HashSet<u32>) -> String {
+    let language = translation.and_then(|_| locale_of(tgi.instance)).map_or("", Locale::language_code);
+    let version = translation.unwrap_or(source).version;
+    let mut out = String::from("msgid \"\"\nmsgstr \"\"\n");
+    for header in [
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=UTF-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+        format!("Language: {}", language),
+        format!("X-Stbl-TGI: {}", tgi),
+        format!("X-Stbl-Version: {}", version),
+        format!("X-Generator: s4pi-reforged {}", env!("CARGO_PKG_VERSION")),
+    ] {
+        out.push_str(&format!("\"{}\\n\"\n", po_escape(&header)));
+    }
+
+    let translated: HashMap<u32, &StblEntry> = translation.iter()
+        .flat_map(|t| t.entries.iter().map(|e| (e.key_hash, e)))
+        .collect();
+    let source_keys: HashSet<u32> = source.entries.iter().map(|e| e.key_hash).collect();
+    let extra = translation.iter()
+        .flat_map(|t| t.entries.iter())
+        .filter(|e| !source_keys.contains(&e.key_hash));
+    for entry in source.entries.iter().chain(extra) {
+        let target = translated.get(&entry.key_hash);
+        out.push('\n');
+        let flags = target.map_or(entry.flags, |t| t.flags);
+        if flags != 0 {
+            out.push_str(&format!("#. flags: {}\n", flags));
+        }
+        if target.is_some() && fuzzy.contains(&entry.key_hash) {
+            out.push_str("#, fuzzy\n");
+        }
+        out.push_str(&po_field("msgctxt", &format!("0x{:08X}", entry.key_hash)));
+        out.push_str(&po_field("msgid", &entry.string_value));
+        out.push_str(&po_field("msgstr", target.map_or("", |t| t.string_value.as_str())));
+    }
+    out
+}
+
+/// Reads the text of a quoted .po string
+fn po_unquote(line: &str, line_number: usize) -> Result<String> {
+    let inner = line.strip_prefix('"').and_then(|l| l.strip_suffix('"'))
+        .ok_or_else(|| Error::InvalidInput(format!("Line {}: expected a quoted string", line_number)))?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('\\' | '"')) => out.push(c),
+            other => return Err(Error::InvalidInput(format!("Line {}: unknown escape \\{}", line_number, other.unwrap_or(' ')))),
+        }
+    }
+    Ok(out)
+}
+
+/// One message of a .po file
+#[derive(Default)]
+struct PoMessage {
+    context: Option<String>,
+    id: Option<String>,
+    translation: Option<String>,
+    fuzzy: bool,
+    flags: Option<u8>,
+}
+
+fn parse_po(text: &str) -> Result<Vec<PoMessage>> {
+    let mut messages = Vec::new();
+    let mut message = PoMessage::default();
+    // The field continuation lines add to
+    let mut field: Option<&str> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        // A comment after a msgstr starts the next message
+        let starts_message = line.starts_with('#') || line.starts_with("msgctxt") || line.starts_with("msgid ");
+        if starts_message && message.translation.is_some() {
+            messages.push(std::mem::take(&mut message));
+        }
+        if line.is_empty() || line.starts_with("#~") {
+            field = None;
+        } else if let Some(flags) = line.strip_prefix("#,") {
+            message.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+        } else if let Some(flags) = line.strip_prefix("#. flags:") {
+            message.flags = Some(flags.trim().parse()
+                .map_err(|_| Error::InvalidInput(format!("Line {}: invalid flags '{}'", line_number, flags.trim())))?);
+        } else if line.starts_with('#') {
+            field = None;
+        } else if line.starts_with("msgid_plural") || line.starts_with("msgstr[") {
+            return Err(Error::Unsupported(format!("Line {}: string tables have no plural forms", line_number)));
+        } else if let Some((keyword, rest)) = line.split_once(' ').filter(|(k, _)| ["msgctxt", "msgid", "msgstr"].contains(k)) {
+            let value = Some(po_unquote(rest.trim(), line_number)?);
+            match keyword {
+                "msgctxt" => message.context = value,
+                "msgid" => message.id = value,
+                _ => message.translation = value,
+            }
+            field = Some(keyword);
+        } else if line.starts_with('"') {
+            let target = match field {
+                Some("msgctxt") => &mut message.context,
+                Some("msgid") => &mut message.id,
+                Some(_) => &mut message.translation,
+                None => return Err(Error::InvalidInput(format!("Line {}: text outside a message", line_number))),
+            };
+            target.get_or_insert_with(String::new).push_str(&po_unquote(line, line_number)?);
+        } else {
+            return Err(Error::InvalidInput(format!("Line {}: unrecognised line '{}'", line_number, line)));
+        }
+    }
+    if message.id.is_some() {
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Rebuilds a string table from a catalogue written by [`table_to_po`] and then translated,
+/// with `string_length` recomputed for the translated text.
+///
+/// The table is the one in the `X-Stbl-TGI` header, moved to the `Language` header's locale
+/// when one is set, so a translation started from the English template gets the translated
+/// table's key. Strings with an empty or fuzzy `msgstr` keep their `msgid`, as gettext does, so
+/// a partial translation still gives a complete table.
+pub fn table_from_po(text: &str) -> Result<(TGI, StblResource)> {
+    table_from_po_untranslated(text).map(|(tgi, stbl, _)| (tgi, stbl))
+}
+
+/// [`table_from_po`], also returning the keys of the strings that kept their `msgid`
+pub(crate) fn table_from_po_untranslated(text: &str) -> Result<(TGI, StblResource, HashSet<u32>)> {
+    let messages = parse_po(text)?;
+    let mut headers = HashMap::new();
+    if let Some(header) = messages.iter().find(|m| m.context.is_none() && m.id.as_deref() == Some("")) {
+        for line in header.translation.as_deref().unwrap_or_default().lines() {
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+    }
+
+    let mut tgi: TGI = headers.get("x-stbl-tgi")
+        .ok_or_else(|| Error::InvalidInput("The catalogue has no X-Stbl-TGI header to say which string table it is".to_string()))?
+        .parse()?;
+    if !is_stbl_type(tgi.res_type) {
+        return Err(Error::InvalidInput(format!("{} is not a string table key", tgi)));
+    }
+    if let Some(language) = headers.get("language").filter(|l| !l.is_empty()) {
+        let locale = Locale::from_language_code(language)
+            .ok_or_else(|| Error::InvalidInput(format!("The game has no string tables for language '{}'", language)))?;
+        tgi.instance = with_locale(tgi.instance, locale);
+    }
+    let version = match headers.get("x-stbl-version") {
+        Some(version) => version.parse().map_err(|_| Error::InvalidInput(format!("Invalid string table version '{}'", version)))?,
+        None => 5,
+    };
+
+    let mut seen = HashSet::new();
+    let mut untranslated = HashSet::new();
+    let mut entries = Vec::new();
+    for message in messages.into_iter().filter(|m| m.context.is_some() || m.id.as_deref() != Some("")) {
+        let id = message.id.unwrap_or_default();
+        let key = message.context
+            .ok_or_else(|| Error::InvalidInput(format!("\"{}\" has no msgctxt with its string key", id)))?;
+        let key_hash = u32::try_from(parse_number(&key)?)
+            .map_err(|_| Error::InvalidInput(format!("String key '{}' does not fit in 32 bits", key)))?;
+        if !seen.insert(key_hash) {
+            return Err(Error::InvalidInput(format!("String key {} is in the catalogue twice", key)));
+        }
+        let text = match message.translation {
+            Some(translation) if !translation.is_empty() && !message.fuzzy => translation,
+            _ => {
+                untranslated.insert(key_hash);
+                id
+            }
+        };
+        entries.push(StblEntry { key_hash, flags: message.flags.unwrap_or(0), string_value: text });
+    }
+
+    let mut stbl = StblResource { version, is_compressed: 0, reserved: [0, 0], string_length: 0, entries };
+    stbl.recalculate_string_length();
+    Ok((tgi, stbl, untranslated))
+}
+
+/// One package's text for a key that's involved in a [`KeyCollision`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDefinition {
+    pub package: PathBuf,
+    pub table: TGI,
+    pub text: String,
+}
+
+/// A string key given different text by more than one package in the same locale.
+/// Whichever package loads last wins, so the others show the wrong text in game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCollision {
+    /// Raw locale code, see [`locale_of`]
+    pub locale: u8,
+    pub key_hash: u32,
+    pub definitions: Vec<KeyDefinition>,
+}
+
+/// Finds keys defined with different text by two or more packages.
+///
+/// Keys are only compared within a locale, so translations of the same key never collide, and
+/// packages that agree on the text (e.g. a mod and its own copy) aren't reported.
+/// Collisions come back sorted by locale and key.
+pub fn find_key_collisions(tables: &[(PathBuf, TGI, StblResource)]) -> Vec<KeyCollision> {
+    let mut by_key: BTreeMap<(u8, u32), Vec<KeyDefinition>> = BTreeMap::new();
+    for (package, tgi, stbl) in tables {
+        for entry in &stbl.entries {
+            by_key.entry((locale_code(tgi.instance), entry.key_hash)).or_default().push(KeyDefinition {
+                package: package.clone(),
+                table: *tgi,
+                text: entry.string_value.clone(),
+            });
+        }
+    }
+
+    by_key.into_iter()
+        .filter(|(_, definitions)| {
+            let first = &definitions[0];
+            definitions.iter().any(|d| d.package != first.package)
+                && definitions.iter().any(|d| d.text != first.text)
+        })
+        .map(|((locale, key_hash), definitions)| KeyCollision { locale, key_hash, definitions })
+        .collect()
+}
+
+/// String tables merged by [`merge_tables`]
+#[derive(Debug, Default)]
+pub struct StblMerge {
+    /// One table per locale, sorted by locale code
+    pub tables: Vec<(TGI, StblResource)>,
+    /// Keys given different text by more than one package
+    pub collisions: Vec<KeyCollision>,
+    /// Strings left out because a later table has the same key in the same locale, whether or
+    /// not the text differs
+    pub replaced: usize,
+}
+
+/// Merges string tables into one per locale, the way the game sees them: when more than one
+/// table has a key, the text of the last one (in the order given, which should be load order)
+/// wins, and strings keep the position their key first appeared at.
+///
+/// The merged tables take the type, group and instance of the first table, with each table's
+/// locale in the top byte of the instance. Keys given different text by different packages are
+/// reported as by [`find_key_collisions`].
+pub fn merge_tables(tables: &[(PathBuf, TGI, StblResource)]) -> StblMerge {
+    let Some((_, first, _)) = tables.first() else { return StblMerge::default() };
+    let mut by_locale: BTreeMap<u8, (StblResource, HashMap<u32, usize>)> = BTreeMap::new();
+    let mut replaced = 0;
+    for (_, tgi, stbl) in tables {
+        let (merged, positions) = by_locale.entry(locale_code(tgi.instance)).or_insert_with(|| {
+            (StblResource { version: stbl.version, is_compressed: 0, reserved: [0, 0], string_length: 0, entries: Vec::new() }, HashMap::new())
+        });
+        for entry in &stbl.entries {
+            let string = StblEntry { key_hash: entry.key_hash, flags: entry.flags, string_value: entry.string_value.clone() };
+            match positions.get(&entry.key_hash) {
+                Some(&i) => {
+                    merged.entries[i] = string;
+                    replaced += 1;
+                }
+                None => {
+                    positions.insert(entry.key_hash, merged.entries.len());
+                    merged.entries.push(string);
+                }
+            }
+        }
+    }
+
+    let merged = by_locale.into_iter()
+        .map(|(locale, (mut stbl, _))| {
+            stbl.recalculate_string_length();
+            let instance = (first.instance & 0x00FF_FFFF_FFFF_FFFF) | ((locale as u64) << 56);
+            (TGI { instance, ..*first }, stbl)
+        })
+        .collect();
+    StblMerge { tables: merged, collisions: find_key_collisions(tables), replaced }
+}
+
+/// Reads every string table from the packages under `folder` and runs [`find_key_collisions`].
+/// Packages and tables that can't be read are skipped with a warning.
+pub fn scan_key_collisions<P: AsRef<Path>>(folder: P) -> Vec<KeyCollision> {
+    let mut packages: Vec<PathBuf> = WalkDir::new(folder.as_ref())
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_path_buf())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+        .collect();
+    packages.sort();
+    find_key_collisions(&read_tables(&packages))
+}
+
+/// Reads every string table from `packages`, in the order given and then index order.
+/// Packages and tables that can't be read are skipped with a warning.
+pub fn read_tables(packages: &[PathBuf]) -> Vec<(PathBuf, TGI, StblResource)> {
+    packages
+        .par_iter()
+        .flat_map_iter(|path| {
+            let pkg = match Package::open(path) {
+                Ok(pkg) => pkg,
+                Err(e) => {
+                    warn!("Skipping {:?}: {}", path, e);
+                    return Vec::new();
+                }
+            };
+            let entries: Vec<_> = pkg.entries.iter().filter(|e| is_stbl_type(e.tgi.res_type)).cloned().collect();
+            entries.into_iter()
+                .filter_map(|entry| match pkg.read_resource(&entry) {
+                    Ok(TypedResource::Stbl(stbl)) => Some((path.clone(), entry.tgi, stbl)),
+                    Ok(_) => None,
+                    Err(e) => {
+                        warn!("Skipping string table {} in {:?}: {}", entry.tgi, path, e);
+                        None
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reads a string key mapping: one `OLD NEW` pair of hex keys per line (`OLD=NEW` and
+/// `OLD,NEW` also work; `0x` is optional). Blank lines and lines starting with `#` are skipped.
+pub fn parse_key_mapping(text: &str) -> Result<BTreeMap<u32, u32>> {
+    let mut mapping = BTreeMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '=' || c == ',').filter(|s| !s.is_empty()).collect();
+        let [old, new] = parts.as_slice() else {
+            return Err(Error::InvalidInput(format!("Line {}: expected two keys, found {:?}", n + 1, line)));
+        };
+        let parse = |s: &str| {
+            let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+            u32::from_str_radix(hex, 16).map_err(|_| Error::InvalidInput(format!("Line {}: {:?} is not a hex key", n + 1, s)))
+        };
+        let (old, new) = (parse(old)?, parse(new)?);
+        if mapping.insert(old, new).is_some() {
+            return Err(Error::InvalidInput(format!("Line {}: key 0x{:08X} is mapped more than once", n + 1, old)));
+        }
+    }
+    Ok(mapping)
+}
+
+/// The mapping that undoes `mapping`. Fails if two keys map to the same new key, since that
+/// can't be undone.
+pub fn reverse_key_mapping(mapping: &BTreeMap<u32, u32>) -> Result<BTreeMap<u32, u32>> {
+    let mut reverse = BTreeMap::new();
+    for (&old, &new) in mapping {
+        if let Some(other) = reverse.insert(new, old) {
+            return Err(Error::InvalidInput(format!("Keys 0x{:08X} and 0x{:08X} both map to 0x{:08X}", other, old, new)));
+        }
+    }
+    Ok(reverse)
+}
+
+/// Writes a mapping in the format [`parse_key_mapping`] reads
+pub fn format_key_mapping(mapping: &BTreeMap<u32, u32>) -> String {
+    mapping.iter().map(|(old, new)| format!("0x{:08X} 0x{:08X}\n", old, new)).collect()
+}
+
+/// Replaces every `0x`-prefixed 8 digit hex key in `xml` that `mapping` lists, which is how
+/// tuning refers to strings (e.g. `<T n="display_name">0x1A2B3C4D</T>`). Returns the new text
+/// and the number of references changed.
+pub fn rekey_xml(xml: &str, mapping: &BTreeMap<u32, u32>) -> (String, usize) {
+    let bytes = xml.as_bytes();
+    let mut out = String::with_capacity(xml.len());
+    let mut changed = 0;
+    let mut last = 0;
+    let mut i = 0;
+    while i + 10 <= bytes.len() {
+        let is_key = (bytes[i] == b'0' && (bytes[i + 1] == b'x' || bytes[i + 1] == b'X'))
+            && (i == 0 || !bytes[i - 1].is_ascii_alphanumeric())
+            && bytes[i + 2..i + 10].iter().all(u8::is_ascii_hexdigit)
+            && bytes.get(i + 10).is_none_or(|b| !b.is_ascii_alphanumeric());
+        if is_key {
+            let key = u32::from_str_radix(&xml[i + 2..i + 10], 16).unwrap_or_default();
+            if let Some(new) = mapping.get(&key) {
+                out.push_str(&xml[last..i]);
+                out.push_str(&format!("0x{:08X}", new));
+                last = i + 10;
+                changed += 1;
+            }
+            i += 10;
+        } else {
+            i += 1;
+        }
+    }
+    out.push_str(&xml[last..]);
+    (out, changed)
+}
+
+/// What [`rekey_package`] changed (or would change, for a dry run)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RekeyReport {
+    /// String table entries given a new key
+    pub strings: usize,
+    pub tables: usize,
+    /// Key references rewritten in tuning
+    pub tuning_refs: usize,
+    pub tuning_files: usize,
+    /// Tables where a new key is already used by a string that keeps its key; nothing is written
+    /// while there are any
+    pub conflicts: Vec<(TGI, u32)>,
+}
+
+/// Rewrites string keys in every string table of the package at `path`, and the references to
+/// them in its tuning, according to `mapping`. With `dry_run` the package is left alone and the
+/// report says what would change.
+pub fn rekey_package<P: AsRef<Path>>(path: P, mapping: &BTreeMap<u32, u32>, dry_run: bool) -> Result<RekeyReport> {
+    let path = path.as_ref();
+    let pkg = Package::open(path)?;
+    let mut report = RekeyReport::default();
+    let mut replacements: HashMap<TGI, Vec<u8>> = HashMap::new();
+
+    for entry in pkg.entries.clone() {
+        if is_stbl_type(entry.tgi.res_type) {
+            let TypedResource::Stbl(mut stbl) = pkg.read_resource(&entry)? else { continue };
+            let kept: HashSet<u32> = stbl.entries.iter().map(|e| e.key_hash).filter(|k| !mapping.contains_key(k)).collect();
+            let mut changed = 0;
+            for string in &mut stbl.entries {
+                if let Some(&new) = mapping.get(&string.key_hash) {
+                    if kept.contains(&new) {
+                        report.conflicts.push((entry.tgi, new));
+                    }
+                    string.key_hash = new;
+                    changed += 1;
+                }
+            }
+            if changed > 0 {
+                report.strings += changed;
+                report.tables += 1;
+                replacements.insert(entry.tgi, stbl.to_bytes()?);
+            }
+        } else if matches!(TypedResource::kind_of(entry.tgi.res_type), ResourceKind::Xml | ResourceKind::Text) {
+            let data = pkg.read_raw_resource(&entry)?;
+            if !looks_like_xml(&data) {
+                continue;
+            }
+            let Ok(xml) = std::str::from_utf8(&data) else { continue };
+            let (rekeyed, changed) = rekey_xml(xml, mapping);
+            if changed > 0 {
+                report.tuning_refs += changed;
+                report.tuning_files += 1;
+                replacements.insert(entry.tgi, rekeyed.into_bytes());
+            }
+        }
+    }
+    drop(pkg);
+
+    if !dry_run && report.conflicts.is_empty() && !replacements.is_empty() {
+        Package::replace_resources(path, &replacements)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,1087 @@
+use crate::error::{Error, Result};
+use crate::package::index::TGI;
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+use std::collections::{HashMap, HashSet};
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+use crate::package::{Package, index::IndexEntry, resource::{ResourceKind, TypedResource}};
+
+/// Resource types that hold texture data
+pub const DST_IMAGE: u32 = 0x00B2D882;
+pub const DDS_UNCOMPRESSED: u32 = 0xB6C8B6A0;
+pub const RLE2_IMAGE: u32 = 0x3453CF95;
+pub const RLES_IMAGE: u32 = 0xBA856C78;
+pub const LRLE_IMAGE: u32 = 0x2BC04EDF;
+
+pub const TEXTURE_TYPES: [u32; 5] = [DST_IMAGE, DDS_UNCOMPRESSED, RLE2_IMAGE, RLES_IMAGE, LRLE_IMAGE];
+
+pub fn is_texture_type(res_type: u32) -> bool {
+    TEXTURE_TYPES.contains(&res_type)
+}
+
+/// Short folder-friendly name for a texture resource type
+pub fn texture_type_name(res_type: u32) -> &'static str {
+    match res_type {
+        DST_IMAGE => "DST",
+        DDS_UNCOMPRESSED => "DDS",
+        RLE2_IMAGE => "RLE2",
+        RLES_IMAGE => "RLES",
+        LRLE_IMAGE => "LRLE",
+        _ => "Unknown",
+    }
+}
+
+pub const DDS_HEADER_SIZE: usize = 128;
+
+const FOURCC_DXT1: [u8; 4] = *b"DXT1";
+const FOURCC_DXT3: [u8; 4] = *b"DXT3";
+const FOURCC_DXT5: [u8; 4] = *b"DXT5";
+const FOURCC_DST1: [u8; 4] = *b"DST1";
+const FOURCC_DST3: [u8; 4] = *b"DST3";
+const FOURCC_DST5: [u8; 4] = *b"DST5";
+
+/// Container format of a texture resource, detected from its leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// Plain DDS
+    Dds,
+    /// DDS with shuffled DXT blocks (DST1/DST3/DST5)
+    Dst,
+    /// Run-length encoded DXT5
+    Rle2,
+    /// Run-length encoded DXT5 with a specular mask
+    Rles,
+    /// Run-length encoded RGBA used by CAS
+    Lrle,
+    Unknown,
+}
+
+pub fn detect_format(data: &[u8]) -> TextureFormat {
+    if data.len() < 8 {
+        return TextureFormat::Unknown;
+    }
+    match &data[0..4] {
+        b"DDS " => {
+            if data.len() >= DDS_HEADER_SIZE && data[84..87] == *b"DST" {
+                TextureFormat::Dst
+            } else {
+                TextureFormat::Dds
+            }
+        }
+        b"LRLE" => TextureFormat::Lrle,
+        _ => match &data[4..8] {
+            b"RLE2" => TextureFormat::Rle2,
+            b"RLES" => TextureFormat::Rles,
+            _ => TextureFormat::Unknown,
+        },
+    }
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| Error::InvalidResource(format!("Texture data truncated at offset {}", pos)))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| Error::InvalidResource(format!("Texture data truncated at offset {}", pos)))
+}
+
+fn slice(data: &[u8], pos: usize, len: usize) -> Result<&[u8]> {
+    data.get(pos..pos + len)
+        .ok_or_else(|| Error::InvalidResource(format!("Texture data truncated at offset {} (wanted {} bytes)", pos, len)))
+}
+
+/// The parts of a DDS header needed to decode it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdsInfo {
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    pub pixel_flags: u32,
+    pub fourcc: [u8; 4],
+    pub bit_count: u32,
+    pub masks: [u32; 4],
+}
+
+impl DdsInfo {
+    pub fn is_compressed(&self) -> bool {
+        self.pixel_flags & 0x04 != 0
+    }
+
+    /// Human readable pixel format, e.g. "DXT5" or "RGBA32"
+    pub fn format_name(&self) -> String {
+        if self.is_compressed() {
+            String::from_utf8_lossy(&self.fourcc).into_owned()
+        } else if self.masks[3] != 0 {
+            format!("RGBA{}", self.bit_count)
+        } else {
+            format!("RGB{}", self.bit_count)
+        }
+    }
+}
+
+pub fn parse_dds_header(data: &[u8]) -> Result<DdsInfo> {
+    if data.len() < DDS_HEADER_SIZE || &data[0..4] != b"DDS " {
+        return Err(Error::InvalidResource("Not a DDS file".to_string()));
+    }
+    Ok(DdsInfo {
+        height: read_u32(data, 12)?,
+        width: read_u32(data, 16)?,
+        mip_count: read_u32(data, 28)?.max(1),
+        pixel_flags: read_u32(data, 80)?,
+        fourcc: slice(data, 84, 4)?.try_into().unwrap(),
+        bit_count: read_u32(data, 88)?,
+        masks: [read_u32(data, 92)?, read_u32(data, 96)?, read_u32(data, 100)?, read_u32(data, 104)?],
+    })
+}
+
+/// Builds a 128-byte DDS header (including the "DDS " magic) for a block-compressed texture
+pub fn dds_header(width: u32, height: u32, mip_count: u32, fourcc: [u8; 4]) -> Vec<u8> {
+    let block_size = if fourcc == FOURCC_DXT1 { 8 } else { 16 };
+    let linear_size = width.div_ceil(4).max(1) * height.div_ceil(4).max(1) * block_size;
+
+    // DDSD_CAPS | HEIGHT | WIDTH | PIXELFORMAT | LINEARSIZE, plus MIPMAPCOUNT when there are mips
+    let mut flags: u32 = 0x1 | 0x2 | 0x4 | 0x1000 | 0x80000;
+    let mut caps: u32 = 0x1000; // DDSCAPS_TEXTURE
+    if mip_count > 1 {
+        flags |= 0x20000;
+        caps |= 0x8 | 0x400000; // COMPLEX | MIPMAP
+    }
+
+    let mut header = Vec::with_capacity(DDS_HEADER_SIZE);
+    header.extend_from_slice(b"DDS ");
+    for value in [124, flags, height, width, linear_size, 0, mip_count] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&[0u8; 44]);
+    header.extend_from_slice(&32u32.to_le_bytes()); // pixel format size
+    header.extend_from_slice(&0x4u32.to_le_bytes()); // DDPF_FOURCC
+    header.extend_from_slice(&fourcc);
+    header.extend_from_slice(&[0u8; 20]);
+    header.extend_from_slice(&caps.to_le_bytes());
+    header.extend_from_slice(&[0u8; 16]);
+    header
+}
+
+/// The parts of a DXT block as (offset in the block, length), in the order DST stores them. Each
+/// part is kept in its own run across the whole image, one after the other.
+type DstParts = &'static [(usize, usize)];
+
+/// How a DST texture stores its blocks: the DXT FourCC it's shuffled from, the DXT block size and
+/// the parts of each block
+fn dst_layout(fourcc: [u8; 4]) -> Result<([u8; 4], usize, DstParts)> {
+    match fourcc {
+        // Colour endpoints, colour indices
+        FOURCC_DST1 => Ok((FOURCC_DXT1, 8, &[(0, 4), (4, 4)])),
+        // Explicit alpha, colour endpoints, colour indices
+        FOURCC_DST3 => Ok((FOURCC_DXT3, 16, &[(0, 8), (8, 4), (12, 4)])),
+        // Alpha endpoints, colour endpoints, alpha indices, colour indices
+        FOURCC_DST5 => Ok((FOURCC_DXT5, 16, &[(0, 2), (8, 4), (2, 6), (12, 4)])),
+        other => Err(Error::InvalidResource(format!("Not a DST texture (FourCC {:?})", String::from_utf8_lossy(&other)))),
+    }
+}
+
+/// The DST FourCC for a DXT one
+fn dst_fourcc(fourcc: [u8; 4]) -> Option<[u8; 4]> {
+    match fourcc {
+        FOURCC_DXT1 => Some(FOURCC_DST1),
+        FOURCC_DXT3 => Some(FOURCC_DST3),
+        FOURCC_DXT5 => Some(FOURCC_DST5),
+        _ => None,
+    }
+}
+
+/// Appends DXT blocks `range` of a shuffled `body` holding `blocks` blocks in all
+fn unshuffle_blocks(body: &[u8], parts: &[(usize, usize)], blocks: usize, range: std::ops::Range<usize>, out: &mut Vec<u8>) {
+    let block_size = parts.iter().map(|(_, len)| len).sum::<usize>();
+    for block in range {
+        let mut run = 0;
+        let mut dxt = [0u8; 16];
+        for &(offset, len) in parts {
+            let start = run + block * len;
+            dxt[offset..offset + len].copy_from_slice(&body[start..start + len]);
+            run += blocks * len;
+        }
+        out.extend_from_slice(&dxt[..block_size]);
+    }
+}
+
+/// Reorders the block data of a DST1/DST3/DST5 texture back into a standard DXT1/DXT3/DXT5 DDS.
+///
+/// DST stores each part of every block (endpoints, indices, alpha) in its own run across the whole
+/// image; this interleaves them again. [`shuffle_dst`] reverses it.
+pub fn unshuffle_dst(data: &[u8]) -> Result<Vec<u8>> {
+    let info = parse_dds_header(data)?;
+    let (fourcc, block_size, parts) = dst_layout(info.fourcc)?;
+    let body = &data[DDS_HEADER_SIZE..];
+    let blocks = body.len() / block_size;
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..DDS_HEADER_SIZE]);
+    out[84..88].copy_from_slice(&fourcc);
+    unshuffle_blocks(body, parts, blocks, 0..blocks, &mut out);
+
+    // Anything after the last whole block is copied as-is
+    out.extend_from_slice(&body[blocks * block_size..]);
+    Ok(out)
+}
+
+/// Shuffles a DXT1/DXT3/DXT5 DDS into the matching DST texture, the way the game stores them, for
+/// importing an edited texture back into a DST resource. The reverse of [`unshuffle_dst`].
+pub fn shuffle_dst(data: &[u8]) -> Result<Vec<u8>> {
+    let info = parse_dds_header(data)?;
+    let dst = dst_fourcc(info.fourcc).filter(|_| info.is_compressed()).ok_or_else(|| Error::Unsupported(format!(
+        "Only DXT1, DXT3 and DXT5 textures can be stored as DST, not {}", info.format_name(),
+    )))?;
+    let (_, block_size, parts) = dst_layout(dst)?;
+    let body = &data[DDS_HEADER_SIZE..];
+    let blocks = body.len() / block_size;
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..DDS_HEADER_SIZE]);
+    out[84..88].copy_from_slice(&dst);
+    for &(offset, len) in parts {
+        for block in body[..blocks * block_size].chunks_exact(block_size) {
+            out.extend_from_slice(&block[offset..offset + len]);
+        }
+    }
+    out.extend_from_slice(&body[blocks * block_size..]);
+    Ok(out)
+}
+
+/// Header fields shared by RLE2 and RLES textures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RleInfo {
+    pub format: TextureFormat,
+    pub width: u16,
+    pub height: u16,
+    pub mip_count: u16,
+}
+
+pub fn parse_rle_header(data: &[u8]) -> Result<RleInfo> {
+    let format = detect_format(data);
+    if format != TextureFormat::Rle2 && format != TextureFormat::Rles {
+        return Err(Error::InvalidResource("Not an RLE texture".to_string()));
+    }
+    Ok(RleInfo {
+        format,
+        width: read_u16(data, 8)?,
+        height: read_u16(data, 10)?,
+        mip_count: read_u16(data, 12)?,
+    })
+}
+
+/// Reads the per-mip section offsets of an RLE2 or RLES texture, plus a final entry holding the
+/// end of each section so mip `i` spans `headers[i]..headers[i + 1]`
+fn rle2_mip_headers(data: &[u8], format: TextureFormat, mip_count: usize) -> Result<Vec<[usize; 5]>> {
+    // Each mip header is (command, offset2, offset3, offset0, offset1), and RLES adds offset4 for
+    // its specular section. The data for every mip is stored section by section, so the end of
+    // each section is the start of the next one.
+    let header_size = if format == TextureFormat::Rles { 24 } else { 20 };
+    let mut headers = Vec::with_capacity(mip_count + 1);
+    for i in 0..mip_count {
+        let base = 16 + i * header_size;
+        headers.push([
+            read_u32(data, base)? as usize,
+            read_u32(data, base + 4)? as usize,
+            read_u32(data, base + 8)? as usize,
+            read_u32(data, base + 12)? as usize,
+            read_u32(data, base + 16)? as usize,
+        ]);
+    }
+    let first = headers[0];
+    let end = if format == TextureFormat::Rles { read_u32(data, 16 + 20)? as usize } else { data.len() };
+    headers.push([first[1], first[2], first[3], first[4], end]);
+    Ok(headers)
+}
+
+/// Expands the DXT5 blocks of one RLE2 mip level onto `out`
+fn decode_rle2_mip(data: &[u8], headers: &[[usize; 5]], mip: usize, out: &mut Vec<u8>) -> Result<()> {
+    const TRANSPARENT_BLOCK: [u8; 16] = [0; 16];
+    const OPAQUE_ALPHA: [u8; 8] = [0x00, 0x05, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+    let [command_start, mut off2, mut off3, mut off0, mut off1] = headers[mip];
+    let command_end = headers[mip + 1][0];
+
+    let mut pos = command_start;
+    while pos < command_end {
+        let command = read_u16(data, pos)?;
+        pos += 2;
+        let op = command & 3;
+        let count = (command >> 2) as usize;
+        match op {
+            0 => {
+                for _ in 0..count {
+                    out.extend_from_slice(&TRANSPARENT_BLOCK);
+                }
+            }
+            1 => {
+                for _ in 0..count {
+                    out.extend_from_slice(slice(data, off0, 2)?);
+                    out.extend_from_slice(slice(data, off1, 6)?);
+                    out.extend_from_slice(slice(data, off2, 4)?);
+                    out.extend_from_slice(slice(data, off3, 4)?);
+                    off0 += 2;
+                    off1 += 6;
+                    off2 += 4;
+                    off3 += 4;
+                }
+            }
+            2 => {
+                for _ in 0..count {
+                    out.extend_from_slice(&OPAQUE_ALPHA);
+                    out.extend_from_slice(slice(data, off2, 4)?);
+                    out.extend_from_slice(slice(data, off3, 4)?);
+                    off2 += 4;
+                    off3 += 4;
+                }
+            }
+            _ => return Err(Error::InvalidResource(format!("Invalid RLE command {} at offset {}", op, pos - 2))),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes an RLE2 or RLES texture into a DXT5 DDS file. RLES textures store a specular mask in
+/// an extra section, which a DDS has no room for; the colour and alpha blocks decode as for RLE2.
+pub fn decode_rle2(data: &[u8]) -> Result<Vec<u8>> {
+    let info = parse_rle_header(data)?;
+    let mip_count = info.mip_count as usize;
+    if mip_count == 0 {
+        return Err(Error::InvalidResource("RLE texture has no mip levels".to_string()));
+    }
+    let headers = rle2_mip_headers(data, info.format, mip_count)?;
+
+    let mut out = dds_header(info.width as u32, info.height as u32, mip_count as u32, FOURCC_DXT5);
+    for mip in 0..mip_count {
+        decode_rle2_mip(data, &headers, mip, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Header of an LRLE texture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LrleInfo {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    pub mip_count: u32,
+}
+
+/// LRLE version whose pixels are indices into a palette stored after the mip offsets
+pub const LRLE_V2: u32 = u32::from_le_bytes(*b"V002");
+
+pub fn parse_lrle_header(data: &[u8]) -> Result<LrleInfo> {
+    if detect_format(data) != TextureFormat::Lrle {
+        return Err(Error::InvalidResource("Not an LRLE texture".to_string()));
+    }
+    Ok(LrleInfo {
+        version: read_u32(data, 4)?,
+        width: read_u16(data, 8)?,
+        height: read_u16(data, 10)?,
+        mip_count: read_u32(data, 12)?,
+    })
+}
+
+/// Where each LRLE mip's commands start and end, and the palette of a [`LRLE_V2`] texture
+struct LrleLayout {
+    mips: Vec<std::ops::Range<usize>>,
+    palette: Vec<[u8; 4]>,
+}
+
+fn lrle_layout(data: &[u8], info: &LrleInfo) -> Result<LrleLayout> {
+    let mip_count = info.mip_count as usize;
+    if mip_count == 0 || mip_count > 16 {
+        return Err(Error::InvalidResource(format!("Invalid LRLE mip count {}", mip_count)));
+    }
+    let offsets = (0..mip_count).map(|i| read_u32(data, 16 + i * 4).map(|o| o as usize)).collect::<Result<Vec<_>>>()?;
+
+    let mut palette = Vec::new();
+    if info.version == LRLE_V2 {
+        let base = 16 + mip_count * 4;
+        let count = read_u32(data, base)? as usize;
+        let colors = slice(data, base + 4, count.saturating_mul(4))?;
+        palette.extend(colors.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]));
+    }
+
+    let mut ranges = Vec::with_capacity(mip_count);
+    for (i, &start) in offsets.iter().enumerate() {
+        let end = offsets.get(i + 1).copied().unwrap_or(data.len());
+        if start > end || end > data.len() {
+            return Err(Error::InvalidResource(format!("Invalid LRLE offset for mip level {}", i)));
+        }
+        ranges.push(start..end);
+    }
+    Ok(LrleLayout { mips: ranges, palette })
+}
+
+/// Reads a command's pixel count: the top five bits of the command byte, followed (when bit 2 is
+/// set) by a little-endian base-128 number holding the higher bits
+fn lrle_count(data: &[u8], pos: &mut usize, command: u8) -> Result<usize> {
+    let mut count = (command >> 3) as usize;
+    if command & 4 != 0 {
+        let mut shift = 5;
+        loop {
+            let byte = *slice(data, *pos, 1)?.first().unwrap();
+            *pos += 1;
+            count |= ((byte & 0x7F) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift > 40 {
+                return Err(Error::InvalidResource(format!("LRLE count too long at offset {}", *pos)));
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Decodes one LRLE mip level to its pixels, 4 bytes each (B, G, R, A) in rows from the top.
+///
+/// The commands fill the image 4x4 block by 4x4 block, left to right and top to bottom, each block
+/// in rows. The low two bits of a command byte choose what follows it:
+/// 0 leaves `count` pixels transparent, 1 gives `count` pixels one by one, 2 gives one pixel to
+/// repeat `count` times, and 3 (version 1 only) gives `count` pixels one channel at a time, each
+/// channel as runs whose control byte holds a length and, in its low bit, whether it repeats the
+/// next byte or is followed by that many bytes. A pixel is 4 bytes in version 1 and a palette
+/// index in [`LRLE_V2`].
+fn decode_lrle_mip(data: &[u8], commands: std::ops::Range<usize>, palette: &[[u8; 4]], width: u32, height: u32) -> Result<Vec<u8>> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let total = blocks_x * 4 * height.div_ceil(4) * 4;
+    let mut pixels = vec![0u8; width * height * 4];
+    let mut put = |index: usize, pixel: [u8; 4]| {
+        let (block, within) = (index / 16, index % 16);
+        let x = (block % blocks_x) * 4 + within % 4;
+        let y = (block / blocks_x) * 4 + within / 4;
+        if x < width && y < height {
+            let dst = (y * width + x) * 4;
+            pixels[dst..dst + 4].copy_from_slice(&pixel);
+        }
+    };
+    let color = |data: &[u8], pos: &mut usize| -> Result<[u8; 4]> {
+        if palette.is_empty() {
+            let bytes = slice(data, *pos, 4)?;
+            *pos += 4;
+            Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+        } else {
+            let index = *slice(data, *pos, 1)?.first().unwrap() as usize;
+            *pos += 1;
+            palette.get(index).copied()
+                .ok_or_else(|| Error::InvalidResource(format!("LRLE palette index {} out of range", index)))
+        }
+    };
+
+    let data = &data[..commands.end];
+    let mut pos = commands.start;
+    let mut written = 0;
+    while written < total {
+        let command = *slice(data, pos, 1)?.first().unwrap();
+        pos += 1;
+        let count = lrle_count(data, &mut pos, command)?;
+        if written + count > total {
+            return Err(Error::InvalidResource(format!("LRLE command at offset {} runs past the image", pos)));
+        }
+        match command & 3 {
+            0 => {}
+            1 => {
+                for i in 0..count {
+                    put(written + i, color(data, &mut pos)?);
+                }
+            }
+            2 => {
+                let pixel = color(data, &mut pos)?;
+                for i in 0..count {
+                    put(written + i, pixel);
+                }
+            }
+            _ if palette.is_empty() => {
+                let mut run = vec![[0u8; 4]; count];
+                for channel in 0..4 {
+                    let mut filled = 0;
+                    while filled < count {
+                        let control = *slice(data, pos, 1)?.first().unwrap();
+                        pos += 1;
+                        let len = ((control >> 1) as usize).min(count - filled);
+                        if control & 1 != 0 {
+                            let value = *slice(data, pos, 1)?.first().unwrap();
+                            pos += 1;
+                            run[filled..filled + len].iter_mut().for_each(|p| p[channel] = value);
+                        } else {
+                            for (p, value) in run[filled..filled + len].iter_mut().zip(slice(data, pos, len)?) {
+                                p[channel] = *value;
+                            }
+                            pos += len;
+                        }
+                        if len == 0 {
+                            return Err(Error::InvalidResource(format!("Empty LRLE channel run at offset {}", pos)));
+                        }
+                        filled += len;
+                    }
+                }
+                for (i, pixel) in run.into_iter().enumerate() {
+                    put(written + i, pixel);
+                }
+            }
+            op => return Err(Error::InvalidResource(format!("Invalid LRLE command {} at offset {}", op, pos - 1))),
+        }
+        written += count;
+    }
+    Ok(pixels)
+}
+
+/// Builds a 128-byte DDS header for an uncompressed 32-bit texture stored B, G, R, A
+fn bgra_dds_header(width: u32, height: u32, mip_count: u32) -> Vec<u8> {
+    let mut header = dds_header(width, height, mip_count, [0; 4]);
+    let mut flags = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    flags = (flags & !0x80000) | 0x8; // PITCH instead of LINEARSIZE
+    header[8..12].copy_from_slice(&flags.to_le_bytes());
+    header[20..24].copy_from_slice(&(width * 4).to_le_bytes());
+    header[80..84].copy_from_slice(&(0x1u32 | 0x40).to_le_bytes()); // ALPHAPIXELS | RGB
+    for (i, value) in [32u32, 0x00FF0000, 0x0000FF00, 0x000000FF, 0xFF000000].into_iter().enumerate() {
+        header[88 + i * 4..92 + i * 4].copy_from_slice(&value.to_le_bytes());
+    }
+    header
+}
+
+/// Decodes an LRLE texture into an uncompressed 32-bit DDS file with every mip level
+pub fn decode_lrle(data: &[u8]) -> Result<Vec<u8>> {
+    let info = parse_lrle_header(data)?;
+    let layout = lrle_layout(data, &info)?;
+    let mut out = bgra_dds_header(info.width as u32, info.height as u32, info.mip_count);
+    for (level, commands) in layout.mips.into_iter().enumerate() {
+        let (width, height) = mip_dimensions(info.width as u32, info.height as u32, level as u32);
+        out.extend(decode_lrle_mip(data, commands, &layout.palette, width, height)?);
+    }
+    Ok(out)
+}
+
+/// Converts any supported texture to a standard DDS file
+pub fn to_dds(data: &[u8]) -> Result<Vec<u8>> {
+    match detect_format(data) {
+        TextureFormat::Dds => Ok(data.to_vec()),
+        TextureFormat::Dst => unshuffle_dst(data),
+        TextureFormat::Rle2 | TextureFormat::Rles => decode_rle2(data),
+        TextureFormat::Lrle => decode_lrle(data),
+        TextureFormat::Unknown => Err(Error::InvalidResource("Unrecognised texture format".to_string())),
+    }
+}
+
+/// Width and height of mip `level` of a `width` x `height` texture
+pub fn mip_dimensions(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level.min(31)).max(1), (height >> level.min(31)).max(1))
+}
+
+/// The largest mip level whose sides are no bigger than `max_size`, or the smallest level if
+/// none are
+pub fn preview_level(width: u32, height: u32, mip_count: u32, max_size: u32) -> u32 {
+    (0..mip_count.max(1))
+        .find(|&level| {
+            let (w, h) = mip_dimensions(width, height, level);
+            w.max(h) <= max_size
+        })
+        .unwrap_or(mip_count.max(1) - 1)
+}
+
+/// Bytes taken by the first `level` mips of a DDS body
+fn dds_level_offset(info: &DdsInfo, block_size: usize, level: u32) -> usize {
+    (0..level)
+        .map(|l| dds_level_size(info, block_size, l))
+        .sum()
+}
+
+fn dds_level_size(info: &DdsInfo, block_size: usize, level: u32) -> usize {
+    let (w, h) = mip_dimensions(info.width, info.height, level);
+    if info.is_compressed() {
+        w.div_ceil(4) as usize * h.div_ceil(4) as usize * block_size
+    } else {
+        w as usize * h as usize * (info.bit_count / 8) as usize
+    }
+}
+
+/// Copies `header` with its size and mip count changed to describe a single mip level
+fn single_level_header(header: &[u8], width: u32, height: u32, linear_size: usize) -> Vec<u8> {
+    let mut out = header[..DDS_HEADER_SIZE].to_vec();
+    out[12..16].copy_from_slice(&height.to_le_bytes());
+    out[16..20].copy_from_slice(&width.to_le_bytes());
+    out[20..24].copy_from_slice(&(linear_size as u32).to_le_bytes());
+    out[28..32].copy_from_slice(&1u32.to_le_bytes());
+    out
+}
+
+/// Pulls mip `level` out of any supported texture as a single-level DDS file.
+///
+/// Only the blocks of the requested level are read: DDS levels are sliced out, DST blocks are
+/// gathered from each shuffled section, and only the level's own RLE commands are run. This is
+/// much cheaper than [`to_dds`] when all that's wanted is a small preview of a large texture.
+pub fn extract_mip(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let info = texture_info(data)?;
+    if level >= info.mip_count.max(1) {
+        return Err(Error::InvalidResource(format!("Mip level {} out of range (texture has {} levels)", level, info.mip_count)));
+    }
+    let (width, height) = mip_dimensions(info.width, info.height, level);
+
+    match info.format {
+        TextureFormat::Dds => {
+            let dds = parse_dds_header(data)?;
+            let block_size = if dds.fourcc == FOURCC_DXT1 { 8 } else { 16 };
+            let body = &data[DDS_HEADER_SIZE..];
+            let size = dds_level_size(&dds, block_size, level);
+            let level_data = slice(body, dds_level_offset(&dds, block_size, level), size)?;
+            let mut out = single_level_header(data, width, height, size);
+            out.extend_from_slice(level_data);
+            Ok(out)
+        }
+        TextureFormat::Dst => {
+            let dds = parse_dds_header(data)?;
+            let (fourcc, block_size, parts) = dst_layout(dds.fourcc)?;
+            let body = &data[DDS_HEADER_SIZE..];
+            let blocks = body.len() / block_size;
+            let first = dds_level_offset(&dds, block_size, level) / block_size;
+            let count = dds_level_size(&dds, block_size, level) / block_size;
+            if first + count > blocks {
+                return Err(Error::InvalidResource(format!("DST texture truncated before mip level {}", level)));
+            }
+
+            let mut out = dds_header(width, height, 1, fourcc);
+            out.reserve(count * block_size);
+            unshuffle_blocks(body, parts, blocks, first..first + count, &mut out);
+            Ok(out)
+        }
+        TextureFormat::Rle2 | TextureFormat::Rles => {
+            let headers = rle2_mip_headers(data, info.format, info.mip_count as usize)?;
+            let mut out = dds_header(width, height, 1, FOURCC_DXT5);
+            decode_rle2_mip(data, &headers, level as usize, &mut out)?;
+            Ok(out)
+        }
+        TextureFormat::Lrle => {
+            let lrle = parse_lrle_header(data)?;
+            let mut layout = lrle_layout(data, &lrle)?;
+            let mut out = bgra_dds_header(width, height, 1);
+            out.extend(decode_lrle_mip(data, layout.mips.swap_remove(level as usize), &layout.palette, width, height)?);
+            Ok(out)
+        }
+        TextureFormat::Unknown => Err(Error::InvalidResource("Unrecognised texture format".to_string())),
+    }
+}
+
+/// A single-level DDS of the largest mip no bigger than `max_size` on either side, see
+/// [`extract_mip`]
+pub fn extract_preview(data: &[u8], max_size: u32) -> Result<Vec<u8>> {
+    let info = texture_info(data)?;
+    extract_mip(data, preview_level(info.width, info.height, info.mip_count, max_size))
+}
+
+/// Size and encoding of a texture, read from its header without decoding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    /// Pixel format inside the container, e.g. "DXT5" or "DST1"
+    pub pixel_format: String,
+}
+
+impl TextureInfo {
+    /// True for textures of 4096 pixels or more on a side, which cost a lot of VRAM in game
+    pub fn is_oversized(&self) -> bool {
+        self.width >= OVERSIZED_TEXTURE || self.height >= OVERSIZED_TEXTURE
+    }
+}
+
+pub const OVERSIZED_TEXTURE: u32 = 4096;
+
+pub fn texture_info(data: &[u8]) -> Result<TextureInfo> {
+    let format = detect_format(data);
+    match format {
+        TextureFormat::Dds | TextureFormat::Dst => {
+            let dds = parse_dds_header(data)?;
+            Ok(TextureInfo {
+                format,
+                width: dds.width,
+                height: dds.height,
+                mip_count: dds.mip_count,
+                pixel_format: dds.format_name(),
+            })
+        }
+        TextureFormat::Rle2 | TextureFormat::Rles => {
+            let rle = parse_rle_header(data)?;
+            Ok(TextureInfo {
+                format,
+                width: rle.width as u32,
+                height: rle.height as u32,
+                mip_count: rle.mip_count as u32,
+                pixel_format: String::from_utf8_lossy(&data[0..4]).into_owned(),
+            })
+        }
+        TextureFormat::Lrle => {
+            let lrle = parse_lrle_header(data)?;
+            Ok(TextureInfo {
+                format,
+                width: lrle.width as u32,
+                height: lrle.height as u32,
+                mip_count: lrle.mip_count,
+                pixel_format: String::from("RGBA"),
+            })
+        }
+        TextureFormat::Unknown => Err(Error::InvalidResource("Unrecognised texture format".to_string())),
+    }
+}
+
+/// The material channels a CAS part or object texture can fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TextureChannel {
+    Diffuse,
+    Shadow,
+    Specular,
+    Normal,
+    Emission,
+}
+
+/// A texture's channel and header info, as used by one owner
+#[derive(Debug, Clone)]
+pub struct ChannelTexture {
+    pub tgi: TGI,
+    pub channel: TextureChannel,
+    pub info: TextureInfo,
+}
+
+/// Which channels a CAS part or object provides textures for
+#[derive(Debug, Clone)]
+pub struct ChannelReport {
+    pub owner: TGI,
+    pub is_cas: bool,
+    pub textures: Vec<ChannelTexture>,
+    /// Expected channels with no texture
+    pub missing: Vec<TextureChannel>,
+}
+
+impl ChannelReport {
+    pub fn oversized(&self) -> impl Iterator<Item = &ChannelTexture> {
+        self.textures.iter().filter(|t| t.info.is_oversized())
+    }
+}
+
+/// Assigns channels to an owner's textures from their formats.
+///
+/// Each channel is stored in a distinctive format: CAS diffuse maps are LRLE (or RLE2 in older
+/// CC), specular maps RLES and normal maps DST, while the RLE2 maps after the diffuse are the shadow
+/// and then emission maps. Object materials use DST for both diffuse and specular.
+pub fn assign_channels(is_cas: bool, textures: &[(TGI, TextureInfo)]) -> Vec<ChannelTexture> {
+    let mut assigned = Vec::with_capacity(textures.len());
+    let mut has_diffuse = textures.iter().any(|(_, info)| is_cas && info.format == TextureFormat::Lrle);
+    let mut has_shadow = false;
+    let mut dst_seen = 0;
+
+    for (tgi, info) in textures {
+        let channel = match info.format {
+            TextureFormat::Lrle => TextureChannel::Diffuse,
+            TextureFormat::Rles => TextureChannel::Specular,
+            TextureFormat::Rle2 => {
+                if !has_diffuse {
+                    has_diffuse = true;
+                    TextureChannel::Diffuse
+                } else if !has_shadow || !is_cas {
+                    has_shadow = true;
+                    TextureChannel::Shadow
+                } else {
+                    TextureChannel::Emission
+                }
+            }
+            TextureFormat::Dst | TextureFormat::Dds if is_cas => TextureChannel::Normal,
+            TextureFormat::Dst | TextureFormat::Dds | TextureFormat::Unknown => {
+                dst_seen += 1;
+                if dst_seen == 1 && !has_diffuse {
+                    has_diffuse = true;
+                    TextureChannel::Diffuse
+                } else {
+                    TextureChannel::Specular
+                }
+            }
+        };
+        assigned.push(ChannelTexture { tgi: *tgi, channel, info: info.clone() });
+    }
+    assigned
+}
+
+/// Reports the textures of every CAS part and object in a package and the channels they cover
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+pub fn channel_report(pkg: &Package) -> Vec<ChannelReport> {
+    let owners = find_texture_owners(pkg);
+    let entries: HashMap<TGI, IndexEntry> = pkg.entries.iter().map(|e| (e.tgi, e.clone())).collect();
+
+    let mut by_owner: HashMap<TGI, Vec<TGI>> = HashMap::new();
+    for (texture, texture_owners) in &owners {
+        for owner in texture_owners {
+            by_owner.entry(*owner).or_default().push(*texture);
+        }
+    }
+
+    let mut infos: HashMap<TGI, TextureInfo> = HashMap::new();
+    let mut reports = Vec::new();
+    let mut owner_list: Vec<TGI> = by_owner.keys().copied().collect();
+    owner_list.sort_by_key(|t| (t.res_type, t.res_group, t.instance));
+
+    for owner in owner_list {
+        let mut textures = by_owner.remove(&owner).unwrap_or_default();
+        textures.sort_by_key(|t| (t.res_type, t.res_group, t.instance));
+        let mut with_info = Vec::new();
+        for tgi in textures {
+            if let std::collections::hash_map::Entry::Vacant(slot) = infos.entry(tgi) {
+                let Some(entry) = entries.get(&tgi) else { continue };
+                let Ok(info) = pkg.read_raw_resource(entry).and_then(|data| texture_info(&data)) else { continue };
+                slot.insert(info);
+            }
+            with_info.push((tgi, infos[&tgi].clone()));
+        }
+
+        let is_cas = TypedResource::kind_of(owner.res_type) == ResourceKind::CasPart;
+        let assigned = assign_channels(is_cas, &with_info);
+        let expected: &[TextureChannel] = if is_cas {
+            &[TextureChannel::Diffuse, TextureChannel::Shadow, TextureChannel::Specular]
+        } else {
+            &[TextureChannel::Diffuse]
+        };
+        let missing = expected.iter()
+            .filter(|c| !assigned.iter().any(|t| t.channel == **c))
+            .copied()
+            .collect();
+        reports.push(ChannelReport { owner, is_cas, textures: assigned, missing });
+    }
+    reports
+}
+
+/// An 8-bit RGBA image
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(out)
+    }
+}
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let r = ((c >> 11) & 0x1F) as u8;
+    let g = ((c >> 5) & 0x3F) as u8;
+    let b = (c & 0x1F) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Decodes the 16 RGBA pixels of a DXT color block
+fn decode_color_block(block: &[u8], dxt1: bool, out: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (p0, p1) = (rgb565(c0), rgb565(c1));
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [p0[0], p0[1], p0[2], 255];
+    palette[1] = [p1[0], p1[1], p1[2], 255];
+    if c0 > c1 || !dxt1 {
+        for ch in 0..3 {
+            palette[2][ch] = mix(p0[ch], p1[ch], 2, 1);
+            palette[3][ch] = mix(p0[ch], p1[ch], 1, 2);
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for ch in 0..3 {
+            palette[2][ch] = mix(p0[ch], p1[ch], 1, 1);
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, 0];
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in out.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+fn decode_dxt5_alpha(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    let (a0, a1) = (block[0] as u16, block[1] as u16);
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 2..8u16 {
+            palette[i as usize] = (((8 - i) * a0 + (i - 1) * a1) / 7) as u8;
+        }
+    } else {
+        for i in 2..6u16 {
+            palette[i as usize] = (((6 - i) * a0 + (i - 1) * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = 0u64;
+    for (i, b) in block[2..8].iter().enumerate() {
+        bits |= (*b as u64) << (8 * i);
+    }
+    for (i, pixel) in out.iter_mut().enumerate() {
+        pixel[3] = palette[((bits >> (3 * i)) & 7) as usize];
+    }
+}
+
+fn decode_dxt3_alpha(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let nibble = (block[i / 2] >> (4 * (i % 2))) & 0x0F;
+        pixel[3] = nibble * 17;
+    }
+}
+
+fn decode_uncompressed(info: &DdsInfo, body: &[u8]) -> Result<RgbaImage> {
+    let bytes_per_pixel = (info.bit_count / 8) as usize;
+    if !(2..=4).contains(&bytes_per_pixel) {
+        return Err(Error::Unsupported(format!("Unsupported DDS bit depth: {}", info.bit_count)));
+    }
+    let pixel_count = (info.width * info.height) as usize;
+    let source = slice(body, 0, pixel_count * bytes_per_pixel)?;
+
+    let channel = |value: u32, mask: u32| -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let shifted = (value & mask) >> mask.trailing_zeros();
+        let max = mask >> mask.trailing_zeros();
+        ((shifted * 255 + max / 2) / max) as u8
+    };
+
+    let mut pixels = Vec::with_capacity(pixel_count * 4);
+    for px in source.chunks_exact(bytes_per_pixel) {
+        let mut value = 0u32;
+        for (i, b) in px.iter().enumerate() {
+            value |= (*b as u32) << (8 * i);
+        }
+        let [r, g, b, a] = info.masks;
+        pixels.push(channel(value, r));
+        pixels.push(channel(value, g));
+        pixels.push(channel(value, b));
+        pixels.push(if a == 0 { 255 } else { channel(value, a) });
+    }
+    Ok(RgbaImage { width: info.width, height: info.height, pixels })
+}
+
+/// Decodes the top mip level of a DDS file (DXT1/3/5 or uncompressed) to RGBA
+pub fn decode_dds(data: &[u8]) -> Result<RgbaImage> {
+    let info = parse_dds_header(data)?;
+    let body = &data[DDS_HEADER_SIZE..];
+
+    if !info.is_compressed() {
+        return decode_uncompressed(&info, body);
+    }
+
+    let block_size = match info.fourcc {
+        FOURCC_DXT1 => 8,
+        FOURCC_DXT3 | FOURCC_DXT5 => 16,
+        other => return Err(Error::Unsupported(format!("Unsupported DDS FourCC: {}", String::from_utf8_lossy(&other)))),
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let blocks_x = width.div_ceil(4).max(1);
+    let blocks_y = height.div_ceil(4).max(1);
+    let mut pixels = vec![0u8; width * height * 4];
+    let mut block_pixels = [[0u8; 4]; 16];
+
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let block = slice(body, (by * blocks_x + bx) * block_size, block_size)?;
+            match info.fourcc {
+                FOURCC_DXT1 => decode_color_block(block, true, &mut block_pixels),
+                FOURCC_DXT3 => {
+                    decode_color_block(&block[8..], false, &mut block_pixels);
+                    decode_dxt3_alpha(block, &mut block_pixels);
+                }
+                _ => {
+                    decode_color_block(&block[8..], false, &mut block_pixels);
+                    decode_dxt5_alpha(block, &mut block_pixels);
+                }
+            }
+
+            for (i, pixel) in block_pixels.iter().enumerate() {
+                let x = bx * 4 + i % 4;
+                let y = by * 4 + i / 4;
+                if x < width && y < height {
+                    let dst = (y * width + x) * 4;
+                    pixels[dst..dst + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+    }
+
+    Ok(RgbaImage { width: info.width, height: info.height, pixels })
+}
+
+/// Maps texture TGIs in a package to the CASP and OBJD resources that use them.
+///
+/// CASPs list their textures directly. OBJDs are followed through their model and any RCOL
+/// (MODL/MLOD) resources it references, since materials live in the mesh.
+#[cfg(all(feature = "catalog", feature = "meshes"))]
+pub fn find_texture_owners(pkg: &Package) -> HashMap<TGI, Vec<TGI>> {
+    let present: HashSet<TGI> = pkg.entries.iter().map(|e| e.tgi).collect();
+    let entries: HashMap<TGI, IndexEntry> = pkg.entries.iter().map(|e| (e.tgi, e.clone())).collect();
+    let mut owners: HashMap<TGI, Vec<TGI>> = HashMap::new();
+
+    let owner_entries: Vec<IndexEntry> = pkg.entries.iter()
+        .filter(|e| matches!(TypedResource::kind_of(e.tgi.res_type), ResourceKind::CasPart | ResourceKind::ObjectDefinition))
+        .cloned()
+        .collect();
+
+    for owner in owner_entries {
+        let mut referenced: Vec<TGI> = Vec::new();
+        match pkg.read_resource(&owner) {
+            Ok(TypedResource::CasPart(casp)) => {
+                referenced = casp.tgi_list().unwrap_or_default();
+            }
+            Ok(TypedResource::ObjectDefinition(objd)) => {
+                // Model (0x8D20ACC6) -> MODL -> MLOD -> textures
+                let mut queue: Vec<(TGI, usize)> = objd.models().unwrap_or_default().iter().map(|t| (*t, 0)).collect();
+                let mut visited = HashSet::new();
+                while let Some((tgi, depth)) = queue.pop() {
+                    if !visited.insert(tgi) {
+                        continue;
+                    }
+                    referenced.push(tgi);
+                    if depth >= 3 {
+                        continue;
+                    }
+                    let Some(entry) = entries.get(&tgi) else { continue };
+                    if let Ok(TypedResource::Rcol(rcol)) = pkg.read_resource(entry) {
+                        for child in rcol.external_resources.iter().chain(rcol.chunks.iter().map(|c| &c.tgi)) {
+                            queue.push((*child, depth + 1));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for tgi in referenced {
+            if is_texture_type(tgi.res_type) && present.contains(&tgi) {
+                let list = owners.entry(tgi).or_default();
+                if !list.contains(&owner.tgi) {
+                    list.push(owner.tgi);
+                }
+            }
+        }
+    }
+
+    owners
+}
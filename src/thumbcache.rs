@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::error::Result;
+use log::warn;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::package::Package;
+use crate::package::index::{IndexEntry, TGI};
+use crate::package::resource::{ResourceKind, TypedResource};
+
+/// File name of the game's thumbnail store in the user's Sims 4 documents folder
+pub const THUMBCACHE_FILE_NAME: &str = "localthumbcache.package";
+
+/// The in-game renders the game caches for catalog items, keyed by the instance of the item
+#[derive(Debug)]
+pub struct ThumbCache {
+    pub path: PathBuf,
+    pub thumbnails: HashMap<u64, Vec<IndexEntry>>,
+}
+
+impl ThumbCache {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let pkg = Package::open(path.as_ref())?;
+        let mut thumbnails: HashMap<u64, Vec<IndexEntry>> = HashMap::new();
+        for entry in &pkg.entries {
+            if TypedResource::kind_of(entry.tgi.res_type) == ResourceKind::Thumbnail {
+                thumbnails.entry(entry.tgi.instance).or_default().push(entry.clone());
+            }
+        }
+        for entries in thumbnails.values_mut() {
+            entries.sort_by_key(|e| std::cmp::Reverse(e.memsize));
+        }
+        Ok(Self { path: path.as_ref().to_path_buf(), thumbnails })
+    }
+
+    /// Cached thumbnails for an item, largest first
+    pub fn get(&self, instance: u64) -> Option<&[IndexEntry]> {
+        self.thumbnails.get(&instance).map(|v| v.as_slice())
+    }
+
+    /// Reads the largest cached thumbnail for an item
+    pub fn read_best(&self, instance: u64) -> Result<Option<Vec<u8>>> {
+        let Some(entry) = self.get(instance).and_then(|entries| entries.first()) else {
+            return Ok(None);
+        };
+        let pkg = Package::open(&self.path)?;
+        Ok(Some(pkg.read_raw_resource(entry)?))
+    }
+}
+
+/// File extension for thumbnail data, based on its signature
+pub fn thumbnail_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        "jpg"
+    } else if data.starts_with(b"\x89PNG") {
+        "png"
+    } else {
+        "bin"
+    }
+}
+
+/// A CC item in the Mods folder that has a render in the thumbnail cache
+#[derive(Debug, Clone)]
+pub struct ThumbMatch {
+    pub package: PathBuf,
+    /// The CASP, OBJD or catalog resource the thumbnail belongs to
+    pub item: TGI,
+    /// True if the package also ships its own thumbnail for the item
+    pub has_own_thumbnail: bool,
+}
+
+fn is_catalog_item(res_type: u32) -> bool {
+    matches!(
+        TypedResource::kind_of(res_type),
+        ResourceKind::CasPart | ResourceKind::ObjectDefinition | ResourceKind::Catalog
+    )
+}
+
+/// Links cached thumbnails to the CC items in every package under `mods_dir`.
+/// Packages that fail to open are skipped.
+pub fn match_mods<P: AsRef<Path>>(cache: &ThumbCache, mods_dir: P) -> Vec<ThumbMatch> {
+    let mut packages: Vec<PathBuf> = WalkDir::new(mods_dir.as_ref())
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path().to_path_buf())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+        .collect();
+    packages.sort();
+
+    packages
+        .par_iter()
+        .flat_map_iter(|path| {
+            let pkg = match Package::open(path) {
+                Ok(pkg) => pkg,
+                Err(e) => {
+                    warn!("Skipping {:?}: {}", path, e);
+                    return Vec::new();
+                }
+            };
+            let own_thumbnails: Vec<u64> = pkg.entries.iter()
+                .filter(|e| TypedResource::kind_of(e.tgi.res_type) == ResourceKind::Thumbnail)
+                .map(|e| e.tgi.instance)
+                .collect();
+            pkg.entries.iter()
+                .filter(|e| is_catalog_item(e.tgi.res_type) && cache.thumbnails.contains_key(&e.tgi.instance))
+                .map(|e| ThumbMatch {
+                    package: path.clone(),
+                    item: e.tgi,
+                    has_own_thumbnail: own_thumbnails.contains(&e.tgi.instance),
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::future::Future;
+use crate::error::{Error, Result};
+use crate::package::resource::{StblEntry, StblResource};
+use crate::stbl::{table_from_json, table_from_po_untranslated, table_to_json_marked, table_to_po_marked, Locale};
+use crate::TGI;
+
+/// A machine-translation backend (DeepL, a local model, ...) supplied by the caller
+pub trait Translator {
+    /// Translates each of `texts` from `source` to `target`, returning one string per input in the same order
+    fn translate_batch(&self, texts: &[String], source: Locale, target: Locale) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    /// How many strings to send per `translate_batch` call
+    fn batch_size(&self) -> usize {
+        50
+    }
+}
+
+/// A string filled in by a [`Translator`], kept so it can be reviewed by a person later
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineTranslation {
+    pub key_hash: u32,
+    pub source_text: String,
+    pub text: String,
+}
+
+/// Fills the strings `target` is missing (absent or empty) by translating them from `source`.
+///
+/// Existing translations are never overwritten. Returns every string that was added so the
+/// caller can store it for review.
+pub async fn fill_missing<T: Translator>(
+    translator: &T,
+    source: &StblResource,
+    source_locale: Locale,
+    target: &mut StblResource,
+    target_locale: Locale,
+) -> Result<Vec<MachineTranslation>> {
+    let missing: Vec<&StblEntry> = source.entries.iter()
+        .filter(|e| !e.string_value.is_empty())
+        .filter(|e| !target.entries.iter().any(|t| t.key_hash == e.key_hash && !t.string_value.is_empty()))
+        .collect();
+
+    let mut added = Vec::with_capacity(missing.len());
+    for batch in missing.chunks(translator.batch_size().max(1)) {
+        let texts: Vec<String> = batch.iter().map(|e| e.string_value.clone()).collect();
+        let translated = translator.translate_batch(&texts, source_locale, target_locale).await?;
+        if translated.len() != batch.len() {
+            return Err(Error::Other(format!("Translator returned {} strings for a batch of {}", translated.len(), batch.len())));
+        }
+        for (entry, text) in batch.iter().zip(translated) {
+            added.push(MachineTranslation { key_hash: entry.key_hash, source_text: entry.string_value.clone(), text });
+        }
+    }
+
+    for translation in &added {
+        match target.entries.iter_mut().find(|e| e.key_hash == translation.key_hash) {
+            Some(entry) => entry.string_value = translation.text.clone(),
+            None => target.entries.push(StblEntry { key_hash: translation.key_hash, flags: 0, string_value: translation.text.clone() }),
+        }
+    }
+    target.recalculate_string_length();
+    Ok(added)
+}
+
+/// [`fill_missing`] for callers without an async runtime
+pub fn fill_missing_blocking<T: Translator>(
+    translator: &T,
+    source: &StblResource,
+    source_locale: Locale,
+    target: &mut StblResource,
+    target_locale: Locale,
+) -> Result<Vec<MachineTranslation>> {
+    pollster::block_on(fill_missing(translator, source, source_locale, target, target_locale))
+}
+
+/// Formats machine translations as CSV (`key,source,translation`) for review
+pub fn review_csv(translations: &[MachineTranslation]) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let mut csv = String::from("key,source,translation\n");
+    for t in translations {
+        csv.push_str(&format!("0x{:08X},{},{}\n", t.key_hash, quote(&t.source_text), quote(&t.text)));
+    }
+    csv
+}
+
+/// A translated string table read by [`import_prefilled`], with its missing strings filled in
+#[derive(Debug)]
+pub struct PrefilledTable {
+    pub tgi: TGI,
+    pub table: StblResource,
+    /// The file again, with the added strings marked for review
+    pub text: String,
+    /// The strings that were added, e.g. for [`review_csv`]
+    pub added: Vec<MachineTranslation>,
+}
+
+/// Reads a translated string table from the text of a `.json` file (see
+/// [`crate::stbl::table_from_json`]) or a `.po` file (see [`crate::stbl::table_from_po`]), and
+/// fills the strings it is missing from `source` with `translator`. In a .po file, that is the
+/// strings with an empty or fuzzy `msgstr`.
+///
+/// The returned table holds the machine translations, ready to import. So they aren't taken for
+/// a person's work, the returned text marks them: `"machine_translated": true` in JSON, and
+/// `#, fuzzy` in a .po file. Writing the text back over the file keeps the mark; note that a
+/// fuzzy string is read back with its source text until a translator clears the flag.
+pub async fn import_prefilled<T: Translator>(
+    translator: &T,
+    text: &str,
+    source: &StblResource,
+    source_locale: Locale,
+) -> Result<PrefilledTable> {
+    let is_json = text.trim_start().starts_with('{');
+    let (tgi, mut table, untranslated) = if is_json {
+        table_from_json(text).map(|(tgi, table)| (tgi, table, HashSet::new()))?
+    } else {
+        table_from_po_untranslated(text)?
+    };
+    let target_locale = crate::stbl::locale_of(tgi.instance)
+        .ok_or_else(|| Error::InvalidInput(format!("String table {} has no known language", tgi)))?;
+
+    // Untranslated .po strings hold their msgid; empty them so they count as missing, and put
+    // back whatever the translator has no source text for
+    let kept: Vec<(u32, String)> = table.entries.iter_mut()
+        .filter(|e| untranslated.contains(&e.key_hash))
+        .map(|e| (e.key_hash, std::mem::take(&mut e.string_value)))
+        .collect();
+    let added = fill_missing(translator, source, source_locale, &mut table, target_locale).await?;
+    for (key_hash, text) in kept {
+        if let Some(entry) = table.entries.iter_mut().find(|e| e.key_hash == key_hash && e.string_value.is_empty()) {
+            entry.string_value = text;
+        }
+    }
+    table.recalculate_string_length();
+
+    let marked: HashSet<u32> = added.iter().map(|t| t.key_hash).collect();
+    let text = if is_json {
+        table_to_json_marked(&tgi, &table, &marked)
+    } else {
+        table_to_po_marked(&tgi, source, Some(&table), &marked)
+    };
+    Ok(PrefilledTable { tgi, table, text, added })
+}
+
+/// [`import_prefilled`] for callers without an async runtime
+pub fn import_prefilled_blocking<T: Translator>(
+    translator: &T,
+    text: &str,
+    source: &StblResource,
+    source_locale: Locale,
+) -> Result<PrefilledTable> {
+    pollster::block_on(import_prefilled(translator, text, source, source_locale))
+}
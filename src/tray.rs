@@ -0,0 +1,422 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use crate::error::{Context, Error, Result};
+use log::warn;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::package::Package;
+use crate::package::index::TGI;
+
+/// Extensions of the files the game writes to the Tray folder
+pub const TRAY_EXTENSIONS: [&str; 8] = [
+    "trayitem", "householdbinary", "hhi", "sgi", "blueprint", "bpi", "room", "rmi",
+];
+
+pub fn is_tray_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| TRAY_EXTENSIONS.contains(&ext.as_str()) || ext == "sim")
+}
+
+/// A single protobuf field value, as stored on the wire
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireValue {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Fixed32(u32),
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or_else(|| Error::Other(format!("Truncated varint at offset {}", pos)))?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Other(format!("Varint too long at offset {}", pos)))
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let bytes = data.get(*pos..*pos + len)
+        .ok_or_else(|| Error::Other(format!("Field runs past end of data at offset {}", pos)))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Decodes one level of a protobuf message into `(field number, value)` pairs.
+///
+/// No schema is needed: nested messages come back as `Bytes` and can be decoded again.
+pub fn decode_message(data: &[u8]) -> Result<Vec<(u32, WireValue)>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let key = read_varint(data, &mut pos)?;
+        let field = (key >> 3) as u32;
+        if field == 0 {
+            return Err(Error::Other(format!("Invalid field number 0 at offset {}", pos)));
+        }
+        let value = match key & 7 {
+            0 => WireValue::Varint(read_varint(data, &mut pos)?),
+            1 => WireValue::Fixed64(u64::from_le_bytes(take(data, &mut pos, 8)?.try_into().unwrap())),
+            2 => {
+                let len = read_varint(data, &mut pos)? as usize;
+                WireValue::Bytes(take(data, &mut pos, len)?.to_vec())
+            }
+            5 => WireValue::Fixed32(u32::from_le_bytes(take(data, &mut pos, 4)?.try_into().unwrap())),
+            wire => return Err(Error::Other(format!("Unsupported wire type {} at offset {}", wire, pos))),
+        };
+        fields.push((field, value));
+    }
+    Ok(fields)
+}
+
+/// Decodes the protobuf body of a tray file. Files written by the game start with a short header
+/// before the body, so a few known offsets are tried.
+fn decode_tray(data: &[u8]) -> Result<Vec<(u32, WireValue)>> {
+    let mut last_error = Error::Other("Empty file".to_string());
+    for offset in [0usize, 8, 4] {
+        if offset >= data.len() {
+            continue;
+        }
+        match decode_message(&data[offset..]) {
+            Ok(fields) if !fields.is_empty() => return Ok(fields),
+            Ok(_) => {}
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error).context("Not a recognised tray/Sim file")
+}
+
+/// Field numbers of the tray messages read here. Unknown fields are skipped, so data added by
+/// later game versions doesn't stop a file from being read.
+mod field {
+    // The household body of a .householdbinary
+    pub const HOUSEHOLD_NAME: u32 = 2;
+    pub const HOUSEHOLD_DESCRIPTION: u32 = 3;
+    pub const HOUSEHOLD_SIMS: u32 = 5;
+
+    // A Sim, inside a household or on its own in a .sim export
+    pub const SIM_ID: u32 = 1;
+    pub const SIM_FIRST_NAME: u32 = 3;
+    pub const SIM_LAST_NAME: u32 = 4;
+    pub const SIM_AGE: u32 = 5;
+    pub const SIM_OUTFITS: u32 = 6;
+    pub const SIM_FACIAL_ATTRIBUTES: u32 = 7;
+    pub const SIM_GENETIC_DATA: u32 = 8;
+    pub const SIM_SKIN_TONE: u32 = 9;
+
+    // Outfit list, and each outfit in it
+    pub const OUTFIT_LIST_OUTFITS: u32 = 1;
+    pub const OUTFIT_CATEGORY: u32 = 2;
+    pub const OUTFIT_PARTS: u32 = 4;
+    pub const OUTFIT_BODY_TYPES: u32 = 5;
+    /// `ids` of an id list, `body_types` of a body type list
+    pub const LIST_VALUES: u32 = 1;
+
+    // Face and body customization: sculpts, and sliders as (modifier, amount)
+    pub const FACE_SCULPTS: u32 = 1;
+    pub const FACE_MODIFIERS: u32 = 2;
+    pub const BODY_MODIFIERS: u32 = 3;
+    pub const MODIFIER_KEY: u32 = 1;
+    pub const MODIFIER_AMOUNT: u32 = 2;
+
+    // Genetics: the parts and face a Sim passes on to its children
+    pub const GENETIC_PARTS: u32 = 1;
+    pub const GENETIC_FACE: u32 = 2;
+    pub const GENETIC_PHYSIQUE: u32 = 3;
+    pub const PART_ID: u32 = 1;
+    pub const PART_BODY_TYPE: u32 = 2;
+}
+
+fn strings(fields: &[(u32, WireValue)], number: u32) -> impl Iterator<Item = String> + '_ {
+    fields.iter().filter_map(move |(n, value)| match value {
+        WireValue::Bytes(bytes) if *n == number => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    })
+}
+
+fn messages(fields: &[(u32, WireValue)], number: u32) -> Result<Vec<Vec<(u32, WireValue)>>> {
+    fields.iter()
+        .filter_map(|(n, value)| match value {
+            WireValue::Bytes(bytes) if *n == number => Some(decode_message(bytes)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A repeated integer field, written one value per field or packed into one run of varints
+fn varints(fields: &[(u32, WireValue)], number: u32) -> Result<Vec<u64>> {
+    let mut values = Vec::new();
+    for (n, value) in fields {
+        match value {
+            WireValue::Varint(v) | WireValue::Fixed64(v) if *n == number => values.push(*v),
+            WireValue::Bytes(bytes) if *n == number => {
+                let mut pos = 0;
+                while pos < bytes.len() {
+                    values.push(read_varint(bytes, &mut pos)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(values)
+}
+
+fn varint(fields: &[(u32, WireValue)], number: u32) -> Result<Option<u64>> {
+    Ok(varints(fields, number)?.pop())
+}
+
+fn float(fields: &[(u32, WireValue)], number: u32) -> Option<f32> {
+    fields.iter().rev().find_map(|(n, value)| match value {
+        WireValue::Fixed32(bits) if *n == number => Some(f32::from_bits(*bits)),
+        _ => None,
+    })
+}
+
+/// A face or body slider: the sculpt/modifier instance and how far it is applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SliderValue {
+    pub instance: u64,
+    pub amount: f32,
+}
+
+/// Sculpts and sliders, as a Sim wears them or passes them on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaceData {
+    pub sculpts: Vec<u64>,
+    pub sliders: Vec<SliderValue>,
+}
+
+impl FaceData {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let fields = decode_message(data)?;
+        let mut sliders = Vec::new();
+        for modifier in messages(&fields, field::FACE_MODIFIERS)?.into_iter().chain(messages(&fields, field::BODY_MODIFIERS)?) {
+            if let Some(instance) = varint(&modifier, field::MODIFIER_KEY)? {
+                sliders.push(SliderValue { instance, amount: float(&modifier, field::MODIFIER_AMOUNT).unwrap_or(0.0) });
+            }
+        }
+        Ok(Self { sculpts: varints(&fields, field::FACE_SCULPTS)?, sliders })
+    }
+
+    fn references(&self) -> impl Iterator<Item = u64> + '_ {
+        self.sculpts.iter().copied().chain(self.sliders.iter().map(|s| s.instance))
+    }
+}
+
+/// One of a Sim's outfits: its category (everyday, formal, ...) and the CAS parts in it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outfit {
+    pub category: u32,
+    pub parts: Vec<u64>,
+    /// The body type of each part, in the same order
+    pub body_types: Vec<u32>,
+}
+
+/// A CAS part a Sim passes on to its children, and the body type it fills
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneticPart {
+    pub instance: u64,
+    pub body_type: u32,
+}
+
+/// What a Sim passes on to its children: parts, face and physique
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Genetics {
+    pub parts: Vec<GeneticPart>,
+    pub face: FaceData,
+    pub physique: Option<String>,
+}
+
+/// A Sim read from a tray household or a `.sim` export
+#[derive(Debug, Clone, Default)]
+pub struct SimFile {
+    pub sim_id: Option<u64>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub age: Option<u32>,
+    pub skin_tone: Option<u64>,
+    pub outfits: Vec<Outfit>,
+    pub face: FaceData,
+    pub genetics: Genetics,
+    /// Instances of every resource the Sim uses: outfit and genetic parts, sculpts, sliders and
+    /// skin tone. These are matched against installed CC to find requirements.
+    pub references: BTreeSet<u64>,
+}
+
+impl SimFile {
+    /// Parses a `.sim` export, or one Sim's message from a household
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::from_fields(&decode_tray(data)?)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read(path.as_ref())
+            .with_context(|| format!("Failed to read {:?}", path.as_ref()))?;
+        Self::parse(&data)
+    }
+
+    fn from_fields(fields: &[(u32, WireValue)]) -> Result<Self> {
+        let mut sim = SimFile {
+            sim_id: varint(fields, field::SIM_ID)?,
+            first_name: strings(fields, field::SIM_FIRST_NAME).next(),
+            last_name: strings(fields, field::SIM_LAST_NAME).next(),
+            age: varint(fields, field::SIM_AGE)?.map(|age| age as u32),
+            skin_tone: varint(fields, field::SIM_SKIN_TONE)?,
+            ..Default::default()
+        };
+        for list in messages(fields, field::SIM_OUTFITS)? {
+            for outfit in messages(&list, field::OUTFIT_LIST_OUTFITS)? {
+                let list_values = |number| -> Result<Vec<u64>> {
+                    Ok(messages(&outfit, number)?.iter().map(|l| varints(l, field::LIST_VALUES)).collect::<Result<Vec<_>>>()?.concat())
+                };
+                sim.outfits.push(Outfit {
+                    category: varint(&outfit, field::OUTFIT_CATEGORY)?.unwrap_or(0) as u32,
+                    parts: list_values(field::OUTFIT_PARTS)?,
+                    body_types: list_values(field::OUTFIT_BODY_TYPES)?.into_iter().map(|t| t as u32).collect(),
+                });
+            }
+        }
+        if let Some(WireValue::Bytes(bytes)) = fields.iter().find(|(n, _)| *n == field::SIM_FACIAL_ATTRIBUTES).map(|(_, v)| v) {
+            sim.face = FaceData::parse(bytes)?;
+        }
+        for genetics in messages(fields, field::SIM_GENETIC_DATA)? {
+            for part in messages(&genetics, field::GENETIC_PARTS)? {
+                if let Some(instance) = varint(&part, field::PART_ID)? {
+                    let body_type = varint(&part, field::PART_BODY_TYPE)?.unwrap_or(0) as u32;
+                    sim.genetics.parts.push(GeneticPart { instance, body_type });
+                }
+            }
+            if let Some(WireValue::Bytes(bytes)) = genetics.iter().find(|(n, _)| *n == field::GENETIC_FACE).map(|(_, v)| v) {
+                sim.genetics.face = FaceData::parse(bytes)?;
+            }
+            sim.genetics.physique = strings(&genetics, field::GENETIC_PHYSIQUE).next();
+        }
+
+        let references = sim.outfits.iter().flat_map(|o| o.parts.iter().copied())
+            .chain(sim.face.references())
+            .chain(sim.genetics.parts.iter().map(|p| p.instance))
+            .chain(sim.genetics.face.references())
+            .chain(sim.skin_tone)
+            .filter(|&instance| instance != 0)
+            .collect();
+        sim.references = references;
+        Ok(sim)
+    }
+
+    /// First and last name, as far as the Sim has them
+    pub fn name(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.first_name, &self.last_name].into_iter().flatten().map(String::as_str).collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+
+    /// Face and body sliders the Sim wears
+    pub fn sliders(&self) -> &[SliderValue] {
+        &self.face.sliders
+    }
+}
+
+/// A saved household split into its Sims, so required CC can be attributed to each one
+#[derive(Debug, Clone, Default)]
+pub struct Household {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub sims: Vec<SimFile>,
+}
+
+impl Household {
+    /// Parses a `.householdbinary` file. Each Sim is read from its own message, so its
+    /// references only include what that Sim uses.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let fields = decode_tray(data)?;
+        let sims = messages(&fields, field::HOUSEHOLD_SIMS)?.iter()
+            .map(|sim| SimFile::from_fields(sim))
+            .collect::<Result<_>>()?;
+        let name = strings(&fields, field::HOUSEHOLD_NAME).next();
+        let description = strings(&fields, field::HOUSEHOLD_DESCRIPTION).next();
+        Ok(Self { name, description, sims })
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read(path.as_ref())
+            .with_context(|| format!("Failed to read {:?}", path.as_ref()))?;
+        Self::parse(&data)
+    }
+
+    /// Every resource some Sim in the household uses
+    pub fn references(&self) -> BTreeSet<u64> {
+        self.sims.iter().flat_map(|sim| sim.references.iter().copied()).collect()
+    }
+}
+
+/// Index of resource instances provided by the packages in a Mods folder
+#[derive(Debug, Default)]
+pub struct CcIndex {
+    pub packages: Vec<PathBuf>,
+    /// Instance -> (TGI, index into `packages`) for every resource that could be referenced
+    pub instances: HashMap<u64, Vec<(TGI, usize)>>,
+}
+
+impl CcIndex {
+    /// Reads the index of every package under `mods_dir`. Packages that fail to open are skipped.
+    pub fn build<P: AsRef<Path>>(mods_dir: P) -> Result<Self> {
+        let mut packages: Vec<PathBuf> = WalkDir::new(mods_dir.as_ref())
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().to_path_buf())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "package"))
+            .collect();
+        packages.sort();
+
+        let indexes: Vec<Vec<TGI>> = packages
+            .par_iter()
+            .map(|path| match Package::open(path) {
+                Ok(pkg) => pkg.entries.iter().map(|e| e.tgi).collect(),
+                Err(e) => {
+                    warn!("Skipping {:?}: {}", path, e);
+                    Vec::new()
+                }
+            })
+            .collect();
+
+        let mut instances: HashMap<u64, Vec<(TGI, usize)>> = HashMap::new();
+        for (i, tgis) in indexes.into_iter().enumerate() {
+            for tgi in tgis {
+                instances.entry(tgi.instance).or_default().push((tgi, i));
+            }
+        }
+
+        Ok(Self { packages, instances })
+    }
+
+    /// The CC resources (and the packages holding them) that a Sim references
+    pub fn required_by(&self, sim: &SimFile) -> Vec<(TGI, &Path)> {
+        self.providing(&sim.references)
+    }
+
+    /// The CC resources (and the packages holding them) with any of `references` as instance
+    pub fn providing(&self, references: &BTreeSet<u64>) -> Vec<(TGI, &Path)> {
+        let mut found = Vec::new();
+        for instance in references {
+            for (tgi, package) in self.instances.get(instance).into_iter().flatten() {
+                found.push((*tgi, self.packages[*package].as_path()));
+            }
+        }
+        found
+    }
+
+    /// The packages a Sim needs, sorted and without duplicates
+    pub fn required_packages(&self, sim: &SimFile) -> Vec<&Path> {
+        self.packages_providing(&sim.references)
+    }
+
+    /// The packages holding any of `references`, sorted and without duplicates
+    pub fn packages_providing(&self, references: &BTreeSet<u64>) -> Vec<&Path> {
+        let packages: BTreeSet<&Path> = self.providing(references).into_iter().map(|(_, p)| p).collect();
+        packages.into_iter().collect()
+    }
+}
@@ -0,0 +1,300 @@
+use crate::error::{Error, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// Formatting options applied to tuning XML on extract
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XmlFormatOptions {
+    /// Indent nested elements, one element per line
+    pub pretty: bool,
+    /// Sort each element's attributes by name
+    pub sort_attributes: bool,
+    /// Drop whitespace-only text between elements
+    pub strip_whitespace: bool,
+}
+
+impl XmlFormatOptions {
+    /// All options on: stable output that diffs cleanly between mod versions
+    pub fn canonical() -> Self {
+        Self { pretty: true, sort_attributes: true, strip_whitespace: true }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        !self.pretty && !self.sort_attributes && !self.strip_whitespace
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    /// Raw (still escaped) character data, including entity references
+    Text(String),
+    CData(String),
+    Comment(String),
+    /// Declarations, processing instructions and doctypes, kept verbatim
+    Raw(String),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    /// True if the element holds character data beyond formatting whitespace
+    fn has_text(&self) -> bool {
+        self.children.iter().any(|c| match c {
+            Node::Text(t) => !t.trim().is_empty(),
+            Node::CData(_) => true,
+            _ => false,
+        })
+    }
+
+    fn only_whitespace(&self) -> bool {
+        self.children.iter().all(|c| matches!(c, Node::Text(t) if t.trim().is_empty()))
+    }
+}
+
+fn start_element(start: &quick_xml::events::BytesStart) -> Result<Element> {
+    let mut attributes = Vec::new();
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| Error::InvalidResource(format!("Invalid XML attribute: {}", e)))?;
+        attributes.push((attr.key.0.to_string(), attr.value.into_owned()));
+    }
+    Ok(Element {
+        name: start.name().0.to_string(),
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+fn push_text(children: &mut Vec<Node>, text: &str) {
+    if let Some(Node::Text(existing)) = children.last_mut() {
+        existing.push_str(text);
+    } else {
+        children.push(Node::Text(text.to_string()));
+    }
+}
+
+fn parse(xml: &str) -> Result<Vec<Node>> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root: Vec<Node> = Vec::new();
+
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| Error::InvalidResource(format!("XML parse error at byte {}: {}", reader.error_position(), e)))?;
+
+        let children = match stack.last_mut() {
+            Some(parent) => &mut parent.children,
+            None => &mut root,
+        };
+
+        match event {
+            Event::Start(start) => stack.push(start_element(&start)?),
+            Event::Empty(start) => children.push(Node::Element(start_element(&start)?)),
+            Event::End(_) => {
+                let element = stack.pop().ok_or_else(|| Error::InvalidResource("Unexpected closing tag".to_string()))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(Node::Element(element)),
+                    None => root.push(Node::Element(element)),
+                }
+            }
+            Event::Text(text) => push_text(children, &text),
+            Event::GeneralRef(r) => push_text(children, &format!("&{};", &*r)),
+            Event::CData(data) => children.push(Node::CData(data.to_string())),
+            Event::Comment(comment) => children.push(Node::Comment(comment.to_string())),
+            Event::Decl(decl) => children.push(Node::Raw(format!("<?{}?>", &*decl))),
+            Event::PI(pi) => children.push(Node::Raw(format!("<?{}?>", &*pi))),
+            Event::DocType(doctype) => children.push(Node::Raw(format!("<!DOCTYPE {}>", &*doctype))),
+            Event::Eof => break,
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return Err(Error::InvalidResource(format!("Unclosed element <{}>", open.name)));
+    }
+    Ok(root)
+}
+
+struct XmlWriter {
+    out: String,
+    options: XmlFormatOptions,
+}
+
+impl XmlWriter {
+    fn newline(&mut self, depth: usize) {
+        if self.options.pretty {
+            if !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            for _ in 0..depth {
+                self.out.push_str("  ");
+            }
+        }
+    }
+
+    fn write_nodes(&mut self, nodes: &[Node], depth: usize, inline: bool) {
+        for node in nodes {
+            if let Node::Text(text) = node {
+                if (self.options.strip_whitespace || (self.options.pretty && !inline))
+                    && text.trim().is_empty()
+                {
+                    continue;
+                }
+            }
+            if !inline {
+                self.newline(depth);
+            }
+            self.write_node(node, depth);
+        }
+    }
+
+    fn write_node(&mut self, node: &Node, depth: usize) {
+        match node {
+            Node::Element(element) => self.write_element(element, depth),
+            Node::Text(text) => self.out.push_str(text),
+            Node::CData(data) => {
+                self.out.push_str("<![CDATA[");
+                self.out.push_str(data);
+                self.out.push_str("]]>");
+            }
+            Node::Comment(comment) => {
+                self.out.push_str("<!--");
+                self.out.push_str(comment);
+                self.out.push_str("-->");
+            }
+            Node::Raw(raw) => self.out.push_str(raw),
+        }
+    }
+
+    fn write_element(&mut self, element: &Element, depth: usize) {
+        self.out.push('<');
+        self.out.push_str(&element.name);
+
+        let mut attributes: Vec<&(String, String)> = element.attributes.iter().collect();
+        if self.options.sort_attributes {
+            attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        for (key, value) in attributes {
+            let quote = if value.contains('"') { '\'' } else { '"' };
+            self.out.push(' ');
+            self.out.push_str(key);
+            self.out.push('=');
+            self.out.push(quote);
+            self.out.push_str(value);
+            self.out.push(quote);
+        }
+
+        let has_content = element.children.iter().any(|c| match c {
+            Node::Text(t) => !(self.options.strip_whitespace && t.trim().is_empty()),
+            _ => true,
+        });
+        if !has_content {
+            self.out.push_str(" />");
+            return;
+        }
+        self.out.push('>');
+
+        // Elements holding text are written on one line so the text itself is never altered
+        let inline = !self.options.pretty || element.has_text() || element.only_whitespace();
+        self.write_nodes(&element.children, depth + 1, inline);
+        if !inline {
+            self.newline(depth);
+        }
+
+        self.out.push_str("</");
+        self.out.push_str(&element.name);
+        self.out.push('>');
+    }
+}
+
+/// Reformats an XML document (typically tuning) according to `options`.
+///
+/// Character data is never unescaped or re-escaped, so the output is byte-identical to the
+/// input apart from the requested whitespace and attribute order changes.
+pub fn format_xml(xml: &str, options: &XmlFormatOptions) -> Result<String> {
+    if options.is_passthrough() {
+        return Ok(xml.to_string());
+    }
+
+    let nodes = parse(xml.trim_start_matches('\u{FEFF}'))?;
+    let mut writer = XmlWriter { out: String::with_capacity(xml.len()), options: *options };
+    writer.write_nodes(&nodes, 0, !options.pretty);
+    if options.pretty {
+        writer.out.push('\n');
+    }
+    Ok(writer.out)
+}
+
+/// Returns true if the data looks like an XML document (optionally preceded by a BOM/whitespace)
+pub fn looks_like_xml(data: &[u8]) -> bool {
+    let data = data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data);
+    data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+}
+
+/// Reads an attribute from the root element of a tuning file
+fn root_attribute(xml: &str, name: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) | Event::Empty(start) => {
+                return start.attributes()
+                    .filter_map(|a| a.ok())
+                    .find(|a| a.key.0 == name)
+                    .map(|a| a.value.into_owned());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Reads the `n="..."` name from the root element of a tuning file, if present
+pub fn tuning_name(xml: &str) -> Option<String> {
+    root_attribute(xml, "n")
+}
+
+/// Reads the `i="..."` instance type (e.g. "buff") from the root element of a tuning file
+pub fn tuning_instance_type(xml: &str) -> Option<String> {
+    root_attribute(xml, "i")
+}
+
+/// Resource type IDs of tuning instance types, with the name the game's instance manager uses
+pub const TUNING_TYPES: [(u32, &str); 28] = [
+    (0x0C772E27, "action"),
+    (0x28B64675, "aspiration"),
+    (0xE350DBD8, "aspiration_category"),
+    (0xC020FCAD, "aspiration_track"),
+    (0xDEBAFB73, "broadcaster"),
+    (0x6017E896, "buff"),
+    (0x73996BEB, "career"),
+    (0x2C01BC15, "career_level"),
+    (0x48C75CE3, "career_track"),
+    (0xEE17C6AD, "animation"),
+    (0xE882D22F, "interaction"),
+    (0xD8800D66, "lot_tuning"),
+    (0xBA7B60B8, "mood"),
+    (0xB61DE6B4, "object"),
+    (0x7147A350, "object_part"),
+    (0xAD6FDF1F, "posture"),
+    (0xEB97F823, "recipe"),
+    (0x6FA49828, "reward"),
+    (0x9CC21262, "service_npc"),
+    (0xFBC3AEEB, "situation"),
+    (0x9C07855F, "situation_job"),
+    (0x69A5DAA4, "slot_type"),
+    (0x7DF2169C, "snippet"),
+    (0x51077643, "static_commodity"),
+    (0x339BC5BD, "statistic"),
+    (0x4F739CEE, "test_based_score"),
+    (0xCB5FDDC7, "trait"),
+    (0x03B33DDF, "tuning"),
+];
+
+/// The tuning instance type stored under `res_type`, e.g. "buff" for 0x6017E896
+pub fn tuning_class(res_type: u32) -> Option<&'static str> {
+    TUNING_TYPES.iter().find(|(t, _)| *t == res_type).map(|(_, name)| *name)
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::error::{Context, Error, Result};
+use crate::package::Package;
+use crate::package::investigate::{InvestigationReport, TypeStatus};
+
+/// Name of the file holding imported type names, in [`registry_dir`]
+pub const REGISTRY_NAME: &str = "type-names.json";
+
+/// Bytes of a resource kept as its magic
+const MAGIC_BYTES: usize = 4;
+
+/// What is shared about one unknown resource type. Nothing identifies the package or the
+/// resources in it: no paths, names, groups or instances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTypeRecord {
+    pub res_type: u32,
+    /// True if there is a wrapper for the type but resources failed to parse
+    pub failed: bool,
+    pub count: usize,
+    /// Resource counts by size, each size rounded up to a power of two
+    pub sizes: BTreeMap<u32, usize>,
+    /// The first bytes of one resource, which often name the format
+    pub magic: Vec<u8>,
+}
+
+impl UnknownTypeRecord {
+    /// One JSON line: `type` as hex, `status`, `count`, `sizes` (keyed by the rounded-up size),
+    /// `magic` as hex and the tool version
+    pub fn to_json(&self) -> String {
+        let magic: String = self.magic.iter().map(|b| format!("{:02X}", b)).collect();
+        let sizes: serde_json::Map<String, serde_json::Value> = self.sizes.iter()
+            .map(|(size, count)| (size.to_string(), (*count).into()))
+            .collect();
+        serde_json::json!({
+            "type": format!("0x{:08X}", self.res_type),
+            "status": if self.failed { "failed" } else { "unknown" },
+            "count": self.count,
+            "sizes": sizes,
+            "magic": magic,
+            "tool_version": env!("CARGO_PKG_VERSION"),
+        }).to_string()
+    }
+}
+
+/// Records for the types [`Package::investigate`] found no wrapper for or couldn't parse
+pub fn unknown_type_records(pkg: &Package, report: &InvestigationReport) -> Vec<UnknownTypeRecord> {
+    report.types.iter()
+        .filter(|(_, summary)| summary.status != TypeStatus::Known)
+        .map(|(res_type, summary)| {
+            let mut sizes = BTreeMap::new();
+            for entry in pkg.entries.iter().filter(|e| e.tgi.res_type == *res_type) {
+                *sizes.entry(entry.memsize.max(1).next_power_of_two()).or_insert(0) += 1;
+            }
+            let magic = summary.sample.as_ref()
+                .map(|s| s.head[..s.head.len().min(MAGIC_BYTES)].to_vec())
+                .unwrap_or_default();
+            UnknownTypeRecord { res_type: *res_type, failed: summary.status == TypeStatus::Failed, count: summary.count, sizes, magic }
+        })
+        .collect()
+}
+
+/// Appends records to a JSON lines file, creating it if needed
+pub fn append_records<P: AsRef<Path>>(path: P, records: &[UnknownTypeRecord]) -> Result<()> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    for record in records {
+        writeln!(file, "{}", record.to_json())?;
+    }
+    Ok(())
+}
+
+/// Where imported type names are kept: `%APPDATA%\s4pi-reforged` on Windows and
+/// `~/.config/s4pi-reforged` (or under `XDG_CONFIG_HOME`) elsewhere
+pub fn registry_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(std::env::var_os("HOME")?).join(".config")
+    };
+    Some(base.join("s4pi-reforged"))
+}
+
+/// Names for resource types the wrappers don't cover, from community-curated mappings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeMap {
+    pub names: BTreeMap<u32, String>,
+}
+
+/// What [`TypeMap::import`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub added: usize,
+    /// Types that already had a different name
+    pub renamed: usize,
+    pub unchanged: usize,
+}
+
+fn parse_type_id(text: &str) -> Result<u32> {
+    let hex = text.trim().trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(hex, 16).map_err(|_| Error::InvalidInput(format!("{:?} is not a hex type ID", text)))
+}
+
+impl TypeMap {
+    /// Reads a mapping file: a JSON object of hex type IDs to names, e.g.
+    /// `{"0x12345678": "Fancy Format"}`
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(data).context("Type map is not valid JSON")?;
+        let object = value.as_object().ok_or_else(|| Error::InvalidInput("Type map must be a JSON object of type IDs to names".to_string()))?;
+        let mut names = BTreeMap::new();
+        for (key, name) in object {
+            let name = name.as_str().ok_or_else(|| Error::InvalidInput(format!("The name for {} is not a string", key)))?;
+            names.insert(parse_type_id(key)?, name.trim().to_string());
+        }
+        Ok(Self { names })
+    }
+
+    pub fn to_json(&self) -> String {
+        let object: serde_json::Map<String, serde_json::Value> = self.names.iter()
+            .map(|(res_type, name)| (format!("0x{:08X}", res_type), name.as_str().into()))
+            .collect();
+        serde_json::to_string_pretty(&serde_json::Value::Object(object)).unwrap_or_default()
+    }
+
+    /// Reads the map at `path`, or an empty one if there is no file yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(data) => Self::from_json(&data).with_context(|| format!("Failed to read {:?}", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        }
+        crate::atomic::write(path, self.to_json()).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// The map in [`registry_dir`], or an empty one if it can't be read
+    pub fn registry() -> Self {
+        registry_dir()
+            .and_then(|dir| Self::load(dir.join(REGISTRY_NAME)).ok())
+            .unwrap_or_default()
+    }
+
+    /// Adds the names in `other`; where both name a type, `other` wins
+    pub fn import(&mut self, other: &TypeMap) -> ImportReport {
+        let mut report = ImportReport::default();
+        for (res_type, name) in &other.names {
+            match self.names.insert(*res_type, name.clone()) {
+                None => report.added += 1,
+                Some(old) if old != *name => report.renamed += 1,
+                Some(_) => report.unchanged += 1,
+            }
+        }
+        report
+    }
+
+    pub fn name_of(&self, res_type: u32) -> Option<&str> {
+        self.names.get(&res_type).map(String::as_str)
+    }
+}
@@ -0,0 +1,43 @@
+use s4pi_reforged::archive::{is_archive, list_packages, open_package, open_packages};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::io::Write;
+
+#[test]
+fn test_open_packages_in_zip() {
+    let dir = std::env::temp_dir().join(format!("s4pi_archive_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let tgi = TGI { res_type: 0x545AC67A, res_group: 0, instance: 0x1234 };
+    let entry = IndexEntry { tgi, offset: 0, filesize: 0, memsize: 5, compression: 0, committed: 1 };
+    let package_path = dir.join("mod.package");
+    Package::write_stored(&package_path, &[(entry, b"hello".to_vec())]).unwrap();
+    let package_bytes = std::fs::read(&package_path).unwrap();
+
+    let zip_path = dir.join("download.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("Readme.txt", options).unwrap();
+    zip.write_all(b"Put the package in your Mods folder").unwrap();
+    zip.start_file("Mods/mod.package", options).unwrap();
+    zip.write_all(&package_bytes).unwrap();
+    zip.start_file("Mods/broken.package", options).unwrap();
+    zip.write_all(b"not a package").unwrap();
+    zip.finish().unwrap();
+
+    assert!(is_archive(&zip_path));
+    assert!(!is_archive(&package_path));
+    assert_eq!(list_packages(&zip_path).unwrap(), vec!["Mods/mod.package", "Mods/broken.package"]);
+
+    let pkg = open_package(&zip_path, "Mods/mod.package").unwrap();
+    let entry = pkg.find(&tgi).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), b"hello");
+    assert!(open_package(&zip_path, "Mods/missing.package").is_err());
+
+    let packages = open_packages(&zip_path).unwrap();
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].1.as_ref().unwrap().entries.len(), 1);
+    assert!(packages[1].1.is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,46 @@
+use rayon::prelude::*;
+use s4pi_reforged::atomic::{write, AtomicFile};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+use std::io::Write;
+
+fn listing(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_atomic_writes() {
+    let dir = std::env::temp_dir().join(format!("s4pi_atomic_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.txt");
+
+    write(&path, b"first").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"first");
+
+    // Abandoned halfway, the old contents stay and the temporary file goes
+    let mut file = AtomicFile::create(&path).unwrap();
+    file.write_all(b"half of the").unwrap();
+    assert_eq!(listing(&dir).len(), 2);
+    drop(file);
+    assert_eq!(std::fs::read(&path).unwrap(), b"first");
+    assert_eq!(listing(&dir), vec!["a.txt"]);
+
+    // Writers racing for the same package each write a whole one, and the last rename wins
+    let package = dir.join("merged.package");
+    (0..8u64).into_par_iter().for_each(|i| {
+        let data = vec![i as u8; 1000 + i as usize];
+        let entries = HashMap::from([(TGI { res_type: 0x0333406C, res_group: 0, instance: i }, (data, 1000 + i as u32, 0, 1))]);
+        Package::write_merged(&package, &entries, false).unwrap();
+    });
+    let pkg = Package::open(&package).unwrap();
+    assert_eq!(pkg.entries.len(), 1);
+    let entry = &pkg.entries[0];
+    assert_eq!(pkg.read_raw_resource(entry).unwrap(), vec![entry.tgi.instance as u8; entry.memsize as usize]);
+    assert_eq!(listing(&dir), vec!["a.txt", "merged.package"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
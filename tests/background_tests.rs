@@ -0,0 +1,35 @@
+use s4pi_reforged::background::{is_background_thread, run, BackgroundMode};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_background_mode_limits_reads() {
+    let dir = std::env::temp_dir().join(format!("s4pi_background_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("background.package");
+    let tgi = TGI { res_type: 0x545AC67A, res_group: 0, instance: 1 };
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+    let mut resources = HashMap::new();
+    resources.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    Package::write_merged(&path, &resources, false).unwrap();
+
+    // Reads outside background work aren't held back
+    assert!(!is_background_thread());
+    let mode = BackgroundMode { threads: 2, read_rate: 500_000 };
+    let started = Instant::now();
+    let (threads, in_background) = run(&mode, || {
+        let pkg = Package::open(&path).unwrap();
+        let entry = pkg.find(&tgi).cloned().unwrap();
+        for _ in 0..3 {
+            assert_eq!(pkg.read_raw_resource(&entry).unwrap(), data);
+        }
+        (rayon::current_num_threads(), is_background_thread())
+    }).unwrap();
+    assert_eq!(threads, 2);
+    assert!(in_background);
+    // 300 KB at 500 KB/s: the first read goes straight away, the rest wait their turn
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,50 @@
+use s4pi_reforged::backup::{backup, list_snapshots, restore};
+
+#[test]
+fn test_backup_and_restore() {
+    let dir = std::env::temp_dir().join(format!("s4pi_backup_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mods = dir.join("Mods");
+    let dest = mods.join("Backup");
+    std::fs::create_dir_all(mods.join("Hair")).unwrap();
+    std::fs::write(mods.join("Hair/hair.package"), b"hair v1").unwrap();
+    std::fs::write(mods.join("Hair/copy.package"), b"hair v1").unwrap();
+    std::fs::write(mods.join("sofa.package"), b"sofa").unwrap();
+
+    // Identical files are stored once, and the backup inside Mods isn't backed up itself
+    let first = backup(&mods, &dest).unwrap();
+    assert!(first.created);
+    assert_eq!(first.snapshot.files.len(), 3);
+    assert_eq!((first.new_files, first.new_bytes), (2, 11));
+
+    let unchanged = backup(&mods, &dest).unwrap();
+    assert!(!unchanged.created);
+    assert_eq!(unchanged.snapshot.id, first.snapshot.id);
+
+    // Only the changed file takes up space in the second snapshot
+    std::fs::write(mods.join("Hair/hair.package"), b"hair v2").unwrap();
+    std::fs::remove_file(mods.join("sofa.package")).unwrap();
+    let second = backup(&mods, &dest).unwrap();
+    assert!(second.created);
+    assert_eq!((second.new_files, second.new_bytes), (1, 7));
+    assert_eq!(list_snapshots(&dest).unwrap().len(), 2);
+
+    std::fs::create_dir_all(mods.join("Merged")).unwrap();
+    std::fs::write(mods.join("Merged/merged.package"), b"merged").unwrap();
+    let report = restore(&dest, Some(&first.snapshot.id), &mods).unwrap();
+    assert_eq!(report.restored, ["Hair/hair.package", "sofa.package"]);
+    assert_eq!(report.removed, ["Merged/merged.package"]);
+    assert_eq!(report.unchanged, 1);
+    assert_eq!(std::fs::read(mods.join("Hair/hair.package")).unwrap(), b"hair v1");
+    assert_eq!(std::fs::read(mods.join("sofa.package")).unwrap(), b"sofa");
+    assert!(!mods.join("Merged").exists());
+
+    // The state before the restore was kept, so the restore can be undone
+    let before = report.before.unwrap();
+    restore(&dest, Some(&before), &mods).unwrap();
+    assert_eq!(std::fs::read(mods.join("Merged/merged.package")).unwrap(), b"merged");
+    assert!(!mods.join("sofa.package").exists());
+
+    assert!(restore(&dest, Some("missing"), &mods).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
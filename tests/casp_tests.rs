@@ -0,0 +1,107 @@
+use s4pi_reforged::edit::set_field;
+use s4pi_reforged::package::resource::CasPartResource;
+use s4pi_reforged::{Resource, TypedResource, TGI};
+
+/// A CASP laid out the way the game writes `version`, with a body after the swatches that
+/// refers to the TGI list by index, the way LODs and texture keys do
+fn casp(version: u32, name: &str, tags: &[(u16, u32)], tgis: &[TGI]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&version.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // TGI list offset, filled in below
+    data.extend_from_slice(&0u32.to_le_bytes()); // preset count
+    let name: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    data.push(name.len() as u8);
+    data.extend_from_slice(&name);
+    data.extend_from_slice(&1.5f32.to_le_bytes()); // sort priority
+    data.extend_from_slice(&7u16.to_le_bytes()); // secondary sort index
+    data.extend_from_slice(&[0; 9]); // property ID, aural material, parameter flags
+    if version >= 39 {
+        data.push(0);
+    }
+    data.extend_from_slice(&[0; 8]);
+    if version >= 41 {
+        data.extend_from_slice(&[0; 8]);
+    }
+    data.extend_from_slice(&vec![0; if version >= 36 { 8 } else { 4 }]);
+    data.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+    for &(category, value) in tags {
+        data.extend_from_slice(&category.to_le_bytes());
+        if version >= 37 {
+            data.extend_from_slice(&value.to_le_bytes());
+        } else {
+            data.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+    }
+    data.extend_from_slice(&vec![0; if version >= 43 { 16 } else { 12 }]);
+    data.extend_from_slice(&[0; 17]);
+    if version >= 34 {
+        data.extend_from_slice(&5i16.to_le_bytes()); // pack ID
+        data.extend_from_slice(&[0; 10]);
+    } else {
+        data.push(0);
+    }
+    data.push(1);
+    data.extend_from_slice(&0xFF20A040u32.to_le_bytes());
+    data.extend_from_slice(&[0xAB, 0, 1, 0xCD]); // body with TGI indices
+    let offset = (data.len() - 8) as u32;
+    data[4..8].copy_from_slice(&offset.to_le_bytes());
+    data.push(tgis.len() as u8);
+    for tgi in tgis {
+        data.extend_from_slice(&tgi.instance.to_le_bytes());
+        data.extend_from_slice(&tgi.res_group.to_le_bytes());
+        data.extend_from_slice(&tgi.res_type.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_casp_round_trip() {
+    let geom = TGI { res_type: 0x015A1849, res_group: 0, instance: 0x1111 };
+    let image = TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x2222 };
+    for version in [30, 34, 36, 37, 39, 41, 43, 46] {
+        let original = casp(version, "yfTop_Test", &[(65, 1234), (68, 0)], &[geom, image]);
+        let mut part = CasPartResource::from_bytes(&original).unwrap();
+        assert_eq!(part.to_bytes().unwrap(), original, "version {} reads and writes unchanged", version);
+
+        part.set_name("yfTop_Renamed_Much_Longer").unwrap();
+        part.set_tags(&[(65, 4321)]).unwrap();
+        let hat = TGI { res_type: 0x015A1849, res_group: 0, instance: 0x3333 };
+        part.set_tgi_list(&[geom, image, hat]).unwrap();
+
+        let part = CasPartResource::from_bytes(&part.to_bytes().unwrap()).unwrap();
+        assert_eq!(part.name().unwrap(), "yfTop_Renamed_Much_Longer");
+        assert_eq!(part.tags().unwrap(), vec![(65, 4321)]);
+        assert_eq!(part.tgi_list().unwrap(), vec![geom, image, hat]);
+        assert_eq!(part.sort_order().unwrap(), (1.5, 7));
+        assert_eq!(part.pack_id().unwrap(), if version >= 34 { Some(5) } else { None });
+        assert_eq!(part.swatch_colors().unwrap(), vec![0xFF20A040]);
+        let body = part.raw_data.windows(4).position(|w| w == [0xAB, 0, 1, 0xCD]);
+        assert!(body.is_some(), "version {} keeps the rest of the part", version);
+
+        // Entries the part refers to by position can't be dropped or moved
+        let mut part = part;
+        assert!(part.set_tgi_list(&[geom, image]).is_err());
+        assert!(part.set_tgi_list(&[image, geom, hat]).is_err());
+        assert_eq!(part.tgi_list().unwrap(), vec![geom, image, hat]);
+
+        // Putting the name and tags back gives the bytes of a part written with the new list
+        part.set_name("yfTop_Test").unwrap();
+        part.set_tags(&[(65, 1234), (68, 0)]).unwrap();
+        let expected = casp(version, "yfTop_Test", &[(65, 1234), (68, 0)], &[geom, image, hat]);
+        assert_eq!(part.to_bytes().unwrap(), expected, "version {} round trips after edits", version);
+    }
+
+    // Tag values were 16-bit before version 37
+    let mut old = CasPartResource::from_bytes(&casp(36, "a", &[], &[geom])).unwrap();
+    assert!(old.set_tags(&[(65, 0x10000)]).is_err());
+
+    let mut resource = TypedResource::CasPart(CasPartResource::from_bytes(&casp(43, "a", &[], &[geom])).unwrap());
+    set_field(&mut resource, "name", "b").unwrap();
+    set_field(&mut resource, "tags", "65:0x10,68:2").unwrap();
+    assert!(set_field(&mut resource, "tags", "65").is_err());
+    assert!(set_field(&mut resource, "price", "1").is_err());
+    let TypedResource::CasPart(part) = resource else { unreachable!() };
+    assert_eq!(part.name().unwrap(), "b");
+    assert_eq!(part.tags().unwrap(), vec![(65, 16), (68, 2)]);
+    assert_eq!(part.tgi_list().unwrap(), vec![geom]);
+}
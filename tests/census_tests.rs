@@ -0,0 +1,81 @@
+use s4pi_reforged::census::{diff, format_mod_list, mod_list, Census};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::path::{Path, PathBuf};
+
+fn tgi(res_type: u32, instance: u64) -> TGI {
+    TGI { res_type, res_group: 0, instance }
+}
+
+fn write(path: &Path, resources: &[(TGI, &[u8])]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let resources: Vec<(IndexEntry, Vec<u8>)> = resources.iter()
+        .map(|(tgi, data)| (IndexEntry {
+            tgi: *tgi,
+            offset: 0,
+            filesize: 0,
+            memsize: data.len() as u32,
+            compression: 0,
+            committed: 1,
+        }, data.to_vec()))
+        .collect();
+    Package::write_stored(path, &resources).unwrap();
+}
+
+#[test]
+fn test_census_diff() {
+    let dir = std::env::temp_dir().join(format!("s4pi_census_test_{}", std::process::id()));
+    let before = dir.join("before");
+    let after = dir.join("after");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    write(&before.join("a.package"), &[(tgi(0x545AC67A, 1), b"same"), (tgi(0x545AC67A, 2), b"old"), (tgi(0x220557DA, 3), b"gone")]);
+    write(&before.join("sub/b.package"), &[(tgi(0x545AC67A, 4), b"moves")]);
+    write(&after.join("a.package"), &[(tgi(0x545AC67A, 1), b"same"), (tgi(0x545AC67A, 2), b"newer"), (tgi(0x545AC67A, 4), b"moves")]);
+    write(&after.join("c.package"), &[(tgi(0x0333406C, 5), b"<I/>")]);
+    std::fs::write(after.join("broken.package"), b"not a package").unwrap();
+
+    let before = Census::scan(&before);
+    let after = Census::scan(&after);
+    assert_eq!(before.packages, vec![PathBuf::from("a.package"), PathBuf::from("sub/b.package")]);
+    assert_eq!(after.resources.len(), 4);
+    assert_eq!(after.resources[&tgi(0x545AC67A, 4)][0].package, PathBuf::from("a.package"));
+
+    let diff = diff(&before, &after);
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].0, tgi(0x220557DA, 3));
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].0, tgi(0x0333406C, 5));
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].tgi, tgi(0x545AC67A, 2));
+    assert_eq!((diff.changed[0].before[0].size, diff.changed[0].after[0].size), (3, 5));
+    assert_eq!(diff.moved.len(), 1);
+    assert_eq!(diff.moved[0].before[0].package, PathBuf::from("sub/b.package"));
+    assert_eq!(diff.type_counts.get(&0x220557DA), Some(&(1, 0)));
+    assert_eq!(diff.type_counts.get(&0x0333406C), Some(&(0, 1)));
+    assert!(!diff.type_counts.contains_key(&0x545AC67A));
+    assert!(!diff.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_mod_list() {
+    let dir = std::env::temp_dir().join(format!("s4pi_modlist_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    write(&dir.join("b.package"), &[(tgi(0x545AC67A, 1), b"one"), (tgi(0x545AC67A, 2), b"two")]);
+    std::fs::create_dir_all(dir.join("Scripts")).unwrap();
+    std::fs::write(dir.join("Scripts/a.ts4script"), b"PK").unwrap();
+    std::fs::write(dir.join("readme.txt"), b"not a mod").unwrap();
+
+    let list = mod_list(&dir);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].path, PathBuf::from("Scripts/a.ts4script"));
+    assert_eq!((list[0].size, list[0].resources), (2, None));
+    assert_eq!(list[1].path, PathBuf::from("b.package"));
+    assert_eq!(list[1].resources, Some(2));
+    let text = format_mod_list(&list);
+    assert_eq!(text.lines().nth(1), Some("Scripts/a.ts4script\t2\t-"));
+    assert!(text.lines().nth(2).unwrap().ends_with("\t2"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,61 @@
+use s4pi_reforged::conflict::{find_conflicts, folder_conflicts, resolve, ConflictPolicy, Severity};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+
+fn tgi(instance: u64) -> TGI {
+    TGI { res_type: 0x545AC67A, res_group: 0, instance }
+}
+
+#[test]
+fn test_conflict_resolution() {
+    let a = [tgi(1), tgi(2)];
+    let b = [tgi(2), tgi(3)];
+    let c = [tgi(2), tgi(3), tgi(4)];
+    let packages = [("b.package", &b[..]), ("Zed/c.package", &c[..]), ("a.package", &a[..])];
+    let conflicts = find_conflicts(packages);
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].tgi, tgi(2));
+    assert_eq!(conflicts[0].packages, vec!["a.package", "b.package", "Zed/c.package"]);
+    assert_eq!(conflicts[1].packages, vec!["b.package", "Zed/c.package"]);
+
+    let last = resolve(&conflicts, &ConflictPolicy::LastAlphabetical).unwrap();
+    assert_eq!(last[0].winner, "Zed/c.package");
+    assert_eq!(last[0].losers, vec!["a.package", "b.package"]);
+
+    let chosen = resolve(&conflicts, &ConflictPolicy::Choose(HashMap::from([(tgi(2), "a.package".to_string())]))).unwrap();
+    assert_eq!(chosen[0].winner, "a.package");
+    assert_eq!(chosen[1].winner, "Zed/c.package");
+    assert!(resolve(&conflicts, &ConflictPolicy::Choose(HashMap::from([(tgi(3), "a.package".to_string())]))).is_err());
+
+    let error = resolve(&conflicts, &ConflictPolicy::Error).unwrap_err().to_string();
+    assert!(error.starts_with("2 resource(s)"));
+    assert!(resolve(&[], &ConflictPolicy::Error).unwrap().is_empty());
+}
+
+#[test]
+fn test_folder_conflicts() {
+    let dir = std::env::temp_dir().join(format!("s4pi_folder_conflicts_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("Sub")).unwrap();
+    let thumbnail = TGI { res_type: 0x3C1AF1F2, res_group: 0, instance: 9 };
+    let manifest = TGI { res_type: 0x7FB6AD8A, res_group: 0, instance: 0 };
+    let write = |name: &str, tgis: &[TGI]| {
+        let entries: HashMap<TGI, (Vec<u8>, u32, u16, u16)> = tgis.iter().map(|t| (*t, (vec![1, 2, 3], 3, 0, 1))).collect();
+        Package::write_merged(dir.join(name), &entries, false).unwrap();
+    };
+    write("b.package", &[tgi(1), thumbnail, manifest]);
+    write("Sub/a.package", &[tgi(1), tgi(2), thumbnail, manifest]);
+    write("c.package", &[tgi(3)]);
+
+    let conflicts = folder_conflicts(&dir);
+    assert_eq!(conflicts.len(), 2, "merge manifests don't count");
+    assert_eq!(conflicts[0].tgi, thumbnail);
+    assert_eq!(conflicts[1].tgi, tgi(1));
+    assert_eq!(conflicts[1].packages, vec!["b.package", "Sub/a.package"]);
+
+    assert_eq!(Severity::of(tgi(1).res_type), Severity::High);
+    assert_eq!(Severity::of(thumbnail.res_type), Severity::Low);
+    assert_eq!(Severity::of(0x00B2D882), Severity::Medium);
+    assert_eq!(Severity::of(0x0C772E27), Severity::High, "tuning");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,83 @@
+use s4pi_reforged::devwatch::{check_changes, snapshot, DevIssue, SIMDATA_TYPE};
+use s4pi_reforged::instance::name_instance;
+use s4pi_reforged::package::resource::StblEntry;
+use s4pi_reforged::{IndexEntry, Package, Resource, StblResource, TGI};
+
+const BUFF_TYPE: u32 = 0x6017E896;
+
+fn tuning(name: &str, value: u32) -> Vec<u8> {
+    format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<I c=\"Buff\" i=\"buff\" m=\"buffs.buff\" n=\"{}\" s=\"0\"><T n=\"duration\">{}</T></I>", name, value).into_bytes()
+}
+
+fn stbl(keys: &[u32]) -> Vec<u8> {
+    let mut table = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: keys.iter().map(|&key_hash| StblEntry { key_hash, flags: 0, string_value: "Text".to_string() }).collect(),
+    };
+    table.recalculate_string_length();
+    table.to_bytes().unwrap()
+}
+
+fn write(path: &std::path::Path, resources: Vec<(TGI, Vec<u8>)>) -> Package {
+    let resources: Vec<(IndexEntry, Vec<u8>)> = resources.into_iter()
+        .map(|(tgi, data)| (IndexEntry { tgi, offset: 0, filesize: 0, memsize: data.len() as u32, compression: 0, committed: 1 }, data))
+        .collect();
+    Package::write_stored(path, &resources).unwrap();
+    snapshot(path).unwrap()
+}
+
+#[test]
+fn test_check_changes() {
+    let dir = std::env::temp_dir().join(format!("s4pi_devwatch_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("project.package");
+
+    let buff = TGI { res_type: BUFF_TYPE, res_group: 0, instance: name_instance("mod:MyBuff") };
+    let buff_simdata = TGI { res_type: SIMDATA_TYPE, ..buff };
+    let trait_ = TGI { res_type: BUFF_TYPE, res_group: 0, instance: name_instance("mod:OtherBuff") };
+    let trait_simdata = TGI { res_type: SIMDATA_TYPE, ..trait_ };
+    let strings = TGI { res_type: 0x220557DA, res_group: 0, instance: 0x0011223344556677 };
+    let previous = write(&path, vec![
+        (buff, tuning("mod:MyBuff", 10)),
+        (buff_simdata, b"simdata 10".to_vec()),
+        (trait_, tuning("mod:OtherBuff", 1)),
+        (trait_simdata, b"simdata 1".to_vec()),
+        (strings, stbl(&[0x1111, 0x2222])),
+    ]);
+
+    // The snapshot keeps reading the old build after the file is overwritten
+    let misnamed = TGI { res_type: BUFF_TYPE, res_group: 0, instance: 0x8000000000000001 };
+    let orphan = TGI { res_type: SIMDATA_TYPE, res_group: 0, instance: 0x8000000000000002 };
+    let current = write(&path, vec![
+        (buff, tuning("mod:MyBuff", 20)),
+        (buff_simdata, b"simdata 10".to_vec()),
+        (trait_, tuning("mod:OtherBuff", 1)),
+        (strings, stbl(&[0x1111, 0x3333, 0x3333, 0])),
+        (misnamed, tuning("mod:Misnamed", 1)),
+        (orphan, b"simdata".to_vec()),
+    ]);
+    assert_eq!(previous.find(&trait_simdata).map(|e| previous.read_raw_resource(e).unwrap()), Some(b"simdata 1".to_vec()));
+
+    let report = check_changes(&previous, &current).unwrap();
+    assert_eq!(report.diff.added, vec![orphan, misnamed]);
+    assert_eq!(report.diff.changed.iter().map(|c| c.tgi).collect::<Vec<_>>(), vec![strings, buff]);
+    assert_eq!(report.diff.removed, vec![trait_simdata]);
+    assert_eq!(report.issues, vec![
+        (strings, DevIssue::DuplicateStringKey(0x3333)),
+        (strings, DevIssue::ZeroStringKey),
+        (strings, DevIssue::RemovedStringKey(0x2222)),
+        (orphan, DevIssue::OrphanSimData),
+        (misnamed, DevIssue::TuningHash { name: "mod:Misnamed".to_string(), expected: name_instance("mod:Misnamed") }),
+        (trait_, DevIssue::MissingSimData { simdata: trait_simdata }),
+        (buff, DevIssue::StaleSimData { simdata: buff_simdata }),
+    ]);
+
+    // Nothing changed, nothing to report
+    let report = check_changes(&current, &current).unwrap();
+    assert!(report.diff.is_empty() && report.issues.is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -2,7 +2,7 @@ use s4pi_reforged::Package;
 use std::env;
 use std::path::Path;
 
-fn main() -> anyhow::Result<()> {
+fn main() -> s4pi_reforged::Result<()> {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
         println!("Usage: diagnostic <package_path>");
@@ -10,7 +10,7 @@ fn main() -> anyhow::Result<()> {
     }
 
     let path = Path::new(&args[1]);
-    let mut pkg = Package::open(path)?;
+    let pkg = Package::open(path)?;
 
     println!("Package: {}", path.display());
     println!("Header: {:?}", pkg.header);
@@ -0,0 +1,99 @@
+use s4pi_reforged::edit::{parse_assignment, parse_number, set_field, set_fields};
+use s4pi_reforged::package::resource::StblEntry;
+use s4pi_reforged::{CatalogResource, Package, Resource, StblResource, TypedResource, TGI};
+use std::collections::HashMap;
+
+fn catalog_bytes() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x19u32.to_le_bytes()); // version
+    data.extend_from_slice(&0x09u32.to_le_bytes()); // common version
+    data.extend_from_slice(&0x11223344u32.to_le_bytes()); // name hash
+    data.extend_from_slice(&0x55667788u32.to_le_bytes()); // desc hash
+    data.extend_from_slice(&100u32.to_le_bytes()); // price
+    data.extend_from_slice(&0u64.to_le_bytes()); // thumbnail hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // dev category flags
+    data.push(0); // product styles count
+    data.push(0); // unused2 (common version < 10)
+    data.extend_from_slice(&0u32.to_le_bytes()); // legacy tag count
+    data.extend_from_slice(&0u32.to_le_bytes()); // selling point count
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlock by hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlocked by hash
+    data.extend_from_slice(&0u16.to_le_bytes()); // swatch colors sort priority
+    data.extend_from_slice(&0u64.to_le_bytes()); // variant thumb image hash
+    data.extend_from_slice(&[0u8; 16]); // aural materials version + 3 materials
+    data.extend_from_slice(&1u32.to_le_bytes()); // aural properties version
+    data.extend_from_slice(&0u32.to_le_bytes()); // aural quality
+    data.extend_from_slice(&[0u8; 12]); // unused0..2
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags high
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags low
+    data.extend_from_slice(&0u64.to_le_bytes()); // slot type set
+    data
+}
+
+#[test]
+fn test_parse_assignment() {
+    assert_eq!(parse_assignment("price=250").unwrap(), ("price".to_string(), "250".to_string()));
+    assert_eq!(parse_assignment("0x1A=a=b").unwrap().1, "a=b");
+    assert!(parse_assignment("price").is_err());
+    assert_eq!(parse_number("0xFF").unwrap(), 255);
+    assert_eq!(parse_number("250").unwrap(), 250);
+    assert!(parse_number("0xZZ").is_err());
+}
+
+#[test]
+fn test_set_catalog_fields() {
+    let mut resource = TypedResource::Catalog(CatalogResource::from_bytes(&catalog_bytes()).unwrap());
+    set_field(&mut resource, "price", "250").unwrap();
+    set_field(&mut resource, "name_hash", "0xDEADBEEF").unwrap();
+    assert!(set_field(&mut resource, "colour", "1").is_err());
+    assert!(set_field(&mut resource, "price", "0x100000000").is_err());
+
+    let TypedResource::Catalog(catalog) = resource else { unreachable!() };
+    let reparsed = CatalogResource::from_bytes(&catalog.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.common.price, 250);
+    assert_eq!(reparsed.common.name_hash, 0xDEADBEEF);
+    assert_eq!(reparsed.common.description_hash, 0x55667788);
+}
+
+#[test]
+fn test_set_rewrites_package() {
+    let dir = std::env::temp_dir().join(format!("s4pi_edit_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut stbl = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: vec![StblEntry { key_hash: 0x12345678, flags: 0, string_value: "Sit".to_string() }],
+    };
+    stbl.recalculate_string_length();
+    let stbl_tgi = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: 0x1 };
+    let other_tgi = TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x2 };
+
+    let mut entries = HashMap::new();
+    let stbl_data = stbl.to_bytes().unwrap();
+    entries.insert(stbl_tgi, (stbl_data.clone(), stbl_data.len() as u32, 0x5A42, 1));
+    entries.insert(other_tgi, (vec![7u8; 64], 64, 0, 1));
+    let path = dir.join("strings.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let edits = vec![
+        ("0x12345678".to_string(), "Sit Down".to_string()),
+        ("0x0000ABCD".to_string(), "New".to_string()),
+    ];
+    set_fields(&path, &stbl_tgi, &edits).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries.len(), 2);
+    let entry = pkg.find(&stbl_tgi).cloned().unwrap();
+    let TypedResource::Stbl(edited) = pkg.read_resource(&entry).unwrap() else { panic!("not a string table") };
+    assert_eq!(edited.entries.len(), 2);
+    assert_eq!(edited.entries[0].string_value, "Sit Down");
+    assert_eq!(edited.entries[1].key_hash, 0xABCD);
+    let other = pkg.find(&other_tgi).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&other).unwrap(), vec![7u8; 64]);
+
+    assert!(set_fields(&path, &other_tgi, &edits).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
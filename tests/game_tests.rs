@@ -0,0 +1,78 @@
+use s4pi_reforged::game::{find_game_packages, is_delta_package, is_game_package, GameIndex, OverrideReport};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[test]
+fn test_is_game_package() {
+    assert!(is_game_package(Path::new("Data/Client/ClientFullBuild0.package")));
+    assert!(is_game_package(Path::new("Delta/EP01/SimulationDeltaBuild0.package")));
+    assert!(is_game_package(Path::new("Data/Client/Strings_ENG_US.package")));
+    assert!(!is_game_package(Path::new("Mods/SomeCC.package")));
+    assert!(!is_game_package(Path::new("Data/Client/ClientFullBuild0.txt")));
+}
+
+#[test]
+fn test_override_classification() {
+    let dir = std::env::temp_dir().join(format!("s4pi_game_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("Data/Client")).unwrap();
+
+    let maxis_tgi = TGI { res_type: 0x034AEECB, res_group: 0, instance: 0x1234 };
+    let new_tgi = TGI { res_type: 0x034AEECB, res_group: 0, instance: 0x5678 };
+
+    let mut entries = HashMap::new();
+    entries.insert(maxis_tgi, (b"<I/>".to_vec(), 4, 0, 1));
+    Package::write_merged(dir.join("Data/Client/ClientFullBuild0.package"), &entries, false).unwrap();
+
+    let index = GameIndex::build(&dir).unwrap();
+    assert_eq!(index.packages.len(), 1);
+    assert!(index.contains(&maxis_tgi));
+    assert!(index.source_of(&maxis_tgi).unwrap().ends_with("ClientFullBuild0.package"));
+
+    let report = OverrideReport::classify(&index, &[maxis_tgi, new_tgi]);
+    assert_eq!(report.overrides, vec![maxis_tgi]);
+    assert_eq!(report.additions, vec![new_tgi]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_delta_packages_override_full_builds() {
+    let dir = std::env::temp_dir().join(format!("s4pi_game_delta_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("Data/Client")).unwrap();
+    std::fs::create_dir_all(dir.join("Delta/EP01")).unwrap();
+    std::fs::create_dir_all(dir.join("EP01")).unwrap();
+
+    let patched = TGI { res_type: 0x034AEECB, res_group: 0, instance: 1 };
+    let removed = TGI { res_type: 0x034AEECB, res_group: 0, instance: 2 };
+    let pack = TGI { res_type: 0x034AEECB, res_group: 0, instance: 3 };
+    // Stored as given, so a delta can carry deleted entries (compression 0xFFE0)
+    let write = |path: &str, resources: &[(TGI, u16)]| {
+        let stored: Vec<_> = resources.iter()
+            .map(|(tgi, compression)| (IndexEntry { tgi: *tgi, offset: 0, filesize: 4, memsize: 4, compression: *compression, committed: 1 }, b"<I/>".to_vec()))
+            .collect();
+        Package::write_stored(dir.join(path), &stored).unwrap();
+    };
+    write("Data/Client/ClientFullBuild0.package", &[(patched, 0), (removed, 0)]);
+    write("Data/Client/ClientFullBuild10.package", &[(patched, 0)]);
+    write("Data/Client/ClientFullBuild2.package", &[(patched, 0)]);
+    // Sorts before the full builds by path, but loads after them
+    write("Data/Client/ClientDeltaBuild0.package", &[(patched, 0), (removed, 0xFFE0)]);
+    write("EP01/ClientFullBuild0.package", &[(pack, 0)]);
+    write("Delta/EP01/ClientDeltaBuild0.package", &[(pack, 0)]);
+
+    assert!(is_delta_package(Path::new("Delta/EP01/Strings_ENG_US.package")));
+    assert!(!is_delta_package(Path::new("EP01/ClientFullBuild0.package")));
+    let names: Vec<String> = find_game_packages(&dir).iter()
+        .map(|p| p.strip_prefix(&dir).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect();
+    assert_eq!(names.iter().position(|n| n == "Data/Client/ClientFullBuild10.package"), Some(2));
+    assert!(names[..4].iter().all(|n| n.contains("FullBuild")));
+
+    let index = GameIndex::build(&dir).unwrap();
+    assert!(index.source_of(&patched).unwrap().ends_with("Data/Client/ClientDeltaBuild0.package"));
+    assert!(index.source_of(&pack).unwrap().ends_with("Delta/EP01/ClientDeltaBuild0.package"));
+    assert!(!index.contains(&removed));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,396 @@
+use s4pi_reforged::gltf::{bind_pose, geom_to_glb};
+use s4pi_reforged::mesh::{find_group_rig, find_mesh_groups, lint_geom, LintIssue, MeshGroup, GEOM_TYPE};
+use s4pi_reforged::package::resource::{GeomResource, RigBone};
+use s4pi_reforged::{Package, Resource, TGI};
+use std::collections::HashMap;
+
+/// Builds a version 5 GEOM with position, normal, one UV channel, bone indices and byte weights
+fn build_geom(positions: &[[f32; 3]], faces: &[[u16; 3]], bone_hashes: &[u32]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"GEOM");
+    data.extend_from_slice(&5u32.to_le_bytes()); // version
+    data.extend_from_slice(&0u32.to_le_bytes()); // tgi_offset (patched below)
+    data.extend_from_slice(&0u32.to_le_bytes()); // tgi_size
+    data.extend_from_slice(&0u32.to_le_bytes()); // embedded_id
+    data.extend_from_slice(&0u32.to_le_bytes()); // merge_group
+    data.extend_from_slice(&0u32.to_le_bytes()); // sort_order
+    data.extend_from_slice(&(positions.len() as u32).to_le_bytes());
+
+    // (usage, data_type, element_size)
+    let formats: [(u32, u32, u8); 5] = [(1, 1, 12), (2, 1, 12), (3, 1, 8), (4, 2, 4), (5, 2, 4)];
+    data.extend_from_slice(&(formats.len() as u32).to_le_bytes());
+    for (usage, data_type, size) in formats {
+        data.extend_from_slice(&usage.to_le_bytes());
+        data.extend_from_slice(&data_type.to_le_bytes());
+        data.push(size);
+    }
+
+    for (i, p) in positions.iter().enumerate() {
+        for v in p {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [0.0f32, 1.0, 0.0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [p[0], 1.0 - p[1]] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&[(i % bone_hashes.len().max(1)) as u8, 0, 0, 0]);
+        data.extend_from_slice(&[255, 0, 0, 0]);
+    }
+
+    data.extend_from_slice(&1u32.to_le_bytes()); // item_count
+    data.push(2); // bytes per face point
+    data.extend_from_slice(&(faces.len() as u32 * 3).to_le_bytes());
+    for face in faces {
+        for index in face {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+    data.extend_from_slice(&0i32.to_le_bytes()); // skin_index
+    data.extend_from_slice(&(bone_hashes.len() as u32).to_le_bytes());
+    for hash in bone_hashes {
+        data.extend_from_slice(&hash.to_le_bytes());
+    }
+
+    let tgi_offset = data.len() as u32 - 16;
+    data[8..12].copy_from_slice(&tgi_offset.to_le_bytes());
+    data
+}
+
+fn quad() -> Vec<u8> {
+    build_geom(
+        &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.0, 2.0, 0.5]],
+        &[[0, 1, 2], [0, 2, 3]],
+        &[0x11111111, 0x22222222],
+    )
+}
+
+#[test]
+fn test_geom_vertex_accessors() {
+    let geom = GeomResource::from_bytes(&quad()).unwrap();
+    assert_eq!(geom.positions().len(), 4);
+    assert_eq!(geom.positions()[2], [1.0, 2.0, 0.0]);
+    assert_eq!(geom.normals()[0], [0.0, 1.0, 0.0]);
+    assert_eq!(geom.uv_channel_count(), 1);
+    assert_eq!(geom.uvs(0)[1], [1.0, 1.0]);
+    assert!(geom.uvs(1).is_empty());
+
+    let bones = geom.bone_assignments();
+    assert_eq!(bones.len(), 4);
+    assert_eq!(bones[1].bones[0], 1);
+    assert_eq!(bones[1].weights, [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(geom.bone_hashes.hashes[bones[1].bones[0] as usize], 0x22222222);
+    assert_eq!(geom.faces.faces.len(), 2);
+}
+
+#[test]
+fn test_geom_stats() {
+    let stats = GeomResource::from_bytes(&quad()).unwrap().stats();
+    assert_eq!(stats.vertex_count, 4);
+    assert_eq!(stats.face_count, 2);
+    assert_eq!(stats.bounds_min, [0.0, 0.0, 0.0]);
+    assert_eq!(stats.bounds_max, [1.0, 2.0, 0.5]);
+    assert_eq!(stats.size(), [1.0, 2.0, 0.5]);
+    assert_eq!(stats.uv_min, [0.0, -1.0]);
+    assert_eq!(stats.uv_max, [1.0, 1.0]);
+    assert_eq!(stats.bone_count, 2);
+}
+
+#[test]
+fn test_mesh_groups_follow_casp_order() {
+    let dir = std::env::temp_dir().join(format!("s4pi_geom_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let lod0 = TGI { res_type: GEOM_TYPE, res_group: 0, instance: 0x10 };
+    let lod1 = TGI { res_type: GEOM_TYPE, res_group: 0, instance: 0x11 };
+    let loose = TGI { res_type: GEOM_TYPE, res_group: 0, instance: 0x99 };
+    let casp = TGI { res_type: 0x034AE111, res_group: 0, instance: 0x1 };
+
+    // CASP: version, offset to TGI list (from byte 8), then the list (instance, group, type)
+    let mut casp_data = Vec::new();
+    casp_data.extend_from_slice(&0x2Cu32.to_le_bytes());
+    casp_data.extend_from_slice(&0u32.to_le_bytes());
+    casp_data.push(2);
+    for tgi in [lod0, lod1] {
+        casp_data.extend_from_slice(&tgi.instance.to_le_bytes());
+        casp_data.extend_from_slice(&tgi.res_group.to_le_bytes());
+        casp_data.extend_from_slice(&tgi.res_type.to_le_bytes());
+    }
+
+    let mut entries = HashMap::new();
+    entries.insert(casp, (casp_data.clone(), casp_data.len() as u32, 0, 1));
+    for tgi in [lod0, lod1, loose] {
+        let geom = quad();
+        entries.insert(tgi, (geom.clone(), geom.len() as u32, 0, 1));
+    }
+    let path = dir.join("mesh.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let groups = find_mesh_groups(&pkg);
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0], MeshGroup { owner: Some(casp), lods: vec![lod0, lod1] });
+    assert_eq!(groups[1], MeshGroup { owner: None, lods: vec![loose] });
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A clear-format RIG with a single root bone
+fn rig_data(hash: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in [4u32, 2, 1] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    for v in [0.0f32, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0] {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data.extend_from_slice(&4u32.to_le_bytes());
+    data.extend_from_slice(b"root");
+    data.extend_from_slice(&(-1i32).to_le_bytes());
+    data.extend_from_slice(&(-1i32).to_le_bytes());
+    data.extend_from_slice(&hash.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_group_rig_follows_references() {
+    let dir = std::env::temp_dir().join(format!("s4pi_group_rig_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let geom = TGI { res_type: GEOM_TYPE, res_group: 0, instance: 0x10 };
+    let loose = TGI { res_type: GEOM_TYPE, res_group: 0, instance: 0x99 };
+    let casp = TGI { res_type: 0x034AE111, res_group: 0, instance: 0x1 };
+    let first_rig = TGI { res_type: 0x8EAF13DE, res_group: 0, instance: 0x20 };
+    let used_rig = TGI { res_type: 0x8EAF13DE, res_group: 0, instance: 0x21 };
+
+    let mut casp_data = Vec::new();
+    casp_data.extend_from_slice(&0x2Cu32.to_le_bytes());
+    casp_data.extend_from_slice(&0u32.to_le_bytes());
+    casp_data.push(2);
+    for tgi in [geom, used_rig] {
+        casp_data.extend_from_slice(&tgi.instance.to_le_bytes());
+        casp_data.extend_from_slice(&tgi.res_group.to_le_bytes());
+        casp_data.extend_from_slice(&tgi.res_type.to_le_bytes());
+    }
+
+    let mut entries = HashMap::new();
+    entries.insert(casp, (casp_data.clone(), casp_data.len() as u32, 0, 1));
+    for tgi in [geom, loose] {
+        entries.insert(tgi, (quad(), quad().len() as u32, 0, 1));
+    }
+    for (tgi, hash) in [(first_rig, 0x11111111), (used_rig, 0x22222222)] {
+        let rig = rig_data(hash);
+        entries.insert(tgi, (rig.clone(), rig.len() as u32, 0, 1));
+    }
+    let path = dir.join("rigs.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let groups = find_mesh_groups(&pkg);
+    let (tgi, bones) = find_group_rig(&pkg, &groups[0]).unwrap();
+    assert_eq!((tgi, bones[0].hash), (used_rig, 0x22222222));
+    // Several rigs and no reference: nothing to go on
+    assert_eq!(groups[1].lods, vec![loose]);
+    assert!(find_group_rig(&pkg, &groups[1]).is_none());
+
+    // A package's only rig is used without a reference
+    entries.remove(&used_rig);
+    Package::write_merged(&path, &entries, false).unwrap();
+    let pkg = Package::open(&path).unwrap();
+    let groups = find_mesh_groups(&pkg);
+    assert_eq!(find_group_rig(&pkg, &groups[1]).unwrap().0, first_rig);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn bone(name: &str, hash: u32, parent_index: i32) -> RigBone {
+    RigBone {
+        position: [0.0; 3],
+        orientation: [0.0, 0.0, 0.0, 1.0],
+        scale: [1.0; 3],
+        name: name.to_string(),
+        opposite_index: -1,
+        parent_index,
+        hash,
+        flags: 0,
+    }
+}
+
+#[test]
+fn test_mesh_lint() {
+    let data = build_geom(
+        &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [2.0, 0.0, 0.0]],
+        &[[0, 1, 2], [0, 1, 3], [0, 0, 2], [0, 1, 7]],
+        &[0x11111111, 0x22222222],
+    );
+    let geom = GeomResource::from_bytes(&data).unwrap();
+
+    let rig = vec![bone("b__ROOT__", 0x11111111, -1)];
+    let issues = lint_geom(&geom, Some(&rig));
+    assert!(issues.contains(&LintIssue::MissingBone { hash: 0x22222222 }));
+    assert!(!issues.contains(&LintIssue::DegenerateFace { face: 0 }));
+    assert!(issues.contains(&LintIssue::DegenerateFace { face: 1 })); // collinear
+    assert!(issues.contains(&LintIssue::DegenerateFace { face: 2 })); // repeated vertex
+    assert!(issues.contains(&LintIssue::FaceIndexOutOfRange { face: 3 }));
+    assert!(issues.contains(&LintIssue::UvOutOfRange { vertex: 3, channel: 0, uv: [2.0, 1.0] }));
+    assert!(!issues.iter().any(|i| matches!(i, LintIssue::UnnormalizedWeights { .. })));
+
+    let without_rig = lint_geom(&geom, None);
+    assert!(!without_rig.iter().any(|i| matches!(i, LintIssue::MissingBone { .. })));
+}
+
+#[test]
+fn test_bind_pose_follows_hierarchy() {
+    let mut root = bone("b__ROOT__", 1, -1);
+    root.position = [0.0, 1.0, 0.0];
+    let mut child = bone("b__Spine__", 2, 0);
+    child.position = [0.0, 0.5, 0.0];
+
+    let world = bind_pose(&[root, child]);
+    assert_eq!(&world[1][12..15], &[0.0, 1.5, 0.0]);
+}
+
+fn glb_json(glb: &[u8]) -> serde_json::Value {
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+    let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+    assert_eq!(&glb[16..20], b"JSON");
+    serde_json::from_slice(&glb[20..20 + json_len]).unwrap()
+}
+
+#[test]
+fn test_skinned_gltf_export() {
+    let geom = GeomResource::from_bytes(&quad()).unwrap();
+    let mut root = bone("b__ROOT__", 0x11111111, -1);
+    root.position = [0.0, 1.0, 0.0];
+    let rig = vec![root, bone("b__Pelvis__", 0x22222222, 0)];
+
+    let doc = glb_json(&geom_to_glb(&geom, Some(&rig), "quad").unwrap());
+    let nodes = doc["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), 3);
+    assert_eq!(nodes[0]["skin"], 0);
+    assert_eq!(nodes[1]["name"], "b__ROOT__");
+    assert_eq!(nodes[1]["children"], serde_json::json!([2]));
+    assert_eq!(doc["skins"][0]["joints"], serde_json::json!([1, 2]));
+    assert_eq!(doc["scenes"][0]["nodes"], serde_json::json!([0, 1]));
+
+    let attributes = &doc["meshes"][0]["primitives"][0]["attributes"];
+    for attribute in ["POSITION", "NORMAL", "TEXCOORD_0", "JOINTS_0", "WEIGHTS_0"] {
+        assert!(attributes.get(attribute).is_some(), "missing {}", attribute);
+    }
+    let position = &doc["accessors"][attributes["POSITION"].as_u64().unwrap() as usize];
+    assert_eq!(position["count"], 4);
+    assert_eq!(position["max"], serde_json::json!([1.0, 2.0, 0.5]));
+
+    let unskinned = glb_json(&geom_to_glb(&geom, None, "quad").unwrap());
+    assert!(unskinned.get("skins").is_none());
+    assert_eq!(unskinned["nodes"].as_array().unwrap().len(), 1);
+}
+
+/// Builds a version 12 GEOM laid out as the game writes it: an embedded MTNF, a vertex ID and
+/// colour per vertex, the unknown lists, and a TGI list of a count and two TGIs at the end
+fn build_geom_v12() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"GEOM");
+    data.extend_from_slice(&12u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // tgi_offset (patched below)
+    data.extend_from_slice(&36u32.to_le_bytes()); // tgi_size
+    data.extend_from_slice(&0x01D10F34u32.to_le_bytes()); // embedded_id
+    data.extend_from_slice(&5u32.to_le_bytes());
+    data.extend_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD, 0xEE]); // MTNF
+    data.extend_from_slice(&0u32.to_le_bytes()); // merge_group
+    data.extend_from_slice(&7u32.to_le_bytes()); // sort_order
+    data.extend_from_slice(&3u32.to_le_bytes()); // num_verts
+
+    let formats: [(u32, u32, u8); 3] = [(1, 1, 12), (7, 3, 4), (10, 4, 4)];
+    data.extend_from_slice(&(formats.len() as u32).to_le_bytes());
+    for (usage, data_type, size) in formats {
+        data.extend_from_slice(&usage.to_le_bytes());
+        data.extend_from_slice(&data_type.to_le_bytes());
+        data.push(size);
+    }
+    for v in 0..3u32 {
+        for axis in [v as f32, 0.5, -1.0] {
+            data.extend_from_slice(&axis.to_le_bytes());
+        }
+        data.extend_from_slice(&0xFF00FF00u32.to_le_bytes());
+        data.extend_from_slice(&(100 + v).to_le_bytes());
+    }
+
+    data.extend_from_slice(&1u32.to_le_bytes()); // item_count
+    data.push(1); // bytes per face point
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(&[0, 1, 2]);
+
+    data.extend_from_slice(&1u32.to_le_bytes()); // unknown_things
+    data.extend_from_slice(&9u32.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&0.25f32.to_le_bytes());
+    data.extend_from_slice(&0.75f32.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes()); // unknown_things2
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(&[1, 0, 2, 0, 3, 0]);
+    for i in 0..13 {
+        data.extend_from_slice(&(i as f32).to_le_bytes());
+    }
+    data.push(4);
+    data.extend_from_slice(&1u32.to_le_bytes()); // bone hashes
+    data.extend_from_slice(&0x12345678u32.to_le_bytes());
+
+    let tgi_offset = data.len() as u32 - 12;
+    data[8..12].copy_from_slice(&tgi_offset.to_le_bytes());
+    data.extend_from_slice(&2u32.to_le_bytes());
+    for (res_type, instance) in [(0x00B2D882u32, 0x1111u64), (0x3453CF95, 0x2222)] {
+        data.extend_from_slice(&res_type.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&instance.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_geom_write_round_trip() {
+    let original = build_geom_v12();
+    let geom = GeomResource::from_bytes(&original).unwrap();
+    assert_eq!(geom.tgi_blocks.len(), 2);
+    assert_eq!(geom.faces.faces, [[0, 1, 2]]);
+    assert_eq!(geom.to_bytes().unwrap(), original);
+
+    // Inside an RCOL, as GEOMs are stored in packages
+    let mut rcol = Vec::new();
+    for value in [3u32, 1, 0, 0, 1] {
+        rcol.extend_from_slice(&value.to_le_bytes());
+    }
+    rcol.extend_from_slice(&0x015A1849u32.to_le_bytes());
+    rcol.extend_from_slice(&0u32.to_le_bytes());
+    rcol.extend_from_slice(&0x99u64.to_le_bytes());
+    rcol.extend_from_slice(&(rcol.len() as u32 + 8).to_le_bytes());
+    rcol.extend_from_slice(&(original.len() as u32).to_le_bytes());
+    rcol.extend_from_slice(&original);
+    assert_eq!(GeomResource::from_package_data(&rcol).unwrap().to_bytes().unwrap(), original);
+
+    // Edits are written with the counts, face point size and TGI list position recomputed
+    let mut geom = GeomResource::from_bytes(&quad()).unwrap();
+    geom.faces.faces.pop();
+    geom.tgi_blocks.push(TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x3333 });
+    let written = GeomResource::from_bytes(&geom.to_bytes().unwrap()).unwrap();
+    assert_eq!(written.faces.faces, [[0, 1, 2]]);
+    assert_eq!(written.positions(), geom.positions());
+    assert_eq!(written.bone_assignments(), geom.bone_assignments());
+    assert_eq!(written.bone_hashes.hashes, [0x11111111, 0x22222222]);
+    assert_eq!(written.tgi_blocks, geom.tgi_blocks);
+    assert_eq!(written.tgi_size, 20);
+
+    // Vertices are replaced as a whole, so positions and bones always match what is written
+    let mut data = geom.vertex_data().clone();
+    data.vertices[1].pop();
+    assert!(geom.set_vertices(geom.vertex_formats().clone(), data).is_err());
+    let mut data = geom.vertex_data().clone();
+    data.vertices.swap(0, 2);
+    geom.set_vertices(geom.vertex_formats().clone(), data).unwrap();
+    assert_eq!(geom.positions()[0], written.positions()[2]);
+    let rewritten = GeomResource::from_bytes(&geom.to_bytes().unwrap()).unwrap();
+    assert_eq!(rewritten.positions(), geom.positions());
+}
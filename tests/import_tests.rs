@@ -0,0 +1,53 @@
+use s4pi_reforged::import::{parse_export_filename, collect_import_files, ExportLayout};
+use s4pi_reforged::TGI;
+
+#[test]
+fn test_s4pe_filename() {
+    let key = parse_export_filename("S4_220557DA_80000000_0004A0D2F3A7A8A5_My_Strings%%+STBL.stbl").unwrap();
+    assert_eq!(key.layout, ExportLayout::S4pe);
+    assert_eq!(key.tgi, TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: 0x0004A0D2F3A7A8A5 });
+    assert_eq!(key.name.as_deref(), Some("My_Strings"));
+
+    let key = parse_export_filename("S4_034AEECB_00000000_0000000000001234%%+_XML.xml").unwrap();
+    assert_eq!(key.tgi.res_type, 0x034AEECB);
+    assert_eq!(key.name, None);
+}
+
+#[test]
+fn test_s4s_filename() {
+    let key = parse_export_filename("E882D22F!00000000!0000000000013C25.buff_Example.BuffTuning.xml").unwrap();
+    assert_eq!(key.layout, ExportLayout::S4s);
+    assert_eq!(key.tgi, TGI { res_type: 0xE882D22F, res_group: 0, instance: 0x13C25 });
+    assert_eq!(key.name.as_deref(), Some("buff_Example"));
+
+    let key = parse_export_filename("00B2D882!00000000!1234567890ABCDEF.binary").unwrap();
+    assert_eq!(key.tgi.instance, 0x1234567890ABCDEF);
+    assert_eq!(key.name, None);
+}
+
+#[test]
+fn test_plain_filename() {
+    let key = parse_export_filename("0x545AC67A-0x00000000-0x00000000DEADBEEF.simdata").unwrap();
+    assert_eq!(key.layout, ExportLayout::Plain);
+    assert_eq!(key.tgi, TGI { res_type: 0x545AC67A, res_group: 0, instance: 0xDEADBEEF });
+
+    assert!(parse_export_filename("readme.txt").is_none());
+    assert!(parse_export_filename("S4_nothex_00000000_0000000000000000.bin").is_none());
+}
+
+#[test]
+fn test_collect_import_files() {
+    let dir = std::env::temp_dir().join(format!("s4pi_import_test_{}", std::process::id()));
+    let sub = dir.join("StringTable");
+    std::fs::create_dir_all(&sub).unwrap();
+    std::fs::write(sub.join("220557DA!80000000!0004A0D2F3A7A8A5.English.StringTable.binary"), b"STBL").unwrap();
+    std::fs::write(dir.join("S4_034AEECB_00000000_0000000000001234%%+_XML.xml"), b"<I/>").unwrap();
+    std::fs::write(dir.join("notes.txt"), b"hello").unwrap();
+
+    let (files, skipped) = collect_import_files(&dir).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(skipped.len(), 1);
+    assert!(files.iter().any(|f| f.key.tgi.res_type == 0x220557DA));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
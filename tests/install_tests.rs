@@ -0,0 +1,144 @@
+use s4pi_reforged::archive::is_safe_entry_name;
+use s4pi_reforged::install::{apply_install, plan_install, undo_last_install, InstallKind, JOURNAL_NAME};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn package_bytes(dir: &Path, data: &[u8]) -> Vec<u8> {
+    let tgi = TGI { res_type: 0x545AC67A, res_group: 0, instance: 1 };
+    let entry = IndexEntry { tgi, offset: 0, filesize: 0, memsize: data.len() as u32, compression: 0, committed: 1 };
+    let path = dir.join("tmp.package");
+    Package::write_stored(&path, &[(entry, data.to_vec())]).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    bytes
+}
+
+#[test]
+fn test_install_and_undo() {
+    let dir = std::env::temp_dir().join(format!("s4pi_install_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mods = dir.join("Mods");
+    std::fs::create_dir_all(&mods).unwrap();
+
+    let mut script = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    script.start_file("mod/main.pyc", zip::write::SimpleFileOptions::default()).unwrap();
+    script.write_all(b"bytecode").unwrap();
+    let script = script.finish().unwrap().into_inner();
+
+    let hair = package_bytes(&dir, b"hair");
+    let zip_path = dir.join("NewHair.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, data) in [
+        ("NewHair/hair.package", hair.as_slice()),
+        ("NewHair/scripts/hair.ts4script", script.as_slice()),
+        ("NewHair/broken.package", b"junk".as_slice()),
+        ("NewHair/Readme.txt", b"Enjoy".as_slice()),
+        ("__MACOSX/NewHair/._hair.package", b"junk".as_slice()),
+    ] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+
+    // A different file already has the package's name
+    std::fs::create_dir_all(mods.join("CC/Hair")).unwrap();
+    std::fs::write(mods.join("CC/Hair/hair.package"), package_bytes(&dir, b"other")).unwrap();
+
+    let plan = plan_install(&zip_path, &mods, Some(Path::new("CC/Hair"))).unwrap();
+    assert_eq!(plan.files.len(), 2);
+    assert_eq!(plan.files[0].destination, PathBuf::from("CC/Hair/hair (2).package"));
+    assert!(plan.files[0].renamed);
+    assert_eq!(plan.files[1].kind, InstallKind::Script);
+    assert_eq!(plan.files[1].destination, PathBuf::from("CC/hair.ts4script"));
+    assert_eq!(plan.rejected.len(), 1);
+    assert_eq!(plan.rejected[0].0, "NewHair/broken.package");
+    assert_eq!(plan.ignored, vec!["NewHair/Readme.txt"]);
+    assert!(plan_install(&zip_path, &mods, Some(Path::new("../outside"))).is_err());
+
+    apply_install(&plan, &mods, &zip_path).unwrap();
+    assert_eq!(std::fs::read(mods.join("CC/Hair/hair (2).package")).unwrap(), hair);
+    assert!(mods.join("CC/hair.ts4script").is_file());
+
+    // Installing again finds everything already there
+    let again = plan_install(&zip_path, &mods, Some(Path::new("CC/Hair"))).unwrap();
+    assert!(again.files.is_empty());
+    assert_eq!(again.already_installed.len(), 2);
+
+    let report = undo_last_install(&mods).unwrap();
+    assert_eq!(report.removed.len(), 2);
+    assert!(report.kept.is_empty());
+    assert!(!mods.join("CC/Hair/hair (2).package").exists());
+    assert!(!mods.join("CC/hair.ts4script").exists());
+    assert!(mods.join("CC/Hair/hair.package").exists());
+    assert!(!mods.join(JOURNAL_NAME).exists());
+    assert!(undo_last_install(&mods).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_install_skips_paths_outside_mods() {
+    let dir = std::env::temp_dir().join(format!("s4pi_install_slip_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mods = dir.join("Mods");
+    std::fs::create_dir_all(&mods).unwrap();
+
+    let package = package_bytes(&dir, b"hair");
+    let zip_path = dir.join("Hostile.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+    let options = zip::write::SimpleFileOptions::default();
+    for name in [
+        "Hostile/fine.package",
+        "..\\..\\Startup\\x.package",
+        "C:\\Users\\Public\\x.package",
+        "/etc/x.package",
+        "Hostile/../../x.package",
+    ] {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(&package).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let plan = plan_install(&zip_path, &mods, None).unwrap();
+    assert_eq!(plan.files.len(), 1);
+    assert_eq!(plan.files[0].destination, PathBuf::from("Hostile/fine.package"));
+    apply_install(&plan, &mods, &zip_path).unwrap();
+    assert!(!dir.join("x.package").exists());
+    assert!(!mods.join("x.package").exists());
+
+    assert!(is_safe_entry_name("Hair/hair.package"));
+    for name in ["..\\x.package", "a\\x.package", "C:x.package", "/x.package", "a/../x.package", ""] {
+        assert!(!is_safe_entry_name(name), "{}", name);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_failed_install_can_be_undone() {
+    let dir = std::env::temp_dir().join(format!("s4pi_install_partial_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mods = dir.join("Mods");
+    let download = dir.join("Pack");
+    std::fs::create_dir_all(&mods).unwrap();
+    std::fs::create_dir_all(&download).unwrap();
+    std::fs::write(download.join("a.package"), package_bytes(&dir, b"a")).unwrap();
+    std::fs::write(download.join("b.package"), package_bytes(&dir, b"b")).unwrap();
+
+    let plan = plan_install(&download, &mods, None).unwrap();
+    assert_eq!(plan.files.len(), 2);
+    // Something in the way of the second file makes the copy fail after the first
+    std::fs::create_dir_all(mods.join("Pack/b.package/blocker")).unwrap();
+    assert!(apply_install(&plan, &mods, &download).is_err());
+    assert!(mods.join("Pack/a.package").is_file());
+
+    let report = undo_last_install(&mods).unwrap();
+    assert_eq!(report.removed, vec![PathBuf::from("Pack/a.package")]);
+    assert!(!mods.join("Pack/a.package").exists());
+    assert!(!mods.join(JOURNAL_NAME).exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
@@ -0,0 +1,53 @@
+use s4pi_reforged::import::{assign_new_instances, parse_export_filename, ImportFile};
+use s4pi_reforged::instance::{fnv64, name_instance, InstanceAllocator, CUSTOM_BIT};
+use s4pi_reforged::package::builder::PackageBuilder;
+use s4pi_reforged::TGI;
+use std::path::PathBuf;
+
+#[test]
+fn test_instance_generators() {
+    assert_eq!(fnv64(""), 0xCBF29CE484222325);
+    assert_eq!(fnv64("a"), 0xAF63BD4C8601B7BE);
+    assert_eq!(fnv64("My_Buff"), fnv64("my_buff"));
+    assert_eq!(name_instance("a"), 0xAF63BD4C8601B7BE | CUSTOM_BIT);
+
+    let tuning = TGI { res_type: 0x0333406C, res_group: 0, instance: name_instance("creator:buff") };
+    let mut builder = PackageBuilder::new();
+    builder.add(tuning, b"<I/>".to_vec());
+    let mut allocator = builder.allocator();
+    assert!(allocator.is_taken(tuning.instance));
+    assert_eq!(allocator.named("creator:buff"), None);
+    assert_eq!(allocator.named("creator:other"), Some(name_instance("creator:other")));
+
+    let random: Vec<u64> = (0..100).map(|_| allocator.random()).collect();
+    assert!(random.iter().all(|i| i & CUSTOM_BIT != 0 && *i != tuning.instance));
+    let mut unique = random.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), random.len());
+
+    let keys = allocator.paired(&[(0xC0DB5AE7, 0), (0x319E4F1D, 0)]);
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].instance, keys[1].instance);
+    assert!(!random.contains(&keys[0].instance));
+}
+
+#[test]
+fn test_assign_new_instances() {
+    let file = |name: &str| ImportFile { path: PathBuf::from(name), key: parse_export_filename(name).unwrap() };
+    let mut files = vec![
+        file("S4_C0DB5AE7_00000000_0000000000001234.objd"),
+        file("S4_319E4F1D_00000000_0000000000001234_my_table%%+COBJ.cobj"),
+        file("S4_0333406C_00000000_0000000000005678.xml"),
+        file("S4_0166038C_00000000_0000000000000000.namemap"),
+    ];
+    let mut allocator = InstanceAllocator::new();
+    let mapping = assign_new_instances(&mut files, &mut allocator);
+
+    assert_eq!(mapping.len(), 2);
+    assert_eq!(files[0].key.tgi.instance, name_instance("my_table"));
+    assert_eq!(files[1].key.tgi.instance, files[0].key.tgi.instance);
+    assert_eq!(files[2].key.tgi.instance, mapping[&0x5678]);
+    assert_ne!(files[2].key.tgi.instance, 0x5678);
+    assert_eq!(files[3].key.tgi.instance, 0);
+}
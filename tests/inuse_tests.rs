@@ -0,0 +1,49 @@
+use s4pi_reforged::inuse::find_in_use;
+
+#[test]
+fn test_find_in_use() {
+    let dir = std::env::temp_dir().join(format!("s4pi_inuse_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("held.package");
+    std::fs::write(&path, b"DBPF").unwrap();
+
+    assert!(find_in_use(&[&path]).is_empty());
+    assert!(find_in_use(&[dir.join("missing.package")]).is_empty());
+
+    #[cfg(not(windows))]
+    {
+        let locked = std::fs::File::open(&path).unwrap();
+        locked.lock().unwrap();
+        let found = find_in_use(&[&path]);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].holders.is_empty());
+        locked.unlock().unwrap();
+        assert!(find_in_use(&[&path]).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("exec 3<\"$0\"; sleep 10")
+            .arg(&path)
+            .spawn()
+            .unwrap();
+        let mut found = Vec::new();
+        for _ in 0..50 {
+            found = find_in_use(&[&path]);
+            if !found.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, path);
+        assert!(found[0].holders.iter().any(|h| h.pid == child.id()));
+        assert!(found[0].to_string().contains("is open in"));
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
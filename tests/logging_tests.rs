@@ -0,0 +1,45 @@
+#![cfg(feature = "logging")]
+
+use log::{Level, Record};
+use s4pi_reforged::logging::{create_run_log, json_line, MAX_RUN_LOGS};
+
+#[test]
+fn test_json_line_includes_fields() {
+    let kvs: &[(&str, &str)] = &[("package", "mods/hair.package"), ("resource", "034AE111:00000000:0000000000000001")];
+    let line = json_line(
+        &Record::builder()
+            .level(Level::Warn)
+            .target("s4pi_reforged")
+            .args(format_args!("Failed to read resource"))
+            .key_values(&kvs)
+            .build(),
+    );
+    let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(json["level"], "WARN");
+    assert_eq!(json["message"], "Failed to read resource");
+    assert_eq!(json["package"], "mods/hair.package");
+    assert_eq!(json["resource"], "034AE111:00000000:0000000000000001");
+    assert!(json["time"].as_u64().unwrap() > 0);
+    assert!(!line.contains('\n'));
+}
+
+#[test]
+fn test_old_run_logs_are_pruned() {
+    let dir = std::env::temp_dir().join(format!("s4pi_logging_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for i in 0..MAX_RUN_LOGS + 5 {
+        std::fs::write(dir.join(format!("s4pi-reforged-{:010}-1.jsonl", i)), "").unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+    let (path, _file) = create_run_log(&dir).unwrap();
+    assert!(path.exists());
+    let logs = std::fs::read_dir(&dir).unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "jsonl"))
+        .count();
+    assert_eq!(logs, MAX_RUN_LOGS);
+    assert!(!dir.join("s4pi-reforged-0000000000-1.jsonl").exists());
+    assert!(dir.join("notes.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
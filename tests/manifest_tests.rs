@@ -0,0 +1,206 @@
+use s4pi_reforged::census::Census;
+use s4pi_reforged::manifest::{is_descriptor, read_descriptor, read_manifest, replace_subpackage, write_descriptor, upgrade, verify, MergedPackageView, SubpackageUpdate, UpgradeOutcome, VerifyIssue};
+use s4pi_reforged::package::resource::{ManifestEntry, ManifestResource, ResourceChecksum};
+use s4pi_reforged::{Package, Resource, TGI};
+use std::collections::HashMap;
+
+fn tgi(instance: u64) -> TGI {
+    TGI { res_type: 0x220557DA, res_group: 0, instance }
+}
+
+/// A merged package with a version 1 manifest: "a" holds resources 1 and 2, "b" holds 3
+fn write_v1_merge(path: &std::path::Path) -> HashMap<TGI, (Vec<u8>, u32, u16, u16)> {
+    let manifest = ManifestResource {
+        version: 1,
+        padding: 0,
+        entries: vec![
+            ManifestEntry { name: "a".to_string(), resources: vec![tgi(1), tgi(2)], checksums: Vec::new() },
+            ManifestEntry { name: "b".to_string(), resources: vec![tgi(3)], checksums: Vec::new() },
+        ],
+    };
+    let manifest_data = manifest.to_bytes().unwrap();
+
+    let mut entries = HashMap::new();
+    entries.insert(TGI { res_type: 0x7FB6AD8A, res_group: 0, instance: 0 }, (manifest_data.clone(), manifest_data.len() as u32, 0x5A42, 1));
+    for i in 1..=3u64 {
+        let data = vec![i as u8; 32];
+        entries.insert(tgi(i), (data, 32, 0x5A42, 1));
+    }
+    Package::write_merged(path, &entries, true).unwrap();
+    entries
+}
+
+#[test]
+fn test_manifest_versions_round_trip() {
+    let mut manifest = ManifestResource {
+        version: 1,
+        padding: 0,
+        entries: vec![ManifestEntry { name: "a".to_string(), resources: vec![tgi(1)], checksums: Vec::new() }],
+    };
+    let v1 = manifest.to_bytes().unwrap();
+    assert_eq!(v1.len(), 4 + 8 + 4 + 4 + 1 + 4 + 16);
+    assert!(ManifestResource::from_bytes(&v1).unwrap().entries[0].checksums.is_empty());
+
+    manifest.version = 2;
+    manifest.entries[0].checksums = vec![ResourceChecksum::of(b"abc")];
+    let reparsed = ManifestResource::from_bytes(&manifest.to_bytes().unwrap()).unwrap();
+    assert!(reparsed.has_checksums());
+    assert_eq!(reparsed.entries[0].checksums, vec![ResourceChecksum { size: 3, hash: 0xE71FA2190541574B }]);
+}
+
+#[test]
+fn test_upgrade_then_verify() {
+    let dir = std::env::temp_dir().join(format!("s4pi_manifest_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("merged.package");
+    let mut entries = write_v1_merge(&path);
+
+    let outcome = upgrade(&path).unwrap();
+    assert_eq!(outcome, UpgradeOutcome::Upgraded { from: 1, resources: 3, missing: Vec::new() });
+    assert_eq!(upgrade(&path).unwrap(), UpgradeOutcome::AlreadyCurrent { version: 2 });
+
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries.len(), 4);
+    let (_, manifest) = read_manifest(&pkg).unwrap();
+    assert_eq!(manifest.version, 2);
+    assert_eq!(manifest.entries[0].checksums[1], ResourceChecksum::of(&[2u8; 32]));
+    assert!(verify(&pkg, &manifest).is_empty());
+    drop(pkg);
+
+    // Damage resource 3 while keeping the upgraded manifest
+    let manifest_tgi = TGI { res_type: 0x7FB6AD8A, res_group: 0, instance: 0 };
+    let manifest_data = manifest.to_bytes().unwrap();
+    entries.insert(manifest_tgi, (manifest_data.clone(), manifest_data.len() as u32, 0x5A42, 1));
+    entries.insert(tgi(3), (vec![9u8; 32], 32, 0x5A42, 1));
+    entries.remove(&tgi(2));
+    Package::write_merged(&path, &entries, true).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let issues = verify(&pkg, &manifest);
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0], VerifyIssue::Missing { package: "a".to_string(), tgi: tgi(2) });
+    assert!(matches!(&issues[1], VerifyIssue::Mismatch { package, .. } if package == "b"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_merged_package_view() {
+    let dir = std::env::temp_dir().join(format!("s4pi_merged_view_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("merged.package");
+    write_v1_merge(&path);
+
+    let view = MergedPackageView::open(&path).unwrap();
+    assert_eq!(view.packages().collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(view.resources_of("a").unwrap(), &[tgi(1), tgi(2)]);
+    assert_eq!(view.resources_of("b.package").unwrap(), &[tgi(3)]);
+    assert!(view.resources_of("c").is_err());
+    assert_eq!(view.read_raw_resource(&tgi(2)).unwrap(), vec![2u8; 32]);
+
+    let extracted = dir.join("b.package");
+    view.extract("b", &extracted).unwrap();
+    let pkg = Package::open(&extracted).unwrap();
+    assert_eq!(pkg.entries.len(), 1);
+    let entry = pkg.entries[0].clone();
+    assert_eq!(entry.tgi, tgi(3));
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), vec![3u8; 32]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_replace_subpackage() {
+    let dir = std::env::temp_dir().join(format!("s4pi_replace_subpackage_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("merged.package");
+    write_v1_merge(&path);
+    upgrade(&path).unwrap();
+
+    // The new "a" changes 1, drops 2, adds 4 and also has 3, which "b" was merged after
+    let new_a = dir.join("a.package");
+    let mut entries = HashMap::new();
+    for (i, data) in [(1u64, vec![9u8; 40]), (3, vec![7u8; 32]), (4, vec![4u8; 16])] {
+        entries.insert(tgi(i), (data.clone(), data.len() as u32, 0x5A42, 1));
+    }
+    Package::write_merged(&new_a, &entries, true).unwrap();
+
+    let update = replace_subpackage(&path, "a", &new_a).unwrap();
+    assert_eq!(update, SubpackageUpdate {
+        added: vec![tgi(4)],
+        updated: vec![tgi(1)],
+        removed: vec![tgi(2)],
+        unchanged: 0,
+        shadowed: vec![tgi(3)],
+    });
+
+    let view = MergedPackageView::open(&path).unwrap();
+    assert_eq!(view.resources_of("a").unwrap(), &[tgi(1), tgi(3), tgi(4)]);
+    assert_eq!(view.resources_of("b").unwrap(), &[tgi(3)]);
+    assert_eq!(view.read_raw_resource(&tgi(1)).unwrap(), vec![9u8; 40]);
+    assert_eq!(view.read_raw_resource(&tgi(3)).unwrap(), vec![3u8; 32]);
+    assert!(view.find(&tgi(2)).is_none());
+    drop(view);
+
+    let pkg = Package::open(&path).unwrap();
+    let (_, manifest) = read_manifest(&pkg).unwrap();
+    assert!(verify(&pkg, &manifest).is_empty());
+    drop(pkg);
+
+    let again = replace_subpackage(&path, "a", &new_a).unwrap();
+    assert_eq!(again.unchanged, 2);
+    assert!(again.added.is_empty() && again.updated.is_empty() && again.removed.is_empty());
+
+    // A new "b" without 3 would leave "a"'s copy of it, which the merge didn't keep, to restore
+    let new_b = dir.join("b.package");
+    let mut entries = HashMap::new();
+    entries.insert(tgi(5), (vec![5u8; 8], 8, 0x5A42, 1));
+    Package::write_merged(&new_b, &entries, true).unwrap();
+    let err = replace_subpackage(&path, "b", &new_b).unwrap_err();
+    assert!(err.to_string().contains(&tgi(3).to_string()));
+    let view = MergedPackageView::open(&path).unwrap();
+    assert_eq!(view.resources_of("b").unwrap(), &[tgi(3)]);
+    assert!(view.find(&tgi(5)).is_none());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_content_descriptor() {
+    let dir = std::env::temp_dir().join(format!("s4pi_descriptor_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("shared")).unwrap();
+    let mut opened = Vec::new();
+    for (name, resources) in [("a.package", vec![(1u64, 40usize), (2, 8)]), ("b.package", vec![(3, 32)])] {
+        let mut entries = HashMap::new();
+        for (i, size) in resources {
+            entries.insert(tgi(i), (vec![i as u8; size], size as u32, 0, 1));
+        }
+        Package::write_merged(dir.join(name), &entries, true).unwrap();
+        opened.push((name, Package::open(dir.join(name)).unwrap()));
+    }
+    assert!(!is_descriptor(&opened[0].1));
+
+    let path = dir.join("shared").join("mods.package");
+    let packages: Vec<(&str, &Package)> = opened.iter().map(|(name, pkg)| (*name, pkg)).collect();
+    assert_eq!(write_descriptor(&path, &packages).unwrap(), 3);
+
+    let descriptor = Package::open(&path).unwrap();
+    assert!(is_descriptor(&descriptor));
+    assert!(descriptor.entries.iter().filter(|e| e.tgi.res_type == 0x220557DA).all(|e| e.filesize == 0));
+    let manifest = read_descriptor(&descriptor).unwrap().unwrap();
+    assert_eq!(manifest.entries[0].resources.len(), 2);
+    assert_eq!(manifest.entries[1].checksums, vec![ResourceChecksum::of(&[3u8; 32])]);
+
+    let census = Census::scan(dir.join("shared"));
+    let names: Vec<String> = census.packages.iter().map(|p| p.to_string_lossy().replace('\\', "/")).collect();
+    assert_eq!(names, vec!["mods.package/a.package", "mods.package/b.package"]);
+    assert_eq!(census.resources[&tgi(1)][0].size, 40);
+
+    // Scanned on its own, the descriptor lines up with the folder it describes
+    let folder = Census::scan(&dir);
+    let shared = Census::scan(&path);
+    assert_eq!(shared.packages, vec![std::path::PathBuf::from("a.package"), "b.package".into()]);
+    assert_eq!(shared.resources[&tgi(3)][0], folder.resources[&tgi(3)][0]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -9,6 +9,22 @@ fn test_catalog_parsing() {
     data.extend_from_slice(&0x55667788u32.to_le_bytes()); // desc hash
     data.extend_from_slice(&100u32.to_le_bytes()); // price
     data.extend_from_slice(&0x123456789ABCDEF0u64.to_le_bytes()); // thumbnail hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // dev category flags
+    data.push(0); // product styles count
+    data.push(0); // unused2 (common version < 10)
+    data.extend_from_slice(&0u32.to_le_bytes()); // legacy tag count
+    data.extend_from_slice(&0u32.to_le_bytes()); // selling point count
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlock by hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlocked by hash
+    data.extend_from_slice(&0u16.to_le_bytes()); // swatch colors sort priority
+    data.extend_from_slice(&0u64.to_le_bytes()); // variant thumb image hash
+    data.extend_from_slice(&[0u8; 16]); // aural materials version + 3 materials
+    data.extend_from_slice(&1u32.to_le_bytes()); // aural properties version
+    data.extend_from_slice(&0u32.to_le_bytes()); // aural quality
+    data.extend_from_slice(&[0u8; 12]); // unused0..2
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags high
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags low
+    data.extend_from_slice(&0u64.to_le_bytes()); // slot type set
     
     let res = CatalogResource::from_bytes(&data).unwrap();
     assert_eq!(res.version, 0x19);
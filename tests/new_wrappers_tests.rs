@@ -1,4 +1,4 @@
-use s4pi_reforged::{Resource, ThumbnailResource, ComplateResource, TxtcResource, ObjKeyResource, SimModifierResource, BoneResource};
+use s4pi_reforged::{Resource, ThumbnailKind, ThumbnailResource, TypedResource, ComplateResource, TxtcResource, ObjKeyResource, SimModifierResource, BoneResource, BlendGeometryResource, ClipResource};
 
 #[test]
 fn test_thumbnail_parsing() {
@@ -13,6 +13,28 @@ fn test_thumbnail_parsing() {
     assert_eq!(to_bytes, data);
 }
 
+#[test]
+fn test_thumbnail_kind() {
+    assert_eq!(ThumbnailKind::of(0x3C1AF1F2), Some(ThumbnailKind::CasPart));
+    assert_eq!(ThumbnailKind::of(0x3C2A8647), Some(ThumbnailKind::Object));
+    assert_eq!(ThumbnailKind::of(0x0589DC47), Some(ThumbnailKind::SizeVariant { family: 0x0589DC44, size: 3 }));
+    assert_eq!(ThumbnailKind::of(0x0580A2B7), None);
+    assert_eq!(ThumbnailKind::of(0x220557DA), None);
+    assert_eq!(ThumbnailKind::SizeVariant { family: 0x0580A2B4, size: 1 }.label(), "0580A2B4_size1");
+
+    // Every type dispatched as a thumbnail has a kind
+    for res_type in [
+        0x0D338A3A, 0x16CCF748, 0x3BD45407, 0x3C1AF1F2, 0x3C2A8647, 0x5B282D45, 0xCD9DE247, 0xE18CAEE2,
+        0xE254AE6E, 0x0580A2B6, 0x0589DC44, 0x05B1769A, 0x05B1B526, 0x2653E3CA, 0x2D4284F2, 0x5DE9DBA2,
+        0x626F60CE, 0x9C925813, 0xA1FF2FC4, 0xAD366F95, 0xAD366F96, 0xFCEAB65B,
+    ] {
+        let TypedResource::Thumbnail(thumb) = TypedResource::from_bytes(res_type, &[0xFF, 0xD8]).unwrap() else {
+            panic!("0x{:08X} is not dispatched as a thumbnail", res_type);
+        };
+        assert!(thumb.kind.is_some(), "0x{:08X}", res_type);
+    }
+}
+
 #[test]
 fn test_complate_parsing() {
     let mut data = Vec::new();
@@ -73,3 +95,55 @@ fn test_bone_parsing() {
     assert_eq!(bone.version, 1);
     assert_eq!(bone.raw_data, data);
 }
+
+#[test]
+fn test_blend_geometry_parsing() {
+    let mut data = b"BGEO".to_vec();
+    for value in [0x600u32, 1, 2, 3] {
+        data.extend_from_slice(&value.to_le_bytes()); // version, LODs, vertices, vectors
+    }
+    for value in [0u32, 2, 3] {
+        data.extend_from_slice(&value.to_le_bytes()); // LOD 0: index base, vertices, vectors
+    }
+    data.extend_from_slice(&0b11u16.to_le_bytes()); // position + normal at offset 0
+    data.extend_from_slice(&((2u16 << 2) | 1).to_le_bytes()); // position at offset 2
+    for value in [8000u16, 0x8000 | 4000, 0, 0, 0, 0, 0, 0, 0] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let TypedResource::BlendGeometry(bgeo) = TypedResource::from_bytes(0x067CAA11, &data).unwrap() else {
+        panic!("Expected BlendGeometry resource");
+    };
+    assert_eq!(bgeo.version, 0x600);
+    assert_eq!(bgeo.vectors.len(), 3);
+    let vertices = bgeo.lod_vertices(0).unwrap();
+    assert!(vertices[0].has_position() && vertices[0].has_normal());
+    assert!(vertices[1].has_position() && !vertices[1].has_normal());
+    assert_eq!(vertices[1].offset(), 2);
+    assert_eq!(bgeo.vectors[0].to_f32(), [1.0, -0.5, 0.0]);
+    assert!(bgeo.lod_vertices(1).is_none());
+    assert_eq!(BlendGeometryResource::from_bytes(&data).unwrap().to_bytes().unwrap(), data);
+}
+
+#[test]
+fn test_clip_header() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&11u32.to_le_bytes()); // version
+    data.extend_from_slice(&0u32.to_le_bytes()); // flags
+    data.extend_from_slice(&2.5f32.to_le_bytes()); // duration
+    data.extend_from_slice(&[0u8; 7 * 4]); // initial offset
+    data.extend_from_slice(&[0u8; 4 * 4]); // namespace and joint hashes
+    data.extend_from_slice(&6u32.to_le_bytes());
+    data.extend_from_slice(b"a_pose");
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(b"rig");
+
+    let clip = ClipResource::from_bytes(&data).unwrap();
+    let header = clip.header().unwrap();
+    assert_eq!(header.duration, 2.5);
+    assert_eq!(header.clip_name.as_deref(), Some("a_pose"));
+    assert_eq!(header.rig_namespace, "rig");
+
+    data.truncate(data.len() - 5);
+    assert!(ClipResource::from_bytes(&data).unwrap().header().is_err());
+}
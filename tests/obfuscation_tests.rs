@@ -0,0 +1,61 @@
+use s4pi_reforged::package::obfuscation::{Anomaly, Verdict};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::io::Write;
+
+fn entry(instance: u64, memsize: u32, compression: u16) -> IndexEntry {
+    IndexEntry { tgi: TGI { res_type: 0x545AC67A, res_group: 0, instance }, offset: 0, filesize: 0, memsize, compression, committed: 1 }
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_check_obfuscation_and_recover() {
+    let dir = std::env::temp_dir().join(format!("s4pi_obfuscation_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let plain = b"plain resource data".repeat(20);
+    let packed = zlib(&plain);
+
+    let path = dir.join("obfuscated.package");
+    Package::write_stored(&path, &[
+        // Stored plain, flagged as zlib
+        (entry(1, plain.len() as u32, 0x5A42), plain.clone()),
+        // Stored as zlib, flagged as plain
+        (entry(2, plain.len() as u32, 0), packed.clone()),
+        // A compression code the game doesn't have
+        (entry(3, plain.len() as u32, 0x1234), packed.clone()),
+        (entry(4, plain.len() as u32, 0), plain.clone()),
+        // Neither plain nor compressed at the size it claims
+        (entry(5, 4096, 0x5A42), plain.clone()),
+    ]).unwrap();
+
+    let mut pkg = Package::open(&path).unwrap();
+    assert!(pkg.read_raw_resource(&pkg.entries[0]).is_err());
+    let report = pkg.check_obfuscation();
+    assert_eq!(report.verdict, Verdict::Obfuscated);
+    assert!(report.summary().starts_with("intentionally obfuscated: 2 wrong compression flag, 1 unknown compression code, 1 undecodable"));
+    assert_eq!(report.anomalies[2].1, Anomaly::UnknownCompression { claimed: 0x1234, actual: 0x5A42 });
+    assert_eq!(report.lost, vec![pkg.entries[4].tgi]);
+    assert_eq!(report.recovered.len(), 4);
+
+    pkg.recover(&report);
+    for entry in pkg.entries.clone() {
+        assert_eq!(pkg.read_raw_resource(&entry).unwrap(), plain);
+    }
+    let cleaned = dir.join("cleaned.package");
+    pkg.save_as(&cleaned).unwrap();
+    assert!(Package::open(&cleaned).unwrap().check_obfuscation().is_clean());
+
+    // A single bad resource is damage, not obfuscation
+    let damaged = dir.join("damaged.package");
+    Package::write_stored(&damaged, &[
+        (entry(1, plain.len() as u32, 0), plain.clone()),
+        (entry(2, 4096, 0x5A42), plain.clone()),
+    ]).unwrap();
+    assert_eq!(Package::open(&damaged).unwrap().check_obfuscation().verdict, Verdict::Damaged);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
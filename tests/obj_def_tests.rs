@@ -1,4 +1,4 @@
-use s4pi_reforged::{TypedResource, ObjectProperty};
+use s4pi_reforged::{Resource, TypedResource, ObjectProperty, ObjectPropertyId, TGI};
 use std::io::{Write, Cursor};
 
 #[test]
@@ -23,7 +23,7 @@ fn test_object_definition_parsing() {
     if let TypedResource::ObjectDefinition(obj) = res {
         assert_eq!(obj.version, 1);
         assert_eq!(obj.properties.len(), 1);
-        if let Some(ObjectProperty::String(name)) = obj.properties.get(&0xE7F07786) {
+        if let Some(ObjectProperty::String(name)) = obj.properties.get(&ObjectPropertyId::Name) {
             assert_eq!(name, "Test");
         } else {
             panic!("Expected String property for Name");
@@ -32,3 +32,95 @@ fn test_object_definition_parsing() {
         panic!("Expected ObjectDefinition resource");
     }
 }
+
+#[test]
+fn test_object_definition_accessors() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&1u16.to_le_bytes()); // version
+    data.extend_from_slice(&6u32.to_le_bytes()); // table offset
+    data.extend_from_slice(&3u16.to_le_bytes()); // entry count
+    for (id, offset) in [(0xE4F4FAA4u32, 32u32), (0x8D20ACC6, 36), (0x12345678, 56)] {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&450u32.to_le_bytes()); // price
+    data.extend_from_slice(&16u32.to_le_bytes()); // one model TGI, stored ITG with the instance halves swapped
+    data.extend_from_slice(&0x0000000100000002u64.to_le_bytes());
+    data.extend_from_slice(&0x01661233u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+
+    let TypedResource::ObjectDefinition(mut obj) = TypedResource::from_bytes(0xC0DB5AE7, &data).unwrap() else {
+        panic!("Expected ObjectDefinition resource");
+    };
+    assert_eq!(obj.simoleon_price(), Some(450));
+    assert_eq!(obj.models(), Some(&[TGI { res_type: 0x01661233, res_group: 0, instance: 0x0000000200000001 }][..]));
+    assert_eq!(obj.name(), None);
+    assert!(obj.properties.contains_key(&ObjectPropertyId::Other(0x12345678)));
+
+    obj.set_name("Chair");
+    obj.set_simoleon_price(99);
+    assert_eq!(obj.name(), Some("Chair"));
+    assert_eq!(obj.simoleon_price(), Some(99));
+
+    assert_eq!(ObjectPropertyId::from_id(0xB994039B), ObjectPropertyId::TuningId);
+    assert_eq!(ObjectPropertyId::TuningId.id(), 0xB994039B);
+    assert_eq!(ObjectPropertyId::from_id(7).id(), 7);
+    assert_eq!(ObjectPropertyId::from_id(ObjectPropertyId::Unknown4.id()), ObjectPropertyId::Unknown4);
+
+    // set only takes the kind of value the property is stored as
+    assert!(obj.set(ObjectPropertyId::SimoleonPrice, ObjectProperty::String("free".to_string())).is_err());
+    assert!(obj.set(ObjectPropertyId::Other(0xE7F07786), ObjectProperty::Unknown(vec![0])).is_err());
+    assert!(obj.set(ObjectPropertyId::Other(7), ObjectProperty::UInt32(1)).is_err());
+    assert_eq!(obj.simoleon_price(), Some(99));
+    obj.set(ObjectPropertyId::SimoleonPrice, ObjectProperty::UInt32(120)).unwrap();
+    obj.set(ObjectPropertyId::Other(7), ObjectProperty::Unknown(vec![1, 2])).unwrap();
+    assert_eq!(obj.simoleon_price(), Some(120));
+}
+
+#[test]
+fn test_object_definition_write() {
+    // Laid out as the game does: properties first, then the table
+    let mut data = Vec::new();
+    data.extend_from_slice(&2u16.to_le_bytes()); // version
+    data.extend_from_slice(&0u32.to_le_bytes()); // table offset, patched below
+    let mut table = Vec::new();
+    table.push((0xE7F07786u32, data.len() as u32)); // name
+    data.extend_from_slice(&5u32.to_le_bytes());
+    data.extend_from_slice(b"Chair");
+    table.push((0x0BADF00D, data.len() as u32)); // unknown, kept as it is
+    data.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7]);
+    table.push((0x8D20ACC6, data.len() as u32)); // models
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&0x0000000100000002u64.to_le_bytes());
+    data.extend_from_slice(&0x01661233u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    table.push((0xE4F4FAA4, data.len() as u32)); // price
+    data.extend_from_slice(&450u32.to_le_bytes());
+    let table_offset = data.len() as u32;
+    data[2..6].copy_from_slice(&table_offset.to_le_bytes());
+    data.extend_from_slice(&(table.len() as u16).to_le_bytes());
+    for (id, offset) in table {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    let TypedResource::ObjectDefinition(mut obj) = TypedResource::from_bytes(0xC0DB5AE7, &data).unwrap() else {
+        panic!("Expected ObjectDefinition resource");
+    };
+    assert!(matches!(obj.get(ObjectPropertyId::Other(0x0BADF00D)), Some(ObjectProperty::Unknown(bytes)) if bytes == &[1, 2, 3, 4, 5, 6, 7]));
+    assert_eq!(obj.to_bytes().unwrap(), data);
+
+    // Edits move the properties after them; added properties go at the end
+    obj.set_name("Armchair");
+    obj.set_tuning_id(0x1234);
+    let written = obj.to_bytes().unwrap();
+    let TypedResource::ObjectDefinition(reread) = TypedResource::from_bytes(0xC0DB5AE7, &written).unwrap() else {
+        panic!("Expected ObjectDefinition resource");
+    };
+    assert_eq!(reread.name(), Some("Armchair"));
+    assert_eq!(reread.tuning_id(), Some(0x1234));
+    assert_eq!(reread.simoleon_price(), Some(450));
+    assert_eq!(reread.models(), obj.models());
+    assert!(matches!(reread.get(ObjectPropertyId::Other(0x0BADF00D)), Some(ObjectProperty::Unknown(bytes)) if bytes == &[1, 2, 3, 4, 5, 6, 7]));
+    assert_eq!(reread.order.last(), Some(&ObjectPropertyId::TuningId));
+}
@@ -0,0 +1,102 @@
+use s4pi_reforged::objcheck::{check_objects, KnownResources, ObjectIssue, OBJECT_TUNING_TYPE};
+use s4pi_reforged::package::resource::StblEntry;
+use s4pi_reforged::preview::{COBJ_TYPE, OBJD_TYPE};
+use s4pi_reforged::{Package, Resource, StblResource, TGI};
+use std::collections::HashMap;
+
+const MODEL: TGI = TGI { res_type: 0x01661233, res_group: 0, instance: 0x0000000200000001 };
+
+/// An OBJD with a tuning ID and one model
+fn objd_bytes(tuning_id: u64, model: TGI) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&1u16.to_le_bytes()); // version
+    data.extend_from_slice(&6u32.to_le_bytes()); // table offset
+    data.extend_from_slice(&2u16.to_le_bytes()); // entry count
+    for (id, offset) in [(0xB994039Bu32, 24u32), (0x8D20ACC6, 32)] {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&tuning_id.to_le_bytes());
+    data.extend_from_slice(&16u32.to_le_bytes()); // one model TGI, stored ITG with the instance halves swapped
+    data.extend_from_slice(&model.instance.rotate_left(32).to_le_bytes());
+    data.extend_from_slice(&model.res_type.to_le_bytes());
+    data.extend_from_slice(&model.res_group.to_le_bytes());
+    data
+}
+
+/// A version 9 COBJ with the given name and description hashes
+fn cobj_bytes(name_hash: u32, description_hash: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x19u32.to_le_bytes()); // version
+    data.extend_from_slice(&0x09u32.to_le_bytes()); // common version
+    data.extend_from_slice(&name_hash.to_le_bytes());
+    data.extend_from_slice(&description_hash.to_le_bytes());
+    data.extend_from_slice(&100u32.to_le_bytes()); // price
+    data.extend_from_slice(&0u64.to_le_bytes()); // thumbnail hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // dev category flags
+    data.push(0); // product styles count
+    data.push(0); // unused2 (common version < 10)
+    data.extend_from_slice(&0u32.to_le_bytes()); // legacy tag count
+    data.extend_from_slice(&0u32.to_le_bytes()); // selling point count
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlock by hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlocked by hash
+    data.extend_from_slice(&0u16.to_le_bytes()); // swatch colors sort priority
+    data.extend_from_slice(&0u64.to_le_bytes()); // variant thumb image hash
+    data.extend_from_slice(&[0u8; 16]); // aural materials version + 3 materials
+    data.extend_from_slice(&1u32.to_le_bytes()); // aural properties version
+    data.extend_from_slice(&0u32.to_le_bytes()); // aural quality
+    data.extend_from_slice(&[0u8; 12]); // unused0..2
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags high
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags low
+    data.extend_from_slice(&0u64.to_le_bytes()); // slot type set
+    data
+}
+
+#[test]
+fn test_check_objects() {
+    let dir = std::env::temp_dir().join(format!("s4pi_objcheck_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut stbl = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: vec![StblEntry { key_hash: 0x11223344, flags: 0, string_value: "Comfy Chair".to_string() }],
+    };
+    stbl.recalculate_string_length();
+
+    // 0xA is complete apart from its description; 0xB points at tuning and a model that don't
+    // exist and has no catalog entry
+    let mut entries = HashMap::new();
+    for (tgi, data) in [
+        (TGI { res_type: OBJD_TYPE, res_group: 0, instance: 0xA }, objd_bytes(0x77, MODEL)),
+        (TGI { res_type: COBJ_TYPE, res_group: 0, instance: 0xA }, cobj_bytes(0x11223344, 0x55667788)),
+        (TGI { res_type: OBJECT_TUNING_TYPE, res_group: 0, instance: 0x77 }, b"<I/>".to_vec()),
+        (MODEL, vec![0; 4]),
+        (TGI { res_type: 0x220557DA, res_group: 0, instance: 0xAA }, stbl.to_bytes().unwrap()),
+        (TGI { res_type: OBJD_TYPE, res_group: 0, instance: 0xB }, objd_bytes(0x78, TGI { instance: 0x99, ..MODEL })),
+    ] {
+        entries.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+    let path = dir.join("objects.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+    let pkg = Package::open(&path).unwrap();
+
+    let mut checks = check_objects(&pkg, &KnownResources::default());
+    checks.sort_by_key(|c| c.objd.instance);
+    assert_eq!(checks.len(), 2);
+    assert_eq!(checks[0].issues, vec![ObjectIssue::UnresolvedString { field: "description", hash: 0x55667788 }]);
+    assert_eq!(checks[1].issues, vec![
+        ObjectIssue::MissingTuning { tuning_id: 0x78 },
+        ObjectIssue::MissingReference { property: "model", tgi: TGI { instance: 0x99, ..MODEL } },
+        ObjectIssue::NoCatalogEntry,
+    ]);
+    assert!(checks[1].issues[1].to_string().contains("not in the package or the game"));
+
+    let known = KnownResources { game: None, string_keys: [0x55667788].into_iter().collect() };
+    let checks = check_objects(&pkg, &known);
+    assert!(checks.iter().find(|c| c.objd.instance == 0xA).unwrap().issues.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,86 @@
+use s4pi_reforged::census::Census;
+use s4pi_reforged::merge::{merge, unmerge, MergeOptions};
+use s4pi_reforged::observer::OperationObserver;
+use s4pi_reforged::{IndexEntry, Package, TgiFilter, TGI};
+use std::sync::Mutex;
+
+/// Every event an operation reports, in order
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl OperationObserver for Recorder {
+    fn on_start(&self, operation: &str, total: Option<usize>) {
+        self.events.lock().unwrap().push(format!("start {} {:?}", operation, total));
+    }
+
+    fn on_progress(&self, done: usize, total: Option<usize>, _item: &str) {
+        self.events.lock().unwrap().push(format!("progress {}/{:?}", done, total));
+    }
+
+    fn on_item_skipped(&self, item: &str, _reason: &str) {
+        self.events.lock().unwrap().push(format!("skipped {}", item));
+    }
+
+    fn on_finish(&self, operation: &str, success: bool) {
+        self.events.lock().unwrap().push(format!("finish {} {}", operation, success));
+    }
+}
+
+fn write_package(path: &std::path::Path, instance: u64) {
+    let data = format!("resource {}", instance).into_bytes();
+    let tgi = TGI { res_type: 0x545AC67A, res_group: 0, instance };
+    let entry = IndexEntry { tgi, offset: 0, filesize: 0, memsize: data.len() as u32, compression: 0, committed: 1 };
+    Package::write_stored(path, &[(entry, data)]).unwrap();
+}
+
+#[test]
+fn test_operation_observer() {
+    let dir = std::env::temp_dir().join(format!("s4pi_observer_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    write_package(&dir.join("a.package"), 1);
+    write_package(&dir.join("b.package"), 2);
+    std::fs::write(dir.join("broken.package"), b"not a package").unwrap();
+    let recorder = Recorder::default();
+
+    let summary = merge(&dir, &MergeOptions::default(), &recorder).unwrap();
+    assert_eq!((summary.files_processed, summary.files_skipped, summary.total_resources), (2, 1, 2));
+    assert_eq!(summary.outputs, [dir.join("merged").join("merged.package")]);
+    let events = recorder.take();
+    assert_eq!(events.first().unwrap(), "start merge Some(3)");
+    assert_eq!(events.last().unwrap(), "finish merge true");
+    assert!(events.contains(&"skipped broken.package".to_string()));
+    assert_eq!(events.iter().filter(|e| e.starts_with("progress")).count(), 3);
+
+    let census = Census::scan_with(&dir, &recorder);
+    assert_eq!(census.packages.len(), 4, "the merged package is counted too");
+    let events = recorder.take();
+    assert_eq!(events.first().unwrap(), "start scan Some(4)");
+    assert_eq!(events.iter().filter(|e| e.starts_with("skipped")).count(), 1);
+
+    let summary = unmerge(&summary.outputs[0], &recorder).unwrap();
+    assert_eq!(summary.packages.len(), 2);
+    assert!(summary.verified);
+    assert_eq!(summary.damaged, 0);
+    let events = recorder.take();
+    assert_eq!(&events[..2], ["start verify Some(2)", "progress 1/Some(2)"]);
+    assert!(events.contains(&"start unmerge Some(2)".to_string()));
+    assert_eq!(events.last().unwrap(), "finish unmerge true");
+
+    let pkg = Package::open(dir.join("a.package")).unwrap();
+    let extracted = s4pi_reforged::import::extract_resources(&pkg, &TgiFilter::default(), dir.join("resources"), &recorder).unwrap();
+    assert_eq!(extracted.extracted, 1);
+    assert_eq!(recorder.take(), ["start extract Some(1)", "progress 1/Some(1)", "finish extract true"]);
+
+    // Observing nothing is always an option
+    assert_eq!(merge(&dir.join("resources"), &MergeOptions::default(), &()).unwrap().files_processed, 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,723 @@
+use s4pi_reforged::package::header::{HeaderIssue, IndexLocation, PackageHeader};
+use s4pi_reforged::{sort_entries_for_game, CompressionScheme, GenericResource, IndexEntry, Package, Resource, TGI, TypedResource};
+use std::collections::HashMap;
+
+#[test]
+fn test_tgi_parse_and_display() {
+    let expected = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: 0x00A1B2C3D4E5F601 };
+    for text in [
+        "220557DA:80000000:00A1B2C3D4E5F601",
+        "0x220557DA!0x80000000!0x00A1B2C3D4E5F601",
+        "220557da-80000000-a1b2c3d4e5f601",
+        "220557DA_80000000_00A1B2C3D4E5F601",
+    ] {
+        assert_eq!(text.parse::<TGI>().unwrap(), expected, "{}", text);
+    }
+    assert_eq!(expected.to_string(), "220557DA:80000000:00A1B2C3D4E5F601");
+    assert!("220557DA:80000000".parse::<TGI>().is_err());
+    assert!("XYZ:0:0".parse::<TGI>().is_err());
+}
+
+#[test]
+fn test_find_entry() {
+    let dir = std::env::temp_dir().join(format!("s4pi_package_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("find.package");
+
+    let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 0x42 };
+    let mut entries = HashMap::new();
+    entries.insert(tgi, (b"<I/>".to_vec(), 4, 0, 1));
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let entry = pkg.find(&tgi).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), b"<I/>");
+    assert!(pkg.find(&TGI { instance: 0x43, ..tgi }).is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_write_merged_keeps_data_that_looks_compressed() {
+    let dir = std::env::temp_dir().join(format!("s4pi_lookalike_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // Decompressed payloads that start with a zlib header byte or carry the RefPack signature
+    let zlib_like = b"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx".to_vec();
+    let refpack_like = [0x10, 0xFB, 0, 0, 64].iter().copied().chain(std::iter::repeat_n(7u8, 59)).collect::<Vec<u8>>();
+    let mut entries = HashMap::new();
+    for (i, data) in [zlib_like, refpack_like].into_iter().enumerate() {
+        let tgi = TGI { res_type: 0x00B2D882, res_group: 0, instance: i as u64 };
+        entries.insert(tgi, (data.clone(), data.len() as u32, 0x5A42, 1));
+    }
+    let path = dir.join("lookalike.package");
+    Package::write_merged(&path, &entries, true).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    for (tgi, (data, ..)) in &entries {
+        let entry = pkg.find(tgi).cloned().unwrap();
+        assert_eq!(&pkg.read_raw_resource(&entry).unwrap(), data);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_write_merged_keeps_compression_codes() {
+    let dir = std::env::temp_dir().join(format!("s4pi_codes_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // "ABCDEFGH" as RefPack: flags 0x80 (4-byte size), signature, size 8, one 8-byte literal run, stop
+    let refpack = [0x80, 0xFB, 0, 0, 0, 8, 0xE1, b'A', b'B', b'C', b'D', b'E', b'F', b'G', b'H', 0xFC].to_vec();
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, b"ABCDEFGH").unwrap();
+    let zlib = encoder.finish().unwrap();
+
+    let refpack_tgi = TGI { res_type: 0x00B2D882, res_group: 0, instance: 1 };
+    let zlib_tgi = TGI { instance: 2, ..refpack_tgi };
+    let stored_tgi = TGI { instance: 3, ..refpack_tgi };
+    let mut entries = HashMap::new();
+    entries.insert(refpack_tgi, (refpack, 8, 0xFFFF, 1));
+    entries.insert(zlib_tgi, (zlib, 8, 0x5A42, 1));
+    entries.insert(stored_tgi, (b"ABCDEFGH".to_vec(), 8, 0, 1));
+    let path = dir.join("codes.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    for (tgi, code) in [(refpack_tgi, 0xFFFF), (zlib_tgi, 0x5A42), (stored_tgi, 0)] {
+        let entry = pkg.find(&tgi).cloned().unwrap();
+        assert_eq!(entry.compression, code, "{}", tgi);
+        assert_eq!(pkg.read_raw_resource(&entry).unwrap(), b"ABCDEFGH", "{}", tgi);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_game_sort_order() {
+    let tgi = |res_type: u32, res_group: u32, instance: u64| TGI { res_type, res_group, instance };
+    let manifest = tgi(0x7FB6AD8A, 0, 0);
+    let expected = vec![
+        tgi(0x00B2D882, 0, 5),
+        tgi(0x00B2D882, 0x80000000, 1),
+        tgi(0x220557DA, 0, 0x8000000000000000),
+        tgi(0x220557DA, 0, 0xFFFFFFFFFFFFFFFF),
+        manifest,
+    ];
+    let entry = |tgi: TGI, offset: u32| IndexEntry { tgi, offset, filesize: 0, memsize: 0, compression: 0, committed: 1 };
+    let mut entries: Vec<IndexEntry> = [4, 2, 0, 3, 1].iter().map(|&i| entry(expected[i], i as u32)).collect();
+    entries.push(entry(expected[1], 99));
+    sort_entries_for_game(&mut entries);
+    assert_eq!(entries.iter().map(|e| e.tgi).collect::<Vec<_>>(), [&expected[..2], &expected[1..]].concat());
+    // Duplicates keep the order they were in
+    assert_eq!((entries[1].offset, entries[2].offset), (1, 99));
+
+    // Merged packages are written in the same order, with no type put first
+    let dir = std::env::temp_dir().join(format!("s4pi_game_order_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let resources: HashMap<TGI, (Vec<u8>, u32, u16, u16)> = expected.iter().map(|&tgi| (tgi, (vec![1, 2, 3], 3, 0, 1))).collect();
+    let path = dir.join("sorted.package");
+    Package::write_merged(&path, &resources, false).unwrap();
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries.iter().map(|e| e.tgi).collect::<Vec<_>>(), expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_header_location_and_repair() {
+    let dir = std::env::temp_dir().join(format!("s4pi_header_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("header.package");
+    let entries: HashMap<_, _> = (1..=3u64)
+        .map(|i| (TGI { res_type: 0x220557DA, res_group: 0, instance: i }, (vec![i as u8; 8], 8, 0, 1)))
+        .collect();
+    Package::write_merged(&path, &entries, false).unwrap();
+    let file_len = std::fs::metadata(&path).unwrap().len();
+
+    let pkg = Package::open(&path).unwrap();
+    let location = pkg.header.index_location();
+    assert_eq!(pkg.header.entry_count(), 3);
+    assert_eq!(location.size, 4 + 3 * 32);
+    assert_eq!(location.position + location.size as u64, file_len);
+    assert_eq!(pkg.header.index_position_low as u64, location.position);
+    assert!(pkg.header.validate(file_len).is_empty());
+
+    let mut damaged = PackageHeader { magic: *b"DBPF", major: 2, minor: 1, index_count: 40, ..Default::default() };
+    damaged.set_index_location(IndexLocation { position: location.position, size: location.size });
+    damaged.index_position_low = 0x60;
+    let issues = damaged.validate(file_len);
+    assert!(issues.contains(&HeaderIssue::EntryCountTooLarge { count: 40, index_size: location.size }));
+    assert!(issues.contains(&HeaderIssue::PositionMismatch { position: location.position, legacy: 0x60 }));
+    assert!(damaged.validate(location.position).iter().any(|i| matches!(i, HeaderIssue::IndexOutOfBounds { .. })));
+
+    damaged.repair_from(&pkg.entries);
+    assert_eq!(damaged.entry_count(), 3);
+    assert_eq!(damaged.index_location(), location);
+    assert!(damaged.validate(file_len).is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compression_report() {
+    let dir = std::env::temp_dir().join(format!("s4pi_compression_report_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut entries = HashMap::new();
+    for instance in 0..3u64 {
+        let tgi = TGI { res_type: 0x220557DA, res_group: 0, instance };
+        entries.insert(tgi, (vec![b'a'; 200], 200, 0x5A42, 1));
+    }
+    entries.insert(TGI { res_type: 0x00B2D882, res_group: 0, instance: 9 }, (vec![1, 2, 3, 4], 4, 0, 1));
+    let path = dir.join("stats.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let report = Package::open(&path).unwrap().compression_report();
+    assert_eq!(report.total.count, 4);
+    assert_eq!(report.total.memory_bytes, 604);
+    assert_eq!(report.uncompressed().count, 1);
+    assert_eq!(report.uncompressed().stored_bytes, 4);
+
+    let zlib = report.by_scheme[&CompressionScheme::Zlib];
+    assert_eq!(zlib.count, 3);
+    assert!(zlib.stored_bytes < zlib.memory_bytes);
+    assert!(zlib.ratio() < 1.0);
+
+    let stbl = &report.by_type[&0x220557DA];
+    assert_eq!(stbl.total.count, 3);
+    assert_eq!(stbl.by_scheme.keys().copied().collect::<Vec<_>>(), vec![CompressionScheme::Zlib]);
+    assert_eq!(CompressionScheme::from_code(0x1234), CompressionScheme::Unknown(0x1234));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_merge_stats() {
+    use s4pi_reforged::package::stats::MergeStats;
+
+    let stbl = |instance| TGI { res_type: 0x220557DA, res_group: 0, instance };
+    let image = TGI { res_type: 0x00B2D882, res_group: 0, instance: 9 };
+    let mut stats = MergeStats::default();
+    stats.add_source(&[stbl(1), stbl(2), image]);
+    stats.add_source(&[stbl(1)]);
+    stats.add_dropped(0x220557DA, true);
+    stats.add_dropped(0x00B2D882, false);
+    stats.add_output(&[
+        IndexEntry { tgi: stbl(1), offset: 96, filesize: 20, memsize: 50, compression: 0x5A42, committed: 1 },
+        IndexEntry { tgi: stbl(2), offset: 116, filesize: 10, memsize: 10, compression: 0, committed: 1 },
+        IndexEntry { tgi: image, offset: 126, filesize: 4, memsize: 4, compression: 0, committed: 1 },
+    ]);
+
+    let strings = stats.by_type[&0x220557DA];
+    assert_eq!((strings.merged, strings.source_packages, strings.deduplicated, strings.overridden), (2, 2, 1, 0));
+    assert_eq!((strings.stored_bytes, strings.memory_bytes), (30, 60));
+    assert_eq!(stats.by_type[&0x00B2D882].overridden, 1);
+    let total = stats.total();
+    assert_eq!((total.merged, total.deduplicated, total.overridden, total.stored_bytes), (3, 1, 1, 34));
+}
+
+#[test]
+fn test_write_single() {
+    let dir = std::env::temp_dir().join(format!("s4pi_write_single_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 0x1234 };
+    let tuning = b"<I n=\"fix\">".repeat(50);
+
+    let path = dir.join("override.package");
+    Package::write_single(&path, tgi, tuning.clone(), true).unwrap();
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries.len(), 1);
+    assert_eq!(pkg.entries[0].tgi, tgi);
+    assert_eq!(pkg.entries[0].scheme(), CompressionScheme::Zlib);
+    assert_eq!(pkg.read_raw_resource(&pkg.entries[0]).unwrap(), tuning);
+
+    Package::write_single(&path, tgi, tuning.clone(), false).unwrap();
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries[0].scheme(), CompressionScheme::Uncompressed);
+    assert_eq!(pkg.entries[0].memsize as usize, tuning.len());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_generic_resource_keeps_entry_flags() {
+    let dir = std::env::temp_dir().join(format!("s4pi_generic_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let tgi = TGI { res_type: 0x12345678, res_group: 0, instance: 1 };
+    let entry = IndexEntry { tgi, offset: 0, filesize: 0, memsize: 6, compression: 0, committed: 0 };
+    let path = dir.join("generic.package");
+    Package::write_stored(&path, &[(entry, b"opaque".to_vec())]).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let entry = pkg.entries[0].clone();
+    let TypedResource::Generic(generic) = pkg.read_resource(&entry).unwrap() else { panic!("not generic") };
+    assert_eq!(generic.source.as_ref().map(|e| (e.compression, e.committed)), Some((0, 0)));
+    assert_eq!(generic.merge_entry(), (b"opaque".to_vec(), 6, 0, 0));
+
+    let copy = dir.join("copy.package");
+    Package::write_merged(&copy, &HashMap::from([(tgi, generic.merge_entry())]), false).unwrap();
+    let written = Package::open(&copy).unwrap().entries[0].clone();
+    assert_eq!((written.compression, written.committed), (0, 0));
+
+    assert_eq!(GenericResource::from_bytes(b"new").unwrap().merge_entry().2, 0x5A42);
+
+    // The builder and in-place edits keep them too
+    use s4pi_reforged::package::builder::PackageBuilder;
+    let generic = TypedResource::Generic(generic);
+    let built = PackageBuilder::new().add_typed(tgi, &generic).unwrap().build().unwrap();
+    assert_eq!((built.entries[0].compression, built.entries[0].committed), (0, 0));
+
+    let other = TGI { instance: 2, ..tgi };
+    let mut edited = PackageBuilder::new().add(other, vec![7; 64]).build().unwrap();
+    edited.insert_typed(tgi, &generic).unwrap();
+    let saved = dir.join("edited.package");
+    edited.save_as(&saved).unwrap();
+    let pkg = Package::open(&saved).unwrap();
+    let written = pkg.find(&tgi).unwrap();
+    assert_eq!((written.compression, written.committed), (0, 0));
+    assert_eq!(pkg.find(&other).unwrap().committed, 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_investigate() {
+    use s4pi_reforged::package::investigate::TypeStatus;
+
+    let dir = std::env::temp_dir().join(format!("s4pi_investigate_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut entries = HashMap::new();
+    entries.insert(TGI { res_type: 0x12345678, res_group: 0, instance: 1 }, (b"mystery".to_vec(), 7, 0, 1));
+    entries.insert(TGI { res_type: 0x12345678, res_group: 0, instance: 2 }, (b"mystery2".to_vec(), 8, 0, 1));
+    entries.insert(TGI { res_type: 0x220557DA, res_group: 0, instance: 3 }, (b"not a string table".to_vec(), 18, 0, 1));
+    entries.insert(TGI { res_type: 0x00B2D882, res_group: 0, instance: 4 }, (vec![0u8; 16], 16, 0, 1));
+    let path = dir.join("investigate.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let report = Package::open(&path).unwrap().investigate();
+    assert_eq!(report.resource_count, 4);
+
+    let unknown = &report.types[&0x12345678];
+    assert_eq!((unknown.count, unknown.status), (2, TypeStatus::Unknown));
+    assert_eq!(unknown.sample.as_ref().unwrap().head, b"mystery");
+
+    let failed = &report.types[&0x220557DA];
+    assert_eq!(failed.status, TypeStatus::Failed);
+    assert_eq!(failed.errors.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].head, b"not a string table");
+
+    assert_eq!(report.types[&0x00B2D882].status, TypeStatus::Known);
+    assert!(report.types[&0x00B2D882].sample.is_none());
+    assert_eq!(report.unknown_types().collect::<Vec<_>>(), vec![0x12345678, 0x220557DA]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_edit_and_save() {
+    let dir = std::env::temp_dir().join(format!("s4pi_package_save_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("edit.package");
+
+    let kept = TGI { res_type: 0x0333406C, res_group: 0, instance: 1 };
+    let replaced = TGI { instance: 2, ..kept };
+    let removed = TGI { instance: 3, ..kept };
+    let added = TGI { res_type: 0x220557DA, res_group: 0, instance: 4 };
+    let kept_data = b"<I n=\"kept\">".repeat(50);
+    let mut entries = HashMap::new();
+    entries.insert(kept, (kept_data.clone(), kept_data.len() as u32, 0x5A42, 1));
+    entries.insert(replaced, (b"<I/>".to_vec(), 4, 0, 0));
+    entries.insert(removed, (b"<I/>".to_vec(), 4, 0, 1));
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let mut pkg = Package::open(&path).unwrap();
+    let kept_stored = pkg.read_stored_resource(&pkg.find(&kept).cloned().unwrap()).unwrap();
+    assert!(!pkg.is_modified());
+    let new_data = b"<I n=\"replaced\">".repeat(50);
+    pkg.insert_resource(replaced, new_data.clone());
+    pkg.insert_resource(added, b"new".to_vec());
+    assert_eq!(pkg.remove_resource(&removed).map(|e| e.tgi), Some(removed));
+    assert!(pkg.remove_resource(&removed).is_none());
+    assert!(pkg.is_modified());
+
+    // Edits can be read back before saving
+    let entry = pkg.find(&replaced).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), new_data);
+
+    pkg.save().unwrap();
+    assert!(!pkg.is_modified());
+    assert_eq!(pkg.path(), Some(path.as_path()));
+    assert!(!dir.join("edit.package.tmp").exists());
+
+    let reopened = Package::open(&path).unwrap();
+    assert_eq!(reopened.entries.len(), 3);
+    assert!(reopened.find(&removed).is_none());
+    let entry = reopened.find(&kept).cloned().unwrap();
+    assert_eq!(reopened.read_stored_resource(&entry).unwrap(), kept_stored);
+    let entry = reopened.find(&replaced).cloned().unwrap();
+    assert_eq!(entry.committed, 0);
+    assert_eq!(reopened.read_raw_resource(&entry).unwrap(), new_data);
+    let entry = reopened.find(&added).cloned().unwrap();
+    assert_eq!(reopened.read_raw_resource(&entry).unwrap(), b"new");
+
+    // save_as carries on from the new file; a package from a reader has nowhere to save to
+    let copy = dir.join("copy.package");
+    pkg.save_as(&copy).unwrap();
+    assert_eq!(pkg.path(), Some(copy.as_path()));
+    let mut in_memory = Package::from_reader(std::io::Cursor::new(std::fs::read(&copy).unwrap())).unwrap();
+    in_memory.insert_resource(added, b"newer".to_vec());
+    assert!(in_memory.save().is_err());
+
+    // A save that can't replace its destination leaves the package reading from its own file
+    let unwritable = dir.join("taken");
+    std::fs::create_dir(&unwritable).unwrap();
+    std::fs::write(unwritable.join("blocker"), b"").unwrap();
+    pkg.insert_resource(added, b"unsaved".to_vec());
+    assert!(pkg.save_as(&unwritable).is_err());
+    assert_eq!(pkg.path(), Some(copy.as_path()));
+    let entry = pkg.find(&kept).cloned().unwrap();
+    assert_eq!(pkg.read_stored_resource(&entry).unwrap(), kept_stored);
+    let entry = pkg.find(&added).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), b"unsaved");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_package_builder() {
+    use s4pi_reforged::package::builder::PackageBuilder;
+
+    let tuning = TGI { res_type: 0x034AEECB, res_group: 0, instance: 1 };
+    let plain = TGI { instance: 2, ..tuning };
+    let typed = TGI { instance: 3, ..tuning };
+    let tuning_data = b"<I n=\"tuning\">".repeat(50);
+    let text = TypedResource::from_bytes(typed.res_type, b"<I n=\"typed\"/>").unwrap();
+
+    let mut builder = PackageBuilder::new();
+    builder
+        .created(1_700_000_000)
+        .user_version(1, 2)
+        .add(tuning, b"replaced".to_vec())
+        .add(tuning, tuning_data.clone())
+        .add_with(plain, tuning_data.clone(), CompressionScheme::Uncompressed)
+        .add_typed(typed, &text).unwrap();
+    assert_eq!(builder.len(), 3);
+
+    let pkg = builder.build().unwrap();
+    assert!(pkg.header.is_valid());
+    assert_eq!((pkg.header.created, pkg.header.unused1, pkg.header.unused2), (1_700_000_000, 1, 2));
+    assert_eq!(pkg.entries.iter().map(|e| e.tgi).collect::<Vec<_>>(), vec![tuning, plain, typed]);
+    assert_eq!(pkg.entries[0].scheme(), CompressionScheme::Zlib);
+    assert_eq!(pkg.entries[1].scheme(), CompressionScheme::Uncompressed);
+    for entry in pkg.entries.clone().iter().take(2) {
+        assert_eq!(pkg.read_raw_resource(entry).unwrap(), tuning_data);
+    }
+    assert_eq!(pkg.read_raw_resource(&pkg.entries[2].clone()).unwrap(), b"<I n=\"typed\"/>");
+
+    // Writing to a file gives the same bytes
+    let path = std::env::temp_dir().join(format!("s4pi_package_builder_test_{}.package", std::process::id()));
+    builder.write(&path).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), builder.to_bytes().unwrap());
+    let _ = std::fs::remove_file(&path);
+
+    assert!(builder.remove(&plain).is_some());
+    assert!(builder.remove(&plain).is_none());
+    // Resources after a removed one move up, and are still replaced in place
+    builder.add(typed, b"<I/>".to_vec());
+    let pkg = builder.build().unwrap();
+    assert_eq!(pkg.entries.iter().map(|e| e.tgi).collect::<Vec<_>>(), vec![tuning, typed]);
+    assert_eq!(pkg.read_raw_resource(&pkg.entries[1].clone()).unwrap(), b"<I/>");
+
+    builder.add_with(plain, Vec::new(), CompressionScheme::RefPack);
+    assert!(builder.to_bytes().is_err());
+}
+
+#[test]
+fn test_shared_across_threads() {
+    use rayon::prelude::*;
+
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<Package>();
+
+    let dir = std::env::temp_dir().join(format!("s4pi_package_shared_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("shared.package");
+    let mut entries = HashMap::new();
+    for instance in 0..64u64 {
+        let data = format!("<I n=\"tuning_{}\">", instance).repeat(100).into_bytes();
+        let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance };
+        entries.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+    Package::write_merged(&path, &entries, true).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let read: Vec<(TGI, Vec<u8>)> = pkg.entries.par_iter()
+        .map(|entry| (entry.tgi, pkg.read_raw_resource(entry).unwrap()))
+        .collect();
+    assert_eq!(read.len(), 64);
+    for (tgi, data) in read {
+        assert_eq!(data, entries[&tgi].0);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_write_options() {
+    use s4pi_reforged::{CompressionOverride, WriteOptions};
+
+    let dir = std::env::temp_dir().join(format!("s4pi_write_options_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = b"The quick brown fox jumps over the lazy dog. ".repeat(200);
+    let mut entries = HashMap::new();
+    entries.insert(TGI { res_type: 0x220557DA, res_group: 0, instance: 1 }, (text.clone(), text.len() as u32, 0, 1));
+    entries.insert(TGI { res_type: 0x00B2D882, res_group: 0, instance: 2 }, (text.clone(), text.len() as u32, 0, 1));
+
+    let stored_size = |path: &std::path::Path, res_type: u32| {
+        let pkg = Package::open(path).unwrap();
+        let entry = pkg.entries.iter().find(|e| e.tgi.res_type == res_type).unwrap().clone();
+        assert_eq!(pkg.read_raw_resource(&entry).unwrap(), text);
+        entry.filesize
+    };
+
+    let fast = dir.join("fast.package");
+    Package::write_merged(&fast, &entries, WriteOptions { compress: true, level: 1, ..Default::default() }).unwrap();
+    let small = dir.join("small.package");
+    let mut options = WriteOptions { compress: true, level: 9, threads: Some(2), ..Default::default() };
+    options.overrides.insert(0x00B2D882, CompressionOverride::Store);
+    Package::write_merged(&small, &entries, options.clone()).unwrap();
+    assert!(stored_size(&small, 0x220557DA) <= stored_size(&fast, 0x220557DA));
+    assert_eq!(stored_size(&small, 0x00B2D882), text.len() as u32);
+
+    // A saving that can't be met leaves everything uncompressed
+    let strict = dir.join("strict.package");
+    Package::write_merged(&strict, &entries, WriteOptions { compress: true, min_saving: 0.999, ..Default::default() }).unwrap();
+    assert_eq!(stored_size(&strict, 0x220557DA), text.len() as u32);
+    assert!(Package::write_merged(&strict, &entries, WriteOptions { level: 10, ..Default::default() }).is_err());
+
+    let mut pkg = Package::open(&strict).unwrap();
+    pkg.insert_resource(TGI { res_type: 0x220557DA, res_group: 0, instance: 1 }, text.clone());
+    pkg.save_with(&WriteOptions { level: 9, ..Default::default() }).unwrap();
+    // The replaced resource wasn't compressed before, so it isn't now
+    assert_eq!(stored_size(&strict, 0x220557DA), text.len() as u32);
+    pkg.insert_resource(TGI { res_type: 0x220557DA, res_group: 0, instance: 1 }, text.clone());
+    pkg.save_with(&options).unwrap();
+    assert!(stored_size(&strict, 0x220557DA) < text.len() as u32);
+    assert_eq!(stored_size(&strict, 0x00B2D882), text.len() as u32);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_error_kinds() {
+    use s4pi_reforged::Error;
+
+    let err = Package::from_reader(std::io::Cursor::new(b"not a package at all".to_vec())).err().unwrap();
+    assert!(matches!(err, Error::InvalidHeader(_)), "{:?}", err);
+
+    let dir = std::env::temp_dir().join(format!("s4pi_package_errors_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("errors.package");
+    let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 0x42 };
+    let mut entries = HashMap::new();
+    entries.insert(tgi, (b"<I/>".to_vec(), 4, 0, 1));
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let mut entry = pkg.find(&tgi).cloned().unwrap();
+    entry.compression = 0x5A42;
+    let err = pkg.read_raw_resource(&entry).unwrap_err();
+    assert!(matches!(err, Error::Decompression(_)), "{:?}", err);
+    drop(pkg);
+
+    let missing = TGI { instance: 0x43, ..tgi };
+    let err = s4pi_reforged::edit::set_fields(&path, &missing, &[]).unwrap_err();
+    assert!(matches!(err, Error::Context { .. }));
+    assert!(matches!(err.root_cause(), Error::ResourceNotFound(t) if *t == missing), "{:?}", err);
+    assert!(err.to_string().contains("not found"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tgi_filter() {
+    use s4pi_reforged::package::index::parse_id;
+    use s4pi_reforged::TgiFilter;
+
+    assert_eq!(parse_id("0x034AE111").unwrap(), 0x034AE111);
+    assert_eq!(parse_id("4660").unwrap(), 0x1234);
+    assert_eq!(parse_id("034AE111").unwrap(), 0x034AE111);
+    assert_eq!(parse_id("01661233").unwrap(), 0x01661233);
+    assert_eq!(parse_id("0000000000001234").unwrap(), 0x1234);
+    assert_eq!(parse_id("0").unwrap(), 0);
+    assert!(parse_id("01234").is_err());
+    assert!(parse_id("0xZZ").is_err());
+
+    let args = ["pkg.package", "--type", "0x034AE111,0x3C1AF1F2", "--instance", "4660", "--out", "dir"];
+    let (filter, rest) = TgiFilter::from_args(&args).unwrap();
+    assert_eq!(filter.types, vec![0x034AE111, 0x3C1AF1F2]);
+    assert_eq!(filter.instances, vec![0x1234]);
+    assert_eq!(rest, vec!["pkg.package", "--out", "dir"]);
+
+    let casp = TGI { res_type: 0x034AE111, res_group: 0, instance: 0x1234 };
+    assert!(filter.matches(&casp));
+    assert!(filter.matches(&TGI { res_type: 0x3C1AF1F2, ..casp }));
+    assert!(!filter.matches(&TGI { instance: 0x1235, ..casp }));
+    assert!(!filter.matches(&TGI { res_type: 0x0333406C, ..casp }));
+    assert!(TgiFilter::default().matches(&casp));
+
+    let (filter, _) = TgiFilter::from_args(&["--tgi", "034AE111:00000000:0000000000001234"]).unwrap();
+    assert!(filter.matches(&casp));
+    assert!(!filter.matches(&TGI { res_group: 1, ..casp }));
+    let (filter, _) = TgiFilter::from_args(&["--tgi", "034AE111:00000000:0000000000001234", "--group", "1"]).unwrap();
+    assert!(!filter.matches(&casp), "every option has to match");
+    assert!(TgiFilter::from_args(&["--type", "0x100000000"]).is_err());
+    assert!(TgiFilter::from_args(&["--group"]).is_err());
+}
+
+#[test]
+fn test_package_diff() {
+    let dir = std::env::temp_dir().join(format!("s4pi_package_diff_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tgi = |instance: u64| TGI { res_type: 0x0333406C, res_group: 0, instance };
+    let write = |name: &str, resources: &[(u64, &[u8])], compress: bool| {
+        let entries: HashMap<TGI, (Vec<u8>, u32, u16, u16)> = resources.iter()
+            .map(|(instance, data)| (tgi(*instance), (data.to_vec(), data.len() as u32, 0, 1)))
+            .collect();
+        let path = dir.join(name);
+        Package::write_merged(&path, &entries, compress).unwrap();
+        Package::open(&path).unwrap()
+    };
+    let same = b"<I n=\"same\">padding padding padding padding padding</I>".as_slice();
+    // B stores the unchanged resource compressed, which shouldn't count as a change
+    let a = write("a.package", &[(1, same), (2, b"<I/>"), (3, b"old")], false);
+    let b = write("b.package", &[(1, same), (3, b"newer"), (4, b"<T/>")], true);
+
+    let diff = a.diff(&b).unwrap();
+    assert_eq!(diff.removed, vec![tgi(2)]);
+    assert_eq!(diff.added, vec![tgi(4)]);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].tgi, tgi(3));
+    assert_eq!((diff.changed[0].before.size, diff.changed[0].after.size), (3, 5));
+    assert_eq!(diff.unchanged, 1);
+    assert!(!diff.is_empty());
+    assert!(a.diff(&a).unwrap().is_empty());
+
+    let json: serde_json::Value = serde_json::from_str(&diff.to_json()).unwrap();
+    assert_eq!(json["only_in_a"][0], "0333406C:00000000:0000000000000002");
+    assert_eq!(json["changed"][0]["b"]["size"], 5);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_validate() {
+    use s4pi_reforged::package::validate::{validate_file, EntryIssue};
+
+    let dir = std::env::temp_dir().join(format!("s4pi_validate_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let entry = |instance: u64, res_type: u32, memsize: u32, compression: u16| {
+        IndexEntry { tgi: TGI { res_type, res_group: 0, instance }, offset: 0, filesize: 0, memsize, compression, committed: 1 }
+    };
+    let stored = |entry: IndexEntry, data: &[u8]| (IndexEntry { filesize: data.len() as u32, ..entry }, data.to_vec());
+    let resources = vec![
+        stored(entry(1, 0x12345678, 4, 0), b"good"),
+        stored(entry(2, 0x12345678, 10, 0), b"short"),
+        stored(entry(3, 0x12345678, 4, 0x5A42), b"not zlib"),
+        stored(entry(4, 0x220557DA, 2, 0), b"xx"),
+        stored(entry(5, 0x12345678, 0, 0xFFE0), b""),
+    ];
+    let path = dir.join("damaged.package");
+    Package::write_stored(&path, &resources).unwrap();
+
+    let report = Package::open(&path).unwrap().validate().unwrap();
+    assert_eq!(report.checked, 4);
+    assert!(report.header.is_empty());
+    let issues: Vec<(u64, &EntryIssue)> = report.entries.iter().map(|(e, issue)| (e.tgi.instance, issue)).collect();
+    assert_eq!(issues.len(), 3);
+    assert_eq!(issues[0], (2, &EntryIssue::SizeMismatch { memsize: 10, actual: 5 }));
+    assert!(matches!(issues[1], (3, EntryIssue::DecompressionFailed(_))));
+    assert!(matches!(issues[2], (4, EntryIssue::ParseFailed(_))));
+    assert_eq!(report.issue_count(), 3);
+
+    // Point the first entry's data past the end of the file
+    let mut bytes = std::fs::read(&path).unwrap();
+    let index = Package::open(&path).unwrap().header.index_location().position as usize;
+    bytes[index + 4 + 16..index + 4 + 20].copy_from_slice(&0xFFFF_FF00u32.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    let report = validate_file(&path).unwrap();
+    assert!(matches!(report.entries[0].1, EntryIssue::OutOfBounds { offset: 0xFFFF_FF00, filesize: 4, .. }));
+
+    // With an index past the end of the file, only the header can be checked
+    bytes.truncate(index);
+    std::fs::write(&path, &bytes).unwrap();
+    let report = validate_file(&path).unwrap();
+    assert!(report.index_error.is_some());
+    assert!(report.header.iter().any(|i| matches!(i, HeaderIssue::IndexOutOfBounds { .. })));
+    assert!(!report.is_valid());
+
+    let good = dir.join("good.package");
+    Package::write_stored(&good, &resources[..1]).unwrap();
+    assert!(validate_file(&good).unwrap().is_valid());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_repair() {
+    use s4pi_reforged::package::repair::{repair, Fix};
+
+    let dir = std::env::temp_dir().join(format!("s4pi_repair_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tgi = |instance: u64| TGI { res_type: 0x0333406C, res_group: 0, instance };
+    let text = b"<I n=\"compressible\">padding padding padding padding padding padding</I>".to_vec();
+
+    // Borrow a zlib stream from a package written compressed
+    let zlib_path = dir.join("zlib.package");
+    Package::write_merged(&zlib_path, &HashMap::from([(tgi(9), (text.clone(), text.len() as u32, 0x5A42, 1))]), true).unwrap();
+    let zlib_pkg = Package::open(&zlib_path).unwrap();
+    let zlib = zlib_pkg.read_stored_resource(&zlib_pkg.entries[0]).unwrap();
+
+    let entry = |instance: u64, data: &[u8], memsize: u32, compression: u16| {
+        let entry = IndexEntry { tgi: tgi(instance), offset: 0, filesize: data.len() as u32, memsize, compression, committed: 1 };
+        (entry, data.to_vec())
+    };
+    let resources = vec![
+        entry(1, b"fine", 4, 0),
+        entry(2, b"wrong size", 99, 0),
+        // Compressed, but marked as stored
+        entry(3, &zlib, text.len() as u32, 0),
+        entry(4, &zlib, text.len() as u32, 0x5A42),
+        entry(5, b"cut off", 7, 0),
+    ];
+    let path = dir.join("damaged.package");
+    Package::write_stored(&path, &resources).unwrap();
+
+    // Break the header count, entry 4's compressed flag and entry 5's offset
+    let mut bytes = std::fs::read(&path).unwrap();
+    let index = Package::open(&path).unwrap().header.index_location().position as usize;
+    bytes[36..40].copy_from_slice(&9u32.to_le_bytes());
+    let record = |i: usize| index + 4 + i * 32;
+    bytes[record(3) + 23] &= 0x7F;
+    bytes[record(4) + 16..record(4) + 20].copy_from_slice(&0x00FF_0000u32.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(Package::open(&path).is_err());
+
+    let output = dir.join("repaired.package");
+    let report = repair(&path, &output).unwrap();
+    assert_eq!(report.fixes, vec![
+        Fix::EntryCount { header: 9, index: 5 },
+        Fix::Memsize { tgi: tgi(2), from: 99, to: 10 },
+        Fix::CompressionCode { tgi: tgi(3), code: 0x5A42 },
+        Fix::SizeFlag { tgi: tgi(4) },
+        Fix::DroppedEntry { tgi: tgi(5), offset: 0x00FF_0000, filesize: 7 },
+    ]);
+    assert_eq!(report.kept, 4);
+    assert_eq!(std::fs::read(&path).unwrap(), bytes, "the original is left alone");
+
+    let repaired = Package::open(&output).unwrap();
+    assert!(repaired.validate().unwrap().is_valid());
+    assert_eq!(repaired.read_raw_resource(repaired.find(&tgi(3)).unwrap()).unwrap(), text);
+    assert_eq!(repaired.read_raw_resource(repaired.find(&tgi(4)).unwrap()).unwrap(), text);
+    assert!(repair(&output, &output).unwrap().fixes.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
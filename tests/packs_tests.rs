@@ -0,0 +1,58 @@
+use s4pi_reforged::packs::{installed_packs, pack_code, pack_label, pack_requirements};
+use s4pi_reforged::{CasPartResource, Package, Resource, TGI};
+use std::collections::HashMap;
+
+/// A version 43 CASP with the given name and pack ID, and everything else zeroed
+fn casp(name: &str, pack: i16) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&43u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // TGI list offset
+    data.extend_from_slice(&0u32.to_le_bytes()); // preset count
+    let name: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    data.push(name.len() as u8);
+    data.extend_from_slice(&name);
+    data.extend_from_slice(&[0; 16]); // sort fields and flags
+    data.extend_from_slice(&[0; 24]); // excluded part and modifier region flags
+    data.extend_from_slice(&1u32.to_le_bytes()); // one tag
+    data.extend_from_slice(&[0; 6]);
+    data.extend_from_slice(&[0; 16]); // price, title, description, created description
+    data.extend_from_slice(&[0; 17]); // texture space, body type and subtype, age/gender, reserved
+    data.extend_from_slice(&pack.to_le_bytes());
+    data.extend_from_slice(&[0; 8]);
+    data
+}
+
+#[test]
+fn test_pack_requirements() {
+    let part = CasPartResource::from_bytes(&casp("yfHair_Test", 18)).unwrap();
+    assert_eq!(part.name().unwrap(), "yfHair_Test");
+    assert_eq!(part.pack_id().unwrap(), Some(18));
+
+    let dir = std::env::temp_dir().join(format!("s4pi_packs_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("game").join("EP03")).unwrap();
+    std::fs::create_dir_all(dir.join("game").join("Data")).unwrap();
+    let path = dir.join("cc.package");
+
+    let mut resources = HashMap::new();
+    for (instance, pack) in [(1, 18i16), (2, 18), (3, 0), (4, 23)] {
+        let data = casp("part", pack);
+        let tgi = TGI { res_type: 0x034AE111, res_group: 0, instance };
+        resources.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+    Package::write_merged(&path, &resources, true).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let report = pack_requirements(&pkg);
+    assert_eq!(report.keys().copied().collect::<Vec<_>>(), vec![18, 23]);
+    assert_eq!(report[&18].len(), 2);
+
+    assert_eq!(pack_code(18), "EP03");
+    assert_eq!(pack_label(23), "EP04 Cats & Dogs");
+    assert_eq!(pack_code(-5), "pack -5");
+    let installed = installed_packs(dir.join("game"));
+    assert!(installed.contains("EP03"));
+    assert!(!installed.contains(&pack_code(23)));
+    assert_eq!(installed.len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
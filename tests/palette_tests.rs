@@ -0,0 +1,132 @@
+use s4pi_reforged::palette::{color_name, decode_thumbnail, dominant_colors, package_palette};
+use s4pi_reforged::texture::RgbaImage;
+use s4pi_reforged::{CasPartResource, Package, Resource, TGI};
+use std::collections::HashMap;
+
+/// A version 43 CASP with the given name, sort priority and swatch colors, and everything else zeroed
+fn casp(name: &str, priority: f32, swatches: &[u32]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&43u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // TGI list offset
+    data.extend_from_slice(&0u32.to_le_bytes()); // preset count
+    let name: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    data.push(name.len() as u8);
+    data.extend_from_slice(&name);
+    data.extend_from_slice(&priority.to_le_bytes());
+    data.extend_from_slice(&[0; 12]); // secondary sort index, property ID, aural material, flags
+    data.extend_from_slice(&[0; 24]); // excluded part and modifier region flags
+    data.extend_from_slice(&0u32.to_le_bytes()); // no tags
+    data.extend_from_slice(&[0; 16]); // price, title, description, created description
+    data.extend_from_slice(&[0; 17]); // texture space, body type and subtype, age/gender, reserved
+    data.extend_from_slice(&[0; 12]); // pack ID, pack flags, reserved
+    data.push(swatches.len() as u8);
+    for color in swatches {
+        data.extend_from_slice(&color.to_le_bytes());
+    }
+    data
+}
+
+/// A 16x16 thumbnail: a white backdrop with a 10x10 square of `rgb` in the middle
+fn thumbnail(rgb: [u8; 3]) -> RgbaImage {
+    let mut pixels = Vec::new();
+    for y in 0..16 {
+        for x in 0..16 {
+            let inside = (3..13).contains(&x) && (3..13).contains(&y);
+            let [r, g, b] = if inside { rgb } else { [255, 255, 255] };
+            pixels.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    RgbaImage { width: 16, height: 16, pixels }
+}
+
+#[test]
+fn test_swatch_palette() {
+    let part = CasPartResource::from_bytes(&casp("yfTop_Test", 2.5, &[0xFF20A040, 0xFFFFFFFF])).unwrap();
+    assert_eq!(part.sort_order().unwrap(), (2.5, 0));
+    assert_eq!(part.swatch_colors().unwrap(), vec![0xFF20A040, 0xFFFFFFFF]);
+
+    assert_eq!(color_name([200, 20, 20]), "red");
+    assert_eq!(color_name([40, 160, 60]), "green");
+    assert_eq!(color_name([250, 250, 250]), "white");
+    assert_eq!(color_name([110, 70, 30]), "brown");
+
+    let image = decode_thumbnail(&thumbnail([40, 160, 60]).to_png().unwrap()).unwrap();
+    let colors = dominant_colors(&image, 3);
+    assert_eq!(colors.len(), 1, "the white backdrop is left out");
+    assert_eq!(colors[0].rgb, [40, 160, 60]);
+    assert_eq!(colors[0].share, 1.0);
+
+    let dir = std::env::temp_dir().join(format!("s4pi_palette_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cc.package");
+    let mut resources = HashMap::new();
+    // The green swatch has a green thumbnail; the blue one sorts first but shows a red render
+    for (instance, data) in [
+        (1, casp("green", 2.0, &[0xFF28A03C])),
+        (2, casp("blue", 1.0, &[0xFF2040C0])),
+        (1, thumbnail([40, 160, 60]).to_png().unwrap()),
+        (2, thumbnail([200, 20, 20]).to_png().unwrap()),
+    ] {
+        let res_type = if data.starts_with(b"\x89PNG") { 0x3C1AF1F2 } else { 0x034AE111 };
+        let tgi = TGI { res_type, res_group: 0, instance };
+        resources.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+    Package::write_merged(&path, &resources, true).unwrap();
+
+    let palette = package_palette(&Package::open(&path).unwrap());
+    let names: Vec<&str> = palette.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["blue", "green"]);
+    assert_eq!(palette[0].matches_thumbnail(), Some(false));
+    assert_eq!(palette[1].matches_thumbnail(), Some(true));
+    assert_eq!(palette[0].color_names(), vec!["red", "blue"]);
+    assert_eq!(palette[1].thumbnail, Some(TGI { res_type: 0x3C1AF1F2, res_group: 0, instance: 1 }));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// An 8x8 mid-gray baseline JPEG with `alpha` (a PNG) in an ALFA segment at offset 24, as the
+/// game stores transparent thumbnails
+fn jpeg_with_alpha(alpha: &[u8]) -> Vec<u8> {
+    let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+    data.extend_from_slice(b"JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00");
+    data.extend_from_slice(&[0xFF, 0xE0]);
+    data.extend_from_slice(&(10 + alpha.len() as u16).to_be_bytes());
+    data.extend_from_slice(b"ALFA");
+    data.extend_from_slice(&(alpha.len() as u32).to_be_bytes());
+    data.extend_from_slice(alpha);
+    data.extend_from_slice(&[0xFF, 0xDB, 0x00, 0x43, 0x00]);
+    data.extend_from_slice(&[1; 64]);
+    data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00]);
+    for class in [0x00, 0x10] {
+        // One code, 0, for DC difference 0 and end-of-block
+        data.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x14, class, 1]);
+        data.extend_from_slice(&[0; 16]);
+    }
+    data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+    data.extend_from_slice(&[0x3F, 0xFF, 0xD9]);
+    data
+}
+
+#[test]
+fn test_thumbnail_alpha() {
+    let mut mask = RgbaImage { width: 8, height: 8, pixels: Vec::new() };
+    for i in 0..64 {
+        let level = if i < 32 { 0 } else { 200 };
+        mask.pixels.extend_from_slice(&[level, level, level, 255]);
+    }
+    let data = jpeg_with_alpha(&mask.to_png().unwrap());
+
+    let thumb = s4pi_reforged::ThumbnailResource::from_bytes(&data).unwrap();
+    assert!(thumb.has_alpha);
+    let image = thumb.to_rgba().unwrap();
+    assert_eq!((image.width, image.height), (8, 8));
+    assert_eq!(&image.pixels[..4], &[128, 128, 128, 0]);
+    assert_eq!(&image.pixels[252..], &[128, 128, 128, 200]);
+
+    let png = decode_thumbnail(&thumb.to_png().unwrap()).unwrap();
+    assert_eq!(png.pixels, image.pixels);
+
+    // An alpha channel of the wrong size is an error rather than a garbled image
+    let small = RgbaImage { width: 4, height: 4, pixels: vec![255; 64] };
+    assert!(decode_thumbnail(&jpeg_with_alpha(&small.to_png().unwrap())).is_err());
+}
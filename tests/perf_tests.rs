@@ -0,0 +1,41 @@
+use s4pi_reforged::perf::{busy, format_bytes, PerfTimer};
+use s4pi_reforged::{IndexEntry, Package, TGI};
+use std::collections::HashMap;
+
+#[test]
+fn test_perf_summary() {
+    let dir = std::env::temp_dir().join(format!("s4pi_perf_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("perf.package");
+
+    let timer = PerfTimer::start("merge");
+    let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 1 };
+    let data = b"<I n=\"tuning\">".repeat(1000);
+    let mut resources = HashMap::new();
+    resources.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    busy(|| Package::write_merged(&path, &resources, true)).unwrap();
+    let pkg = Package::open(&path).unwrap();
+    let entry: IndexEntry = pkg.find(&tgi).cloned().unwrap();
+    assert_eq!(pkg.read_raw_resource(&entry).unwrap(), data);
+    let summary = timer.finish();
+
+    let file_size = std::fs::metadata(&path).unwrap().len();
+    assert_eq!(summary.operation, "merge");
+    assert_eq!(summary.bytes_written, file_size);
+    assert_eq!(summary.bytes_read, entry.filesize as u64);
+    assert_eq!((summary.stored, summary.uncompressed), (entry.filesize as u64, data.len() as u64));
+    assert!(summary.compression_ratio().unwrap() < 0.5);
+    assert!(summary.threads >= 1);
+    assert!(!summary.busy.is_zero());
+    // This test binary doesn't install the tracking allocator
+    assert_eq!(summary.peak_memory, None);
+    let text = summary.to_string();
+    assert!(text.starts_with("merge took"), "{}", text);
+    assert!(text.contains("compressed to"), "{}", text);
+
+    assert_eq!(format_bytes(512), "512 B");
+    assert_eq!(format_bytes(1536), "1.5 KB");
+    assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,101 @@
+use s4pi_reforged::package::resource::StblEntry;
+use s4pi_reforged::preview::{object_previews, COBJ_TYPE, OBJD_TYPE};
+use s4pi_reforged::{Package, Resource, StblResource, TGI};
+use std::collections::HashMap;
+
+/// An OBJD named "chair_internal" with a price of 450 and one model that isn't in the package
+fn objd_bytes() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&1u16.to_le_bytes()); // version
+    data.extend_from_slice(&6u32.to_le_bytes()); // table offset
+    data.extend_from_slice(&3u16.to_le_bytes()); // entry count
+    for (id, offset) in [(0xE7F07786u32, 32u32), (0xE4F4FAA4, 50), (0x8D20ACC6, 54)] {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&14u32.to_le_bytes()); // name
+    data.extend_from_slice(b"chair_internal");
+    data.extend_from_slice(&450u32.to_le_bytes()); // price
+    data.extend_from_slice(&16u32.to_le_bytes()); // one model TGI, stored ITG with the instance halves swapped
+    data.extend_from_slice(&0x0000000100000002u64.to_le_bytes());
+    data.extend_from_slice(&0x01661233u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data
+}
+
+/// A version 9 COBJ with name hash 0x11223344 and a price of 100
+fn cobj_bytes() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x19u32.to_le_bytes()); // version
+    data.extend_from_slice(&0x09u32.to_le_bytes()); // common version
+    data.extend_from_slice(&0x11223344u32.to_le_bytes()); // name hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // desc hash
+    data.extend_from_slice(&100u32.to_le_bytes()); // price
+    data.extend_from_slice(&0u64.to_le_bytes()); // thumbnail hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // dev category flags
+    data.push(0); // product styles count
+    data.push(0); // unused2 (common version < 10)
+    data.extend_from_slice(&2u32.to_le_bytes()); // legacy tag count
+    data.extend_from_slice(&1234u16.to_le_bytes());
+    data.extend_from_slice(&567u16.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // selling point count
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlock by hash
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlocked by hash
+    data.extend_from_slice(&0u16.to_le_bytes()); // swatch colors sort priority
+    data.extend_from_slice(&0u64.to_le_bytes()); // variant thumb image hash
+    data.extend_from_slice(&[0u8; 16]); // aural materials version + 3 materials
+    data.extend_from_slice(&1u32.to_le_bytes()); // aural properties version
+    data.extend_from_slice(&0u32.to_le_bytes()); // aural quality
+    data.extend_from_slice(&[0u8; 12]); // unused0..2
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags high
+    data.extend_from_slice(&0u32.to_le_bytes()); // placement flags low
+    data.extend_from_slice(&0u64.to_le_bytes()); // slot type set
+    data
+}
+
+#[test]
+fn test_object_preview() {
+    let dir = std::env::temp_dir().join(format!("s4pi_preview_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut stbl = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: vec![StblEntry { key_hash: 0x11223344, flags: 0, string_value: "Comfy Chair".to_string() }],
+    };
+    stbl.recalculate_string_length();
+    let thumbnail = [0xFF, 0xD8, 0xFF, 0xE0, 0, 0];
+
+    let instance = 0xABCD;
+    let mut entries = HashMap::new();
+    for (res_type, instance, data) in [
+        (OBJD_TYPE, instance, objd_bytes()),
+        (COBJ_TYPE, instance, cobj_bytes()),
+        (0x220557DA, 0x00AA, stbl.to_bytes().unwrap()),
+        (0x3C2A8647, instance, thumbnail.to_vec()),
+    ] {
+        entries.insert(TGI { res_type, res_group: 0, instance }, (data.clone(), data.len() as u32, 0, 1));
+    }
+    let path = dir.join("chair.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let previews = object_previews(&pkg).unwrap();
+    assert_eq!(previews.len(), 1);
+    let preview = &previews[0];
+    assert_eq!(preview.name.as_deref(), Some("chair_internal"));
+    assert_eq!(preview.display_name.as_deref(), Some("Comfy Chair"));
+    assert_eq!(preview.price, Some(100));
+    assert_eq!(preview.tags, vec![1234, 567]);
+    assert_eq!(preview.thumbnail.as_ref().map(|(_, data)| data.as_slice()), Some(&thumbnail[..]));
+    assert_eq!(preview.models.len(), 1);
+    assert!(!preview.models[0].present);
+
+    let text = preview.to_string();
+    assert!(text.contains("Object:      Comfy Chair"));
+    assert!(text.contains("(not in package)"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,41 @@
+use s4pi_reforged::provenance::{Provenance, SourceFile, PROVENANCE_TYPE};
+use s4pi_reforged::{Package, TGI};
+use std::collections::{BTreeMap, HashMap};
+
+#[test]
+fn test_provenance_round_trip() {
+    let dir = std::env::temp_dir().join(format!("s4pi_provenance_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("abc.package"), b"abc").unwrap();
+
+    let source = SourceFile::hash(&dir.join("abc.package"), "sub/abc.package").unwrap();
+    assert_eq!(source.size, 3);
+    assert_eq!(source.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+    let mut options = BTreeMap::new();
+    options.insert("compress".to_string(), "true".to_string());
+    let provenance = Provenance::new(options, vec![source]);
+    assert_eq!(Provenance::tgi().res_type, PROVENANCE_TYPE);
+
+    let record = provenance.to_bytes();
+    let mut entries = HashMap::new();
+    entries.insert(Provenance::tgi(), (record.clone(), record.len() as u32, 0x5A42, 1));
+    entries.insert(TGI { res_type: 0x220557DA, res_group: 0, instance: 1 }, (vec![0; 16], 16, 0, 1));
+    let path = dir.join("merged.package");
+    Package::write_merged(&path, &entries, true).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let read = Provenance::read(&pkg).unwrap().unwrap();
+    assert_eq!(read, provenance);
+    assert_eq!(read.tool_version, env!("CARGO_PKG_VERSION"));
+    let text = read.to_string();
+    assert!(text.contains("Options: compress=true"));
+    assert!(text.contains("sub/abc.package"));
+
+    std::fs::remove_file(&path).unwrap();
+    entries.remove(&Provenance::tgi());
+    Package::write_merged(&path, &entries, true).unwrap();
+    assert!(Provenance::read(&Package::open(&path).unwrap()).unwrap().is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
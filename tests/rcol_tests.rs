@@ -1,4 +1,4 @@
-use s4pi_reforged::{Resource, RcolResource, TGI};
+use s4pi_reforged::{Resource, RcolResource};
 
 #[test]
 fn test_rcol_parsing() {
@@ -36,3 +36,42 @@ fn test_rcol_parsing() {
     assert_eq!(rcol.chunks[0].tag, "GEOM");
     assert_eq!(rcol.chunks[0].tgi.res_type, 0x015A1849);
 }
+
+#[test]
+fn test_rcol_write() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&3u32.to_le_bytes()); // version
+    data.extend_from_slice(&2i32.to_le_bytes()); // public_chunks
+    data.extend_from_slice(&0u32.to_le_bytes()); // unused
+    data.extend_from_slice(&1i32.to_le_bytes()); // count_resources
+    data.extend_from_slice(&2i32.to_le_bytes()); // count_chunks
+    for (res_type, instance) in [(0x01661233u32, 1u64), (0x01D10F34, 2), (0x00B2D882, 3)] {
+        data.extend_from_slice(&res_type.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&instance.to_le_bytes());
+    }
+    let first = data.len() as u32 + 16;
+    for (position, length) in [(first, 8i32), (first + 8, 4)] {
+        data.extend_from_slice(&position.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+    }
+    data.extend_from_slice(b"MODL\x01\x00\x00\x00");
+    data.extend_from_slice(b"MLOD");
+
+    // An unedited RCOL is written back as it was
+    let mut rcol = RcolResource::from_bytes(&data).unwrap();
+    assert_eq!(rcol.to_bytes().unwrap(), data);
+
+    // A chunk that grows moves the ones after it, each starting on 4 bytes
+    rcol.chunks[0].data.push(0xFF);
+    rcol.external_resources[0].instance = 4;
+    let written = rcol.to_bytes().unwrap();
+    assert_eq!(written.len(), data.len() + 4);
+    let reread = RcolResource::from_bytes(&written).unwrap();
+    assert_eq!(reread.chunks[0].data, rcol.chunks[0].data);
+    assert_eq!(reread.chunks[1].tag, "MLOD");
+    assert_eq!(reread.chunks[1].data, b"MLOD");
+    assert_eq!(reread.chunks[1].tgi, rcol.chunks[1].tgi);
+    assert_eq!(reread.external_resources, rcol.external_resources);
+    assert_eq!(u32::from_le_bytes(written[first as usize - 8..first as usize - 4].try_into().unwrap()), first + 12);
+}
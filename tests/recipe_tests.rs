@@ -0,0 +1,42 @@
+use s4pi_reforged::recipe::Recipe;
+use std::path::Path;
+
+#[test]
+fn test_recipe_steps_and_expansion() {
+    let text = r#"
+        [vars]
+        mods = "/sims/Mods"
+
+        [profiles]
+        strict = ["--on-conflict", "error"]
+
+        [[step]]
+        command = "packs"
+        args = ["{mods}"]
+        keep_going = true
+
+        [[step]]
+        name = "Merge"
+        command = "merge"
+        args = ["{mods}"]
+        profile = "strict"
+
+        [[step]]
+        command = "manifest"
+        args = ["verify", "{recipe_dir}/merged.package"]
+    "#;
+    let recipe = Recipe::from_toml(text, Path::new("/recipes")).unwrap();
+    assert_eq!(recipe.steps.len(), 3);
+    assert!(recipe.steps[0].keep_going);
+    assert_eq!(recipe.steps[1].name.as_deref(), Some("Merge"));
+    assert_eq!(recipe.args(&recipe.steps[1]).unwrap(), vec!["/sims/Mods", "--on-conflict", "error"]);
+    assert_eq!(recipe.args(&recipe.steps[2]).unwrap(), vec!["verify", "/recipes/merged.package"]);
+
+    // The Mods folder is the caller's to fill in
+    assert!(!Recipe::from_toml("", Path::new(".")).unwrap().vars.contains_key("mods"));
+
+    let unset = Recipe::from_toml("[[step]]\ncommand = \"packs\"\nargs = [\"{backup}\"]", Path::new(".")).unwrap();
+    assert!(unset.args(&unset.steps[0]).unwrap_err().to_string().contains("{backup} is not set"));
+    assert!(Recipe::from_toml("[[step]]\ncommand = \"merge\"\nprofile = \"fast\"", Path::new(".")).is_err());
+    assert!(Recipe::from_toml("[[step]]\ncomand = \"merge\"", Path::new(".")).is_err());
+}
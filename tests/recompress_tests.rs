@@ -0,0 +1,55 @@
+use s4pi_reforged::recompress::refpack_to_zlib;
+use s4pi_reforged::{CompressionScheme, IndexEntry, Package, TGI};
+use std::io::Write;
+
+fn entry(instance: u64, memsize: u32, compression: u16) -> IndexEntry {
+    IndexEntry {
+        tgi: TGI { res_type: 0x0C772E27, res_group: 0, instance },
+        offset: 0,
+        filesize: 0,
+        memsize,
+        compression,
+        committed: 1,
+    }
+}
+
+#[test]
+fn test_refpack_to_zlib() {
+    let dir = std::env::temp_dir().join(format!("s4pi_recompress_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // "abcd" as a literal, then a 60 byte back-reference to it
+    let refpack = vec![0x10, 0xFB, 0, 0, 64, 0xE0, b'a', b'b', b'c', b'd', 0xC0, 0, 3, 55, 0xFC];
+    let broken = vec![0x10, 0xFB, 0, 0, 64, 0x00, 0x00];
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"zlib data zlib data").unwrap();
+    let zlib = encoder.finish().unwrap();
+
+    let path = dir.join("old.package");
+    Package::write_stored(&path, &[
+        (entry(1, 64, 0xFFFF), refpack),
+        (entry(2, 64, 0xFFFF), broken.clone()),
+        (entry(3, 19, 0x5A42), zlib.clone()),
+        (entry(4, 5, 0), b"plain".to_vec()),
+    ]).unwrap();
+
+    let report = refpack_to_zlib(&path).unwrap();
+    assert_eq!(report.converted, 1);
+    assert_eq!(report.bytes_before, 15);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0.instance, 2);
+
+    let pkg = Package::open(&path).unwrap();
+    let entries = pkg.entries.clone();
+    assert_eq!(entries[0].scheme(), CompressionScheme::Zlib);
+    assert_eq!(pkg.read_raw_resource(&entries[0]).unwrap(), b"abcd".repeat(16));
+    assert_eq!(entries[1].scheme(), CompressionScheme::RefPack);
+    assert_eq!(pkg.read_stored_resource(&entries[1]).unwrap(), broken);
+    assert_eq!(pkg.read_stored_resource(&entries[2]).unwrap(), zlib);
+    assert_eq!(pkg.read_raw_resource(&entries[3]).unwrap(), b"plain");
+
+    // Nothing left to convert, so the package is left alone
+    let again = refpack_to_zlib(&path).unwrap();
+    assert_eq!(again.converted, 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,67 @@
+use s4pi_reforged::package::resource::{CasPartResource, ManifestEntry, ManifestResource, RcolChunk, RcolResource};
+use s4pi_reforged::relink::relink;
+use s4pi_reforged::{Resource, TypedResource, TGI};
+use std::collections::HashMap;
+
+fn tgi(res_type: u32, instance: u64) -> TGI {
+    TGI { res_type, res_group: 0, instance }
+}
+
+#[test]
+fn test_relink_casp_tgi_list() {
+    let texture = tgi(0x00B2D882, 0x10);
+    let mesh = tgi(0x015A1849, 0x20);
+
+    // Version, offset to the TGI list (from byte 8), a 4-byte body, then the list
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x2Cu32.to_le_bytes());
+    data.extend_from_slice(&4u32.to_le_bytes());
+    data.extend_from_slice(&[0xAA; 4]);
+    data.push(2);
+    for t in [texture, mesh] {
+        data.extend_from_slice(&t.instance.to_le_bytes());
+        data.extend_from_slice(&t.res_group.to_le_bytes());
+        data.extend_from_slice(&t.res_type.to_le_bytes());
+    }
+
+    let new_texture = tgi(0x00B2D882, 0xFEED);
+    let map = HashMap::from([(texture, new_texture), (tgi(0x00B2D882, 0x99), tgi(0x00B2D882, 0x98))]);
+    let mut resource = TypedResource::CasPart(CasPartResource::from_bytes(&data).unwrap());
+    assert_eq!(relink(&mut resource, &map), 1);
+
+    let TypedResource::CasPart(casp) = resource else { unreachable!() };
+    assert_eq!(casp.tgi_list().unwrap(), vec![new_texture, mesh]);
+    assert_eq!(&casp.raw_data[8..12], &[0xAA; 4]);
+    assert_eq!(casp.raw_data.len(), data.len());
+}
+
+#[test]
+fn test_relink_manifest_and_rcol() {
+    let old = tgi(0x015A1849, 1);
+    let new = tgi(0x015A1849, 2);
+    let map = HashMap::from([(old, new)]);
+
+    let mut manifest = TypedResource::Manifest(ManifestResource {
+        version: 1,
+        padding: 0,
+        entries: vec![ManifestEntry { name: "a".to_string(), resources: vec![old, tgi(0x220557DA, 1)], checksums: Vec::new() }],
+    });
+    assert_eq!(relink(&mut manifest, &map), 1);
+    let TypedResource::Manifest(manifest) = manifest else { unreachable!() };
+    assert_eq!(manifest.entries[0].resources, vec![new, tgi(0x220557DA, 1)]);
+
+    let mut rcol = TypedResource::Rcol(RcolResource {
+        version: 3,
+        public_chunks: 1,
+        unused: 0,
+        external_resources: vec![tgi(0x00B2D882, 5), old],
+        chunks: vec![RcolChunk { tgi: old, tag: "MODL".to_string(), data: Vec::new() }],
+    });
+    assert_eq!(relink(&mut rcol, &map), 2);
+    let TypedResource::Rcol(rcol) = rcol else { unreachable!() };
+    assert_eq!(rcol.external_resources[1], new);
+    assert_eq!(rcol.chunks[0].tgi, new);
+
+    let mut text = TypedResource::from_bytes(0x034AEECB, b"hello").unwrap();
+    assert_eq!(relink(&mut text, &map), 0);
+}
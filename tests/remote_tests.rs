@@ -0,0 +1,80 @@
+use s4pi_reforged::remote::is_url;
+
+#[test]
+fn test_is_url() {
+    assert!(is_url("https://example.com/cc.package"));
+    assert!(is_url("HTTP://example.com/cc.package"));
+    assert!(!is_url("./Mods/cc.package"));
+    assert!(!is_url("C:\\Mods\\cc.package"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_open_package_over_http() {
+    use s4pi_reforged::remote::open_package;
+    use s4pi_reforged::{Package, TGI};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves `file` to each connection, honouring Range headers if `ranges` is set
+    fn serve(file: Vec<u8>, ranges: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/cc.package", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range = None;
+                for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(spec) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (start, end) = spec.split_once('-').unwrap();
+                        range = Some((start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()));
+                    }
+                }
+                let (status, body, extra) = match range.filter(|_| ranges) {
+                    Some((start, end)) => {
+                        let end = end.min(file.len() - 1);
+                        let extra = format!("Content-Range: bytes {}-{}/{}\r\n", start, end, file.len());
+                        ("206 Partial Content", &file[start..=end], extra)
+                    }
+                    None => ("200 OK", &file[..], String::new()),
+                };
+                let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n", status, body.len(), extra);
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        url
+    }
+
+    let dir = std::env::temp_dir().join(format!("s4pi_remote_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cc.package");
+    let mut resources = HashMap::new();
+    for instance in 0..4u64 {
+        // Incompressible, so the package is well past the first range
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i.wrapping_mul(2654435761).wrapping_add(instance as u32) >> 13) as u8).collect();
+        resources.insert(TGI { res_type: 0x0333406C, res_group: 0, instance }, (data.clone(), data.len() as u32, 0, 1));
+    }
+    Package::write_merged(&path, &resources, false).unwrap();
+    let file = std::fs::read(&path).unwrap();
+
+    let remote = open_package(&serve(file.clone(), true)).unwrap();
+    assert_eq!(remote.size, file.len() as u64);
+    assert_eq!(remote.package.entries.len(), 4);
+    let before = remote.fetched();
+    assert!(before < file.len() as u64 / 2, "fetched {} of {}", before, file.len());
+    let tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 2 };
+    let entry = remote.package.find(&tgi).cloned().unwrap();
+    assert_eq!(remote.package.read_raw_resource(&entry).unwrap(), resources[&tgi].0);
+    assert!(remote.fetched() < file.len() as u64);
+
+    let error = open_package(&serve(file, false)).err().unwrap();
+    assert!(error.to_string().contains("doesn't support range requests"), "{}", error);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
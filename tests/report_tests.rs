@@ -0,0 +1,54 @@
+use s4pi_reforged::report::{catch_panic, hexdump, FailureContext, FailureReport};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+
+#[test]
+fn test_catch_panic() {
+    let err = catch_panic::<()>(|| panic!("bad index {}", 7)).unwrap_err();
+    assert_eq!(err.to_string(), "panicked: bad index 7");
+    assert_eq!(catch_panic(|| Ok(5)).unwrap(), 5);
+}
+
+#[test]
+fn test_hexdump() {
+    assert_eq!(hexdump(b"DBPF\x02"), format!("00000000  44 42 50 46 02{}  DBPF.\n", " ".repeat(47 - 14)));
+}
+
+#[test]
+fn test_failure_report_for_corrupt_resource() {
+    let dir = std::env::temp_dir().join(format!("s4pi_report_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let tgi = TGI { res_type: 0x220557DA, res_group: 0, instance: 0x42 };
+    let mut entries = HashMap::new();
+    entries.insert(tgi, (vec![0u8; 64], 64, 0x5A42, 1));
+    let path = dir.join("corrupt.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    // Damage the zlib stream after its header
+    let offset = Package::open(&path).unwrap().find(&tgi).unwrap().offset as usize;
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[offset + 2..offset + 6].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    std::fs::write(&path, bytes).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let entry = pkg.find(&tgi).cloned().unwrap();
+    let error = pkg.read_raw_resource(&entry).unwrap_err();
+
+    let report = FailureReport::new();
+    report.add(FailureContext::from_stored(&path, Some(&pkg.header), &entry, &error));
+    assert_eq!(report.len(), 1);
+
+    let text = report.to_text("merge");
+    assert!(text.contains(&format!("Tool version: {}", env!("CARGO_PKG_VERSION"))));
+    assert!(text.contains("Resource:    220557DA:00000000:0000000000000042"));
+    assert!(text.contains("Compression: 0x5A42"));
+    assert!(text.contains("Failed to decompress"));
+    assert!(text.contains("magic \"DBPF\", version 2.1"));
+    assert!(text.contains("78 9C DE AD BE EF"));
+
+    let written = report.write(&dir, "merge").unwrap();
+    assert!(std::fs::read_to_string(written).unwrap().contains("DE AD BE EF"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
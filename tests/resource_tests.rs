@@ -1,7 +1,5 @@
-use s4pi_reforged::{Package, TypedResource, NameMapResource, StblResource, ObjectDefinitionResource, SimDataResource, TextResource};
-use std::fs::File;
+use s4pi_reforged::TypedResource;
 use std::io::{Write, Cursor};
-use binrw::{BinWrite, BinRead, BinReaderExt};
 
 #[test]
 fn test_stbl_parsing() {
@@ -60,3 +58,18 @@ fn test_text_parsing() {
         panic!("Expected Text resource");
     }
 }
+
+#[test]
+fn test_file_extension() {
+    assert_eq!(TypedResource::file_extension(0x0333406C, b"<?xml version=\"1.0\"?><I/>"), "xml");
+    assert_eq!(TypedResource::file_extension(0x220557DA, b"STBL\x05"), "stbl");
+    assert_eq!(TypedResource::file_extension(0x3C1AF1F2, &[0xFF, 0xD8, 0xFF, 0xE0]), "jpg");
+    assert_eq!(TypedResource::file_extension(0x3453CF95, b"DXT5RLE2\0\0\0\0"), "rle2");
+    let mut dds = vec![0u8; 128];
+    dds[..4].copy_from_slice(b"DDS ");
+    assert_eq!(TypedResource::file_extension(0x00B2D882, &dds), "dds");
+    dds[84..88].copy_from_slice(b"DST5");
+    assert_eq!(TypedResource::file_extension(0x00B2D882, &dds), "dst");
+    assert_eq!(TypedResource::file_extension(0x12345678, b"<I n=\"x\"/>"), "xml");
+    assert_eq!(TypedResource::file_extension(0x12345678, &[1, 2, 3]), "bin");
+}
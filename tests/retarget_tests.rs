@@ -0,0 +1,71 @@
+use s4pi_reforged::package::resource::TypedResource;
+use s4pi_reforged::retarget::{retarget_package, retarget_xml};
+use s4pi_reforged::{Package, Resource, TGI};
+
+const SIMDATA_XML: &str = r#"<SimData version="0x00000101" u="0xFFFFFFFF">
+  <Instances>
+    <I name="mod:MyInteraction" schema="Interaction">
+      <T name="buff">12345</T>
+      <T name="weight">12345</T>
+      <L name="loot">
+        <T type="TableSetReference">99</T>
+        <T type="TableSetReference">12345</T>
+      </L>
+    </I>
+  </Instances>
+  <Schemas>
+    <Schema name="Interaction" schema_hash="0x00000000">
+      <Columns>
+        <Column name="buff" type="TableSetReference" flags="0x0000" />
+        <Column name="weight" type="UInt32" flags="0x0000" />
+        <Column name="loot" type="Vector" flags="0x0000" />
+      </Columns>
+    </Schema>
+  </Schemas>
+</SimData>
+"#;
+
+#[test]
+fn test_retarget_tuning_references() {
+    let (xml, lines) = retarget_xml("<I n=\"x\" s=\"12345\">\n<T n=\"buff\">12345<!--12345--></T>\n<T>123456</T><T>0x12345</T><L><T>12345</T></L></I>", 12345, 777);
+    assert_eq!(lines, [2, 3]);
+    assert_eq!(xml, "<I n=\"x\" s=\"12345\">\n<T n=\"buff\">777<!--12345--></T>\n<T>123456</T><T>0x12345</T><L><T>777</T></L></I>");
+
+    let dir = std::env::temp_dir().join(format!("s4pi_retarget_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tuning_tgi = TGI { res_type: 0xE882D22F, res_group: 0, instance: 2 };
+    let simdata_tgi = TGI { res_type: 0x545AC67A, res_group: 0, instance: 2 };
+    let tuning = b"<?xml version=\"1.0\"?>\n<I n=\"x\" s=\"2\">\n  <T n=\"buff\">12345</T>\n</I>".to_vec();
+    let simdata = s4pi_reforged::simdata::from_xml(SIMDATA_XML).unwrap().to_bytes().unwrap();
+    let mut entries = std::collections::HashMap::new();
+    entries.insert(tuning_tgi, (tuning.clone(), tuning.len() as u32, 0x5A42, 1));
+    entries.insert(simdata_tgi, (simdata.clone(), simdata.len() as u32, 0x5A42, 1));
+    let path = dir.join("mod.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let dry = retarget_package(&path, 12345, 777, true).unwrap();
+    let mut sites: Vec<(u32, String)> = dry.sites.iter().map(|s| (s.resource.res_type, s.location.clone())).collect();
+    sites.sort();
+    assert_eq!(sites, [
+        (simdata_tgi.res_type, "TableSetReference[1]".to_string()),
+        (simdata_tgi.res_type, "mod:MyInteraction[0].buff".to_string()),
+        (tuning_tgi.res_type, "line 3".to_string()),
+    ]);
+    assert_eq!(dry.resources, 2);
+    assert_eq!(retarget_package(&path, 12345, 777, false).unwrap(), dry);
+
+    // The 32-bit field holding the same number is left alone
+    let pkg = Package::open(&path).unwrap();
+    let entry = pkg.find(&simdata_tgi).cloned().unwrap();
+    let TypedResource::SimData(simdata) = pkg.read_resource(&entry).unwrap() else { panic!("Expected SimData") };
+    let xml = s4pi_reforged::simdata::to_xml(&simdata).unwrap();
+    assert!(xml.contains("<T name=\"buff\">0x0000000000000309</T>"));
+    assert!(xml.contains("<T name=\"weight\">12345</T>"));
+    assert!(xml.contains(">0x0000000000000063</T>\n        <T type=\"TableSetReference\">0x0000000000000309</T>"));
+    let entry = pkg.find(&tuning_tgi).cloned().unwrap();
+    assert!(String::from_utf8(pkg.read_raw_resource(&entry).unwrap()).unwrap().contains(">777<"));
+    drop(pkg);
+
+    assert!(retarget_package(&path, 12345, 777, false).unwrap().sites.is_empty());
+    std::fs::remove_dir_all(&dir).ok();
+}
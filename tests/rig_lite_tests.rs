@@ -1,3 +1,4 @@
+use s4pi_reforged::gltf::skeleton_to_glb;
 use s4pi_reforged::{Resource, RigResource, LiteResource};
 
 #[test]
@@ -21,3 +22,91 @@ fn test_lite_parsing() {
     let lite = LiteResource::from_bytes(&data).unwrap();
     assert_eq!(lite.version, 4);
 }
+
+#[test]
+fn test_rig_bones() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&4u32.to_le_bytes()); // major
+    data.extend_from_slice(&2u32.to_le_bytes()); // minor
+    data.extend_from_slice(&2u32.to_le_bytes()); // bone count
+    for (name, parent, hash) in [("b__ROOT__", -1i32, 0x11111111u32), ("b__Pelvis__", 0, 0x22222222)] {
+        for v in [0.0f32, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&(-1i32).to_le_bytes()); // opposite
+        data.extend_from_slice(&parent.to_le_bytes());
+        data.extend_from_slice(&hash.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes()); // flags
+    }
+
+    let rig = RigResource::from_bytes(&data).unwrap();
+    assert_eq!(rig.format, "Clear");
+    let bones = rig.bones().unwrap();
+    assert_eq!(bones.len(), 2);
+    assert_eq!(bones[0].name, "b__ROOT__");
+    assert_eq!(bones[0].position, [0.0, 1.0, 0.0]);
+    assert_eq!(bones[0].orientation, [0.0, 0.0, 0.0, 1.0]);
+    assert_eq!(bones[1].parent_index, 0);
+    assert_eq!(bones[1].hash, 0x22222222);
+
+    let granny = RigResource::from_bytes(&[0xDE, 0x13, 0xAF, 0x8E, 0, 0, 0, 0]).unwrap();
+    assert!(granny.bones().is_err());
+}
+
+#[test]
+fn test_rig_skeleton() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&4u32.to_le_bytes()); // major
+    data.extend_from_slice(&2u32.to_le_bytes()); // minor
+    data.extend_from_slice(&4u32.to_le_bytes()); // bone count
+    for (name, opposite, parent) in [("b__ROOT__", -1i32, -1i32), ("b__L_Thigh__", 2, 0), ("b__R_Thigh__", 1, 0), ("b__L_Calf__", -1, 1)] {
+        for v in [0.0f32, 0.5, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(&opposite.to_le_bytes());
+        data.extend_from_slice(&parent.to_le_bytes());
+        data.extend_from_slice(&s4pi_reforged::instance::fnv32(name).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes()); // flags
+    }
+    data.extend_from_slice(&5u32.to_le_bytes());
+    data.extend_from_slice(b"yfRig");
+    data.extend_from_slice(&1u32.to_le_bytes()); // IK chains
+    data.extend_from_slice(&2u32.to_le_bytes());
+    for value in [3i32, 1] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [-1i32; 11].into_iter().chain([2, -1, -1, 1]) {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let skeleton = RigResource::from_bytes(&data).unwrap().skeleton().unwrap();
+    assert_eq!((skeleton.major, skeleton.minor), (4, 2));
+    assert_eq!(skeleton.name.as_deref(), Some("yfRig"));
+    assert_eq!(skeleton.roots(), [0]);
+    assert_eq!(skeleton.children(0), [1, 2]);
+    assert_eq!(skeleton.parent(3), Some(1));
+    assert_eq!(skeleton.find("b__R_Thigh__"), Some(2));
+    assert_eq!(skeleton.walk(), [(0, 0), (1, 1), (3, 2), (2, 1)]);
+    assert_eq!(skeleton.ik_chains.len(), 1);
+    assert_eq!(skeleton.ik_chains[0].bones, [3, 1]);
+    assert_eq!((skeleton.ik_chains[0].pole, skeleton.ik_chains[0].root), (2, 1));
+
+    // Bones alone don't need the rest of the rig
+    let bones_only = RigResource::from_bytes(&data[..data.len() - 85]).unwrap();
+    assert_eq!(bones_only.bones().unwrap(), skeleton.bones);
+    assert!(bones_only.skeleton().is_err());
+
+    let glb = skeleton_to_glb(&skeleton.bones, "yfRig").unwrap();
+    assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+    let json_len = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+    assert_eq!(glb.len(), 20 + json_len);
+    let json: serde_json::Value = serde_json::from_slice(&glb[20..]).unwrap();
+    assert_eq!(json["scenes"][0]["nodes"], serde_json::json!([0]));
+    assert_eq!(json["nodes"][0]["children"], serde_json::json!([1, 2]));
+    assert_eq!(json["nodes"][3]["name"], "b__L_Calf__");
+    assert!(json.get("meshes").is_none());
+}
@@ -0,0 +1,25 @@
+use s4pi_reforged::package::resource::{ResourceKind, TypedResource, RESOURCE_KINDS};
+use s4pi_reforged::schema::{definitions, formats, to_json};
+
+#[test]
+fn test_schema() {
+    let formats = formats();
+    for (kind, types) in RESOURCE_KINDS {
+        let format = formats.iter().find(|f| f.kind == *kind).unwrap();
+        assert_eq!(format.resource_types, *types);
+        assert!(types.iter().all(|&t| TypedResource::kind_of(t) == *kind));
+    }
+    assert_eq!(formats.last().unwrap().kind, ResourceKind::Generic);
+
+    let stbl = formats.iter().find(|f| f.kind == ResourceKind::Stbl).unwrap();
+    assert_eq!(stbl.wrapper.name, "StblResource");
+    let entries = stbl.wrapper.fields.iter().find(|f| f.name == "entries").unwrap();
+    assert_eq!(entries.type_name, "Vec<StblEntry>");
+    assert!(definitions().iter().any(|d| d.name == "StblEntry"));
+
+    let json: serde_json::Value = serde_json::from_str(&to_json()).unwrap();
+    let stbl = json["formats"].as_array().unwrap().iter().find(|f| f["kind"] == "Stbl").unwrap();
+    assert_eq!(stbl["resource_types"][1], "0x220557DA");
+    assert_eq!(stbl["wrapper"]["fields"][0]["name"], "version");
+    assert_eq!(stbl["writable"], true);
+}
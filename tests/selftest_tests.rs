@@ -0,0 +1,19 @@
+use s4pi_reforged::selftest::{run, Outcome, Step};
+
+#[test]
+fn test_selftest_passes() {
+    let dir = std::env::temp_dir().join(format!("s4pi_selftest_test_{}", std::process::id()));
+    let report = run(&dir).unwrap();
+    let failures: Vec<String> = report.samples.iter()
+        .flat_map(|s| Step::ALL.iter().zip(&s.outcomes).map(move |(step, outcome)| (s, step, outcome)))
+        .filter_map(|(s, step, outcome)| match outcome {
+            Outcome::Failed(e) => Some(format!("{} {}: {} failed: {}", s.group, s.name, step.name(), e)),
+            _ => None,
+        })
+        .collect();
+    assert!(report.passed(), "{:#?}", failures);
+    assert!(report.samples.iter().any(|s| s.group == "index" && s.name == "type 0x07"));
+    assert!(report.samples.iter().any(|s| s.group == "compression" && s.name == "refpack"));
+    assert!(report.samples.iter().all(|s| s.outcomes.iter().all(|o| *o == Outcome::Passed)));
+    std::fs::remove_dir_all(&dir).ok();
+}
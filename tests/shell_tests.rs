@@ -0,0 +1,43 @@
+use s4pi_reforged::shell::{desktop_entries, mime_definition, windows_registry_entries, ShellTarget, PACKAGE_MIME_TYPE};
+use std::path::Path;
+
+#[test]
+fn test_windows_registry_entries() {
+    let entries = windows_registry_entries(Path::new(r"C:\Tools\s4pi-reforged.exe"));
+    let (action, key, command) = &entries[0];
+    assert_eq!(action.target, ShellTarget::Folder);
+    assert_eq!(key, r"Software\Classes\Directory\shell\s4pi-reforged.merge");
+    assert_eq!(command, r#"cmd.exe /k ""C:\Tools\s4pi-reforged.exe" merge "%1"""#);
+    assert!(entries[1].1.starts_with(r"Software\Classes\SystemFileAssociations\.package\shell\"));
+    assert_eq!(entries[2].2, r#""C:\Tools\s4pi-reforged.exe" "%1""#);
+}
+
+#[test]
+fn test_desktop_entries() {
+    let entries = desktop_entries(Path::new("/opt/s4pi/s4pi-reforged"));
+    assert_eq!(entries.len(), 3);
+    let (name, contents) = &entries[2];
+    assert_eq!(name, "s4pi-reforged-inspect.desktop");
+    assert!(contents.contains("Exec=\"/opt/s4pi/s4pi-reforged\" %f\nTerminal=false\n"));
+    assert!(contents.contains(&format!("MimeType={};", PACKAGE_MIME_TYPE)));
+    assert!(entries[0].1.contains("MimeType=inode/directory;"));
+    assert!(mime_definition().contains("<glob pattern=\"*.package\"/>"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_install_and_uninstall_on_linux() {
+    let dir = std::env::temp_dir().join(format!("s4pi_shell_test_{}", std::process::id()));
+    std::env::set_var("XDG_DATA_HOME", &dir);
+
+    let installed = s4pi_reforged::shell::install(Path::new("/opt/s4pi/s4pi-reforged")).unwrap();
+    assert_eq!(installed.len(), 4);
+    assert!(dir.join("applications/s4pi-reforged-merge.desktop").exists());
+    assert!(dir.join("mime/packages/s4pi-reforged.xml").exists());
+
+    let removed = s4pi_reforged::shell::uninstall().unwrap();
+    assert_eq!(removed.len(), 4);
+    assert!(!dir.join("applications/s4pi-reforged-merge.desktop").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
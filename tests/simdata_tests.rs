@@ -0,0 +1,192 @@
+use s4pi_reforged::package::simdata::{SimDataType, SimDataValue};
+use s4pi_reforged::simdata::{from_xml, to_xml};
+use s4pi_reforged::{Resource, SimDataResource, TGI};
+use std::collections::HashMap;
+
+/// Writes the offset field at `at` pointing to `target`, counted from the field as SimData does
+fn offset(data: &mut [u8], at: usize, target: usize) {
+    data[at..at + 4].copy_from_slice(&((target as i64 - at as i64) as i32).to_le_bytes());
+}
+
+fn null(data: &mut [u8], at: usize) {
+    data[at..at + 4].copy_from_slice(&i32::MIN.to_le_bytes());
+}
+
+fn put(data: &mut [u8], at: usize, bytes: &[u8]) {
+    data[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+/// A version 0x101 SimData for one buff: a named instance table whose schema has a float,
+/// a string, a vector of u32, a resource key, a child object and a localization key
+fn buff_simdata(duration: f32) -> Vec<u8> {
+    let names = ["mod:MyBuff", "Buff", "Child", "duration", "name", "tags", "key", "child", "loc", "value", "flag", "Hello"];
+    const STRINGS: usize = 408;
+    let mut strings = HashMap::new();
+    let mut text = Vec::new();
+    for name in names {
+        strings.insert(name, STRINGS + text.len());
+        text.extend_from_slice(name.as_bytes());
+        text.push(0);
+    }
+    let mut data = vec![0u8; STRINGS + text.len()];
+    put(&mut data, STRINGS, &text);
+
+    // Header, then 4 tables at 28, 2 schemas at 140, their columns at 188 and 308, and rows
+    put(&mut data, 0, b"DATA");
+    put(&mut data, 4, &0x101u32.to_le_bytes());
+    offset(&mut data, 8, 28);
+    put(&mut data, 12, &4u32.to_le_bytes());
+    offset(&mut data, 16, 140);
+    put(&mut data, 20, &2u32.to_le_bytes());
+    put(&mut data, 24, &u32::MAX.to_le_bytes());
+
+    // Name, schema, data type, row size, rows and row count
+    let tables = [
+        (Some("mod:MyBuff"), Some(140), 13, 40, 348, 1),
+        (None, None, 7u32, 4u32, 388, 3u32),
+        (None, Some(164), 13, 8, 400, 1),
+        (None, None, 1, 1, STRINGS, text.len() as u32),
+    ];
+    for (i, (name, schema, data_type, row_size, rows, row_count)) in tables.into_iter().enumerate() {
+        let at = 28 + i * 28;
+        match name {
+            Some(name) => offset(&mut data, at, strings[name]),
+            None => null(&mut data, at),
+        }
+        match schema {
+            Some(schema) => offset(&mut data, at + 8, schema),
+            None => null(&mut data, at + 8),
+        }
+        put(&mut data, at + 12, &data_type.to_le_bytes());
+        put(&mut data, at + 16, &row_size.to_le_bytes());
+        offset(&mut data, at + 20, rows);
+        put(&mut data, at + 24, &row_count.to_le_bytes());
+    }
+
+    let schemas = [("Buff", 40u32, 188, 6u32), ("Child", 8, 308, 2)];
+    for (i, (name, size, columns, count)) in schemas.into_iter().enumerate() {
+        let at = 140 + i * 24;
+        offset(&mut data, at, strings[name]);
+        put(&mut data, at + 4, &(0x1000 + i as u32).to_le_bytes());
+        put(&mut data, at + 8, &0xABCDu32.to_le_bytes());
+        put(&mut data, at + 12, &size.to_le_bytes());
+        offset(&mut data, at + 16, columns);
+        put(&mut data, at + 20, &count.to_le_bytes());
+    }
+    let columns: [(usize, &str, u16, u32, Option<usize>); 8] = [
+        (188, "duration", 10, 0, None),
+        (208, "name", 11, 4, None),
+        (228, "tags", 14, 8, None),
+        (248, "key", 19, 16, None),
+        (268, "child", 13, 32, Some(164)),
+        (288, "loc", 20, 36, None),
+        (308, "value", 6, 0, None),
+        (328, "flag", 0, 4, None),
+    ];
+    for (at, name, data_type, field_offset, schema) in columns {
+        offset(&mut data, at, strings[name]);
+        put(&mut data, at + 8, &data_type.to_le_bytes());
+        put(&mut data, at + 12, &field_offset.to_le_bytes());
+        match schema {
+            Some(schema) => offset(&mut data, at + 16, schema),
+            None => null(&mut data, at + 16),
+        }
+    }
+
+    // The buff's row, its tags and its child
+    put(&mut data, 348, &duration.to_le_bytes());
+    offset(&mut data, 352, strings["Hello"]);
+    offset(&mut data, 356, 388);
+    put(&mut data, 360, &3u32.to_le_bytes());
+    put(&mut data, 364, &0x00A1B2C3D4E5F601u64.to_le_bytes());
+    put(&mut data, 372, &0x00B2D882u32.to_le_bytes());
+    put(&mut data, 376, &0u32.to_le_bytes());
+    offset(&mut data, 380, 400);
+    put(&mut data, 384, &0x1A2B3C4Du32.to_le_bytes());
+    for (i, tag) in [65u32, 66, 1234].into_iter().enumerate() {
+        put(&mut data, 388 + i * 4, &tag.to_le_bytes());
+    }
+    put(&mut data, 400, &(-7i32).to_le_bytes());
+    put(&mut data, 404, &[1]);
+    data
+}
+
+#[test]
+fn test_simdata_object_model() {
+    let data = buff_simdata(4.5);
+    let simdata = SimDataResource::from_bytes(&data).unwrap();
+    assert_eq!(simdata.version, 0x101);
+    assert_eq!(simdata.tables.len(), 4);
+    assert_eq!(simdata.schemas.iter().map(|s| s.name.as_deref()).collect::<Vec<_>>(), [Some("Buff"), Some("Child")]);
+    assert_eq!(simdata.schemas[0].columns[2].data_type, SimDataType::Vector);
+    assert_eq!(simdata.tables[2].schema, Some(1));
+    assert_eq!(simdata.to_bytes().unwrap(), data);
+
+    let instances = simdata.instances().unwrap();
+    assert_eq!(instances.len(), 1);
+    let (name, buff) = &instances[0];
+    assert_eq!(name, "mod:MyBuff");
+    assert_eq!(buff.schema.as_deref(), Some("Buff"));
+    assert_eq!(buff.get("duration"), Some(&SimDataValue::Float(4.5)));
+    assert_eq!(buff.get("name"), Some(&SimDataValue::String(Some("Hello".to_string()))));
+    assert_eq!(buff.get("tags"), Some(&SimDataValue::Vector(vec![
+        SimDataValue::UInt32(65), SimDataValue::UInt32(66), SimDataValue::UInt32(1234),
+    ])));
+    assert_eq!(buff.get("key"), Some(&SimDataValue::ResourceKey(TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x00A1B2C3D4E5F601 })));
+    let Some(SimDataValue::Object(Some(child))) = buff.get("child") else { panic!("expected a child object") };
+    assert_eq!(child.fields, [("value".to_string(), SimDataValue::Int32(-7)), ("flag".to_string(), SimDataValue::Boolean(true))]);
+    assert_eq!(buff.get("loc"), Some(&SimDataValue::LocalizationKey(0x1A2B3C4D)));
+    assert_eq!(buff.flatten()["tags[2]"], "1234");
+    assert_eq!(simdata.rows(1).unwrap().len(), 3);
+
+    // Comparing two versions names the fields they disagree on
+    let other = SimDataResource::from_bytes(&buff_simdata(8.0)).unwrap();
+    assert_eq!(simdata.differences(&other).unwrap(), ["mod:MyBuff.duration"]);
+    assert!(simdata.differences(&simdata).unwrap().is_empty());
+
+    // Offsets that point past the end are reported, not followed
+    assert!(SimDataResource::from_bytes(&data[..200]).is_err());
+}
+
+#[test]
+fn test_simdata_corrupt_counts() {
+    use s4pi_reforged::TypedResource;
+
+    // Counts far beyond what the data could hold are rejected before anything is allocated
+    for (at, count) in [(12, u32::MAX), (20, u32::MAX), (160, 0x7FFF_FFFF)] {
+        let mut data = buff_simdata(1.0);
+        put(&mut data, at, &count.to_le_bytes());
+        assert!(TypedResource::from_bytes(0x545AC67A, &data).is_err(), "count at {}", at);
+    }
+
+    // A vector into a table with no row size can't be stepped through
+    let mut data = buff_simdata(1.0);
+    put(&mut data, 28 + 28 + 16, &0u32.to_le_bytes());
+    put(&mut data, 360, &u32::MAX.to_le_bytes());
+    let simdata = SimDataResource::from_bytes(&data).unwrap();
+    assert!(simdata.instances().is_err());
+}
+
+#[test]
+fn test_simdata_xml_round_trip() {
+    let simdata = SimDataResource::from_bytes(&buff_simdata(4.5)).unwrap();
+    let xml = to_xml(&simdata).unwrap();
+    assert!(xml.contains("<I name=\"mod:MyBuff\" schema=\"Buff\">"));
+    assert!(xml.contains("<T name=\"duration\">4.5</T>"));
+    assert!(xml.contains("<T type=\"UInt32\">1234</T>"));
+    assert!(xml.contains("<Column name=\"child\" name_hash=\"0x00000000\" type=\"Object\" flags=\"0x0000\" schema=\"Child\" />"));
+
+    // Reading it back lays the data out anew, with the same objects in it
+    let rebuilt = from_xml(&xml).unwrap();
+    assert_eq!(rebuilt.version, 0x101);
+    assert_eq!(rebuilt.header_word(), Some(u32::MAX));
+    assert_eq!(rebuilt.instances().unwrap(), simdata.instances().unwrap());
+    assert_eq!(rebuilt.schemas[0].columns.iter().map(|c| c.offset).collect::<Vec<_>>(), [0, 4, 8, 16, 32, 36]);
+    assert_eq!(to_xml(&rebuilt).unwrap(), xml);
+
+    // An edit made in the text comes back as that one difference
+    let edited = from_xml(&xml.replace(">4.5<", ">8<")).unwrap();
+    assert_eq!(simdata.differences(&edited).unwrap(), ["mod:MyBuff.duration"]);
+    let error = from_xml(&xml.replace(">4.5<", ">slow<")).unwrap_err();
+    assert!(format!("{:#}", error).contains("duration"), "{:#}", error);
+}
@@ -0,0 +1,68 @@
+use s4pi_reforged::manifest::{add_checksums, verify_parts, VerifyIssue};
+use s4pi_reforged::package::resource::{ManifestEntry, ManifestResource, ResourceChecksum};
+use s4pi_reforged::split::{is_split_output, merge_parts, MergeCategory};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn tgi(res_type: u32, instance: u64) -> TGI {
+    TGI { res_type, res_group: 0, instance }
+}
+
+#[test]
+fn test_classify_packages() {
+    let casp = tgi(0x034AE111, 1);
+    let cobj = tgi(0x319E4F1D, 2);
+    let objd = tgi(0xC0DB5AE7, 2);
+    let tuning = tgi(0x0C772E27, 3);
+    let simdata = tgi(0x545AC67A, 3);
+    let texture = tgi(0x00B2D882, 4);
+
+    assert_eq!(MergeCategory::classify(&[texture, casp]), MergeCategory::Cas);
+    assert_eq!(MergeCategory::classify(&[cobj, objd, texture]), MergeCategory::BuildBuy);
+    assert_eq!(MergeCategory::classify(&[tuning, simdata]), MergeCategory::Tuning);
+    assert_eq!(MergeCategory::classify(&[tuning, texture]), MergeCategory::Other);
+    assert_eq!(MergeCategory::classify(&[]), MergeCategory::Other);
+    assert_eq!(MergeCategory::BuildBuy.file_name(), "merged_BuildBuy.package");
+}
+
+#[test]
+fn test_split_parts_share_a_manifest() {
+    let dir = std::env::temp_dir().join(format!("s4pi_split_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let cas = [tgi(0x034AE111, 1), tgi(0x00B2D882, 1)];
+    let tuning = [tgi(0x0C772E27, 2)];
+    let mut manifest = ManifestResource {
+        version: 1,
+        padding: 0,
+        entries: vec![
+            ManifestEntry { name: "hair".to_string(), resources: cas.to_vec(), checksums: Vec::new() },
+            ManifestEntry { name: "trait".to_string(), resources: tuning.to_vec(), checksums: Vec::new() },
+        ],
+    };
+    let checksums: HashMap<TGI, ResourceChecksum> = cas.iter().chain(&tuning)
+        .map(|t| (*t, ResourceChecksum::of(&[t.instance as u8; 16])))
+        .collect();
+    add_checksums(&mut manifest, &checksums);
+
+    for (category, tgis) in [(MergeCategory::Cas, &cas[..]), (MergeCategory::Tuning, &tuning[..])] {
+        let entries: HashMap<TGI, (Vec<u8>, u32, u16, u16)> = tgis.iter()
+            .map(|t| (*t, (vec![t.instance as u8; 16], 16, 0, 1)))
+            .collect();
+        Package::write_merged(dir.join(category.file_name()), &entries, false).unwrap();
+    }
+
+    let cas_path = dir.join("merged_CAS.package");
+    assert!(is_split_output(&cas_path));
+    assert_eq!(merge_parts(&cas_path), vec![cas_path.clone(), dir.join("merged_Tuning.package")]);
+    assert_eq!(merge_parts(Path::new("merged.package")).len(), 1);
+
+    let parts: Vec<Package> = merge_parts(&cas_path).iter().map(|p| Package::open(p).unwrap()).collect();
+    assert!(verify_parts(&parts, &manifest).is_empty());
+
+    let cas_only = vec![Package::open(&cas_path).unwrap()];
+    assert_eq!(verify_parts(&cas_only, &manifest), vec![VerifyIssue::Missing { package: "trait".to_string(), tgi: tuning[0] }]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,322 @@
+use s4pi_reforged::translate::{fill_missing_blocking, import_prefilled_blocking, review_csv, MachineTranslation, Translator};
+use s4pi_reforged::stbl::{find_key_collisions, merge_tables, table_from_json, table_from_po, table_to_json, table_to_po, parse_key_mapping, pseudo_localize, pseudo_localize_table, locale_code, locale_of, rekey_package, rekey_xml, reverse_key_mapping, strip_locales, with_locale, Locale, PseudoOptions};
+use s4pi_reforged::{Package, Resource, StblResource, TypedResource, TGI};
+use std::path::PathBuf;
+use s4pi_reforged::package::resource::StblEntry;
+
+#[test]
+fn test_pseudo_localize_string() {
+    let options = PseudoOptions::default();
+    let out = pseudo_localize("Hello", &options);
+    assert!(out.starts_with("[Ĥéļļö"));
+    assert!(out.ends_with(']'));
+    assert!(out.chars().count() > "[Hello]".len());
+}
+
+#[test]
+fn test_pseudo_localize_keeps_tokens() {
+    let options = PseudoOptions { accents: true, padding_percent: 0, brackets: false };
+    let out = pseudo_localize("Hi {0.SimFirstName}, <font color=\"#FF0000\">go</font>", &options);
+    assert_eq!(out, "Ĥí {0.SimFirstName}, <font color=\"#FF0000\">ĝö</font>");
+}
+
+#[test]
+fn test_pseudo_localize_table() {
+    let mut stbl = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: vec![StblEntry { key_hash: 0x12345678, flags: 0, string_value: "Sit".to_string() }],
+    };
+    stbl.recalculate_string_length();
+    assert_eq!(stbl.string_length, 4);
+
+    let pseudo = pseudo_localize_table(&stbl, &PseudoOptions::default());
+    assert_eq!(pseudo.entries[0].key_hash, 0x12345678);
+    assert_eq!(pseudo.string_length, pseudo.entries[0].string_value.len() as u32 + 1);
+
+    let bytes = pseudo.to_bytes().unwrap();
+    let reparsed = StblResource::from_bytes(&bytes).unwrap();
+    assert_eq!(reparsed.entries[0].string_value, pseudo.entries[0].string_value);
+}
+
+struct Shout;
+
+impl Translator for Shout {
+    async fn translate_batch(&self, texts: &[String], _source: Locale, _target: Locale) -> s4pi_reforged::Result<Vec<String>> {
+        Ok(texts.iter().map(|t| t.to_uppercase()).collect())
+    }
+
+    fn batch_size(&self) -> usize {
+        1
+    }
+}
+
+fn table(entries: &[(u32, &str)]) -> StblResource {
+    let mut stbl = StblResource {
+        version: 5,
+        is_compressed: 0,
+        reserved: [0, 0],
+        string_length: 0,
+        entries: entries.iter()
+            .map(|(key_hash, s)| StblEntry { key_hash: *key_hash, flags: 0, string_value: s.to_string() })
+            .collect(),
+    };
+    stbl.recalculate_string_length();
+    stbl
+}
+
+#[test]
+fn test_fill_missing_translations() {
+    let english = table(&[(1, "Sit"), (2, "Stand"), (3, "Dance")]);
+    let mut german = table(&[(1, "Sitzen"), (2, "")]);
+
+    let added = fill_missing_blocking(&Shout, &english, Locale::English, &mut german, Locale::German).unwrap();
+    assert_eq!(added.len(), 2);
+    assert_eq!(added[0], MachineTranslation { key_hash: 2, source_text: "Stand".to_string(), text: "STAND".to_string() });
+    assert_eq!(german.entries.len(), 3);
+    assert_eq!(german.entries[0].string_value, "Sitzen");
+    assert_eq!(german.entries[2].string_value, "DANCE");
+    assert_eq!(german.string_length, 7 + 6 + 6);
+
+    assert!(review_csv(&added).contains("0x00000002,\"Stand\",\"STAND\""));
+}
+
+#[test]
+fn test_import_prefilled() {
+    let english = table(&[(1, "Sit"), (2, "Stand")]);
+    let tgi = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: with_locale(0x1234, Locale::German) };
+    let german = table(&[(1, "Sitzen")]);
+
+    // JSON keeps the machine translation, marked
+    let json = import_prefilled_blocking(&Shout, &table_to_json(&tgi, &german), &english, Locale::English).unwrap();
+    assert_eq!(json.tgi, tgi);
+    assert_eq!(json.added.len(), 1);
+    assert_eq!(json.table.entries[1].string_value, "STAND");
+    assert!(json.text.contains("\"machine_translated\": true"));
+    assert_eq!(json.text.matches("machine_translated").count(), 1);
+    assert_eq!(table_from_json(&json.text).unwrap().1.entries[1].string_value, "STAND");
+
+    // A .po file flags it fuzzy, so it reads back as English until someone accepts it
+    let po = import_prefilled_blocking(&Shout, &table_to_po(&tgi, &english, Some(&german)), &english, Locale::English).unwrap();
+    assert_eq!(po.table.entries[1].string_value, "STAND");
+    assert!(po.text.contains("#, fuzzy\nmsgctxt \"0x00000002\"\nmsgid \"Stand\"\nmsgstr \"STAND\""));
+    assert_eq!(po.text.matches("fuzzy").count(), 1);
+    let (po_tgi, reread) = table_from_po(&po.text).unwrap();
+    assert_eq!(po_tgi, tgi);
+    assert_eq!(reread.entries[0].string_value, "Sitzen");
+    assert_eq!(reread.entries[1].string_value, "Stand");
+    assert_eq!(table_from_po(&po.text.replace("#, fuzzy\n", "")).unwrap().1.entries[1].string_value, "STAND");
+}
+
+#[test]
+fn test_find_key_collisions() {
+    let english = |instance: u64| TGI { res_type: 0x220557DA, res_group: 0x80000000, instance };
+    let german = |instance: u64| TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: (0x02 << 56) | instance };
+    assert_eq!(locale_code(german(1).instance), 0x02);
+
+    let tables = vec![
+        (PathBuf::from("a.package"), english(1), table(&[(1, "Sit"), (2, "Stand"), (3, "Dance")])),
+        (PathBuf::from("a.package"), german(1), table(&[(1, "Sitzen")])),
+        (PathBuf::from("b.package"), english(2), table(&[(1, "Sit"), (2, "Stnad")])),
+        (PathBuf::from("c.package"), german(3), table(&[(1, "Hinsetzen")])),
+        (PathBuf::from("c.package"), english(3), table(&[(3, "Dance")])),
+    ];
+
+    let collisions = find_key_collisions(&tables);
+    assert_eq!(collisions.len(), 2);
+    assert_eq!((collisions[0].locale, collisions[0].key_hash), (0x00, 2));
+    assert_eq!(collisions[0].definitions[1].package, PathBuf::from("b.package"));
+    assert_eq!(collisions[0].definitions[1].text, "Stnad");
+    assert_eq!((collisions[1].locale, collisions[1].key_hash), (0x02, 1));
+    assert_eq!(collisions[1].definitions.len(), 2);
+
+    // The same package disagreeing with itself isn't a cross-package collision
+    let single = vec![
+        (PathBuf::from("a.package"), english(1), table(&[(1, "Sit")])),
+        (PathBuf::from("a.package"), english(2), table(&[(1, "Seat")])),
+    ];
+    assert!(find_key_collisions(&single).is_empty());
+}
+
+#[test]
+fn test_locale_of_instance() {
+    let english = 0x00A1B2C3D4E5F601u64;
+    let german = with_locale(english, Locale::German);
+    assert_eq!(german, 0x08A1B2C3D4E5F601);
+    assert_eq!(locale_of(german), Some(Locale::German));
+    assert_eq!(with_locale(german, Locale::English), english);
+    assert_eq!(locale_of(0x09A1B2C3D4E5F601), None);
+
+    assert_eq!("GER_DE".parse::<Locale>().unwrap(), Locale::German);
+    assert_eq!("spanish (latin america)".parse::<Locale>().unwrap(), Locale::SpanishLatinAmerica);
+    assert_eq!("0x11".parse::<Locale>().unwrap(), Locale::PortugueseBrazil);
+    assert!("Klingon".parse::<Locale>().is_err());
+    assert!(Locale::ALL.iter().all(|l| Locale::from_code(l.code()) == Some(*l)));
+}
+
+#[test]
+fn test_rekey_strings_and_tuning() {
+    let mapping = parse_key_mapping("# old new\n0x0000AAAA 0x0000BBBB\n0000cccc=0x0000DDDD\n\n").unwrap();
+    assert_eq!(mapping.len(), 2);
+    assert!(parse_key_mapping("0x1").is_err());
+    assert!(parse_key_mapping("1 2\n1 3").is_err());
+    assert_eq!(reverse_key_mapping(&mapping).unwrap()[&0xBBBB], 0xAAAA);
+    assert!(reverse_key_mapping(&parse_key_mapping("1 3\n2 3").unwrap()).is_err());
+
+    let (xml, changed) = rekey_xml("<T n=\"a\">0x0000aaaa</T><T>0x0000AAAA1</T><T>0x0000CCCC<!--x--></T>", &mapping);
+    assert_eq!(changed, 2);
+    assert_eq!(xml, "<T n=\"a\">0x0000BBBB</T><T>0x0000AAAA1</T><T>0x0000DDDD<!--x--></T>");
+
+    let dir = std::env::temp_dir().join(format!("s4pi_rekey_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stbl_tgi = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: 1 };
+    let tuning_tgi = TGI { res_type: 0x0333406C, res_group: 0, instance: 2 };
+    let stbl_data = table(&[(0xAAAA, "Sit"), (0x1234, "Stand")]).to_bytes().unwrap();
+    let tuning = b"<?xml version=\"1.0\"?><I n=\"x\"><T n=\"display_name\">0x0000AAAA</T></I>".to_vec();
+    let mut entries = std::collections::HashMap::new();
+    entries.insert(stbl_tgi, (stbl_data.clone(), stbl_data.len() as u32, 0x5A42, 1));
+    entries.insert(tuning_tgi, (tuning.clone(), tuning.len() as u32, 0x5A42, 1));
+    let path = dir.join("mod.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let dry = rekey_package(&path, &mapping, true).unwrap();
+    assert_eq!((dry.strings, dry.tables, dry.tuning_refs, dry.tuning_files), (1, 1, 1, 1));
+    let report = rekey_package(&path, &mapping, false).unwrap();
+    assert_eq!(report, dry);
+
+    let pkg = Package::open(&path).unwrap();
+    let entry = pkg.find(&stbl_tgi).cloned().unwrap();
+    let TypedResource::Stbl(stbl) = pkg.read_resource(&entry).unwrap() else { panic!("Expected a string table") };
+    assert_eq!(stbl.entries[0].key_hash, 0xBBBB);
+    assert_eq!(stbl.entries[0].string_value, "Sit");
+    let entry = pkg.find(&tuning_tgi).cloned().unwrap();
+    assert!(String::from_utf8(pkg.read_raw_resource(&entry).unwrap()).unwrap().contains(">0x0000BBBB<"));
+    drop(pkg);
+
+    // Moving a string onto a key another string keeps is refused
+    let clash = parse_key_mapping("0x0000BBBB 0x00001234").unwrap();
+    let report = rekey_package(&path, &clash, false).unwrap();
+    assert_eq!(report.conflicts, vec![(stbl_tgi, 0x1234)]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_stbl_json_round_trip() {
+    let tgi = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: with_locale(0x00AB, Locale::French) };
+    let json = table_to_json(&tgi, &table(&[(0xAAAA, "Sit"), (0x1234, "Say \"hi\"\nnow")]));
+    assert!(json.contains("\"key\": \"0x0000AAAA\""));
+    assert!(json.contains("\"language\": \"FRE_FR\""));
+
+    // A translator's edit: new text for one string and a new string
+    let edited = json.replace("\"Sit\"", "\"S'asseoir\"").replace(
+        "\"strings\": [",
+        "\"strings\": [\n    { \"key\": \"0x00005678\", \"text\": \"Été\" },",
+    );
+    let (read_tgi, stbl) = table_from_json(&edited).unwrap();
+    assert_eq!(read_tgi, tgi);
+    let strings: Vec<(u32, &str)> = stbl.entries.iter().map(|e| (e.key_hash, e.string_value.as_str())).collect();
+    assert_eq!(strings, vec![(0x5678, "Été"), (0xAAAA, "S'asseoir"), (0x1234, "Say \"hi\"\nnow")]);
+    assert_eq!(stbl.string_length, "Été".len() as u32 + "S'asseoir".len() as u32 + "Say \"hi\"\nnow".len() as u32 + 3);
+    let reread = StblResource::from_bytes(&stbl.to_bytes().unwrap()).unwrap();
+    assert_eq!(reread.entries[0].string_value, "Été");
+
+    assert!(table_from_json(&json.replace("0x00001234", "0x0000AAAA")).is_err());
+    assert!(table_from_json(&json.replace("220557DA", "0333406C")).is_err());
+}
+
+#[test]
+fn test_stbl_po_round_trip() {
+    let english_tgi = TGI { res_type: 0x220557DA, res_group: 0x80000000, instance: 0x00AB };
+    let mut english = table(&[(0xAAAA, "Sit"), (0x1234, "Say \"hi\"\nnow"), (0x5678, "Stand")]);
+    english.entries[2].flags = 3;
+
+    let template = table_to_po(&english_tgi, &english, None);
+    assert!(template.contains("\"Language: \\n\""));
+    assert!(template.contains("msgctxt \"0x00001234\"\nmsgid \"\"\n\"Say \\\"hi\\\"\\n\"\n\"now\"\nmsgstr \"\"\n"), "{}", template);
+    assert!(template.contains("#. flags: 3\nmsgctxt \"0x00005678\""));
+
+    // What a translation tool makes of the template: a language, one translation wrapped over
+    // two lines, one marked fuzzy and one left untranslated, plus comments it adds
+    let translated = template
+        .replace("\"Language: \\n\"", "\"Language: de\\n\"")
+        .replace("msgid \"Sit\"\nmsgstr \"\"", "#: ui/chair\nmsgid \"Sit\"\nmsgstr \"\"\n\"Hin\"\n\"setzen\"")
+        .replace("msgid \"Stand\"\nmsgstr \"\"", "msgid \"Stand\"\nmsgstr \"Stehen\"")
+        .replace("#. flags: 3", "#, fuzzy\n#. flags: 3")
+        + "\n#~ msgctxt \"0x00009999\"\n#~ msgid \"Old\"\n#~ msgstr \"Alt\"\n";
+    let (tgi, german) = table_from_po(&translated).unwrap();
+    assert_eq!(tgi, TGI { instance: with_locale(0x00AB, Locale::German), ..english_tgi });
+    let strings: Vec<(u32, u8, &str)> = german.entries.iter().map(|e| (e.key_hash, e.flags, e.string_value.as_str())).collect();
+    assert_eq!(strings, vec![(0xAAAA, 0, "Hinsetzen"), (0x1234, 0, "Say \"hi\"\nnow"), (0x5678, 3, "Stand")]);
+    assert_eq!(german.string_length, german.entries.iter().map(|e| e.string_value.len() as u32 + 1).sum::<u32>());
+
+    // Exported again, the translation keeps its language and the English text as msgids
+    let po = table_to_po(&tgi, &english, Some(&german));
+    assert!(po.contains("\"Language: de_DE\\n\""));
+    assert!(po.contains("msgid \"Sit\"\nmsgstr \"Hinsetzen\""));
+    let (same_tgi, same) = table_from_po(&po).unwrap();
+    assert_eq!(same_tgi, tgi);
+    assert_eq!(same.entries.len(), 3);
+
+    assert_eq!(Locale::from_language_code("pt-BR"), Some(Locale::PortugueseBrazil));
+    assert_eq!(Locale::from_language_code("es_AR"), Some(Locale::Spanish));
+    assert!(table_from_po(&po.replace("de_DE", "tlh")).is_err());
+    assert!(table_from_po(&po.replace("X-Stbl-TGI", "X-Other")).is_err());
+    assert!(table_from_po(&po.replace("0x00005678", "0x0000AAAA")).is_err());
+}
+
+#[test]
+fn test_merge_tables() {
+    let english = |instance| TGI { res_type: 0x220557DA, res_group: 0x80000000, instance };
+    let tables = vec![
+        (PathBuf::from("a.package"), english(0x10), table(&[(1, "One"), (2, "Two")])),
+        (PathBuf::from("b.package"), english(0x20), table(&[(2, "Second"), (3, "Three")])),
+        (PathBuf::from("b.package"), english(with_locale(0x20, Locale::French)), table(&[(2, "Deux")])),
+        (PathBuf::from("c.package"), english(0x30), table(&[(3, "Three")])),
+    ];
+    let merge = merge_tables(&tables);
+
+    assert_eq!(merge.tables.len(), 2);
+    let (tgi, merged) = &merge.tables[0];
+    assert_eq!(*tgi, english(0x10));
+    let strings: Vec<(u32, &str)> = merged.entries.iter().map(|e| (e.key_hash, e.string_value.as_str())).collect();
+    assert_eq!(strings, vec![(1, "One"), (2, "Second"), (3, "Three")]);
+    assert_eq!(merged.string_length, 4 + 7 + 6);
+    assert_eq!(merge.tables[1].0, english(with_locale(0x10, Locale::French)));
+    assert_eq!(merge.replaced, 2);
+    // The two packages that agree on key 3 aren't a collision
+    assert_eq!(merge.collisions.len(), 1);
+    assert_eq!(merge.collisions[0].key_hash, 2);
+    assert!(merge_tables(&[]).tables.is_empty());
+}
+
+#[test]
+fn test_strip_locales() {
+    let dir = std::env::temp_dir().join(format!("s4pi_strip_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stbl = |locale: Locale| TGI { res_type: 0x220557DA, res_group: 0, instance: with_locale(0x1234, locale) };
+    // A top byte no language uses
+    let unknown = TGI { res_type: 0x220557DA, res_group: 0, instance: 0x7F00_0000_0000_1234 };
+    let tuning = TGI { res_type: 0x0333406C, res_group: 0, instance: 0x1234 };
+    let data = table(&[(1, "Hi")]).to_bytes().unwrap();
+    let mut entries = std::collections::HashMap::new();
+    for tgi in [stbl(Locale::English), stbl(Locale::French), stbl(Locale::German), unknown, tuning] {
+        entries.insert(tgi, (data.clone(), data.len() as u32, 0, 1));
+    }
+    let path = dir.join("mod.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let keep = [Locale::English, Locale::German];
+    assert_eq!(strip_locales(&path, &keep, true).unwrap(), vec![stbl(Locale::French)]);
+    assert_eq!(Package::open(&path).unwrap().entries.len(), 5);
+    assert_eq!(strip_locales(&path, &keep, false).unwrap(), vec![stbl(Locale::French)]);
+    let pkg = Package::open(&path).unwrap();
+    assert_eq!(pkg.entries.len(), 4);
+    assert!(pkg.find(&stbl(Locale::French)).is_none());
+    assert!(pkg.find(&unknown).is_some() && pkg.find(&tuning).is_some());
+    drop(pkg);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
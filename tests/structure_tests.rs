@@ -0,0 +1,88 @@
+use s4pi_reforged::package::structure::{guess_structure, FieldGuess, TextEncoding};
+use s4pi_reforged::TGI;
+
+#[test]
+fn test_guess_structure() {
+    let texture = TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x1122334455667788 };
+    let mut data = Vec::new();
+    data.extend_from_slice(&0xDEADu16.to_le_bytes());
+    // CASP-style name
+    let name: Vec<u8> = "yfTop".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+    data.push(name.len() as u8);
+    data.extend_from_slice(&name);
+    for value in [1.0f32, 0.5, -2.25] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(b"bone_name\0");
+    // u8 count of ITG-ordered keys, as at the end of a CASP
+    data.push(2);
+    for instance in [texture.instance, texture.instance + 1] {
+        data.extend_from_slice(&instance.to_le_bytes());
+        data.extend_from_slice(&texture.res_type.to_le_bytes());
+        data.extend_from_slice(&texture.res_group.to_le_bytes());
+    }
+    // u32 count of 8-byte records to the end
+    data.extend_from_slice(&3u32.to_le_bytes());
+    data.extend_from_slice(&[0xAB; 24]);
+
+    let regions = guess_structure(&data);
+    let guesses: Vec<&FieldGuess> = regions.iter().map(|r| &r.guess).collect();
+    assert_eq!(guesses, vec![
+        &FieldGuess::Unknown,
+        &FieldGuess::Text { text: "yfTop".to_string(), encoding: TextEncoding::SevenBitUtf16Be },
+        &FieldGuess::Floats(vec![1.0, 0.5, -2.25]),
+        &FieldGuess::Text { text: "bone_name".to_string(), encoding: TextEncoding::NullTerminated },
+        &FieldGuess::Array { count: 2, count_size: 1, record_size: 16, tgis: true },
+        &FieldGuess::Array { count: 3, count_size: 4, record_size: 8, tgis: false },
+    ]);
+    assert_eq!(regions.iter().map(|r| r.len).sum::<usize>(), data.len());
+    assert_eq!((regions[0].offset, regions[0].len), (0, 2));
+    assert!(regions[4].to_string().contains("u8 count 2, then 2 x 16-byte records (TGIs)"), "{}", regions[4]);
+
+    let key: Vec<u8> = [texture.res_type.to_le_bytes().to_vec(), 0u32.to_le_bytes().to_vec(), texture.instance.to_le_bytes().to_vec()].concat();
+    assert_eq!(guess_structure(&key)[0].guess, FieldGuess::Tgi { tgi: texture, itg: false });
+}
+
+#[test]
+fn test_investigate_resource() {
+    use s4pi_reforged::package::investigate::{entropy, MAX_ENTROPY_BLOCKS};
+    use s4pi_reforged::Package;
+
+    // Zeros, a key past the part the structure guess looks at, then noise
+    let texture = TGI { res_type: 0x00B2D882, res_group: 0, instance: 0x1122334455667788 };
+    let mut data = vec![0u8; 100_000];
+    data.extend_from_slice(&texture.res_type.to_le_bytes());
+    data.extend_from_slice(&texture.res_group.to_le_bytes());
+    data.extend_from_slice(&texture.instance.to_le_bytes());
+    let mut state = 0x2545F491u32;
+    data.extend((0..200_000).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    }));
+    assert_eq!(entropy(&[7; 10]), 0.0);
+    assert_eq!(entropy(&[0, 1]), 1.0);
+
+    let dir = std::env::temp_dir().join(format!("s4pi_investigate_resource_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tgi = TGI { res_type: 0x12345678, res_group: 0, instance: 1 };
+    let entries = std::collections::HashMap::from([(tgi, (data.clone(), data.len() as u32, 0x5A42, 1))]);
+    let path = dir.join("big.package");
+    Package::write_merged(&path, &entries, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let report = pkg.investigate_resource(&tgi).unwrap();
+    assert_eq!(report.decompressed, Ok(data.len()));
+    assert!(report.stored_len < data.len());
+    assert!(report.parse_error.is_none());
+    assert_eq!(report.magic, None);
+    assert!(report.entropy_blocks.len() <= MAX_ENTROPY_BLOCKS);
+    assert_eq!(report.entropy_blocks[0].bits, 0.0);
+    assert!(report.entropy_blocks.last().unwrap().bits > 7.5);
+    assert_eq!(report.tgis.first(), Some(&(100_000, texture, false)));
+    assert_eq!(report.head, vec![0; 64]);
+    assert!(pkg.investigate_resource(&TGI { instance: 2, ..tgi }).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
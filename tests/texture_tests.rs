@@ -0,0 +1,290 @@
+use s4pi_reforged::texture::{
+    assign_channels, decode_dds, decode_rle2, dds_header, detect_format, extract_mip, extract_preview, parse_dds_header,
+    preview_level, shuffle_dst, texture_info, to_dds, unshuffle_dst, TextureChannel, TextureFormat, TextureInfo,
+};
+use s4pi_reforged::TGI;
+
+fn with_fourcc(mut header: Vec<u8>, fourcc: &[u8; 4]) -> Vec<u8> {
+    header[84..88].copy_from_slice(fourcc);
+    header
+}
+
+#[test]
+fn test_detect_format() {
+    assert_eq!(detect_format(&dds_header(4, 4, 1, *b"DXT5")), TextureFormat::Dds);
+    assert_eq!(detect_format(&with_fourcc(dds_header(4, 4, 1, *b"DXT5"), b"DST5")), TextureFormat::Dst);
+    assert_eq!(detect_format(b"DXT5RLE2\x04\x00\x04\x00"), TextureFormat::Rle2);
+    assert_eq!(detect_format(b"DXT5RLES\x04\x00\x04\x00"), TextureFormat::Rles);
+    assert_eq!(detect_format(b"LRLE\x00\x00\x00\x00"), TextureFormat::Lrle);
+    assert_eq!(detect_format(b"\x89PNG\r\n\x1a\n"), TextureFormat::Unknown);
+}
+
+#[test]
+fn test_unshuffle_dst1() {
+    // Two blocks: colour endpoints for both, then indices for both
+    let mut data = with_fourcc(dds_header(8, 4, 1, *b"DXT1"), b"DST1");
+    data.extend_from_slice(&[1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+
+    let dds = unshuffle_dst(&data).unwrap();
+    assert_eq!(&dds[84..88], b"DXT1");
+    assert_eq!(&dds[128..], &[1, 1, 1, 1, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4]);
+}
+
+#[test]
+fn test_unshuffle_dst5() {
+    let mut data = with_fourcc(dds_header(8, 4, 1, *b"DXT5"), b"DST5");
+    data.extend_from_slice(&[0xA0; 2 * 2]); // alpha endpoints
+    data.extend_from_slice(&[0xC0; 4 * 2]); // colour endpoints
+    data.extend_from_slice(&[0xA1; 6 * 2]); // alpha indices
+    data.extend_from_slice(&[0xC1; 4 * 2]); // colour indices
+
+    let dds = unshuffle_dst(&data).unwrap();
+    assert_eq!(&dds[84..88], b"DXT5");
+    let mut block = Vec::new();
+    block.extend_from_slice(&[0xA0; 2]);
+    block.extend_from_slice(&[0xA1; 6]);
+    block.extend_from_slice(&[0xC0; 4]);
+    block.extend_from_slice(&[0xC1; 4]);
+    assert_eq!(&dds[128..144], &block[..]);
+    assert_eq!(&dds[144..160], &block[..]);
+}
+
+#[test]
+fn test_dst_shuffle_round_trip() {
+    // Two DXT3 blocks: explicit alpha, colour endpoints, colour indices
+    let mut dds = dds_header(8, 4, 1, *b"DXT3");
+    for block in [0x10u8, 0x20] {
+        dds.extend_from_slice(&[block | 0xA; 8]);
+        dds.extend_from_slice(&[block | 0xC; 4]);
+        dds.extend_from_slice(&[block | 0xD; 4]);
+    }
+
+    let dst = shuffle_dst(&dds).unwrap();
+    assert_eq!(&dst[84..88], b"DST3");
+    let mut body = Vec::new();
+    for (part, len) in [(0xA, 8), (0xC, 4), (0xD, 4)] {
+        body.extend(std::iter::repeat_n(0x10 | part, len));
+        body.extend(std::iter::repeat_n(0x20 | part, len));
+    }
+    assert_eq!(&dst[128..], &body[..]);
+    assert_eq!(unshuffle_dst(&dst).unwrap(), dds);
+
+    // DXT1 and DXT5 come back as they were too
+    for fourcc in [*b"DXT1", *b"DXT5"] {
+        let mut dds = dds_header(8, 8, 2, fourcc);
+        dds.extend((0..80u8).map(|i| i.wrapping_mul(37)));
+        assert_eq!(unshuffle_dst(&shuffle_dst(&dds).unwrap()).unwrap(), dds);
+    }
+
+    let uncompressed = with_fourcc(dds_header(4, 4, 1, *b"DXT5"), &[0; 4]);
+    assert!(shuffle_dst(&uncompressed).is_err());
+}
+
+#[test]
+fn test_decode_rle2() {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"DXT5RLE2");
+    data.extend_from_slice(&8u16.to_le_bytes());
+    data.extend_from_slice(&4u16.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    // Mip header: commands, colour endpoints, colour indices, alpha endpoints, alpha indices
+    for offset in [36u32, 40, 44, 48, 48] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&((1u16 << 2) | 2).to_le_bytes()); // one opaque block
+    data.extend_from_slice(&(1u16 << 2).to_le_bytes()); // one transparent block
+    data.extend_from_slice(&[0x11; 4]);
+    data.extend_from_slice(&[0x22; 4]);
+
+    let dds = decode_rle2(&data).unwrap();
+    let info = parse_dds_header(&dds).unwrap();
+    assert_eq!((info.width, info.height, info.mip_count), (8, 4, 1));
+    assert_eq!(info.format_name(), "DXT5");
+    assert_eq!(&dds[128..136], &[0x00, 0x05, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(&dds[136..144], &[0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+    assert_eq!(&dds[144..160], &[0u8; 16]);
+    assert_eq!(to_dds(&data).unwrap(), dds);
+    assert_eq!(&extract_mip(&data, 0).unwrap()[128..], &dds[128..]);
+}
+
+#[test]
+fn test_extract_mip() {
+    // 8x4 DST5 with two mips: two blocks at level 0 and one at level 1, each block's bytes tagged
+    let mut data = with_fourcc(dds_header(8, 4, 2, *b"DXT5"), b"DST5");
+    for (section, len) in [(0xA0u8, 2), (0xC0, 4), (0xA1, 6), (0xC1, 4)] {
+        for block in 0..3u8 {
+            data.extend(std::iter::repeat_n(section + block * 2, len));
+        }
+    }
+    let full = unshuffle_dst(&data).unwrap();
+
+    let mip = extract_mip(&data, 1).unwrap();
+    let info = parse_dds_header(&mip).unwrap();
+    assert_eq!((info.width, info.height, info.mip_count), (4, 2, 1));
+    assert_eq!(info.format_name(), "DXT5");
+    assert_eq!(&mip[128..], &full[128 + 32..]);
+    assert_eq!(&extract_mip(&data, 0).unwrap()[128..], &full[128..128 + 32]);
+
+    // The unshuffled DDS gives the same level
+    assert_eq!(&extract_mip(&full, 1).unwrap()[128..], &mip[128..]);
+    assert!(extract_mip(&data, 2).is_err());
+
+    assert_eq!(preview_level(2048, 1024, 12, 256), 3);
+    assert_eq!(preview_level(64, 64, 1, 16), 0);
+    assert_eq!(parse_dds_header(&extract_preview(&data, 4).unwrap()).unwrap().width, 4);
+}
+
+#[test]
+fn test_decode_dxt1_to_png() {
+    let mut data = dds_header(4, 4, 1, *b"DXT1");
+    data.extend_from_slice(&0xF800u16.to_le_bytes()); // pure red
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+
+    let image = decode_dds(&data).unwrap();
+    assert_eq!((image.width, image.height), (4, 4));
+    assert!(image.pixels.chunks_exact(4).all(|p| p == [255, 0, 0, 255]));
+
+    let png = image.to_png().unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn test_decode_rles() {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"DXT5RLES");
+    data.extend_from_slice(&8u16.to_le_bytes());
+    data.extend_from_slice(&4u16.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    // As for RLE2, with the specular section last
+    for offset in [40u32, 44, 48, 52, 52, 52] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&((1u16 << 2) | 2).to_le_bytes());
+    data.extend_from_slice(&(1u16 << 2).to_le_bytes());
+    data.extend_from_slice(&[0x11; 4]);
+    data.extend_from_slice(&[0x22; 4]);
+    data.extend_from_slice(&[0x33; 4]);
+
+    let dds = to_dds(&data).unwrap();
+    let info = parse_dds_header(&dds).unwrap();
+    assert_eq!((info.width, info.height, info.mip_count), (8, 4, 1));
+    assert_eq!(dds.len(), 128 + 32);
+    assert_eq!(&dds[128..136], &[0x00, 0x05, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(&dds[136..144], &[0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+    assert_eq!(&dds[144..160], &[0u8; 16]);
+    assert_eq!(&extract_mip(&data, 0).unwrap()[128..], &dds[128..]);
+}
+
+fn lrle_header(version: u32, width: u16, height: u16) -> Vec<u8> {
+    let mut data = b"LRLE".to_vec();
+    data.extend_from_slice(&version.to_le_bytes());
+    data.extend_from_slice(&width.to_le_bytes());
+    data.extend_from_slice(&height.to_le_bytes());
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_decode_lrle() {
+    // 6x4, so the second 4x4 block hangs two pixels past the right edge
+    let mut data = lrle_header(0, 6, 4);
+    data.extend_from_slice(&20u32.to_le_bytes());
+    data.extend_from_slice(&[(16 << 3) | 2, 1, 2, 3, 0xFF]); // first block in one colour
+    data.extend_from_slice(&[(2 << 3) | 1, 10, 11, 12, 0xFF, 20, 21, 22, 0x80]);
+    data.push(2 << 3); // two transparent pixels
+    data.push((12 << 3) | 3); // twelve pixels channel by channel
+    data.extend_from_slice(&[(12 << 1) | 1, 0x40]);
+    data.push(12 << 1);
+    data.extend(0..12u8);
+    data.extend_from_slice(&[(12 << 1) | 1, 0x50, (12 << 1) | 1, 0xFF]);
+
+    let dds = to_dds(&data).unwrap();
+    let info = parse_dds_header(&dds).unwrap();
+    assert_eq!((info.width, info.height, info.mip_count), (6, 4, 1));
+    assert_eq!(dds.len(), 128 + 6 * 4 * 4);
+    let pixel = |x: usize, y: usize| &dds[128 + (y * 6 + x) * 4..][..4];
+    assert_eq!(pixel(0, 0), [1, 2, 3, 0xFF]);
+    assert_eq!(pixel(3, 3), [1, 2, 3, 0xFF]);
+    assert_eq!(pixel(4, 0), [10, 11, 12, 0xFF]);
+    assert_eq!(pixel(5, 0), [20, 21, 22, 0x80]);
+    assert_eq!(pixel(4, 1), [0x40, 0, 0x50, 0xFF]);
+    assert_eq!(pixel(5, 1), [0x40, 1, 0x50, 0xFF]);
+    assert_eq!(pixel(4, 2), [0x40, 4, 0x50, 0xFF]);
+    assert_eq!(pixel(5, 3), [0x40, 9, 0x50, 0xFF]);
+    assert_eq!(extract_mip(&data, 0).unwrap(), dds);
+
+    let image = decode_dds(&dds).unwrap();
+    assert_eq!(&image.pixels[..4], &[3, 2, 1, 0xFF]);
+
+    // Running past the image is an error
+    let mut long = lrle_header(0, 4, 4);
+    long.extend_from_slice(&20u32.to_le_bytes());
+    long.extend_from_slice(&[(17 << 3) | 2, 0, 0, 0, 0]);
+    assert!(to_dds(&long).is_err());
+}
+
+#[test]
+fn test_decode_lrle_v2_palette() {
+    let mut data = lrle_header(u32::from_le_bytes(*b"V002"), 8, 8);
+    data.extend_from_slice(&32u32.to_le_bytes()); // after the palette
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&[0, 0, 0, 0, 0x10, 0x20, 0x30, 0xFF]);
+    // 64 pixels of colour 1, the count carried on in the next byte
+    data.extend_from_slice(&[4 | 2, 2, 1]);
+
+    let dds = to_dds(&data).unwrap();
+    assert_eq!(dds.len(), 128 + 8 * 8 * 4);
+    assert!(dds[128..].chunks_exact(4).all(|p| p == [0x10, 0x20, 0x30, 0xFF]));
+
+    // Channel-coded pixels only exist in version 1, and indices must be in the palette
+    let mut channels = data.clone();
+    channels.truncate(channels.len() - 3);
+    let mut bad_index = channels.clone();
+    channels.extend_from_slice(&[(1 << 3) | 3, 3, 0]);
+    assert!(to_dds(&channels).is_err());
+    bad_index.extend_from_slice(&[4 | 2, 2, 5]);
+    assert!(to_dds(&bad_index).is_err());
+}
+
+fn info(format: TextureFormat, size: u32) -> TextureInfo {
+    TextureInfo { format, width: size, height: size, mip_count: 1, pixel_format: String::new() }
+}
+
+#[test]
+fn test_texture_info() {
+    let info = texture_info(&dds_header(4096, 2048, 13, *b"DXT5")).unwrap();
+    assert_eq!((info.width, info.height, info.mip_count), (4096, 2048, 13));
+    assert_eq!(info.pixel_format, "DXT5");
+    assert!(info.is_oversized());
+
+    let lrle = texture_info(b"LRLE\x00\x00\x00\x00\x00\x04\x00\x02\x09\x00\x00\x00").unwrap();
+    assert_eq!((lrle.format, lrle.width, lrle.height, lrle.mip_count), (TextureFormat::Lrle, 1024, 512, 9));
+    assert!(!lrle.is_oversized());
+}
+
+#[test]
+fn test_assign_channels() {
+    let tgi = |instance| TGI { res_type: 0, res_group: 0, instance };
+    let cas = assign_channels(true, &[
+        (tgi(1), info(TextureFormat::Rle2, 1024)),
+        (tgi(2), info(TextureFormat::Lrle, 1024)),
+        (tgi(3), info(TextureFormat::Rles, 1024)),
+        (tgi(4), info(TextureFormat::Dst, 1024)),
+        (tgi(5), info(TextureFormat::Rle2, 1024)),
+    ]);
+    let channels: Vec<TextureChannel> = cas.iter().map(|t| t.channel).collect();
+    assert_eq!(channels, vec![
+        TextureChannel::Shadow,
+        TextureChannel::Diffuse,
+        TextureChannel::Specular,
+        TextureChannel::Normal,
+        TextureChannel::Emission,
+    ]);
+
+    let object = assign_channels(false, &[(tgi(1), info(TextureFormat::Dst, 1024)), (tgi(2), info(TextureFormat::Dst, 512))]);
+    assert_eq!(object[0].channel, TextureChannel::Diffuse);
+    assert_eq!(object[1].channel, TextureChannel::Specular);
+}
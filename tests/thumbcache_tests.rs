@@ -0,0 +1,55 @@
+use s4pi_reforged::thumbcache::{match_mods, thumbnail_extension, ThumbCache};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+
+#[test]
+fn test_thumbnail_extension() {
+    assert_eq!(thumbnail_extension(&[0xFF, 0xD8, 0xFF, 0xE0]), "jpg");
+    assert_eq!(thumbnail_extension(b"\x89PNG\r\n\x1a\n"), "png");
+    assert_eq!(thumbnail_extension(b"ALFA"), "bin");
+}
+
+#[test]
+fn test_match_cached_thumbnails() {
+    let dir = std::env::temp_dir().join(format!("s4pi_thumbcache_test_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("Mods")).unwrap();
+
+    let hair = TGI { res_type: 0x034AE111, res_group: 0x80000000, instance: 0xAAAA_0000_0000_0001 };
+    let chair = TGI { res_type: 0xC0DB5AE7, res_group: 0, instance: 0xBBBB_0000_0000_0002 };
+    let uncached = TGI { res_type: 0x034AE111, res_group: 0x80000000, instance: 0xCCCC_0000_0000_0003 };
+
+    let small = [0xFF, 0xD8, 0x00];
+    let large = [0xFF, 0xD8, 0x01, 0x02, 0x03];
+    let mut cache = HashMap::new();
+    cache.insert(TGI { res_type: 0x3C1AF1F2, res_group: 0, instance: hair.instance }, (small.to_vec(), 3, 0, 1));
+    cache.insert(TGI { res_type: 0x3C1AF1F2, res_group: 1, instance: hair.instance }, (large.to_vec(), 5, 0, 1));
+    cache.insert(TGI { res_type: 0x5B282D45, res_group: 0, instance: chair.instance }, (small.to_vec(), 3, 0, 1));
+    let cache_path = dir.join("localthumbcache.package");
+    Package::write_merged(&cache_path, &cache, false).unwrap();
+
+    let mut mods = HashMap::new();
+    mods.insert(hair, (vec![0u8; 8], 8, 0, 1));
+    mods.insert(uncached, (vec![0u8; 8], 8, 0, 1));
+    mods.insert(TGI { res_type: 0x3C1AF1F2, res_group: 0, instance: hair.instance }, (small.to_vec(), 3, 0, 1));
+    Package::write_merged(dir.join("Mods/hair.package"), &mods, false).unwrap();
+
+    let mut mods = HashMap::new();
+    mods.insert(chair, (vec![0u8; 8], 8, 0, 1));
+    Package::write_merged(dir.join("Mods/chair.package"), &mods, false).unwrap();
+
+    let cache = ThumbCache::open(&cache_path).unwrap();
+    assert_eq!(cache.thumbnails.len(), 2);
+    assert_eq!(cache.read_best(hair.instance).unwrap().unwrap(), large);
+    assert!(cache.read_best(uncached.instance).unwrap().is_none());
+
+    let mut matches = match_mods(&cache, dir.join("Mods"));
+    matches.sort_by_key(|m| m.item.instance);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].item, hair);
+    assert!(matches[0].has_own_thumbnail);
+    assert_eq!(matches[1].item, chair);
+    assert!(!matches[1].has_own_thumbnail);
+    assert!(matches[1].package.ends_with("chair.package"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,210 @@
+use s4pi_reforged::tray::{decode_message, is_tray_file, CcIndex, GeneticPart, Household, SimFile, SliderValue, WireValue};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn bytes_field(field: u32, bytes: &[u8], out: &mut Vec<u8>) {
+    varint(((field as u64) << 3) | 2, out);
+    varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn varint_field(field: u32, value: u64, out: &mut Vec<u8>) {
+    varint((field as u64) << 3, out);
+    varint(value, out);
+}
+
+fn float_field(field: u32, value: f32, out: &mut Vec<u8>) {
+    varint(((field as u64) << 3) | 5, out);
+    out.extend_from_slice(&value.to_bits().to_le_bytes());
+}
+
+fn packed(values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &value in values {
+        varint(value, &mut out);
+    }
+    out
+}
+
+const SIM_ID: u64 = 0x0444_5555_6666_7777;
+const CASP_INSTANCE: u64 = 0x9A3F_1234_5678_ABCD;
+const SHOES_INSTANCE: u64 = 0x9A3F_1234_5678_0001;
+const SCULPT_INSTANCE: u64 = 0x0000_1111_2222_3333;
+const MODIFIER_INSTANCE: u64 = 0x0000_4444_5555_6666;
+const GENETIC_PART: u64 = 0x7A00_0000_0000_0042;
+const GENETIC_MODIFIER: u64 = 0x7B00_0000_0000_0043;
+const SKIN_TONE: u64 = 0x0000_0000_0001_D2A8;
+
+fn modifier(key: u64, amount: f32) -> Vec<u8> {
+    let mut out = Vec::new();
+    varint_field(1, key, &mut out);
+    float_field(2, amount, &mut out);
+    out
+}
+
+/// One Sim: names, an outfit, a sculpt and slider, genetics, a skin tone, and data the parser
+/// must not mistake for references (the Sim's id, a timestamp and an unknown message holding a
+/// 64-bit value and a float)
+fn sim_message(first: &[u8], part: u64) -> Vec<u8> {
+    let mut ids = Vec::new();
+    bytes_field(1, &packed(&[part, SHOES_INSTANCE]), &mut ids);
+    let mut body_types = Vec::new();
+    bytes_field(1, &packed(&[2, 8]), &mut body_types);
+    let mut outfit = Vec::new();
+    varint_field(1, 0x0123_4567_89AB_CDEF, &mut outfit); // outfit id
+    varint_field(2, 1, &mut outfit);
+    varint_field(3, 1_700_000_000_000, &mut outfit); // created
+    bytes_field(4, &ids, &mut outfit);
+    bytes_field(5, &body_types, &mut outfit);
+    let mut outfits = Vec::new();
+    bytes_field(1, &outfit, &mut outfits);
+
+    let mut face = Vec::new();
+    varint_field(1, SCULPT_INSTANCE, &mut face);
+    bytes_field(2, &modifier(MODIFIER_INSTANCE, 0.5), &mut face);
+
+    let mut genetic_part = Vec::new();
+    varint_field(1, GENETIC_PART, &mut genetic_part);
+    varint_field(2, 3, &mut genetic_part);
+    let mut genetic_face = Vec::new();
+    bytes_field(3, &modifier(GENETIC_MODIFIER, -0.25), &mut genetic_face);
+    let mut genetics = Vec::new();
+    bytes_field(1, &genetic_part, &mut genetics);
+    bytes_field(2, &genetic_face, &mut genetics);
+    bytes_field(3, b"0.5,0.25,0,0", &mut genetics);
+
+    let mut unknown = Vec::new();
+    unknown.push(0x09); // field 1, fixed64
+    unknown.extend_from_slice(&0x5555_6666_7777_8888u64.to_le_bytes());
+    float_field(2, 1.0, &mut unknown);
+
+    let mut sim = Vec::new();
+    varint_field(1, SIM_ID, &mut sim);
+    bytes_field(3, first, &mut sim);
+    bytes_field(4, b"Goth", &mut sim);
+    varint_field(5, 4, &mut sim);
+    bytes_field(6, &outfits, &mut sim);
+    bytes_field(7, &face, &mut sim);
+    bytes_field(8, &genetics, &mut sim);
+    varint_field(9, SKIN_TONE, &mut sim);
+    bytes_field(20, &unknown, &mut sim);
+    varint_field(21, 0x0000_0190_0000_0000, &mut sim);
+    sim
+}
+
+/// A household with one Sim, behind the header the game writes
+fn sample_sim() -> Vec<u8> {
+    let mut household = Vec::new();
+    bytes_field(2, b"Goth Family", &mut household);
+    bytes_field(5, &sim_message(b"Bella", CASP_INSTANCE), &mut household);
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&household);
+    data
+}
+
+#[test]
+fn test_decode_message() {
+    let fields = decode_message(&[0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i']).unwrap();
+    assert_eq!(fields, vec![(1, WireValue::Varint(150)), (2, WireValue::Bytes(b"hi".to_vec()))]);
+    assert!(decode_message(&[0x12, 0x05, b'h']).is_err());
+}
+
+#[test]
+fn test_is_tray_file() {
+    assert!(is_tray_file(Path::new("0x00000001!0x0123456789abcdef.householdbinary")));
+    assert!(is_tray_file(Path::new("Bella.sim")));
+    assert!(!is_tray_file(Path::new("Bella.package")));
+}
+
+#[test]
+fn test_parse_sim() {
+    let household = Household::parse(&sample_sim()).unwrap();
+    assert_eq!(household.name.as_deref(), Some("Goth Family"));
+    let sim = &household.sims[0];
+    assert_eq!(sim.name().as_deref(), Some("Bella Goth"));
+    assert_eq!(sim.sim_id, Some(SIM_ID));
+    assert_eq!(sim.age, Some(4));
+    assert_eq!(sim.outfits.len(), 1);
+    assert_eq!(sim.outfits[0].category, 1);
+    assert_eq!(sim.outfits[0].parts, vec![CASP_INSTANCE, SHOES_INSTANCE]);
+    assert_eq!(sim.outfits[0].body_types, vec![2, 8]);
+    assert_eq!(sim.face.sculpts, vec![SCULPT_INSTANCE]);
+    assert_eq!(sim.sliders(), &[SliderValue { instance: MODIFIER_INSTANCE, amount: 0.5 }]);
+
+    assert_eq!(sim.genetics.parts, vec![GeneticPart { instance: GENETIC_PART, body_type: 3 }]);
+    assert_eq!(sim.genetics.face.sliders, vec![SliderValue { instance: GENETIC_MODIFIER, amount: -0.25 }]);
+    assert_eq!(sim.genetics.physique.as_deref(), Some("0.5,0.25,0,0"));
+
+    // Only what the Sim wears or passes on is a reference; ids, timestamps and unknown fields aren't
+    let expected = [CASP_INSTANCE, SHOES_INSTANCE, SCULPT_INSTANCE, MODIFIER_INSTANCE, GENETIC_PART, GENETIC_MODIFIER, SKIN_TONE];
+    assert_eq!(sim.references, expected.into_iter().collect());
+
+    // A Sim on its own parses the same as one inside a household
+    let alone = SimFile::parse(&sim_message(b"Bella", CASP_INSTANCE)).unwrap();
+    assert_eq!(alone.references, sim.references);
+}
+
+#[test]
+fn test_required_cc() {
+    let dir = std::env::temp_dir().join(format!("s4pi_tray_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let casp = TGI { res_type: 0x034AEECB, res_group: 0x80000000, instance: CASP_INSTANCE };
+    let unrelated = TGI { res_type: 0x034AEECB, res_group: 0, instance: 0xDEAD_BEEF_0000_0001 };
+
+    let mut entries = HashMap::new();
+    entries.insert(casp, (vec![0u8; 16], 16, 0, 1));
+    Package::write_merged(dir.join("hair.package"), &entries, false).unwrap();
+
+    let mut entries = HashMap::new();
+    entries.insert(unrelated, (vec![0u8; 16], 16, 0, 1));
+    Package::write_merged(dir.join("shoes.package"), &entries, false).unwrap();
+
+    let index = CcIndex::build(&dir).unwrap();
+    let sim = &Household::parse(&sample_sim()).unwrap().sims[0];
+    let required = index.required_by(sim);
+    assert_eq!(required.len(), 1);
+    assert_eq!(required[0].0, casp);
+    let packages = index.required_packages(sim);
+    assert_eq!(packages.len(), 1);
+    assert!(packages[0].ends_with("hair.package"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_household_splits_sims() {
+    // Lot data before the Sims carries a name and a nested message of its own
+    let mut lot = Vec::new();
+    bytes_field(1, b"Willow Creek", &mut lot);
+    let mut lot_data = Vec::new();
+    bytes_field(1, &lot, &mut lot_data);
+
+    let mut household = Vec::new();
+    bytes_field(2, b"Pancakes", &mut household);
+    bytes_field(3, b"A quiet family", &mut household);
+    bytes_field(4, &lot_data, &mut household);
+    bytes_field(5, &sim_message(b"Bob", 0x1111_0000_0000_0001), &mut household);
+    bytes_field(5, &sim_message(b"Eliza", 0x2222_0000_0000_0002), &mut household);
+
+    let parsed = Household::parse(&household).unwrap();
+    assert_eq!(parsed.name.as_deref(), Some("Pancakes"));
+    assert_eq!(parsed.description.as_deref(), Some("A quiet family"));
+    assert_eq!(parsed.sims.len(), 2);
+    assert_eq!(parsed.sims[0].first_name.as_deref(), Some("Bob"));
+    assert!(parsed.sims[0].references.contains(&0x1111_0000_0000_0001));
+    assert!(!parsed.sims[0].references.contains(&0x2222_0000_0000_0002));
+    assert_eq!(parsed.sims[1].first_name.as_deref(), Some("Eliza"));
+    assert!(parsed.references().contains(&0x2222_0000_0000_0002));
+}
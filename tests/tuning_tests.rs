@@ -0,0 +1,60 @@
+use s4pi_reforged::tuning::{format_xml, looks_like_xml, tuning_class, tuning_instance_type, tuning_name, TUNING_TYPES, XmlFormatOptions};
+use s4pi_reforged::TypedResource;
+use s4pi_reforged::package::resource::ResourceKind;
+
+const SAMPLE: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<I s=\"12345\" n=\"buff_Example\" i=\"buff\" m=\"buffs.buff\" c=\"Buff\"><T n=\"visible\">True</T>   <L n=\"tags\"><E>Tag_A &amp; B</E></L></I>";
+
+#[test]
+fn test_canonical_tuning() {
+    let out = format_xml(SAMPLE, &XmlFormatOptions::canonical()).unwrap();
+    let expected = concat!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+        "<I c=\"Buff\" i=\"buff\" m=\"buffs.buff\" n=\"buff_Example\" s=\"12345\">\n",
+        "  <T n=\"visible\">True</T>\n",
+        "  <L n=\"tags\">\n",
+        "    <E>Tag_A &amp; B</E>\n",
+        "  </L>\n",
+        "</I>\n",
+    );
+    assert_eq!(out, expected);
+
+    // Canonical output is stable when fed back in
+    assert_eq!(format_xml(&out, &XmlFormatOptions::canonical()).unwrap(), out);
+}
+
+#[test]
+fn test_strip_whitespace_only() {
+    let options = XmlFormatOptions { pretty: false, sort_attributes: false, strip_whitespace: true };
+    let out = format_xml("<I n=\"a\">\n  <T> keep me </T>\n</I>", &options).unwrap();
+    assert_eq!(out, "<I n=\"a\"><T> keep me </T></I>");
+}
+
+#[test]
+fn test_passthrough_and_helpers() {
+    assert_eq!(format_xml(SAMPLE, &XmlFormatOptions::default()).unwrap(), SAMPLE);
+    assert!(looks_like_xml(b"\xEF\xBB\xBF  <I/>"));
+    assert!(!looks_like_xml(b"DATA"));
+    assert_eq!(tuning_name(SAMPLE).as_deref(), Some("buff_Example"));
+    assert!(format_xml("<I><T></I>", &XmlFormatOptions::canonical()).is_err());
+}
+
+#[test]
+fn test_kind_of() {
+    assert_eq!(TypedResource::kind_of(0xE882D22F), ResourceKind::Text);
+    assert_eq!(TypedResource::kind_of(0x0069453E), ResourceKind::Xml);
+    assert_eq!(TypedResource::kind_of(0x220557DA), ResourceKind::Stbl);
+    assert_eq!(TypedResource::kind_of(0xDEADBEEF), ResourceKind::Generic);
+}
+
+#[test]
+fn test_tuning_class() {
+    assert_eq!(tuning_class(0x6017E896), Some("buff"));
+    assert_eq!(tuning_class(0xCB5FDDC7), Some("trait"));
+    assert_eq!(tuning_class(0x220557DA), None);
+    assert_eq!(tuning_instance_type(SAMPLE).as_deref(), Some("buff"));
+
+    // Every registered type is treated as tuning
+    for (res_type, _) in TUNING_TYPES {
+        assert!(matches!(TypedResource::kind_of(res_type), ResourceKind::Xml | ResourceKind::Text), "{:08X}", res_type);
+    }
+}
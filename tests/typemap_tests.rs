@@ -0,0 +1,58 @@
+use s4pi_reforged::typemap::{append_records, unknown_type_records, TypeMap};
+use s4pi_reforged::{Package, TGI};
+use std::collections::HashMap;
+
+#[test]
+fn test_unknown_type_records_and_typemap() {
+    let dir = std::env::temp_dir().join(format!("s4pi_typemap_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("unknown.package");
+
+    let unknown = 0x1234ABCD;
+    let mut resources = HashMap::new();
+    for (instance, size) in [(1u64, 100usize), (2, 120), (3, 3000)] {
+        let mut data = b"FANC".to_vec();
+        data.resize(size, 0);
+        resources.insert(TGI { res_type: unknown, res_group: 0x80000000, instance }, (data.clone(), size as u32, 0, 1));
+    }
+    let tuning = b"<I n=\"known\"/>".to_vec();
+    resources.insert(TGI { res_type: 0x034AEECB, res_group: 0, instance: 4 }, (tuning.clone(), tuning.len() as u32, 0, 1));
+    Package::write_merged(&path, &resources, false).unwrap();
+
+    let pkg = Package::open(&path).unwrap();
+    let report = pkg.investigate();
+    let records = unknown_type_records(&pkg, &report);
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!((record.res_type, record.count, record.failed), (unknown, 3, false));
+    assert_eq!(record.sizes.iter().map(|(s, c)| (*s, *c)).collect::<Vec<_>>(), vec![(128, 2), (4096, 1)]);
+    assert_eq!(record.magic, b"FANC");
+
+    let log = dir.join("unknown-types.jsonl");
+    append_records(&log, &records).unwrap();
+    append_records(&log, &records).unwrap();
+    let text = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(text.lines().count(), 2);
+    let line: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+    assert_eq!(line["type"], "0x1234ABCD");
+    assert_eq!(line["magic"], "46414E43");
+    assert_eq!(line["sizes"]["128"], 2);
+    // Nothing that identifies the package
+    assert!(!text.contains("unknown.package") && !text.contains("80000000"));
+
+    let registry = dir.join("config").join("type-names.json");
+    let mut names = TypeMap::load(&registry).unwrap();
+    assert!(names.names.is_empty());
+    let imported = TypeMap::from_json(br#"{"0x1234abcd": "Fancy Format", "DEADBEEF": "Other"}"#).unwrap();
+    let result = names.import(&imported);
+    assert_eq!((result.added, result.renamed), (2, 0));
+    let result = names.import(&TypeMap::from_json(br#"{"0x1234ABCD": "Fancier Format"}"#).unwrap());
+    assert_eq!((result.added, result.renamed), (0, 1));
+    names.save(&registry).unwrap();
+    let reloaded = TypeMap::load(&registry).unwrap();
+    assert_eq!(reloaded.name_of(unknown), Some("Fancier Format"));
+    assert_eq!(reloaded.name_of(0xDEADBEEF), Some("Other"));
+    assert!(TypeMap::from_json(br#"{"not hex": "x"}"#).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}